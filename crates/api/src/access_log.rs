@@ -0,0 +1,259 @@
+use std::{
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use log::{log, Level};
+use requests::AppState;
+use types::{Timestamp, UNMATCHED_ROUTE};
+
+/// Correlation id of an API request, kept from the client or generated, echoed in the
+/// response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Longest correlation id kept from a client, longer ones are replaced
+pub const MAX_REQUEST_ID_LEN: usize = 128;
+/// Routes polled by the probes and scrapers, logged at debug level only
+const QUIET_ROUTES: &[&str] = &["/healthcheck", "/metrics"];
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Template of the route that served a response
+#[derive(Debug, Clone)]
+struct RouteTemplate(String);
+
+/// Tags the response with the template of its route, `access_log` labels the request
+/// with it. Runs as a route layer, after the routing
+pub async fn tag_route(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let mut response = next.run(request).await;
+    if let Some(route) = route {
+        response.extensions_mut().insert(RouteTemplate(route));
+    }
+    response
+}
+
+/// Counts every API request in the API metrics and logs it on the `access` target:
+/// method, route template, status, latency, client ip and correlation id. The bodies are
+/// never read
+pub async fn access_log(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let started = Instant::now();
+    let request_id = client_request_id(request.headers()).unwrap_or_else(new_request_id);
+    let request_id_value = HeaderValue::from_str(&request_id).ok();
+    if let Some(value) = &request_id_value {
+        request
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER, value.clone());
+    }
+    let method = request.method().clone();
+    let client_ip = client_ip(&request, state.client_ip_header.as_deref());
+
+    let mut response = next.run(request).await;
+    let elapsed = started.elapsed();
+    let status = response.status().as_u16();
+    let route = response
+        .extensions()
+        .get::<RouteTemplate>()
+        .map_or(UNMATCHED_ROUTE, |route| route.0.as_str());
+    state
+        .api_metrics
+        .record(method.as_str(), route, status, elapsed);
+
+    let level = if QUIET_ROUTES.contains(&route) {
+        Level::Debug
+    } else {
+        Level::Info
+    };
+    log!(
+        target: "access",
+        level,
+        "method={method} route={route} status={status} latency_ms={:.1} client_ip={client_ip} request_id={request_id}",
+        elapsed.as_secs_f64() * 1000.0
+    );
+
+    if let Some(value) = request_id_value {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Correlation id sent by the client, None when missing or not made of up to
+/// `MAX_REQUEST_ID_LEN` letters, digits, `-`, `_`, `.` and `:`
+fn client_request_id(headers: &HeaderMap) -> Option<String> {
+    let id = headers.get(REQUEST_ID_HEADER)?.to_str().ok()?;
+    let valid = !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+    valid.then(|| id.to_string())
+}
+
+fn new_request_id() -> String {
+    format!(
+        "{:x}-{:x}",
+        Timestamp::now().as_millis(),
+        NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// First address of the trusted proxy header when configured and sent, else the peer
+/// address. `-` when neither is known
+fn client_ip(request: &Request, trusted_header: Option<&str>) -> String {
+    let forwarded = trusted_header
+        .and_then(|header| request.headers().get(header))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty());
+    if let Some(ip) = forwarded {
+        return ip.to_string();
+    }
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or_else(|| "-".to_string(), |peer| peer.0.ip().to_string())
+}
+
+#[cfg(test)]
+mod access_log_test {
+    use std::net::SocketAddr;
+
+    use axum::{
+        extract::{ConnectInfo, Request},
+        http::StatusCode,
+    };
+    use requests::test_utils::test_state;
+    use test_support::{input_request, test_db};
+    use tokio::net::TcpListener;
+    use types::{BRequest, Chains, Timestamp, UNMATCHED_ROUTE};
+
+    use super::client_ip;
+    use crate::{api_router, REQUEST_ID_HEADER};
+
+    #[tokio::test]
+    async fn test_routes_labeled_by_template_and_request_id_echoed() {
+        let (state, _rx_evm, _rx_sol) = test_state(test_db());
+        let requests: Vec<BRequest> = ["1", "2"]
+            .into_iter()
+            .map(|token_id| {
+                let request =
+                    BRequest::new(input_request(Chains::SOLANA, token_id), Timestamp::now());
+                request.save(&state.db).unwrap();
+                request
+            })
+            .collect();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = api_router(state.clone()).into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let http = reqwest::Client::new();
+
+        let response = http
+            .get(format!("{url}/bridge/requests/{}", requests[0].id))
+            .header(REQUEST_ID_HEADER, "checkout-42:retry.1")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), StatusCode::OK.as_u16());
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "checkout-42:retry.1");
+
+        // Invalid ids are replaced, a request without one gets one
+        for sent in [Some("bad id"), None] {
+            let mut get = http.get(format!("{url}/bridge/requests/{}", requests[1].id));
+            if let Some(sent) = sent {
+                get = get.header(REQUEST_ID_HEADER, sent);
+            }
+            let response = get.send().await.unwrap();
+            let echoed = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+            assert!(!echoed.is_empty() && echoed != "bad id", "{echoed}");
+        }
+
+        let unauthorized = http
+            .get(format!("{url}/admin/rpc-health"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            unauthorized.status().as_u16(),
+            StatusCode::UNAUTHORIZED.as_u16()
+        );
+        let unknown = http
+            .get(format!("{url}/unknown/path"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unknown.status().as_u16(), StatusCode::NOT_FOUND.as_u16());
+        assert!(unknown.headers().contains_key(REQUEST_ID_HEADER));
+
+        let routes = state.api_metrics.routes();
+        let labels: Vec<(&str, &str)> = routes
+            .iter()
+            .map(|route| (route.method.as_str(), route.route.as_str()))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ("GET", "/admin/rpc-health"),
+                ("GET", "/bridge/requests/{id}"),
+                ("GET", UNMATCHED_ROUTE),
+            ]
+        );
+        assert_eq!(routes[0].statuses["4xx"], 1);
+        assert_eq!(routes[1].requests(), 3);
+        assert_eq!(routes[1].statuses["2xx"], 3);
+
+        let metrics = http
+            .get(format!("{url}/metrics"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(metrics.contains(
+            "relayer_api_requests_total{method=\"GET\",route=\"/bridge/requests/{id}\",status_class=\"2xx\"} 3"
+        ));
+        assert!(metrics.contains(
+            "relayer_api_request_seconds_count{method=\"GET\",route=\"/bridge/requests/{id}\"} 3"
+        ));
+        assert!(!metrics.contains(requests[0].id.as_str()));
+    }
+
+    #[test]
+    fn test_client_ip_from_the_trusted_header_only() {
+        let request = |forwarded: &str| {
+            let mut request = Request::builder()
+                .header("x-forwarded-for", forwarded)
+                .body(axum::body::Body::empty())
+                .unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 443))));
+            request
+        };
+        assert_eq!(
+            client_ip(&request("203.0.113.7, 10.0.0.2"), Some("x-forwarded-for")),
+            "203.0.113.7"
+        );
+        assert_eq!(client_ip(&request("203.0.113.7"), None), "10.0.0.1");
+        assert_eq!(
+            client_ip(&request(" "), Some("x-forwarded-for")),
+            "10.0.0.1"
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use requests::{errors::RequestError, is_admin, AppState};
+use serde_json::json;
+
+/// Token of the `Authorization: Bearer <token>` header, None without one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BearerToken(pub Option<String>);
+
+impl<S: Send + Sync> FromRequestParts<S> for BearerToken {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(BearerToken(
+            bearer_token(&parts.headers).map(str::to_string),
+        ))
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Answers 401 to the requests of the admin routes without the admin token, before their
+/// handler runs. Runs as a route layer of the `/admin` routes
+pub async fn require_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !is_admin(bearer_token(request.headers()), state.admin_token.as_ref()) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": RequestError::Unauthorized().to_string() })),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod admin_auth_test {
+    use axum::http::StatusCode;
+    use requests::test_utils::test_state;
+    use serde_json::{json, Value};
+    use test_support::test_db;
+    use tokio::net::TcpListener;
+
+    use crate::api_router;
+
+    #[tokio::test]
+    async fn test_admin_routes_require_the_admin_token() {
+        let (mut state, _rx_evm, _rx_sol) = test_state(test_db());
+        state.admin_token = Some("secret".into());
+        let pause = state.pause.clone();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, api_router(state)).await });
+        let http = reqwest::Client::new();
+        let update =
+            json!({ "direction": "all", "paused": true, "reason": "incident", "resume_at": null });
+
+        for token in [None, Some("wrong"), Some("")] {
+            let mut get = http.get(format!("{url}/admin/pause"));
+            let mut put = http.put(format!("{url}/admin/pause")).json(&update);
+            if let Some(token) = token {
+                get = get.bearer_auth(token);
+                put = put.bearer_auth(token);
+            }
+            for request in [get, put] {
+                let response = request.send().await.unwrap();
+                assert_eq!(
+                    response.status().as_u16(),
+                    StatusCode::UNAUTHORIZED.as_u16(),
+                    "{token:?}"
+                );
+                let body: Value = response.json().await.unwrap();
+                assert_eq!(body["error"], "Not authorized");
+            }
+        }
+        assert_eq!(pause.state().all, None);
+
+        let paused = http
+            .put(format!("{url}/admin/pause"))
+            .bearer_auth("secret")
+            .json(&update)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(paused.status().as_u16(), StatusCode::OK.as_u16());
+        let read = http
+            .get(format!("{url}/admin/pause"))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(read.status().as_u16(), StatusCode::OK.as_u16());
+        assert!(pause.state().all.is_some());
+
+        // The public routes don't need it
+        let health = http.get(format!("{url}/healthcheck")).send().await.unwrap();
+        assert_eq!(health.status().as_u16(), StatusCode::OK.as_u16());
+    }
+}
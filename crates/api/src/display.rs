@@ -0,0 +1,155 @@
+use std::str::FromStr;
+
+use alloy::primitives::{Address, U256};
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use types::{BRequest, Chains};
+
+/// Address of `chain` as shown to API clients, EIP-55 checksummed for EVM and canonical
+/// base58 for Solana. Values that don't parse are returned as stored
+pub fn display_address(chain: &Chains, value: &str) -> String {
+    match chain {
+        Chains::EVM => Address::from_str(value.trim())
+            .map(|address| address.to_checksum(None))
+            .unwrap_or_else(|_| value.to_string()),
+        Chains::SOLANA => Pubkey::from_str(value.trim())
+            .map(|key| key.to_string())
+            .unwrap_or_else(|_| value.to_string()),
+    }
+}
+
+/// EVM token id in decimal, hex ids are converted
+pub fn display_token_id(value: &str) -> String {
+    if value.trim().is_empty() {
+        return value.to_string();
+    }
+    U256::from_str(value.trim())
+        .map(|id| id.to_string())
+        .unwrap_or_else(|_| value.to_string())
+}
+
+/// Request with its addresses and token ids formatted for display, the stored request
+/// keeps the normalized forms
+pub fn display_request(mut request: BRequest) -> BRequest {
+    let origin = request.input.origin_network.clone();
+    let destination = match origin {
+        Chains::EVM => Chains::SOLANA,
+        Chains::SOLANA => Chains::EVM,
+    };
+
+    let input = &mut request.input;
+    input.contract_or_mint = display_address(&origin, &input.contract_or_mint);
+    input.token_owner = display_address(&origin, &input.token_owner);
+    input.destination_account = display_address(&destination, &input.destination_account);
+    if origin == Chains::EVM {
+        input.token_id = display_token_id(&input.token_id);
+    }
+
+    let output = &mut request.output;
+    output.detination_contract_id_or_mint =
+        display_address(&destination, &output.detination_contract_id_or_mint);
+    // Token id of an EVM mint, token account of a Solana one
+    output.detination_token_id_or_account = match destination {
+        Chains::EVM => display_token_id(&output.detination_token_id_or_account),
+        Chains::SOLANA => display_address(&destination, &output.detination_token_id_or_account),
+    };
+
+    if let Some(context) = request.last_error_context.as_mut() {
+        context.contract = context
+            .contract
+            .as_deref()
+            .map(|contract| display_address(&context.chain, contract));
+    }
+    request
+}
+
+/// Request as returned by the API, see `display_request`
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct RequestResponse(BRequest);
+
+impl From<BRequest> for RequestResponse {
+    fn from(request: BRequest) -> Self {
+        RequestResponse(display_request(request))
+    }
+}
+
+#[cfg(test)]
+mod display_test {
+    use serde_json::Value;
+    use test_support::{request_in_status, solana_key, EVM_ACCOUNT, EVM_TOKEN_CONTRACT};
+    use types::{BRequest, CallContext, Chains, Status, Timestamp};
+
+    use crate::{display_address, display_token_id, RequestResponse};
+
+    /// Completed request with the fields API clients see pinned to fixed values
+    fn fixture(origin: Chains) -> BRequest {
+        let mut request = request_in_status(origin.clone(), "0x2a", Status::Completed);
+        request.id = format!("0x{}", "11".repeat(32)).parse().unwrap();
+        request.created_by_version = "0.1.0".to_string();
+        request.created_at = Timestamp::from_millis(1_700_000_000_000);
+        request.last_update = Timestamp::from_millis(1_700_000_060_000);
+        request.finalized_at = Some(request.last_update);
+        match origin {
+            // Stored normalized to lowercase
+            Chains::EVM => request.input.token_owner = EVM_ACCOUNT.to_lowercase(),
+            Chains::SOLANA => {
+                request.input.token_id = String::new();
+                request.input.destination_account = EVM_ACCOUNT.to_lowercase();
+                request.output.detination_contract_id_or_mint = EVM_TOKEN_CONTRACT.to_string();
+                request.output.detination_token_id_or_account = "0x2a".to_string();
+                request.last_error = Some("timeout".to_string());
+                request.last_error_context = Some(
+                    CallContext::new(Chains::EVM, "mintToken")
+                        .request(&request.id)
+                        .contract("0x5fbdb2315678afecb367f032d93f642f64180aa3"),
+                );
+            }
+        }
+        request
+    }
+
+    fn assert_golden(request: BRequest, golden: &str) {
+        let response = serde_json::to_value(RequestResponse::from(request)).unwrap();
+        let expected: Value = serde_json::from_str(golden).unwrap();
+        assert_eq!(
+            response,
+            expected,
+            "response differs from the golden file:\n{}",
+            serde_json::to_string_pretty(&response).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_request_responses_match_golden_files() {
+        assert_golden(
+            fixture(Chains::EVM),
+            include_str!("../tests/golden/evm_request.json"),
+        );
+        assert_golden(
+            fixture(Chains::SOLANA),
+            include_str!("../tests/golden/solana_request.json"),
+        );
+    }
+
+    #[test]
+    fn test_display_helpers() {
+        assert_eq!(
+            display_address(&Chains::EVM, " 0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266"),
+            EVM_ACCOUNT
+        );
+        assert_eq!(
+            display_address(&Chains::SOLANA, &solana_key(3).to_string()),
+            solana_key(3).to_string()
+        );
+        // Unparseable values are shown as stored
+        assert_eq!(
+            display_address(&Chains::EVM, "not an address"),
+            "not an address"
+        );
+        assert_eq!(display_address(&Chains::SOLANA, ""), "");
+        assert_eq!(display_token_id("0x2a"), "42");
+        assert_eq!(display_token_id("42"), "42");
+        assert_eq!(display_token_id(""), "");
+    }
+}
@@ -3,3 +3,12 @@ pub mod service;
 
 pub mod routes;
 pub use routes::*;
+
+pub mod display;
+pub use display::*;
+
+pub mod access_log;
+pub use access_log::*;
+
+pub mod admin_auth;
+pub use admin_auth::*;
@@ -1,36 +1,156 @@
 use axum::{
-    http::StatusCode,
-    routing::{get, post},
+    extract::{MatchedPath, Request},
+    http::{Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
     Json, Router,
 };
-use requests::AppState;
+use requests::{AppState, Role};
 use serde_json::json;
 use tower_http::cors::{Any, CorsLayer};
 
 use crate::{
-    block_explorers, completed_requests, new_brige_from_evm, new_brige_from_solana,
-    pending_requests, request_data,
+    access_log, backfill_requests, batch_request_status, begin_maintenance_window, block_explorers,
+    cancel_load_test, capabilities, claim_orphan_request, collections, completed_requests,
+    end_maintenance_window, evm_token_preflight, export_completed, get_pause, healthcheck,
+    id_migration_report, load_test_report, metrics, new_brige_from_evm, new_brige_from_solana,
+    orphan_requests, pending_requests, pending_summary, purge_account, quota_report,
+    recheck_destination_account, redirect_request_mint, refresh_metadata, refund_custodied_token,
+    relayer_status, remove_collection, reprocess_pending_request, request_data,
+    request_diagnostics, request_failure_report, request_queue_position,
+    requests_by_client_reference, requests_by_tx, require_admin, reset_quota, rpc_health,
+    start_load_test, start_tx_audit, tag_route, tx_audit_report, update_collection,
+    update_endpoints, update_pause, version, wrapped_evm_token, wrapped_solana_token,
 };
 
+/// Routes reading the chains, not served by read-only relayers whatever their method
+pub const CHAIN_READ_ROUTES: &[&str] = &["/bridge/preflight/evm/{contract}/{token_id}"];
+
+/// Routes posting their input only to read the database, served by read-only relayers
+pub const POSTED_READ_ROUTES: &[&str] = &["/bridge/requests/batch-status"];
+
+/// Answers 501 to the routes a read-only relayer can't serve: every method but GET and
+/// HEAD out of the posted reads, and the reads of the chains
+async fn read_only_guard(request: Request, next: Next) -> Response {
+    let path = request.extensions().get::<MatchedPath>();
+    let reads_chain = path.is_some_and(|path| CHAIN_READ_ROUTES.contains(&path.as_str()));
+    let posted_read = *request.method() == Method::POST
+        && path.is_some_and(|path| POSTED_READ_ROUTES.contains(&path.as_str()));
+    if reads_chain || !(posted_read || matches!(*request.method(), Method::GET | Method::HEAD)) {
+        return (
+            StatusCode::NOT_IMPLEMENTED,
+            Json(json!({ "error": "Not served by a read-only relayer" })),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// Routes of the API over `state`. Read-only relayers answer 501 to the routes writing
+/// or reading the chains, see `read_only_guard`. The admin routes answer 401 without the
+/// admin token. Every request is counted and logged by `access_log`
 pub fn api_router(state: AppState) -> Router {
+    let read_only = state.role == Role::ReadOnly;
+    let access_state = state.clone();
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any);
 
-    let app = Router::new()
+    // Every admin route needs the admin token, checked once by `require_admin`
+    let admin = Router::new()
+        .route("/admin/pause", get(get_pause).put(update_pause))
+        .route("/admin/maintenance/begin", post(begin_maintenance_window))
+        .route("/admin/maintenance/end", post(end_maintenance_window))
+        .route("/admin/endpoints", put(update_endpoints))
+        .route("/admin/status", get(relayer_status))
+        .route("/admin/rpc-health", get(rpc_health))
+        .route("/admin/id-migration", get(id_migration_report))
+        .route("/admin/backfill", post(backfill_requests))
+        .route("/admin/purge", post(purge_account))
+        .route("/admin/tx-audit", get(tx_audit_report).post(start_tx_audit))
+        .route(
+            "/admin/requests/{id}/reprocess",
+            post(reprocess_pending_request),
+        )
+        .route("/admin/requests/{id}/refund", post(refund_custodied_token))
         .route(
-            "/healthcheck",
-            get(|| async { (StatusCode::OK, Json(json!({"running": true}))) }),
+            "/admin/requests/{id}/refresh-metadata",
+            post(refresh_metadata),
         )
+        .route("/admin/loadtest", post(start_load_test))
+        .route(
+            "/admin/loadtest/{run_id}",
+            get(load_test_report).delete(cancel_load_test),
+        )
+        .route(
+            "/admin/quotas/{account}",
+            get(quota_report).delete(reset_quota),
+        )
+        .route("/admin/collections", get(collections))
+        .route(
+            "/admin/collections/{contract}",
+            put(update_collection).delete(remove_collection),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_admin));
+
+    let app = Router::new()
+        .route("/healthcheck", get(healthcheck))
+        .route("/version", get(version))
+        .route("/metrics", get(metrics))
+        .merge(admin)
         .route("/bridge/evm-to-solana", post(new_brige_from_evm))
         .route("/bridge/solana-to-evm", post(new_brige_from_solana))
         .route("/bridge/pending-requests", get(pending_requests))
+        .route("/bridge/pending-requests/summary", get(pending_summary))
         .route("/bridge/completed-requests", get(completed_requests))
+        .route("/bridge/export/completed", get(export_completed))
+        .route("/bridge/requests", get(requests_by_client_reference))
+        .route("/bridge/requests/batch-status", post(batch_request_status))
         .route("/bridge/requests/{id}", get(request_data))
+        .route(
+            "/bridge/requests/{id}/diagnostics",
+            get(request_diagnostics),
+        )
+        .route(
+            "/bridge/requests/{id}/queue-position",
+            get(request_queue_position),
+        )
+        .route(
+            "/bridge/requests/{id}/failure-report",
+            get(request_failure_report),
+        )
+        .route(
+            "/bridge/requests/{id}/redirect-mint",
+            post(redirect_request_mint),
+        )
+        .route(
+            "/bridge/requests/{id}/recheck",
+            post(recheck_destination_account),
+        )
+        .route(
+            "/bridge/preflight/evm/{contract}/{token_id}",
+            get(evm_token_preflight),
+        )
+        .route("/bridge/wrapped/solana/{mint}", get(wrapped_solana_token))
+        .route(
+            "/bridge/wrapped/evm/{contract}/{token_id}",
+            get(wrapped_evm_token),
+        )
+        .route("/bridge/tx/{hash}", get(requests_by_tx))
+        .route("/bridge/orphans", get(orphan_requests))
+        .route("/bridge/orphans/{id}/claim", post(claim_orphan_request))
         .route("/bridge/block_explorers", get(block_explorers))
-        .with_state(state)
-        .layer(cors);
+        .route("/bridge/capabilities", get(capabilities))
+        .with_state(state);
 
-    app
+    let app = if read_only {
+        app.route_layer(middleware::from_fn(read_only_guard))
+    } else {
+        app
+    };
+    app.route_layer(middleware::from_fn(tag_route))
+        .layer(cors)
+        .layer(middleware::from_fn_with_state(access_state, access_log))
 }
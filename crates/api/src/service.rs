@@ -1,37 +1,378 @@
+use std::fmt::Write;
+
 use axum::{
-    extract::{Path, State},
-    http::Uri,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, Uri},
+    response::{IntoResponse, Response},
     Json,
 };
 use log::error;
 use requests::{
-    endpoints::{get_pending_requests, get_request, new_request},
-    get_completed_requests, AppState,
+    begin_maintenance, cancel_loadtest, claim_orphan, clear_quota, csv_row, delete_collection,
+    end_maintenance,
+    endpoints::{
+        get_failure_report, get_pending_requests, get_pending_summary, get_queue_position,
+        get_request, new_request,
+    },
+    errors::RequestError,
+    evm_preflight, export_page, get_batch_status, get_capabilities, get_collections,
+    get_completed_requests, get_id_migration_report, get_loadtest, get_orphans, get_quota,
+    get_requests_by_client_reference, get_requests_by_tx, get_rpc_health, get_status,
+    get_tx_audit_report, get_wrapped_token, json_row, mint_tx_state, new_request_with_key,
+    purge_account_data, recheck_request, redirect_mint, refresh_request_metadata, refund_request,
+    reprocess_request, run_backfill, run_tx_audit, set_collection, start_loadtest,
+    switch_endpoints, AppState, BackfillParams, BatchStatusInput, ClaimOrphanInput, DrainReport,
+    EndpointsReport, EndpointsUpdate, ExportFilter, ExportFormat, IdempotentRequest,
+    LoadTestParams, LoadTestReport, MaintenanceInput, PreflightQuery, PreflightReport, PurgeInput,
+    QuotaReport, RedirectMintInput, RelayerStatus, ReprocessResult, RequestsQuery, Role,
+    RpcHealthQuery, RpcHealthReport, SetCollectionInput, WrappedTokenInfo,
+    BATCH_STATUS_MAX_AGE_SECS, CAPABILITIES_MAX_AGE_SECS, EXPORT_CSV_HEADER, EXPORT_PAGE_SIZE,
 };
 use serde_json::{json, Value};
-use types::{BRequest, Chains, EVMInputRequest, InputRequest, SolanaInputRequest};
+use storage::{
+    db::SCHEMA_VERSION,
+    metrics::{cf_label, Histogram, StorageMetrics},
+};
+use types::{
+    block_explorer_link, private_text, tx_explorer_link, ApiMetrics, BRequest, BackfillReport,
+    BridgedToken, ChainHead, Chains, ClientLabels, CollectionEntry, EVMInputRequest, ExplorerBase,
+    FailureReport, FastPath, IdMigrationReport, InputRequest, PauseState, PauseUpdate,
+    PurgeReceipt, RequestId, SolanaInputRequest, Status, TxAuditReport, HEAD_STALE_THRESHOLD,
+    RELAYER_GIT_COMMIT, RELAYER_VERSION,
+};
+
+use crate::{display_request, BearerToken, RequestResponse};
+
+/// Header of the client chosen key of a request creation
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Set on the responses replayed for an idempotency key
+const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+pub async fn healthcheck(State(state): State<AppState>) -> Json<Value> {
+    let evm_head = *state.evm_head.borrow();
+    let solana_head = *state.solana_head.borrow();
+
+    Json(json!({
+        "running": true,
+        "version": RELAYER_VERSION,
+        "commit": RELAYER_GIT_COMMIT,
+        "EVM": chain_head_json(&evm_head),
+        "SOLANA": chain_head_json(&solana_head),
+        "duplicated_mints_dropped": state.in_flight.dropped_count(),
+        "authorized_backend": {
+            "EVM": state.evm_client.is_authorized_backend(),
+            "SOLANA": state.solana_client.is_authorized_backend(),
+        },
+    }))
+}
+
+pub async fn version(State(state): State<AppState>) -> Json<Value> {
+    let stored_schema = state.db.schema_version().unwrap_or(None);
+
+    Json(json!({
+        "version": RELAYER_VERSION,
+        "commit": RELAYER_GIT_COMMIT,
+        "schema_version": SCHEMA_VERSION,
+        "stored_schema_version": stored_schema,
+    }))
+}
+
+/// Prometheus text exposition of the relayer metrics
+pub async fn metrics(State(state): State<AppState>) -> Response {
+    let mut body = String::new();
+
+    body.push_str("# TYPE relayer_channel_depth gauge\n");
+    for chain in [Chains::EVM, Chains::SOLANA] {
+        let _ = writeln!(
+            body,
+            "relayer_channel_depth{{chain=\"{:?}\"}} {}",
+            chain,
+            state.channel_metrics.depth(&chain)
+        );
+    }
+
+    let stats = state.channel_metrics.latency_stats();
+    for (name, stage) in [
+        ("relayer_channel_wait_seconds", "wait"),
+        ("relayer_message_processing_seconds", "processing"),
+    ] {
+        let _ = writeln!(body, "# TYPE {name} summary");
+        for stats in stats.iter() {
+            let labels = format!("chain=\"{}\",function=\"{}\"", stats.chain, stats.function);
+            let quantiles = match stage {
+                "wait" => [("0.5", stats.wait_p50), ("0.95", stats.wait_p95)],
+                _ => [
+                    ("0.5", stats.processing_p50),
+                    ("0.95", stats.processing_p95),
+                ],
+            };
+            for (quantile, latency) in quantiles {
+                if let Some(latency) = latency {
+                    let _ = writeln!(
+                        body,
+                        "{name}{{{labels},quantile=\"{quantile}\"}} {}",
+                        latency.as_secs_f64()
+                    );
+                }
+            }
+        }
+    }
+    body.push_str("# TYPE relayer_mismatched_events_total counter\n");
+    for chain in [Chains::EVM, Chains::SOLANA] {
+        let _ = writeln!(
+            body,
+            "relayer_mismatched_events_total{{chain=\"{:?}\"}} {}",
+            chain,
+            state.event_validator.mismatched_count(&chain)
+        );
+    }
+    body.push_str("# TYPE relayer_gas_limit_exceeded_total counter\n");
+    let _ = writeln!(
+        body,
+        "relayer_gas_limit_exceeded_total {}",
+        state.evm_client.gas_policy.exceeded_count()
+    );
+    body.push_str("# TYPE relayer_missing_signer_total counter\n");
+    let _ = writeln!(
+        body,
+        "relayer_missing_signer_total {}",
+        state.solana_client.missing_signer_count()
+    );
+    body.push_str("# TYPE relayer_fast_path_resends_total counter\n");
+    for (path, count) in [
+        (
+            FastPath::FreshBlockhash,
+            state.solana_client.blockhash_resend_count(),
+        ),
+        (FastPath::NonceResync, state.evm_client.nonce_resync_count()),
+    ] {
+        let _ = writeln!(
+            body,
+            "relayer_fast_path_resends_total{{path=\"{}\"}} {count}",
+            path.name()
+        );
+    }
+    body.push_str("# TYPE relayer_backfill_logs_total counter\n");
+    for (outcome, count) in [
+        ("processed", state.backfill_progress.processed()),
+        ("deferred", state.backfill_progress.deferred()),
+    ] {
+        let _ = writeln!(
+            body,
+            "relayer_backfill_logs_total{{outcome=\"{outcome}\"}} {count}"
+        );
+    }
+    body.push_str("# TYPE relayer_processor_restarts_total counter\n");
+    for chain in [Chains::EVM, Chains::SOLANA] {
+        let _ = writeln!(
+            body,
+            "relayer_processor_restarts_total{{chain=\"{:?}\"}} {}",
+            chain,
+            state.processor_health.of(&chain).restarts()
+        );
+    }
+    body.push_str("# TYPE relayer_processor_heartbeat_age_seconds gauge\n");
+    for chain in [Chains::EVM, Chains::SOLANA] {
+        if let Some(last_beat) = state.processor_health.of(&chain).last_beat() {
+            let _ = writeln!(
+                body,
+                "relayer_processor_heartbeat_age_seconds{{chain=\"{:?}\"}} {}",
+                chain,
+                last_beat.elapsed_until(state.clock.now()).as_secs_f64()
+            );
+        }
+    }
+    body.push_str("# TYPE relayer_tx_audit_verified_total counter\n");
+    let _ = writeln!(
+        body,
+        "relayer_tx_audit_verified_total {}",
+        state.tx_audit.verified_count()
+    );
+    body.push_str("# TYPE relayer_tx_audit_discrepancies_total counter\n");
+    for (kind, count) in state.tx_audit.discrepancy_counts() {
+        let _ = writeln!(
+            body,
+            "relayer_tx_audit_discrepancies_total{{kind=\"{kind}\"}} {count}"
+        );
+    }
+    body.push_str("# TYPE relayer_sweep_claims_active gauge\n");
+    match state.work_claims.active_count(&state.db) {
+        Ok(active) => _ = writeln!(body, "relayer_sweep_claims_active {active}"),
+        Err(e) => error!("Could not count the sweep claims: {e}"),
+    }
+    body.push_str("# TYPE relayer_sweep_claims_skipped_total counter\n");
+    let _ = writeln!(
+        body,
+        "relayer_sweep_claims_skipped_total {}",
+        state.work_claims.skipped_count()
+    );
+    body.push_str("# TYPE relayer_requests_needing_intervention gauge\n");
+    match types::requests_by_status(&state.db, &Status::NeedsIntervention, None) {
+        Ok(held) => _ = writeln!(body, "relayer_requests_needing_intervention {}", held.len()),
+        Err(e) => error!("Could not count the requests needing intervention: {e}"),
+    }
+    body.push_str("# TYPE relayer_custody_tokens gauge\n");
+    match types::custody_exposure(&state.db) {
+        Ok(exposure) => {
+            for chain in [Chains::EVM, Chains::SOLANA] {
+                let _ = writeln!(
+                    body,
+                    "relayer_custody_tokens{{origin=\"{:?}\"}} {}",
+                    chain,
+                    exposure.of(&chain)
+                );
+            }
+        }
+        Err(e) => error!("Could not count the tokens in custody: {e}"),
+    }
+    body.push_str("# TYPE relayer_custody_limit gauge\n");
+    let limits = state.custody_limits;
+    for (scope, limit) in [
+        ("total", limits.max_total),
+        ("evm_to_solana", limits.max_evm_to_solana),
+        ("solana_to_evm", limits.max_solana_to_evm),
+    ] {
+        if let Some(limit) = limit.filter(|limit| *limit > 0) {
+            let _ = writeln!(body, "relayer_custody_limit{{scope=\"{scope}\"}} {limit}");
+        }
+    }
+    body.push_str("# TYPE relayer_negative_cache_hits_total counter\n");
+    let _ = writeln!(
+        body,
+        "relayer_negative_cache_hits_total {}",
+        state.missing_requests.hit_count()
+    );
+    let _ = writeln!(body, "# TYPE relayer_channel_messages gauge");
+    for stats in stats.iter() {
+        let _ = writeln!(
+            body,
+            "relayer_channel_messages{{chain=\"{}\",function=\"{}\"}} {}",
+            stats.chain, stats.function, stats.messages
+        );
+    }
+    write_api_metrics(&mut body, &state.api_metrics);
+    if let Some(storage_metrics) = &state.storage_metrics {
+        write_storage_metrics(&mut body, storage_metrics);
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response()
+}
+
+/// Requests served by route template and status class, and their latency
+fn write_api_metrics(body: &mut String, api_metrics: &ApiMetrics) {
+    let routes = api_metrics.routes();
+    body.push_str("# TYPE relayer_api_requests_total counter\n");
+    for route in routes.iter() {
+        for (class, count) in route.statuses.iter() {
+            let _ = writeln!(
+                body,
+                "relayer_api_requests_total{{method=\"{}\",route=\"{}\",status_class=\"{class}\"}} {count}",
+                route.method, route.route
+            );
+        }
+    }
+    body.push_str("# TYPE relayer_api_request_seconds histogram\n");
+    for route in routes.iter() {
+        let labels = format!("method=\"{}\",route=\"{}\"", route.method, route.route);
+        write_histogram(body, "relayer_api_request_seconds", &labels, &route.latency);
+    }
+}
+
+/// Database operation histograms and the last sample of its size
+fn write_storage_metrics(body: &mut String, storage_metrics: &StorageMetrics) {
+    let histograms = storage_metrics.histograms();
+    for (name, measure) in [
+        ("relayer_db_operation_seconds", "latency"),
+        ("relayer_db_operation_bytes", "bytes"),
+    ] {
+        let _ = writeln!(body, "# TYPE {name} histogram");
+        for histograms in histograms.iter() {
+            let labels = format!(
+                "operation=\"{}\",cf=\"{}\"",
+                histograms.operation.name(),
+                cf_label(histograms.cf)
+            );
+            let histogram = match measure {
+                "latency" => &histograms.latency,
+                _ => &histograms.bytes,
+            };
+            write_histogram(body, name, &labels, histogram);
+        }
+    }
+    let sample = storage_metrics.last_sample();
+    body.push_str("# TYPE relayer_db_records gauge\n");
+    for (cf, records) in sample.records {
+        let _ = writeln!(body, "relayer_db_records{{cf=\"{}\"}} {records}", cf.name());
+    }
+    if let Some(disk_bytes) = sample.disk_bytes {
+        body.push_str("# TYPE relayer_db_disk_bytes gauge\n");
+        let _ = writeln!(body, "relayer_db_disk_bytes {disk_bytes}");
+    }
+}
+
+fn write_histogram(body: &mut String, name: &str, labels: &str, histogram: &Histogram) {
+    for (bound, count) in histogram.cumulative_buckets() {
+        let _ = writeln!(body, "{name}_bucket{{{labels},le=\"{bound}\"}} {count}");
+    }
+    let _ = writeln!(
+        body,
+        "{name}_bucket{{{labels},le=\"+Inf\"}} {}",
+        histogram.count
+    );
+    let _ = writeln!(body, "{name}_sum{{{labels}}} {}", histogram.sum);
+    let _ = writeln!(body, "{name}_count{{{labels}}} {}", histogram.count);
+}
+
+fn chain_head_json(head: &ChainHead) -> Value {
+    json!({
+        "head": head.height,
+        "age_secs": head.age().as_secs(),
+        "stale": head.is_stale(HEAD_STALE_THRESHOLD),
+    })
+}
 
 pub async fn new_brige_from_solana(
     uri: Uri,
+    headers: HeaderMap,
     State(state): State<AppState>,
     Json(input): Json<SolanaInputRequest>,
-) -> Result<Json<BRequest>, (axum::http::StatusCode, Json<Value>)> {
-    new_brige_request(uri, state, input.into()).await
+) -> Result<Response, (axum::http::StatusCode, Json<Value>)> {
+    let callback_url = input.callback_url.clone();
+    let labels = input.labels();
+    new_brige_request(uri, &headers, state, input.into(), callback_url, labels).await
 }
 
 pub async fn new_brige_from_evm(
     uri: Uri,
+    headers: HeaderMap,
     State(state): State<AppState>,
     Json(input): Json<EVMInputRequest>,
-) -> Result<Json<BRequest>, (axum::http::StatusCode, Json<Value>)> {
-    new_brige_request(uri, state, input.into()).await
+) -> Result<Response, (axum::http::StatusCode, Json<Value>)> {
+    let callback_url = input.callback_url.clone();
+    let labels = input.labels();
+    new_brige_request(uri, &headers, state, input.into(), callback_url, labels).await
 }
 
+/// 202 while the lock transaction of the request is queued to the tx processors, its
+/// progress is read from `/bridge/requests/{id}`
+fn creation_status(request: &BRequest) -> axum::http::StatusCode {
+    match request.status {
+        Status::Initializing => axum::http::StatusCode::ACCEPTED,
+        _ => axum::http::StatusCode::OK,
+    }
+}
+
+/// Creates the request, under the `Idempotency-Key` header when set. Replayed responses
+/// carry the `Idempotent-Replayed: true` header
 async fn new_brige_request(
     uri: Uri,
+    headers: &HeaderMap,
     state: AppState,
     input: InputRequest,
-) -> Result<Json<BRequest>, (axum::http::StatusCode, Json<Value>)> {
+    callback_url: Option<String>,
+    labels: ClientLabels,
+) -> Result<Response, (axum::http::StatusCode, Json<Value>)> {
     let is_invalid_route = match (uri.to_string().as_str(), &input.origin_network) {
         ("/bridge/evm-to-solana", Chains::SOLANA) => true,
         ("/bridge/solana-to-evm", Chains::EVM) => true,
@@ -50,10 +391,90 @@ async fn new_brige_request(
         ));
     }
 
-    match new_request(input.clone().into(), state).await {
-        Ok(request) => Ok(Json(request)),
+    let created = match headers.get(IDEMPOTENCY_KEY_HEADER) {
+        Some(key) => match key.to_str() {
+            Ok(key) => new_request_with_key(key, input, callback_url, labels, state).await,
+            Err(_) => Err(RequestError::InvalidIdempotencyKey(
+                "not visible ASCII".to_string(),
+            )),
+        },
+        None => new_request(input, callback_url, labels, state)
+            .await
+            .map(|request| IdempotentRequest {
+                request,
+                replayed: false,
+            }),
+    };
+    match created {
+        Ok(IdempotentRequest {
+            request,
+            replayed: false,
+        }) => Ok((
+            creation_status(&request),
+            Json(RequestResponse::from(request)),
+        )
+            .into_response()),
+        Ok(IdempotentRequest {
+            request,
+            replayed: true,
+        }) => Ok((
+            creation_status(&request),
+            [(IDEMPOTENT_REPLAYED_HEADER, "true")],
+            Json(RequestResponse::from(request)),
+        )
+            .into_response()),
+        Err(e @ RequestError::InvalidIdempotencyKey(_)) => Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({ "error": e.to_string() })),
+        )),
+        Err(
+            e @ (RequestError::IdempotencyKeyConflict(_)
+            | RequestError::IdempotencyKeyInProgress(_)),
+        ) => Err((
+            axum::http::StatusCode::CONFLICT,
+            Json(json!({ "error": e.to_string() })),
+        )),
+        Err(RequestError::BridgePaused(reason)) => Err((
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "Bridge paused", "reason": reason })),
+        )),
+        Err(RequestError::TokenAlreadyBridging(request_id)) => Err((
+            axum::http::StatusCode::CONFLICT,
+            Json(json!({ "error": "Token already bridging", "request_id": request_id })),
+        )),
+        Err(RequestError::GasLimitExceeded(reason)) => Err((
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({ "error": "Gas limit exceeded", "reason": reason })),
+        )),
+        Err(RequestError::TokenNotTransferable(reason)) => Err((
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({ "error": "Token not transferable", "reason": reason })),
+        )),
+        Err(RequestError::InvalidCallbackUrl(reason)) => Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Invalid callback URL", "reason": reason })),
+        )),
+        Err(RequestError::InvalidTokenId(reason)) => Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Invalid token id", "field": "token_id", "reason": reason })),
+        )),
+        Err(RequestError::InvalidLabel(field, reason)) => Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("Invalid {field}"), "field": field, "reason": reason })),
+        )),
+        Err(e @ RequestError::InvalidAccount(..)) => {
+            Err((axum::http::StatusCode::BAD_REQUEST, Json(error_body(&e))))
+        }
+        Err(RequestError::QuotaExceeded(account, reset_at)) => Err((
+            axum::http::StatusCode::TOO_MANY_REQUESTS,
+            Json(json!({ "error": "Quota exceeded", "account": account, "reset_at": reset_at })),
+        )),
+        Err(RequestError::CustodyLimitReached(scope, limit)) => Err((
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "Custody limit reached", "scope": scope, "limit": limit })),
+        )),
         Err(e) => {
-            error!("AppState error: {e}");
+            error!("AppState error: {}", private_text(&e.to_string()));
             Err((
                 axum::http::StatusCode::INTERNAL_SERVER_ERROR,
                 Json(json!({ "error": e.to_string() })),
@@ -62,6 +483,279 @@ async fn new_brige_request(
     }
 }
 
+/// Body of an error answer, invalid accounts name their field and the chain mix-up hint
+fn error_body(e: &RequestError) -> Value {
+    match e {
+        RequestError::InvalidAccount(field, reason, hint) => json!({
+            "error": format!("Invalid {field}"),
+            "field": field,
+            "reason": reason,
+            "hint": hint,
+        }),
+        _ => json!({ "error": e.to_string() }),
+    }
+}
+
+/// Cursors, queues, pauses and balances of the relayer in one document, authorized by
+/// `Authorization: Bearer <admin token>`
+pub async fn relayer_status(
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<RelayerStatus>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    get_status(admin_token, &state)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("Relayer status failed: {e}");
+            let status = match e {
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(json!({ "error": e.to_string() })))
+        })
+}
+
+/// Live statistics and history of the chain RPC endpoints over `?from=..&to=..` (unix
+/// seconds), authorized by `Authorization: Bearer <admin token>`
+pub async fn rpc_health(
+    BearerToken(admin_token): BearerToken,
+    Query(query): Query<RpcHealthQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<RpcHealthReport>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    get_rpc_health(query, admin_token, &state)
+        .map(Json)
+        .map_err(|e| {
+            let status = match e {
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                RequestError::InvalidTimeRange(_) => axum::http::StatusCode::BAD_REQUEST,
+                _ => {
+                    error!("RPC health failed: {e}");
+                    axum::http::StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            (status, Json(json!({ "error": e.to_string() })))
+        })
+}
+
+/// Stored requests counted by id scheme, authorized by `Authorization: Bearer <admin token>`
+pub async fn id_migration_report(
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<IdMigrationReport>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    get_id_migration_report(admin_token, &state)
+        .map(Json)
+        .map_err(|e| {
+            error!("Id migration report failed: {e}");
+            let status = match e {
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(json!({ "error": e.to_string() })))
+        })
+}
+
+/// Rebuilds the requests of a range of the chain history, authorized by
+/// `Authorization: Bearer <admin token>`. Answers once the ranges are scanned
+pub async fn backfill_requests(
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+    Json(params): Json<BackfillParams>,
+) -> Result<Json<BackfillReport>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    run_backfill(params, admin_token, &state)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("Backfill failed: {e}");
+            let status = match e {
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                RequestError::InvalidBackfillRange(_) => axum::http::StatusCode::BAD_REQUEST,
+                _ => axum::http::StatusCode::BAD_GATEWAY,
+            };
+            (status, Json(json!({ "error": e.to_string() })))
+        })
+}
+
+/// Checks on their chain the tx hashes of the recently finalized requests, authorized by
+/// `Authorization: Bearer <admin token>`. 409 while the nightly run is going
+pub async fn start_tx_audit(
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<TxAuditReport>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    run_tx_audit(admin_token, &state)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            let status = match e {
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                RequestError::TxAuditRunning() => axum::http::StatusCode::CONFLICT,
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(json!({ "error": e.to_string() })))
+        })
+}
+
+/// Report of the last tx audit run, authorized by `Authorization: Bearer <admin token>`.
+/// 404 before the first run
+pub async fn tx_audit_report(
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<TxAuditReport>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    match get_tx_audit_report(admin_token, &state) {
+        Ok(Some(report)) => Ok(Json(report)),
+        Ok(None) => Err((
+            axum::http::StatusCode::NOT_FOUND,
+            Json(json!({ "error": "No tx audit has run yet" })),
+        )),
+        Err(e) => {
+            let status = match e {
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Err((status, Json(json!({ "error": e.to_string() }))))
+        }
+    }
+}
+
+/// Replaces an account by a salted hash in its finished requests, authorized by
+/// `Authorization: Bearer <admin token>`. Unfinished requests of the account get a 409
+/// listing them
+pub async fn purge_account(
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+    Json(input): Json<PurgeInput>,
+) -> Result<Json<PurgeReceipt>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    purge_account_data(&input, admin_token, &state)
+        .map(Json)
+        .map_err(|e| {
+            let status = match &e {
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                RequestError::InvalidPurgeAccount(_) => axum::http::StatusCode::BAD_REQUEST,
+                RequestError::PurgeBlocked(requests) => {
+                    return (
+                        axum::http::StatusCode::CONFLICT,
+                        Json(json!({ "error": e.to_string(), "requests": requests })),
+                    );
+                }
+                RequestError::PurgeDisabled() => axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                _ => {
+                    error!("Account purge failed: {e}");
+                    axum::http::StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            (status, Json(json!({ "error": e.to_string() })))
+        })
+}
+
+pub async fn get_pause(State(state): State<AppState>) -> Json<PauseState> {
+    Json(state.pause.state())
+}
+
+pub async fn update_pause(
+    State(state): State<AppState>,
+    Json(update): Json<PauseUpdate>,
+) -> Result<Json<PauseState>, (axum::http::StatusCode, Json<Value>)> {
+    let resumed = !update.paused;
+    if let Err(e) = state.pause.apply(&state.db, update) {
+        error!("Could not update pause state: {e}");
+        return Err((
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        ));
+    }
+
+    if resumed {
+        let state_clone = state.clone();
+        tokio::spawn(async move { requests::drain_resumed_events(&state_clone).await });
+    }
+    Ok(Json(state.pause.state()))
+}
+
+/// Starts a maintenance and waits for the work in flight to drain, authorized by
+/// `Authorization: Bearer <admin token>`
+pub async fn begin_maintenance_window(
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+    Json(input): Json<MaintenanceInput>,
+) -> Result<Json<DrainReport>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    begin_maintenance(input, admin_token, &state)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            let status = match e {
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                _ => {
+                    error!("Could not begin the maintenance: {e}");
+                    axum::http::StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            (status, Json(json!({ "error": e.to_string() })))
+        })
+}
+
+/// Ends the maintenance and picks up the work held back during it, authorized by
+/// `Authorization: Bearer <admin token>`
+pub async fn end_maintenance_window(
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<PauseState>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    let pause = end_maintenance(admin_token, &state).map_err(|e| {
+        let status = match e {
+            RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+            _ => {
+                error!("Could not end the maintenance: {e}");
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, Json(json!({ "error": e.to_string() })))
+    })?;
+    let state_clone = state.clone();
+    tokio::spawn(async move { requests::resume_held_work(&state_clone).await });
+    Ok(Json(pause))
+}
+
+/// Moves the chain clients to new node URLs during a maintenance, authorized by
+/// `Authorization: Bearer <admin token>`. 409 outside a maintenance, 502 when a new node
+/// doesn't answer, the current endpoints are kept on any error
+pub async fn update_endpoints(
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+    Json(update): Json<EndpointsUpdate>,
+) -> Result<Json<EndpointsReport>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    switch_endpoints(update, admin_token, &state)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            let status = match e {
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                RequestError::MaintenanceRequired() => axum::http::StatusCode::CONFLICT,
+                RequestError::InvalidEndpoint(_, _) => axum::http::StatusCode::BAD_REQUEST,
+                RequestError::EndpointUnreachable(_) => axum::http::StatusCode::BAD_GATEWAY,
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(json!({ "error": e.to_string() })))
+        })
+}
+
 pub async fn pending_requests(
     State(state): State<AppState>,
 ) -> Result<Json<Vec<String>>, axum::http::StatusCode> {
@@ -71,30 +765,576 @@ pub async fn pending_requests(
     }
 }
 
+pub async fn pending_summary(State(state): State<AppState>) -> Json<Value> {
+    Json(get_pending_summary(&state.db))
+}
+
+/// Request id of a path parameter, malformed ids are rejected before any lookup
+fn path_request_id(id: &str) -> Result<RequestId, (axum::http::StatusCode, Json<Value>)> {
+    RequestId::parse(id).map_err(|e| {
+        (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({ "error": e.to_string() })),
+        )
+    })
+}
+
 pub async fn request_data(
     Path(id): Path<String>,
     State(state): State<AppState>,
-) -> Result<Json<BRequest>, axum::http::StatusCode> {
-    match get_request(&id, &state.db) {
-        Ok(Some(request)) => Ok(Json(request)),
+) -> Result<Json<RequestResponse>, axum::http::StatusCode> {
+    let id = RequestId::parse(&id).map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+    match get_request(&id, &state) {
+        Ok(Some(request)) => Ok(Json(request.into())),
         _ => Err(axum::http::StatusCode::NOT_FOUND),
     }
 }
 
-pub async fn block_explorers(
+/// Place of the request in the pending queue, processed first in first out
+pub async fn request_queue_position(
+    Path(id): Path<String>,
     State(state): State<AppState>,
 ) -> Result<Json<Value>, axum::http::StatusCode> {
-    if state.evm_client.block_explorer != String::default()
-        && state.solana_client.block_explorer != String::default()
-    {
-        Ok(
-            json!({"EVM": state.evm_client.block_explorer, "SOLANA": state.solana_client.block_explorer}).into(),
+    let id = RequestId::parse(&id).map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+    if !matches!(get_request(&id, &state), Ok(Some(_))) {
+        return Err(axum::http::StatusCode::NOT_FOUND);
+    }
+    match get_queue_position(&id, &state.db) {
+        Ok(position) => Ok(Json(json!({ "request_id": id, "position": position }))),
+        Err(e) => {
+            error!("Queue position of request {id} failed: {e}");
+            Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Status of several requests in one call, unknown ids are listed in `not_found`. The
+/// max-age tells pollers the interval between two calls
+pub async fn batch_request_status(
+    State(state): State<AppState>,
+    Json(input): Json<BatchStatusInput>,
+) -> Result<Response, (axum::http::StatusCode, Json<Value>)> {
+    match get_batch_status(&input.ids, &state, display_request) {
+        Ok(status) => Ok((
+            [(
+                header::CACHE_CONTROL,
+                format!("private, max-age={BATCH_STATUS_MAX_AGE_SECS}"),
+            )],
+            Json(status),
         )
-    } else {
-        Err(axum::http::StatusCode::NOT_FOUND)
+            .into_response()),
+        Err(e) => {
+            let status = match e {
+                RequestError::BatchTooLarge(..) => axum::http::StatusCode::BAD_REQUEST,
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Err((status, Json(json!({ "error": e.to_string() }))))
+        }
+    }
+}
+
+/// Attempts and suggested actions of a request given up after its last retry, 404 when
+/// the request did not fail
+pub async fn request_failure_report(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<FailureReport>, axum::http::StatusCode> {
+    let id = RequestId::parse(&id).map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+    match get_failure_report(&id, &state.db) {
+        Ok(Some(report)) => Ok(Json(report)),
+        Ok(None) => Err(axum::http::StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failure report of request {id} failed: {e}");
+            Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Origin of a Solana mint bridged from EVM, or the EVM token minted for a Solana mint
+pub async fn wrapped_solana_token(
+    Path(mint): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<WrappedTokenInfo>, axum::http::StatusCode> {
+    wrapped_token(BridgedToken::new(Chains::SOLANA, &mint, ""), &state)
+}
+
+/// Origin of an EVM token bridged from Solana, or the Solana mint of a bridged EVM token
+pub async fn wrapped_evm_token(
+    Path((contract, token_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<Json<WrappedTokenInfo>, axum::http::StatusCode> {
+    wrapped_token(BridgedToken::new(Chains::EVM, &contract, &token_id), &state)
+}
+
+/// Whether the owner can bridge an EVM token, checked before approving the bridge
+pub async fn evm_token_preflight(
+    Path((contract, token_id)): Path<(String, String)>,
+    Query(query): Query<PreflightQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<PreflightReport>, (axum::http::StatusCode, Json<Value>)> {
+    evm_preflight(&contract, &token_id, &query.owner, &state)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("Preflight of token {token_id} of {contract} failed: {e}");
+            let status = match e {
+                RequestError::InvalidPreflight(_) => axum::http::StatusCode::BAD_REQUEST,
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(json!({ "error": e.to_string() })))
+        })
+}
+
+fn wrapped_token(
+    token: BridgedToken,
+    state: &AppState,
+) -> Result<Json<WrappedTokenInfo>, axum::http::StatusCode> {
+    match get_wrapped_token(&token, &state.db) {
+        Ok(Some(info)) => Ok(Json(info)),
+        Ok(None) => Err(axum::http::StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Wrapped asset lookup of {token:?} failed: {e}");
+            Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR)
+        }
     }
 }
 
+/// Requests that recorded a transaction, by EVM tx hash or Solana signature
+pub async fn requests_by_tx(
+    Path(tx_hash): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, (axum::http::StatusCode, Json<Value>)> {
+    match get_requests_by_tx(&tx_hash, &state.db) {
+        Ok((chain, requests)) => Ok(Json(json!({
+            "tx_hash": tx_hash.trim(),
+            "chain": chain,
+            "requests": requests
+                .into_iter()
+                .map(RequestResponse::from)
+                .collect::<Vec<_>>(),
+        }))),
+        Err(e) => {
+            let status = match e {
+                RequestError::InvalidTxHash(_) => axum::http::StatusCode::BAD_REQUEST,
+                RequestError::UnknownTxHash(_) => axum::http::StatusCode::NOT_FOUND,
+                _ => {
+                    error!("Lookup of the requests of tx {tx_hash} failed: {e}");
+                    axum::http::StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            Err((status, Json(json!({ "error": e.to_string() }))))
+        }
+    }
+}
+
+/// Requests created with a client reference, every match as references are not unique
+pub async fn requests_by_client_reference(
+    Query(filter): Query<RequestsQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, (axum::http::StatusCode, Json<Value>)> {
+    let Some(reference) = filter.client_reference else {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Missing filter", "field": "client_reference" })),
+        ));
+    };
+    match get_requests_by_client_reference(&reference, &state.db) {
+        Ok(requests) => Ok(Json(json!({
+            "client_reference": reference.trim(),
+            "requests": requests
+                .into_iter()
+                .map(RequestResponse::from)
+                .collect::<Vec<_>>(),
+        }))),
+        Err(RequestError::InvalidLabel(field, reason)) => Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("Invalid {field}"), "field": field, "reason": reason })),
+        )),
+        Err(e) => {
+            error!("Lookup of the requests of client reference {reference} failed: {e}");
+            Err((
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": e.to_string() })),
+            ))
+        }
+    }
+}
+
+/// Last error of the request with the chain call it came from
+pub async fn request_diagnostics(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, axum::http::StatusCode> {
+    let id = RequestId::parse(&id).map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+    match get_request(&id, &state) {
+        Ok(Some(request)) => {
+            // Node errors leave the mint state out, diagnostics are still returned. Read-only
+            // relayers don't reach the chains
+            let mint_state = match state.role {
+                Role::Full => mint_tx_state(&state, &request).await,
+                Role::ReadOnly => Ok(None),
+            };
+            let mint_tx = match mint_state {
+                Ok(Some((tx_hash, tx_state))) => json!({
+                    "tx_hash": tx_hash,
+                    "state": tx_state,
+                    "priced_out": tx_state.priced_out(),
+                }),
+                Ok(None) => Value::Null,
+                Err(e) => {
+                    error!("Could not read the mint transaction of request {id}: {e}");
+                    Value::Null
+                }
+            };
+            let request = display_request(request);
+            let evm_explorer = ExplorerBase::parse(&state.evm_client.block_explorer).ok();
+            let solana_explorer = ExplorerBase::parse(&state.solana_client.block_explorer).ok();
+            let tx_links: Vec<Option<String>> = request
+                .tx_hashes
+                .iter()
+                .map(|hash| tx_explorer_link(evm_explorer.as_ref(), solana_explorer.as_ref(), hash))
+                .collect();
+            let origin_explorer = match request.input.origin_network {
+                Chains::EVM => evm_explorer.as_ref(),
+                Chains::SOLANA => solana_explorer.as_ref(),
+            };
+            let lock_block_link = request
+                .lock_block
+                .and_then(|height| block_explorer_link(origin_explorer, height));
+            Ok(Json(json!({
+                "id": request.id,
+                "status": request.status,
+                "last_update": request.last_update,
+                "last_error": request.last_error,
+                "error_context": request.last_error_context,
+                "last_error_record": request.last_error_record,
+                "previous_errors": request.previous_errors,
+                "tx_hashes": request.tx_hashes,
+                "tx_links": tx_links,
+                "lock_block": request.lock_block,
+                "lock_block_link": lock_block_link,
+                "mint_tx": mint_tx,
+            })))
+        }
+        _ => Err(axum::http::StatusCode::NOT_FOUND),
+    }
+}
+
+/// Recovery of a failed mint, authorized by `Authorization: Bearer <admin token>` or an
+/// owner signature in the body
+pub async fn redirect_request_mint(
+    Path(id): Path<String>,
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+    Json(input): Json<RedirectMintInput>,
+) -> Result<Json<RequestResponse>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    let id = path_request_id(&id)?;
+    match redirect_mint(&id, input, admin_token, &state).await {
+        Ok(request) => Ok(Json(request.into())),
+        Err(e) => {
+            error!("Redirect mint of request {id} failed: {e}");
+            let status = match e {
+                RequestError::NoExistingRequest(_) => axum::http::StatusCode::NOT_FOUND,
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                RequestError::InvalidAccount(..) => axum::http::StatusCode::BAD_REQUEST,
+                RequestError::RedirectNotAllowed(_) => axum::http::StatusCode::CONFLICT,
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Err((status, Json(error_body(&e))))
+        }
+    }
+}
+
+/// Checks the destination token account of a request waiting for it, its mint is sent
+/// once the account is funded
+pub async fn recheck_destination_account(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<RequestResponse>, (axum::http::StatusCode, Json<Value>)> {
+    let id = path_request_id(&id)?;
+    match recheck_request(&id, &state).await {
+        Ok(request) => Ok(Json(request.into())),
+        Err(e) => {
+            error!("Recheck of request {id} failed: {e}");
+            let status = match e {
+                RequestError::NoExistingRequest(_) => axum::http::StatusCode::NOT_FOUND,
+                RequestError::RecheckNotAllowed(_) => axum::http::StatusCode::CONFLICT,
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Err((status, Json(error_body(&e))))
+        }
+    }
+}
+
+/// Tokens in custody for request ids never submitted to the API
+pub async fn orphan_requests(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<RequestResponse>>, axum::http::StatusCode> {
+    get_orphans(&state.db)
+        .map(|orphans| Json(orphans.into_iter().map(Into::into).collect()))
+        .map_err(|e| {
+            error!("Could not read orphan requests: {e}");
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// Sets the destination of an orphan, authorized by a signature of the token owner
+pub async fn claim_orphan_request(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Json(input): Json<ClaimOrphanInput>,
+) -> Result<Json<RequestResponse>, (axum::http::StatusCode, Json<Value>)> {
+    let id = path_request_id(&id)?;
+    match claim_orphan(&id, input, &state).await {
+        Ok(request) => Ok(Json(request.into())),
+        Err(e) => {
+            error!("Claim of orphan request {id} failed: {e}");
+            let status = match e {
+                RequestError::NoExistingRequest(_) => axum::http::StatusCode::NOT_FOUND,
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                RequestError::InvalidAccount(..) => axum::http::StatusCode::BAD_REQUEST,
+                RequestError::ClaimNotAllowed(_) => axum::http::StatusCode::CONFLICT,
+                RequestError::CustodyLimitReached(..) => {
+                    axum::http::StatusCode::SERVICE_UNAVAILABLE
+                }
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Err((status, Json(error_body(&e))))
+        }
+    }
+}
+
+/// Runs the sweep step of one request now, authorized by `Authorization: Bearer <admin token>`
+pub async fn reprocess_pending_request(
+    Path(id): Path<String>,
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<ReprocessResult>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    let id = path_request_id(&id)?;
+    match reprocess_request(&id, admin_token, &state).await {
+        Ok(result) => Ok(Json(result)),
+        Err(e) => {
+            error!("Reprocess of request {id} failed: {e}");
+            let status = match e {
+                RequestError::NoExistingRequest(_) => axum::http::StatusCode::NOT_FOUND,
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                RequestError::ReprocessNotAllowed(_) => axum::http::StatusCode::CONFLICT,
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Err((status, Json(json!({ "error": e.to_string() }))))
+        }
+    }
+}
+
+/// Queues the refund of a token in custody, authorized by `Authorization: Bearer <admin token>`
+pub async fn refund_custodied_token(
+    Path(id): Path<String>,
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<RequestResponse>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    let id = path_request_id(&id)?;
+    match refund_request(&id, admin_token, &state).await {
+        Ok(request) => Ok(Json(request.into())),
+        Err(e) => {
+            error!("Refund of request {id} failed: {e}");
+            let status = match e {
+                RequestError::NoExistingRequest(_) => axum::http::StatusCode::NOT_FOUND,
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                RequestError::RefundNotAllowed(_) => axum::http::StatusCode::CONFLICT,
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Err((status, Json(json!({ "error": e.to_string() }))))
+        }
+    }
+}
+
+/// Drops the metadata snapshot of a request, its next mint reads the origin token again.
+/// Authorized by `Authorization: Bearer <admin token>`
+pub async fn refresh_metadata(
+    Path(id): Path<String>,
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<RequestResponse>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    let id = path_request_id(&id)?;
+    match refresh_request_metadata(&id, admin_token, &state) {
+        Ok(request) => Ok(Json(request.into())),
+        Err(e) => {
+            error!("Metadata refresh of request {id} failed: {e}");
+            let status = match e {
+                RequestError::NoExistingRequest(_) => axum::http::StatusCode::NOT_FOUND,
+                RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+                RequestError::RefreshNotAllowed(_) => axum::http::StatusCode::CONFLICT,
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Err((status, Json(json!({ "error": e.to_string() }))))
+        }
+    }
+}
+
+fn loadtest_error(run_id: &str, e: RequestError) -> (axum::http::StatusCode, Json<Value>) {
+    error!("Load test {run_id} request failed: {e}");
+    let status = match e {
+        RequestError::NoExistingRequest(_) => axum::http::StatusCode::NOT_FOUND,
+        RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+        RequestError::LoadTestNotAllowed(_) => axum::http::StatusCode::CONFLICT,
+        _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(json!({ "error": e.to_string() })))
+}
+
+/// Starts a dry run load test, authorized by `Authorization: Bearer <admin token>`
+pub async fn start_load_test(
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+    Json(params): Json<LoadTestParams>,
+) -> Result<Json<Value>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    start_loadtest(params, admin_token, &state)
+        .map(|run_id| Json(json!({ "run_id": run_id })))
+        .map_err(|e| loadtest_error("start", e))
+}
+
+pub async fn load_test_report(
+    Path(run_id): Path<String>,
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<LoadTestReport>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    get_loadtest(&run_id, admin_token, &state)
+        .map(Json)
+        .map_err(|e| loadtest_error(&run_id, e))
+}
+
+pub async fn cancel_load_test(
+    Path(run_id): Path<String>,
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    cancel_loadtest(&run_id, admin_token, &state)
+        .map(|_| Json(json!({ "run_id": run_id, "canceled": true })))
+        .map_err(|e| loadtest_error(&run_id, e))
+}
+
+fn quota_error(account: &str, e: RequestError) -> (axum::http::StatusCode, Json<Value>) {
+    error!("Quota request of {account} failed: {e}");
+    let status = match e {
+        RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+        _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(json!({ "error": e.to_string() })))
+}
+
+/// Quota usage of an account, authorized by `Authorization: Bearer <admin token>`
+pub async fn quota_report(
+    Path(account): Path<String>,
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<QuotaReport>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    get_quota(&account, admin_token, &state)
+        .map(Json)
+        .map_err(|e| quota_error(&account, e))
+}
+
+pub async fn reset_quota(
+    Path(account): Path<String>,
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<QuotaReport>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    clear_quota(&account, admin_token, &state)
+        .map(Json)
+        .map_err(|e| quota_error(&account, e))
+}
+
+fn collection_error(contract: &str, e: RequestError) -> (axum::http::StatusCode, Json<Value>) {
+    error!("Collection request of {contract} failed: {e}");
+    let status = match e {
+        RequestError::Unauthorized() => axum::http::StatusCode::UNAUTHORIZED,
+        RequestError::InvalidCollection(_) => axum::http::StatusCode::BAD_REQUEST,
+        _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(json!({ "error": e.to_string() })))
+}
+
+/// Solana collections of the bridged EVM contracts, authorized by
+/// `Authorization: Bearer <admin token>`
+pub async fn collections(
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<CollectionEntry>>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    get_collections(admin_token, &state)
+        .map(Json)
+        .map_err(|e| collection_error("every contract", e))
+}
+
+pub async fn update_collection(
+    Path(contract): Path<String>,
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+    Json(input): Json<SetCollectionInput>,
+) -> Result<Json<CollectionEntry>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    set_collection(&contract, input, admin_token, &state)
+        .await
+        .map(Json)
+        .map_err(|e| collection_error(&contract, e))
+}
+
+pub async fn remove_collection(
+    Path(contract): Path<String>,
+    BearerToken(admin_token): BearerToken,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, (axum::http::StatusCode, Json<Value>)> {
+    let admin_token = admin_token.as_deref();
+
+    delete_collection(&contract, admin_token, &state)
+        .map(|_| Json(json!({ "removed": contract })))
+        .map_err(|e| collection_error(&contract, e))
+}
+
+pub async fn block_explorers(
+    State(state): State<AppState>,
+) -> Result<Json<Value>, axum::http::StatusCode> {
+    // Bases are validated at startup, parsing again keeps unchecked values out of the response
+    match (
+        ExplorerBase::parse(&state.evm_client.block_explorer),
+        ExplorerBase::parse(&state.solana_client.block_explorer),
+    ) {
+        (Ok(evm), Ok(solana)) => Ok(json!({"EVM": evm.as_str(), "SOLANA": solana.as_str()}).into()),
+        _ => Err(axum::http::StatusCode::NOT_FOUND),
+    }
+}
+
+/// Features, policies and limits of this deployment, cacheable for a short time
+pub async fn capabilities(State(state): State<AppState>) -> Response {
+    (
+        [(
+            header::CACHE_CONTROL,
+            format!("public, max-age={CAPABILITIES_MAX_AGE_SECS}"),
+        )],
+        Json(get_capabilities(&state)),
+    )
+        .into_response()
+}
+
 pub async fn completed_requests(
     State(state): State<AppState>,
 ) -> Result<Json<Vec<String>>, axum::http::StatusCode> {
@@ -103,3 +1343,517 @@ pub async fn completed_requests(
         None => Ok(Json(vec![String::new()])),
     }
 }
+
+#[derive(Default)]
+struct ExportCursor {
+    after: Option<String>,
+    started: bool,
+    done: bool,
+    rows: usize,
+}
+
+/// Streams the completed requests page by page so large histories aren't buffered
+pub async fn export_completed(
+    State(state): State<AppState>,
+    Query(filter): Query<ExportFilter>,
+) -> Response {
+    let format = filter.format;
+    let stream = futures_util::stream::unfold(
+        (ExportCursor::default(), state.db.clone(), filter),
+        move |(mut cursor, db, filter)| async move {
+            if cursor.done {
+                return None;
+            }
+
+            let mut chunk = String::new();
+            if !cursor.started {
+                cursor.started = true;
+                chunk.push_str(match format {
+                    ExportFormat::Csv => EXPORT_CSV_HEADER,
+                    ExportFormat::Json => "[",
+                });
+            }
+
+            match export_page(&db, &filter, cursor.after.as_deref(), EXPORT_PAGE_SIZE) {
+                Ok((requests, next)) => {
+                    for request in requests.into_iter().map(display_request) {
+                        match format {
+                            ExportFormat::Csv => chunk.push_str(&csv_row(&request)),
+                            ExportFormat::Json => {
+                                if cursor.rows > 0 {
+                                    chunk.push(',');
+                                }
+                                chunk.push_str(&json_row(&request).to_string());
+                            }
+                        }
+                        cursor.rows += 1;
+                    }
+
+                    cursor.done = next.is_none();
+                    cursor.after = next;
+                    if cursor.done && format == ExportFormat::Json {
+                        chunk.push(']');
+                    }
+                    Some((Ok(chunk), (cursor, db, filter)))
+                }
+                Err(e) => {
+                    error!("Completed requests export failed: {e}");
+                    cursor.done = true;
+                    Some((
+                        Err(std::io::Error::other(e.to_string())),
+                        (cursor, db, filter),
+                    ))
+                }
+            }
+        },
+    );
+
+    let content_type = match format {
+        ExportFormat::Csv => "text/csv",
+        ExportFormat::Json => "application/json",
+    };
+    (
+        [(header::CONTENT_TYPE, content_type)],
+        Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod service_test {
+    use std::sync::{atomic::Ordering, Arc};
+
+    use axum::{
+        extract::{Path, Query, State},
+        http::{HeaderMap, StatusCode},
+        Json,
+    };
+    use requests::{
+        test_utils::test_state, AppState, PurgeInput, RequestsQuery, Role,
+        BATCH_STATUS_MAX_AGE_SECS, MAX_BATCH_STATUS_IDS,
+    };
+    use serde_json::Value;
+    use storage::metrics::StorageMetrics;
+    use test_support::{input_request, mock_rpc, solana_key, test_db, EVM_ACCOUNT};
+    use tokio::net::TcpListener;
+    use types::{
+        BRequest, Chains, EVMInputRequest, SolanaInputRequest, Status, Timestamp, TxMessage,
+        MAX_CLIENT_REFERENCE_LEN, MAX_TAGS,
+    };
+
+    use crate::{
+        api_router, metrics, new_brige_from_evm, new_brige_from_solana, purge_account,
+        request_data, requests_by_client_reference, requests_by_tx, BearerToken,
+    };
+
+    const EVM_TX: &str = "0x5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060";
+    const SOLANA_TX: &str =
+        "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW";
+
+    #[tokio::test]
+    async fn test_requests_by_tx_hash() {
+        let (state, _rx_evm, _rx_sol) = test_state(test_db());
+        let mut request = BRequest::new(input_request(Chains::EVM, "0x2a"), Timestamp::now());
+        request.save(&state.db).unwrap();
+        request.add_tx(EVM_TX, &state.db).unwrap();
+        request.add_tx(SOLANA_TX, &state.db).unwrap();
+
+        for (tx_hash, chain) in [(EVM_TX, "EVM"), (SOLANA_TX, "SOLANA")] {
+            let found = requests_by_tx(Path(tx_hash.to_string()), State(state.clone()))
+                .await
+                .unwrap()
+                .0;
+            assert_eq!(found["tx_hash"], tx_hash);
+            assert_eq!(found["chain"], chain);
+            assert_eq!(found["requests"][0]["id"], request.id.as_str());
+        }
+
+        let unknown = format!("0x{}", "ab".repeat(32));
+        let (status, body) = requests_by_tx(Path(unknown.clone()), State(state.clone()))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            body.0["error"],
+            format!("No request recorded the transaction {unknown}")
+        );
+        let (status, _) = requests_by_tx(Path("0xtx".to_string()), State(state))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    fn solana_input(mint_seed: u8) -> SolanaInputRequest {
+        SolanaInputRequest {
+            token_mint: solana_key(mint_seed).to_string(),
+            token_account: solana_key(2).to_string(),
+            origin_network: Chains::SOLANA,
+            destination_account: EVM_ACCOUNT.to_string(),
+            callback_url: None,
+            client_reference: None,
+            tags: vec![],
+        }
+    }
+
+    async fn create_solana_request(
+        state: &AppState,
+        input: SolanaInputRequest,
+    ) -> (StatusCode, Value) {
+        let response = match new_brige_from_solana(
+            "/bridge/solana-to-evm".parse().unwrap(),
+            HeaderMap::new(),
+            State(state.clone()),
+            Json(input),
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err((status, body)) => return (status, body.0),
+        };
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn create_from_solana(state: &AppState, mint_seed: u8) -> (StatusCode, Value) {
+        create_solana_request(state, solana_input(mint_seed)).await
+    }
+
+    #[tokio::test]
+    async fn test_requests_found_by_client_reference() {
+        let (state, _rx_evm, _rx_sol) = test_state(test_db());
+        let mut ids = vec![];
+        for mint_seed in [11, 12] {
+            let mut input = solana_input(mint_seed);
+            input.client_reference = Some(" order-1 ".to_string());
+            input.tags = vec!["vip".to_string(), "eu".to_string()];
+            let (status, body) = create_solana_request(&state, input).await;
+            assert_eq!(status, StatusCode::ACCEPTED);
+            assert_eq!(body["client_reference"], "order-1");
+            assert_eq!(body["tags"], serde_json::json!(["vip", "eu"]));
+            ids.push(body["id"].as_str().unwrap().to_string());
+        }
+        assert_eq!(create_from_solana(&state, 13).await.0, StatusCode::ACCEPTED);
+
+        let query = |reference: Option<&str>| {
+            Query(RequestsQuery {
+                client_reference: reference.map(str::to_string),
+            })
+        };
+        let found = requests_by_client_reference(query(Some("order-1")), State(state.clone()))
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(found["client_reference"], "order-1");
+        let mut found_ids: Vec<String> = found["requests"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|request| {
+                assert_eq!(request["tags"][0], "vip");
+                request["id"].as_str().unwrap().to_string()
+            })
+            .collect();
+        found_ids.sort();
+        ids.sort();
+        assert_eq!(found_ids, ids);
+        let unknown = requests_by_client_reference(query(Some("order-2")), State(state.clone()))
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(unknown["requests"], serde_json::json!([]));
+        let (status, body) = requests_by_client_reference(query(None), State(state.clone()))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.0["field"], "client_reference");
+
+        let mut oversized = solana_input(14);
+        oversized.client_reference = Some("r".repeat(MAX_CLIENT_REFERENCE_LEN + 1));
+        let (status, body) = create_solana_request(&state, oversized).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "Invalid client_reference");
+        assert_eq!(body["field"], "client_reference");
+        let mut too_many_tags = solana_input(15);
+        too_many_tags.tags = vec!["tag".to_string(); MAX_TAGS + 1];
+        let (status, body) = create_solana_request(&state, too_many_tags).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["field"], "tags");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_token_id_rejected_with_its_field() {
+        let (state, _rx_evm, _rx_sol) = test_state(test_db());
+        let (status, body) = new_brige_from_evm(
+            "/bridge/evm-to-solana".parse().unwrap(),
+            HeaderMap::new(),
+            State(state),
+            Json(EVMInputRequest {
+                token_contract: "0x5FbDB2315678afecb367f032d93F642f64180aa3".to_string(),
+                token_id: "1a4".to_string(),
+                token_owner: EVM_ACCOUNT.to_string(),
+                origin_network: Chains::EVM,
+                destination_account: solana_key(3).to_string(),
+                callback_url: None,
+                client_reference: None,
+                tags: vec![],
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.0["error"], "Invalid token id");
+        assert_eq!(body.0["field"], "token_id");
+        assert!(body.0["reason"]
+            .as_str()
+            .unwrap()
+            .contains("0x-prefixed hex"));
+    }
+
+    #[tokio::test]
+    async fn test_account_of_the_origin_chain_rejected_with_a_hint() {
+        let (state, _rx_evm, _rx_sol) = test_state(test_db());
+        let (status, body) = new_brige_from_evm(
+            "/bridge/evm-to-solana".parse().unwrap(),
+            HeaderMap::new(),
+            State(state.clone()),
+            Json(EVMInputRequest {
+                token_contract: "0x5FbDB2315678afecb367f032d93F642f64180aa3".to_string(),
+                token_id: "1".to_string(),
+                token_owner: EVM_ACCOUNT.to_string(),
+                origin_network: Chains::EVM,
+                destination_account: EVM_ACCOUNT.to_string(),
+                callback_url: None,
+                client_reference: None,
+                tags: vec![],
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.0["error"], "Invalid destination_account");
+        assert_eq!(body.0["field"], "destination_account");
+        assert_eq!(body.0["reason"], "expected a base58 Solana public key");
+        assert!(body.0["hint"]
+            .as_str()
+            .unwrap()
+            .contains("looks like an EVM address but this endpoint bridges to Solana"));
+
+        let mut input = solana_input(1);
+        input.token_mint = "0x5FbDB2315678afecb367f032d93F642f64180aa3".to_string();
+        let (status, body) = create_solana_request(&state, input).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["field"], "token_mint");
+        assert!(body["hint"]
+            .as_str()
+            .unwrap()
+            .contains("looks like an EVM contract address but this endpoint bridges from Solana"));
+    }
+
+    async fn rendered(state: &AppState, id: &str) -> Value {
+        let rendered = request_data(Path(id.to_string()), State(state.clone()))
+            .await
+            .unwrap();
+        serde_json::to_value(rendered.0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_request_accepted_then_locked_in_background() {
+        let (mut state, _rx_evm, mut rx_sol) = test_state(test_db());
+        // The mock accepts any send
+        state.solana_client.set_rpc(mock_rpc([]));
+
+        let (status, body) = create_from_solana(&state, 1).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(body["status"], "Initializing");
+        let id = body["id"].as_str().unwrap().to_string();
+        assert_eq!(rendered(&state, &id).await["status"], "Initializing");
+
+        let Some(TxMessage::NewRequest(lock)) = rx_sol.recv().await else {
+            panic!("expected the lock of the request");
+        };
+        assert_eq!(lock.request_id.as_str(), id);
+        let signature = solana::lock_request(&state.solana_client, &state.db, &lock)
+            .await
+            .unwrap()
+            .unwrap();
+        let locked = rendered(&state, &id).await;
+        assert_eq!(locked["status"], "RequestReceived");
+        assert_eq!(locked["tx_hashes"][0], signature.to_string());
+
+        // Handled once, a message sent again by the sweep is skipped
+        assert_eq!(
+            solana::lock_request(&state.solana_client, &state.db, &lock)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lock_failure_surfaced_on_the_request() {
+        let (state, _rx_evm, mut rx_sol) = test_state(test_db());
+        state
+            .solana_client
+            .authorized_backend
+            .store(false, Ordering::Relaxed);
+
+        let (status, body) = create_from_solana(&state, 5).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let id = body["id"].as_str().unwrap().to_string();
+
+        let Some(TxMessage::NewRequest(lock)) = rx_sol.recv().await else {
+            panic!("expected the lock of the request");
+        };
+        assert!(solana::lock_request(&state.solana_client, &state.db, &lock)
+            .await
+            .is_err());
+        let failed = rendered(&state, &id).await;
+        assert_eq!(failed["status"], "Canceled");
+        let reason = failed["cancel_reason"]["LockFailed"].as_str().unwrap();
+        assert!(reason.contains("is not the authorized backend"), "{reason}");
+        assert!(failed["last_error"].is_string());
+
+        // Canceled before its lock, the token can be bridged again
+        assert_eq!(create_from_solana(&state, 5).await.0, StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn test_purged_request_rendered_with_markers() {
+        let (mut state, _rx_evm, _rx_sol) = test_state(test_db());
+        state.admin_token = Some("secret".into());
+        state.purge_salt = Some("salt".into());
+
+        let mut request = BRequest::new(input_request(Chains::EVM, "0x2a"), Timestamp::now());
+        request.save(&state.db).unwrap();
+        let owner = request.input.token_owner.clone();
+        let purge = |account: &str| {
+            purge_account(
+                BearerToken(Some("secret".to_string())),
+                State(state.clone()),
+                Json(PurgeInput {
+                    account: account.to_string(),
+                }),
+            )
+        };
+
+        let (status, body) = purge(&owner).await.unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body.0["requests"][0], request.id.as_str());
+
+        request.status = Status::Completed;
+        request.save(&state.db).unwrap();
+        let receipt = purge(&owner).await.unwrap().0;
+        assert_eq!(receipt.requests, vec![request.id.clone()]);
+
+        let rendered = request_data(Path(request.id.to_string()), State(state.clone()))
+            .await
+            .unwrap();
+        let rendered = serde_json::to_value(rendered.0).unwrap();
+        let marker = rendered["input"]["token_owner"].as_str().unwrap();
+        assert!(types::is_purge_marker(marker), "{marker}");
+        assert!(rendered["purged_at"].is_u64());
+        assert!(!rendered
+            .to_string()
+            .to_lowercase()
+            .contains(&owner.to_lowercase()));
+    }
+
+    #[tokio::test]
+    async fn test_read_only_relayer_serves_reads() {
+        let (mut state, _rx_evm, _rx_sol) = test_state(test_db());
+        let request = BRequest::new(input_request(Chains::SOLANA, "1"), Timestamp::now());
+        request.save(&state.db).unwrap();
+        state.db = state.db.as_read_only();
+        state.role = Role::ReadOnly;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, api_router(state)).await });
+        let http = reqwest::Client::new();
+
+        let found = http
+            .get(format!("{url}/bridge/requests/{}", request.id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(found.status().as_u16(), StatusCode::OK.as_u16());
+        let found: Value = found.json().await.unwrap();
+        assert_eq!(found["id"], request.id.as_str());
+
+        let unknown = BRequest::new(input_request(Chains::SOLANA, "2"), Timestamp::now()).id;
+        let batch = http
+            .post(format!("{url}/bridge/requests/batch-status"))
+            .json(&serde_json::json!({ "ids": [request.id, unknown] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(batch.status().as_u16(), StatusCode::OK.as_u16());
+        assert_eq!(
+            batch.headers()["cache-control"],
+            format!("private, max-age={BATCH_STATUS_MAX_AGE_SECS}")
+        );
+        let batch: Value = batch.json().await.unwrap();
+        assert_eq!(
+            batch["requests"][request.id.as_str()]["status"],
+            "RequestReceived"
+        );
+        assert!(batch["requests"][request.id.as_str()]["tx_hashes"].is_null());
+        assert_eq!(batch["not_found"], serde_json::json!([unknown]));
+
+        let too_many = http
+            .post(format!("{url}/bridge/requests/batch-status"))
+            .json(&serde_json::json!({ "ids": vec![request.id.clone(); MAX_BATCH_STATUS_IDS + 1] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(too_many.status().as_u16(), StatusCode::BAD_REQUEST.as_u16());
+
+        // Writes and chain reads are refused before their handlers run
+        let refused = [
+            http.post(format!("{url}/bridge/evm-to-solana"))
+                .json(&serde_json::json!({})),
+            http.put(format!("{url}/admin/pause")),
+            http.get(format!(
+                "{url}/bridge/preflight/evm/0x5FbDB2315678afecb367f032d93F642f64180aa3/1"
+            )),
+        ];
+        for refused in refused {
+            let response = refused.send().await.unwrap();
+            assert_eq!(
+                response.status().as_u16(),
+                StatusCode::NOT_IMPLEMENTED.as_u16()
+            );
+            let body: Value = response.json().await.unwrap();
+            assert_eq!(body["error"], "Not served by a read-only relayer");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_storage_metrics_exposed() {
+        let (mut state, _rx_evm, _rx_sol) = test_state(test_db());
+        let storage_metrics = StorageMetrics::default();
+        state.db = state.db.with_recorder(Arc::new(storage_metrics.clone()));
+        state.storage_metrics = Some(storage_metrics);
+        BRequest::new(input_request(Chains::EVM, "1"), Timestamp::now())
+            .save(&state.db)
+            .unwrap();
+        state.db.sample_storage().unwrap();
+
+        let response = metrics(State(state)).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(body.contains("# TYPE relayer_db_operation_seconds histogram"));
+        assert!(body
+            .contains("relayer_db_operation_seconds_count{operation=\"batch\",cf=\"multiple\"} 1"));
+        assert!(body.contains(
+            "relayer_db_operation_bytes_bucket{operation=\"batch\",cf=\"multiple\",le=\"+Inf\"} 1"
+        ));
+        assert!(body.contains("relayer_db_records{cf=\"requests\"} 1"));
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::atomic::Ordering;
+
+use alloy::{network::TxSigner, primitives::Address, providers::WalletProvider};
+use log::{error, info};
+
+use types::{with_timeout, Result};
+
+use crate::{provider_rpc, BridgeContract, EVMClient, EvmError};
+
+impl EVMClient {
+    pub fn is_authorized_backend(&self) -> bool {
+        self.authorized_backend.load(Ordering::Relaxed)
+    }
+
+    /// Fails fast instead of sending transactions the bridge contract will revert
+    pub fn ensure_authorized_backend(&self) -> Result<(), EvmError> {
+        if self.is_authorized_backend() {
+            Ok(())
+        } else {
+            Err(EvmError::NotAuthorizedBackend(
+                self.signer.default_signer().address().to_string(),
+            ))
+        }
+    }
+}
+
+/// Compares the backend exposed by the bridge contract with the relayer signer,
+/// returns None when the contract doesn't expose it
+pub async fn check_backend_authorization(client: EVMClient) -> Result<Option<bool>> {
+    let provider = provider_rpc(client.clone())?;
+    let contract = BridgeContract::new(client.bridge_contract, provider.clone());
+
+    let backend =
+        match with_timeout("backend", client.timeouts.read, contract.backend().call()).await {
+            Ok(backend) => backend._0,
+            Err(e) => {
+                info!("EVM bridge contract doesn't expose its backend: {}", e);
+                return Ok(None);
+            }
+        };
+
+    Ok(Some(update_authorization(
+        &client,
+        backend,
+        provider.default_signer_address(),
+    )))
+}
+
+pub fn update_authorization(client: &EVMClient, backend: Address, signer: Address) -> bool {
+    let authorized = backend == signer;
+
+    if authorized {
+        info!("EVM relayer wallet is the bridge backend");
+    } else {
+        error!(
+            "UNAUTHORIZED: EVM relayer wallet {} is not the bridge backend {}, transactions will not be sent",
+            signer, backend
+        );
+    }
+    client
+        .authorized_backend
+        .store(authorized, Ordering::Relaxed);
+
+    authorized
+}
@@ -0,0 +1,521 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use alloy::{eips::BlockNumberOrTag, providers::Provider, rpc::types::Log};
+use futures_util::future::join_all;
+use log::{error, info, warn};
+use tokio::sync::{OnceCell, Semaphore};
+use types::{
+    with_timeout, BackfillProgress, BridgeError, CallContext, Chains, HistoricalEvent,
+    InputRequest, OutputResult, RequestId, Result, RpcLimiter, Timestamp, WrapCallContext,
+};
+
+use crate::{
+    bridge_events_filter, decode_bridge_log, provider_rpc, provider_type::MyProviderRPC, sort_logs,
+    BridgeContract, BridgeLog, EVMClient, NewRequest, TokenMinted,
+};
+
+/// Blocks of each eth_getLogs query of a backfill, under the range limit of most providers
+pub const BACKFILL_BLOCK_CHUNK: u64 = 2_000;
+
+/// Bridge event of a contract log. The owner of a NewRequest is not in the log, it is
+/// left empty to be read from the contract
+pub fn historical_event(log: &Log) -> Option<HistoricalEvent> {
+    let tx = log
+        .transaction_hash
+        .map(|hash| hash.to_string())
+        .unwrap_or_default();
+    let event = match decode_bridge_log(log) {
+        Ok(Some(BridgeLog::NewRequest(NewRequest {
+            requestId,
+            tokenContract,
+            tokenId,
+        }))) => HistoricalEvent::NewRequest {
+            request_id: parse_request_id(&requestId)?,
+            input: InputRequest {
+                contract_or_mint: tokenContract.to_string(),
+                token_id: tokenId.to_string(),
+                token_owner: String::new(),
+                origin_network: Chains::EVM,
+                destination_account: String::new(),
+            },
+            tx,
+            locked_at: None,
+        },
+        Ok(Some(BridgeLog::TokenMinted(TokenMinted {
+            requestId,
+            tokenContract,
+            to,
+            tokenId,
+        }))) => HistoricalEvent::TokenMinted {
+            request_id: parse_request_id(&requestId)?,
+            chain: Chains::EVM,
+            output: OutputResult {
+                detination_token_id_or_account: tokenId.to_string(),
+                detination_contract_id_or_mint: tokenContract.to_string(),
+            },
+            destination_account: Some(to.to_string()),
+            tx,
+        },
+        Ok(None) => return None,
+        Err(e) => {
+            error!("Skipping bridge contract log in backfill: {e}");
+            return None;
+        }
+    };
+    Some(event)
+}
+
+fn parse_request_id(request_id: &str) -> Option<RequestId> {
+    RequestId::parse(request_id)
+        .inspect_err(|e| error!("Skipping bridge contract log in backfill: {e}"))
+        .ok()
+}
+
+/// Lookups of the logs of a window running at once when not configured
+pub const DEFAULT_BACKFILL_PARALLELISM: usize = 8;
+/// Tries of each lookup of a log before the log is deferred
+pub const BACKFILL_LOOKUP_ATTEMPTS: u32 = 3;
+
+pub type LookupFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Chain reads completing the NewRequest logs of a backfill
+pub trait BackfillLookups: Send + Sync {
+    /// Account that asked for the bridge of `request_id`, not in its log
+    fn request_owner<'a>(&'a self, request_id: &'a RequestId) -> LookupFuture<'a, String>;
+    /// Time of the block `number`, from its header
+    fn block_time(&self, number: u64) -> LookupFuture<'_, Timestamp>;
+}
+
+/// Lookups on the bridge contract and the node of the client
+struct ContractLookups {
+    client: EVMClient,
+    provider: MyProviderRPC,
+}
+
+impl ContractLookups {
+    async fn read_owner(&self, request_id: &RequestId) -> Result<String> {
+        let contract = BridgeContract::new(self.client.bridge_contract, self.provider.clone());
+        let owner = with_timeout(
+            "requestOwner",
+            self.client.timeouts.read,
+            contract.requestOwner(request_id.to_string()).call(),
+        )
+        .await
+        .with_call_context(|| self.client.call_context("requestOwner", request_id))?;
+        Ok(owner._0.to_string())
+    }
+
+    async fn read_block_time(&self, number: u64) -> Result<Timestamp> {
+        let block = with_timeout(
+            "get_block_by_number",
+            self.client.timeouts.read,
+            self.provider
+                .get_block_by_number(BlockNumberOrTag::Number(number)),
+        )
+        .await
+        .with_call_context(|| {
+            CallContext::new(Chains::EVM, "get_block_by_number")
+                .contract(self.client.bridge_contract)
+        })?
+        .ok_or_else(|| BridgeError::Other(format!("EVM block {number} not found")))?;
+        Ok(Timestamp::from_secs(block.header.timestamp))
+    }
+}
+
+impl BackfillLookups for ContractLookups {
+    fn request_owner<'a>(&'a self, request_id: &'a RequestId) -> LookupFuture<'a, String> {
+        Box::pin(self.read_owner(request_id))
+    }
+
+    fn block_time(&self, number: u64) -> LookupFuture<'_, Timestamp> {
+        Box::pin(self.read_block_time(number))
+    }
+}
+
+/// Log whose lookups kept failing, left out of the events of its window
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeferredLog {
+    pub request_id: RequestId,
+    pub block: Option<u64>,
+    pub tx: String,
+    pub reason: String,
+}
+
+/// Bridge events of a backfill and the logs it deferred
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EvmHistory {
+    pub events: Vec<HistoricalEvent>,
+    pub deferred: Vec<DeferredLog>,
+}
+
+/// Times of the blocks of a window. The logs of a block share one header read, also when
+/// they are looked up at once
+#[derive(Default)]
+struct BlockTimes(Mutex<HashMap<u64, Arc<OnceCell<Timestamp>>>>);
+
+impl BlockTimes {
+    async fn get(
+        &self,
+        number: u64,
+        lookups: &dyn BackfillLookups,
+        limiter: &RpcLimiter,
+    ) -> Result<Timestamp> {
+        let cell = self.0.lock().unwrap().entry(number).or_default().clone();
+        cell.get_or_try_init(|| with_retries(limiter, || lookups.block_time(number)))
+            .await
+            .copied()
+    }
+}
+
+/// Runs the lookup until it succeeds, at most `BACKFILL_LOOKUP_ATTEMPTS` times. Each
+/// attempt waits for the limiter
+async fn with_retries<'a, T>(
+    limiter: &RpcLimiter,
+    lookup: impl Fn() -> LookupFuture<'a, T>,
+) -> Result<T> {
+    let mut attempt = 1;
+    loop {
+        limiter.acquire().await;
+        match lookup().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < BACKFILL_LOOKUP_ATTEMPTS => {
+                warn!("Backfill lookup failed on attempt {attempt}, retrying: {e}");
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Fills the owner and the lock time of a NewRequest event, the other events need no
+/// lookup
+async fn complete_event(
+    log: &Log,
+    mut event: HistoricalEvent,
+    lookups: &dyn BackfillLookups,
+    limiter: &RpcLimiter,
+    block_times: &BlockTimes,
+) -> Result<HistoricalEvent, DeferredLog> {
+    let HistoricalEvent::NewRequest {
+        request_id,
+        input,
+        tx,
+        locked_at,
+    } = &mut event
+    else {
+        return Ok(event);
+    };
+    let request_id: &RequestId = request_id;
+    let deferred = |e: BridgeError| DeferredLog {
+        request_id: request_id.clone(),
+        block: log.block_number,
+        tx: tx.clone(),
+        reason: e.to_string(),
+    };
+    input.token_owner = with_retries(limiter, || lookups.request_owner(request_id))
+        .await
+        .map_err(deferred)?;
+    if let Some(number) = log.block_number {
+        *locked_at = Some(
+            block_times
+                .get(number, lookups, limiter)
+                .await
+                .map_err(deferred)?,
+        );
+    }
+    Ok(event)
+}
+
+/// Bridge events of the logs in log order, the lookups of up to `parallelism` logs run
+/// at once. A log whose lookups keep failing is deferred without failing the others
+pub async fn complete_logs(
+    logs: &[Log],
+    lookups: &dyn BackfillLookups,
+    limiter: &RpcLimiter,
+    parallelism: usize,
+    progress: &BackfillProgress,
+) -> EvmHistory {
+    let slots = Semaphore::new(parallelism.max(1));
+    let block_times = BlockTimes::default();
+    let completions = logs
+        .iter()
+        .filter_map(|log| Some((log, historical_event(log)?)))
+        .map(|(log, event)| {
+            let (slots, block_times) = (&slots, &block_times);
+            async move {
+                let _slot = slots.acquire().await.expect("Backfill semaphore closed");
+                let completed = complete_event(log, event, lookups, limiter, block_times).await;
+                progress.record(completed.is_err());
+                completed
+            }
+        });
+
+    let mut history = EvmHistory::default();
+    for completed in join_all(completions).await {
+        match completed {
+            Ok(event) => history.events.push(event),
+            Err(deferred) => {
+                error!(
+                    "Backfill deferred the NewRequest of request {} in block {:?}, tx {}: {}",
+                    deferred.request_id, deferred.block, deferred.tx, deferred.reason
+                );
+                history.deferred.push(deferred);
+            }
+        }
+    }
+    history
+}
+
+/// Bridge events of the blocks `from_block..=to_block` in log order, each RPC request
+/// waits for the limiter. A log whose lookups fail is deferred, a failed log query fails
+/// the backfill
+pub async fn historical_events(
+    client: &EVMClient,
+    from_block: u64,
+    to_block: u64,
+    limiter: &RpcLimiter,
+    parallelism: usize,
+    progress: &BackfillProgress,
+) -> Result<EvmHistory> {
+    let provider = provider_rpc(client.clone())?;
+    let lookups = ContractLookups {
+        client: client.clone(),
+        provider: provider.clone(),
+    };
+    let mut history = EvmHistory::default();
+    let mut start = from_block;
+    while start <= to_block {
+        let end = to_block.min(start.saturating_add(BACKFILL_BLOCK_CHUNK - 1));
+        let filter = bridge_events_filter(&[client.bridge_contract])
+            .from_block(start)
+            .to_block(end);
+        limiter.acquire().await;
+        let mut logs = with_timeout("get_logs", client.timeouts.read, provider.get_logs(&filter))
+            .await
+            .with_call_context(|| {
+                CallContext::new(Chains::EVM, "get_logs").contract(client.bridge_contract)
+            })?;
+        sort_logs(&mut logs);
+
+        let window = complete_logs(&logs, &lookups, limiter, parallelism, progress).await;
+        info!(
+            "Backfill read EVM blocks {start} to {end}: {} logs, {} deferred, {} processed so far",
+            logs.len(),
+            window.deferred.len(),
+            progress.processed()
+        );
+        history.events.extend(window.events);
+        history.deferred.extend(window.deferred);
+        start = end.saturating_add(1);
+    }
+    Ok(history)
+}
+
+#[cfg(test)]
+mod backfill_test {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+        time::Duration,
+    };
+
+    use alloy::primitives::{Address, B256, U256};
+    use test_support::{new_request_log, token_minted_log, EVM_ACCOUNT, EVM_TOKEN_CONTRACT};
+    use types::{
+        BRequest, BackfillProgress, BridgeError, Chains, HistoricalEvent, RequestId, Result,
+        RpcLimiter, Timestamp,
+    };
+
+    use crate::{
+        complete_logs, historical_event, BackfillLookups, LookupFuture, BACKFILL_LOOKUP_ATTEMPTS,
+    };
+
+    /// Counts the lookups and how many run at once. The owner of `failing` can't be read,
+    /// the one of `flaky` on the first attempt only
+    #[derive(Default)]
+    struct CountingLookups {
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+        owner_reads: AtomicUsize,
+        block_reads: AtomicUsize,
+        failing: Option<RequestId>,
+        flaky: Mutex<Option<RequestId>>,
+    }
+
+    impl CountingLookups {
+        async fn lookup<T>(&self, value: Result<T>) -> Result<T> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            value
+        }
+    }
+
+    impl BackfillLookups for CountingLookups {
+        fn request_owner<'a>(&'a self, request_id: &'a RequestId) -> LookupFuture<'a, String> {
+            self.owner_reads.fetch_add(1, Ordering::SeqCst);
+            let mut flaky = self.flaky.lock().unwrap();
+            let fails = self.failing.as_ref() == Some(request_id)
+                || flaky.take_if(|id| *id == *request_id).is_some();
+            let owner = if fails {
+                Err(BridgeError::Other("connection reset".to_string()))
+            } else {
+                Ok(EVM_ACCOUNT.to_string())
+            };
+            Box::pin(self.lookup(owner))
+        }
+
+        fn block_time(&self, number: u64) -> LookupFuture<'_, Timestamp> {
+            self.block_reads.fetch_add(1, Ordering::SeqCst);
+            Box::pin(self.lookup(Ok(Timestamp::from_secs(1_700_000_000 + number))))
+        }
+    }
+
+    fn request_id(token_id: u64) -> RequestId {
+        BRequest::generate_id(EVM_TOKEN_CONTRACT, &token_id.to_string(), EVM_ACCOUNT)
+    }
+
+    /// Four NewRequest logs in each of the blocks 10, 11 and 12
+    fn window_logs() -> Vec<alloy::rpc::types::Log> {
+        let token_contract: Address = EVM_TOKEN_CONTRACT.parse().unwrap();
+        (0..12)
+            .map(|token_id| {
+                let position = (10 + token_id / 4, token_id % 4);
+                new_request_log(
+                    &request_id(token_id),
+                    token_contract,
+                    U256::from(token_id),
+                    position,
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_lookups_are_capped_and_share_block_headers() {
+        let lookups = CountingLookups::default();
+        let progress = BackfillProgress::default();
+
+        let history = complete_logs(
+            &window_logs(),
+            &lookups,
+            &RpcLimiter::per_second(0),
+            3,
+            &progress,
+        )
+        .await;
+
+        assert_eq!(lookups.max_running.load(Ordering::SeqCst), 3);
+        assert_eq!(lookups.owner_reads.load(Ordering::SeqCst), 12);
+        // One header read per block
+        assert_eq!(lookups.block_reads.load(Ordering::SeqCst), 3);
+        assert!(history.deferred.is_empty());
+        assert_eq!(history.events.len(), 12);
+        // In log order, completed
+        for (token_id, event) in history.events.iter().enumerate() {
+            let HistoricalEvent::NewRequest {
+                request_id: id,
+                input,
+                locked_at,
+                ..
+            } = event
+            else {
+                panic!("NewRequest event expected");
+            };
+            assert_eq!(*id, request_id(token_id as u64));
+            assert_eq!(input.token_owner, EVM_ACCOUNT);
+            let block = 10 + token_id as u64 / 4;
+            assert_eq!(
+                *locked_at,
+                Some(Timestamp::from_secs(1_700_000_000 + block))
+            );
+        }
+        assert_eq!((progress.processed(), progress.deferred()), (12, 0));
+    }
+
+    #[tokio::test]
+    async fn test_failing_log_deferred_without_its_window() {
+        let lookups = CountingLookups {
+            failing: Some(request_id(5)),
+            flaky: Mutex::new(Some(request_id(7))),
+            ..Default::default()
+        };
+        let progress = BackfillProgress::default();
+
+        let history = complete_logs(
+            &window_logs(),
+            &lookups,
+            &RpcLimiter::per_second(0),
+            4,
+            &progress,
+        )
+        .await;
+
+        assert_eq!(history.events.len(), 11);
+        assert!(history
+            .events
+            .iter()
+            .all(|event| *event.request_id() != request_id(5)));
+        assert_eq!(history.deferred.len(), 1);
+        let deferred = &history.deferred[0];
+        assert_eq!(deferred.request_id, request_id(5));
+        assert_eq!(deferred.block, Some(11));
+        assert!(deferred.reason.contains("connection reset"));
+        // Every attempt on the failing log, a retry of the flaky one
+        assert_eq!(
+            lookups.owner_reads.load(Ordering::SeqCst),
+            10 + BACKFILL_LOOKUP_ATTEMPTS as usize + 2
+        );
+        assert_eq!((progress.processed(), progress.deferred()), (12, 1));
+    }
+
+    #[test]
+    fn test_logs_decode_to_historical_events() {
+        let token_contract: Address = EVM_TOKEN_CONTRACT.parse().unwrap();
+        let to: Address = EVM_ACCOUNT.parse().unwrap();
+        let id = BRequest::generate_id(EVM_TOKEN_CONTRACT, "42", EVM_ACCOUNT);
+
+        let mut log = new_request_log(&id, token_contract, U256::from(42), (10, 0));
+        log.transaction_hash = Some(B256::repeat_byte(1));
+        let Some(HistoricalEvent::NewRequest {
+            request_id,
+            input,
+            tx,
+            ..
+        }) = historical_event(&log)
+        else {
+            panic!("NewRequest log not decoded");
+        };
+        assert_eq!(request_id, id);
+        assert_eq!(input.contract_or_mint, token_contract.to_string());
+        assert_eq!(input.token_id, "42");
+        assert_eq!(input.origin_network, Chains::EVM);
+        assert_eq!(tx, B256::repeat_byte(1).to_string());
+
+        let log = token_minted_log(&id, token_contract, to, U256::from(7), (11, 0));
+        let Some(HistoricalEvent::TokenMinted {
+            chain,
+            output,
+            destination_account,
+            ..
+        }) = historical_event(&log)
+        else {
+            panic!("TokenMinted log not decoded");
+        };
+        assert_eq!(chain, Chains::EVM);
+        assert_eq!(output.detination_token_id_or_account, "7");
+        assert_eq!(destination_account, Some(to.to_string()));
+
+        // Malformed request ids are skipped
+        let log = new_request_log("0xrequest1", token_contract, U256::from(1), (12, 0));
+        assert_eq!(historical_event(&log), None);
+    }
+}
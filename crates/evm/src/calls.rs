@@ -1,17 +1,23 @@
 use alloy::{
+    consensus::Transaction as _,
+    eips::BlockNumberOrTag,
     primitives::{Address, U256},
-    providers::Provider,
+    providers::{Provider, WalletProvider},
     rpc::types::Transaction,
     sol,
 };
 
-use eyre::Result;
 use log::info;
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 use storage::db::Database;
-use types::{MessageMint, TxMessage};
+use types::{
+    custody_step, parse_token_id, stored_lock_state, with_timeout, BRequest, BridgeError,
+    CallContext, ChainHeadReceiver, Chains, CustodyStep, InputRequest, MessageMint,
+    MissingUriPolicy, OnChainTx, RequestId, Result, RpcLimiter, Status, Timestamp, TxMessage,
+    TxState, Wei, WrapCallContext,
+};
 
-use crate::{provider_rpc, EVMClient};
+use crate::{minted_token_from_logs, provider_rpc, BridgeContract, EVMClient, MintedToken};
 
 sol! {
     #[sol(rpc)]
@@ -21,41 +27,124 @@ sol! {
     }
 }
 
-pub async fn check_token_owner(client: EVMClient, db: &Database, request_id: &str) -> Result<()> {
+pub async fn check_token_owner(
+    client: EVMClient,
+    db: &Database,
+    head: &ChainHeadReceiver,
+    request_id: &str,
+) -> Result<()> {
     let provider = provider_rpc(client.clone())?;
     if let Ok(Some(mut request)) = types::request_data(&request_id, db) {
-        let token_contract = Address::from_str(&request.input.contract_or_mint)?;
-        let token_id: U256 = request.input.token_id.parse().expect("Invalid U256 string");
+        // Custody of the contract the token was locked in
+        let client = client.for_request(&request)?;
+        // Lock event seen before the processor recorded its tx, the sweep checks the
+        // request once it is RequestReceived
+        if request.status == Status::Initializing {
+            info!("Lock of request {request_id} not recorded yet, waiting for the sweep");
+            return Ok(());
+        }
+        let token_contract =
+            Address::from_str(&request.input.contract_or_mint).map_err(BridgeError::from_error)?;
+        let token_id = parse_token_id(&request.input.token_id).map_err(BridgeError::from)?;
 
-        let contract = ERC721Token::new(token_contract, provider);
-        let token_owner = contract.ownerOf(token_id).call().await?._0;
+        let contract = ERC721Token::new(token_contract, provider.clone());
+        let token_owner = with_timeout(
+            "ownerOf",
+            client.timeouts.read,
+            contract.ownerOf(token_id).call(),
+        )
+        .await
+        .with_call_context(|| {
+            CallContext::new(Chains::EVM, "ownerOf")
+                .request(request_id)
+                .contract(token_contract)
+        })?
+        ._0;
 
-        if token_owner != client.bridge_contract {
-            let _ = request.cancel(db);
+        let in_custody = token_owner == client.bridge_contract;
+        // The block of the lock log recorded by the listener spares reading the lock again,
+        // only its depth under the head published by the head watcher is checked
+        let lock_tx = match (request.lock_block, request.tx_hashes.first()) {
+            _ if in_custody => None,
+            (Some(lock_block), _) => Some(stored_lock_state(lock_block, head.borrow().height)),
+            (None, Some(lock_tx)) => Some(get_tx_state(client.clone(), lock_tx, request_id).await?),
+            (None, None) => None,
+        };
+        let now = client.clock.now();
+        let age = request.created_at.elapsed_until(now);
+        match custody_step(in_custody, lock_tx.as_ref(), age) {
+            CustodyStep::InCustody => {}
+            CustodyStep::Wait => {
+                info!("Token of request {request_id} not in custody yet, waiting for the lock");
+                return Ok(());
+            }
+            CustodyStep::Cancel(reason) => {
+                info!("Canceling request {request_id}, {reason:?}");
+                request.cancel_with_reason(db, reason, now)?;
+                return Ok(());
+            }
         }
-        request.update_state(db)?;
+        if types::reject_custody_conflict(&mut request, db, now)? {
+            return Ok(());
+        }
+        request.update_state(db, now)?;
 
-        let token_metadata = get_token_metadata(client.clone(), token_contract, token_id)
-            .await
-            .unwrap();
+        // The token is in custody, a request without URI waits in TokenReceived
+        let Some(token_metadata) =
+            resolve_token_uri(provider, &client, db, &mut request, now).await?
+        else {
+            return Ok(());
+        };
 
         client
             .tx_channel
-            .send(TxMessage {
-                accion: types::Function::Mint,
-                mint_data: Some(MessageMint {
-                    request_id: request_id.to_string(),
-                    token_metadata: token_metadata,
-                }),
-                request_data: None,
-            })
-            .await
-            .unwrap();
+            .send(TxMessage::Mint(MessageMint {
+                request_id: request.id.clone(),
+                token_metadata,
+                trace_context: request.trace_context.clone(),
+            }))
+            .await?;
     }
 
     Ok(())
 }
 
+/// Records the custody of a NewRequest event without local request, the token owner is
+/// read from the request record of the bridge contract of the client, the one of the event
+pub async fn record_orphan_request(
+    client: EVMClient,
+    db: &Database,
+    request_id: &RequestId,
+    token_contract: Address,
+    token_id: U256,
+) -> Result<()> {
+    let provider = provider_rpc(client.clone())?;
+    let contract = BridgeContract::new(client.bridge_contract, provider);
+    let token_owner = with_timeout(
+        "requestOwner",
+        client.timeouts.read,
+        contract.requestOwner(request_id.to_string()).call(),
+    )
+    .await
+    .with_call_context(|| client.call_context("requestOwner", request_id))?
+    ._0;
+
+    types::record_orphan(
+        db,
+        request_id,
+        InputRequest {
+            contract_or_mint: token_contract.to_string(),
+            token_id: token_id.to_string(),
+            token_owner: token_owner.to_string(),
+            origin_network: Chains::EVM,
+            destination_account: String::new(),
+        },
+        client.bridge_addresses(),
+        client.clock.now(),
+    )?;
+    Ok(())
+}
+
 pub async fn get_token_metadata(
     client: EVMClient,
     token_contract: Address,
@@ -64,7 +153,14 @@ pub async fn get_token_metadata(
     let provider = provider_rpc(client.clone())?;
 
     let contract = ERC721Token::new(token_contract, provider);
-    let token_metadata = contract.tokenURI(token_id).call().await?._0;
+    let token_metadata = with_timeout(
+        "tokenURI",
+        client.timeouts.read,
+        contract.tokenURI(token_id).call(),
+    )
+    .await
+    .with_call_context(|| CallContext::new(Chains::EVM, "tokenURI").contract(token_contract))?
+    ._0;
 
     info!(
         "Read token contract from evm {}, with token Id {} and metadata {}",
@@ -74,10 +170,628 @@ pub async fn get_token_metadata(
     Ok(token_metadata)
 }
 
+/// Holder of a token, from `ownerOf` of its contract
+pub async fn read_token_owner<P: Provider>(
+    provider: P,
+    timeout: Duration,
+    token_contract: Address,
+    token_id: U256,
+) -> Result<Address> {
+    let owner = with_timeout(
+        "ownerOf",
+        timeout,
+        ERC721Token::new(token_contract, &provider)
+            .ownerOf(token_id)
+            .call(),
+    )
+    .await
+    .with_call_context(|| CallContext::new(Chains::EVM, "ownerOf").contract(token_contract))?
+    ._0;
+    Ok(owner)
+}
+
+/// Same as `read_token_owner` through the client provider
+pub async fn get_token_owner(
+    client: EVMClient,
+    token_contract: Address,
+    token_id: U256,
+) -> Result<Address> {
+    let provider = provider_rpc(client.clone())?;
+    read_token_owner(provider, client.timeouts.read, token_contract, token_id).await
+}
+
+/// URI read from an origin token contract
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenUri {
+    Uri(String),
+    /// `tokenURI` reverted or returned an empty string, with the reason
+    Unavailable(String),
+}
+
+/// Reverts of a view call, nodes answer them with an error response
+pub(crate) fn reverted(err: &alloy::contract::Error) -> bool {
+    match err {
+        alloy::contract::Error::TransportError(e) => e
+            .as_error_resp()
+            .is_some_and(|payload| payload.message.contains("revert")),
+        alloy::contract::Error::ZeroData(..) => true,
+        _ => false,
+    }
+}
+
+/// Reads the URI of an origin token. Reverts and empty URIs are `Unavailable`, other
+/// failures, like the node not answering, are errors
+pub async fn read_token_uri<P: Provider>(
+    provider: P,
+    timeout: Duration,
+    token_contract: Address,
+    token_id: U256,
+) -> Result<TokenUri> {
+    let contract = ERC721Token::new(token_contract, provider);
+    let context = || CallContext::new(Chains::EVM, "tokenURI").contract(token_contract);
+    let result = with_timeout("tokenURI", timeout, async {
+        Ok::<_, BridgeError>(contract.tokenURI(token_id).call().await)
+    })
+    .await
+    .with_call_context(context)?;
+
+    match result {
+        Ok(uri) if uri._0.trim().is_empty() => Ok(TokenUri::Unavailable(format!(
+            "tokenURI of token {token_id} of {token_contract} is empty"
+        ))),
+        Ok(uri) => Ok(TokenUri::Uri(uri._0)),
+        Err(e) if reverted(&e) => Ok(TokenUri::Unavailable(format!(
+            "tokenURI of token {token_id} of {token_contract} reverted: {e}"
+        ))),
+        Err(e) => Err(e).with_call_context(context),
+    }
+}
+
+/// URI to mint for an EVM origin request. An unavailable URI is replaced by the
+/// placeholder of the client, or parks the request until a later retry and gives None
+pub async fn resolve_token_uri<P: Provider>(
+    provider: P,
+    client: &EVMClient,
+    db: &Database,
+    request: &mut BRequest,
+    now: Timestamp,
+) -> Result<Option<String>> {
+    let token_contract =
+        Address::from_str(&request.input.contract_or_mint).map_err(BridgeError::from_error)?;
+    let token_id = parse_token_id(&request.input.token_id).map_err(BridgeError::from)?;
+
+    match read_token_uri(provider, client.timeouts.read, token_contract, token_id).await? {
+        TokenUri::Uri(uri) => {
+            request.clear_metadata_pending(db)?;
+            Ok(Some(uri))
+        }
+        TokenUri::Unavailable(reason) => match &client.missing_uri {
+            MissingUriPolicy::Placeholder(uri) => {
+                info!("{reason}, minting request {} with {uri}", request.id);
+                Ok(Some(uri.clone()))
+            }
+            MissingUriPolicy::Retry => {
+                request.park_metadata(db, &reason, now)?;
+                Ok(None)
+            }
+        },
+    }
+}
+
+/// Same as `resolve_token_uri` through the RPC node of the client
+pub async fn token_uri_for_mint(
+    client: EVMClient,
+    db: &Database,
+    request: &mut BRequest,
+    now: Timestamp,
+) -> Result<Option<String>> {
+    let provider = provider_rpc(client.clone())?;
+    resolve_token_uri(provider, &client, db, request, now).await
+}
+
+/// Token minted by a mint transaction, read from the TokenMinted log of its receipt
+pub async fn get_minted_token(
+    client: EVMClient,
+    tx: &str,
+    request_id: &str,
+) -> Result<Option<MintedToken>> {
+    let provider = provider_rpc(client.clone())?;
+    let tx_hash = tx.parse().map_err(BridgeError::from_error)?;
+
+    let receipt = client
+        .observed(
+            "get_transaction_receipt",
+            client.timeouts.read,
+            provider.get_transaction_receipt(tx_hash),
+        )
+        .await
+        .with_call_context(|| client.call_context("get_transaction_receipt", request_id))?;
+    Ok(receipt.and_then(|receipt| {
+        minted_token_from_logs(receipt.inner.logs(), client.bridge_contract, request_id)
+    }))
+}
+
+/// Whether the wrapped token of a Solana origin mint exists on the token contract of the
+/// bridge. `ownerOf` reverts for the tokens never minted, other failures are errors
+pub async fn wrapped_token_minted<P: Provider>(
+    provider: P,
+    timeout: Duration,
+    bridge_contract: Address,
+    origin_mint: &str,
+) -> Result<bool> {
+    let token_id = U256::from_be_slice(
+        &bs58::decode(origin_mint)
+            .into_vec()
+            .map_err(BridgeError::from_error)?,
+    );
+    let token_contract = with_timeout(
+        "tokenAddress",
+        timeout,
+        BridgeContract::new(bridge_contract, &provider)
+            .tokenAddress()
+            .call(),
+    )
+    .await
+    .with_call_context(|| CallContext::new(Chains::EVM, "tokenAddress").contract(bridge_contract))?
+    ._0;
+
+    let context = || CallContext::new(Chains::EVM, "ownerOf").contract(token_contract);
+    let owner = with_timeout("ownerOf", timeout, async {
+        Ok::<_, BridgeError>(
+            ERC721Token::new(token_contract, &provider)
+                .ownerOf(token_id)
+                .call()
+                .await,
+        )
+    })
+    .await
+    .with_call_context(context)?;
+    match owner {
+        Ok(_) => Ok(true),
+        Err(e) if reverted(&e) => Ok(false),
+        Err(e) => Err(e).with_call_context(context),
+    }
+}
+
+/// Same as `wrapped_token_minted` for the request, on the bridge it was created against
+pub async fn destination_minted(client: EVMClient, request: &BRequest) -> Result<bool> {
+    let client = client.for_request(request)?;
+    let provider = provider_rpc(client.clone())?;
+    wrapped_token_minted(
+        provider,
+        client.timeouts.read,
+        client.bridge_contract,
+        &request.input.contract_or_mint,
+    )
+    .await
+}
+
+/// Fee paid by the relayer for a transaction, None until it is mined
+pub async fn get_transaction_fee(
+    client: EVMClient,
+    tx: &str,
+    request_id: &str,
+) -> Result<Option<Wei>> {
+    let provider = provider_rpc(client.clone())?;
+    let tx_hash = tx.parse().map_err(BridgeError::from_error)?;
+
+    let receipt = client
+        .observed(
+            "get_transaction_receipt",
+            client.timeouts.read,
+            provider.get_transaction_receipt(tx_hash),
+        )
+        .await
+        .with_call_context(|| client.call_context("get_transaction_receipt", request_id))?;
+    Ok(receipt.map(|receipt| {
+        Wei::new(u128::from(receipt.gas_used).saturating_mul(receipt.effective_gas_price))
+    }))
+}
+
+/// Transaction recorded on a request as the tx audit reads it from its receipt, missing
+/// until mined. The request waits for the limiter
+pub async fn recorded_tx(client: EVMClient, tx: &str, limiter: &RpcLimiter) -> Result<OnChainTx> {
+    let provider = provider_rpc(client.clone())?;
+    let tx_hash = tx.parse().map_err(BridgeError::from_error)?;
+
+    limiter.acquire().await;
+    let receipt = client
+        .observed(
+            "get_transaction_receipt",
+            client.timeouts.read,
+            provider.get_transaction_receipt(tx_hash),
+        )
+        .await
+        .with_call_context(|| {
+            CallContext::new(Chains::EVM, "get_transaction_receipt").contract(tx)
+        })?;
+    Ok(match receipt {
+        Some(receipt) => OnChainTx::Found {
+            success: receipt.status(),
+            called: receipt.to.iter().map(Address::to_string).collect(),
+        },
+        None => OnChainTx::Missing,
+    })
+}
+
 pub async fn get_transaction_data(client: EVMClient, tx: &str) -> Result<Option<Transaction>> {
     let provider = provider_rpc(client.clone())?;
-    let tx_hash = tx.parse()?;
+    let tx_hash = tx.parse().map_err(BridgeError::from_error)?;
 
-    let data = provider.get_transaction_by_hash(tx_hash).await?;
+    let data = client
+        .observed(
+            "get_transaction_by_hash",
+            client.timeouts.read,
+            provider.get_transaction_by_hash(tx_hash),
+        )
+        .await?;
     return Ok(data);
 }
+
+/// State of a transaction from the status of its receipt, the max fee of the transaction
+/// when the node has it and the latest base fee
+pub fn classify_tx(
+    receipt_status: Option<bool>,
+    pending_max_fee: Option<u128>,
+    base_fee_per_gas: Option<u128>,
+) -> TxState {
+    match (receipt_status, pending_max_fee) {
+        (Some(success), _) => TxState::Mined { success },
+        (None, Some(max_fee)) => TxState::Pending {
+            max_fee_per_gas: Some(max_fee),
+            base_fee_per_gas,
+        },
+        (None, None) => TxState::Dropped,
+    }
+}
+
+/// Whether a relayer transaction is mined, waiting in the mempool or dropped
+pub async fn get_tx_state(client: EVMClient, tx: &str, request_id: &str) -> Result<TxState> {
+    let provider = provider_rpc(client.clone())?;
+    let tx_hash = tx.parse().map_err(BridgeError::from_error)?;
+
+    let receipt = client
+        .observed(
+            "get_transaction_receipt",
+            client.timeouts.read,
+            provider.get_transaction_receipt(tx_hash),
+        )
+        .await
+        .with_call_context(|| client.call_context("get_transaction_receipt", request_id))?;
+    if let Some(receipt) = receipt {
+        return Ok(classify_tx(Some(receipt.status()), None, None));
+    }
+
+    let transaction = client
+        .observed(
+            "get_transaction_by_hash",
+            client.timeouts.read,
+            provider.get_transaction_by_hash(tx_hash),
+        )
+        .await
+        .with_call_context(|| client.call_context("get_transaction_by_hash", request_id))?;
+    let Some(transaction) = transaction else {
+        return Ok(classify_tx(None, None, None));
+    };
+
+    let latest = client
+        .observed(
+            "get_block_by_number",
+            client.timeouts.read,
+            provider.get_block_by_number(BlockNumberOrTag::Latest),
+        )
+        .await
+        .with_call_context(|| client.call_context("get_block_by_number", request_id))?;
+    let base_fee = latest
+        .and_then(|block| block.header.base_fee_per_gas)
+        .map(u128::from);
+    Ok(classify_tx(
+        None,
+        Some(transaction.max_fee_per_gas()),
+        base_fee,
+    ))
+}
+
+/// Balance of the relayer wallet, paying the gas of the bridge transactions
+pub async fn signer_balance(client: EVMClient) -> Result<Wei> {
+    let provider = provider_rpc(client.clone())?;
+    let balance = client
+        .observed(
+            "get_balance",
+            client.timeouts.read,
+            provider.get_balance(provider.default_signer_address()),
+        )
+        .await?;
+    Ok(Wei::new(balance.try_into().unwrap_or(u128::MAX)))
+}
+
+/// State of the EVM node seen by the relayer, checked when starting in dev mode
+#[derive(Debug, Clone)]
+pub struct DevChainStatus {
+    pub chain_id: u64,
+    pub signer: Address,
+    pub signer_balance: Wei,
+    /// Code found at the bridge contract address
+    pub bridge_deployed: bool,
+}
+
+pub async fn dev_chain_status(client: EVMClient) -> Result<DevChainStatus> {
+    let provider = provider_rpc(client.clone())?;
+    let signer = provider.default_signer_address();
+
+    let chain_id = with_timeout(
+        "get_chain_id",
+        client.timeouts.read,
+        provider.get_chain_id(),
+    )
+    .await?;
+    let balance = signer_balance(client.clone()).await?;
+    let code = with_timeout(
+        "get_code_at",
+        client.timeouts.read,
+        provider.get_code_at(client.bridge_contract),
+    )
+    .await?;
+
+    Ok(DevChainStatus {
+        chain_id,
+        signer,
+        signer_balance: balance,
+        bridge_deployed: !code.is_empty(),
+    })
+}
+
+#[cfg(test)]
+mod calls_test {
+    use std::{str::FromStr, time::Duration};
+
+    use alloy::{
+        primitives::{Address, Bytes, U256},
+        providers::{Provider, ProviderBuilder},
+        sol_types::SolValue,
+        transport::mock::Asserter,
+    };
+    use test_support::{request_in_status, solana_key, test_db, EVM_ACCOUNT, EVM_TOKEN_CONTRACT};
+    use types::{
+        request_data, tx_channel, BrandingConfig, CallContext, Chains, ChannelMetrics,
+        MissingUriPolicy, RpcTimeouts, Secret, Status, Timestamp, TxState, UriPolicy,
+    };
+
+    use crate::{
+        classify_tx, evm_initialize, get_minted_token, read_token_uri, resolve_token_uri,
+        wrapped_token_minted, EVMClient, FeeSettings, GasLimits, TokenUri,
+    };
+
+    const PLACEHOLDER: &str = "ipfs://placeholder.json";
+
+    fn client(missing_uri: MissingUriPolicy) -> EVMClient {
+        let (tx, _rx) = tx_channel(Chains::SOLANA, 1, &ChannelMetrics::default());
+        // Nothing listens on the port, the calls fail with connection refused
+        evm_initialize(
+            "http://127.0.0.1:1",
+            "ws://127.0.0.1:1",
+            &Secret::from("0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"),
+            "0x5FbDB2315678afecb367f032d93F642f64180aa3",
+            tx,
+            "",
+            UriPolicy::default(),
+            None,
+            BrandingConfig::default(),
+            RpcTimeouts::default(),
+            GasLimits::default(),
+            FeeSettings::default(),
+            missing_uri,
+            false,
+        )
+        .unwrap()
+    }
+
+    /// Token contract answering the queued `tokenURI` responses
+    fn mocked_token(asserter: &Asserter) -> impl Provider {
+        ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .on_mocked_client(asserter.clone())
+    }
+
+    fn push_token_uri(asserter: &Asserter, uri: &str) {
+        asserter.push_success(&Bytes::from((uri.to_string(),).abi_encode_params()));
+    }
+
+    fn push_revert(asserter: &Asserter) {
+        asserter.push_failure_msg("execution reverted: URI query for unrevealed token");
+    }
+
+    #[tokio::test]
+    async fn test_token_uri_revert_and_empty_are_unavailable() {
+        let asserter = Asserter::new();
+        let contract = Address::from_str(EVM_TOKEN_CONTRACT).unwrap();
+        let read = |asserter: &Asserter| {
+            read_token_uri(
+                mocked_token(asserter),
+                Duration::from_secs(1),
+                contract,
+                U256::from(1),
+            )
+        };
+
+        push_revert(&asserter);
+        let TokenUri::Unavailable(reason) = read(&asserter).await.unwrap() else {
+            panic!("reverted tokenURI read as available");
+        };
+        assert!(reason.contains("reverted"), "{reason}");
+
+        push_token_uri(&asserter, "");
+        let TokenUri::Unavailable(reason) = read(&asserter).await.unwrap() else {
+            panic!("empty tokenURI read as available");
+        };
+        assert!(reason.contains("empty"), "{reason}");
+
+        push_token_uri(&asserter, "ipfs://token/1.json");
+        assert_eq!(
+            read(&asserter).await.unwrap(),
+            TokenUri::Uri("ipfs://token/1.json".to_string())
+        );
+
+        // Node failures are not a missing URI, they are retried as errors
+        asserter.push_failure_msg("header not found");
+        let err = read(&asserter).await.unwrap_err();
+        assert_eq!(CallContext::of(&err).unwrap().operation, "tokenURI");
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_uri_parks_request_until_revealed() {
+        let db = test_db();
+        let client = client(MissingUriPolicy::Retry);
+        let mut request = request_in_status(Chains::EVM, "1", Status::TokenReceived);
+        request.save(&db).unwrap();
+        let asserter = Asserter::new();
+        let now = Timestamp::from_secs(1_700_000_000);
+
+        push_revert(&asserter);
+        let uri = resolve_token_uri(mocked_token(&asserter), &client, &db, &mut request, now)
+            .await
+            .unwrap();
+        assert_eq!(uri, None);
+
+        push_token_uri(&asserter, "");
+        let retry_at = request.metadata_pending.unwrap().retry_at;
+        let uri = resolve_token_uri(
+            mocked_token(&asserter),
+            &client,
+            &db,
+            &mut request,
+            retry_at,
+        )
+        .await
+        .unwrap();
+        assert_eq!(uri, None);
+
+        let parked = request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(parked.status, Status::TokenReceived);
+        let pending = parked.metadata_pending.unwrap();
+        assert_eq!(pending.attempts, 2);
+        assert_eq!(
+            pending.retry_at,
+            retry_at.saturating_add(Duration::from_secs(120))
+        );
+        assert!(parked.last_error.unwrap().contains("empty"));
+
+        // Revealed, the URI is minted and the request is not parked anymore
+        push_token_uri(&asserter, "ipfs://token/1.json");
+        let uri = resolve_token_uri(
+            mocked_token(&asserter),
+            &client,
+            &db,
+            &mut request,
+            pending.retry_at,
+        )
+        .await
+        .unwrap();
+        assert_eq!(uri.as_deref(), Some("ipfs://token/1.json"));
+        let revealed = request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(revealed.metadata_pending, None);
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_uri_mints_placeholder() {
+        let db = test_db();
+        let client = client(MissingUriPolicy::Placeholder(PLACEHOLDER.to_string()));
+        let mut request = request_in_status(Chains::EVM, "1", Status::TokenReceived);
+        request.save(&db).unwrap();
+        let asserter = Asserter::new();
+
+        push_revert(&asserter);
+        let uri = resolve_token_uri(
+            mocked_token(&asserter),
+            &client,
+            &db,
+            &mut request,
+            Timestamp::now(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(uri.as_deref(), Some(PLACEHOLDER));
+        assert_eq!(request.metadata_pending, None);
+    }
+
+    #[tokio::test]
+    async fn test_failing_call_error_names_request_and_operation() {
+        let client = client(MissingUriPolicy::default());
+
+        let tx_hash = format!("0x{}", "ab".repeat(32));
+        let err = get_minted_token(client, &tx_hash, "0xrequest42")
+            .await
+            .unwrap_err();
+
+        let formatted = err.to_string();
+        assert!(formatted.contains("0xrequest42"), "{formatted}");
+        assert!(formatted.contains("get_transaction_receipt"), "{formatted}");
+        let context = CallContext::of(&err).unwrap();
+        assert_eq!(context.chain, Chains::EVM);
+        assert_eq!(
+            context.contract.as_deref(),
+            Some("0x5FbDB2315678afecb367f032d93F642f64180aa3")
+        );
+    }
+
+    #[test]
+    fn test_classify_mined_pending_and_dropped() {
+        // The receipt decides over the mempool
+        assert_eq!(
+            classify_tx(Some(true), Some(10), Some(20)),
+            TxState::Mined { success: true }
+        );
+        assert_eq!(
+            classify_tx(Some(false), None, None),
+            TxState::Mined { success: false }
+        );
+
+        let pending = classify_tx(None, Some(30_000_000_000), Some(45_000_000_000));
+        assert_eq!(
+            pending,
+            TxState::Pending {
+                max_fee_per_gas: Some(30_000_000_000),
+                base_fee_per_gas: Some(45_000_000_000),
+            }
+        );
+        assert!(pending.priced_out());
+        assert!(!classify_tx(None, Some(50_000_000_000), Some(45_000_000_000)).priced_out());
+
+        assert_eq!(
+            classify_tx(None, None, Some(45_000_000_000)),
+            TxState::Dropped
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wrapped_token_minted_from_its_owner() {
+        let asserter = Asserter::new();
+        let bridge = Address::from_str("0x5FbDB2315678afecb367f032d93F642f64180aa3").unwrap();
+        let wrapped = Address::from_str(EVM_TOKEN_CONTRACT).unwrap();
+        let origin_mint = solana_key(1).to_string();
+        let minted = || {
+            wrapped_token_minted(
+                mocked_token(&asserter),
+                Duration::from_secs(1),
+                bridge,
+                &origin_mint,
+            )
+        };
+
+        // tokenAddress then ownerOf
+        asserter.push_success(&Bytes::from((wrapped,).abi_encode_params()));
+        asserter.push_success(&Bytes::from(
+            (Address::from_str(EVM_ACCOUNT).unwrap(),).abi_encode_params(),
+        ));
+        assert!(minted().await.unwrap());
+
+        asserter.push_success(&Bytes::from((wrapped,).abi_encode_params()));
+        asserter.push_failure_msg("execution reverted: ERC721: invalid token ID");
+        assert!(!minted().await.unwrap());
+
+        // A node failing is not a missing token
+        asserter.push_success(&Bytes::from((wrapped,).abi_encode_params()));
+        asserter.push_failure_msg("header not found");
+        assert!(minted().await.is_err());
+    }
+}
@@ -3,58 +3,310 @@ use alloy::{
     primitives::Address,
     providers::{Provider, ProviderBuilder, WsConnect},
     signers::local::PrivateKeySigner,
+    transports::TransportError,
+};
+use arc_swap::ArcSwap;
+use log::error;
+use std::{
+    future::IntoFuture,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use types::{
+    system_clock, with_timeout, BRequest, BrandingConfig, BridgeAddresses, BridgeDeployment,
+    BridgeError, CallContext, Chains, MetadataPinning, MissingUriPolicy, Result, RpcHealth,
+    RpcTimeouts, Secret, SharedClock, SharedEventCursor, Timestamp, TxSender, UriPolicy,
 };
-use eyre::Result;
-use std::{str::FromStr, sync::Arc};
-use tokio::sync::mpsc::Sender;
-use types::TxMessage;
 
-use crate::provider_type::{MyProviderRPC, MyProviderWS};
+use crate::{
+    provider_type::{MyProviderRPC, MyProviderWS},
+    DelegateRegistry, FeeSettings, GasLimits, GasPolicy, SharedFeeMode,
+};
 
-#[derive(Clone)]
-pub struct EVMClient {
+/// Node URLs the client calls
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvmEndpoints {
     pub rpc: String,
     pub ws: String,
+}
+
+#[derive(Clone)]
+pub struct EVMClient {
+    /// Shared by the clones of the client, the tx processors and listeners included, all
+    /// of them move to the endpoints adopted by one
+    pub endpoints: Arc<ArcSwap<EvmEndpoints>>,
     pub signer: Arc<EthereumWallet>,
+    /// Contract the client calls, the current one of the deployment unless the client is
+    /// the one of a request created before an upgrade
     pub bridge_contract: Address,
-    pub tx_channel: Sender<TxMessage>,
+    pub tx_channel: TxSender,
     pub block_explorer: String,
+    pub uri_policy: UriPolicy,
+    /// Metadata is pinned before minting when set
+    pub metadata_pinning: Option<MetadataPinning>,
+    /// Provenance appended to the minted token URIs
+    pub branding: BrandingConfig,
+    /// Mint of origin tokens whose tokenURI reverts or is empty
+    pub missing_uri: MissingUriPolicy,
+    pub timeouts: RpcTimeouts,
+    /// Cleared when the bridge contract backend differs from the signer
+    pub authorized_backend: Arc<AtomicBool>,
+    /// Local test node
+    pub dev_mode: bool,
+    /// Gas limit caps of the bridge contract calls
+    pub gas_policy: GasPolicy,
+    pub fee_settings: FeeSettings,
+    /// EIP-1559 or legacy transactions, from the base fee of the latest block
+    pub fee_mode: SharedFeeMode,
+    /// Block of the last bridge event processed
+    pub event_cursor: SharedEventCursor,
+    /// Transactions resent with a resynced nonce or found already known to the node
+    pub nonce_resyncs: Arc<AtomicU64>,
+    /// Delegations accepted from the holders of the tokens bridged by their delegates
+    pub delegate_registry: Option<DelegateRegistry>,
+    /// Outcomes of the calls to the endpoints, shared by the clones of the client
+    pub rpc_health: RpcHealth,
+    /// Bridge addresses of the new requests and the ones they replaced, None when the
+    /// client only knows `bridge_contract`
+    pub bridge_deployment: Option<Arc<BridgeDeployment>>,
+    /// Wall clock the request times are read from, shared with the relayer state
+    pub clock: SharedClock,
+}
+
+impl EVMClient {
+    /// Context of a bridge contract call made for `request_id`
+    pub fn call_context(&self, operation: &str, request_id: &str) -> CallContext {
+        CallContext::new(Chains::EVM, operation)
+            .request(request_id)
+            .contract(self.bridge_contract)
+    }
+
+    pub fn rpc_url(&self) -> String {
+        self.endpoints.load().rpc.clone()
+    }
+
+    pub fn ws_url(&self) -> String {
+        self.endpoints.load().ws.clone()
+    }
+
+    /// Call to the current RPC endpoint, recorded in its health. Error responses of the
+    /// node are answers, transport errors and timeouts are failures
+    pub async fn observed<T, F>(&self, operation: &str, after: Duration, call: F) -> Result<T>
+    where
+        F: IntoFuture<Output = Result<T, TransportError>>,
+    {
+        self.rpc_health
+            .observe(
+                &Chains::EVM,
+                &self.rpc_url(),
+                operation,
+                after,
+                call,
+                |e: &TransportError| e.as_error_resp().is_some(),
+            )
+            .await
+    }
+
+    /// Clone of the client calling other endpoints, the other clones keep theirs
+    pub fn with_endpoints(&self, rpc_url: &str, ws_url: &str) -> Self {
+        EVMClient {
+            endpoints: shared_endpoints(rpc_url, ws_url),
+            ..self.clone()
+        }
+    }
+
+    /// Moves every clone of the client to the endpoints of `candidate`
+    pub fn adopt_endpoints(&self, candidate: &EVMClient) {
+        self.endpoints.store(candidate.endpoints.load_full());
+    }
+
+    pub fn nonce_resync_count(&self) -> u64 {
+        self.nonce_resyncs.load(Ordering::Relaxed)
+    }
+
+    pub fn with_delegate_registry(mut self, delegate_registry: Option<DelegateRegistry>) -> Self {
+        self.delegate_registry = delegate_registry;
+        self
+    }
+
+    pub fn with_bridge_deployment(mut self, bridge_deployment: Arc<BridgeDeployment>) -> Self {
+        self.bridge_deployment = Some(bridge_deployment);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Clone of the client calling the bridge contract `contract`
+    pub fn at_contract(&self, contract: Address) -> Self {
+        EVMClient {
+            bridge_contract: contract,
+            ..self.clone()
+        }
+    }
+
+    /// Clone of the client calling the bridge contract `request` was created against.
+    /// Requests without recorded addresses use the first contract of the deployment
+    pub fn for_request(&self, request: &BRequest) -> Result<Self> {
+        let recorded = match (&request.bridge_addresses, &self.bridge_deployment) {
+            (Some(addresses), _) => addresses,
+            (None, Some(deployment)) => &deployment.legacy,
+            (None, None) => return Ok(self.clone()),
+        };
+        let contract = Address::from_str(&recorded.evm_contract).map_err(|e| {
+            BridgeError::Other(format!(
+                "Invalid bridge contract {}: {e}",
+                recorded.evm_contract
+            ))
+        })?;
+        Ok(self.at_contract(contract))
+    }
+
+    /// Bridge contracts whose events are listened to at `now`, the current one first
+    pub fn listened_contracts(&self, now: Timestamp) -> Vec<Address> {
+        let mut contracts = vec![self.bridge_contract];
+        if let Some(deployment) = &self.bridge_deployment {
+            for addresses in deployment.listened(now) {
+                match Address::from_str(&addresses.evm_contract) {
+                    Ok(contract) if !contracts.contains(&contract) => contracts.push(contract),
+                    Ok(_) => {}
+                    Err(e) => error!(
+                        "Not listening to the bridge contract {}: {e}",
+                        addresses.evm_contract
+                    ),
+                }
+            }
+        }
+        contracts
+    }
+
+    /// Addresses of the deployment of the contract the client calls
+    pub fn bridge_addresses(&self) -> Option<BridgeAddresses> {
+        self.bridge_deployment
+            .as_ref()?
+            .find(|addresses| {
+                Address::from_str(&addresses.evm_contract).ok() == Some(self.bridge_contract)
+            })
+            .cloned()
+    }
+}
+
+fn shared_endpoints(rpc_url: &str, ws_url: &str) -> Arc<ArcSwap<EvmEndpoints>> {
+    Arc::new(ArcSwap::from_pointee(EvmEndpoints {
+        rpc: rpc_url.to_string(),
+        ws: ws_url.to_string(),
+    }))
 }
 
 pub fn evm_initialize(
     rpc_url: &str,
     ws_url: &str,
-    account_key: &str,
+    account_key: &Secret<String>,
     bridge_contract: &str,
-    tx_channel: Sender<TxMessage>,
+    tx_channel: TxSender,
     block_explorer: &str,
+    uri_policy: UriPolicy,
+    metadata_pinning: Option<MetadataPinning>,
+    branding: BrandingConfig,
+    timeouts: RpcTimeouts,
+    gas_limits: GasLimits,
+    fee_settings: FeeSettings,
+    missing_uri: MissingUriPolicy,
+    dev_mode: bool,
 ) -> Result<EVMClient> {
-    let signer: PrivateKeySigner = account_key.parse().expect("should parse private key");
+    // The parse error is not kept, it could quote the key
+    let signer: PrivateKeySigner = account_key
+        .expose()
+        .parse()
+        .map_err(|_| BridgeError::Other("Invalid EVM private key".to_string()))?;
     let wallet = EthereumWallet::from(signer.clone());
 
-    let bridge_contract_address = Address::from_str(bridge_contract)?;
+    let bridge_contract_address =
+        Address::from_str(bridge_contract).map_err(BridgeError::from_error)?;
 
     let evm_client = EVMClient {
-        rpc: rpc_url.to_string(),
-        ws: ws_url.to_string(),
+        endpoints: shared_endpoints(rpc_url, ws_url),
         signer: Arc::new(wallet),
         bridge_contract: bridge_contract_address,
         tx_channel: tx_channel,
         block_explorer: block_explorer.to_string(),
+        uri_policy,
+        metadata_pinning,
+        branding,
+        missing_uri,
+        timeouts,
+        authorized_backend: Arc::new(AtomicBool::new(true)),
+        dev_mode,
+        gas_policy: GasPolicy::new(gas_limits),
+        fee_settings,
+        fee_mode: SharedFeeMode::default(),
+        event_cursor: SharedEventCursor::default(),
+        nonce_resyncs: Arc::default(),
+        delegate_registry: None,
+        rpc_health: RpcHealth::default(),
+        bridge_deployment: None,
+        clock: system_clock(),
     };
 
     Ok(evm_client)
 }
 
+/// Client of a read-only relayer: a throwaway signer instead of a loaded key, the node
+/// is never called by the reads it serves
+pub fn evm_read_only_client(
+    rpc_url: &str,
+    ws_url: &str,
+    bridge_contract: &str,
+    tx_channel: TxSender,
+    block_explorer: &str,
+    timeouts: RpcTimeouts,
+) -> Result<EVMClient> {
+    Ok(EVMClient {
+        endpoints: shared_endpoints(rpc_url, ws_url),
+        signer: Arc::new(EthereumWallet::from(PrivateKeySigner::random())),
+        bridge_contract: Address::from_str(bridge_contract).map_err(BridgeError::from_error)?,
+        tx_channel,
+        block_explorer: block_explorer.to_string(),
+        uri_policy: UriPolicy::default(),
+        metadata_pinning: None,
+        branding: BrandingConfig::default(),
+        missing_uri: MissingUriPolicy::default(),
+        timeouts,
+        authorized_backend: Arc::new(AtomicBool::new(true)),
+        dev_mode: false,
+        gas_policy: GasPolicy::new(GasLimits::default()),
+        fee_settings: FeeSettings::default(),
+        fee_mode: SharedFeeMode::default(),
+        event_cursor: SharedEventCursor::default(),
+        nonce_resyncs: Arc::default(),
+        delegate_registry: None,
+        rpc_health: RpcHealth::default(),
+        bridge_deployment: None,
+        clock: system_clock(),
+    })
+}
+
 pub async fn get_latest_block_number(client: &EVMClient) -> Result<u64> {
     let provider = provider_rpc(client.to_owned())?;
 
-    let latest_block = provider.get_block_number().await?;
+    let latest_block = client
+        .observed(
+            "get_block_number",
+            client.timeouts.read,
+            provider.get_block_number(),
+        )
+        .await?;
     Ok(latest_block)
 }
 
 pub fn provider_rpc(client: EVMClient) -> Result<MyProviderRPC> {
-    let rpc_url = client.rpc.parse()?;
+    let rpc_url = client.rpc_url().parse().map_err(BridgeError::from_error)?;
 
     // Create a provider with the HTTP transport using the `reqwest` crate.
     let provider: MyProviderRPC = ProviderBuilder::new()
@@ -65,9 +317,14 @@ pub fn provider_rpc(client: EVMClient) -> Result<MyProviderRPC> {
 }
 
 pub async fn provider_ws(client: EVMClient) -> Result<MyProviderWS> {
-    let rpc_url = client.ws;
+    let rpc_url = client.ws_url();
     let ws = WsConnect::new(rpc_url);
-    let provider: MyProviderWS = ProviderBuilder::new().on_ws(ws).await?;
+    let provider: MyProviderWS = with_timeout(
+        "ws_connect",
+        client.timeouts.read,
+        ProviderBuilder::new().on_ws(ws),
+    )
+    .await?;
 
     Ok(provider)
 }
@@ -0,0 +1,25 @@
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum EvmError {
+    #[error("Relayer wallet {0} is not the authorized backend of the bridge contract")]
+    NotAuthorizedBackend(String),
+
+    /// Operation with the node gas estimate over the configured limit
+    #[error("{0} gas estimate {1} exceeds the limit of {2}")]
+    GasLimitExceeded(String, u64, u64),
+
+    /// Bridge contract log with the topic of a bridge event and data of another layout
+    #[error("Malformed {0} log: {1}")]
+    MalformedLog(&'static str, String),
+}
+
+impl From<EvmError> for types::BridgeError {
+    fn from(err: EvmError) -> Self {
+        match err {
+            EvmError::NotAuthorizedBackend(signer) => {
+                types::BridgeError::NotAuthorizedBackend(signer)
+            }
+            EvmError::GasLimitExceeded(..) => types::BridgeError::GasLimitExceeded(err.to_string()),
+            EvmError::MalformedLog(..) => types::BridgeError::Other(err.to_string()),
+        }
+    }
+}
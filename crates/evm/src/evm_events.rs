@@ -1,13 +1,26 @@
 use alloy::{
-    eips::BlockNumberOrTag, providers::Provider, rpc::types::Filter, sol, sol_types::SolEvent,
+    eips::BlockNumberOrTag,
+    primitives::{Address, U256},
+    providers::Provider,
+    rpc::types::{Filter, Log},
+    sol,
+    sol_types::SolEvent,
 };
-use eyre::Result;
 use futures_util::stream::StreamExt;
-use log::info;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use storage::db::Database;
-use types::Status;
+use tracing::Instrument;
+use types::{
+    capture_replay_input, event_span, log_account, log_processed, mark_log_processed,
+    prune_processed_logs, with_timeout, BRequest, BridgeError, BridgePause, ChainHeadReceiver,
+    Chains, EventValidator, LogMeta, ReplayInput, RequestId, Result, Status, Timestamp,
+};
 
-use crate::{check_token_owner, provider_ws, EVMClient};
+use crate::{
+    check_token_owner, detect_fee_mode, provider_ws, record_orphan_request, EVMClient, EvmError,
+};
 
 sol! {
     #[sol(rpc)]
@@ -15,60 +28,727 @@ sol! {
     event TokenMinted(string requestId, address tokenContract, address to, uint256 tokenId);
 }
 
-pub async fn catch_event(client: EVMClient, db: &Database) -> Result<()> {
-    let provider = provider_ws(client.clone()).await?;
+/// Event name of the TokenMinted logs in the replay bundles
+pub const TOKEN_MINTED_EVENT: &str = "TokenMinted";
+
+/// TokenMinted log of a request as kept in the replay bundles
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TokenMintedInput {
+    pub token_contract: String,
+    pub token_id: String,
+    /// Account the token was minted to, not in the bundles captured before it was checked
+    #[serde(default)]
+    pub to: Option<String>,
+    /// Position of the log, as in the request history
+    pub log: String,
+}
+
+/// Completes a TokenMinted request with the token of its TokenMinted log, unless it was
+/// minted to another account than the destination
+fn apply_token_minted(
+    db: &Database,
+    request: &mut BRequest,
+    minted: &TokenMintedInput,
+    now: Timestamp,
+) -> Result<()> {
+    request.record_event(&format!("EVM TokenMinted log at {}", minted.log), db, now)?;
+    if request.status != Status::TokenMinted {
+        return Ok(());
+    }
+    if let Some(to) = &minted.to {
+        if !request.verify_mint_recipient(db, to, now)? {
+            return Ok(());
+        }
+    }
+    request.complete_minted(db, &minted.token_contract, &minted.token_id, now)
+}
+
+/// TokenMinted log received for `request_id`, captured for replay under `REPLAY_CAPTURE`
+pub fn handle_token_minted(
+    db: &Database,
+    request_id: &str,
+    minted: TokenMintedInput,
+    now: Timestamp,
+) -> Result<()> {
+    let Ok(Some(mut request)) = types::request_data(request_id, db) else {
+        return Ok(());
+    };
+    capture_replay_input(
+        db,
+        &request,
+        now,
+        ReplayInput::Event {
+            chain: Chains::EVM,
+            name: TOKEN_MINTED_EVENT.to_string(),
+            payload: serde_json::to_value(&minted)?,
+        },
+    );
+    apply_token_minted(db, &mut request, &minted, now)
+}
+
+/// Replays a captured TokenMinted log of a request without the chain
+pub fn replay_token_minted(
+    db: &Database,
+    request_id: &str,
+    minted: &TokenMintedInput,
+    now: Timestamp,
+) -> Result<()> {
+    match types::request_data(request_id, db)? {
+        Some(mut request) => apply_token_minted(db, &mut request, minted, now),
+        None => Ok(()),
+    }
+}
+
+/// Token of a TokenMinted log and the account it was minted to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MintedToken {
+    pub token_contract: Address,
+    pub token_id: U256,
+    pub to: Address,
+}
+
+/// Token minted for `request_id` according to the TokenMinted logs of the bridge contract
+pub fn minted_token_from_logs(
+    logs: &[Log],
+    bridge_contract: Address,
+    request_id: &str,
+) -> Option<MintedToken> {
+    logs.iter()
+        .filter(|log| log.address() == bridge_contract)
+        .filter_map(|log| log.log_decode::<TokenMinted>().ok())
+        .map(|log| log.inner.data)
+        .find(|event| event.requestId == request_id)
+        .map(|event| MintedToken {
+            token_contract: event.tokenContract,
+            token_id: event.tokenId,
+            to: event.to,
+        })
+}
+
+/// Event of a bridge contract log
+pub enum BridgeLog {
+    NewRequest(NewRequest),
+    TokenMinted(TokenMinted),
+}
+
+/// Bridge event of a log, None for the logs of other events. A log with the topic of a
+/// bridge event and data that doesn't decode is a `MalformedLog` error
+pub fn decode_bridge_log(log: &Log) -> Result<Option<BridgeLog>, EvmError> {
+    let event = match log.topic0() {
+        Some(&NewRequest::SIGNATURE_HASH) => BridgeLog::NewRequest(
+            log.log_decode::<NewRequest>()
+                .map_err(|e| EvmError::MalformedLog("NewRequest", e.to_string()))?
+                .inner
+                .data,
+        ),
+        Some(&TokenMinted::SIGNATURE_HASH) => BridgeLog::TokenMinted(
+            log.log_decode::<TokenMinted>()
+                .map_err(|e| EvmError::MalformedLog(TOKEN_MINTED_EVENT, e.to_string()))?
+                .inner
+                .data,
+        ),
+        _ => return Ok(None),
+    };
+    Ok(Some(event))
+}
+
+/// Wait for more logs of the latest block before releasing it
+const BLOCK_LOGS_QUIET_PERIOD: Duration = Duration::from_millis(500);
+
+/// Logs of both the NewRequest and TokenMinted events of the bridge contracts, used by
+/// the subscription and any block range query so they see the same events
+pub fn bridge_events_filter(bridge_contracts: &[Address]) -> Filter {
+    Filter::new()
+        .address(bridge_contracts.to_vec())
+        .events([NewRequest::SIGNATURE, TokenMinted::SIGNATURE])
+}
 
-    let filter_request = Filter::new()
-        .address(client.bridge_contract)
-        .event(NewRequest::SIGNATURE)
-        .from_block(BlockNumberOrTag::Latest);
+pub fn sort_logs(logs: &mut [Log]) {
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+}
 
-    let filter_mint = Filter::new()
-        .address(client.bridge_contract)
-        .event(TokenMinted::SIGNATURE)
-        .from_block(BlockNumberOrTag::Latest);
+/// Holds the subscription logs of the latest block, releasing the older blocks sorted by
+/// (block, log index) once logs of a newer block arrive
+#[derive(Debug, Default)]
+pub struct BlockLogBuffer {
+    logs: Vec<Log>,
+    latest_block: Option<u64>,
+}
 
-    let sub_request = provider.subscribe_logs(&filter_request).await.unwrap();
-    let sub_mint = provider.subscribe_logs(&filter_mint).await.unwrap();
+impl BlockLogBuffer {
+    pub fn push(&mut self, log: Log) -> Vec<Log> {
+        let ready = match (self.latest_block, log.block_number) {
+            (Some(latest), Some(block)) if block > latest => self.take(),
+            _ => vec![],
+        };
+        self.latest_block = self.latest_block.max(log.block_number);
+        self.logs.push(log);
+        ready
+    }
+
+    /// Releases every buffered log, used when the stream goes quiet or ends
+    pub fn take(&mut self) -> Vec<Log> {
+        let mut logs = std::mem::take(&mut self.logs);
+        sort_logs(&mut logs);
+        logs
+    }
+}
+
+pub async fn catch_event(
+    client: EVMClient,
+    db: &Database,
+    head: &ChainHeadReceiver,
+    pause: &BridgePause,
+    validator: &EventValidator,
+) -> Result<()> {
+    while listen_bridge_logs(&client, db, head, pause, validator).await? {}
+    Ok(())
+}
+
+/// Handles the logs of the listened bridge contracts until the stream ends (false) or
+/// the contracts listened to change (true)
+async fn listen_bridge_logs(
+    client: &EVMClient,
+    db: &Database,
+    head: &ChainHeadReceiver,
+    pause: &BridgePause,
+    validator: &EventValidator,
+) -> Result<bool> {
+    let provider = provider_ws(client.clone()).await?;
+    if let Err(e) = detect_fee_mode(client).await {
+        warn!("Could not recheck the EVM fee mode: {e}");
+    }
 
-    let mut stream =
-        futures_util::stream::select(sub_request.into_stream(), sub_mint.into_stream());
+    // The contracts replaced by an upgrade are listened to until their transition ends
+    let contracts = client.listened_contracts(client.clock.now());
+    let filter = bridge_events_filter(&contracts).from_block(BlockNumberOrTag::Latest);
+    let subscription = with_timeout(
+        "subscribe_logs",
+        client.timeouts.read,
+        provider.subscribe_logs(&filter),
+    )
+    .await?;
+    let mut stream = subscription.into_stream();
+    let mut buffer = BlockLogBuffer::default();
 
     info!("Listening for evm events...");
-    while let Some(log) = stream.next().await {
-        match log.topic0() {
-            Some(&NewRequest::SIGNATURE_HASH) => {
-                let NewRequest {
-                    requestId,
-                    tokenContract,
-                    tokenId,
-                } = log.log_decode()?.inner.data;
-                info!("EVENT New EVM bridge request event, request id: {}, token contract {:?}, token id {:?}", &requestId, &tokenContract, &tokenId);
-                check_token_owner(client.clone(), db, &requestId)
-                    .await
-                    .unwrap();
+    loop {
+        let (ready, ended) =
+            match tokio::time::timeout(BLOCK_LOGS_QUIET_PERIOD, stream.next()).await {
+                Ok(Some(log)) => (buffer.push(log), false),
+                Ok(None) => (buffer.take(), true),
+                Err(_) => (buffer.take(), false),
+            };
+        let latest_block = ready.iter().filter_map(|log| log.block_number).max();
+        for log in ready {
+            let Some(meta) = LogMeta::from_log(&log) else {
+                warn!("Skipping EVM log without a block position: {log:?}");
+                continue;
+            };
+            if log_processed(db, &meta)? {
+                debug!("Skipping EVM log already processed at {meta}");
+                continue;
             }
-            Some(&TokenMinted::SIGNATURE_HASH) => {
-                let TokenMinted {
-                    requestId,
-                    tokenContract,
-                    to,
-                    tokenId,
-                } = log.log_decode()?.inner.data;
-                info!("EVENT New EVM token minted for request Id {requestId} with token contract {tokenContract} to account {to} and token id {tokenId}");
-                if let Ok(Some(mut request)) = types::request_data(&requestId, db) {
-                    if request.status == Status::TokenMinted {
-                        if request.output.detination_contract_id_or_mint
-                            == tokenContract.to_string()
-                            && request.output.detination_token_id_or_account == tokenId.to_string()
-                        {
-                            request.update_state(db)?;
+            handle_log(client, db, head, pause, validator, log, &meta).await?;
+            let now = client.clock.now();
+            mark_log_processed(db, &meta, now)?;
+            client.event_cursor.record(meta.block_number, now);
+        }
+        if let Some(block) = latest_block {
+            prune_processed_logs(db, block)?;
+        }
+        if ended {
+            return Ok(false);
+        }
+        if client.listened_contracts(client.clock.now()) != contracts {
+            info!("Transition of a replaced bridge contract ended, resubscribing");
+            return Ok(true);
+        }
+    }
+}
+
+async fn handle_log(
+    client: &EVMClient,
+    db: &Database,
+    head: &ChainHeadReceiver,
+    pause: &BridgePause,
+    validator: &EventValidator,
+    log: Log,
+    meta: &LogMeta,
+) -> Result<()> {
+    let event = match decode_bridge_log(&log) {
+        Ok(Some(event)) => event,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            error!("Skipping bridge contract log at {meta}: {e}");
+            return Ok(());
+        }
+    };
+    match event {
+        BridgeLog::NewRequest(NewRequest {
+            requestId,
+            tokenContract,
+            tokenId,
+        }) => {
+            info!("EVENT New EVM bridge request event, request id: {}, token contract {:?}, token id {:?}", &requestId, &tokenContract, &tokenId);
+            let Some(request_id) = event_request_id(&requestId) else {
+                return Ok(());
+            };
+            let span = event_span(&Chains::EVM, "NewRequest", &request_id, db);
+            async {
+                if !validator.validate(
+                    db,
+                    &Chains::EVM,
+                    &request_id,
+                    &tokenContract.to_string(),
+                    Some(&tokenId.to_string()),
+                    client.clock.now(),
+                )? {
+                    return Ok(());
+                }
+                // Tokens transferred without an API request wait for their owner to claim them
+                let Some(mut request) = types::request_data(&request_id, db)? else {
+                    return record_orphan_request(
+                        client.at_contract(log.address()),
+                        db,
+                        &request_id,
+                        tokenContract,
+                        tokenId,
+                    )
+                    .await;
+                };
+                request.lock_block = Some(meta.block_number);
+                request.record_event(
+                    &format!("EVM NewRequest log at {meta}"),
+                    db,
+                    client.clock.now(),
+                )?;
+                if pause.paused(&Chains::EVM).is_some() {
+                    pause.buffer_event(db, &request_id)?;
+                    return Ok(());
+                }
+                if let Err(e) = check_token_owner(client.clone(), db, head, &request_id).await {
+                    error!("Checking owner of request {request_id}, error {e}");
+                    // Retried by the sweep unless it fails on every retry
+                    if e.is_corrupted_data() {
+                        if let Some(mut request) = types::request_data(&request_id, db)? {
+                            error!("Canceling request {request_id} with corrupted data: {e}");
+                            request.cancel_corrupted(db, &e.to_string(), client.clock.now())?;
                         }
                     }
                 }
+                Ok::<_, BridgeError>(())
             }
-            _ => (),
+            .instrument(span)
+            .await?;
+        }
+        BridgeLog::TokenMinted(TokenMinted {
+            requestId,
+            tokenContract,
+            to,
+            tokenId,
+        }) => {
+            info!(
+                "EVENT New EVM token minted for request Id {requestId} with token contract {tokenContract} to account {} and token id {tokenId}",
+                log_account(&to.to_string())
+            );
+            let Some(request_id) = event_request_id(&requestId) else {
+                return Ok(());
+            };
+            let _span = event_span(&Chains::EVM, "TokenMinted", &request_id, db).entered();
+            handle_token_minted(
+                db,
+                &request_id,
+                TokenMintedInput {
+                    token_contract: tokenContract.to_string(),
+                    token_id: tokenId.to_string(),
+                    to: Some(to.to_string()),
+                    log: meta.to_string(),
+                },
+                client.clock.now(),
+            )?;
         }
     }
     Ok(())
 }
+
+/// Request id of a bridge contract event, events with a malformed id are logged and
+/// skipped
+fn event_request_id(request_id: &str) -> Option<RequestId> {
+    RequestId::parse(request_id)
+        .inspect_err(|e| error!("Skipping bridge contract event: {e}"))
+        .ok()
+}
+
+#[cfg(test)]
+mod evm_events_test {
+    use std::{sync::Arc, time::Duration};
+
+    use alloy::{
+        primitives::{address, Address, B256, U256},
+        rpc::types::Log,
+        sol_types::SolEvent,
+    };
+    use proptest::prelude::*;
+    use test_support::{
+        new_request_log, raw_bridge_log, test_db, token_minted_log, BRIDGE_CONTRACT, EVM_ACCOUNT,
+        EVM_TOKEN_CONTRACT,
+    };
+
+    use types::{
+        request_data, tx_channel, BRequest, BrandingConfig, BridgeAddresses, BridgeDeployment,
+        Chains, ChannelMetrics, ErrorClass, InputRequest, MissingUriPolicy, RpcTimeouts, Secret,
+        Status, Timestamp, UriPolicy,
+    };
+
+    use crate::{
+        bridge_events_filter, decode_bridge_log, evm_initialize, handle_token_minted,
+        historical_event, minted_token_from_logs, sort_logs, BlockLogBuffer, EvmError, FeeSettings,
+        GasLimits, MintedToken, NewRequest, TokenMinted, TokenMintedInput,
+    };
+
+    use super::event_request_id;
+
+    fn topic0() -> impl Strategy<Value = B256> {
+        prop_oneof![
+            Just(NewRequest::SIGNATURE_HASH),
+            Just(TokenMinted::SIGNATURE_HASH),
+            any::<[u8; 32]>().prop_map(B256::from),
+        ]
+    }
+
+    /// Encoding of either event, then cut, with a word changed or with bytes appended.
+    /// Mutation 3 leaves it valid
+    fn near_valid_log() -> impl Strategy<Value = (Log, bool)> {
+        (
+            "\\PC{0,80}",
+            any::<[u8; 20]>(),
+            any::<[u8; 32]>(),
+            any::<bool>(),
+            0u8..4,
+            any::<usize>(),
+            any::<[u8; 32]>(),
+        )
+            .prop_map(
+                |(request_id, contract, token_id, minted, mutation, position, word)| {
+                    let (contract, token_id) =
+                        (Address::from(contract), U256::from_be_bytes(token_id));
+                    let mut log = if minted {
+                        token_minted_log(&request_id, contract, contract, token_id, (1, 0))
+                    } else {
+                        new_request_log(&request_id, contract, token_id, (1, 0))
+                    };
+                    let mut data = log.inner.data.data.to_vec();
+                    match mutation {
+                        0 => data.truncate(position % data.len()),
+                        1 => {
+                            let at = position % (data.len() / 32) * 32;
+                            data[at..at + 32].copy_from_slice(&word);
+                        }
+                        2 => data.extend_from_slice(&word[..position % 32 + 1]),
+                        _ => {}
+                    }
+                    log = raw_bridge_log(log.topics().to_vec(), data);
+                    (log, mutation == 3)
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn test_arbitrary_logs_never_panic(
+            topic0 in topic0(),
+            topics in proptest::collection::vec(any::<[u8; 32]>(), 0..4),
+            data in proptest::collection::vec(any::<u8>(), 0..512),
+        ) {
+            let topics = std::iter::once(topic0)
+                .chain(topics.into_iter().map(B256::from))
+                .collect();
+            let log = raw_bridge_log(topics, data);
+            if let Err(e) = decode_bridge_log(&log) {
+                prop_assert!(matches!(e, EvmError::MalformedLog(..)));
+            }
+            let _ = historical_event(&log);
+        }
+
+        #[test]
+        fn test_near_valid_logs_decode_or_fail_typed((log, valid) in near_valid_log()) {
+            match decode_bridge_log(&log) {
+                Ok(Some(_)) => {}
+                Ok(None) => prop_assert!(false, "Bridge event topic not recognized"),
+                Err(e) => {
+                    prop_assert!(!valid, "Valid log rejected: {e}");
+                    prop_assert!(matches!(e, EvmError::MalformedLog(..)));
+                }
+            }
+            let _ = historical_event(&log);
+        }
+    }
+
+    fn log(block: u64, index: u64) -> Log {
+        new_request_log(
+            "0xrequest",
+            EVM_TOKEN_CONTRACT.parse().unwrap(),
+            U256::from(1),
+            (block, index),
+        )
+    }
+
+    fn positions(logs: &[Log]) -> Vec<(u64, u64)> {
+        logs.iter()
+            .map(|log| (log.block_number.unwrap(), log.log_index.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_event_logs_decode_round_trip() {
+        let token_contract: Address = EVM_TOKEN_CONTRACT.parse().unwrap();
+        let to: Address = EVM_ACCOUNT.parse().unwrap();
+        let token_id = U256::from(42);
+
+        let log = new_request_log("0xrequest1", token_contract, token_id, (10, 0));
+        assert_eq!(log.topic0(), Some(&NewRequest::SIGNATURE_HASH));
+        let NewRequest {
+            requestId,
+            tokenContract,
+            tokenId,
+        } = log.log_decode().unwrap().inner.data;
+        assert_eq!(
+            (requestId.as_str(), tokenContract, tokenId),
+            ("0xrequest1", token_contract, token_id)
+        );
+
+        let log = token_minted_log("0xrequest1", token_contract, to, token_id, (10, 1));
+        assert_eq!(log.topic0(), Some(&TokenMinted::SIGNATURE_HASH));
+        let event = log.log_decode::<TokenMinted>().unwrap().inner.data;
+        assert_eq!(event.to, to);
+
+        // Only TokenMinted logs of the bridge contract for the request are considered
+        let other_bridge = {
+            let mut log =
+                token_minted_log("0xrequest1", token_contract, to, U256::from(99), (9, 0));
+            log.inner.address = address!("0000000000000000000000000000000000000001");
+            log
+        };
+        let logs = [
+            new_request_log("0xrequest1", token_contract, token_id, (10, 0)),
+            token_minted_log("0xrequest2", token_contract, to, U256::from(7), (10, 1)),
+            other_bridge,
+            log,
+        ];
+        assert_eq!(
+            minted_token_from_logs(&logs, BRIDGE_CONTRACT, "0xrequest1"),
+            Some(MintedToken {
+                token_contract,
+                token_id,
+                to
+            })
+        );
+        assert_eq!(
+            minted_token_from_logs(&logs, BRIDGE_CONTRACT, "0xrequest3"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_token_minted_to_another_account_is_held() {
+        let db = test_db();
+        let minted = |token_id: &str| {
+            let mut request = BRequest::new(
+                InputRequest {
+                    contract_or_mint: "mint".to_string(),
+                    token_id: token_id.to_string(),
+                    token_owner: "owner".to_string(),
+                    origin_network: Chains::SOLANA,
+                    destination_account: EVM_ACCOUNT.to_string(),
+                },
+                Timestamp::now(),
+            );
+            request.status = Status::TokenMinted;
+            request.save(&db).unwrap();
+            request
+        };
+        let log = |to: &str| TokenMintedInput {
+            token_contract: EVM_TOKEN_CONTRACT.to_string(),
+            token_id: "7".to_string(),
+            to: Some(to.to_string()),
+            log: "block 10 log 1".to_string(),
+        };
+
+        let request = minted("1");
+        handle_token_minted(
+            &db,
+            &request.id,
+            log(&EVM_ACCOUNT.to_lowercase()),
+            Timestamp::now(),
+        )
+        .unwrap();
+        let stored = request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::Completed);
+
+        let request = minted("2");
+        let other = "0x3C44CdDdB6a900fa2b585dd299e03d12FA4293BC";
+        handle_token_minted(&db, &request.id, log(other), Timestamp::now()).unwrap();
+        let stored = request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::NeedsIntervention);
+        assert_eq!(stored.finalized_at, None);
+        let report = stored.failure_report.unwrap();
+        assert_eq!(report.attempts[0].class, ErrorClass::DestinationMismatch);
+    }
+
+    #[test]
+    fn test_malformed_event_request_ids_are_skipped() {
+        let id = BRequest::generate_id(EVM_TOKEN_CONTRACT, "1", EVM_ACCOUNT);
+        assert_eq!(
+            event_request_id(&id.to_uppercase().replacen("0X", "0x", 1)),
+            Some(id)
+        );
+        for malformed in ["", "0xrequest1", "request123"] {
+            assert_eq!(event_request_id(malformed), None);
+        }
+    }
+
+    #[test]
+    fn test_filter_covers_both_events() {
+        let upgraded = address!("00000000000000000000000000000000000000b2");
+        let filter = bridge_events_filter(&[upgraded, BRIDGE_CONTRACT]);
+        assert!(filter.address.matches(&BRIDGE_CONTRACT));
+        assert!(filter.address.matches(&upgraded));
+        assert!(!filter
+            .address
+            .matches(&EVM_TOKEN_CONTRACT.parse::<Address>().unwrap()));
+        assert!(filter.topics[0].matches(&NewRequest::SIGNATURE_HASH));
+        assert!(filter.topics[0].matches(&TokenMinted::SIGNATURE_HASH));
+    }
+
+    #[test]
+    fn test_requests_finish_on_the_contract_they_started_on() {
+        let (old_contract, new_contract) = (
+            BRIDGE_CONTRACT,
+            address!("00000000000000000000000000000000000000b2"),
+        );
+        let addresses = |contract: Address| BridgeAddresses {
+            evm_contract: contract.to_string(),
+            solana_program: "program".to_string(),
+            solana_account: "account".to_string(),
+        };
+        let now = Timestamp::now();
+        let transition = Duration::from_secs(3600);
+        // Configured with the new contract after running with the old one
+        let mut deployment = BridgeDeployment::new(addresses(old_contract));
+        deployment.upgrade(addresses(new_contract), now, transition);
+        let (tx, _rx) = tx_channel(Chains::SOLANA, 1, &ChannelMetrics::default());
+        let client = evm_initialize(
+            "http://127.0.0.1:1",
+            "ws://127.0.0.1:1",
+            &Secret::from("0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"),
+            &new_contract.to_string(),
+            tx,
+            "",
+            UriPolicy::default(),
+            None,
+            BrandingConfig::default(),
+            RpcTimeouts::default(),
+            GasLimits::default(),
+            FeeSettings::default(),
+            MissingUriPolicy::default(),
+            false,
+        )
+        .unwrap()
+        .with_bridge_deployment(Arc::new(deployment));
+
+        let db = test_db();
+        let minting = |token_id: &str, contract: Option<Address>| {
+            let mut request = BRequest::new(
+                InputRequest {
+                    contract_or_mint: "mint".to_string(),
+                    token_id: token_id.to_string(),
+                    token_owner: "owner".to_string(),
+                    origin_network: Chains::SOLANA,
+                    destination_account: EVM_ACCOUNT.to_string(),
+                },
+                Timestamp::now(),
+            );
+            request.bridge_addresses = contract.map(addresses);
+            request.status = Status::TokenMinted;
+            request.save(&db).unwrap();
+            request
+        };
+        let old = minting("1", Some(old_contract));
+        let new = minting("2", Some(new_contract));
+        let legacy = minting("3", None);
+        let contract_of = |request: &BRequest| client.for_request(request).unwrap().bridge_contract;
+        assert_eq!(contract_of(&old), old_contract);
+        assert_eq!(contract_of(&new), new_contract);
+        // Saved before the addresses were recorded, it started on the first contract
+        assert_eq!(contract_of(&legacy), old_contract);
+
+        assert_eq!(
+            client.listened_contracts(now),
+            vec![new_contract, old_contract]
+        );
+        assert_eq!(
+            client.listened_contracts(now.saturating_add(transition)),
+            vec![new_contract]
+        );
+
+        // The TokenMinted logs of both contracts complete their requests, each one read
+        // from the contract of its request
+        let token_contract: Address = EVM_TOKEN_CONTRACT.parse().unwrap();
+        let to: Address = EVM_ACCOUNT.parse().unwrap();
+        let mut from_new = token_minted_log(&new.id, token_contract, to, U256::from(2), (10, 1));
+        from_new.inner.address = new_contract;
+        let logs = [
+            token_minted_log(&old.id, token_contract, to, U256::from(1), (10, 0)),
+            from_new,
+        ];
+        assert_eq!(
+            minted_token_from_logs(&logs, contract_of(&new), &old.id),
+            None
+        );
+        for (request, token_id) in [(&old, 1u64), (&new, 2)] {
+            let minted = minted_token_from_logs(&logs, contract_of(request), &request.id).unwrap();
+            assert_eq!(minted.token_id, U256::from(token_id));
+            handle_token_minted(
+                &db,
+                &request.id,
+                TokenMintedInput {
+                    token_contract: minted.token_contract.to_string(),
+                    token_id: minted.token_id.to_string(),
+                    to: Some(minted.to.to_string()),
+                    log: format!("block 10 log {}", token_id - 1),
+                },
+                Timestamp::now(),
+            )
+            .unwrap();
+            let stored = request_data(&request.id, &db).unwrap().unwrap();
+            assert_eq!(stored.status, Status::Completed);
+        }
+    }
+
+    #[test]
+    fn test_scrambled_logs_are_handled_in_order() {
+        let mut logs = vec![log(11, 2), log(10, 5), log(11, 0), log(10, 1), log(12, 0)];
+        sort_logs(&mut logs);
+        assert_eq!(
+            positions(&logs),
+            vec![(10, 1), (10, 5), (11, 0), (11, 2), (12, 0)]
+        );
+
+        // Subscription order, scrambled inside each block
+        let mut buffer = BlockLogBuffer::default();
+        let mut handled = vec![];
+        for log in [log(10, 3), log(10, 0), log(10, 1), log(11, 4), log(11, 2)] {
+            handled.extend(buffer.push(log));
+        }
+        assert_eq!(positions(&handled), vec![(10, 0), (10, 1), (10, 3)]);
+
+        // The latest block is released once the stream goes quiet
+        handled.extend(buffer.take());
+        assert_eq!(
+            positions(&handled),
+            vec![(10, 0), (10, 1), (10, 3), (11, 2), (11, 4)]
+        );
+        assert!(buffer.take().is_empty());
+    }
+}
@@ -1,58 +1,187 @@
 use alloy::{
     primitives::{Address, U256},
-    providers::{Provider, WalletProvider},
+    providers::{Provider, SendableTx, WalletProvider},
+    rpc::types::TransactionRequest,
     sol,
 };
 
-use eyre::Result;
-use log::info;
-use std::str::FromStr;
+use log::{debug, error, info};
+use std::{str::FromStr, sync::atomic::Ordering, time::Instant};
 use storage::db::Database;
-use tokio::sync::mpsc::Receiver;
-use types::{Status, TxMessage};
+use tracing::Instrument;
+use types::{
+    clear_send_intent, debug_detail, is_already_known, is_timeout, mark_intent_sent,
+    parse_token_id, record_send_intent, resolve_send_intent, sanitize_token_uri,
+    translate_token_uri, with_fast_path, with_timeout, BridgeError, CancelReason, Chains,
+    ErrorComponent, FastPath, InFlightRegistry, LoggableMessage, MessageNewRequest, RequestId,
+    Result, SendIntent, SendOperation, Status, TaskHealth, Timestamp, TxMessage, TxReceiver,
+    WrapCallContext, PROCESSOR_IDLE_TICK,
+};
 
-use crate::{provider_rpc, EVMClient};
+use crate::{
+    gas_pricing, provider_rpc, provider_type::MyProviderRPC, EVMClient, EvmSendLookup, GasOperation,
+};
 
-const MAX_FEE_PER_GAS: u128 = 3000000000;
-const MAX_PRIORIRY_FEE: u128 = 3000000000;
+/// Sets the gas limit of `tx` from the node estimate, bounded by the gas policy of the
+/// client. Estimates over the limit fail with `BridgeError::GasLimitExceeded`
+async fn apply_gas_limit(
+    client: &EVMClient,
+    provider: &MyProviderRPC,
+    tx: &mut TransactionRequest,
+    operation: GasOperation,
+    request_id: &str,
+) -> Result<()> {
+    let estimate = with_timeout(
+        "estimate_gas",
+        client.timeouts.read,
+        provider.estimate_gas(tx.clone()),
+    )
+    .await
+    .with_call_context(|| client.call_context(operation.name(), request_id))?;
+    tx.gas = Some(client.gas_policy.gas_limit(operation, estimate)?);
+    Ok(())
+}
+
+/// Signs and sends `tx`, returns its hash. A nonce already used is read again from the
+/// pending transactions of the signer and the transaction resent at once, a transaction
+/// the node already has is taken as sent. See `FastPath::NonceResync`.
+///
+/// The send is journaled as `journaled` of the request: the intent left by an earlier
+/// send is looked up first and its transaction taken as sent when found, then each
+/// signed transaction is recorded with its nonce and hash before it is sent. The caller
+/// clears the intent once the hash is recorded on the request
+#[allow(clippy::too_many_arguments)]
+async fn send_with_nonce_resync(
+    client: &EVMClient,
+    db: &Database,
+    provider: &MyProviderRPC,
+    tx: TransactionRequest,
+    operation: &str,
+    journaled: SendOperation,
+    request_id: &str,
+) -> Result<String> {
+    let lookup = EvmSendLookup {
+        client: client.clone(),
+    };
+    if let Some(tx_hash) = resolve_send_intent(db, &lookup, request_id, journaled)
+        .await
+        .with_call_context(|| client.call_context(operation, request_id))?
+    {
+        return Ok(tx_hash);
+    }
+
+    let signer = provider.default_signer_address();
+    let sent = with_fast_path(FastPath::NonceResync, &client.nonce_resyncs, |attempt| {
+        let mut tx = tx.clone();
+        async move {
+            if attempt > 0 {
+                let nonce = with_timeout(
+                    "get_transaction_count",
+                    client.timeouts.read,
+                    provider.get_transaction_count(signer).pending(),
+                )
+                .await
+                .with_call_context(|| client.call_context("get_transaction_count", request_id))?;
+                tx.nonce = Some(nonce);
+            }
+            let nonce = tx.nonce;
+
+            // Signed before sending, the hash is known when the node already has it
+            let filled = with_timeout("fill_transaction", client.timeouts.read, provider.fill(tx))
+                .await
+                .with_call_context(|| client.call_context(operation, request_id))?;
+            let SendableTx::Envelope(envelope) = filled else {
+                return Err(BridgeError::Other(format!(
+                    "Transaction of {operation} not signed by the relayer wallet"
+                )));
+            };
+            let tx_hash = *envelope.tx_hash();
+            let mut intent = SendIntent::new(request_id, Chains::EVM, journaled);
+            intent.nonce = nonce;
+            intent.tx_hash = Some(tx_hash.to_string());
+            record_send_intent(db, &intent)?;
+
+            match with_timeout(
+                "send_transaction",
+                client.timeouts.send,
+                provider.send_tx_envelope(envelope),
+            )
+            .await
+            {
+                Ok(pending_tx) => {
+                    info!("Transaction sent: {:?}", pending_tx);
+                    let receipt =
+                        with_timeout("register", client.timeouts.send, pending_tx.register())
+                            .await
+                            .with_call_context(|| client.call_context(operation, request_id))?;
+                    Ok(receipt.tx_hash().to_string())
+                }
+                Err(e) if is_already_known(&e.to_string()) => {
+                    client.nonce_resyncs.fetch_add(1, Ordering::Relaxed);
+                    info!("Transaction {tx_hash} already known to the node, taken as sent");
+                    Ok(tx_hash.to_string())
+                }
+                Err(e) => Err(e).with_call_context(|| client.call_context(operation, request_id)),
+            }
+        }
+    })
+    .await;
+
+    match &sent {
+        Ok(tx_hash) => mark_intent_sent(db, request_id, journaled, tx_hash)?,
+        // A send timing out may have reached the node, its intent is looked up before the
+        // next one
+        Err(e) if is_timeout(e) => {}
+        Err(_) => clear_send_intent(db, request_id, journaled),
+    }
+    sent
+}
 
 sol! {
     #[sol(rpc)]
     interface BridgeContract {
         function newBridgeRequest(string requestId, address tokenContract, address tokenOwner, uint256 tokenId) external;
         function mintToken(string requestId, address to, uint256 tokenId, string tokenURI) external;
+        function releaseToken(string requestId, address to) external;
         function tokenAddress() external view returns (address);
+        function backend() external view returns (address);
+        function requestOwner(string requestId) external view returns (address);
     }
 }
 
 pub async fn initialize_evm_request(
     client: EVMClient,
+    db: &Database,
     token_contract: &str,
     token_owner: &str,
     token_id: &str,
-    request_id: &str,
+    request_id: &RequestId,
 ) -> Result<String> {
     info!("Initialize bridge request from evm");
+    client.ensure_authorized_backend()?;
     let provider = provider_rpc(client.clone())?;
 
     // Set up the contract interaction
-    let token_contract_add = Address::from_str(token_contract)?;
-    let token_owner_add = Address::from_str(token_owner)?;
-    let token_id_u256: U256 = token_id.parse().expect("Invalid U256 string");
+    let token_contract_add = Address::from_str(token_contract).map_err(BridgeError::from_error)?;
+    let token_owner_add = Address::from_str(token_owner).map_err(BridgeError::from_error)?;
+    let token_id_u256 = parse_token_id(token_id).map_err(BridgeError::from)?;
 
     let contract = BridgeContract::new(client.bridge_contract, provider.clone());
 
     let signer = provider.default_signer_address();
-    let nonce = provider.get_transaction_count(signer).await.unwrap();
-    let mut fees = provider.estimate_eip1559_fees().await.unwrap();
-
-    if fees.max_fee_per_gas == 1 && fees.max_priority_fee_per_gas == 1 {
-        fees.max_fee_per_gas = MAX_FEE_PER_GAS;
-        fees.max_priority_fee_per_gas = MAX_PRIORIRY_FEE;
-    }
+    let nonce = with_timeout(
+        "get_transaction_count",
+        client.timeouts.read,
+        provider.get_transaction_count(signer),
+    )
+    .await
+    .with_call_context(|| client.call_context("get_transaction_count", request_id))?;
+    let pricing = gas_pricing(client.clone())
+        .await
+        .with_call_context(|| client.call_context("gas_pricing", request_id))?;
 
     // Build the transaction
-    let tx = contract
+    let mut tx = contract
         .newBridgeRequest(
             request_id.to_string(),
             token_contract_add,
@@ -61,82 +190,199 @@ pub async fn initialize_evm_request(
         )
         .value(U256::from(0))
         .nonce(nonce)
-        .max_fee_per_gas(fees.max_fee_per_gas)
-        .max_priority_fee_per_gas(fees.max_priority_fee_per_gas)
-        .gas(100000)
         .into_transaction_request();
+    pricing.apply(&mut tx);
 
-    let _ = provider.call(tx.clone()).await?;
-
-    let pending_tx = provider.send_transaction(tx).await?;
+    let operation = "newBridgeRequest";
+    let _ = with_timeout("eth_call", client.timeouts.read, provider.call(tx.clone()))
+        .await
+        .with_call_context(|| client.call_context(operation, request_id))?;
+    apply_gas_limit(
+        &client,
+        &provider,
+        &mut tx,
+        GasOperation::NewRequest,
+        request_id,
+    )
+    .await?;
 
-    info!("Transaction sent: {:?}", pending_tx);
-    let receipt = pending_tx.register().await?;
-    let tx_hash = receipt.tx_hash().to_string();
+    send_with_nonce_resync(
+        &client,
+        db,
+        &provider,
+        tx,
+        operation,
+        SendOperation::Lock,
+        request_id,
+    )
+    .await
+}
 
-    Ok(tx_hash)
+/// Sends the lock transaction of a request accepted by the API before it, None when the
+/// request is no longer initializing. A lock that can't be sent cancels the request
+pub async fn lock_request(
+    client: EVMClient,
+    db: &Database,
+    message: &MessageNewRequest,
+) -> Result<Option<String>> {
+    let Some(mut request) = types::request_data(&message.request_id, db)? else {
+        return Ok(None);
+    };
+    if request.status != Status::Initializing {
+        return Ok(None);
+    }
+    match initialize_evm_request(
+        client.for_request(&request)?,
+        db,
+        &message.token_contract,
+        &message.token_owner,
+        &message.token_id,
+        &message.request_id,
+    )
+    .await
+    {
+        Ok(tx_hash) => {
+            request.lock_sent(&tx_hash, db, client.clock.now())?;
+            clear_send_intent(db, &request.id, SendOperation::Lock);
+            Ok(Some(tx_hash))
+        }
+        Err(e) => {
+            let reason: fn(String) -> CancelReason = match e.root() {
+                BridgeError::GasLimitExceeded(_) => CancelReason::GasLimitExceeded,
+                _ => CancelReason::LockFailed,
+            };
+            request.lock_not_sent(db, reason, &e, client.clock.now())?;
+            Err(e)
+        }
+    }
 }
 
 pub async fn mint_new_token(
     client: EVMClient,
     db: &Database,
-    request_id: &str,
+    request_id: &RequestId,
     token_metadata: &str,
 ) -> Result<String> {
     if let Ok(Some(mut request)) = types::request_data(request_id, db) {
+        let client = client.for_request(&request)?;
+        client.ensure_authorized_backend()?;
+        // A retried mint sends the metadata of the first attempt, read and translated once
+        let now = client.clock.now();
+        let token_metadata = match request.metadata_snapshot.clone() {
+            Some(snapshot) => snapshot.token_uri,
+            None => {
+                let Some(sanitized) =
+                    sanitize_token_uri(&client.uri_policy, &mut request, token_metadata, db, now)?
+                else {
+                    return Ok(String::default());
+                };
+                let token_uri = translate_token_uri(
+                    client.metadata_pinning.as_ref(),
+                    &mut request,
+                    token_metadata,
+                    sanitized,
+                    db,
+                    now,
+                )
+                .await?;
+                request.keep_metadata_snapshot(token_metadata, &token_uri, now, db)?;
+                token_uri
+            }
+        };
+        let token_metadata =
+            client
+                .branding
+                .brand_uri(&mut request, &token_metadata, client.uri_policy.max_length);
+
         let provider = provider_rpc(client.clone())?;
 
         let mint_account = request.input.contract_or_mint.clone();
-        let decoded = bs58::decode(mint_account).into_vec()?;
+        let decoded = bs58::decode(mint_account)
+            .into_vec()
+            .map_err(BridgeError::from_error)?;
 
         let token_id: U256 = U256::from_be_slice(&decoded);
 
         let contract = BridgeContract::new(client.bridge_contract, provider.clone());
 
-        let destination_owner = Address::from_str(&request.input.destination_account)?;
+        let destination_owner = Address::from_str(&request.input.destination_account)
+            .map_err(BridgeError::from_error)?;
         let signer = provider.default_signer_address();
-        let nonce = provider.get_transaction_count(signer).await.unwrap();
-        let mut fees = provider.estimate_eip1559_fees().await.unwrap();
-
-        let destination_contract = contract.tokenAddress().call().await?;
+        let nonce = with_timeout(
+            "get_transaction_count",
+            client.timeouts.read,
+            provider.get_transaction_count(signer),
+        )
+        .await
+        .with_call_context(|| client.call_context("get_transaction_count", request_id))?;
+        let pricing = gas_pricing(client.clone())
+            .await
+            .with_call_context(|| client.call_context("gas_pricing", request_id))?;
 
-        if fees.max_fee_per_gas == 1 && fees.max_priority_fee_per_gas == 1 {
-            fees.max_fee_per_gas = MAX_FEE_PER_GAS;
-            fees.max_priority_fee_per_gas = MAX_PRIORIRY_FEE;
-        }
+        let destination_contract = with_timeout(
+            "tokenAddress",
+            client.timeouts.read,
+            contract.tokenAddress().call(),
+        )
+        .await
+        .with_call_context(|| client.call_context("tokenAddress", request_id))?;
 
         // Build the transaction
-        let tx = contract
+        let mut tx = contract
             .mintToken(
                 request_id.to_string(),
                 destination_owner,
                 token_id,
-                token_metadata.to_owned(),
+                token_metadata.clone(),
             )
             .value(U256::from(0))
             .nonce(nonce)
-            .max_fee_per_gas(fees.max_fee_per_gas)
-            .max_priority_fee_per_gas(fees.max_priority_fee_per_gas)
-            .gas(200000)
             .into_transaction_request();
+        pricing.apply(&mut tx);
 
-        let _ = provider.call(tx.clone()).await?;
+        let operation = "mintToken";
+        let _ = with_timeout("eth_call", client.timeouts.read, provider.call(tx.clone()))
+            .await
+            .with_call_context(|| client.call_context(operation, request_id))?;
+        if let Err(err) =
+            apply_gas_limit(&client, &provider, &mut tx, GasOperation::Mint, request_id).await
+        {
+            // Retrying gets the same estimate, the request is canceled
+            if let BridgeError::GasLimitExceeded(reason) = err.root() {
+                error!("Canceling request {request_id}: {reason}");
+                request.cancel_with_reason(
+                    db,
+                    CancelReason::GasLimitExceeded(reason.clone()),
+                    client.clock.now(),
+                )?;
+            }
+            return Err(err);
+        }
 
         // Send the transaction
-        let builder = provider.send_transaction(tx).await?;
-
-        info!("Transaction sent: {:?}", builder);
-        let receipt = builder.register().await?;
-        let tx_hash = receipt.tx_hash().to_string();
+        let tx_hash = send_with_nonce_resync(
+            &client,
+            db,
+            &provider,
+            tx,
+            operation,
+            SendOperation::Mint,
+            request_id,
+        )
+        .await?;
 
+        request.minted_token_uri = Some(token_metadata);
         request.add_tx(&tx_hash, db)?;
+        clear_send_intent(db, request_id, SendOperation::Mint);
+        let sent_at = client.clock.now();
         if request.status == Status::TokenReceived {
-            request.update_state(db)?;
+            request.update_state(db, sent_at)?;
         }
         request.finalize(
             db,
             &destination_contract._0.to_string(),
             &token_id.to_string(),
+            sent_at,
         )?;
 
         return Ok(tx_hash);
@@ -145,38 +391,188 @@ pub async fn mint_new_token(
     Ok(String::default())
 }
 
+/// Transfers the origin token of a canceled EVM request from the bridge back to its owner
+pub async fn refund_token(
+    client: EVMClient,
+    db: &Database,
+    request_id: &RequestId,
+) -> Result<String> {
+    let Some(mut request) = types::request_data(request_id, db)? else {
+        return Ok(String::default());
+    };
+    if !request.refundable() {
+        info!("Request {request_id} is not refundable, skipping the refund");
+        return Ok(String::default());
+    }
+    let client = client.for_request(&request)?;
+    client.ensure_authorized_backend()?;
+    let provider = provider_rpc(client.clone())?;
+
+    let token_owner =
+        Address::from_str(&request.input.token_owner).map_err(BridgeError::from_error)?;
+    let contract = BridgeContract::new(client.bridge_contract, provider.clone());
+
+    let signer = provider.default_signer_address();
+    let nonce = with_timeout(
+        "get_transaction_count",
+        client.timeouts.read,
+        provider.get_transaction_count(signer),
+    )
+    .await
+    .with_call_context(|| client.call_context("get_transaction_count", request_id))?;
+    let pricing = gas_pricing(client.clone())
+        .await
+        .with_call_context(|| client.call_context("gas_pricing", request_id))?;
+
+    // Build the transaction
+    let mut tx = contract
+        .releaseToken(request_id.to_string(), token_owner)
+        .value(U256::from(0))
+        .nonce(nonce)
+        .into_transaction_request();
+    pricing.apply(&mut tx);
+
+    let operation = "releaseToken";
+    let _ = with_timeout("eth_call", client.timeouts.read, provider.call(tx.clone()))
+        .await
+        .with_call_context(|| client.call_context(operation, request_id))?;
+    // Over the limit the refund stays pending, an operator raises it or refunds by hand
+    apply_gas_limit(
+        &client,
+        &provider,
+        &mut tx,
+        GasOperation::Refund,
+        request_id,
+    )
+    .await?;
+
+    let tx_hash = send_with_nonce_resync(
+        &client,
+        db,
+        &provider,
+        tx,
+        operation,
+        SendOperation::Refund,
+        request_id,
+    )
+    .await?;
+
+    request.complete_refund(db, &tx_hash, client.clock.now())?;
+    clear_send_intent(db, request_id, SendOperation::Refund);
+    Ok(tx_hash)
+}
+
+/// Sends the transactions of the messages of `rx_channel` until it is closed, beating
+/// `health` after each message and each `PROCESSOR_IDLE_TICK` without message
 pub async fn process_message(
     client: EVMClient,
     db: &Database,
-    mut rx_channel: Receiver<TxMessage>,
+    rx_channel: &mut TxReceiver,
+    in_flight: InFlightRegistry,
+    health: TaskHealth,
 ) {
-    while let Some(message) = rx_channel.recv().await {
-        info!("Message received in evm tx processor {:?}", &message);
-        match message.accion {
-            types::Function::Mint => {
-                if let Some(mint_data) = message.mint_data {
-                    let tx_result = mint_new_token(
-                        client.clone(),
-                        db,
+    loop {
+        health.beat(Timestamp::now());
+        let message = match tokio::time::timeout(PROCESSOR_IDLE_TICK, rx_channel.recv()).await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(_) => continue,
+        };
+        info!(
+            "Message received in evm tx processor {}",
+            LoggableMessage::new(&message)
+        );
+        if let Some(detail) = debug_detail(&message) {
+            debug!("Full message in evm tx processor {detail}");
+        }
+        let received_at = Instant::now();
+        match &message {
+            TxMessage::Mint(mint_data) => {
+                // The mint stays in flight until the sweep sees it settled, so a queued
+                // duplicate is never sent while the first one is unconfirmed
+                let sent = in_flight
+                    .send_mint(
                         &mint_data.request_id,
-                        &mint_data.token_metadata,
+                        db,
+                        mint_new_token(
+                            client.clone(),
+                            db,
+                            &mint_data.request_id,
+                            &mint_data.token_metadata,
+                        )
+                        .instrument(message.span(&Chains::EVM)),
                     )
                     .await;
-                    info!("Transaction result {:?}", tx_result);
+                let Some(tx_result) = sent else {
+                    info!(
+                        "Dropping duplicated mint message for request {}",
+                        &mint_data.request_id
+                    );
+                    continue;
+                };
+                rx_channel.record_processing(&message.function(), received_at);
+                match tx_result {
+                    // Nothing was sent
+                    Ok(tx_hash) if tx_hash.is_empty() => {
+                        in_flight.release(&mint_data.request_id, db)
+                    }
+                    Ok(tx_hash) => info!("Transaction result {tx_hash}"),
+                    Err(e) => types::record_request_error(
+                        db,
+                        &mint_data.request_id,
+                        ErrorComponent::Evm,
+                        &e,
+                        client.clock.now(),
+                    ),
                 }
             }
-            // TODO not used yet
-            types::Function::NewRequest => {
-                if let Some(request_data) = message.request_data {
-                    initialize_evm_request(
-                        client.clone(),
-                        &request_data.token_contract,
-                        &request_data.token_owner,
-                        &request_data.token_id,
-                        &request_data.request_id,
-                    )
-                    .await
-                    .unwrap();
+            TxMessage::Refund(refund_data) => {
+                if !in_flight.try_acquire(&refund_data.request_id, db) {
+                    info!(
+                        "Dropping duplicated refund message for request {}",
+                        &refund_data.request_id
+                    );
+                    continue;
+                }
+                let tx_result = refund_token(client.clone(), db, &refund_data.request_id)
+                    .instrument(message.span(&Chains::EVM))
+                    .await;
+                in_flight.release(&refund_data.request_id, db);
+                rx_channel.record_processing(&message.function(), received_at);
+                match tx_result {
+                    Ok(tx_hash) => info!("Refund transaction result {tx_hash}"),
+                    Err(e) => types::record_request_error(
+                        db,
+                        &refund_data.request_id,
+                        ErrorComponent::Evm,
+                        &e,
+                        client.clock.now(),
+                    ),
+                }
+            }
+            TxMessage::NewRequest(request_data) => {
+                if !in_flight.try_acquire(&request_data.request_id, db) {
+                    info!(
+                        "Dropping duplicated lock message for request {}",
+                        &request_data.request_id
+                    );
+                    continue;
+                }
+                let tx_result = lock_request(client.clone(), db, request_data)
+                    .instrument(message.span(&Chains::EVM))
+                    .await;
+                in_flight.release(&request_data.request_id, db);
+                rx_channel.record_processing(&message.function(), received_at);
+                match tx_result {
+                    Ok(Some(tx_hash)) => info!("Lock transaction result {tx_hash}"),
+                    Ok(None) => info!(
+                        "Lock of request {} already handled",
+                        &request_data.request_id
+                    ),
+                    Err(e) => error!(
+                        "Lock of request {} not sent, error {:?}",
+                        &request_data.request_id, e
+                    ),
                 }
             }
         }
@@ -0,0 +1,590 @@
+use std::{
+    fmt::Debug,
+    future::Future,
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alloy::{
+    eips::{eip1559::Eip1559Estimation, BlockNumberOrTag},
+    primitives::{keccak256, Address, Bytes, U256},
+    providers::Provider,
+    rpc::types::TransactionRequest,
+};
+use log::{info, warn};
+use types::{is_timeout, with_timeout, BridgeError, Result};
+
+use crate::{provider_rpc, EVMClient};
+
+/// Fees are the node estimate unchanged by default
+pub const DEFAULT_FEE_MULTIPLIER_PERCENT: u64 = 100;
+/// Lowest priority fee sent, 1 gwei
+pub const DEFAULT_MIN_PRIORITY_FEE: u128 = 1_000_000_000;
+
+/// Adjustments of the node fee estimates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeSettings {
+    /// Applied to the estimated fees and gas price, in percent
+    pub multiplier_percent: u64,
+    /// Floor of the priority fee of EIP-1559 transactions, in wei
+    pub min_priority_fee: u128,
+    /// How the gas price is read from the chain
+    pub strategy: FeeStrategyKind,
+}
+
+impl Default for FeeSettings {
+    fn default() -> Self {
+        FeeSettings {
+            multiplier_percent: DEFAULT_FEE_MULTIPLIER_PERCENT,
+            min_priority_fee: DEFAULT_MIN_PRIORITY_FEE,
+            strategy: FeeStrategyKind::Auto,
+        }
+    }
+}
+
+impl FeeSettings {
+    pub fn from_config(multiplier_percent: Option<u64>, min_priority_fee: Option<u64>) -> Self {
+        FeeSettings {
+            multiplier_percent: multiplier_percent.unwrap_or(DEFAULT_FEE_MULTIPLIER_PERCENT),
+            min_priority_fee: min_priority_fee.map_or(DEFAULT_MIN_PRIORITY_FEE, u128::from),
+            strategy: FeeStrategyKind::Auto,
+        }
+    }
+
+    pub fn with_strategy(mut self, strategy: FeeStrategyKind) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    fn scale(&self, fee: u128) -> u128 {
+        fee.saturating_mul(u128::from(self.multiplier_percent)) / 100
+    }
+}
+
+/// Transaction type the chain accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeMode {
+    Eip1559,
+    /// Chains without a base fee, transactions carry a gas price
+    Legacy,
+}
+
+impl FeeMode {
+    /// Mode of a chain from the base fee of its latest block
+    pub fn from_base_fee(base_fee_per_gas: Option<u64>) -> Self {
+        match base_fee_per_gas {
+            Some(_) => FeeMode::Eip1559,
+            None => FeeMode::Legacy,
+        }
+    }
+}
+
+/// Fee mode of the chain shared by the clones of the client, None until detected
+#[derive(Debug, Clone, Default)]
+pub struct SharedFeeMode(Arc<Mutex<Option<FeeMode>>>);
+
+impl SharedFeeMode {
+    pub fn get(&self) -> Option<FeeMode> {
+        *self.0.lock().unwrap()
+    }
+
+    pub fn set(&self, mode: FeeMode) {
+        *self.0.lock().unwrap() = Some(mode);
+    }
+}
+
+/// Gas price of the relayer transactions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GasPricing {
+    Eip1559 {
+        max_fee_per_gas: u128,
+        max_priority_fee_per_gas: u128,
+    },
+    Legacy {
+        gas_price: u128,
+    },
+}
+
+impl GasPricing {
+    /// EIP-1559 fees from the node estimate with the multiplier applied. A priority fee
+    /// raised to the floor raises the max fee by the same amount
+    pub fn eip1559(estimate: Eip1559Estimation, settings: &FeeSettings) -> Self {
+        let priority = settings.scale(estimate.max_priority_fee_per_gas);
+        let floored = priority.max(settings.min_priority_fee);
+        let max_fee = settings
+            .scale(estimate.max_fee_per_gas)
+            .saturating_add(floored - priority)
+            .max(floored);
+        GasPricing::Eip1559 {
+            max_fee_per_gas: max_fee,
+            max_priority_fee_per_gas: floored,
+        }
+    }
+
+    /// Gas price of a legacy transaction from `eth_gasPrice` with the multiplier applied
+    pub fn legacy(gas_price: u128, settings: &FeeSettings) -> Self {
+        GasPricing::Legacy {
+            gas_price: settings.scale(gas_price),
+        }
+    }
+
+    pub fn apply(&self, tx: &mut TransactionRequest) {
+        match *self {
+            GasPricing::Eip1559 {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            } => {
+                tx.max_fee_per_gas = Some(max_fee_per_gas);
+                tx.max_priority_fee_per_gas = Some(max_priority_fee_per_gas);
+            }
+            GasPricing::Legacy { gas_price } => {
+                tx.gas_price = Some(gas_price);
+                tx.max_fee_per_gas = None;
+                tx.max_priority_fee_per_gas = None;
+            }
+        }
+    }
+}
+
+/// Reads the base fee of the latest block and keeps the fee mode in the client. Called
+/// when the client is created and when the event provider reconnects
+pub async fn detect_fee_mode(client: &EVMClient) -> Result<FeeMode> {
+    let provider = provider_rpc(client.clone())?;
+    let latest = with_timeout(
+        "get_block_by_number",
+        client.timeouts.read,
+        provider.get_block_by_number(BlockNumberOrTag::Latest),
+    )
+    .await?
+    .ok_or_else(|| BridgeError::Other("Latest EVM block not found".to_string()))?;
+
+    let mode = FeeMode::from_base_fee(latest.header.base_fee_per_gas);
+    if client.fee_mode.get() != Some(mode) {
+        info!("EVM fee mode: {mode:?}");
+    }
+    client.fee_mode.set(mode);
+    Ok(mode)
+}
+
+pub type PricingFuture<'a> = Pin<Box<dyn Future<Output = Result<GasPricing>> + Send + 'a>>;
+
+/// Source of the gas price of the relayer transactions on a chain
+pub trait FeeStrategy: Debug + Send + Sync {
+    /// Name of the strategy in `EVM_FEE_STRATEGY`
+    fn name(&self) -> &'static str;
+
+    /// Gas price of the next transaction of `client`, with the fee settings applied
+    fn pricing<'a>(&'a self, client: &'a EVMClient) -> PricingFuture<'a>;
+}
+
+async fn legacy_pricing(client: &EVMClient) -> Result<GasPricing> {
+    let provider = provider_rpc(client.clone())?;
+    let gas_price = with_timeout(
+        "get_gas_price",
+        client.timeouts.read,
+        provider.get_gas_price(),
+    )
+    .await?;
+    Ok(GasPricing::legacy(gas_price, &client.fee_settings))
+}
+
+async fn eip1559_pricing(client: &EVMClient) -> Result<GasPricing> {
+    let provider = provider_rpc(client.clone())?;
+    let estimate = with_timeout(
+        "estimate_eip1559_fees",
+        client.timeouts.read,
+        provider.estimate_eip1559_fees(),
+    )
+    .await?;
+    Ok(GasPricing::eip1559(estimate, &client.fee_settings))
+}
+
+/// Fee mode detected from the latest block. Nodes answering the fee history with null
+/// rewards fail the EIP-1559 estimate, the transaction is then priced with `eth_gasPrice`
+#[derive(Debug, Clone, Copy)]
+pub struct DetectedFees;
+
+impl FeeStrategy for DetectedFees {
+    fn name(&self) -> &'static str {
+        "auto"
+    }
+
+    fn pricing<'a>(&'a self, client: &'a EVMClient) -> PricingFuture<'a> {
+        Box::pin(async move {
+            let mode = match client.fee_mode.get() {
+                Some(mode) => mode,
+                None => detect_fee_mode(client).await?,
+            };
+            match mode {
+                FeeMode::Legacy => legacy_pricing(client).await,
+                FeeMode::Eip1559 => match eip1559_pricing(client).await {
+                    Ok(pricing) => Ok(pricing),
+                    Err(e) if is_timeout(&e) => Err(e),
+                    Err(e) => {
+                        warn!("EIP-1559 fee estimate failed, using eth_gasPrice: {e}");
+                        legacy_pricing(client).await
+                    }
+                },
+            }
+        })
+    }
+}
+
+/// EIP-1559 fees from `eth_feeHistory`, whatever the latest block
+#[derive(Debug, Clone, Copy)]
+pub struct Eip1559Fees;
+
+impl FeeStrategy for Eip1559Fees {
+    fn name(&self) -> &'static str {
+        "eip1559"
+    }
+
+    fn pricing<'a>(&'a self, client: &'a EVMClient) -> PricingFuture<'a> {
+        Box::pin(eip1559_pricing(client))
+    }
+}
+
+/// Legacy transactions priced with `eth_gasPrice`
+#[derive(Debug, Clone, Copy)]
+pub struct LegacyFees;
+
+impl FeeStrategy for LegacyFees {
+    fn name(&self) -> &'static str {
+        "legacy"
+    }
+
+    fn pricing<'a>(&'a self, client: &'a EVMClient) -> PricingFuture<'a> {
+        Box::pin(legacy_pricing(client))
+    }
+}
+
+/// Gas oracle of an L2 whose gas price includes the L1 data fees, read with a view
+/// method without arguments returning uint256 words. The word at `result_index` is the
+/// total price per gas in wei, sent as the gas price of a legacy transaction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct L2OracleFees {
+    pub oracle: Address,
+    pub selector: [u8; 4],
+    pub result_index: usize,
+}
+
+impl L2OracleFees {
+    /// Oracle at `oracle` called with `method`, a signature like `getPricesInWei()`
+    pub fn new(oracle: &str, method: &str, result_index: usize) -> Result<Self> {
+        let oracle = Address::from_str(oracle)
+            .map_err(|e| BridgeError::Other(format!("Invalid fee oracle address {oracle}: {e}")))?;
+        let valid_name = method.strip_suffix("()").is_some_and(|name| {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+        if !valid_name {
+            return Err(BridgeError::Other(format!(
+                "Invalid fee oracle method {method}, expected a signature without arguments like getPricesInWei()"
+            )));
+        }
+        let mut selector = [0; 4];
+        selector.copy_from_slice(&keccak256(method.as_bytes())[..4]);
+        Ok(L2OracleFees {
+            oracle,
+            selector,
+            result_index,
+        })
+    }
+
+    /// `eth_call` of the oracle method
+    pub fn call_request(&self) -> TransactionRequest {
+        TransactionRequest::default()
+            .to(self.oracle)
+            .input(Bytes::copy_from_slice(&self.selector).into())
+    }
+
+    /// Price per gas read from the oracle through `provider`
+    pub async fn gas_price<P: Provider>(&self, provider: &P, timeout: Duration) -> Result<u128> {
+        let output = with_timeout(
+            "fee_oracle_call",
+            timeout,
+            provider.call(self.call_request()),
+        )
+        .await?;
+        let start = self.result_index * 32;
+        let word = output.get(start..start + 32).ok_or_else(|| {
+            BridgeError::Other(format!(
+                "Fee oracle {} returned {} bytes, no word {}",
+                self.oracle,
+                output.len(),
+                self.result_index
+            ))
+        })?;
+        u128::try_from(U256::from_be_slice(word)).map_err(|_| {
+            BridgeError::Other(format!(
+                "Fee oracle {} returned an out of range gas price",
+                self.oracle
+            ))
+        })
+    }
+}
+
+impl FeeStrategy for L2OracleFees {
+    fn name(&self) -> &'static str {
+        "l2_oracle"
+    }
+
+    fn pricing<'a>(&'a self, client: &'a EVMClient) -> PricingFuture<'a> {
+        Box::pin(async move {
+            let provider = provider_rpc(client.clone())?;
+            let gas_price = self.gas_price(&provider, client.timeouts.read).await?;
+            Ok(GasPricing::legacy(gas_price, &client.fee_settings))
+        })
+    }
+}
+
+/// Fee strategy of the chain named by `EVM_FEE_STRATEGY`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FeeStrategyKind {
+    #[default]
+    Auto,
+    Eip1559,
+    Legacy,
+    L2Oracle(L2OracleFees),
+}
+
+impl FeeStrategyKind {
+    /// Strategy named `strategy`, auto by default. The L2 oracle needs its address and
+    /// method, its result index defaults to the first word
+    pub fn from_config(
+        strategy: Option<&str>,
+        oracle_address: Option<&str>,
+        oracle_method: Option<&str>,
+        oracle_result_index: Option<usize>,
+    ) -> Result<Self> {
+        match strategy.map(str::trim).unwrap_or("auto") {
+            "auto" => Ok(FeeStrategyKind::Auto),
+            "eip1559" => Ok(FeeStrategyKind::Eip1559),
+            "legacy" => Ok(FeeStrategyKind::Legacy),
+            "l2_oracle" => {
+                let (Some(address), Some(method)) = (oracle_address, oracle_method) else {
+                    return Err(BridgeError::Other("The l2_oracle fee strategy needs EVM_FEE_ORACLE_ADDRESS and EVM_FEE_ORACLE_METHOD".to_string()));
+                };
+                Ok(FeeStrategyKind::L2Oracle(L2OracleFees::new(
+                    address,
+                    method,
+                    oracle_result_index.unwrap_or_default(),
+                )?))
+            }
+            other => Err(BridgeError::Other(format!(
+                "Unknown fee strategy {other}, expected auto, eip1559, legacy or l2_oracle"
+            ))),
+        }
+    }
+
+    pub fn strategy(&self) -> &dyn FeeStrategy {
+        match self {
+            FeeStrategyKind::Auto => &DetectedFees,
+            FeeStrategyKind::Eip1559 => &Eip1559Fees,
+            FeeStrategyKind::Legacy => &LegacyFees,
+            FeeStrategyKind::L2Oracle(oracle) => oracle,
+        }
+    }
+}
+
+/// Gas price of the next transaction from the fee strategy of the chain
+pub async fn gas_pricing(client: EVMClient) -> Result<GasPricing> {
+    client
+        .fee_settings
+        .strategy
+        .strategy()
+        .pricing(&client)
+        .await
+}
+
+#[cfg(test)]
+mod fees_test {
+    use std::time::Duration;
+
+    use alloy::{
+        eips::eip1559::Eip1559Estimation,
+        primitives::{Address, Bytes, TxKind, U256},
+        providers::ProviderBuilder,
+        rpc::types::TransactionRequest,
+        sol,
+        sol_types::{SolCall, SolValue},
+        transport::mock::Asserter,
+    };
+
+    use crate::{FeeMode, FeeSettings, FeeStrategyKind, GasPricing, L2OracleFees, SharedFeeMode};
+
+    sol! {
+        interface ArbGasInfo {
+            function getPricesInWei() external view returns (uint256, uint256, uint256, uint256, uint256, uint256);
+        }
+    }
+
+    const ARB_GAS_INFO: &str = "0x000000000000000000000000000000000000006C";
+
+    const GWEI: u128 = 1_000_000_000;
+
+    fn estimate(max_fee_per_gas: u128, max_priority_fee_per_gas: u128) -> Eip1559Estimation {
+        Eip1559Estimation {
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        }
+    }
+
+    #[test]
+    fn test_legacy_pricing_builds_gas_price_transactions() {
+        assert_eq!(FeeMode::from_base_fee(None), FeeMode::Legacy);
+        assert_eq!(FeeMode::from_base_fee(Some(7)), FeeMode::Eip1559);
+
+        let fee_mode = SharedFeeMode::default();
+        assert_eq!(fee_mode.get(), None);
+        fee_mode.clone().set(FeeMode::Legacy);
+        assert_eq!(fee_mode.get(), Some(FeeMode::Legacy));
+
+        let settings = FeeSettings::from_config(Some(120), None);
+        let pricing = GasPricing::legacy(10 * GWEI, &settings);
+        assert_eq!(
+            pricing,
+            GasPricing::Legacy {
+                gas_price: 12 * GWEI
+            }
+        );
+
+        let mut tx = TransactionRequest {
+            max_fee_per_gas: Some(GWEI),
+            max_priority_fee_per_gas: Some(GWEI),
+            ..Default::default()
+        };
+        pricing.apply(&mut tx);
+        assert_eq!(tx.gas_price, Some(12 * GWEI));
+        assert_eq!(tx.max_fee_per_gas, None);
+        assert_eq!(tx.max_priority_fee_per_gas, None);
+    }
+
+    #[test]
+    fn test_priority_fee_floor() {
+        let settings = FeeSettings::default();
+        // Low base fee chain, the estimate is kept above the floor
+        assert_eq!(
+            GasPricing::eip1559(estimate(5 * GWEI, 2 * GWEI), &settings),
+            GasPricing::Eip1559 {
+                max_fee_per_gas: 5 * GWEI,
+                max_priority_fee_per_gas: 2 * GWEI,
+            }
+        );
+        // 1 wei placeholder of local nodes, raised to the floor with the max fee
+        assert_eq!(
+            GasPricing::eip1559(estimate(1, 1), &settings),
+            GasPricing::Eip1559 {
+                max_fee_per_gas: GWEI,
+                max_priority_fee_per_gas: GWEI,
+            }
+        );
+        // Headroom of the max fee over the priority fee is kept
+        assert_eq!(
+            GasPricing::eip1559(estimate(GWEI + 100, 100), &settings),
+            GasPricing::Eip1559 {
+                max_fee_per_gas: 2 * GWEI,
+                max_priority_fee_per_gas: GWEI,
+            }
+        );
+
+        let settings = FeeSettings::from_config(Some(150), Some(3_000_000_000));
+        assert_eq!(
+            GasPricing::eip1559(estimate(4 * GWEI, 2 * GWEI), &settings),
+            GasPricing::Eip1559 {
+                max_fee_per_gas: 6 * GWEI,
+                max_priority_fee_per_gas: 3 * GWEI,
+            }
+        );
+    }
+
+    #[test]
+    fn test_strategy_selection() {
+        let auto = FeeStrategyKind::from_config(None, None, None, None).unwrap();
+        assert_eq!(auto, FeeStrategyKind::Auto);
+        assert_eq!(auto.strategy().name(), "auto");
+        assert_eq!(FeeSettings::default().strategy, FeeStrategyKind::Auto);
+        for name in ["eip1559", "legacy"] {
+            let kind = FeeStrategyKind::from_config(Some(name), None, None, None).unwrap();
+            assert_eq!(kind.strategy().name(), name);
+        }
+
+        let l2 = FeeStrategyKind::from_config(
+            Some("l2_oracle"),
+            Some(ARB_GAS_INFO),
+            Some("getPricesInWei()"),
+            Some(5),
+        )
+        .unwrap();
+        assert_eq!(l2.strategy().name(), "l2_oracle");
+        let settings = FeeSettings::from_config(Some(110), None).with_strategy(l2);
+        assert_eq!(settings.multiplier_percent, 110);
+        assert_eq!(settings.strategy, l2);
+
+        // The oracle needs its address and a method without arguments
+        assert!(FeeStrategyKind::from_config(Some("l2_oracle"), None, None, None).is_err());
+        for method in ["getPricesInWei", "getL1Fee(bytes)", "()"] {
+            assert!(
+                FeeStrategyKind::from_config(
+                    Some("l2_oracle"),
+                    Some(ARB_GAS_INFO),
+                    Some(method),
+                    None
+                )
+                .is_err(),
+                "{method}"
+            );
+        }
+        assert!(L2OracleFees::new("0x6C", "getPricesInWei()", 0).is_err());
+        assert!(FeeStrategyKind::from_config(Some("arbitrum"), None, None, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_l2_oracle_call() {
+        let oracle = L2OracleFees::new(ARB_GAS_INFO, "getPricesInWei()", 5).unwrap();
+        let request = oracle.call_request();
+        assert_eq!(
+            request.to,
+            Some(TxKind::Call(Address::with_last_byte(0x6C)))
+        );
+        assert_eq!(
+            request.input.input(),
+            Some(&Bytes::from(ArbGasInfo::getPricesInWeiCall::SELECTOR))
+        );
+
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .on_mocked_client(asserter.clone());
+        let prices = (
+            U256::from(1),
+            U256::from(2),
+            U256::from(3),
+            U256::from(4),
+            U256::from(5),
+            U256::from(20 * GWEI / 100),
+        );
+        asserter.push_success(&Bytes::from(prices.abi_encode_params()));
+        assert_eq!(
+            oracle
+                .gas_price(&provider, Duration::from_secs(1))
+                .await
+                .unwrap(),
+            20 * GWEI / 100
+        );
+
+        // Shorter answers than the configured word are errors
+        asserter.push_success(&Bytes::from(U256::from(1).abi_encode()));
+        let err = oracle
+            .gas_price(&provider, Duration::from_secs(1))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no word 5"), "{err}");
+        asserter.push_failure_msg("execution reverted");
+        assert!(oracle
+            .gas_price(&provider, Duration::from_secs(1))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use log::error;
+
+use crate::EvmError;
+
+/// Margin added to the node gas estimate, in percent
+pub const GAS_MARGIN_PERCENT: u64 = 25;
+pub const DEFAULT_NEW_REQUEST_MAX_GAS: u64 = 300_000;
+pub const DEFAULT_MINT_MAX_GAS: u64 = 1_000_000;
+pub const DEFAULT_REFUND_MAX_GAS: u64 = 300_000;
+
+/// Bridge contract calls sent with an estimated gas limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GasOperation {
+    NewRequest,
+    Mint,
+    Refund,
+}
+
+impl GasOperation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GasOperation::NewRequest => "newBridgeRequest",
+            GasOperation::Mint => "mintToken",
+            GasOperation::Refund => "releaseToken",
+        }
+    }
+}
+
+/// Highest gas limit the relayer sends for each operation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GasLimits {
+    pub new_request: u64,
+    pub mint: u64,
+    pub refund: u64,
+}
+
+impl Default for GasLimits {
+    fn default() -> Self {
+        GasLimits {
+            new_request: DEFAULT_NEW_REQUEST_MAX_GAS,
+            mint: DEFAULT_MINT_MAX_GAS,
+            refund: DEFAULT_REFUND_MAX_GAS,
+        }
+    }
+}
+
+impl GasLimits {
+    pub fn from_config(new_request: Option<u64>, mint: Option<u64>, refund: Option<u64>) -> Self {
+        GasLimits {
+            new_request: new_request.unwrap_or(DEFAULT_NEW_REQUEST_MAX_GAS),
+            mint: mint.unwrap_or(DEFAULT_MINT_MAX_GAS),
+            refund: refund.unwrap_or(DEFAULT_REFUND_MAX_GAS),
+        }
+    }
+
+    pub fn max(&self, operation: GasOperation) -> u64 {
+        match operation {
+            GasOperation::NewRequest => self.new_request,
+            GasOperation::Mint => self.mint,
+            GasOperation::Refund => self.refund,
+        }
+    }
+}
+
+/// Gas limit of a transaction, the estimate plus the margin clamped to `max`. An estimate
+/// over `max` is refused, sending it would only burn the relayer funds
+pub fn bounded_gas_limit(
+    operation: GasOperation,
+    estimate: u64,
+    max: u64,
+) -> Result<u64, EvmError> {
+    if estimate > max {
+        return Err(EvmError::GasLimitExceeded(
+            operation.name().to_string(),
+            estimate,
+            max,
+        ));
+    }
+    let with_margin = estimate.saturating_add(estimate.saturating_mul(GAS_MARGIN_PERCENT) / 100);
+    Ok(with_margin.min(max))
+}
+
+/// Gas limits of the client with the number of refused estimates, exposed as a metric
+#[derive(Debug, Clone, Default)]
+pub struct GasPolicy {
+    pub limits: GasLimits,
+    exceeded: Arc<AtomicU64>,
+}
+
+impl GasPolicy {
+    pub fn new(limits: GasLimits) -> Self {
+        GasPolicy {
+            limits,
+            exceeded: Arc::default(),
+        }
+    }
+
+    /// Gas limit of `operation` for the node estimate, refused estimates are counted
+    pub fn gas_limit(&self, operation: GasOperation, estimate: u64) -> Result<u64, EvmError> {
+        bounded_gas_limit(operation, estimate, self.limits.max(operation)).inspect_err(|e| {
+            self.exceeded.fetch_add(1, Ordering::Relaxed);
+            error!("Gas limit exceeded, transaction not sent: {e}");
+        })
+    }
+
+    pub fn exceeded_count(&self) -> u64 {
+        self.exceeded.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod gas_policy_test {
+    use crate::{bounded_gas_limit, EvmError, GasLimits, GasOperation, GasPolicy};
+
+    #[test]
+    fn test_estimate_margin_and_cap() {
+        // Under the cap with the margin, the margin is added
+        assert_eq!(
+            bounded_gas_limit(GasOperation::Mint, 100_000, 200_000),
+            Ok(125_000)
+        );
+        // Under the cap but not with the margin, clamped to the cap
+        assert_eq!(
+            bounded_gas_limit(GasOperation::Mint, 180_000, 200_000),
+            Ok(200_000)
+        );
+        assert_eq!(
+            bounded_gas_limit(GasOperation::Mint, 200_000, 200_000),
+            Ok(200_000)
+        );
+        // Over the cap, not sent
+        assert_eq!(
+            bounded_gas_limit(GasOperation::NewRequest, 30_000_000, 200_000),
+            Err(EvmError::GasLimitExceeded(
+                "newBridgeRequest".to_string(),
+                30_000_000,
+                200_000
+            ))
+        );
+    }
+
+    #[test]
+    fn test_policy_counts_refused_estimates() {
+        let policy = GasPolicy::new(GasLimits::from_config(Some(100_000), None, None));
+
+        assert_eq!(
+            policy.gas_limit(GasOperation::NewRequest, 60_000),
+            Ok(75_000)
+        );
+        assert_eq!(policy.gas_limit(GasOperation::Mint, 400_000), Ok(500_000));
+        assert_eq!(policy.gas_limit(GasOperation::Refund, 80_000), Ok(100_000));
+        assert!(policy.gas_limit(GasOperation::NewRequest, 150_000).is_err());
+        assert_eq!(policy.exceeded_count(), 1);
+        // Clones share the count
+        assert!(policy
+            .clone()
+            .gas_limit(GasOperation::Mint, 5_000_000)
+            .is_err());
+        assert_eq!(policy.exceeded_count(), 2);
+    }
+}
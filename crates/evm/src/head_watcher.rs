@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+
+use alloy::providers::Provider;
+use futures_util::stream::StreamExt;
+use log::{error, info};
+use types::{BridgeError, ChainHead, ChainHeadSender, Chains, Result, Timestamp};
+
+use crate::{get_latest_block_number, provider_ws, EVMClient};
+
+const HEAD_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Publishes the EVM chain head, subscribing to new heads and polling
+/// get_block_number while the subscription is down. Failed subscriptions are recorded
+/// in the health of the websocket endpoint, the polls in the one of the RPC endpoint
+pub async fn watch_chain_head(client: EVMClient, head_tx: ChainHeadSender) {
+    loop {
+        let start = Instant::now();
+        if let Err(e) = subscribe_heads(client.clone(), &head_tx).await {
+            error!("EVM new heads subscription failed, polling instead: {}", e);
+            client.rpc_health.record(
+                &Chains::EVM,
+                &client.ws_url(),
+                false,
+                start.elapsed(),
+                Timestamp::now(),
+            );
+        }
+
+        match get_latest_block_number(&client).await {
+            Ok(number) => {
+                head_tx.send_replace(ChainHead::new(number));
+            }
+            Err(e) => error!("Could not read EVM block number: {}", e),
+        }
+        tokio::time::sleep(HEAD_POLL_INTERVAL).await;
+    }
+}
+
+async fn subscribe_heads(client: EVMClient, head_tx: &ChainHeadSender) -> Result<()> {
+    let provider = provider_ws(client).await?;
+    let mut stream = provider
+        .subscribe_blocks()
+        .await
+        .map_err(BridgeError::from_error)?
+        .into_stream();
+
+    info!("Listening for evm new heads...");
+    while let Some(header) = stream.next().await {
+        head_tx.send_replace(ChainHead::new(header.number));
+    }
+    Ok(())
+}
@@ -11,3 +11,29 @@ pub use evm_txs::*;
 
 pub mod calls;
 pub use calls::*;
+
+pub mod head_watcher;
+pub use head_watcher::*;
+
+pub mod errors;
+pub use errors::*;
+
+pub mod authorization;
+pub use authorization::*;
+
+pub mod gas_policy;
+pub use gas_policy::*;
+
+pub mod fees;
+pub use fees::*;
+
+pub mod backfill;
+pub use backfill::*;
+
+pub mod transferability;
+pub use transferability::*;
+pub mod ownership;
+pub use ownership::*;
+
+pub mod send_lookup;
+pub use send_lookup::*;
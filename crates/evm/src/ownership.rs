@@ -0,0 +1,326 @@
+use alloy::{
+    primitives::{Address, FixedBytes, U256},
+    providers::Provider,
+    sol,
+};
+use log::info;
+use std::{str::FromStr, time::Duration};
+use types::{
+    parse_token_id, with_timeout, BridgeError, CallContext, Chains, CustodyChain,
+    OwnerAuthorization, Result, WrapCallContext,
+};
+
+use crate::{provider_rpc, reverted, EVMClient};
+
+sol! {
+    #[sol(rpc)]
+    interface OperatorToken {
+        function ownerOf(uint256 tokenId) external view returns (address);
+        function isApprovedForAll(address owner, address operator) external view returns (bool);
+    }
+
+    #[sol(rpc)]
+    interface DelegateRegistryV1 {
+        function checkDelegateForToken(address delegate, address vault, address contract_, uint256 tokenId) external view returns (bool);
+    }
+
+    #[sol(rpc)]
+    interface DelegateRegistryV2 {
+        function checkDelegateForERC721(address to, address from, address contract_, uint256 tokenId, bytes32 rights) external view returns (bool);
+    }
+}
+
+/// Interface of a delegate registry, the delegate.xyz versions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DelegateInterface {
+    /// `checkDelegateForToken(delegate, vault, contract, tokenId)`
+    V1,
+    /// `checkDelegateForERC721(to, from, contract, tokenId, rights)`, checked with the
+    /// full rights
+    V2,
+}
+
+/// Registry of the delegations from token holders to the owners they bridge for
+#[derive(Debug, Clone, PartialEq)]
+pub struct DelegateRegistry {
+    pub address: Address,
+    pub interface: DelegateInterface,
+}
+
+impl DelegateRegistry {
+    /// Registry at `address` with the `interface` named `v1` or `v2`, v2 by default. None
+    /// without address
+    pub fn from_config(address: Option<&str>, interface: Option<&str>) -> Result<Option<Self>> {
+        let Some(address) = address else {
+            return Ok(None);
+        };
+        let address = Address::from_str(address).map_err(|e| {
+            BridgeError::Other(format!("Invalid delegate registry address {address}: {e}"))
+        })?;
+        let interface = match interface.unwrap_or("v2") {
+            "v1" => DelegateInterface::V1,
+            "v2" => DelegateInterface::V2,
+            other => {
+                return Err(BridgeError::Other(format!(
+                    "Unknown delegate registry interface {other}, expected v1 or v2"
+                )))
+            }
+        };
+        Ok(Some(DelegateRegistry { address, interface }))
+    }
+}
+
+/// Ownership of an origin token by the token owner named by a request
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ownership {
+    /// `ownerOf` is the token owner
+    Owner,
+    /// Held by another address that authorizes the token owner
+    Authorized(CustodyChain),
+    /// Held by `holder`, which doesn't authorize the token owner
+    NotOwner { holder: Address },
+}
+
+/// Checks that `claimed` owns a token or is authorized by its holder: as an operator of
+/// the holder, or as its delegate in `registry`. Reverts of the authorization calls are
+/// read as not authorized, other failures are errors
+pub async fn token_ownership<P: Provider>(
+    provider: P,
+    timeout: Duration,
+    token_contract: Address,
+    claimed: Address,
+    token_id: U256,
+    registry: Option<&DelegateRegistry>,
+) -> Result<Ownership> {
+    let contract = OperatorToken::new(token_contract, &provider);
+    let context =
+        |operation: &str| CallContext::new(Chains::EVM, operation).contract(token_contract);
+
+    let holder = with_timeout("ownerOf", timeout, contract.ownerOf(token_id).call())
+        .await
+        .with_call_context(|| context("ownerOf"))?
+        ._0;
+    if holder == claimed {
+        return Ok(Ownership::Owner);
+    }
+
+    let approved = with_timeout("isApprovedForAll", timeout, async {
+        Ok::<_, BridgeError>(contract.isApprovedForAll(holder, claimed).call().await)
+    })
+    .await
+    .with_call_context(|| context("isApprovedForAll"))?;
+    match approved {
+        Ok(approved) if approved._0 => {
+            return Ok(Ownership::Authorized(CustodyChain {
+                holder: holder.to_string(),
+                authorization: OwnerAuthorization::Operator,
+            }));
+        }
+        Ok(_) => {}
+        Err(e) if reverted(&e) => {}
+        Err(e) => return Err(e).with_call_context(|| context("isApprovedForAll")),
+    }
+
+    let Some(registry) = registry else {
+        return Ok(Ownership::NotOwner { holder });
+    };
+    let registry_context =
+        || CallContext::new(Chains::EVM, "checkDelegate").contract(registry.address);
+    // Empty rights only match the delegations of all rights
+    let rights = FixedBytes::ZERO;
+    let delegated = with_timeout("checkDelegate", timeout, async {
+        Ok::<_, BridgeError>(match registry.interface {
+            DelegateInterface::V1 => DelegateRegistryV1::new(registry.address, &provider)
+                .checkDelegateForToken(claimed, holder, token_contract, token_id)
+                .call()
+                .await
+                .map(|delegated| delegated._0),
+            DelegateInterface::V2 => DelegateRegistryV2::new(registry.address, &provider)
+                .checkDelegateForERC721(claimed, holder, token_contract, token_id, rights)
+                .call()
+                .await
+                .map(|delegated| delegated._0),
+        })
+    })
+    .await
+    .with_call_context(registry_context)?;
+    match delegated {
+        Ok(true) => Ok(Ownership::Authorized(CustodyChain {
+            holder: holder.to_string(),
+            authorization: OwnerAuthorization::Delegate {
+                registry: registry.address.to_string(),
+            },
+        })),
+        Ok(false) => Ok(Ownership::NotOwner { holder }),
+        Err(e) if reverted(&e) => Ok(Ownership::NotOwner { holder }),
+        Err(e) => Err(e).with_call_context(registry_context),
+    }
+}
+
+/// Same as `token_ownership` through the RPC node of the client, with its delegate
+/// registry
+pub async fn check_token_ownership(
+    client: EVMClient,
+    token_contract: &str,
+    token_owner: &str,
+    token_id: &str,
+) -> Result<Ownership> {
+    let token_contract = Address::from_str(token_contract).map_err(BridgeError::from_error)?;
+    let claimed = Address::from_str(token_owner).map_err(BridgeError::from_error)?;
+    let token_id = parse_token_id(token_id)?;
+    let provider = provider_rpc(client.clone())?;
+
+    let ownership = token_ownership(
+        provider,
+        client.timeouts.read,
+        token_contract,
+        claimed,
+        token_id,
+        client.delegate_registry.as_ref(),
+    )
+    .await?;
+    if let Ownership::Authorized(chain) = &ownership {
+        info!(
+            "Token {token_id} of {token_contract} held by {} for {claimed}, {:?}",
+            chain.holder, chain.authorization
+        );
+    }
+    Ok(ownership)
+}
+
+#[cfg(test)]
+mod ownership_test {
+    use std::{str::FromStr, time::Duration};
+
+    use alloy::{
+        primitives::{Address, Bytes, U256},
+        providers::{Provider, ProviderBuilder},
+        sol_types::SolValue,
+        transport::mock::Asserter,
+    };
+    use test_support::{EVM_ACCOUNT, EVM_TOKEN_CONTRACT};
+    use types::{CallContext, CustodyChain, OwnerAuthorization, Result};
+
+    use crate::{token_ownership, DelegateInterface, DelegateRegistry, Ownership};
+
+    const VAULT: &str = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+    const REGISTRY: &str = "0x00000000000000447e69651d841bD8D104Bed493";
+
+    fn mocked_token(asserter: &Asserter) -> impl Provider {
+        ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .on_mocked_client(asserter.clone())
+    }
+
+    fn push_address(asserter: &Asserter, address: &str) {
+        let address = Address::from_str(address).unwrap();
+        asserter.push_success(&Bytes::from((address,).abi_encode_params()));
+    }
+
+    fn push_bool(asserter: &Asserter, value: bool) {
+        asserter.push_success(&Bytes::from((value,).abi_encode_params()));
+    }
+
+    async fn check(asserter: &Asserter, registry: Option<&DelegateRegistry>) -> Result<Ownership> {
+        token_ownership(
+            mocked_token(asserter),
+            Duration::from_secs(1),
+            Address::from_str(EVM_TOKEN_CONTRACT).unwrap(),
+            Address::from_str(EVM_ACCOUNT).unwrap(),
+            U256::from(1),
+            registry,
+        )
+        .await
+    }
+
+    fn held_by_vault(authorization: OwnerAuthorization) -> Ownership {
+        Ownership::Authorized(CustodyChain {
+            holder: Address::from_str(VAULT).unwrap().to_string(),
+            authorization,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_owner_and_operator_of_the_vault() {
+        let asserter = Asserter::new();
+        push_address(&asserter, EVM_ACCOUNT);
+        assert_eq!(check(&asserter, None).await.unwrap(), Ownership::Owner);
+
+        push_address(&asserter, VAULT);
+        push_bool(&asserter, true);
+        assert_eq!(
+            check(&asserter, None).await.unwrap(),
+            held_by_vault(OwnerAuthorization::Operator)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delegate_of_the_vault() {
+        let asserter = Asserter::new();
+        for interface in [DelegateInterface::V1, DelegateInterface::V2] {
+            let registry = DelegateRegistry {
+                address: Address::from_str(REGISTRY).unwrap(),
+                interface,
+            };
+            push_address(&asserter, VAULT);
+            push_bool(&asserter, false);
+            push_bool(&asserter, true);
+            assert_eq!(
+                check(&asserter, Some(&registry)).await.unwrap(),
+                held_by_vault(OwnerAuthorization::Delegate {
+                    registry: registry.address.to_string(),
+                })
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized_owner() {
+        let asserter = Asserter::new();
+        let vault = Address::from_str(VAULT).unwrap();
+        let registry = DelegateRegistry {
+            address: Address::from_str(REGISTRY).unwrap(),
+            interface: DelegateInterface::V2,
+        };
+
+        // Neither operator nor delegate, reverting checks are not authorizations
+        push_address(&asserter, VAULT);
+        push_bool(&asserter, false);
+        push_bool(&asserter, false);
+        assert_eq!(
+            check(&asserter, Some(&registry)).await.unwrap(),
+            Ownership::NotOwner { holder: vault }
+        );
+        push_address(&asserter, VAULT);
+        asserter.push_failure_msg("execution reverted");
+        assert_eq!(
+            check(&asserter, None).await.unwrap(),
+            Ownership::NotOwner { holder: vault }
+        );
+
+        // Node failures are errors
+        push_address(&asserter, VAULT);
+        push_bool(&asserter, false);
+        asserter.push_failure_msg("header not found");
+        let err = check(&asserter, Some(&registry)).await.unwrap_err();
+        assert_eq!(CallContext::of(&err).unwrap().operation, "checkDelegate");
+    }
+
+    #[test]
+    fn test_registry_config() {
+        assert_eq!(
+            DelegateRegistry::from_config(None, Some("v1")).unwrap(),
+            None
+        );
+        let registry = DelegateRegistry::from_config(Some(REGISTRY), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(registry.interface, DelegateInterface::V2);
+        let registry = DelegateRegistry::from_config(Some(REGISTRY), Some("v1"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(registry.interface, DelegateInterface::V1);
+        assert!(DelegateRegistry::from_config(Some(REGISTRY), Some("v3")).is_err());
+        assert!(DelegateRegistry::from_config(Some("0xregistry"), None).is_err());
+    }
+}
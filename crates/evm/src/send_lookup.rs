@@ -0,0 +1,148 @@
+use alloy::{
+    primitives::{Address, B256},
+    providers::{Provider, WalletProvider},
+};
+use std::{str::FromStr, time::Duration};
+use types::{
+    with_timeout, BridgeError, CallContext, Chains, IntentLookup, IntentLookupFuture, Result,
+    SendIntent, SendLookup, WrapCallContext,
+};
+
+use crate::{provider_rpc, EVMClient};
+
+/// Looks for the transaction of an EVM intent: by its hash, then by its nonce among the
+/// transactions of `signer`. A nonce the chain or the mempool used for another
+/// transaction means the intent one can't land. A free nonce is reused by the next send,
+/// only one of the two transactions can land
+pub async fn find_sent_tx<P: Provider>(
+    provider: &P,
+    timeout: Duration,
+    signer: Address,
+    intent: &SendIntent,
+) -> Result<IntentLookup> {
+    let context =
+        |operation: &str| CallContext::new(Chains::EVM, operation).request(&intent.request_id);
+
+    if let Some(tx_hash) = &intent.tx_hash {
+        let hash = B256::from_str(tx_hash).map_err(BridgeError::from_error)?;
+        // Whether the node knows the transaction, mined or pending, its content doesn't matter
+        let known: Option<serde_json::Value> = with_timeout(
+            "get_transaction_by_hash",
+            timeout,
+            provider.raw_request("eth_getTransactionByHash".into(), (hash,)),
+        )
+        .await
+        .with_call_context(|| context("get_transaction_by_hash"))?;
+        if known.is_some() {
+            return Ok(IntentLookup::Found(tx_hash.clone()));
+        }
+    }
+
+    let Some(nonce) = intent.nonce else {
+        return Ok(IntentLookup::NotFound);
+    };
+    let mined = with_timeout(
+        "get_transaction_count",
+        timeout,
+        provider.get_transaction_count(signer).latest(),
+    )
+    .await
+    .with_call_context(|| context("get_transaction_count"))?;
+    if mined > nonce {
+        return Ok(IntentLookup::NotFound);
+    }
+    let pending = with_timeout(
+        "get_transaction_count",
+        timeout,
+        provider.get_transaction_count(signer).pending(),
+    )
+    .await
+    .with_call_context(|| context("get_transaction_count"))?;
+    // A transaction waits with the nonce, the node may have it under another hash
+    Ok(if pending > nonce {
+        IntentLookup::Undecided
+    } else {
+        IntentLookup::NotFound
+    })
+}
+
+/// Looks up the EVM intents through the RPC node of the client, for the relayer wallet
+pub struct EvmSendLookup {
+    pub client: EVMClient,
+}
+
+impl SendLookup for EvmSendLookup {
+    fn lookup<'a>(&'a self, intent: &'a SendIntent) -> IntentLookupFuture<'a> {
+        Box::pin(async move {
+            let provider = provider_rpc(self.client.clone())?;
+            let signer = provider.default_signer_address();
+            find_sent_tx(&provider, self.client.timeouts.read, signer, intent).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod send_lookup_test {
+    use std::{str::FromStr, time::Duration};
+
+    use alloy::{
+        primitives::{Address, U64},
+        providers::ProviderBuilder,
+        transport::mock::Asserter,
+    };
+    use serde_json::{json, Value};
+    use test_support::EVM_ACCOUNT;
+    use types::{Chains, IntentLookup, SendIntent, SendOperation};
+
+    use crate::find_sent_tx;
+
+    const TX_HASH: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    async fn lookup(asserter: &Asserter) -> IntentLookup {
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .on_mocked_client(asserter.clone());
+        let mut intent = SendIntent::new("request", Chains::EVM, SendOperation::Mint);
+        intent.nonce = Some(5);
+        intent.tx_hash = Some(TX_HASH.to_string());
+        find_sent_tx(
+            &provider,
+            Duration::from_secs(1),
+            Address::from_str(EVM_ACCOUNT).unwrap(),
+            &intent,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_mint_found_by_hash_is_not_sent_again() {
+        let asserter = Asserter::new();
+        asserter.push_success(&json!({ "hash": TX_HASH, "nonce": "0x5" }));
+        assert_eq!(
+            lookup(&asserter).await,
+            IntentLookup::Found(TX_HASH.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mint_found_by_nonce() {
+        // Unknown to the node, its nonce used by another transaction: sent again
+        let asserter = Asserter::new();
+        asserter.push_success(&Value::Null);
+        asserter.push_success(&U64::from(6));
+        assert_eq!(lookup(&asserter).await, IntentLookup::NotFound);
+
+        // Nonce still free, the resend reuses it
+        asserter.push_success(&Value::Null);
+        asserter.push_success(&U64::from(5));
+        asserter.push_success(&U64::from(5));
+        assert_eq!(lookup(&asserter).await, IntentLookup::NotFound);
+
+        // A transaction waits with the nonce, not sent again
+        asserter.push_success(&Value::Null);
+        asserter.push_success(&U64::from(5));
+        asserter.push_success(&U64::from(6));
+        assert_eq!(lookup(&asserter).await, IntentLookup::Undecided);
+    }
+}
@@ -0,0 +1,259 @@
+use alloy::{
+    hex,
+    primitives::{Address, FixedBytes, U256},
+    providers::Provider,
+    sol,
+    sol_types::decode_revert_reason,
+};
+use log::info;
+use std::{str::FromStr, time::Duration};
+use types::{
+    parse_token_id, with_timeout, BridgeError, CallContext, Chains, Result, WrapCallContext,
+};
+
+use crate::{provider_rpc, reverted, EVMClient};
+
+/// ERC-165 id of the ERC-5192 minimal soulbound interface
+pub const ERC5192_INTERFACE_ID: [u8; 4] = [0xb4, 0x5a, 0x3c, 0x0e];
+
+sol! {
+    #[sol(rpc)]
+    interface SoulboundToken {
+        function supportsInterface(bytes4 interfaceId) external view returns (bool);
+        function locked(uint256 tokenId) external view returns (bool);
+        function transferFrom(address from, address to, uint256 tokenId) external;
+    }
+}
+
+/// Whether an origin token can be moved to the bridge custody
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transferability {
+    Transferable,
+    /// Locked by ERC-5192 or reverting the transfer, with the reason
+    NotTransferable(String),
+}
+
+/// Reason of a reverted call: the `Error(string)` or panic decoded from the revert data,
+/// the selector of a custom error, or the message of the node
+fn revert_reason(err: &alloy::contract::Error) -> String {
+    if let alloy::contract::Error::TransportError(e) = err {
+        if let Some(payload) = e.as_error_resp() {
+            if let Some(data) = payload.as_revert_data() {
+                if let Some(reason) = decode_revert_reason(&data) {
+                    return reason;
+                }
+                if data.len() >= 4 {
+                    return format!("custom error 0x{}", hex::encode(&data[..4]));
+                }
+            }
+            return payload.message.to_string();
+        }
+    }
+    err.to_string()
+}
+
+/// Checks that `owner` can transfer a token to the bridge before the bridge request is
+/// sent. Tokens locked by ERC-5192 are not transferable, then `transferFrom` to the bridge
+/// is simulated from the owner to catch other transfer restrictions. Contracts without
+/// ERC-165 are only simulated. Node failures are errors
+pub async fn token_transferability<P: Provider>(
+    provider: P,
+    timeout: Duration,
+    token_contract: Address,
+    owner: Address,
+    bridge: Address,
+    token_id: U256,
+) -> Result<Transferability> {
+    let contract = SoulboundToken::new(token_contract, provider);
+    let context =
+        |operation: &str| CallContext::new(Chains::EVM, operation).contract(token_contract);
+
+    let supports = with_timeout("supportsInterface", timeout, async {
+        Ok::<_, BridgeError>(
+            contract
+                .supportsInterface(FixedBytes(ERC5192_INTERFACE_ID))
+                .call()
+                .await,
+        )
+    })
+    .await
+    .with_call_context(|| context("supportsInterface"))?;
+    let soulbound = match supports {
+        Ok(supports) => supports._0,
+        Err(e) if reverted(&e) => false,
+        Err(e) => return Err(e).with_call_context(|| context("supportsInterface")),
+    };
+
+    if soulbound {
+        let locked = with_timeout("locked", timeout, async {
+            Ok::<_, BridgeError>(contract.locked(token_id).call().await)
+        })
+        .await
+        .with_call_context(|| context("locked"))?;
+        match locked {
+            Ok(locked) if locked._0 => {
+                return Ok(Transferability::NotTransferable(format!(
+                    "token {token_id} of {token_contract} is locked (ERC-5192)"
+                )));
+            }
+            Ok(_) => {}
+            // Left to the simulation, `locked` reverts for unknown tokens
+            Err(e) if reverted(&e) => {}
+            Err(e) => return Err(e).with_call_context(|| context("locked")),
+        }
+    }
+
+    let transfer = with_timeout("transferFrom", timeout, async {
+        Ok::<_, BridgeError>(
+            contract
+                .transferFrom(owner, bridge, token_id)
+                .from(owner)
+                .call()
+                .await,
+        )
+    })
+    .await
+    .with_call_context(|| context("transferFrom"))?;
+    match transfer {
+        Ok(_) => Ok(Transferability::Transferable),
+        Err(e) if reverted(&e) => Ok(Transferability::NotTransferable(format!(
+            "transfer of token {token_id} of {token_contract} reverted: {}",
+            revert_reason(&e)
+        ))),
+        Err(e) => Err(e).with_call_context(|| context("transferFrom")),
+    }
+}
+
+/// Same as `token_transferability` through the RPC node of the client, to the bridge
+/// contract of the client
+pub async fn check_token_transferable(
+    client: EVMClient,
+    token_contract: &str,
+    token_owner: &str,
+    token_id: &str,
+) -> Result<Transferability> {
+    let token_contract = Address::from_str(token_contract).map_err(BridgeError::from_error)?;
+    let owner = Address::from_str(token_owner).map_err(BridgeError::from_error)?;
+    let token_id = parse_token_id(token_id)?;
+    let provider = provider_rpc(client.clone())?;
+
+    let transferability = token_transferability(
+        provider,
+        client.timeouts.read,
+        token_contract,
+        owner,
+        client.bridge_contract,
+        token_id,
+    )
+    .await?;
+    if let Transferability::NotTransferable(reason) = &transferability {
+        info!("Token {token_id} of {token_contract} can't be bridged: {reason}");
+    }
+    Ok(transferability)
+}
+
+#[cfg(test)]
+mod transferability_test {
+    use std::{borrow::Cow, str::FromStr, time::Duration};
+
+    use alloy::{
+        primitives::{Address, Bytes, U256},
+        providers::{Provider, ProviderBuilder},
+        rpc::json_rpc::ErrorPayload,
+        sol,
+        sol_types::{SolError, SolValue},
+        transport::mock::Asserter,
+    };
+    use test_support::{EVM_ACCOUNT, EVM_TOKEN_CONTRACT};
+    use types::{CallContext, Result};
+
+    use crate::{token_transferability, Transferability};
+
+    sol! {
+        error TransferRestricted(address owner);
+    }
+
+    const BRIDGE: &str = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+
+    fn mocked_token(asserter: &Asserter) -> impl Provider {
+        ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .on_mocked_client(asserter.clone())
+    }
+
+    fn push_bool(asserter: &Asserter, value: bool) {
+        asserter.push_success(&Bytes::from((value,).abi_encode_params()));
+    }
+
+    fn push_revert_data(asserter: &Asserter, data: &[u8]) {
+        let data = format!("\"0x{}\"", alloy::hex::encode(data));
+        asserter.push_failure(ErrorPayload {
+            code: 3,
+            message: Cow::Borrowed("execution reverted"),
+            data: Some(serde_json::value::RawValue::from_string(data).unwrap()),
+        });
+    }
+
+    async fn check(asserter: &Asserter) -> Result<Transferability> {
+        token_transferability(
+            mocked_token(asserter),
+            Duration::from_secs(1),
+            Address::from_str(EVM_TOKEN_CONTRACT).unwrap(),
+            Address::from_str(EVM_ACCOUNT).unwrap(),
+            Address::from_str(BRIDGE).unwrap(),
+            U256::from(1),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_locked_erc5192_token_not_transferable() {
+        let asserter = Asserter::new();
+        push_bool(&asserter, true);
+        push_bool(&asserter, true);
+        let Transferability::NotTransferable(reason) = check(&asserter).await.unwrap() else {
+            panic!("locked token read as transferable");
+        };
+        assert!(reason.contains("ERC-5192"), "{reason}");
+
+        // Unlocked, the transfer is still simulated
+        push_bool(&asserter, true);
+        push_bool(&asserter, false);
+        asserter.push_success(&Bytes::new());
+        assert_eq!(
+            check(&asserter).await.unwrap(),
+            Transferability::Transferable
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transfer_restriction_reason_decoded() {
+        let asserter = Asserter::new();
+        // No ERC-165, the transfer reverts with a custom error
+        asserter.push_failure_msg("execution reverted");
+        let owner = Address::from_str(EVM_ACCOUNT).unwrap();
+        push_revert_data(&asserter, &TransferRestricted { owner }.abi_encode());
+        let Transferability::NotTransferable(reason) = check(&asserter).await.unwrap() else {
+            panic!("restricted token read as transferable");
+        };
+        let selector = alloy::hex::encode(TransferRestricted::SELECTOR);
+        assert!(reason.contains(&selector), "{reason}");
+
+        // Error(string) reverts keep their message
+        push_bool(&asserter, false);
+        push_revert_data(
+            &asserter,
+            &alloy::sol_types::Revert::from("Soulbound: transfer disabled").abi_encode(),
+        );
+        let Transferability::NotTransferable(reason) = check(&asserter).await.unwrap() else {
+            panic!("restricted token read as transferable");
+        };
+        assert!(reason.contains("Soulbound: transfer disabled"), "{reason}");
+
+        // Node failures are errors, not restrictions
+        push_bool(&asserter, false);
+        asserter.push_failure_msg("header not found");
+        let err = check(&asserter).await.unwrap_err();
+        assert_eq!(CallContext::of(&err).unwrap().operation, "transferFrom");
+    }
+}
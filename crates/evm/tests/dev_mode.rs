@@ -0,0 +1,64 @@
+//! Dev mode against a local anvil node, run with `anvil` listening on localhost:8545 and
+//! `cargo test -p evm --test dev_mode -- --ignored`
+
+use evm::{
+    detect_fee_mode, dev_chain_status, evm_initialize, gas_pricing, get_latest_block_number,
+    FeeMode, FeeSettings, GasLimits, GasPricing,
+};
+use types::{
+    tx_channel, BrandingConfig, Chains, ChannelMetrics, MissingUriPolicy, RpcTimeouts, Secret,
+    UriPolicy,
+};
+
+/// First of the default anvil accounts
+const ANVIL_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+const ANVIL_CHAIN_ID: u64 = 31337;
+
+fn dev_client() -> evm::EVMClient {
+    let (tx, _rx) = tx_channel(Chains::SOLANA, 10, &ChannelMetrics::default());
+    evm_initialize(
+        "http://localhost:8545",
+        "ws://localhost:8545",
+        &Secret::from(ANVIL_KEY),
+        "0x5FbDB2315678afecb367f032d93F642f64180aa3",
+        tx,
+        "",
+        UriPolicy::default(),
+        None,
+        BrandingConfig::default(),
+        RpcTimeouts::default(),
+        GasLimits::default(),
+        FeeSettings::default(),
+        MissingUriPolicy::default(),
+        true,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+#[ignore = "needs anvil on localhost:8545"]
+async fn test_dev_mode_against_anvil() {
+    let client = dev_client();
+
+    get_latest_block_number(&client).await.unwrap();
+
+    let status = dev_chain_status(client.clone()).await.unwrap();
+    assert_eq!(status.chain_id, ANVIL_CHAIN_ID);
+    assert!(status.signer_balance.raw() > 0);
+
+    let mode = detect_fee_mode(&client).await.unwrap();
+    match gas_pricing(client).await.unwrap() {
+        GasPricing::Eip1559 {
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        } => {
+            assert_eq!(mode, FeeMode::Eip1559);
+            assert!(max_fee_per_gas > 1);
+            assert!(max_priority_fee_per_gas <= max_fee_per_gas);
+        }
+        GasPricing::Legacy { gas_price } => {
+            assert_eq!(mode, FeeMode::Legacy);
+            assert!(gas_price > 0)
+        }
+    }
+}
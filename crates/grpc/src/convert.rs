@@ -0,0 +1,423 @@
+use types::{
+    BRequest, CallContext, CancelReason, Chains, HistoryEntry, InputRequest, InvalidRequestId,
+    OutputResult, Status, Timestamp,
+};
+
+use crate::proto;
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ConversionError {
+    #[error("Missing field {0}")]
+    MissingField(&'static str),
+
+    #[error("Invalid {0} value {1}")]
+    InvalidEnum(&'static str, i32),
+
+    #[error(transparent)]
+    InvalidRequestId(#[from] InvalidRequestId),
+}
+
+impl From<Status> for proto::Status {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::RequestReceived => proto::Status::RequestReceived,
+            Status::TokenReceived => proto::Status::TokenReceived,
+            Status::TokenMinted => proto::Status::TokenMinted,
+            Status::Completed => proto::Status::Completed,
+            Status::Canceled => proto::Status::Canceled,
+            Status::NeedsDestination => proto::Status::NeedsDestination,
+            Status::RefundEligible => proto::Status::RefundEligible,
+            Status::Refunded => proto::Status::Refunded,
+            Status::Initializing => proto::Status::Initializing,
+            Status::NeedsIntervention => proto::Status::NeedsIntervention,
+            Status::AwaitingDestinationAccount => proto::Status::AwaitingDestinationAccount,
+        }
+    }
+}
+
+/// Status of a raw proto enum value, unspecified and unknown values are rejected
+pub fn status_from_proto(value: i32) -> Result<Status, ConversionError> {
+    match proto::Status::try_from(value) {
+        Ok(proto::Status::RequestReceived) => Ok(Status::RequestReceived),
+        Ok(proto::Status::TokenReceived) => Ok(Status::TokenReceived),
+        Ok(proto::Status::TokenMinted) => Ok(Status::TokenMinted),
+        Ok(proto::Status::Completed) => Ok(Status::Completed),
+        Ok(proto::Status::Canceled) => Ok(Status::Canceled),
+        Ok(proto::Status::NeedsDestination) => Ok(Status::NeedsDestination),
+        Ok(proto::Status::RefundEligible) => Ok(Status::RefundEligible),
+        Ok(proto::Status::Refunded) => Ok(Status::Refunded),
+        Ok(proto::Status::Initializing) => Ok(Status::Initializing),
+        Ok(proto::Status::NeedsIntervention) => Ok(Status::NeedsIntervention),
+        Ok(proto::Status::AwaitingDestinationAccount) => Ok(Status::AwaitingDestinationAccount),
+        Ok(proto::Status::Unspecified) | Err(_) => {
+            Err(ConversionError::InvalidEnum("status", value))
+        }
+    }
+}
+
+impl From<Chains> for proto::Chain {
+    fn from(chain: Chains) -> Self {
+        match chain {
+            Chains::EVM => proto::Chain::Evm,
+            Chains::SOLANA => proto::Chain::Solana,
+        }
+    }
+}
+
+fn chain_from_proto(value: i32) -> Result<Chains, ConversionError> {
+    match proto::Chain::try_from(value) {
+        Ok(proto::Chain::Evm) => Ok(Chains::EVM),
+        Ok(proto::Chain::Solana) => Ok(Chains::SOLANA),
+        Ok(proto::Chain::Unspecified) | Err(_) => {
+            Err(ConversionError::InvalidEnum("origin_network", value))
+        }
+    }
+}
+
+impl From<Timestamp> for proto::UnixTime {
+    fn from(time: Timestamp) -> Self {
+        proto::UnixTime {
+            millis: time.as_millis(),
+        }
+    }
+}
+
+impl From<proto::UnixTime> for Timestamp {
+    fn from(time: proto::UnixTime) -> Self {
+        Timestamp::from_millis(time.millis)
+    }
+}
+
+fn required<T>(value: Option<T>, field: &'static str) -> Result<T, ConversionError> {
+    value.ok_or(ConversionError::MissingField(field))
+}
+
+impl From<InputRequest> for proto::InputRequest {
+    fn from(input: InputRequest) -> Self {
+        proto::InputRequest {
+            contract_or_mint: input.contract_or_mint,
+            token_id: input.token_id,
+            token_owner: input.token_owner,
+            origin_network: proto::Chain::from(input.origin_network).into(),
+            destination_account: input.destination_account,
+        }
+    }
+}
+
+impl TryFrom<proto::InputRequest> for InputRequest {
+    type Error = ConversionError;
+
+    fn try_from(input: proto::InputRequest) -> Result<Self, Self::Error> {
+        Ok(InputRequest {
+            contract_or_mint: input.contract_or_mint,
+            token_id: input.token_id,
+            token_owner: input.token_owner,
+            origin_network: chain_from_proto(input.origin_network)?,
+            destination_account: input.destination_account,
+        })
+    }
+}
+
+impl From<OutputResult> for proto::OutputResult {
+    fn from(output: OutputResult) -> Self {
+        proto::OutputResult {
+            destination_token_id_or_account: output.detination_token_id_or_account,
+            destination_contract_id_or_mint: output.detination_contract_id_or_mint,
+        }
+    }
+}
+
+impl From<proto::OutputResult> for OutputResult {
+    fn from(output: proto::OutputResult) -> Self {
+        OutputResult {
+            detination_token_id_or_account: output.destination_token_id_or_account,
+            detination_contract_id_or_mint: output.destination_contract_id_or_mint,
+        }
+    }
+}
+
+impl From<CancelReason> for proto::CancelReason {
+    fn from(reason: CancelReason) -> Self {
+        let reason = match reason {
+            CancelReason::MetadataInvalid(error) => {
+                proto::cancel_reason::Reason::MetadataInvalid(error)
+            }
+            CancelReason::DataCorrupted(error) => {
+                proto::cancel_reason::Reason::DataCorrupted(error)
+            }
+            CancelReason::TokenAlreadyBridging(request_id) => {
+                proto::cancel_reason::Reason::TokenAlreadyBridging(request_id)
+            }
+            CancelReason::GasLimitExceeded(error) => {
+                proto::cancel_reason::Reason::GasLimitExceeded(error)
+            }
+            CancelReason::LockFailed(error) => proto::cancel_reason::Reason::LockFailed(error),
+            CancelReason::Expired(error) => proto::cancel_reason::Reason::Expired(error),
+            CancelReason::RetriesExhausted(error) => {
+                proto::cancel_reason::Reason::RetriesExhausted(error)
+            }
+        };
+        proto::CancelReason {
+            reason: Some(reason),
+        }
+    }
+}
+
+impl TryFrom<proto::CancelReason> for CancelReason {
+    type Error = ConversionError;
+
+    fn try_from(reason: proto::CancelReason) -> Result<Self, Self::Error> {
+        match required(reason.reason, "cancel_reason.reason")? {
+            proto::cancel_reason::Reason::MetadataInvalid(error) => {
+                Ok(CancelReason::MetadataInvalid(error))
+            }
+            proto::cancel_reason::Reason::DataCorrupted(error) => {
+                Ok(CancelReason::DataCorrupted(error))
+            }
+            proto::cancel_reason::Reason::TokenAlreadyBridging(request_id) => {
+                Ok(CancelReason::TokenAlreadyBridging(request_id))
+            }
+            proto::cancel_reason::Reason::GasLimitExceeded(error) => {
+                Ok(CancelReason::GasLimitExceeded(error))
+            }
+            proto::cancel_reason::Reason::LockFailed(error) => Ok(CancelReason::LockFailed(error)),
+            proto::cancel_reason::Reason::Expired(error) => Ok(CancelReason::Expired(error)),
+            proto::cancel_reason::Reason::RetriesExhausted(error) => {
+                Ok(CancelReason::RetriesExhausted(error))
+            }
+        }
+    }
+}
+
+impl From<CallContext> for proto::CallContext {
+    fn from(context: CallContext) -> Self {
+        proto::CallContext {
+            chain: proto::Chain::from(context.chain).into(),
+            operation: context.operation,
+            request_id: context.request_id,
+            contract: context.contract,
+        }
+    }
+}
+
+impl TryFrom<proto::CallContext> for CallContext {
+    type Error = ConversionError;
+
+    fn try_from(context: proto::CallContext) -> Result<Self, Self::Error> {
+        Ok(CallContext {
+            chain: chain_from_proto(context.chain)?,
+            operation: context.operation,
+            request_id: context.request_id,
+            contract: context.contract,
+        })
+    }
+}
+
+impl From<BRequest> for proto::BRequest {
+    fn from(request: BRequest) -> Self {
+        proto::BRequest {
+            id: request.id.into(),
+            status: proto::Status::from(request.status).into(),
+            input: Some(request.input.into()),
+            tx_hashes: request.tx_hashes,
+            output: Some(request.output.into()),
+            last_update: Some(request.last_update.into()),
+            created_by_version: request.created_by_version,
+            cancel_reason: request.cancel_reason.map(Into::into),
+            history: request
+                .history
+                .into_iter()
+                .map(|entry| proto::HistoryEntry {
+                    time: Some(entry.time.into()),
+                    event: entry.event,
+                })
+                .collect(),
+            created_at: Some(request.created_at.into()),
+            finalized_at: request.finalized_at.map(Into::into),
+            last_error: request.last_error,
+            last_error_context: request.last_error_context.map(Into::into),
+            original_token_uri: request.original_token_uri,
+            pinned_metadata_cid: request.pinned_metadata_cid,
+            refund_pending: request.refund_pending,
+        }
+    }
+}
+
+impl TryFrom<proto::BRequest> for BRequest {
+    type Error = ConversionError;
+
+    fn try_from(request: proto::BRequest) -> Result<Self, Self::Error> {
+        let history = request
+            .history
+            .into_iter()
+            .map(|entry| {
+                Ok(HistoryEntry {
+                    time: required(entry.time, "history.time")?.into(),
+                    event: entry.event,
+                })
+            })
+            .collect::<Result<_, ConversionError>>()?;
+
+        Ok(BRequest {
+            id: request.id.parse()?,
+            status: status_from_proto(request.status)?,
+            input: required(request.input, "input")?.try_into()?,
+            tx_hashes: request.tx_hashes,
+            output: request.output.map(Into::into).unwrap_or_default(),
+            last_update: required(request.last_update, "last_update")?.into(),
+            created_by_version: request.created_by_version,
+            cancel_reason: request.cancel_reason.map(TryInto::try_into).transpose()?,
+            history,
+            created_at: required(request.created_at, "created_at")?.into(),
+            finalized_at: request.finalized_at.map(Into::into),
+            last_error: request.last_error,
+            last_error_context: request
+                .last_error_context
+                .map(TryInto::try_into)
+                .transpose()?,
+            original_token_uri: request.original_token_uri,
+            pinned_metadata_cid: request.pinned_metadata_cid,
+            refund_pending: request.refund_pending,
+            metadata_pending: None,
+            trace_context: None,
+            callback: None,
+            failed_attempts: vec![],
+            failure_report: None,
+            custody_signature: None,
+            alias_id: None,
+            reconstructed: false,
+            minted_token_uri: None,
+            metadata_snapshot: None,
+            finality_pending: None,
+            last_error_record: None,
+            previous_errors: vec![],
+            branding: None,
+            purged_at: None,
+            lock_block: None,
+            client_reference: None,
+            tags: vec![],
+            custody_chain: None,
+            bridge_addresses: None,
+            destination_account_pending: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod convert_test {
+    use types::{
+        BRequest, CallContext, CancelReason, Chains, HistoryEntry, InputRequest, InvalidRequestId,
+        Status, Timestamp,
+    };
+
+    use crate::{proto, ConversionError};
+
+    fn sample_request(origin_network: Chains) -> BRequest {
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xabc123".to_string(),
+                token_id: "42".to_string(),
+                token_owner: "0xowner456".to_string(),
+                origin_network,
+                destination_account: "destination789".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.tx_hashes = vec!["0xlock".to_string(), "mint".to_string()];
+        request.output.detination_contract_id_or_mint = "mint".to_string();
+        request.output.detination_token_id_or_account = "account".to_string();
+        request.history = vec![HistoryEntry {
+            time: Timestamp::from_millis(1_700_000_000_123),
+            event: "Token URI rewritten".to_string(),
+        }];
+        request.finalized_at = Some(Timestamp::from_millis(1_700_000_100_001));
+        request.last_error = Some("timeout".to_string());
+        request.original_token_uri = Some("https://example.com/42.json".to_string());
+        request.pinned_metadata_cid = Some("bafkrei42".to_string());
+        request.refund_pending = true;
+        request.last_error_context = Some(
+            CallContext::new(request.input.origin_network.clone(), "send_transaction")
+                .request(&request.id)
+                .contract("0xbridge"),
+        );
+        request
+    }
+
+    fn assert_round_trip(request: BRequest) {
+        let message = proto::BRequest::from(request.clone());
+        let back = BRequest::try_from(message).unwrap();
+        assert_eq!(
+            serde_json::to_value(&back).unwrap(),
+            serde_json::to_value(&request).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_request_round_trip() {
+        for status in Status::ALL {
+            for chain in [Chains::EVM, Chains::SOLANA] {
+                let mut request = sample_request(chain);
+                request.status = status.clone();
+                assert_round_trip(request);
+            }
+        }
+
+        let mut request = sample_request(Chains::EVM);
+        for reason in [
+            CancelReason::MetadataInvalid("javascript".to_string()),
+            CancelReason::DataCorrupted("bad mint".to_string()),
+            CancelReason::TokenAlreadyBridging("0xother".to_string()),
+            CancelReason::GasLimitExceeded("mintToken gas estimate".to_string()),
+            CancelReason::LockFailed("lock transaction reverted".to_string()),
+            CancelReason::Expired("token not in custody".to_string()),
+            CancelReason::RetriesExhausted("rpc unavailable".to_string()),
+        ] {
+            request.cancel_reason = Some(reason);
+            assert_round_trip(request.clone());
+        }
+
+        // Fields left empty by new requests
+        assert_round_trip(BRequest::new(
+            InputRequest {
+                contract_or_mint: String::new(),
+                token_id: String::new(),
+                token_owner: String::new(),
+                origin_network: Chains::SOLANA,
+                destination_account: String::new(),
+            },
+            Timestamp::now(),
+        ));
+    }
+
+    #[test]
+    fn test_invalid_messages_are_rejected() {
+        let mut message = proto::BRequest::from(sample_request(Chains::EVM));
+        message.status = proto::Status::Unspecified.into();
+        assert_eq!(
+            BRequest::try_from(message.clone()).err(),
+            Some(ConversionError::InvalidEnum("status", 0))
+        );
+
+        message.status = 99;
+        assert_eq!(
+            BRequest::try_from(message.clone()).err(),
+            Some(ConversionError::InvalidEnum("status", 99))
+        );
+
+        let mut message = proto::BRequest::from(sample_request(Chains::EVM));
+        message.input = None;
+        assert_eq!(
+            BRequest::try_from(message).err(),
+            Some(ConversionError::MissingField("input"))
+        );
+
+        let mut message = proto::BRequest::from(sample_request(Chains::EVM));
+        message.id = "request123".to_string();
+        assert_eq!(
+            BRequest::try_from(message).err(),
+            Some(ConversionError::InvalidRequestId(InvalidRequestId(
+                "request123".to_string()
+            )))
+        );
+    }
+}
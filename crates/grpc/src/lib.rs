@@ -0,0 +1,9 @@
+pub mod proto {
+    tonic::include_proto!("bridge.v1");
+}
+
+pub mod convert;
+pub use convert::*;
+
+pub mod service;
+pub use service::*;
@@ -0,0 +1,381 @@
+use std::{future::Future, net::SocketAddr, pin::Pin};
+
+use futures_util::Stream;
+use log::info;
+use requests::AppState;
+use storage::db::Database;
+use tonic::{transport::Server, Request, Response, Status};
+use types::{
+    request_data, requests_by_status_after, BRequest, BridgeError, RequestId, RequestUpdates,
+    StatusFeedItem,
+};
+
+use crate::{
+    proto::{
+        self,
+        bridge_server::{Bridge, BridgeServer},
+        GetRequestRequest, ListRequestsRequest, ListRequestsResponse, WatchRequestRequest,
+    },
+    status_from_proto,
+};
+
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Clone)]
+pub struct BridgeService {
+    state: AppState,
+}
+
+impl BridgeService {
+    pub fn new(state: AppState) -> Self {
+        BridgeService { state }
+    }
+}
+
+/// Serves the gRPC API until `shutdown` resolves
+pub async fn serve(
+    state: AppState,
+    port: u16,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), tonic::transport::Error> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    info!("gRPC server listening on {addr}");
+    Server::builder()
+        .add_service(BridgeServer::new(BridgeService::new(state)))
+        .serve_with_shutdown(addr, shutdown)
+        .await
+}
+
+fn internal(error: BridgeError) -> Status {
+    Status::internal(error.to_string())
+}
+
+fn parse_request_id(id: &str) -> Result<RequestId, Status> {
+    RequestId::parse(id).map_err(|e| Status::invalid_argument(e.to_string()))
+}
+
+fn parse_page_token(token: &str) -> Result<Option<(types::Status, String)>, Status> {
+    if token.is_empty() {
+        return Ok(None);
+    }
+    let invalid = || Status::invalid_argument(format!("Invalid page token {token}"));
+    let (status, id) = token.split_once(':').ok_or_else(invalid)?;
+    let status = types::Status::ALL
+        .into_iter()
+        .find(|candidate| format!("{candidate:?}") == status)
+        .ok_or_else(invalid)?;
+    Ok(Some((status, id.to_string())))
+}
+
+/// Page of requests in status bucket order. The token is `<status>:<last id>`, so pages
+/// without a status filter continue in the next bucket
+pub fn list_page(
+    db: &Database,
+    status: Option<types::Status>,
+    page_size: usize,
+    page_token: &str,
+) -> Result<(Vec<BRequest>, String), Status> {
+    let statuses = match status {
+        Some(status) => vec![status],
+        None => types::Status::ALL.to_vec(),
+    };
+    let (start, mut after) = match parse_page_token(page_token)? {
+        Some((status, id)) => {
+            let start = statuses
+                .iter()
+                .position(|candidate| *candidate == status)
+                .ok_or_else(|| Status::invalid_argument("Page token of another status"))?;
+            (start, Some(id))
+        }
+        None => (0, None),
+    };
+
+    let mut requests = vec![];
+    let mut scanned = 0;
+    let mut last_key = None;
+    for status in &statuses[start..] {
+        if scanned == page_size {
+            break;
+        }
+        let ids = requests_by_status_after(
+            db,
+            status,
+            after.take().as_deref(),
+            Some(page_size - scanned),
+        )
+        .map_err(internal)?;
+        for (id, _) in ids {
+            scanned += 1;
+            if let Some(request) = request_data(&id, db).map_err(internal)? {
+                requests.push(request);
+            }
+            last_key = Some(format!("{status:?}:{id}"));
+        }
+    }
+
+    let next_page_token = match last_key {
+        Some(key) if scanned == page_size => key,
+        _ => String::new(),
+    };
+    Ok((requests, next_page_token))
+}
+
+/// Emits the request when its status changes. After a lag of the feed the request is
+/// read again and emitted as is, a resync without a transition
+struct Watch {
+    db: Database,
+    request_id: RequestId,
+    updates: RequestUpdates,
+    current: Option<BRequest>,
+    last_status: Option<types::Status>,
+    resync: bool,
+    done: bool,
+}
+
+impl Watch {
+    async fn next(&mut self) -> Option<Result<proto::BRequest, Status>> {
+        if self.done {
+            return None;
+        }
+        loop {
+            if let Some(request) = self.current.take() {
+                if self.resync || self.last_status.as_ref() != Some(&request.status) {
+                    self.resync = false;
+                    self.last_status = Some(request.status.clone());
+                    self.done = matches!(
+                        request.status,
+                        types::Status::Completed | types::Status::Refunded
+                    ) || (request.status == types::Status::Canceled
+                        && !request.refund_pending);
+                    return Some(Ok(request.into()));
+                }
+            }
+
+            match self.updates.next().await {
+                StatusFeedItem::Update(update) => self.current = Some(update),
+                StatusFeedItem::Resync => match request_data(&self.request_id, &self.db) {
+                    Ok(request) => {
+                        self.resync = request.is_some();
+                        self.current = request;
+                    }
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(internal(e)));
+                    }
+                },
+                StatusFeedItem::TooSlow => {
+                    self.done = true;
+                    return Some(Err(Status::resource_exhausted(format!(
+                        "Watch of request {} can't keep up with the status updates",
+                        self.request_id
+                    ))));
+                }
+                StatusFeedItem::Closed => return None,
+            }
+        }
+    }
+}
+
+/// Current state of the request followed by its status transitions
+pub fn watch_request(
+    db: Database,
+    request_id: &RequestId,
+) -> Result<impl Stream<Item = Result<proto::BRequest, Status>> + Send + 'static, Status> {
+    // Subscribed before reading the request so no transition is missed in between
+    let updates = RequestUpdates::subscribe(request_id);
+    let request = request_data(request_id, &db)
+        .map_err(internal)?
+        .ok_or_else(|| Status::not_found(format!("Request {request_id} not found")))?;
+
+    let watch = Watch {
+        db,
+        request_id: request_id.clone(),
+        updates,
+        current: Some(request),
+        last_status: None,
+        resync: false,
+        done: false,
+    };
+    Ok(futures_util::stream::unfold(
+        watch,
+        |mut watch| async move { watch.next().await.map(|item| (item, watch)) },
+    ))
+}
+
+#[tonic::async_trait]
+impl Bridge for BridgeService {
+    async fn get_request(
+        &self,
+        request: Request<GetRequestRequest>,
+    ) -> Result<Response<proto::BRequest>, Status> {
+        let id = parse_request_id(&request.into_inner().id)?;
+        match request_data(&id, &self.state.db).map_err(internal)? {
+            Some(request) => Ok(Response::new(request.into())),
+            None => Err(Status::not_found(format!("Request {id} not found"))),
+        }
+    }
+
+    async fn list_requests(
+        &self,
+        request: Request<ListRequestsRequest>,
+    ) -> Result<Response<ListRequestsResponse>, Status> {
+        let request = request.into_inner();
+        let status = request
+            .status
+            .map(status_from_proto)
+            .transpose()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let page_size = match request.page_size as usize {
+            0 => DEFAULT_PAGE_SIZE,
+            size => size.min(MAX_PAGE_SIZE),
+        };
+
+        let (requests, next_page_token) =
+            list_page(&self.state.db, status, page_size, &request.page_token)?;
+        Ok(Response::new(ListRequestsResponse {
+            requests: requests.into_iter().map(Into::into).collect(),
+            next_page_token,
+        }))
+    }
+
+    type WatchRequestStream =
+        Pin<Box<dyn Stream<Item = Result<proto::BRequest, Status>> + Send + 'static>>;
+
+    async fn watch_request(
+        &self,
+        request: Request<WatchRequestRequest>,
+    ) -> Result<Response<Self::WatchRequestStream>, Status> {
+        let id = parse_request_id(&request.into_inner().id)?;
+        let stream = watch_request(self.state.db.clone(), &id)?;
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+#[cfg(test)]
+mod service_test {
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+    use storage::db::Database;
+    use test_support::test_db;
+    use types::{BRequest, Chains, InputRequest, Status, Timestamp};
+
+    use crate::{list_page, proto, watch_request};
+
+    fn saved_request(db: &Database, token_id: &str, status: Status) -> BRequest {
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xabc123".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner456".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination789".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.status = status;
+        request.save(db).unwrap();
+        request
+    }
+
+    #[test]
+    fn test_list_pages() {
+        let db = test_db();
+        for token_id in 0..3 {
+            saved_request(&db, &token_id.to_string(), Status::Completed);
+        }
+        saved_request(&db, "3", Status::TokenReceived);
+
+        // Status filter
+        let (first, token) = list_page(&db, Some(Status::Completed), 2, "").unwrap();
+        assert_eq!(first.len(), 2);
+        assert!(token.starts_with("Completed:"));
+        let (second, token) = list_page(&db, Some(Status::Completed), 2, &token).unwrap();
+        assert_eq!(second.len(), 1);
+        assert!(token.is_empty());
+        assert!(first.iter().all(|request| request.id != second[0].id));
+
+        // All the statuses, the page crosses the buckets
+        let mut ids = vec![];
+        let mut token = String::new();
+        loop {
+            let (page, next) = list_page(&db, None, 3, &token).unwrap();
+            ids.extend(page.into_iter().map(|request| request.id));
+            if next.is_empty() {
+                break;
+            }
+            token = next;
+        }
+        assert_eq!(ids.len(), 4);
+
+        assert!(list_page(&db, None, 3, "Unknown:0x1").is_err());
+        assert!(list_page(&db, Some(Status::Canceled), 3, "Completed:0x1").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_watch_emits_status_transitions() {
+        let db = test_db();
+        let mut request = saved_request(&db, "1", Status::TokenReceived);
+
+        let stream = watch_request(db.clone(), &request.id).unwrap();
+        let updater = tokio::spawn(async move {
+            request
+                .record_event("Saved without a transition", &db, Timestamp::now())
+                .unwrap();
+            request.update_state(&db, Timestamp::now()).unwrap();
+            request
+                .complete_minted(&db, "mint", "account", Timestamp::now())
+                .unwrap();
+        });
+
+        let statuses: Vec<i32> = tokio::time::timeout(
+            Duration::from_secs(5),
+            stream.map(|request| request.unwrap().status).collect(),
+        )
+        .await
+        .unwrap();
+        updater.await.unwrap();
+
+        assert_eq!(
+            statuses,
+            vec![
+                proto::Status::TokenReceived as i32,
+                proto::Status::TokenMinted as i32,
+                proto::Status::Completed as i32,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lagging_watch_gets_a_resync_snapshot() {
+        let db = test_db();
+        let mut request = saved_request(&db, "1", Status::TokenReceived);
+        let mut stream = Box::pin(watch_request(db.clone(), &request.id).unwrap());
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.status, proto::Status::TokenReceived as i32);
+
+        // Saved without a transition, then buried under more updates than the feed keeps
+        // while the watch is not read
+        request
+            .record_event("Metadata fetched", &db, Timestamp::now())
+            .unwrap();
+        let mut other = saved_request(&db, "2", Status::TokenReceived);
+        for _ in 0..1100 {
+            other
+                .record_event("Retried", &db, Timestamp::now())
+                .unwrap();
+        }
+
+        let resync = tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(resync.status, proto::Status::TokenReceived as i32);
+        assert!(resync
+            .history
+            .iter()
+            .any(|entry| entry.event == "Metadata fetched"));
+    }
+}
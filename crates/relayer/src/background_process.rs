@@ -0,0 +1,324 @@
+use log::{error, info};
+use requests::AppState;
+use tokio::task::JoinSet;
+use types::{
+    recover_send_intents, supervise_processor, ChainHeadSender, Chains, Clock, LoggableMessage,
+    SendLookup, TxReceiver,
+};
+
+/// Spawns the catch up of the database of a read-only relayer with its primary into
+/// `tasks`, the only background work of that role
+pub(crate) fn start_read_only_process(tasks: &mut JoinSet<()>, state: AppState) {
+    start_storage_sampling(tasks, &state);
+
+    info!("Starting database catch up");
+    tasks.spawn(async move {
+        loop {
+            tokio::time::sleep(state.intervals.db_catch_up).await;
+            if let Err(e) = state.db.catch_up() {
+                error!("Could not catch up with the primary database: {e}");
+            }
+        }
+    });
+}
+
+/// Spawns the listeners, processors, sweeps and periodic checks of the relayer into
+/// `tasks`, aborted together on shutdown
+pub(crate) fn start_background_process(
+    tasks: &mut JoinSet<()>,
+    state: AppState,
+    rx_evm: TxReceiver,
+    rx_sol: TxReceiver,
+    evm_head_tx: ChainHeadSender,
+    solana_head_tx: ChainHeadSender,
+) {
+    start_storage_sampling(tasks, &state);
+
+    info!("Starting backend authorization checks");
+    let state_clone = state.clone();
+    tasks.spawn(async move {
+        loop {
+            tokio::time::sleep(state_clone.intervals.authorization_check).await;
+            check_backend_authorization(&state_clone).await;
+        }
+    });
+
+    info!("Starting paused events drain");
+    let state_clone = state.clone();
+    tasks.spawn(async move {
+        loop {
+            // Also picks up pauses that expired on their resume time
+            requests::drain_resumed_events(&state_clone).await;
+            tokio::time::sleep(state_clone.intervals.paused_events_drain).await;
+        }
+    });
+
+    info!("Starting orphan expiry");
+    let state_clone = state.clone();
+    tasks.spawn(async move {
+        loop {
+            requests::expire_unclaimed_orphans(&state_clone);
+            tokio::time::sleep(state_clone.intervals.orphan_expiry).await;
+        }
+    });
+
+    info!("Starting pending refunds");
+    let state_clone = state.clone();
+    tasks.spawn(async move {
+        loop {
+            requests::process_pending_refunds(&state_clone).await;
+            tokio::time::sleep(state_clone.intervals.pending_refunds).await;
+        }
+    });
+
+    info!("Starting token URI retries");
+    let state_clone = state.clone();
+    tasks.spawn(async move {
+        loop {
+            requests::retry_pending_metadata(&state_clone).await;
+            tokio::time::sleep(state_clone.intervals.metadata_retry).await;
+        }
+    });
+
+    let state_clone = state.clone();
+    tasks.spawn(async move {
+        loop {
+            requests::check_pending_finality(&state_clone).await;
+            tokio::time::sleep(state_clone.intervals.finality_check).await;
+        }
+    });
+
+    let state_clone = state.clone();
+    tasks.spawn(async move {
+        loop {
+            let now = state_clone.clock.now();
+            if let Err(e) = state_clone
+                .callbacks
+                .deliver_due(&state_clone.db, now)
+                .await
+            {
+                error!("Error delivering request callbacks: {}", e);
+            }
+            tokio::time::sleep(state_clone.intervals.callback_delivery).await;
+        }
+    });
+
+    info!("Starting idempotency key and RPC health pruning");
+    let state_clone = state.clone();
+    tasks.spawn(async move {
+        loop {
+            requests::prune_idempotency_keys(&state_clone);
+            requests::prune_rpc_health(&state_clone);
+            tokio::time::sleep(state_clone.intervals.idempotency_key_prune).await;
+        }
+    });
+
+    info!("Starting RPC health sampling");
+    let state_clone = state.clone();
+    tasks.spawn(async move {
+        loop {
+            tokio::time::sleep(state_clone.intervals.rpc_health_sample).await;
+            requests::sample_rpc_health(&state_clone);
+        }
+    });
+
+    info!("Starting tx audit");
+    let state_clone = state.clone();
+    tasks.spawn(async move {
+        loop {
+            tokio::time::sleep(state_clone.intervals.tx_audit).await;
+            requests::audit_recorded_txs(&state_clone).await;
+        }
+    });
+
+    info!("Starting chain head watchers");
+    tasks.spawn(evm::watch_chain_head(state.evm_client.clone(), evm_head_tx));
+    tasks.spawn(solana::watch_chain_head(
+        state.solana_client.clone(),
+        solana_head_tx,
+    ));
+
+    info!("Reding pending requests");
+    match requests::sweep_candidates(
+        &state.db,
+        state.clock.as_ref(),
+        state.intervals.received_sweep_min_age,
+    ) {
+        Ok(pending_request) => {
+            let state_clone = state.clone();
+            tasks.spawn(async move {
+                requests::process_pending_request(pending_request, state_clone).await;
+            });
+        }
+        Err(e) => error!("Could not read pending requests: {}", e),
+    }
+
+    info!("Starting EVM event listener");
+    let state_clone = state.clone();
+    tasks.spawn(async move {
+        loop {
+            match evm::catch_event(
+                state_clone.evm_client.clone(),
+                &state_clone.db,
+                &state_clone.evm_head,
+                &state_clone.pause,
+                &state_clone.event_validator,
+            )
+            .await
+            {
+                Ok(_) => error!("EVM event listener exited unexpectedly"),
+                Err(e) => error!("EVM event listener failed: {}", e),
+            }
+
+            let backoff = state_clone.intervals.listener_backoff;
+            error!(
+                "Restarting EVM event listener in {} seconds",
+                backoff.as_secs()
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    });
+
+    info!("Starting Solana event listener");
+    let state_clone = state.clone();
+    tasks.spawn(async move {
+        match solana::subscribe_event(
+            &state_clone.solana_client,
+            &state_clone.db,
+            &state_clone.pause,
+            &state_clone.event_validator,
+        )
+        .await
+        {
+            Ok(_) => error!("Solana event listener exited unexpectedly"),
+            Err(e) => error!("Solana event listener failed: {}", e),
+        }
+        let backoff = state_clone.intervals.listener_backoff;
+        error!(
+            "Restarting Solana event listener in {} seconds",
+            backoff.as_secs()
+        );
+        tokio::time::sleep(backoff).await;
+    });
+
+    if state.dry_run {
+        info!("Dry run, tx processor messages are dropped");
+        tasks.spawn(drop_messages(rx_evm));
+        tasks.spawn(drop_messages(rx_sol));
+        return;
+    }
+
+    info!("Starting EVM message processor");
+    let state_clone = state.clone();
+    tasks.spawn(supervise_processor(
+        Chains::EVM,
+        rx_evm,
+        state.processor_health.of(&Chains::EVM).clone(),
+        state.intervals.processor_restart_backoff,
+        state.intervals.processor_wedged_after,
+        move |mut rx| {
+            let state = state_clone.clone();
+            async move {
+                evm::process_message(
+                    state.evm_client,
+                    &state.db,
+                    &mut rx,
+                    state.in_flight,
+                    state.processor_health.of(&Chains::EVM).clone(),
+                )
+                .await
+            }
+        },
+    ));
+
+    info!("Starting Solana message processor");
+    let state_clone = state.clone();
+    tasks.spawn(supervise_processor(
+        Chains::SOLANA,
+        rx_sol,
+        state.processor_health.of(&Chains::SOLANA).clone(),
+        state.intervals.processor_restart_backoff,
+        state.intervals.processor_wedged_after,
+        move |mut rx| {
+            let state = state_clone.clone();
+            async move {
+                solana::process_message(
+                    state.solana_client,
+                    &state.db,
+                    &mut rx,
+                    state.in_flight,
+                    state.processor_health.of(&Chains::SOLANA).clone(),
+                )
+                .await
+            }
+        },
+    ));
+}
+
+/// Spawns the periodic sampling of the database size into the storage metrics, nothing
+/// runs when they are disabled
+fn start_storage_sampling(tasks: &mut JoinSet<()>, state: &AppState) {
+    if state.storage_metrics.is_none() {
+        return;
+    }
+    info!("Starting database size sampling");
+    let state = state.clone();
+    tasks.spawn(async move {
+        loop {
+            if let Err(e) = state.db.sample_storage() {
+                error!("Could not sample the database size: {e}");
+            }
+            tokio::time::sleep(state.intervals.db_metrics_sample).await;
+        }
+    });
+}
+
+/// Tx processor of the dry run mode, messages are logged instead of sent
+async fn drop_messages(mut rx: TxReceiver) {
+    while let Some(message) = rx.recv().await {
+        info!("Dry run, not sending {}", LoggableMessage::new(&message));
+    }
+}
+
+/// Compares the configured signers with the bridge backends on both chains
+/// Looks up the transactions a crash left half sent before the processors start, so none
+/// is sent twice. See `types::recover_send_intents`
+pub(crate) async fn recover_half_sent_txs(state: &AppState) {
+    let lookups: [(Chains, Box<dyn SendLookup>); 2] = [
+        (
+            Chains::EVM,
+            Box::new(evm::EvmSendLookup {
+                client: state.evm_client.clone(),
+            }),
+        ),
+        (
+            Chains::SOLANA,
+            Box::new(solana::SolanaSendLookup {
+                client: state.solana_client.clone(),
+            }),
+        ),
+    ];
+    for (chain, lookup) in lookups {
+        let recovered = recover_send_intents(
+            &state.db,
+            &chain,
+            lookup.as_ref(),
+            &state.in_flight,
+            state.clock.now(),
+        )
+        .await;
+        match recovered {
+            Ok(recovery) => info!("Half sent {chain:?} transactions recovered: {recovery:?}"),
+            Err(e) => error!("Could not recover the half sent {chain:?} transactions: {e}"),
+        }
+    }
+}
+
+pub(crate) async fn check_backend_authorization(state: &AppState) {
+    if let Err(e) = solana::check_backend_authorization(&state.solana_client).await {
+        error!("Could not check Solana backend authorization: {}", e);
+    }
+    if let Err(e) = evm::check_backend_authorization(state.evm_client.clone()).await {
+        error!("Could not check EVM backend authorization: {}", e);
+    }
+}
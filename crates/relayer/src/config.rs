@@ -0,0 +1,105 @@
+use serde::Deserialize;
+use types::{ConfirmationStrategy, ExplorerBase, IdScheme, Secret};
+
+/// Configuration of the relayer, read by the binary from the environment variables of the
+/// same name in upper case. See the Configuration section of the README
+#[derive(Deserialize, Debug)]
+pub struct RelayerConfig {
+    pub db_path: String,
+    pub evm_rpc: String,
+    pub evm_ws: String,
+    /// Required unless `read_only`
+    pub evm_pk: Option<Secret<String>>,
+    pub evm_bridge_contract: String,
+    pub bridge_transition_secs: Option<u64>,
+    pub evm_block_explorer: Option<String>,
+    /// Required unless `read_only`
+    pub solana_wallet: Option<String>,
+    pub solana_fee_payer: Option<String>,
+    pub solana_rpc: String,
+    pub solana_ws: String,
+    pub solana_bridge_program: String,
+    pub solana_bridge_account: String,
+    pub solana_block_explorer: Option<String>,
+    pub solana_event_confirmation: Option<ConfirmationStrategy>,
+    pub require_prefunded_ata: Option<bool>,
+    pub port: u16,
+    pub uri_allowed_schemes: Option<String>,
+    pub uri_max_length: Option<usize>,
+    pub uri_max_data_size: Option<usize>,
+    pub evm_ipfs_gateway: Option<String>,
+    pub solana_ipfs_gateway: Option<String>,
+    pub rebuild_status_indexes: Option<bool>,
+    pub rebuild_wrapped_registry: Option<bool>,
+    pub rebuild_tx_index: Option<bool>,
+    pub rebuild_account_index: Option<bool>,
+    pub rpc_read_timeout_secs: Option<u64>,
+    pub rpc_send_timeout_secs: Option<u64>,
+    pub received_sweep_grace_secs: Option<u64>,
+    pub admin_token: Option<Secret<String>>,
+    pub purge_salt: Option<Secret<String>>,
+    pub sync_request_creation: Option<bool>,
+    pub grpc_enabled: Option<bool>,
+    pub grpc_port: Option<u16>,
+    pub api_client_ip_header: Option<String>,
+    pub dev_mode: Option<bool>,
+    pub dry_run: Option<bool>,
+    pub read_only: Option<bool>,
+    pub read_only_secondary_path: Option<String>,
+    pub db_metrics: Option<bool>,
+    pub db_slow_op_threshold_ms: Option<u64>,
+    pub metadata_pinning_url: Option<String>,
+    pub metadata_pinning_jwt: Option<Secret<String>>,
+    pub metadata_pinning_images: Option<bool>,
+    pub metadata_pinning_strict: Option<bool>,
+    pub metadata_translation: Option<bool>,
+    pub quota_max_requests_per_day: Option<u32>,
+    pub quota_max_evm_fees_per_day: Option<String>,
+    pub quota_max_solana_fees_per_day: Option<String>,
+    pub custody_limit: Option<usize>,
+    pub custody_limit_evm_to_solana: Option<usize>,
+    pub custody_limit_solana_to_evm: Option<usize>,
+    pub evm_max_gas_new_request: Option<u64>,
+    pub evm_max_gas_mint: Option<u64>,
+    pub evm_max_gas_refund: Option<u64>,
+    pub evm_fee_multiplier_percent: Option<u64>,
+    pub evm_min_priority_fee_wei: Option<u64>,
+    pub evm_fee_strategy: Option<String>,
+    pub evm_fee_oracle_address: Option<String>,
+    pub evm_fee_oracle_method: Option<String>,
+    pub evm_fee_oracle_result_index: Option<usize>,
+    pub evm_missing_uri_placeholder: Option<String>,
+    pub evm_delegate_registry: Option<String>,
+    pub evm_delegate_registry_interface: Option<String>,
+    pub otlp_endpoint: Option<String>,
+    pub callbacks_enabled: Option<bool>,
+    pub callback_allowed_hosts: Option<String>,
+    pub callback_signing_secret: Option<Secret<String>>,
+    pub request_id_scheme: Option<IdScheme>,
+    pub rpc_max_requests_per_second: Option<u32>,
+    pub backfill_parallelism: Option<usize>,
+    pub idempotency_key_ttl_secs: Option<u64>,
+    pub tx_audit_lookback_secs: Option<u64>,
+    pub log_privacy: Option<bool>,
+    pub replay_capture: Option<bool>,
+    pub branding_name_prefix: Option<String>,
+    pub branding_symbol_prefix: Option<String>,
+    pub branding_provenance: Option<bool>,
+    pub branding_max_name_len: Option<usize>,
+    pub branding_max_symbol_len: Option<usize>,
+}
+
+/// Validated block explorer template, optional on local validators
+pub(crate) fn block_explorer(
+    name: &str,
+    url: Option<&str>,
+    dev_mode: bool,
+) -> Result<String, String> {
+    match url {
+        Some(url) => ExplorerBase::parse(url)
+            .map(|base| base.to_string())
+            .map_err(|e| format!("Invalid {name}: {e}")),
+        None if dev_mode => Ok(String::new()),
+        None => Err(format!("{name} is required")),
+    }
+}
@@ -0,0 +1,14 @@
+#[derive(Debug, thiserror::Error)]
+pub enum RelayerError {
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Relayer startup failed: {0}")]
+    Startup(String),
+
+    #[error("Server error: {0}")]
+    Server(String),
+
+    #[error("Replay export failed: {0}")]
+    Replay(String),
+}
@@ -0,0 +1,37 @@
+//! The bridge relayer as a library, for services embedding it instead of running the
+//! `bridge_relayer` binary. The binary is a wrapper over it
+//!
+//! ```no_run
+//! # async fn run(config: relayer::RelayerConfig, input: types::InputRequest) -> Result<(), Box<dyn std::error::Error>> {
+//! let handle = relayer::BridgeRelayer::builder(config)
+//!     .without_api()
+//!     .build()
+//!     .await?
+//!     .start()
+//!     .await?;
+//!
+//! let mut updates = handle.subscribe_status_changes();
+//! let request = handle.submit_request(input).await?;
+//! while let Ok(update) = updates.recv().await {
+//!     if update.id == request.id {
+//!         println!("{} is {:?}", update.id, update.status);
+//!     }
+//! }
+//! handle.shutdown().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod errors;
+pub use errors::*;
+
+pub mod config;
+pub use config::*;
+
+pub mod relayer;
+pub use relayer::*;
+
+pub mod replay;
+pub use replay::*;
+
+mod background_process;
@@ -0,0 +1,662 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use api::routes::api_router;
+use evm::{
+    detect_fee_mode, get_latest_block_number, DelegateRegistry, FeeSettings, FeeStrategyKind,
+    GasLimits, DEFAULT_BACKFILL_PARALLELISM,
+};
+use log::{error, info};
+use requests::{
+    bootstrap_dev_environment, AppState, LoadTestRuns, NegativeCache, RequestError, Role,
+    DEV_MIN_BALANCE,
+};
+use solana::get_latest_slot;
+use storage::{db::Database, metrics::StorageMetrics};
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast, watch},
+    task::JoinSet,
+};
+use types::{
+    chain_head_channel, redact_url, redact_urls, system_clock, tx_channel, ApiMetrics, BRequest,
+    BackfillProgress, BrandingConfig, BridgeAddresses, BridgePause, CallbackPolicy, CallbackSender,
+    ChainHeadSender, Chains, ChannelMetrics, ClientLabels, CustodyLimits, EventValidator,
+    InFlightRegistry, InputRequest, Intervals, IpfsPinStore, MetadataPinning, MissingUriPolicy,
+    ProcessorHealth, QuotaLimits, RequestLocks, RpcLimiter, RpcTimeouts, Timestamp, TxAudit,
+    TxReceiver, UriPolicy, WorkClaims, DEFAULT_BRIDGE_TRANSITION, DEFAULT_IDEMPOTENCY_KEY_TTL,
+    DEFAULT_RPC_REQUESTS_PER_SECOND, DEFAULT_TX_AUDIT_LOOKBACK, IN_FLIGHT_TIMEOUT,
+    WORK_CLAIM_LEASE,
+};
+
+use crate::{
+    background_process::{
+        check_backend_authorization, recover_half_sent_txs, start_background_process,
+        start_read_only_process,
+    },
+    block_explorer, RelayerConfig, RelayerError,
+};
+
+/// gRPC port when `GRPC_ENABLED` is set without `GRPC_PORT`
+pub const DEFAULT_GRPC_PORT: u16 = 50051;
+/// Database operations slower than this are logged when `DB_SLOW_OP_THRESHOLD_MS` is not set
+pub const DEFAULT_DB_SLOW_OP_THRESHOLD: Duration = Duration::from_secs(1);
+/// Capacity of the channels to the tx processors
+const TX_CHANNEL_CAPACITY: usize = 50;
+
+/// Builds a relayer from its configuration, the startup of the binary: opens the database
+/// and runs its migrations, connects to both chains and tests the connections
+pub struct RelayerBuilder {
+    config: RelayerConfig,
+    api: bool,
+}
+
+impl RelayerBuilder {
+    pub fn new(config: RelayerConfig) -> Self {
+        RelayerBuilder { config, api: true }
+    }
+
+    /// The HTTP API is not served on `port`. For services mounting
+    /// `api::routes::api_router` over the relayer state in their own server
+    pub fn without_api(mut self) -> Self {
+        self.api = false;
+        self
+    }
+
+    /// Relayer ready to start, nothing runs until `BridgeRelayer::start`. Fails on an
+    /// invalid configuration or when a chain can't be reached
+    pub async fn build(self) -> Result<BridgeRelayer, RelayerError> {
+        let config = self.config;
+        let role = if config.read_only.unwrap_or(false) {
+            Role::ReadOnly
+        } else {
+            Role::Full
+        };
+        types::set_log_privacy(config.log_privacy.unwrap_or(false));
+        types::set_replay_capture(config.replay_capture.unwrap_or(false));
+        let channel_metrics = ChannelMetrics::default();
+        let (tx_evm, rx_evm) = tx_channel(Chains::EVM, TX_CHANNEL_CAPACITY, &channel_metrics);
+        let (tx_sol, rx_sol) = tx_channel(Chains::SOLANA, TX_CHANNEL_CAPACITY, &channel_metrics);
+
+        let db = match role {
+            Role::Full => {
+                info!("Opening database at {}", &config.db_path);
+                let db = Database::open(&config.db_path).map_err(|e| {
+                    RelayerError::Startup(format!("Failed to open database at: {}", e))
+                })?;
+                prepare_database(&config, &db)?;
+                db
+            }
+            Role::ReadOnly => {
+                let secondary_path = config
+                    .read_only_secondary_path
+                    .clone()
+                    .unwrap_or_else(|| format!("{}-read-only", config.db_path));
+                info!(
+                    "Opening database at {} read-only, secondary files in {}",
+                    &config.db_path, secondary_path
+                );
+                Database::open_read_only(&config.db_path, &secondary_path).map_err(|e| {
+                    RelayerError::Startup(format!("Failed to open database read-only: {}", e))
+                })?
+            }
+        };
+        let storage_metrics = config.db_metrics.unwrap_or(true).then(|| {
+            StorageMetrics::default().with_slow_threshold(Some(
+                config
+                    .db_slow_op_threshold_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or(DEFAULT_DB_SLOW_OP_THRESHOLD),
+            ))
+        });
+        let db = match &storage_metrics {
+            Some(metrics) => db.with_recorder(Arc::new(metrics.clone())),
+            None => db,
+        };
+        if role == Role::ReadOnly && config.grpc_enabled.unwrap_or(false) {
+            return Err(RelayerError::Config(
+                "GRPC_ENABLED is not supported with READ_ONLY".to_string(),
+            ));
+        }
+
+        let uri_policy = UriPolicy::from_config(
+            config.uri_allowed_schemes.as_deref(),
+            config.uri_max_length,
+            config.uri_max_data_size,
+        );
+
+        // Local test validators: confirmed commitment, short intervals and no block explorers
+        let dev_mode = config.dev_mode.unwrap_or(false);
+        if dev_mode {
+            info!("Running in dev mode");
+        }
+        // No transaction is sent, the admin load tests are only allowed in this mode
+        let dry_run = config.dry_run.unwrap_or(false);
+        if dry_run {
+            info!("Running in dry run mode");
+        }
+        let solana_block_explorer = block_explorer(
+            "SOLANA_BLOCK_EXPLORER",
+            config.solana_block_explorer.as_deref(),
+            dev_mode,
+        )
+        .map_err(RelayerError::Config)?;
+        let evm_block_explorer = block_explorer(
+            "EVM_BLOCK_EXPLORER",
+            config.evm_block_explorer.as_deref(),
+            dev_mode,
+        )
+        .map_err(RelayerError::Config)?;
+
+        let metadata_pinning = match (&config.metadata_pinning_url, &config.metadata_pinning_jwt) {
+            (Some(url), Some(jwt)) => {
+                info!("Pinning token metadata to {}", redact_url(url));
+                let store = IpfsPinStore::new(
+                    url,
+                    jwt,
+                    config.metadata_pinning_images.unwrap_or(false),
+                    uri_policy.clone(),
+                )
+                .map_err(|e| RelayerError::Config(format!("Invalid METADATA_PINNING_URL: {e}")))?;
+                Some(MetadataPinning {
+                    store: Arc::new(store),
+                    strict: config.metadata_pinning_strict.unwrap_or(false),
+                    translate: config.metadata_translation.unwrap_or(false),
+                })
+            }
+            _ => None,
+        };
+        if config.metadata_translation == Some(true) && metadata_pinning.is_none() {
+            error!("METADATA_TRANSLATION needs METADATA_PINNING_URL and METADATA_PINNING_JWT, metadata is not translated");
+        }
+
+        let branding = BrandingConfig::from_config(
+            config.branding_name_prefix.as_deref(),
+            config.branding_symbol_prefix.as_deref(),
+            config.branding_provenance,
+            config.branding_max_name_len,
+            config.branding_max_symbol_len,
+        );
+
+        let quota_limits = QuotaLimits::from_config(
+            config.quota_max_requests_per_day,
+            config.quota_max_evm_fees_per_day.as_deref(),
+            config.quota_max_solana_fees_per_day.as_deref(),
+        )
+        .map_err(|e| RelayerError::Config(format!("Invalid quota limit: {e}")))?;
+        let custody_limits = CustodyLimits {
+            max_total: config.custody_limit,
+            max_evm_to_solana: config.custody_limit_evm_to_solana,
+            max_solana_to_evm: config.custody_limit_solana_to_evm,
+        };
+
+        let callbacks = CallbackSender::new(CallbackPolicy::from_config(
+            config.callbacks_enabled.unwrap_or(true),
+            config.callback_allowed_hosts.as_deref(),
+            config.callback_signing_secret.clone(),
+        ))
+        .map_err(|e| RelayerError::Startup(format!("Callback client initialize failed: {e}")))?;
+
+        let mut intervals = Intervals::for_mode(dev_mode);
+        if let Some(secs) = config.received_sweep_grace_secs {
+            intervals.received_sweep_min_age = Duration::from_secs(secs);
+        }
+
+        let fee_strategy = FeeStrategyKind::from_config(
+            config.evm_fee_strategy.as_deref(),
+            config.evm_fee_oracle_address.as_deref(),
+            config.evm_fee_oracle_method.as_deref(),
+            config.evm_fee_oracle_result_index,
+        )
+        .map_err(|e| RelayerError::Config(format!("Invalid EVM fee strategy: {e}")))?;
+        info!("EVM fee strategy: {}", fee_strategy.strategy().name());
+        let delegate_registry = DelegateRegistry::from_config(
+            config.evm_delegate_registry.as_deref(),
+            config.evm_delegate_registry_interface.as_deref(),
+        )
+        .map_err(|e| RelayerError::Config(format!("Invalid EVM delegate registry: {e}")))?;
+
+        let timeouts =
+            RpcTimeouts::from_config(config.rpc_read_timeout_secs, config.rpc_send_timeout_secs);
+
+        let (solana_client, evm_client, evm_height, solana_height) = match role {
+            Role::ReadOnly => {
+                info!("Running read-only, no key is loaded and the chains are not connected");
+                let solana_client = solana::solana_read_only_client(
+                    &config.solana_rpc,
+                    &config.solana_ws,
+                    &config.solana_bridge_program,
+                    &config.solana_bridge_account,
+                    tx_evm,
+                    &solana_block_explorer,
+                    timeouts,
+                )
+                .map_err(|e| RelayerError::Config(format!("Invalid Solana bridge: {e}")))?;
+                let evm_client = evm::evm_read_only_client(
+                    &config.evm_rpc,
+                    &config.evm_ws,
+                    &config.evm_bridge_contract,
+                    tx_sol,
+                    &evm_block_explorer,
+                    timeouts,
+                )
+                .map_err(|e| RelayerError::Config(format!("Invalid EVM bridge contract: {e}")))?;
+                (solana_client, evm_client, 0, 0)
+            }
+            Role::Full => {
+                let solana_wallet = config
+                    .solana_wallet
+                    .as_deref()
+                    .ok_or_else(|| RelayerError::Config("SOLANA_WALLET is required".to_string()))?;
+                let evm_pk = config
+                    .evm_pk
+                    .as_ref()
+                    .ok_or_else(|| RelayerError::Config("EVM_PK is required".to_string()))?;
+
+                info!("Connecting to Solana at {}", redact_url(&config.solana_rpc));
+                let solana_client = solana::solana_connection(
+                    &config.solana_rpc,
+                    &config.solana_ws,
+                    solana_wallet,
+                    config.solana_fee_payer.as_deref(),
+                    &config.solana_bridge_program,
+                    &config.solana_bridge_account,
+                    tx_evm,
+                    &solana_block_explorer,
+                    uri_policy
+                        .clone()
+                        .with_ipfs_gateway(config.solana_ipfs_gateway.clone()),
+                    metadata_pinning.clone(),
+                    branding.clone(),
+                    timeouts,
+                    dev_mode,
+                    config.solana_event_confirmation.unwrap_or_default(),
+                )
+                .map_err(|e| {
+                    RelayerError::Startup(format!(
+                        "Failed to connect to Solana RPC at {}: {}",
+                        redact_url(&config.solana_rpc),
+                        redact_urls(&e.to_string())
+                    ))
+                })?
+                .with_prefunded_ata(config.require_prefunded_ata.unwrap_or(false));
+
+                info!("Connecting to EVM at {}", redact_url(&config.evm_rpc));
+                let evm_client = evm::evm_initialize(
+                    &config.evm_rpc,
+                    &config.evm_ws,
+                    evm_pk,
+                    &config.evm_bridge_contract,
+                    tx_sol,
+                    &evm_block_explorer,
+                    uri_policy.with_ipfs_gateway(config.evm_ipfs_gateway.clone()),
+                    metadata_pinning,
+                    branding,
+                    timeouts,
+                    GasLimits::from_config(
+                        config.evm_max_gas_new_request,
+                        config.evm_max_gas_mint,
+                        config.evm_max_gas_refund,
+                    ),
+                    FeeSettings::from_config(
+                        config.evm_fee_multiplier_percent,
+                        config.evm_min_priority_fee_wei,
+                    )
+                    .with_strategy(fee_strategy),
+                    MissingUriPolicy::from_config(config.evm_missing_uri_placeholder.as_deref()),
+                    dev_mode,
+                )
+                .map_err(|e| {
+                    RelayerError::Startup(format!(
+                        "Failed to initialize EVM client at {}: {}",
+                        redact_url(&config.evm_rpc),
+                        redact_urls(&e.to_string())
+                    ))
+                })?
+                .with_delegate_registry(delegate_registry);
+
+                // Requests created against replaced addresses keep them
+                let deployment = types::load_bridge_deployment(
+                    &db,
+                    BridgeAddresses {
+                        evm_contract: evm_client.bridge_contract.to_string(),
+                        solana_program: solana_client.bridge_program.to_string(),
+                        solana_account: solana_client.bridge_account.to_string(),
+                    },
+                    Timestamp::now(),
+                    config
+                        .bridge_transition_secs
+                        .map_or(DEFAULT_BRIDGE_TRANSITION, Duration::from_secs),
+                )
+                .map(Arc::new)
+                .map_err(|e| {
+                    RelayerError::Startup(format!("Could not load the bridge deployment: {e}"))
+                })?;
+                let evm_client = evm_client.with_bridge_deployment(deployment.clone());
+                let solana_client = solana_client.with_bridge_deployment(deployment);
+
+                // Test connections with timeouts
+                info!("Testing connections");
+                let evm_test = get_latest_block_number(&evm_client).await.map_err(|_| {
+                    RelayerError::Startup("EVM connection test timed out".to_string())
+                })?;
+                info!("EVM connection successful, latest block: {}", evm_test);
+                if let Err(e) = detect_fee_mode(&evm_client).await {
+                    error!(
+                        "Could not detect the EVM fee mode, detected on the first transaction: {e}"
+                    );
+                }
+
+                let solana_test = get_latest_slot(&solana_client).await.map_err(|_| {
+                    RelayerError::Startup("Solana connection test timed out".to_string())
+                })?;
+                info!("Solana connection successful, latest slot: {}", solana_test);
+                if solana_client.fee_payer.is_some() {
+                    info!(
+                        "Solana fees paid by {} instead of the wallet",
+                        solana_client.fee_payer_pubkey()
+                    );
+                }
+                (solana_client, evm_client, evm_test, solana_test)
+            }
+        };
+
+        // Chain head watchers, seeded with the heights read in the connection test
+        let (evm_head_tx, evm_head_rx) = chain_head_channel(evm_height);
+        let (solana_head_tx, solana_head_rx) = chain_head_channel(solana_height);
+
+        let clock = system_clock();
+        let state = AppState {
+            db: db.clone(),
+            solana_client: solana_client.with_clock(clock.clone()),
+            evm_client: evm_client.with_clock(clock.clone()),
+            evm_head: evm_head_rx,
+            solana_head: solana_head_rx,
+            in_flight: InFlightRegistry::load(&db, IN_FLIGHT_TIMEOUT, clock.clone()),
+            request_locks: RequestLocks::default(),
+            work_claims: WorkClaims::new(WORK_CLAIM_LEASE, clock.clone()),
+            channel_metrics,
+            pause: BridgePause::load(&db, clock.clone()),
+            event_validator: EventValidator::default(),
+            admin_token: config.admin_token.clone(),
+            clock,
+            dev_mode,
+            dry_run,
+            load_tests: LoadTestRuns::default(),
+            intervals,
+            quota_limits,
+            custody_limits,
+            callbacks,
+            missing_requests: NegativeCache::default(),
+            id_scheme: config.request_id_scheme.unwrap_or_default(),
+            rpc_limiter: RpcLimiter::per_second(
+                config
+                    .rpc_max_requests_per_second
+                    .unwrap_or(DEFAULT_RPC_REQUESTS_PER_SECOND),
+            ),
+            backfill_parallelism: config
+                .backfill_parallelism
+                .unwrap_or(DEFAULT_BACKFILL_PARALLELISM),
+            backfill_progress: BackfillProgress::default(),
+            idempotency_key_ttl: config
+                .idempotency_key_ttl_secs
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_IDEMPOTENCY_KEY_TTL),
+            processor_health: ProcessorHealth::default(),
+            tx_audit: TxAudit::new(
+                config
+                    .tx_audit_lookback_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_TX_AUDIT_LOOKBACK),
+            ),
+            purge_salt: config.purge_salt.clone(),
+            sync_creation: config.sync_request_creation.unwrap_or(false),
+            storage_metrics,
+            api_metrics: ApiMetrics::default(),
+            client_ip_header: config
+                .api_client_ip_header
+                .as_deref()
+                .map(str::trim)
+                .filter(|header| !header.is_empty())
+                .map(str::to_ascii_lowercase),
+            role,
+        };
+
+        if dev_mode && role == Role::Full {
+            bootstrap_dev_environment(&state, DEV_MIN_BALANCE)
+                .await
+                .map_err(|e| {
+                    RelayerError::Startup(format!("Dev environment bootstrap failed: {}", e))
+                })?;
+        }
+
+        let mut relayer = BridgeRelayer::new(state, rx_evm, rx_sol, evm_head_tx, solana_head_tx);
+        if self.api {
+            relayer = relayer.with_api(config.port);
+        }
+        if config.grpc_enabled.unwrap_or(false) {
+            relayer = relayer.with_grpc(config.grpc_port.unwrap_or(DEFAULT_GRPC_PORT));
+        }
+        Ok(relayer)
+    }
+}
+
+/// Migrations of the stored data and the index rebuilds asked by the configuration, run
+/// by full relayers before anything reads the database
+fn prepare_database(config: &RelayerConfig, db: &Database) -> Result<(), RelayerError> {
+    types::repair_pending_structures(db, Timestamp::now()).map_err(|e| {
+        RelayerError::Startup(format!("Failed to check the pending structures: {}", e))
+    })?;
+    types::migrate_pending_list(db)
+        .map_err(|e| RelayerError::Startup(format!("Failed to migrate the pending list: {}", e)))?;
+    types::migrate_completed_list(db).map_err(|e| {
+        RelayerError::Startup(format!("Failed to migrate the completed list: {}", e))
+    })?;
+    // Counted again from the request statuses, the custody caps start from the truth
+    types::rebuild_custody_exposure(db).map_err(|e| {
+        RelayerError::Startup(format!("Failed to count the tokens in custody: {}", e))
+    })?;
+
+    // The sweep finds the requests through the status indexes only, they are rebuilt
+    // after a schema bump or when missing
+    let rebuilt = if config.rebuild_status_indexes.unwrap_or(false) {
+        info!("Rebuilding request status indexes");
+        types::rebuild_status_indexes(db).map(|_| ())
+    } else {
+        types::ensure_status_indexes(db).map(|_| ())
+    };
+    rebuilt
+        .map_err(|e| RelayerError::Startup(format!("Failed to rebuild status indexes: {}", e)))?;
+
+    if config.rebuild_wrapped_registry.unwrap_or(false) {
+        info!("Rebuilding the wrapped asset registry");
+        types::rebuild_wrapped_registry(db).map_err(|e| {
+            RelayerError::Startup(format!(
+                "Failed to rebuild the wrapped asset registry: {}",
+                e
+            ))
+        })?;
+    }
+
+    if config.rebuild_tx_index.unwrap_or(false) {
+        info!("Rebuilding the tx hash index");
+        types::rebuild_tx_index(db).map_err(|e| {
+            RelayerError::Startup(format!("Failed to rebuild the tx hash index: {}", e))
+        })?;
+    }
+
+    if config.rebuild_account_index.unwrap_or(false) {
+        info!("Rebuilding the account index");
+        types::rebuild_account_index(db).map_err(|e| {
+            RelayerError::Startup(format!("Failed to rebuild the account index: {}", e))
+        })?;
+    }
+    Ok(())
+}
+
+/// Bridge relayer ready to start: its state, the receiving side of the tx processor
+/// channels and the chain head senders, with the servers to run
+pub struct BridgeRelayer {
+    state: AppState,
+    rx_evm: TxReceiver,
+    rx_sol: TxReceiver,
+    evm_head_tx: ChainHeadSender,
+    solana_head_tx: ChainHeadSender,
+    api_port: Option<u16>,
+    grpc_port: Option<u16>,
+}
+
+impl BridgeRelayer {
+    /// Builder running the startup of the binary from `config`
+    pub fn builder(config: RelayerConfig) -> RelayerBuilder {
+        RelayerBuilder::new(config)
+    }
+
+    /// Relayer over a state built by the caller, without servers. `rx_evm` and `rx_sol`
+    /// receive from the tx channels of the Solana and EVM clients of `state`, the head
+    /// senders update the receivers of `state`
+    pub fn new(
+        state: AppState,
+        rx_evm: TxReceiver,
+        rx_sol: TxReceiver,
+        evm_head_tx: ChainHeadSender,
+        solana_head_tx: ChainHeadSender,
+    ) -> Self {
+        BridgeRelayer {
+            state,
+            rx_evm,
+            rx_sol,
+            evm_head_tx,
+            solana_head_tx,
+            api_port: None,
+            grpc_port: None,
+        }
+    }
+
+    /// Serves the HTTP API on `port` of every interface
+    pub fn with_api(mut self, port: u16) -> Self {
+        self.api_port = Some(port);
+        self
+    }
+
+    /// Serves the gRPC API on `port` of every interface
+    pub fn with_grpc(mut self, port: u16) -> Self {
+        self.grpc_port = Some(port);
+        self
+    }
+
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
+
+    /// Spawns the event listeners, tx processors, pending sweep and periodic checks the
+    /// binary runs, only the database catch up for a read-only state, then the servers.
+    /// Fails when the API port can't be bound, before any task is started
+    pub async fn start(self) -> Result<RelayerHandle, RelayerError> {
+        let api_listener = match self.api_port {
+            Some(port) => Some(TcpListener::bind(format!("0.0.0.0:{port}")).await.map_err(
+                |e| RelayerError::Startup(format!("Could not listen on port {port}: {e}")),
+            )?),
+            None => None,
+        };
+
+        let mut tasks = JoinSet::new();
+        match self.state.role {
+            Role::Full => {
+                check_backend_authorization(&self.state).await;
+                recover_half_sent_txs(&self.state).await;
+                start_background_process(
+                    &mut tasks,
+                    self.state.clone(),
+                    self.rx_evm,
+                    self.rx_sol,
+                    self.evm_head_tx,
+                    self.solana_head_tx,
+                );
+            }
+            // Nothing listens to the chains, the database follows the primary
+            Role::ReadOnly => start_read_only_process(&mut tasks, self.state.clone()),
+        }
+
+        // Shared by the API and gRPC servers
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let mut servers = JoinSet::new();
+        if let Some(port) = self.grpc_port {
+            let state = self.state.clone();
+            let shutdown = shutdown_signal(shutdown_rx.clone());
+            servers.spawn(async move {
+                grpc::serve(state, port, shutdown)
+                    .await
+                    .map_err(|e| RelayerError::Server(format!("gRPC server failed: {e}")))
+            });
+        }
+        if let Some(listener) = api_listener {
+            // The peer address is the client ip of the access logs
+            let app =
+                api_router(self.state.clone()).into_make_service_with_connect_info::<SocketAddr>();
+            servers.spawn(async move {
+                axum::serve(listener, app)
+                    .with_graceful_shutdown(shutdown_signal(shutdown_rx))
+                    .await
+                    .map_err(|e| RelayerError::Server(format!("API server failed: {e}")))
+            });
+        }
+
+        info!("Relayer started");
+        Ok(RelayerHandle {
+            state: self.state,
+            shutdown_tx,
+            tasks,
+            servers,
+        })
+    }
+}
+
+/// Running relayer. Dropping the handle aborts its tasks, `shutdown` also lets the
+/// servers finish their requests
+pub struct RelayerHandle {
+    state: AppState,
+    shutdown_tx: watch::Sender<bool>,
+    tasks: JoinSet<()>,
+    servers: JoinSet<Result<(), RelayerError>>,
+}
+
+impl RelayerHandle {
+    /// State shared by the tasks and servers, for the `requests` functions the API calls
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
+
+    /// Receives every saved request, the same feed as the gRPC `WatchRequest`. Slow
+    /// subscribers lag and must read the request again from the database
+    pub fn subscribe_status_changes(&self) -> broadcast::Receiver<BRequest> {
+        types::subscribe_status_updates()
+    }
+
+    /// Creates a request as `POST /bridge/request` does, queuing its lock transaction to
+    /// the processor of the origin chain unless `SYNC_REQUEST_CREATION` is set
+    pub async fn submit_request(&self, input: InputRequest) -> Result<BRequest, RequestError> {
+        requests::new_request(input, None, ClientLabels::default(), self.state.clone()).await
+    }
+
+    /// Stops the servers once their requests are answered, then aborts the listeners,
+    /// processors and sweeps. Returns the error of a server that failed
+    pub async fn shutdown(mut self) -> Result<(), RelayerError> {
+        info!("Shutting down the relayer");
+        let _ = self.shutdown_tx.send(true);
+        let mut result = Ok(());
+        while let Some(stopped) = self.servers.join_next().await {
+            match stopped {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => result = Err(e),
+                Err(e) => result = Err(RelayerError::Server(e.to_string())),
+            }
+        }
+        self.tasks.shutdown().await;
+        info!("Relayer shutdown complete");
+        result
+    }
+}
+
+async fn shutdown_signal(mut shutdown_rx: watch::Receiver<bool>) {
+    let _ = shutdown_rx.wait_for(|shutdown| *shutdown).await;
+}
@@ -0,0 +1,33 @@
+use std::path::Path;
+
+use log::info;
+use storage::db::Database;
+
+use crate::RelayerError;
+
+/// Writes the replay bundle of a request from the database at `db_path` to `out`, for
+/// the `replay` command of the binary. The relayer using the database must be stopped
+pub fn export_replay(db_path: &str, request_id: &str, out: &Path) -> Result<(), RelayerError> {
+    let request_id =
+        types::RequestId::parse(request_id).map_err(|e| RelayerError::Config(e.to_string()))?;
+    let db = Database::open(db_path)
+        .map_err(|e| RelayerError::Replay(format!("Failed to open database at: {}", e)))?;
+    let bundle = types::export_replay_bundle(&db, &request_id)
+        .map_err(|e| RelayerError::Replay(format!("Failed to read the replay bundle: {}", e)))?
+        .ok_or_else(|| {
+            RelayerError::Config(format!(
+                "No replay bundle for request {request_id}, was REPLAY_CAPTURE set?"
+            ))
+        })?;
+
+    let json = serde_json::to_string_pretty(&bundle)
+        .map_err(|e| RelayerError::Replay(format!("Failed to encode the bundle: {}", e)))?;
+    std::fs::write(out, json)
+        .map_err(|e| RelayerError::Replay(format!("Failed to write {}: {}", out.display(), e)))?;
+    info!(
+        "Replay bundle of request {request_id} written to {}, {} inputs",
+        out.display(),
+        bundle.entries.len()
+    );
+    Ok(())
+}
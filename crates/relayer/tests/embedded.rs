@@ -0,0 +1,76 @@
+//! The relayer embedded through its facade, against a mocked Solana RPC and without
+//! servers. Run with `cargo test -p relayer --test embedded`
+
+use std::time::Duration;
+
+use relayer::BridgeRelayer;
+use requests::test_utils::test_state;
+use solana_client::rpc_request::RpcRequest;
+use test_support::{input_request, missing_account_result, mock_rpc, test_db};
+use types::{chain_head_channel, Chains, MessageMint, Status, TxMessage};
+
+const WAIT: Duration = Duration::from_secs(10);
+
+#[tokio::test]
+async fn test_embedded_relayer_lifecycle() {
+    let db = test_db();
+    let (mut state, rx_evm, rx_sol) = test_state(db.clone());
+    // Read by the backend authorization check on start, an unknown bridge account keeps
+    // the relayer authorized
+    state.solana_client.set_rpc(mock_rpc([(
+        RpcRequest::GetAccountInfo,
+        missing_account_result(),
+    )]));
+    let (evm_head_tx, evm_head) = chain_head_channel(0);
+    let (solana_head_tx, solana_head) = chain_head_channel(0);
+    state.evm_head = evm_head;
+    state.solana_head = solana_head;
+
+    let relayer = BridgeRelayer::new(state, rx_evm, rx_sol, evm_head_tx, solana_head_tx);
+    let handle = relayer.start().await.unwrap();
+    let state = handle.state().clone();
+    let mut updates = handle.subscribe_status_changes();
+
+    let request = handle
+        .submit_request(input_request(Chains::SOLANA, ""))
+        .await
+        .unwrap();
+    assert_eq!(request.status, Status::Initializing);
+    assert!(request.tx_hashes.is_empty());
+
+    // Locked in the background by the Solana tx processor
+    let update = tokio::time::timeout(WAIT, async {
+        loop {
+            let update = updates.recv().await.unwrap();
+            if update.id == request.id && update.status != Status::Initializing {
+                return update;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(update.status, Status::RequestReceived);
+    assert_eq!(update.tx_hashes.len(), 1);
+    let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+    assert_eq!(stored.status, Status::RequestReceived);
+    assert_eq!(stored.tx_hashes, update.tx_hashes);
+
+    // Duplicates are refused as by the API
+    assert!(handle
+        .submit_request(input_request(Chains::SOLANA, ""))
+        .await
+        .is_err());
+
+    tokio::time::timeout(WAIT, handle.shutdown())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // The tx processors stopped with the relayer
+    let mint = TxMessage::Mint(MessageMint {
+        request_id: request.id.clone(),
+        token_metadata: String::new(),
+        trace_context: None,
+    });
+    assert!(state.solana_client.tx_channel.send(mint).await.is_err());
+}
@@ -0,0 +1,162 @@
+use std::str::FromStr;
+
+use alloy::primitives::Address;
+use solana_sdk::pubkey::Pubkey;
+use types::Chains;
+
+use crate::errors::RequestError;
+
+fn is_evm_address(value: &str) -> bool {
+    Address::from_str(value).is_ok()
+}
+
+fn is_solana_key(value: &str) -> bool {
+    Pubkey::from_str(value).is_ok()
+}
+
+fn chain_name(chain: &Chains) -> &'static str {
+    match chain {
+        Chains::EVM => "EVM",
+        Chains::SOLANA => "Solana",
+    }
+}
+
+fn address_kind(chain: &Chains) -> &'static str {
+    match chain {
+        Chains::EVM => "an EVM address",
+        Chains::SOLANA => "a Solana public key",
+    }
+}
+
+/// Checks the destination account is an address of the destination chain, the other one
+/// of `origin`. An address of the origin chain gets a hint naming the mix-up
+pub fn check_destination_account(origin: &Chains, value: &str) -> Result<(), RequestError> {
+    let (valid, mixed_up, expected) = match origin {
+        Chains::EVM => (
+            is_solana_key(value),
+            is_evm_address(value),
+            "a base58 Solana public key",
+        ),
+        Chains::SOLANA => (
+            is_evm_address(value),
+            is_solana_key(value),
+            "a 0x-prefixed EVM address",
+        ),
+    };
+    if valid {
+        return Ok(());
+    }
+    let destination = match origin {
+        Chains::EVM => Chains::SOLANA,
+        Chains::SOLANA => Chains::EVM,
+    };
+    let hint = mixed_up.then(|| {
+        format!(
+            "destination_account looks like {} but this endpoint bridges to {}, send the {} account receiving the token",
+            address_kind(origin),
+            chain_name(&destination),
+            chain_name(&destination)
+        )
+    });
+    Err(RequestError::InvalidAccount(
+        "destination_account".to_string(),
+        format!("expected {expected}"),
+        hint,
+    ))
+}
+
+/// Refuses a token contract or mint in the address format of the other chain, named by
+/// its field in the input of the endpoint. Other malformed values are left to the chain
+/// reads of the creation
+pub fn check_contract_or_mint(origin: &Chains, value: &str) -> Result<(), RequestError> {
+    let (field, expected, hint) = match origin {
+        Chains::EVM if is_solana_key(value) => (
+            "token_contract",
+            "a 0x-prefixed EVM contract address",
+            "token_contract looks like a Solana mint but this endpoint bridges from EVM, use /bridge/solana-to-evm for Solana tokens",
+        ),
+        Chains::SOLANA if is_evm_address(value) => (
+            "token_mint",
+            "a base58 Solana mint",
+            "token_mint looks like an EVM contract address but this endpoint bridges from Solana, use /bridge/evm-to-solana for EVM tokens",
+        ),
+        _ => return Ok(()),
+    };
+    Err(RequestError::InvalidAccount(
+        field.to_string(),
+        format!("expected {expected}"),
+        Some(hint.to_string()),
+    ))
+}
+
+#[cfg(test)]
+mod account_format_test {
+    use test_support::{solana_key, EVM_ACCOUNT};
+    use types::Chains;
+
+    use crate::{check_contract_or_mint, check_destination_account, errors::RequestError};
+
+    fn hint(result: Result<(), RequestError>) -> Option<String> {
+        match result {
+            Err(RequestError::InvalidAccount(_, _, hint)) => hint,
+            other => panic!("Expected an invalid account, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_destination_of_the_right_chain_accepted() {
+        let solana = solana_key(3).to_string();
+        assert_eq!(check_destination_account(&Chains::EVM, &solana), Ok(()));
+        assert_eq!(
+            check_destination_account(&Chains::SOLANA, EVM_ACCOUNT),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_destination_of_the_origin_chain_hinted() {
+        let hint_text = hint(check_destination_account(&Chains::EVM, EVM_ACCOUNT)).unwrap();
+        assert!(hint_text.starts_with(
+            "destination_account looks like an EVM address but this endpoint bridges to Solana"
+        ));
+
+        let solana = solana_key(3).to_string();
+        let hint_text = hint(check_destination_account(&Chains::SOLANA, &solana)).unwrap();
+        assert!(hint_text.starts_with(
+            "destination_account looks like a Solana public key but this endpoint bridges to EVM"
+        ));
+    }
+
+    #[test]
+    fn test_malformed_destination_without_hint() {
+        let err = check_destination_account(&Chains::EVM, "not an account").unwrap_err();
+        assert_eq!(
+            err,
+            RequestError::InvalidAccount(
+                "destination_account".to_string(),
+                "expected a base58 Solana public key".to_string(),
+                None
+            )
+        );
+        assert_eq!(
+            hint(check_destination_account(&Chains::SOLANA, "0x12")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_contract_of_the_other_chain_hinted() {
+        let hint_text = hint(check_contract_or_mint(&Chains::SOLANA, EVM_ACCOUNT)).unwrap();
+        assert!(hint_text.contains("bridges from Solana"));
+        assert!(hint_text.contains("/bridge/evm-to-solana"));
+
+        let mint = solana_key(1).to_string();
+        let hint_text = hint(check_contract_or_mint(&Chains::EVM, &mint)).unwrap();
+        assert!(hint_text.contains("bridges from EVM"));
+
+        assert_eq!(check_contract_or_mint(&Chains::EVM, EVM_ACCOUNT), Ok(()));
+        assert_eq!(check_contract_or_mint(&Chains::SOLANA, &mint), Ok(()));
+        // Left to the chain reads
+        assert_eq!(check_contract_or_mint(&Chains::EVM, "0xabc123"), Ok(()));
+    }
+}
@@ -0,0 +1,132 @@
+use log::info;
+use serde::Deserialize;
+use types::{redact_urls, BackfillReport, Clock};
+
+use crate::{errors::RequestError, is_admin, AppState};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BackfillParams {
+    pub from_evm_block: u64,
+    /// Chain head published by the head watcher when not set
+    #[serde(default)]
+    pub to_evm_block: Option<u64>,
+    /// Scanned up to the latest slot
+    pub from_solana_slot: u64,
+}
+
+/// Rebuilds the requests of the bridge events in the given chain ranges, authorized by the
+/// admin token. Runs over ranges already imported write nothing new
+pub async fn run_backfill(
+    params: BackfillParams,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<BackfillReport, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    let to_evm_block = match params.to_evm_block {
+        Some(block) => block,
+        None => match state.evm_head.borrow().height {
+            0 => {
+                return Err(RequestError::BackfillFailed(
+                    "EVM chain head not observed yet".to_string(),
+                ))
+            }
+            height => height,
+        },
+    };
+    if params.from_evm_block > to_evm_block {
+        return Err(RequestError::InvalidBackfillRange(format!(
+            "EVM block {} is after {to_evm_block}",
+            params.from_evm_block
+        )));
+    }
+
+    info!(
+        "Backfill of EVM blocks {} to {to_evm_block}",
+        params.from_evm_block
+    );
+    let evm_history = evm::historical_events(
+        &state.evm_client,
+        params.from_evm_block,
+        to_evm_block,
+        &state.rpc_limiter,
+        state.backfill_parallelism,
+        &state.backfill_progress,
+    )
+    .await
+    .map_err(|e| RequestError::BackfillFailed(redact_urls(&e.to_string())))?;
+    let mut events = evm_history.events;
+    info!("Backfill of Solana slots from {}", params.from_solana_slot);
+    events.extend(
+        solana::historical_events(
+            &state.solana_client,
+            params.from_solana_slot,
+            &state.rpc_limiter,
+        )
+        .await
+        .map_err(|e| RequestError::BackfillFailed(redact_urls(&e.to_string())))?,
+    );
+
+    let mut report = types::reconstruct_requests(&state.db, &events, state.clock.now())
+        .map_err(|e| RequestError::BackfillFailed(e.to_string()))?;
+    report.deferred = evm_history.deferred.len();
+    info!("Backfill finished {report:?}");
+    Ok(report)
+}
+
+#[cfg(test)]
+mod backfill_test {
+    use test_support::test_db;
+    use types::{chain_head_channel, ChainHead};
+
+    use crate::{errors::RequestError, run_backfill, test_utils::test_state, BackfillParams};
+
+    #[tokio::test]
+    async fn test_backfill_checks_token_and_range() {
+        let db = test_db();
+        let (mut state, _, _) = test_state(db.clone());
+        state.admin_token = Some("secret".into());
+        let params = BackfillParams {
+            from_evm_block: 200,
+            to_evm_block: Some(100),
+            from_solana_slot: 0,
+        };
+
+        assert_eq!(
+            run_backfill(params.clone(), None, &state)
+                .await
+                .unwrap_err(),
+            RequestError::Unauthorized()
+        );
+        assert!(matches!(
+            run_backfill(params, Some("secret"), &state).await,
+            Err(RequestError::InvalidBackfillRange(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_backfill_ends_at_the_watched_head_by_default() {
+        let db = test_db();
+        let (mut state, _, _) = test_state(db.clone());
+        state.admin_token = Some("secret".into());
+        let params = BackfillParams {
+            from_evm_block: 200,
+            to_evm_block: None,
+            from_solana_slot: 0,
+        };
+
+        let (head_tx, evm_head) = chain_head_channel(0);
+        state.evm_head = evm_head;
+        assert!(matches!(
+            run_backfill(params.clone(), Some("secret"), &state).await,
+            Err(RequestError::BackfillFailed(_))
+        ));
+        // Read from the watcher, without calling the EVM endpoint
+        head_tx.send_replace(ChainHead::new(150));
+        assert!(matches!(
+            run_backfill(params, Some("secret"), &state).await,
+            Err(RequestError::InvalidBackfillRange(reason)) if reason.contains("after 150")
+        ));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::{BTreeMap, HashMap};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+use types::{BRequest, OutputResult, RequestId, Status, Timestamp};
+
+use crate::{errors::RequestError, AppState};
+
+/// Request ids looked up by one batch status call
+pub const MAX_BATCH_STATUS_IDS: usize = 100;
+/// Seconds clients wait between two batch status polls
+pub const BATCH_STATUS_MAX_AGE_SECS: u64 = 5;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BatchStatusInput {
+    pub ids: Vec<String>,
+}
+
+/// What a poller needs of a request, without its history and transactions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RequestStatusSummary {
+    pub status: Status,
+    pub last_update: Timestamp,
+    pub output: OutputResult,
+    /// Position in the pending queue starting at 1, None when not pending
+    pub queue_position: Option<usize>,
+}
+
+/// Summaries by the ids they were asked with, the ids of no request are listed apart
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct BatchStatus {
+    pub requests: BTreeMap<String, RequestStatusSummary>,
+    pub not_found: Vec<String>,
+}
+
+/// Status of up to `MAX_BATCH_STATUS_IDS` requests read in one pass. Malformed and
+/// unknown ids are reported in `not_found` instead of failing the batch, `display`
+/// formats each request before its projection
+pub fn get_batch_status(
+    ids: &[String],
+    state: &AppState,
+    display: fn(BRequest) -> BRequest,
+) -> Result<BatchStatus, RequestError> {
+    if ids.len() > MAX_BATCH_STATUS_IDS {
+        return Err(RequestError::BatchTooLarge(ids.len(), MAX_BATCH_STATUS_IDS));
+    }
+    let now = state.clock.now();
+    let mut batch = BatchStatus::default();
+
+    let mut lookups: Vec<(&String, RequestId)> = vec![];
+    for id in ids {
+        match RequestId::parse(id) {
+            Ok(request_id) if !state.missing_requests.is_missing(&request_id, now) => {
+                lookups.push((id, request_id))
+            }
+            _ => batch.not_found.push(id.clone()),
+        }
+    }
+    let request_ids: Vec<&RequestId> = lookups.iter().map(|(_, request_id)| request_id).collect();
+    let requests = types::requests_data(&request_ids, &state.db).map_err(|e| {
+        error!("Could not read the requests of a batch status: {e}");
+        RequestError::CreationError(e.to_string())
+    })?;
+
+    // The queue is read once for the whole batch
+    let positions: HashMap<String, usize> = types::pending_entries(&state.db)
+        .map_err(|e| RequestError::CreationError(e.to_string()))?
+        .into_iter()
+        .enumerate()
+        .map(|(index, (_, id))| (id, index + 1))
+        .collect();
+
+    for ((id, request_id), request) in lookups.into_iter().zip(requests) {
+        let Some(request) = request else {
+            state.missing_requests.insert(&request_id, now);
+            batch.not_found.push(id.clone());
+            continue;
+        };
+        let queue_position = positions.get(request.id.as_str()).copied();
+        let request = display(request);
+        batch.requests.insert(
+            id.clone(),
+            RequestStatusSummary {
+                status: request.status,
+                last_update: request.last_update,
+                output: request.output,
+                queue_position,
+            },
+        );
+    }
+    batch.not_found.sort();
+    batch.not_found.dedup();
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod batch_status_test {
+    use std::convert::identity;
+
+    use test_support::test_db;
+    use types::{BRequest, Chains, InputRequest, Status, Timestamp};
+
+    use crate::{
+        add_pending_request, errors::RequestError, get_batch_status, test_utils::test_state,
+        MAX_BATCH_STATUS_IDS,
+    };
+
+    fn request(token_id: &str) -> BRequest {
+        BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xcontract".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        )
+    }
+
+    #[test]
+    fn test_known_and_unknown_ids() {
+        let db = test_db();
+        let (state, _, _) = test_state(db.clone());
+        let pending = request("1");
+        pending.save(&db).unwrap();
+        add_pending_request(&pending.id, &db).unwrap();
+        let mut minted = request("2");
+        minted.status = Status::TokenMinted;
+        minted.output.detination_token_id_or_account = "token account".to_string();
+        minted.save(&db).unwrap();
+        let unknown = request("3").id.to_string();
+
+        let ids = vec![
+            minted.id.to_string(),
+            unknown.clone(),
+            pending.id.to_string(),
+            "not an id".to_string(),
+            unknown.clone(),
+        ];
+        let status = get_batch_status(&ids, &state, identity).unwrap();
+        assert_eq!(status.requests.len(), 2);
+        let summary = &status.requests[pending.id.as_str()];
+        assert_eq!(summary.status, Status::RequestReceived);
+        assert_eq!(summary.queue_position, Some(1));
+        let summary = &status.requests[minted.id.as_str()];
+        assert_eq!(summary.status, Status::TokenMinted);
+        assert_eq!(summary.output, minted.output);
+        assert_eq!(summary.queue_position, None);
+        assert_eq!(
+            status.not_found,
+            vec![unknown.clone(), "not an id".to_string()]
+        );
+
+        // Unknown ids are remembered, a later batch doesn't read them again
+        assert!(state
+            .missing_requests
+            .is_missing(&unknown, state.clock.now()));
+    }
+
+    #[test]
+    fn test_batch_capped() {
+        let (state, _, _) = test_state(test_db());
+        let ids = vec![request("1").id.to_string(); MAX_BATCH_STATUS_IDS + 1];
+        assert_eq!(
+            get_batch_status(&ids, &state, identity),
+            Err(RequestError::BatchTooLarge(
+                MAX_BATCH_STATUS_IDS + 1,
+                MAX_BATCH_STATUS_IDS
+            ))
+        );
+        let ids = vec![request("1").id.to_string(); MAX_BATCH_STATUS_IDS];
+        assert!(get_batch_status(&ids, &state, identity).is_ok());
+    }
+
+    #[test]
+    fn test_summary_without_heavy_fields() {
+        let db = test_db();
+        let (state, _, _) = test_state(db.clone());
+        let mut request = request("1");
+        request.save(&db).unwrap();
+        request
+            .add_tx(
+                "0x5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060",
+                &db,
+            )
+            .unwrap();
+
+        let status = get_batch_status(&[request.id.to_string()], &state, identity).unwrap();
+        let summary = serde_json::to_value(&status.requests[request.id.as_str()]).unwrap();
+        let mut fields: Vec<&str> = summary
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        fields.sort();
+        assert_eq!(
+            fields,
+            vec!["last_update", "output", "queue_position", "status"]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use serde::{Deserialize, Serialize};
+use types::{Chains, Clock, IdScheme, QuotaLimits, RELAYER_VERSION};
+
+use crate::{AppState, BATCH_STATUS_MAX_AGE_SECS, MAX_BATCH_STATUS_IDS};
+
+/// Version of the capabilities document, raised on breaking changes only. Fields are
+/// added without raising it, clients ignore the ones they don't know
+pub const CAPABILITIES_VERSION: u32 = 1;
+/// Seconds clients can cache the capabilities document
+pub const CAPABILITIES_MAX_AGE_SECS: u64 = 30;
+/// Largest JSON body accepted by the endpoints, the default limit of axum
+pub const MAX_REQUEST_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Bridge direction and whether new requests are accepted in it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DirectionCapability {
+    pub origin: Chains,
+    pub destination: Chains,
+    /// False while the direction or the whole bridge is paused
+    pub enabled: bool,
+    pub pause_reason: Option<String>,
+    /// Unix time (secs) the pause ends, None when it is lifted by an operator
+    pub resume_at: Option<u64>,
+}
+
+/// How new requests are created
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CreationCapabilities {
+    /// Requests are answered once their lock transaction is sent, otherwise they are
+    /// accepted with a 202 and locked in the background
+    pub synchronous: bool,
+    /// The relayer checks the token owner on chain, it never asks for an ownership
+    /// signature, always false
+    pub ownership_signature_required: bool,
+    pub id_scheme: IdScheme,
+    /// Time an Idempotency-Key replays the response of its creation
+    pub idempotency_key_ttl_secs: u64,
+}
+
+/// Per-request callbacks accepted by this deployment
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CallbackCapabilities {
+    pub enabled: bool,
+    /// Hosts callbacks can be sent to, any public host when empty
+    pub allowed_hosts: Vec<String>,
+    /// Bodies are signed with the callback secret of the deployment
+    pub signed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RequestLimits {
+    pub max_body_bytes: usize,
+    /// Requests are created one at a time, always None
+    pub max_batch_size: Option<u32>,
+    /// Ids of a batch status lookup
+    pub max_batch_status_ids: usize,
+    /// Seconds between two batch status polls
+    pub batch_status_poll_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FeatureFlags {
+    /// Requests are accepted but no transaction is sent
+    pub dry_run: bool,
+    /// `/bridge/preflight/evm/{contract}/{token_id}` simulates the transfer of a token
+    pub preflight: bool,
+    /// Status updates are streamed by the gRPC server, the relayer has no websocket, always
+    /// false
+    pub websocket_updates: bool,
+    /// Admin endpoints are enabled by an admin token
+    pub admin: bool,
+}
+
+/// What this deployment supports, for clients to adapt to its configuration. Read from
+/// the live state, a pause shows in the next document
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    pub version: u32,
+    pub relayer_version: String,
+    pub directions: Vec<DirectionCapability>,
+    pub creation: CreationCapabilities,
+    /// Bridges and fees allowed per account and day
+    pub quotas: QuotaLimits,
+    pub callbacks: CallbackCapabilities,
+    pub limits: RequestLimits,
+    pub features: FeatureFlags,
+}
+
+pub fn get_capabilities(state: &AppState) -> Capabilities {
+    let pause = state.pause.state();
+    let now = state.clock.now().as_secs();
+    let directions = [(Chains::EVM, Chains::SOLANA), (Chains::SOLANA, Chains::EVM)]
+        .into_iter()
+        .map(|(origin, destination)| {
+            let active = pause.active_pause(&origin, now);
+            DirectionCapability {
+                enabled: active.is_none(),
+                pause_reason: active.and_then(|pause| pause.reason.clone()),
+                resume_at: active.and_then(|pause| pause.resume_at),
+                origin,
+                destination,
+            }
+        })
+        .collect();
+    let policy = &state.callbacks.policy;
+
+    Capabilities {
+        version: CAPABILITIES_VERSION,
+        relayer_version: RELAYER_VERSION.to_string(),
+        directions,
+        creation: CreationCapabilities {
+            synchronous: state.sync_creation,
+            ownership_signature_required: false,
+            id_scheme: state.id_scheme,
+            idempotency_key_ttl_secs: state.idempotency_key_ttl.as_secs(),
+        },
+        quotas: state.quota_limits,
+        callbacks: CallbackCapabilities {
+            enabled: policy.enabled,
+            allowed_hosts: policy.allowed_hosts.clone(),
+            signed: policy.signing_secret.is_some(),
+        },
+        limits: RequestLimits {
+            max_body_bytes: MAX_REQUEST_BODY_BYTES,
+            max_batch_size: None,
+            max_batch_status_ids: MAX_BATCH_STATUS_IDS,
+            batch_status_poll_secs: BATCH_STATUS_MAX_AGE_SECS,
+        },
+        features: FeatureFlags {
+            dry_run: state.dry_run,
+            preflight: true,
+            websocket_updates: false,
+            admin: state.admin_token.is_some(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod capabilities_test {
+    use serde_json::json;
+    use test_support::test_db;
+    use types::{BridgeDirection, CallbackPolicy, CallbackSender, Chains, Lamports, QuotaLimits};
+
+    use crate::{get_capabilities, test_utils::test_state, Capabilities, CAPABILITIES_VERSION};
+
+    #[tokio::test]
+    async fn test_capabilities_follow_the_configuration() {
+        let (mut state, _rx_evm, _rx_sol) = test_state(test_db());
+        let defaults = get_capabilities(&state);
+        assert_eq!(defaults.version, CAPABILITIES_VERSION);
+        assert!(defaults
+            .directions
+            .iter()
+            .all(|direction| direction.enabled));
+        assert!(!defaults.features.dry_run);
+        assert!(!defaults.creation.synchronous);
+        assert!(defaults.callbacks.enabled);
+        assert_eq!(defaults.quotas, QuotaLimits::default());
+
+        state.dry_run = true;
+        state.sync_creation = true;
+        state.quota_limits.max_requests = Some(5);
+        state.quota_limits.max_solana_fees = Some(Lamports::new(1_000_000));
+        state.callbacks = CallbackSender::new(CallbackPolicy::from_config(
+            false,
+            Some("hooks.example.com"),
+            None,
+        ))
+        .unwrap();
+        state
+            .pause
+            .pause(
+                &state.db,
+                BridgeDirection::SolanaToEvm,
+                Some("maintenance".to_string()),
+                None,
+            )
+            .unwrap();
+
+        let capabilities = serde_json::to_value(get_capabilities(&state)).unwrap();
+        assert_eq!(capabilities["features"]["dry_run"], true);
+        assert_eq!(capabilities["creation"]["synchronous"], true);
+        assert_eq!(capabilities["quotas"]["max_requests"], 5);
+        assert_eq!(
+            capabilities["callbacks"],
+            json!({ "enabled": false, "allowed_hosts": ["hooks.example.com"], "signed": false })
+        );
+        assert_eq!(
+            capabilities["directions"],
+            json!([
+                {
+                    "origin": "EVM",
+                    "destination": "SOLANA",
+                    "enabled": true,
+                    "pause_reason": null,
+                    "resume_at": null,
+                },
+                {
+                    "origin": "SOLANA",
+                    "destination": "EVM",
+                    "enabled": false,
+                    "pause_reason": "maintenance",
+                    "resume_at": null,
+                },
+            ])
+        );
+
+        // Resuming shows in the next document
+        state
+            .pause
+            .resume(&state.db, BridgeDirection::SolanaToEvm)
+            .unwrap();
+        let capabilities = get_capabilities(&state);
+        assert!(capabilities
+            .directions
+            .iter()
+            .all(|direction| direction.enabled));
+        assert_eq!(
+            capabilities.directions[1].origin,
+            Chains::SOLANA,
+            "directions keep their order"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_fields_ignored() {
+        let (state, _rx_evm, _rx_sol) = test_state(test_db());
+        let capabilities = get_capabilities(&state);
+        let mut document = serde_json::to_value(&capabilities).unwrap();
+        // Fields added by a later relayer
+        document["signature_schemes"] = json!(["eip191"]);
+        document["features"]["batch_creation"] = json!(true);
+        document["directions"][0]["max_per_day"] = json!(10);
+
+        let read: Capabilities = serde_json::from_value(document).unwrap();
+        assert_eq!(read, capabilities);
+    }
+}
@@ -0,0 +1,35 @@
+use serde::Deserialize;
+use storage::db::Database;
+use types::{BRequest, BridgeError};
+
+use crate::errors::RequestError;
+
+/// Query of the requests listing, filtered by client reference
+#[derive(Deserialize, Debug, Clone)]
+pub struct RequestsQuery {
+    pub client_reference: Option<String>,
+}
+
+/// Requests created with the client reference `reference`, empty when none was. The
+/// reference is trimmed as it was at creation
+pub fn get_requests_by_client_reference(
+    reference: &str,
+    db: &Database,
+) -> Result<Vec<BRequest>, RequestError> {
+    let reference = reference.trim();
+    if reference.is_empty() {
+        return Err(RequestError::InvalidLabel(
+            "client_reference".to_string(),
+            "must not be empty".to_string(),
+        ));
+    }
+    let db_error = |e: BridgeError| RequestError::CreationError(e.to_string());
+
+    let mut requests = vec![];
+    for request_id in types::requests_by_client_reference(db, reference).map_err(db_error)? {
+        if let Some(request) = types::request_data(&request_id, db).map_err(db_error)? {
+            requests.push(request);
+        }
+    }
+    Ok(requests)
+}
@@ -0,0 +1,147 @@
+use std::str::FromStr;
+
+use alloy::primitives::Address;
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use types::{Clock, CollectionEntry};
+
+use crate::{errors::RequestError, is_admin, AppState};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SetCollectionInput {
+    /// Existing Solana collection NFT the tokens of the contract are verified into
+    pub collection_mint: String,
+}
+
+fn check_admin(admin_token: Option<&str>, state: &AppState) -> Result<(), RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    Ok(())
+}
+
+fn parse_contract(origin_contract: &str) -> Result<(), RequestError> {
+    Address::from_str(origin_contract.trim())
+        .map(|_| ())
+        .map_err(|_| RequestError::InvalidCollection(format!("invalid contract {origin_contract}")))
+}
+
+/// Collections of the bridged EVM contracts, authorized by the admin token
+pub fn get_collections(
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<Vec<CollectionEntry>, RequestError> {
+    check_admin(admin_token, state)?;
+    types::list_collections(&state.db).map_err(|e| RequestError::CreationError(e.to_string()))
+}
+
+/// Sets an existing collection NFT for the tokens of `origin_contract` instead of the one
+/// the relayer creates. Its metadata account must exist
+pub async fn set_collection(
+    origin_contract: &str,
+    input: SetCollectionInput,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<CollectionEntry, RequestError> {
+    check_admin(admin_token, state)?;
+    parse_contract(origin_contract)?;
+    let collection_mint = Pubkey::from_str(input.collection_mint.trim()).map_err(|_| {
+        RequestError::InvalidCollection(format!("invalid mint {}", input.collection_mint))
+    })?;
+
+    match solana::collection_exists(&state.solana_client, &collection_mint).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return Err(RequestError::InvalidCollection(format!(
+                "mint {collection_mint} has no metadata"
+            )))
+        }
+        Err(e) => return Err(RequestError::CreationError(e.to_string())),
+    }
+
+    let entry = CollectionEntry::new(
+        origin_contract,
+        &collection_mint.to_string(),
+        false,
+        state.clock.now(),
+    );
+    types::set_collection(&state.db, &entry)
+        .map_err(|e| RequestError::CreationError(e.to_string()))?;
+    Ok(entry)
+}
+
+/// Removes the collection of `origin_contract`, the next mint creates a new one
+pub fn delete_collection(
+    origin_contract: &str,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<(), RequestError> {
+    check_admin(admin_token, state)?;
+    parse_contract(origin_contract)?;
+    types::remove_collection(&state.db, origin_contract)
+        .map_err(|e| RequestError::CreationError(e.to_string()))
+}
+
+#[cfg(test)]
+mod collections_test {
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::pubkey::Pubkey;
+    use test_support::{account_info_result, mock_rpc, test_db, EVM_TOKEN_CONTRACT};
+
+    use crate::{
+        delete_collection, errors::RequestError, get_collections, set_collection,
+        test_utils::test_state, SetCollectionInput,
+    };
+
+    fn input(mint: &Pubkey) -> SetCollectionInput {
+        SetCollectionInput {
+            collection_mint: mint.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admin_supplies_existing_collection() {
+        let db = test_db();
+        let (mut state, _rx_evm, _rx_sol) = test_state(db.clone());
+        state.admin_token = Some("secret".into());
+        let mint = Pubkey::new_unique();
+        state.solana_client.set_rpc(mock_rpc([(
+            RpcRequest::GetAccountInfo,
+            account_info_result(&[1], &Pubkey::new_unique()),
+        )]));
+
+        assert_eq!(
+            set_collection(EVM_TOKEN_CONTRACT, input(&mint), Some("wrong"), &state)
+                .await
+                .unwrap_err(),
+            RequestError::Unauthorized()
+        );
+        assert!(matches!(
+            set_collection("not a contract", input(&mint), Some("secret"), &state).await,
+            Err(RequestError::InvalidCollection(_))
+        ));
+
+        let entry = set_collection(EVM_TOKEN_CONTRACT, input(&mint), Some("secret"), &state)
+            .await
+            .unwrap();
+        assert_eq!(entry.collection_mint, mint.to_string());
+        assert!(!entry.created_by_relayer);
+        assert_eq!(
+            get_collections(Some("secret"), &state).unwrap(),
+            vec![entry]
+        );
+
+        // The metadata account of the mint is required
+        state.solana_client.set_rpc(mock_rpc([(
+            RpcRequest::GetAccountInfo,
+            serde_json::json!({ "context": { "slot": 1 }, "value": null }),
+        )]));
+        assert!(matches!(
+            set_collection(EVM_TOKEN_CONTRACT, input(&mint), Some("secret"), &state).await,
+            Err(RequestError::InvalidCollection(_))
+        ));
+
+        delete_collection(EVM_TOKEN_CONTRACT, Some("secret"), &state).unwrap();
+        assert!(get_collections(Some("secret"), &state).unwrap().is_empty());
+    }
+}
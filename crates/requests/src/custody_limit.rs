@@ -0,0 +1,115 @@
+use log::{error, info};
+use serde::Serialize;
+use types::{custody_exposure, Chains, CustodyExposure, CustodyLimits};
+
+use crate::{errors::RequestError, AppState};
+
+/// Tokens in bridge custody against the configured caps
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CustodyReport {
+    pub exposure: CustodyExposure,
+    pub limits: CustodyLimits,
+}
+
+/// Custody held by the requests and the caps, None when the exposure can't be read
+pub fn custody_report(state: &AppState) -> Option<CustodyReport> {
+    custody_exposure(&state.db)
+        .inspect_err(|e| error!("Could not count the tokens in custody: {e}"))
+        .ok()
+        .map(|exposure| CustodyReport {
+            exposure,
+            limits: state.custody_limits,
+        })
+}
+
+/// Refuses a new bridge originated in `origin` while the custody is at its cap, accepted
+/// again once enough requests complete or are refunded
+pub fn check_custody_limit(origin: &Chains, state: &AppState) -> Result<(), RequestError> {
+    if !state.custody_limits.is_enforced() {
+        return Ok(());
+    }
+    let exposure =
+        custody_exposure(&state.db).map_err(|e| RequestError::CreationError(e.to_string()))?;
+    match state.custody_limits.reached(origin, &exposure) {
+        Some(reached) => {
+            info!(
+                "Rejecting new {origin:?} bridge, {} custody limit of {} reached",
+                reached.scope, reached.limit
+            );
+            Err(RequestError::CustodyLimitReached(
+                reached.scope,
+                reached.limit,
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod custody_limit_test {
+    use test_support::test_db;
+    use types::{BRequest, Chains, CustodyExposure, InputRequest, Status, Timestamp};
+
+    use crate::{
+        check_custody_limit, custody_report, errors::RequestError, test_utils::test_state,
+    };
+
+    fn request(token_id: &str, origin: Chains) -> BRequest {
+        BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xcontract".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: origin,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        )
+    }
+
+    #[test]
+    fn test_limit_enforced_and_resumed_as_requests_complete() {
+        let db = test_db();
+        let (mut state, _, _) = test_state(db.clone());
+        state.custody_limits.max_evm_to_solana = Some(2);
+        state.custody_limits.max_total = Some(3);
+
+        let mut held: Vec<BRequest> = ["1", "2"]
+            .into_iter()
+            .map(|token_id| {
+                let mut request = request(token_id, Chains::EVM);
+                request.status = Status::TokenReceived;
+                request.save(&db).unwrap();
+                request
+            })
+            .collect();
+        assert_eq!(
+            check_custody_limit(&Chains::EVM, &state),
+            Err(RequestError::CustodyLimitReached(
+                "evm_to_solana".to_string(),
+                2
+            ))
+        );
+        check_custody_limit(&Chains::SOLANA, &state).unwrap();
+
+        let mut other_direction = request("3", Chains::SOLANA);
+        other_direction.status = Status::TokenMinted;
+        other_direction.save(&db).unwrap();
+        assert_eq!(
+            check_custody_limit(&Chains::SOLANA, &state),
+            Err(RequestError::CustodyLimitReached("total".to_string(), 3))
+        );
+
+        // A completed mint frees its place without any operator action
+        held[0].update_state(&db, Timestamp::now()).unwrap();
+        held[0]
+            .complete_minted(&db, "mint", "account", Timestamp::now())
+            .unwrap();
+        check_custody_limit(&Chains::EVM, &state).unwrap();
+        check_custody_limit(&Chains::SOLANA, &state).unwrap();
+
+        let report = custody_report(&state).unwrap();
+        assert_eq!(report.exposure, CustodyExposure { evm: 1, solana: 1 });
+        assert_eq!(report.limits, state.custody_limits);
+    }
+}
@@ -0,0 +1,106 @@
+use log::{error, info};
+use types::{redact_urls, BRequest, RequestId, Status};
+
+use crate::{continue_from_metadata, errors::RequestError, AppState};
+
+/// Checks the destination token account of a request parked in AwaitingDestinationAccount
+/// now instead of at the next sweep. Once funded the request resumes and its mint is
+/// sent, a mint failing to be sent is retried by the sweep
+pub async fn recheck_request(
+    request_id: &RequestId,
+    state: &AppState,
+) -> Result<BRequest, RequestError> {
+    let Some(_lock) = state.request_locks.try_lock(request_id) else {
+        return Err(RequestError::RecheckNotAllowed(
+            "request is being processed".to_string(),
+        ));
+    };
+    let mut request = match types::request_data(request_id, &state.db) {
+        Ok(Some(request)) => request,
+        _ => return Err(RequestError::NoExistingRequest(request_id.to_string())),
+    };
+    if request.status != Status::AwaitingDestinationAccount {
+        return Err(RequestError::RecheckNotAllowed(format!(
+            "request in status {:?} is not waiting for its destination account",
+            request.status
+        )));
+    }
+
+    let resumed = solana::recheck_destination_account(
+        &state.solana_client,
+        &state.db,
+        &mut request,
+        state.clock.now(),
+    )
+    .await
+    .map_err(|e| RequestError::RecheckNotAllowed(redact_urls(&e.to_string())))?;
+    if !resumed {
+        return Ok(request);
+    }
+    info!("Request {} resumed by a recheck", request.id);
+    if let Err(e) = continue_from_metadata(state, &request).await {
+        error!(
+            "Could not send the mint of request {}, the sweep retries it: {e}",
+            request.id
+        );
+    }
+    Ok(types::request_data(request_id, &state.db)
+        .ok()
+        .flatten()
+        .unwrap_or(request))
+}
+
+#[cfg(test)]
+mod destination_account_test {
+    use solana_client::rpc_request::RpcRequest;
+    use test_support::{balance_result, mock_rpc, request_in_status, test_db};
+    use types::{Chains, Status, TOKEN_ACCOUNT_RENT};
+
+    use crate::{errors::RequestError, recheck_request, test_utils::test_state};
+
+    #[tokio::test]
+    async fn test_recheck_resumes_a_funded_request() {
+        let db = test_db();
+        let (mut state, _, _) = test_state(db.clone());
+        state.solana_client.require_prefunded_ata = true;
+        let request = request_in_status(Chains::EVM, "1", Status::AwaitingDestinationAccount);
+        request.save(&db).unwrap();
+
+        state
+            .solana_client
+            .set_rpc(mock_rpc([(RpcRequest::GetBalance, balance_result(0))]));
+        let unfunded = recheck_request(&request.id, &state).await.unwrap();
+        assert_eq!(unfunded.status, Status::AwaitingDestinationAccount);
+
+        // Nothing listens on the EVM node, the mint is retried by the sweep
+        state.evm_client = state
+            .evm_client
+            .with_endpoints("http://127.0.0.1:1", "ws://127.0.0.1:1");
+        state.solana_client.set_rpc(mock_rpc([(
+            RpcRequest::GetBalance,
+            balance_result(TOKEN_ACCOUNT_RENT.raw()),
+        )]));
+        let resumed = recheck_request(&request.id, &state).await.unwrap();
+        assert_eq!(resumed.status, Status::TokenReceived);
+        assert_eq!(resumed.destination_account_pending, None);
+
+        assert!(matches!(
+            recheck_request(&request.id, &state).await,
+            Err(RequestError::RecheckNotAllowed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_no_recheck_while_the_request_is_processed() {
+        let db = test_db();
+        let (state, _, _) = test_state(db.clone());
+        let request = request_in_status(Chains::EVM, "1", Status::AwaitingDestinationAccount);
+        request.save(&db).unwrap();
+
+        let _lock = state.request_locks.try_lock(&request.id).unwrap();
+        assert_eq!(
+            recheck_request(&request.id, &state).await.unwrap_err(),
+            RequestError::RecheckNotAllowed("request is being processed".to_string())
+        );
+    }
+}
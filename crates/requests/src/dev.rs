@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use evm::{dev_chain_status, DevChainStatus};
+use log::{info, warn};
+use types::{BridgeError, Lamports, Result};
+
+use crate::AppState;
+
+/// Balance the relayer keypair is topped up to on a local validator
+pub const DEV_MIN_BALANCE: Lamports = Lamports::new(10_000_000_000);
+
+const AIRDROP_CONFIRM_ATTEMPTS: u32 = 30;
+const AIRDROP_CONFIRM_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone)]
+pub struct DevEnvironment {
+    pub solana_balance: Lamports,
+    /// Lamports requested when the balance was below the minimum
+    pub airdropped: Option<Lamports>,
+    pub evm: DevChainStatus,
+}
+
+/// Prepares the local validators for the relayer: airdrops SOL to the Solana fee payer when
+/// it is below `min_balance` and checks both bridges are deployed
+pub async fn bootstrap_dev_environment(
+    state: &AppState,
+    min_balance: Lamports,
+) -> Result<DevEnvironment> {
+    if !state.dev_mode {
+        return Err(BridgeError::Other(
+            "The dev environment bootstrap only runs in dev mode".to_string(),
+        ));
+    }
+
+    let solana = &state.solana_client;
+    let fee_payer = solana.fee_payer_pubkey();
+    let mut solana_balance = Lamports::new(solana.get_balance(&fee_payer).await?);
+    let mut airdropped = None;
+
+    if let Some(missing) = min_balance
+        .checked_sub(solana_balance)
+        .filter(|m| m.raw() > 0)
+    {
+        info!("Airdropping {missing} to the Solana fee payer {fee_payer}");
+        let signature = solana.request_airdrop(&fee_payer, missing.raw()).await?;
+
+        let mut confirmed = false;
+        for _ in 0..AIRDROP_CONFIRM_ATTEMPTS {
+            if solana.confirm_transaction(&signature).await? {
+                confirmed = true;
+                break;
+            }
+            tokio::time::sleep(AIRDROP_CONFIRM_INTERVAL).await;
+        }
+        if !confirmed {
+            return Err(BridgeError::Other(format!(
+                "Airdrop {signature} was not confirmed"
+            )));
+        }
+        solana_balance = Lamports::new(solana.get_balance(&fee_payer).await?);
+        airdropped = Some(missing);
+    }
+
+    if !solana.account_exists(&solana.bridge_program).await? {
+        return Err(BridgeError::Other(format!(
+            "Solana bridge program {} is not deployed",
+            solana.bridge_program
+        )));
+    }
+    if !solana.account_exists(&solana.bridge_account).await? {
+        return Err(BridgeError::Other(format!(
+            "Solana bridge account {} is not initialized",
+            solana.bridge_account
+        )));
+    }
+
+    let evm = dev_chain_status(state.evm_client.clone()).await?;
+    if !evm.bridge_deployed {
+        return Err(BridgeError::Other(format!(
+            "EVM bridge contract {} is not deployed",
+            state.evm_client.bridge_contract
+        )));
+    }
+    if evm.signer_balance.raw() == 0 {
+        warn!("EVM signer {} has no balance", evm.signer);
+    }
+
+    info!(
+        "Dev environment ready, Solana balance {solana_balance}, EVM chain {} balance {}",
+        evm.chain_id, evm.signer_balance
+    );
+    Ok(DevEnvironment {
+        solana_balance,
+        airdropped,
+        evm,
+    })
+}
@@ -1,36 +1,169 @@
-use std::str::FromStr;
-
-use crate::{add_pending_request, errors::RequestError, AppState};
-use alloy::primitives::Address;
-use log::{error, info};
-use solana_sdk::pubkey::Pubkey;
+use crate::{
+    add_pending_request, check_contract_or_mint, check_custody_limit, check_destination_account,
+    errors::RequestError, is_admin, split_by_origin, AppState,
+};
+use log::{debug, error, info};
+use serde_json::{json, Value};
 use storage::db::Database;
-use types::{BRequest, Chains, InputRequest, Status};
+use types::{
+    check_quota, clear_send_intent, custody_conflict, debug_detail, record_quota_request, BRequest,
+    BridgeAddresses, BridgeError, CallbackDelivery, Chains, ClientLabels, Clock, FailureReport,
+    IdMigrationReport, InputRequest, InvalidLabel, LoggableRequest, MessageNewRequest, RequestId,
+    Result, SendOperation, Status, Timestamp, TxMessage,
+};
 
+#[tracing::instrument(skip_all)]
 pub async fn new_request(
-    input_request: InputRequest,
+    mut input_request: InputRequest,
+    callback_url: Option<String>,
+    labels: ClientLabels,
     state: AppState,
 ) -> Result<BRequest, RequestError> {
-    info!("New request received {:?}", input_request);
+    info!(
+        "New request received {}",
+        LoggableRequest::new(&input_request)
+    );
+    if let Some(detail) = debug_detail(&input_request) {
+        debug!("New request input {detail}");
+    }
+
+    if let Some(pause) = state.pause.paused(&input_request.origin_network) {
+        let reason = pause
+            .reason
+            .unwrap_or_else(|| "maintenance in progress".to_string());
+        info!("Rejecting new request, bridge paused: {reason}");
+        return Err(RequestError::BridgePaused(reason));
+    }
+
+    let callback = match callback_url {
+        Some(url) => match state.callbacks.policy.validate(&url) {
+            Ok(url) => Some(CallbackDelivery::new(url)),
+            Err(reason) => {
+                info!("Rejecting new request, callback URL {url}: {reason}");
+                return Err(RequestError::InvalidCallbackUrl(reason));
+            }
+        },
+        None => None,
+    };
 
-    let mut request = BRequest::new(input_request);
+    let labels = match labels.validate() {
+        Ok(labels) => labels,
+        Err(InvalidLabel { field, reason }) => {
+            info!("Rejecting new request, {field} {reason}");
+            return Err(RequestError::InvalidLabel(field.to_string(), reason));
+        }
+    };
 
-    if already_existing_request(&request.id, &state.db) {
-        return Err(RequestError::AlreadyExistingRequest(request.id));
+    // Hashed in decimal, the same token gets the same id whatever form it was sent in
+    if input_request.origin_network == Chains::EVM {
+        match types::normalize_token_id(&input_request.token_id) {
+            Ok(token_id) => input_request.token_id = token_id,
+            Err(e) => {
+                info!("Rejecting new request, {e}");
+                return Err(RequestError::InvalidTokenId(e.to_string()));
+            }
+        }
     }
 
-    let tx_hash = match request.input.origin_network {
-        Chains::EVM => {
-            let detination_pubkey = Pubkey::from_str(&request.input.destination_account);
-            if detination_pubkey.is_err() {
-                error!("Invalid destination account {:?}", detination_pubkey.err());
-                return Err(RequestError::InvalidDestinationAccount());
+    let mut request = BRequest::with_id_scheme(input_request, state.id_scheme, state.clock.now());
+    request.callback = callback;
+    request.client_reference = labels.client_reference;
+    request.tags = labels.tags;
+    // Kept through an upgrade of the bridge, the request finishes where it started
+    request.bridge_addresses = Some(current_bridge_addresses(&state));
+
+    if already_existing_request(&request, &state) {
+        return Err(RequestError::AlreadyExistingRequest(request.id.to_string()));
+    }
+
+    match custody_conflict(&state.db, &request.input, &request.id) {
+        Ok(Some(conflicting)) => {
+            info!(
+                "Rejecting request {}, token already bridging in request {conflicting}",
+                request.id
+            );
+            return Err(RequestError::TokenAlreadyBridging(conflicting));
+        }
+        Ok(None) => {}
+        Err(e) => return Err(RequestError::CreationError(e.to_string())),
+    }
+
+    let now = state.clock.now();
+    match check_quota(&state.db, &request.input, &state.quota_limits, now) {
+        Ok(Some(exceeded)) => {
+            info!(
+                "Rejecting request {}, quota of {} exceeded",
+                request.id, exceeded.account
+            );
+            return Err(RequestError::QuotaExceeded(
+                exceeded.account,
+                exceeded.reset_at.as_secs(),
+            ));
+        }
+        Ok(None) => {}
+        Err(e) => return Err(RequestError::CreationError(e.to_string())),
+    }
+
+    check_custody_limit(&request.input.origin_network, &state)?;
+
+    // Addresses of the wrong chain are told apart from malformed ones, the usual mistake
+    // is posting to the endpoint of the other direction
+    let origin = &request.input.origin_network;
+    if let Err(e) = check_destination_account(origin, &request.input.destination_account)
+        .and_then(|_| check_contract_or_mint(origin, &request.input.contract_or_mint))
+    {
+        info!("Rejecting request {}, {e}", request.id);
+        return Err(e);
+    }
+
+    if request.input.origin_network == Chains::EVM {
+        // A token held by a vault or wrapper is locked from it when the token owner is
+        // its operator or delegate. Other holders fail the transfer check below
+        match evm::check_token_ownership(
+            state.evm_client.clone(),
+            &request.input.contract_or_mint,
+            &request.input.token_owner,
+            &request.input.token_id,
+        )
+        .await
+        {
+            Ok(evm::Ownership::Authorized(chain)) => request.custody_chain = Some(chain),
+            Ok(evm::Ownership::Owner | evm::Ownership::NotOwner { .. }) => {}
+            Err(e) => error!("Ownership check of request {} failed: {e}", request.id),
+        }
+
+        // Soulbound and restricted tokens are refused with the reason instead of the
+        // generic revert of the bridge request, node failures are left to that request
+        match evm::check_token_transferable(
+            state.evm_client.clone(),
+            &request.input.contract_or_mint,
+            request.lock_owner(),
+            &request.input.token_id,
+        )
+        .await
+        {
+            Ok(evm::Transferability::Transferable) => {}
+            Ok(evm::Transferability::NotTransferable(reason)) => {
+                return Err(RequestError::TokenNotTransferable(reason));
             }
+            Err(e) => error!(
+                "Transferability check of request {} failed: {e}",
+                request.id
+            ),
+        }
+    }
+
+    if !state.sync_creation {
+        return accept_request(request, &state, now).await;
+    }
 
+    let tx_hash = match request.input.origin_network {
+        Chains::EVM => {
             match evm::initialize_evm_request(
-                state.evm_client,
+                state.evm_client.clone(),
+                &state.db,
                 &request.input.contract_or_mint,
-                &request.input.token_owner,
+                request.lock_owner(),
                 &request.input.token_id,
                 &request.id,
             )
@@ -39,19 +172,23 @@ pub async fn new_request(
                 Ok(tx) => tx,
                 Err(err) => {
                     error!("Ethereum transaction has failed {:?}", err);
+                    match err.root() {
+                        BridgeError::GasLimitExceeded(reason) => {
+                            return Err(RequestError::GasLimitExceeded(reason.clone()));
+                        }
+                        auth_err @ BridgeError::NotAuthorizedBackend(_) => {
+                            return Err(RequestError::NotAuthorizedBackend(auth_err.to_string()));
+                        }
+                        _ => {}
+                    }
                     return Err(RequestError::EVMTxError());
                 }
             }
         }
         Chains::SOLANA => {
-            let destination_owner = Address::from_str(&request.input.destination_account);
-            if destination_owner.is_err() {
-                error!("Invalid destination account {:?}", destination_owner.err());
-                return Err(RequestError::InvalidDestinationAccount());
-            }
-
             match solana::initialize_request(
                 &state.solana_client,
+                &state.db,
                 &request.input.contract_or_mint,
                 &request.input.token_owner,
                 &request.id,
@@ -61,6 +198,9 @@ pub async fn new_request(
                 Ok(tx) => tx.to_string(),
                 Err(err) => {
                     error!("Solana transaction has failed {:?}", err);
+                    if let auth_err @ BridgeError::NotAuthorizedBackend(_) = err.root() {
+                        return Err(RequestError::NotAuthorizedBackend(auth_err.to_string()));
+                    }
                     return Err(RequestError::SolanaTxError());
                 }
             }
@@ -70,24 +210,114 @@ pub async fn new_request(
     if request.add_tx(&tx_hash, &state.db).is_err() {
         return Err(RequestError::CreationError("".to_string()));
     }
+    clear_send_intent(&state.db, &request.id, SendOperation::Lock);
+    register_request(&request, &state, now);
+
+    Ok(request)
+}
+
+/// Makes a stored request pending and counts it in the quotas of its accounts
+fn register_request(request: &BRequest, state: &AppState, now: Timestamp) {
+    // Looked up as missing by the existence check of the creation
+    state.missing_requests.invalidate(&request.id);
+    if let Some(alias_id) = &request.alias_id {
+        state.missing_requests.invalidate(alias_id);
+    }
 
     _ = add_pending_request(&request.id, &state.db);
+    if let Err(e) = record_quota_request(&state.db, &request.input, now) {
+        error!("Could not count request {} in its quotas: {e}", request.id);
+    }
+}
 
+/// Stores the request as Initializing and queues its lock transaction, the processor of
+/// the origin chain sends it in the background. A message lost before it is processed is
+/// queued again by the sweep
+async fn accept_request(
+    mut request: BRequest,
+    state: &AppState,
+    now: Timestamp,
+) -> Result<BRequest, RequestError> {
+    request.status = Status::Initializing;
+    if let Err(e) = request.save(&state.db) {
+        return Err(RequestError::CreationError(e.to_string()));
+    }
+    register_request(&request, state, now);
+
+    if let Err(e) = enqueue_lock(state, &request).await {
+        error!("Lock of request {} left to the sweep: {e}", request.id);
+    }
+    info!(
+        "Request {} accepted, its lock transaction is queued",
+        request.id
+    );
     Ok(request)
 }
 
-pub fn get_request(request_id: &str, db: &Database) -> Result<Option<BRequest>, RequestError> {
-    if let Ok(Some(request)) = types::request_data(request_id, db) {
-        return Ok(Some(request));
-    } else {
+/// Bridge addresses the clients call, recorded on the new requests
+pub fn current_bridge_addresses(state: &AppState) -> BridgeAddresses {
+    BridgeAddresses {
+        evm_contract: state.evm_client.bridge_contract.to_string(),
+        solana_program: state.solana_client.bridge_program.to_string(),
+        solana_account: state.solana_client.bridge_account.to_string(),
+    }
+}
+
+/// Sends the NewRequest message of an Initializing request to the processor of its
+/// origin chain
+pub async fn enqueue_lock(state: &AppState, request: &BRequest) -> Result<()> {
+    let message = TxMessage::NewRequest(MessageNewRequest {
+        token_contract: request.input.contract_or_mint.clone(),
+        token_owner: request.lock_owner().to_string(),
+        token_id: request.input.token_id.clone(),
+        request_id: request.id.clone(),
+        trace_context: request.trace_context.clone(),
+    });
+    let sent = match request.input.origin_network {
+        Chains::EVM => state.solana_client.tx_channel.send(message).await,
+        Chains::SOLANA => state.evm_client.tx_channel.send(message).await,
+    };
+    sent.map_err(|e| BridgeError::Other(format!("Could not enqueue lock: {e}")))
+}
+
+/// Ids not found are kept in the negative cache of the state and answered from it until
+/// their TTL or the creation of the request
+pub fn get_request(
+    request_id: &RequestId,
+    state: &AppState,
+) -> Result<Option<BRequest>, RequestError> {
+    let now = state.clock.now();
+    if state.missing_requests.is_missing(request_id, now) {
         return Err(RequestError::NoExistingRequest(request_id.to_string()));
     }
+    match types::request_data(request_id, &state.db) {
+        Ok(Some(request)) => Ok(Some(request)),
+        Ok(None) => {
+            debug!("Request {request_id} not found");
+            state.missing_requests.insert(request_id, now);
+            Err(RequestError::NoExistingRequest(request_id.to_string()))
+        }
+        Err(e) => {
+            error!("Could not read request {request_id}: {e}");
+            Err(RequestError::NoExistingRequest(request_id.to_string()))
+        }
+    }
 }
 
-pub fn already_existing_request(request_id: &str, db: &Database) -> bool {
-    if let Ok(Some(request)) = get_request(request_id, db) {
-        if request.status != Status::Canceled && request.status != Status::Completed {
-            return true;
+/// True when an active request is stored under the id of `request` or its alias, so a
+/// request created under one id scheme is found from the other
+pub fn already_existing_request(request: &BRequest, state: &AppState) -> bool {
+    for request_id in [Some(&request.id), request.alias_id.as_ref()]
+        .into_iter()
+        .flatten()
+    {
+        if let Ok(Some(existing)) = get_request(request_id, state) {
+            if !matches!(
+                existing.status,
+                Status::Canceled | Status::Completed | Status::Refunded
+            ) {
+                return true;
+            }
         }
     }
     return false;
@@ -98,7 +328,337 @@ pub fn get_pending_requests(db: &Database) -> Option<Vec<String>> {
     requests
 }
 
+/// Unified pending view with the number of requests of each origin chain
+pub fn get_pending_summary(db: &Database) -> Value {
+    let pending = types::pending_requests(db).unwrap_or_default();
+    let total = pending.len();
+    let (evm_pending, solana_pending) = split_by_origin(pending, db);
+
+    json!({
+        "total": total,
+        "EVM": evm_pending.len(),
+        "SOLANA": solana_pending.len(),
+    })
+}
+
+/// Place of the request in the pending queue starting at 1, None when not pending
+pub fn get_queue_position(
+    request_id: &RequestId,
+    db: &Database,
+) -> Result<Option<usize>, RequestError> {
+    types::pending_position(db, request_id).map_err(|e| RequestError::CreationError(e.to_string()))
+}
+
+/// Report of a request given up after its last retry, None when it did not fail
+pub fn get_failure_report(
+    request_id: &RequestId,
+    db: &Database,
+) -> Result<Option<FailureReport>, RequestError> {
+    types::request_data(request_id, db)
+        .map(|request| request.and_then(|request| request.failure_report))
+        .map_err(|e| RequestError::CreationError(e.to_string()))
+}
+
+/// Stored requests counted by id scheme, authorized by the admin token
+pub fn get_id_migration_report(
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<IdMigrationReport, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    types::id_migration_report(&state.db).map_err(|e| RequestError::CreationError(e.to_string()))
+}
+
 pub fn get_completed_requests(db: &Database) -> Option<Vec<String>> {
     let requests = types::completed_requests(db);
     requests
 }
+
+#[cfg(test)]
+mod endpoints_test {
+    use std::sync::Arc;
+
+    use solana_sdk::pubkey::Pubkey;
+    use test_support::{input_request, test_db};
+    use types::{
+        BRequest, BridgeDeployment, CallbackPolicy, CallbackSender, Chains, ClientLabels, IdScheme,
+        InputRequest, Status, Timestamp, DEFAULT_BRIDGE_TRANSITION,
+    };
+
+    use crate::{
+        already_existing_request, current_bridge_addresses, errors::RequestError,
+        get_id_migration_report, get_request, new_request, test_utils::test_state,
+    };
+
+    #[test]
+    fn test_missing_request_answered_from_cache() {
+        let db = test_db();
+        let (state, _, _) = test_state(db.clone());
+        let request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xabc123".to_string(),
+                token_id: "42".to_string(),
+                token_owner: "0xowner456".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination789".to_string(),
+            },
+            Timestamp::now(),
+        );
+
+        let missing = Some(RequestError::NoExistingRequest(request.id.to_string()));
+        assert_eq!(get_request(&request.id, &state).err(), missing);
+        assert_eq!(state.missing_requests.hit_count(), 0);
+
+        // Saved without going through new_request, the cached miss still answers
+        request.save(&db).unwrap();
+        assert_eq!(get_request(&request.id, &state).err(), missing);
+        assert_eq!(state.missing_requests.hit_count(), 1);
+
+        state.missing_requests.invalidate(&request.id);
+        assert_eq!(
+            get_request(&request.id, &state)
+                .unwrap()
+                .map(|found| found.id),
+            Some(request.id.clone())
+        );
+    }
+
+    #[test]
+    fn test_existing_request_found_under_either_id() {
+        let db = test_db();
+        let (state, _, _) = test_state(db.clone());
+        let input = InputRequest {
+            contract_or_mint: "0xabc123".to_string(),
+            token_id: "42".to_string(),
+            token_owner: "0xowner456".to_string(),
+            origin_network: Chains::EVM,
+            destination_account: "destination789".to_string(),
+        };
+
+        // Created before the migration, stored under the legacy id without an alias
+        let mut legacy = BRequest::new(input.clone(), Timestamp::now());
+        legacy.save(&db).unwrap();
+        let candidate =
+            BRequest::with_id_scheme(input.clone(), IdScheme::ChainAware, Timestamp::now());
+        assert_ne!(candidate.id, legacy.id);
+        assert!(already_existing_request(&candidate, &state));
+
+        legacy.status = Status::Completed;
+        legacy.save(&db).unwrap();
+        assert!(!already_existing_request(&candidate, &state));
+
+        // Stored under the new id, found by the legacy id through its alias
+        let mut other = input;
+        other.token_id = "43".to_string();
+        BRequest::with_id_scheme(other.clone(), IdScheme::ChainAware, Timestamp::now())
+            .save(&db)
+            .unwrap();
+        let candidate = BRequest::with_id_scheme(other, IdScheme::Legacy, Timestamp::now());
+        assert!(already_existing_request(&candidate, &state));
+    }
+
+    #[test]
+    fn test_id_migration_report_needs_admin_token() {
+        let db = test_db();
+        let (mut state, _, _) = test_state(db.clone());
+        state.admin_token = Some("secret".into());
+        BRequest::with_id_scheme(
+            InputRequest {
+                contract_or_mint: "0xabc123".to_string(),
+                token_id: "42".to_string(),
+                token_owner: "0xowner456".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination789".to_string(),
+            },
+            IdScheme::ChainAware,
+            Timestamp::now(),
+        )
+        .save(&db)
+        .unwrap();
+
+        assert_eq!(
+            get_id_migration_report(None, &state).unwrap_err(),
+            RequestError::Unauthorized()
+        );
+        let report = get_id_migration_report(Some("secret"), &state).unwrap();
+        assert_eq!((report.legacy, report.chain_aware), (0, 1));
+        assert_eq!(report.aliases, 1);
+    }
+
+    #[tokio::test]
+    async fn test_new_request_rejects_token_already_bridging() {
+        let db = test_db();
+        let (state, _, _) = test_state(db.clone());
+
+        let input = InputRequest {
+            contract_or_mint: "0xabc123".to_string(),
+            token_id: "42".to_string(),
+            token_owner: "0xowner456".to_string(),
+            origin_network: Chains::EVM,
+            destination_account: "destination789".to_string(),
+        };
+        let active = BRequest::new(input.clone(), Timestamp::now());
+        active.save(&db).unwrap();
+
+        // Same token under another owner, with an equivalent contract and token id
+        let mut other = input;
+        other.contract_or_mint = "0xABC123".to_string();
+        other.token_id = "0x2a".to_string();
+        other.token_owner = "0xmanual".to_string();
+        assert_eq!(
+            new_request(other, None, ClientLabels::default(), state)
+                .await
+                .err(),
+            Some(RequestError::TokenAlreadyBridging(active.id.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_new_request_normalizes_token_id() {
+        let db = test_db();
+        let (state, _, _) = test_state(db.clone());
+        let input = InputRequest {
+            contract_or_mint: "0xabc123".to_string(),
+            token_id: "420".to_string(),
+            token_owner: "0xowner456".to_string(),
+            origin_network: Chains::EVM,
+            destination_account: "destination789".to_string(),
+        };
+        let decimal = BRequest::with_id_scheme(input.clone(), state.id_scheme, Timestamp::now());
+        decimal.save(&db).unwrap();
+
+        // The hex form is the same token, and the same request
+        let mut hex = input.clone();
+        hex.token_id = "0x1A4".to_string();
+        assert_eq!(
+            new_request(hex, None, ClientLabels::default(), state.clone())
+                .await
+                .err(),
+            Some(RequestError::AlreadyExistingRequest(decimal.id.to_string()))
+        );
+
+        for token_id in ["", "1a4", "0x", "-1", "1_000"] {
+            let mut invalid = input.clone();
+            invalid.token_id = token_id.to_string();
+            assert!(
+                matches!(
+                    new_request(invalid, None, ClientLabels::default(), state.clone()).await,
+                    Err(RequestError::InvalidTokenId(_))
+                ),
+                "{token_id:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_new_request_rejects_invalid_callback_url() {
+        let db = test_db();
+        let (mut state, _, _) = test_state(db.clone());
+        let input = InputRequest {
+            contract_or_mint: "0xabc123".to_string(),
+            token_id: "42".to_string(),
+            token_owner: "0xowner456".to_string(),
+            origin_network: Chains::EVM,
+            destination_account: "destination789".to_string(),
+        };
+        let callback = Some("https://hooks.example.com/job/1".to_string());
+
+        state.callbacks =
+            CallbackSender::new(CallbackPolicy::from_config(false, None, None)).unwrap();
+        assert_eq!(
+            new_request(
+                input.clone(),
+                callback.clone(),
+                ClientLabels::default(),
+                state.clone()
+            )
+            .await
+            .err(),
+            Some(RequestError::InvalidCallbackUrl(
+                "Per-request callbacks are disabled".to_string()
+            ))
+        );
+
+        state.callbacks = CallbackSender::new(CallbackPolicy::from_config(
+            true,
+            Some("hooks.example.com"),
+            None,
+        ))
+        .unwrap();
+        for (url, reason) in [
+            (
+                "http://hooks.example.com/job/1",
+                "Callback URL must be https",
+            ),
+            (
+                "https://other.example.com/job/1",
+                "Callback host other.example.com not allowed",
+            ),
+        ] {
+            assert_eq!(
+                new_request(
+                    input.clone(),
+                    Some(url.to_string()),
+                    ClientLabels::default(),
+                    state.clone()
+                )
+                .await
+                .err(),
+                Some(RequestError::InvalidCallbackUrl(reason.to_string()))
+            );
+        }
+        assert!(
+            types::request_data(&BRequest::new(input, Timestamp::now()).id, &db)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_requests_keep_the_bridge_they_were_created_against() {
+        let db = test_db();
+        let (mut state, _rx_evm, _rx_sol) = test_state(db.clone());
+        // Each one of another mint, the custody of a mint is requested once
+        let create = |state: &crate::AppState| {
+            let mut input = input_request(Chains::SOLANA, "1");
+            input.contract_or_mint = Pubkey::new_unique().to_string();
+            new_request(input, None, ClientLabels::default(), state.clone())
+        };
+        let first = current_bridge_addresses(&state);
+        let before = create(&state).await.unwrap();
+        assert_eq!(before.bridge_addresses, Some(first.clone()));
+
+        // Restarted with the upgraded contract and program
+        state.evm_client = state.evm_client.at_contract(
+            "0x00000000000000000000000000000000000000b2"
+                .parse()
+                .unwrap(),
+        );
+        state.solana_client.bridge_program = Pubkey::new_unique();
+        state.solana_client.bridge_account = Pubkey::new_unique();
+        let upgraded = current_bridge_addresses(&state);
+        let mut deployment = BridgeDeployment::new(first.clone());
+        deployment.upgrade(
+            upgraded.clone(),
+            Timestamp::now(),
+            DEFAULT_BRIDGE_TRANSITION,
+        );
+        let deployment = Arc::new(deployment);
+        state.evm_client = state.evm_client.with_bridge_deployment(deployment.clone());
+        state.solana_client = state.solana_client.with_bridge_deployment(deployment);
+
+        let after = create(&state).await.unwrap();
+        assert_eq!(after.bridge_addresses, Some(upgraded.clone()));
+
+        // Each request is processed against the bridge it was created against
+        for (request, addresses) in [(&before, &first), (&after, &upgraded)] {
+            let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+            let evm = state.evm_client.for_request(&stored).unwrap();
+            assert_eq!(evm.bridge_contract.to_string(), addresses.evm_contract);
+            let solana = state.solana_client.for_request(&stored).unwrap();
+            assert_eq!(solana.bridge_program.to_string(), addresses.solana_program);
+            assert_eq!(solana.bridge_account.to_string(), addresses.solana_account);
+        }
+    }
+}
@@ -1,3 +1,8 @@
+use log::error;
+use types::{redact_urls, BridgeError, ErrorClass, ErrorComponent, ErrorRecord, RequestId};
+
+use crate::AppState;
+
 #[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
 pub enum RequestError {
     #[error("Database request creation error: {0}")]
@@ -15,6 +20,192 @@ pub enum RequestError {
     #[error("A request with that id doesn't exist: {0}")]
     NoExistingRequest(String),
 
-    #[error("Invalid destination account")]
-    InvalidDestinationAccount(),
+    /// Field, reason and a hint when the value is an address of the other chain
+    #[error("Invalid {0}: {1}")]
+    InvalidAccount(String, String, Option<String>),
+
+    #[error("Relayer is not the authorized bridge backend: {0}")]
+    NotAuthorizedBackend(String),
+
+    #[error("Bridge paused: {0}")]
+    BridgePaused(String),
+
+    #[error("Not authorized")]
+    Unauthorized(),
+
+    #[error("Mint can't be redirected: {0}")]
+    RedirectNotAllowed(String),
+
+    #[error("Request can't be reprocessed: {0}")]
+    ReprocessNotAllowed(String),
+
+    #[error("Request can't be refunded: {0}")]
+    RefundNotAllowed(String),
+
+    #[error("Metadata can't be refreshed: {0}")]
+    RefreshNotAllowed(String),
+
+    #[error("Destination account can't be rechecked: {0}")]
+    RecheckNotAllowed(String),
+
+    #[error("Load test not allowed: {0}")]
+    LoadTestNotAllowed(String),
+
+    #[error("Request can't be claimed: {0}")]
+    ClaimNotAllowed(String),
+
+    #[error("Token already bridging in request {0}")]
+    TokenAlreadyBridging(String),
+
+    #[error("Invalid collection: {0}")]
+    InvalidCollection(String),
+
+    #[error("Gas limit exceeded: {0}")]
+    GasLimitExceeded(String),
+
+    /// Account over its quota, with the unix time (secs) the quota resets
+    #[error("Quota of {0} exceeded until {1}")]
+    QuotaExceeded(String, u64),
+
+    /// Scope of the cap reached and its value
+    #[error("Custody limit reached: {1} tokens in custody for {0}")]
+    CustodyLimitReached(String, usize),
+
+    #[error("Invalid callback URL: {0}")]
+    InvalidCallbackUrl(String),
+
+    #[error("Invalid backfill range: {0}")]
+    InvalidBackfillRange(String),
+
+    #[error("Invalid time range: {0}")]
+    InvalidTimeRange(String),
+
+    #[error("Backfill failed: {0}")]
+    BackfillFailed(String),
+
+    #[error("Invalid idempotency key: {0}")]
+    InvalidIdempotencyKey(String),
+
+    #[error("Idempotency key {0} already used with another body")]
+    IdempotencyKeyConflict(String),
+
+    #[error("Request of idempotency key {0} is still being created")]
+    IdempotencyKeyInProgress(String),
+
+    /// Token locked or its transfer to the bridge reverting, with the reason
+    #[error("Token can't be transferred to the bridge: {0}")]
+    TokenNotTransferable(String),
+
+    #[error("Invalid token id: {0}")]
+    InvalidTokenId(String),
+
+    #[error("Invalid preflight: {0}")]
+    InvalidPreflight(String),
+
+    #[error("Preflight failed: {0}")]
+    PreflightFailed(String),
+
+    #[error("Invalid transaction hash {0}, expected an EVM hash or a Solana signature")]
+    InvalidTxHash(String),
+
+    #[error("No request recorded the transaction {0}")]
+    UnknownTxHash(String),
+
+    /// Ids sent and the most a batch accepts
+    #[error("Too many request ids: {0}, at most {1} per batch")]
+    BatchTooLarge(usize, usize),
+
+    #[error("Invalid {0}: {1}")]
+    InvalidLabel(String, String),
+
+    #[error("Account purge is disabled, PURGE_SALT is not set")]
+    PurgeDisabled(),
+
+    #[error("Invalid account to purge: {0:?}")]
+    InvalidPurgeAccount(String),
+
+    #[error("Tx audit already running")]
+    TxAuditRunning(),
+
+    #[error("Tx audit failed: {0}")]
+    TxAuditFailed(String),
+
+    #[error("Maintenance failed: {0}")]
+    MaintenanceFailed(String),
+
+    #[error("Endpoints can only be switched during a maintenance")]
+    MaintenanceRequired(),
+
+    /// Field and reason
+    #[error("Invalid {0}: {1}")]
+    InvalidEndpoint(String, String),
+
+    #[error("Endpoint unreachable, keeping the current ones: {0}")]
+    EndpointUnreachable(String),
+
+    #[error("Requests of the account are not finished: {}", .0.iter().map(RequestId::as_str).collect::<Vec<_>>().join(", "))]
+    PurgeBlocked(Vec<RequestId>),
+}
+
+impl From<BridgeError> for RequestError {
+    fn from(err: BridgeError) -> Self {
+        match err {
+            BridgeError::NotAuthorizedBackend(signer) => RequestError::NotAuthorizedBackend(signer),
+            BridgeError::GasLimitExceeded(reason) => RequestError::GasLimitExceeded(reason),
+            _ => RequestError::CreationError(err.to_string()),
+        }
+    }
+}
+
+/// Typed error of an operation failing with `err`, found under the call contexts. URLs
+/// of the untyped errors are redacted
+pub fn bridge_error(err: &BridgeError) -> BridgeError {
+    match err.root() {
+        BridgeError::Other(_) => BridgeError::Other(redact_urls(&err.to_string())),
+        root => root.clone(),
+    }
+}
+
+/// Keeps an error of an endpoint acting on an existing request, as the processors and
+/// the sweeper keep theirs
+pub fn record_api_error(request_id: &str, category: ErrorClass, message: &str, state: &AppState) {
+    let Ok(Some(mut request)) = types::request_data(request_id, &state.db) else {
+        return;
+    };
+    let record = ErrorRecord::new(ErrorComponent::Api, category, message, state.clock.now());
+    if let Err(e) = request.record_error_record(record, None, &state.db) {
+        error!("Could not record the error of request {request_id}: {e}");
+    }
+}
+
+#[cfg(test)]
+mod errors_test {
+    use solana::SolanaError;
+    use types::{BridgeError, CallContext, Chains, WrapCallContext};
+
+    use crate::{bridge_error, errors::RequestError};
+
+    #[test]
+    fn test_typed_errors_found_under_call_context() {
+        let context = || CallContext::new(Chains::SOLANA, "create_nft").request("0xrequest");
+        let err = Err::<(), _>(BridgeError::from(SolanaError::AccountAlreadyInitialized(
+            "mint".to_string(),
+        )))
+        .with_call_context(context)
+        .unwrap_err();
+        assert_eq!(
+            bridge_error(&err),
+            BridgeError::AccountAlreadyInitialized("mint".to_string())
+        );
+
+        let err = Err::<(), _>(BridgeError::Other("rpc unavailable".to_string()))
+            .with_call_context(context)
+            .unwrap_err();
+        assert_eq!(bridge_error(&err), BridgeError::Other(err.to_string()));
+
+        assert_eq!(
+            RequestError::from(BridgeError::NotAuthorizedBackend("signer".to_string())),
+            RequestError::NotAuthorizedBackend("signer".to_string())
+        );
+    }
 }
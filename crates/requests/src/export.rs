@@ -0,0 +1,216 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use storage::db::Database;
+use types::{requests_by_status_after, BRequest, Result, Status};
+
+pub const EXPORT_PAGE_SIZE: usize = 500;
+
+pub const EXPORT_CSV_HEADER: &str = "request_id,created_at,completed_at,origin_chain,origin_contract_or_mint,token_id,token_owner,destination_account,destination_contract_or_mint,destination_token,lock_tx,mint_tx,fees_paid\n";
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+/// Completed requests export filter, `from` and `to` are unix seconds compared
+/// with the finalize timestamp
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ExportFilter {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+impl ExportFilter {
+    pub fn matches(&self, request: &BRequest) -> bool {
+        let completed_at = completed_at(request);
+        self.from.is_none_or(|from| completed_at >= from)
+            && self.to.is_none_or(|to| completed_at <= to)
+    }
+}
+
+/// Reads one page of completed requests after the `after` id. Returns the requests
+/// matching the filter and the cursor of the next page, None once the scan is done
+pub fn export_page(
+    db: &Database,
+    filter: &ExportFilter,
+    after: Option<&str>,
+    page_size: usize,
+) -> Result<(Vec<BRequest>, Option<String>)> {
+    let ids = requests_by_status_after(db, &Status::Completed, after, Some(page_size))?;
+    let next = if ids.len() < page_size {
+        None
+    } else {
+        ids.last().map(|(id, _)| id.clone())
+    };
+
+    let mut requests = vec![];
+    for (id, _) in ids {
+        if let Some(request) = types::request_data(&id, db)? {
+            if filter.matches(&request) {
+                requests.push(request);
+            }
+        }
+    }
+    Ok((requests, next))
+}
+
+pub fn csv_row(request: &BRequest) -> String {
+    let (lock_tx, mint_tx) = lock_and_mint_txs(request);
+    let fields = [
+        request.id.to_string(),
+        request.created_at.as_secs().to_string(),
+        completed_at(request).to_string(),
+        format!("{:?}", request.input.origin_network),
+        request.input.contract_or_mint.clone(),
+        request.input.token_id.clone(),
+        request.input.token_owner.clone(),
+        request.input.destination_account.clone(),
+        request.output.detination_contract_id_or_mint.clone(),
+        request.output.detination_token_id_or_account.clone(),
+        lock_tx,
+        mint_tx,
+        // Fees are not tracked yet
+        String::new(),
+    ];
+
+    let mut row = fields
+        .iter()
+        .map(|field| csv_escape(field))
+        .collect::<Vec<String>>()
+        .join(",");
+    row.push('\n');
+    row
+}
+
+pub fn json_row(request: &BRequest) -> Value {
+    let (lock_tx, mint_tx) = lock_and_mint_txs(request);
+    json!({
+        "request_id": request.id,
+        "created_at": request.created_at.as_secs(),
+        "completed_at": completed_at(request),
+        "origin_chain": request.input.origin_network,
+        "origin_contract_or_mint": request.input.contract_or_mint,
+        "token_id": request.input.token_id,
+        "token_owner": request.input.token_owner,
+        "destination_account": request.input.destination_account,
+        "destination_contract_or_mint": request.output.detination_contract_id_or_mint,
+        "destination_token": request.output.detination_token_id_or_account,
+        "lock_tx": lock_tx,
+        "mint_tx": mint_tx,
+        "fees_paid": Value::Null,
+    })
+}
+
+/// Quotes the field when it contains a separator, quote or line break
+pub fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn completed_at(request: &BRequest) -> u64 {
+    request
+        .finalized_at
+        .unwrap_or(request.last_update)
+        .as_secs()
+}
+
+fn lock_and_mint_txs(request: &BRequest) -> (String, String) {
+    let lock_tx = request.tx_hashes.first().cloned().unwrap_or_default();
+    let mint_tx = if request.tx_hashes.len() > 1 {
+        request.tx_hashes.last().cloned().unwrap_or_default()
+    } else {
+        String::new()
+    };
+    (lock_tx, mint_tx)
+}
+
+#[cfg(test)]
+mod export_test {
+    use crate::{csv_escape, csv_row, export_page, ExportFilter};
+    use test_support::test_db;
+    use types::{BRequest, Chains, InputRequest, Status, Timestamp};
+
+    fn completed_request(token_id: usize, finalized_at: u64) -> BRequest {
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xabc123".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner456".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination789".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.status = Status::Completed;
+        request.finalized_at = Some(Timestamp::from_secs(finalized_at));
+        request
+    }
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!(csv_escape("plain"), "plain");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("line\nbreak"), "\"line\nbreak\"");
+
+        let mut request = completed_request(1, 100);
+        request.input.token_owner = "owner,\"quoted\"".to_string();
+        request.tx_hashes = vec!["lock".to_string(), "mint".to_string()];
+        let row = csv_row(&request);
+        assert!(row.contains(",\"owner,\"\"quoted\"\"\","));
+        assert!(row.ends_with(",lock,mint,\n"));
+    }
+
+    #[test]
+    fn test_export_pages_over_many_records() {
+        let db = test_db();
+        for token_id in 0..2500 {
+            completed_request(token_id, token_id as u64)
+                .save(&db)
+                .unwrap();
+        }
+        // Not completed
+        BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xother".to_string(),
+                token_id: "1".to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        )
+        .save(&db)
+        .unwrap();
+
+        let filter = ExportFilter {
+            from: Some(1000),
+            to: Some(1999),
+            ..Default::default()
+        };
+        let mut exported = vec![];
+        let mut after: Option<String> = None;
+        let mut pages = 0;
+        loop {
+            let (requests, next) = export_page(&db, &filter, after.as_deref(), 300).unwrap();
+            exported.extend(requests);
+            pages += 1;
+            match next {
+                Some(next) => after = Some(next),
+                None => break,
+            }
+        }
+
+        assert_eq!(pages, 9);
+        assert_eq!(exported.len(), 1000);
+        assert!(exported.iter().all(|request| filter.matches(request)));
+    }
+}
@@ -0,0 +1,250 @@
+use log::{error, info};
+use types::{
+    claim_idempotency_key, complete_idempotency_key, idempotency_body_hash,
+    release_idempotency_key, valid_idempotency_key, BRequest, BridgeError, ClientLabels, Clock,
+    IdempotencyClaim, InputRequest, Status, MAX_IDEMPOTENCY_KEY_LEN,
+};
+
+use crate::{endpoints::new_request, errors::RequestError, AppState};
+
+/// Status of the responses stored for the creations under a key, accepted while the lock
+/// transaction is queued
+const CREATED_STATUS: u16 = 200;
+const ACCEPTED_STATUS: u16 = 202;
+
+/// Request created or replayed under an idempotency key
+#[derive(Debug, Clone)]
+pub struct IdempotentRequest {
+    pub request: BRequest,
+    /// Created by an earlier use of the key, the request is in its current state
+    pub replayed: bool,
+}
+
+/// Creates the request under an Idempotency-Key. A key used again with the same body
+/// replays the request it created, with another body it is a conflict. Failed creations
+/// free the key so the retry runs again, the key of a creation interrupted before it
+/// was recorded is freed after `IDEMPOTENCY_IN_PROGRESS_TIMEOUT`
+pub async fn new_request_with_key(
+    key: &str,
+    input_request: InputRequest,
+    callback_url: Option<String>,
+    labels: ClientLabels,
+    state: AppState,
+) -> Result<IdempotentRequest, RequestError> {
+    if !valid_idempotency_key(key) {
+        return Err(RequestError::InvalidIdempotencyKey(format!(
+            "expected 1 to {MAX_IDEMPOTENCY_KEY_LEN} visible ASCII characters"
+        )));
+    }
+    // The key maps to the request id the content derived idempotency gives to the body
+    let request_id = state.id_scheme.request_id(&input_request);
+    let body_hash = idempotency_body_hash(&input_request, callback_url.as_deref(), &labels);
+    let db_error = |e: BridgeError| RequestError::CreationError(e.to_string());
+
+    let now = state.clock.now();
+    let ttl = state.idempotency_key_ttl;
+    let mut claim = claim_idempotency_key(&state.db, key, &request_id, &body_hash, now, ttl)
+        .map_err(db_error)?;
+    if let IdempotencyClaim::Replay(record) = &claim {
+        match types::request_data(&record.request_id, &state.db).map_err(db_error)? {
+            Some(request) => {
+                info!("Idempotency key {key} replays request {}", request.id);
+                return Ok(IdempotentRequest {
+                    request,
+                    replayed: true,
+                });
+            }
+            None if !record.interrupted(now) => {
+                return Err(RequestError::IdempotencyKeyInProgress(key.to_string()));
+            }
+            // Interrupted before the request was written, the creation runs again
+            None => {
+                release_idempotency_key(&state.db, key).map_err(db_error)?;
+                claim = claim_idempotency_key(&state.db, key, &request_id, &body_hash, now, ttl)
+                    .map_err(db_error)?;
+            }
+        }
+    }
+    if claim == IdempotencyClaim::Conflict {
+        return Err(RequestError::IdempotencyKeyConflict(key.to_string()));
+    }
+
+    match new_request(input_request, callback_url, labels, state.clone()).await {
+        Ok(request) => {
+            let status = match request.status {
+                Status::Initializing => ACCEPTED_STATUS,
+                _ => CREATED_STATUS,
+            };
+            if let Err(e) = complete_idempotency_key(&state.db, key, status) {
+                error!(
+                    "Could not record idempotency key {key} of {}: {e}",
+                    request.id
+                );
+            }
+            Ok(IdempotentRequest {
+                request,
+                replayed: false,
+            })
+        }
+        Err(err) => {
+            if let Err(e) = release_idempotency_key(&state.db, key) {
+                error!("Could not release idempotency key {key}: {e}");
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Deletes the expired idempotency keys
+pub fn prune_idempotency_keys(state: &AppState) {
+    match types::prune_idempotency_keys(&state.db, state.clock.now()) {
+        Ok(0) => {}
+        Ok(pruned) => info!("Pruned {pruned} expired idempotency keys"),
+        Err(e) => error!("Could not prune the idempotency keys: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod idempotency_test {
+    use std::{sync::Arc, time::Duration};
+
+    use test_support::test_db;
+    use types::{
+        claim_idempotency_key, complete_idempotency_key, idempotency_body_hash, idempotency_record,
+        BRequest, BridgeDirection, Chains, ClientLabels, Clock, InputRequest, MockClock, Timestamp,
+        IDEMPOTENCY_IN_PROGRESS_TIMEOUT,
+    };
+
+    use crate::{
+        errors::RequestError, new_request_with_key, prune_idempotency_keys, test_utils::test_state,
+        AppState,
+    };
+
+    fn input(token_id: &str) -> InputRequest {
+        InputRequest {
+            contract_or_mint: "0xabc123".to_string(),
+            token_id: token_id.to_string(),
+            token_owner: "0xowner456".to_string(),
+            origin_network: Chains::EVM,
+            destination_account: "destination789".to_string(),
+        }
+    }
+
+    fn state_at(clock: &MockClock) -> AppState {
+        let (mut state, _, _) = test_state(test_db());
+        state.clock = Arc::new(clock.clone());
+        state
+    }
+
+    /// Stores the request of `input` as created under `key`
+    fn created_under(state: &AppState, key: &str, input: &InputRequest) -> BRequest {
+        let request = BRequest::new(input.clone(), Timestamp::now());
+        let hash = idempotency_body_hash(input, None, &ClientLabels::default());
+        let now = state.clock.now();
+        let ttl = state.idempotency_key_ttl;
+        claim_idempotency_key(&state.db, key, &request.id, &hash, now, ttl).unwrap();
+        request.save(&state.db).unwrap();
+        complete_idempotency_key(&state.db, key, 200).unwrap();
+        request
+    }
+
+    #[tokio::test]
+    async fn test_replay_and_conflicting_body() {
+        let clock = MockClock::new(Timestamp::from_secs(1_700_000_000));
+        let state = state_at(&clock);
+        let created = created_under(&state, "key-1", &input("42"));
+
+        let replay = new_request_with_key(
+            "key-1",
+            input("42"),
+            None,
+            ClientLabels::default(),
+            state.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(replay.replayed);
+        assert_eq!(replay.request.id, created.id);
+
+        assert_eq!(
+            new_request_with_key(
+                "key-1",
+                input("43"),
+                None,
+                ClientLabels::default(),
+                state.clone()
+            )
+            .await
+            .unwrap_err(),
+            RequestError::IdempotencyKeyConflict("key-1".to_string())
+        );
+        assert!(matches!(
+            new_request_with_key("bad key", input("42"), None, ClientLabels::default(), state)
+                .await,
+            Err(RequestError::InvalidIdempotencyKey(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_expired_keys_are_pruned() {
+        let clock = MockClock::new(Timestamp::from_secs(1_700_000_000));
+        let state = state_at(&clock);
+        created_under(&state, "key-1", &input("42"));
+
+        clock.advance(state.idempotency_key_ttl - Duration::from_secs(1));
+        prune_idempotency_keys(&state);
+        assert!(idempotency_record(&state.db, "key-1").unwrap().is_some());
+
+        clock.advance(Duration::from_secs(1));
+        prune_idempotency_keys(&state);
+        assert!(idempotency_record(&state.db, "key-1").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failed_creation_frees_the_key() {
+        let clock = MockClock::new(Timestamp::from_secs(1_700_000_000));
+        let state = state_at(&clock);
+        state
+            .pause
+            .pause(&state.db, BridgeDirection::EvmToSolana, None, None)
+            .unwrap();
+
+        for _ in 0..2 {
+            assert!(matches!(
+                new_request_with_key(
+                    "key-1",
+                    input("42"),
+                    None,
+                    ClientLabels::default(),
+                    state.clone()
+                )
+                .await,
+                Err(RequestError::BridgePaused(_))
+            ));
+            assert!(idempotency_record(&state.db, "key-1").unwrap().is_none());
+        }
+
+        // Interrupted before the request was written, the key waits for the timeout
+        let request_id = BRequest::new(input("42"), Timestamp::now()).id;
+        let hash = idempotency_body_hash(&input("42"), None, &ClientLabels::default());
+        let (now, ttl) = (clock.now(), state.idempotency_key_ttl);
+        claim_idempotency_key(&state.db, "key-2", &request_id, &hash, now, ttl).unwrap();
+        assert_eq!(
+            new_request_with_key(
+                "key-2",
+                input("42"),
+                None,
+                ClientLabels::default(),
+                state.clone()
+            )
+            .await
+            .unwrap_err(),
+            RequestError::IdempotencyKeyInProgress("key-2".to_string())
+        );
+        clock.advance(IDEMPOTENCY_IN_PROGRESS_TIMEOUT);
+        assert!(matches!(
+            new_request_with_key("key-2", input("42"), None, ClientLabels::default(), state).await,
+            Err(RequestError::BridgePaused(_))
+        ));
+    }
+}
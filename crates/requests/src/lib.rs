@@ -9,3 +9,90 @@ pub use endpoints::*;
 
 pub mod pending;
 pub use pending::*;
+
+pub mod export;
+pub use export::*;
+
+pub mod redirect;
+pub use redirect::*;
+
+pub mod dev;
+pub use dev::*;
+
+pub mod orphans;
+pub use orphans::*;
+
+pub mod reprocess;
+pub use reprocess::*;
+
+pub mod refunds;
+pub use refunds::*;
+
+pub mod loadtest;
+pub use loadtest::*;
+
+pub mod quotas;
+pub use quotas::*;
+
+pub mod collections;
+pub use collections::*;
+
+pub mod status;
+pub use status::*;
+
+pub mod negative_cache;
+pub use negative_cache::*;
+
+pub mod backfill;
+pub use backfill::*;
+
+pub mod idempotency;
+pub use idempotency::*;
+
+pub mod wrapped;
+pub use wrapped::*;
+
+pub mod preflight;
+pub use preflight::*;
+
+pub mod tx_lookup;
+pub use tx_lookup::*;
+
+pub mod replay;
+pub use replay::*;
+
+pub mod purge;
+pub use purge::*;
+
+pub mod capabilities;
+pub use capabilities::*;
+
+pub mod tx_audit;
+pub use tx_audit::*;
+
+pub mod client_lookup;
+pub use client_lookup::*;
+
+pub mod account_format;
+pub use account_format::*;
+
+pub mod maintenance;
+pub use maintenance::*;
+
+pub mod batch_status;
+pub use batch_status::*;
+
+pub mod rpc_health;
+pub use rpc_health::*;
+
+pub mod custody_limit;
+pub use custody_limit::*;
+
+pub mod metadata_refresh;
+pub use metadata_refresh::*;
+
+pub mod destination_account;
+pub use destination_account::*;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod test_utils;
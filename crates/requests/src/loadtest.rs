@@ -0,0 +1,718 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use alloy::primitives::Address;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use storage::{
+    db::Database,
+    keys::LOADTEST_PREFIX,
+    metrics::{DbOperation, OperationSample, StorageRecorder},
+};
+use types::{
+    tx_channel, BridgeError, BridgePause, Chains, ChannelMetrics, ClientLabels, CustodyLimits,
+    InFlightRegistry, InputRequest, QuotaLimits, RequestId, RequestLockGuard, RequestLocks, Result,
+    Status, Timestamp, TxMessage, TxReceiver, WorkClaims, IN_FLIGHT_TIMEOUT, WORK_CLAIM_LEASE,
+};
+
+use crate::{
+    enqueue_mint, errors::RequestError, is_admin, new_request, process_pending_request,
+    sweep_candidates, AppState, NegativeCache,
+};
+
+/// Largest number of requests of a single run
+pub const MAX_LOADTEST_REQUESTS: usize = 100_000;
+/// Capacity of the tx channels of a run, same as the relayer tx channels
+const LOADTEST_CHANNEL_CAPACITY: usize = 50;
+/// Pause between the sweeps of a run
+const LOADTEST_SWEEP_INTERVAL: Duration = Duration::from_millis(200);
+/// Wait for the accepted requests to complete once all are generated
+const LOADTEST_DRAIN_TIMEOUT: Duration = Duration::from_secs(60);
+/// Destination contract of the dry run mints
+const DRY_RUN_CONTRACT: &str = "dry-run-contract";
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct LoadTestParams {
+    /// Requests to generate
+    pub count: usize,
+    /// Requests generated per second
+    pub rate: u32,
+    /// Origin chain of the generated requests
+    pub direction: Chains,
+    /// Must be set, runs are only made against the mock chain layer
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum LoadTestStatus {
+    Running,
+    Completed,
+    Canceled,
+    Failed(String),
+}
+
+/// Latencies of one pipeline stage over the run, in milliseconds
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StageLatency {
+    pub stage: String,
+    pub samples: usize,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+}
+
+impl StageLatency {
+    fn from_samples(stage: &str, samples: &mut [Duration]) -> Self {
+        samples.sort();
+        let percentile = |percentile: f64| {
+            (!samples.is_empty()).then(|| {
+                let index = ((samples.len() - 1) as f64 * percentile).round() as usize;
+                as_ms(samples[index])
+            })
+        };
+        StageLatency {
+            stage: stage.to_string(),
+            samples: samples.len(),
+            p50_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+            max_ms: samples.last().copied().map(as_ms),
+        }
+    }
+}
+
+fn as_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LoadTestReport {
+    pub run_id: String,
+    pub params: LoadTestParams,
+    pub status: LoadTestStatus,
+    pub started_at: Timestamp,
+    pub finished_at: Option<Timestamp>,
+    /// Requests sent to the intake by the generator
+    pub generated: usize,
+    /// Requests that reached Completed
+    pub completed: usize,
+    /// Requests refused by the intake or not completed in time
+    pub failed: usize,
+    pub duration_ms: f64,
+    /// Completed requests per minute
+    pub throughput_per_minute: f64,
+    /// `intake`, `lock_queue_wait`, `custody`, `mint_queue_wait`, `mint`, `sweep` and
+    /// `end_to_end`
+    pub stages: Vec<StageLatency>,
+    pub max_channel_depth: usize,
+    pub db_writes: u64,
+    pub db_writes_per_second: f64,
+}
+
+impl LoadTestReport {
+    fn new(run_id: &str, params: LoadTestParams) -> Self {
+        LoadTestReport {
+            run_id: run_id.to_string(),
+            params,
+            status: LoadTestStatus::Running,
+            started_at: Timestamp::now(),
+            finished_at: None,
+            generated: 0,
+            completed: 0,
+            failed: 0,
+            duration_ms: 0.0,
+            throughput_per_minute: 0.0,
+            stages: vec![],
+            max_channel_depth: 0,
+            db_writes: 0,
+            db_writes_per_second: 0.0,
+        }
+    }
+}
+
+pub fn loadtest_key(run_id: &str) -> String {
+    format!("{LOADTEST_PREFIX}{run_id}")
+}
+
+pub fn loadtest_report(db: &Database, run_id: &str) -> Result<Option<LoadTestReport>> {
+    Ok(db.read(loadtest_key(run_id))?)
+}
+
+/// Cancel flags of the running load tests
+#[derive(Clone, Debug, Default)]
+pub struct LoadTestRuns {
+    running: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl LoadTestRuns {
+    fn start(&self) -> (String, Arc<AtomicBool>) {
+        let run_id = format!(
+            "lt-{}-{}",
+            Timestamp::now().as_millis(),
+            self.next_id.fetch_add(1, Ordering::SeqCst)
+        );
+        let canceled = Arc::new(AtomicBool::new(false));
+        self.running
+            .lock()
+            .unwrap()
+            .insert(run_id.clone(), canceled.clone());
+        (run_id, canceled)
+    }
+
+    fn finish(&self, run_id: &str) {
+        self.running.lock().unwrap().remove(run_id);
+    }
+
+    /// Asks a running load test to stop, false when it is not running
+    pub fn cancel(&self, run_id: &str) -> bool {
+        match self.running.lock().unwrap().get(run_id) {
+            Some(canceled) => {
+                canceled.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Origin token input of the `index` generated request
+fn synthetic_input(direction: &Chains, run_id: &str, index: usize) -> InputRequest {
+    match direction {
+        Chains::EVM => InputRequest {
+            contract_or_mint: Address::ZERO.to_string(),
+            token_id: index.to_string(),
+            token_owner: format!("{run_id}-owner"),
+            origin_network: Chains::EVM,
+            destination_account: Pubkey::new_unique().to_string(),
+        },
+        Chains::SOLANA => InputRequest {
+            contract_or_mint: Pubkey::new_unique().to_string(),
+            token_id: String::new(),
+            token_owner: Pubkey::new_unique().to_string(),
+            origin_network: Chains::SOLANA,
+            destination_account: Address::ZERO.to_string(),
+        },
+    }
+}
+
+/// Counts the writes of the database of a run
+#[derive(Debug, Default)]
+struct WriteCounter(AtomicU64);
+
+impl StorageRecorder for WriteCounter {
+    fn record_operation(&self, sample: &OperationSample) {
+        if matches!(
+            sample.operation,
+            DbOperation::Put | DbOperation::Delete | DbOperation::Batch
+        ) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Times the requests of a run reached each stage
+#[derive(Default)]
+struct RunTimes {
+    accepted: HashMap<RequestId, Instant>,
+    custody: HashMap<RequestId, Instant>,
+    completed: HashMap<RequestId, Instant>,
+    intake: Vec<Duration>,
+    sweep: Vec<Duration>,
+}
+
+type SharedRunTimes = Arc<Mutex<RunTimes>>;
+
+/// Namespace of a run: the relayer state over a scratch database, with its own tx
+/// channels, request locks, work claims and in flight registry. Its chain clients are not
+/// authorized backends, a transaction reaching them fails before any RPC call. Quotas and
+/// custody caps are off and the sweep doesn't pause between requests
+fn loadtest_state(
+    state: &AppState,
+    writes: Arc<WriteCounter>,
+) -> (AppState, TxReceiver, TxReceiver) {
+    let db = Database::open_in_memory().with_recorder(writes);
+    let channel_metrics = ChannelMetrics::default();
+    let (evm_tx, evm_rx) = tx_channel(Chains::EVM, LOADTEST_CHANNEL_CAPACITY, &channel_metrics);
+    let (solana_tx, solana_rx) =
+        tx_channel(Chains::SOLANA, LOADTEST_CHANNEL_CAPACITY, &channel_metrics);
+
+    let mut run_state = state.clone();
+    run_state.solana_client.tx_channel = evm_tx;
+    run_state.solana_client.authorized_backend = Arc::new(false.into());
+    run_state.evm_client.tx_channel = solana_tx;
+    run_state.evm_client.authorized_backend = Arc::new(false.into());
+    run_state.in_flight = InFlightRegistry::load(&db, IN_FLIGHT_TIMEOUT, state.clock.clone());
+    run_state.request_locks = RequestLocks::default();
+    run_state.work_claims = WorkClaims::new(WORK_CLAIM_LEASE, state.clock.clone());
+    run_state.pause = BridgePause::load(&db, state.clock.clone());
+    run_state.channel_metrics = channel_metrics;
+    run_state.missing_requests = NegativeCache::default();
+    run_state.load_tests = LoadTestRuns::default();
+    run_state.quota_limits = QuotaLimits::default();
+    run_state.custody_limits = CustodyLimits::default();
+    run_state.sync_creation = false;
+    run_state.dry_run = true;
+    run_state.intervals.evm_pending_request = Duration::ZERO;
+    run_state.intervals.solana_pending_request = Duration::ZERO;
+    run_state.db = db;
+    (run_state, evm_rx, solana_rx)
+}
+
+fn dry_run_tx(kind: &str, request_id: &str) -> String {
+    format!("dry-run-{kind}-{request_id}")
+}
+
+/// Waits for the lock of the request, held by the sweep while it handles it
+async fn lock_request(state: &AppState, request_id: &str) -> RequestLockGuard {
+    loop {
+        if let Some(lock) = state.request_locks.try_lock(request_id) {
+            return lock;
+        }
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+}
+
+/// Lock of an Initializing request: sent, custody confirmed with the metadata of the token
+/// kept, then its mint queued through the in flight registry like the mints of the sweep.
+/// False when the request moved on, the lock was sent again by the sweep
+async fn confirm_lock(state: &AppState, request_id: &RequestId) -> Result<bool> {
+    let Some(mut request) = types::request_data(request_id, &state.db)? else {
+        return Err(BridgeError::Other(format!(
+            "request {request_id} not found"
+        )));
+    };
+    if request.status != Status::Initializing {
+        return Ok(false);
+    }
+    let now = state.clock.now();
+    request.lock_sent(&dry_run_tx("lock", request_id), &state.db, now)?;
+    let uri = format!("ipfs://loadtest/{request_id}");
+    request.keep_metadata_snapshot(&uri, &uri, now, &state.db)?;
+    request.update_state(&state.db, now)?;
+
+    let queued = state
+        .in_flight
+        .send_mint(request_id, &state.db, enqueue_mint(state, &request, uri))
+        .await;
+    match queued {
+        Some(Err(e)) => Err(BridgeError::Other(format!("Could not queue the mint: {e}"))),
+        Some(Ok(())) | None => Ok(true),
+    }
+}
+
+/// Mint of a TokenReceived request: sent, then completed by its TokenMinted event. False
+/// when the request moved on
+fn confirm_mint(state: &AppState, request_id: &RequestId) -> Result<bool> {
+    let Some(mut request) = types::request_data(request_id, &state.db)? else {
+        return Err(BridgeError::Other(format!(
+            "request {request_id} not found"
+        )));
+    };
+    if request.status != Status::TokenReceived {
+        return Ok(false);
+    }
+    let token_id = request.input.token_id.clone();
+    request.output.detination_contract_id_or_mint = DRY_RUN_CONTRACT.to_string();
+    request.output.detination_token_id_or_account = token_id.clone();
+    request.tx_hashes.push(dry_run_tx("mint", request_id));
+    let now = state.clock.now();
+    request.update_state(&state.db, now)?;
+    request.complete_minted(&state.db, DRY_RUN_CONTRACT, &token_id, now)?;
+    Ok(request.status == Status::Completed)
+}
+
+/// Dry run tx processor of a run standing for the chains: the locks it receives are
+/// confirmed at once and their mint queued, the mints complete their request. Handles
+/// each message under the request lock, as the sweep does
+async fn dry_run_processor(mut rx: TxReceiver, state: AppState, times: SharedRunTimes) {
+    while let Some(message) = rx.recv().await {
+        let received_at = Instant::now();
+        let request_id = message.request_id().clone();
+        let confirmed = {
+            let _lock = lock_request(&state, &request_id).await;
+            match &message {
+                TxMessage::NewRequest(_) => confirm_lock(&state, &request_id).await,
+                TxMessage::Mint(_) => confirm_mint(&state, &request_id),
+                TxMessage::Refund(_) => Ok(false),
+            }
+        };
+        rx.record_processing(&message.function(), received_at);
+        match confirmed {
+            Ok(true) => {
+                let mut times = times.lock().unwrap();
+                let reached = match message {
+                    TxMessage::Mint(_) => &mut times.completed,
+                    _ => &mut times.custody,
+                };
+                reached.insert(request_id, Instant::now());
+            }
+            Ok(false) => {}
+            Err(e) => error!(
+                "Load test {:?} of {request_id} failed: {e}",
+                message.function()
+            ),
+        }
+    }
+}
+
+/// Sweeps the namespace of a run until `stop`, each pass timed
+async fn sweep_loop(state: AppState, stop: Arc<AtomicBool>, times: SharedRunTimes) {
+    while !stop.load(Ordering::SeqCst) {
+        let started = Instant::now();
+        match sweep_candidates(
+            &state.db,
+            state.clock.as_ref(),
+            state.intervals.received_sweep_min_age,
+        ) {
+            Ok(candidates) => process_pending_request(candidates, state.clone()).await,
+            Err(e) => error!("Load test sweep failed: {e}"),
+        }
+        times.lock().unwrap().sweep.push(started.elapsed());
+        tokio::time::sleep(LOADTEST_SWEEP_INTERVAL).await;
+    }
+}
+
+/// Stage `stage` of the requests that reached `from` and then `to`
+fn stage_between(
+    stage: &str,
+    from: &HashMap<RequestId, Instant>,
+    to: &HashMap<RequestId, Instant>,
+) -> StageLatency {
+    let mut samples: Vec<Duration> = to
+        .iter()
+        .filter_map(|(id, reached)| from.get(id).map(|start| reached.duration_since(*start)))
+        .collect();
+    StageLatency::from_samples(stage, &mut samples)
+}
+
+/// Wait of the messages of `function` in the tx channels
+fn queue_wait(stage: &str, metrics: &ChannelMetrics, function: &str) -> StageLatency {
+    let stats = metrics
+        .latency_stats()
+        .into_iter()
+        .find(|stats| stats.function == function);
+    StageLatency {
+        stage: stage.to_string(),
+        samples: stats.as_ref().map_or(0, |stats| stats.messages),
+        p50_ms: stats.as_ref().and_then(|stats| stats.wait_p50).map(as_ms),
+        p95_ms: stats.as_ref().and_then(|stats| stats.wait_p95).map(as_ms),
+        max_ms: None,
+    }
+}
+
+/// Generates `params.count` requests at `params.rate` per second and drives them through
+/// the request pipeline in a namespace of their own: the API intake, the dry run tx
+/// processors and the sweep, with the request locks and the in flight registry. Nothing
+/// is written to the relayer database and no transaction reaches a chain
+pub async fn run_loadtest(
+    run_id: &str,
+    params: LoadTestParams,
+    state: &AppState,
+    canceled: Arc<AtomicBool>,
+) -> Result<LoadTestReport> {
+    let writes = Arc::new(WriteCounter::default());
+    let (run_state, rx_evm, rx_solana) = loadtest_state(state, writes.clone());
+    let times = SharedRunTimes::default();
+    let processors = [rx_evm, rx_solana]
+        .map(|rx| tokio::spawn(dry_run_processor(rx, run_state.clone(), times.clone())));
+    let stop_sweep = Arc::new(AtomicBool::new(false));
+    let sweeper = tokio::spawn(sweep_loop(
+        run_state.clone(),
+        stop_sweep.clone(),
+        times.clone(),
+    ));
+
+    let mut report = LoadTestReport::new(run_id, params.clone());
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / params.rate as f64));
+    let started = Instant::now();
+    let mut max_depth = 0;
+
+    for index in 0..params.count {
+        ticker.tick().await;
+        if canceled.load(Ordering::SeqCst) {
+            report.status = LoadTestStatus::Canceled;
+            break;
+        }
+
+        let intake = Instant::now();
+        let input = synthetic_input(&params.direction, run_id, index);
+        let created = new_request(input, None, ClientLabels::default(), run_state.clone()).await;
+        report.generated += 1;
+        match created {
+            Ok(request) => {
+                let mut times = times.lock().unwrap();
+                times.intake.push(intake.elapsed());
+                times.accepted.insert(request.id, intake);
+            }
+            Err(e) => {
+                error!("Load test request {index} refused: {e}");
+                report.failed += 1;
+            }
+        }
+        max_depth = [Chains::EVM, Chains::SOLANA]
+            .iter()
+            .map(|chain| run_state.channel_metrics.depth(chain))
+            .fold(max_depth, usize::max);
+    }
+
+    // The accepted requests go on through the pipeline until completed or given up
+    let accepted = times.lock().unwrap().accepted.len();
+    let drain_until = Instant::now() + LOADTEST_DRAIN_TIMEOUT;
+    while times.lock().unwrap().completed.len() < accepted
+        && Instant::now() < drain_until
+        && !canceled.load(Ordering::SeqCst)
+    {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    if canceled.load(Ordering::SeqCst) {
+        report.status = LoadTestStatus::Canceled;
+    }
+    let duration = started.elapsed();
+    stop_sweep.store(true, Ordering::SeqCst);
+    sweeper.await.map_err(BridgeError::from_error)?;
+    for processor in processors {
+        processor.abort();
+    }
+
+    let mut times = times.lock().unwrap();
+    let times = &mut *times;
+    let completed = times.completed.len();
+    let stages = vec![
+        StageLatency::from_samples("intake", &mut times.intake),
+        queue_wait("lock_queue_wait", &run_state.channel_metrics, "NewRequest"),
+        stage_between("custody", &times.accepted, &times.custody),
+        queue_wait("mint_queue_wait", &run_state.channel_metrics, "Mint"),
+        stage_between("mint", &times.custody, &times.completed),
+        StageLatency::from_samples("sweep", &mut times.sweep),
+        stage_between("end_to_end", &times.accepted, &times.completed),
+    ];
+
+    let writes = writes.0.load(Ordering::SeqCst);
+    if report.status == LoadTestStatus::Running {
+        report.status = LoadTestStatus::Completed;
+    }
+    report.finished_at = Some(Timestamp::now());
+    report.completed = completed;
+    report.failed += accepted - completed;
+    report.duration_ms = as_ms(duration);
+    report.throughput_per_minute =
+        completed as f64 / duration.as_secs_f64().max(f64::EPSILON) * 60.0;
+    report.stages = stages;
+    report.max_channel_depth = max_depth;
+    report.db_writes = writes;
+    report.db_writes_per_second = writes as f64 / duration.as_secs_f64().max(f64::EPSILON);
+    Ok(report)
+}
+
+/// Starts a load test in the background, returns its run id. Only allowed when the
+/// relayer and the request are in dry run, so no transaction reaches a chain
+pub fn start_loadtest(
+    params: LoadTestParams,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<String, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    if !params.dry_run || !state.dry_run {
+        return Err(RequestError::LoadTestNotAllowed(
+            "load tests only run in dry run mode".to_string(),
+        ));
+    }
+    if params.count == 0 || params.count > MAX_LOADTEST_REQUESTS || params.rate == 0 {
+        return Err(RequestError::LoadTestNotAllowed(format!(
+            "count must be between 1 and {MAX_LOADTEST_REQUESTS} and rate positive"
+        )));
+    }
+
+    let (run_id, canceled) = state.load_tests.start();
+    let report = LoadTestReport::new(&run_id, params.clone());
+    state
+        .db
+        .write_value(loadtest_key(&run_id), &report)
+        .map_err(|e| RequestError::CreationError(e.to_string()))?;
+    info!("Starting load test {run_id} {params:?}");
+
+    let state = state.clone();
+    let id = run_id.clone();
+    tokio::spawn(async move {
+        let report = match run_loadtest(&id, params, &state, canceled).await {
+            Ok(report) => report,
+            Err(e) => {
+                error!("Load test {id} failed: {e}");
+                LoadTestReport {
+                    status: LoadTestStatus::Failed(e.to_string()),
+                    finished_at: Some(Timestamp::now()),
+                    ..report
+                }
+            }
+        };
+        info!(
+            "Load test {id} finished {:?}, {} requests per minute",
+            report.status, report.throughput_per_minute
+        );
+        if let Err(e) = state.db.write_value(loadtest_key(&id), &report) {
+            error!("Could not store the report of load test {id}: {e}");
+        }
+        state.load_tests.finish(&id);
+    });
+    Ok(run_id)
+}
+
+pub fn get_loadtest(
+    run_id: &str,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<LoadTestReport, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    match loadtest_report(&state.db, run_id) {
+        Ok(Some(report)) => Ok(report),
+        _ => Err(RequestError::NoExistingRequest(run_id.to_string())),
+    }
+}
+
+pub fn cancel_loadtest(
+    run_id: &str,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<(), RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    if !state.load_tests.cancel(run_id) {
+        return Err(RequestError::LoadTestNotAllowed(format!(
+            "load test {run_id} is not running"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod loadtest_test {
+    use std::{
+        sync::{atomic::AtomicBool, Arc},
+        time::Duration,
+    };
+
+    use storage::keys::REQUEST_KEY_PREFIX;
+    use test_support::test_db;
+    use types::{BRequest, Chains};
+
+    use crate::{
+        cancel_loadtest, errors::RequestError, get_loadtest, get_pending_requests, run_loadtest,
+        start_loadtest, test_utils::test_state, LoadTestParams, LoadTestStatus,
+    };
+
+    fn params(count: usize, direction: Chains) -> LoadTestParams {
+        LoadTestParams {
+            count,
+            rate: 1000,
+            direction,
+            dry_run: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_through_the_pipeline_in_their_namespace() {
+        let db = test_db();
+        let (mut state, _rx_evm, _rx_sol) = test_state(db.clone());
+        state.dry_run = true;
+        for direction in [Chains::EVM, Chains::SOLANA] {
+            let report = run_loadtest("run", params(25, direction), &state, Arc::default())
+                .await
+                .unwrap();
+            assert_eq!(report.status, LoadTestStatus::Completed);
+            assert_eq!(report.generated, 25);
+            assert_eq!(report.completed, 25);
+            assert_eq!(report.failed, 0);
+            assert!(report.db_writes >= 25 * 5, "{}", report.db_writes);
+            let stages: Vec<&str> = report.stages.iter().map(|s| s.stage.as_str()).collect();
+            assert_eq!(
+                stages,
+                vec![
+                    "intake",
+                    "lock_queue_wait",
+                    "custody",
+                    "mint_queue_wait",
+                    "mint",
+                    "sweep",
+                    "end_to_end"
+                ]
+            );
+            for stage in report.stages.iter().filter(|stage| stage.stage != "sweep") {
+                assert_eq!(stage.samples, 25, "stage {}", stage.stage);
+            }
+            assert!(report.stages[5].samples > 0);
+            assert!(report.throughput_per_minute > 0.0);
+        }
+        // Nothing reached the relayer database nor its tx channels
+        assert!(get_pending_requests(&db).unwrap_or_default().is_empty());
+        assert!(db
+            .scan_prefix::<BRequest>(REQUEST_KEY_PREFIX, None)
+            .unwrap()
+            .is_empty());
+        assert_eq!(state.channel_metrics.depth(&Chains::EVM), 0);
+        assert_eq!(state.in_flight.count(), 0);
+
+        // Canceled before the first request
+        let report = run_loadtest(
+            "run",
+            params(25, Chains::EVM),
+            &state,
+            Arc::new(AtomicBool::new(true)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.status, LoadTestStatus::Canceled);
+        assert_eq!(report.generated, 0);
+    }
+
+    #[tokio::test]
+    async fn test_loadtest_requires_dry_run() {
+        let db = test_db();
+        let (mut state, _rx_evm, _rx_sol) = test_state(db.clone());
+        state.admin_token = Some("secret".into());
+
+        assert!(matches!(
+            start_loadtest(params(5, Chains::EVM), Some("secret"), &state),
+            Err(RequestError::LoadTestNotAllowed(_))
+        ));
+        state.dry_run = true;
+        let mut live = params(5, Chains::EVM);
+        live.dry_run = false;
+        assert!(matches!(
+            start_loadtest(live, Some("secret"), &state),
+            Err(RequestError::LoadTestNotAllowed(_))
+        ));
+        assert_eq!(
+            start_loadtest(params(5, Chains::EVM), None, &state).unwrap_err(),
+            RequestError::Unauthorized()
+        );
+
+        let run_id = start_loadtest(params(5, Chains::EVM), Some("secret"), &state).unwrap();
+        let report = loop {
+            let report = get_loadtest(&run_id, Some("secret"), &state).unwrap();
+            if report.status != LoadTestStatus::Running {
+                break report;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        assert_eq!(report.status, LoadTestStatus::Completed);
+        assert_eq!(report.completed, 5);
+        // Nothing generated in the relayer database
+        assert!(get_pending_requests(&db).unwrap_or_default().is_empty());
+        assert!(matches!(
+            cancel_loadtest(&run_id, Some("secret"), &state),
+            Err(RequestError::LoadTestNotAllowed(_))
+        ));
+    }
+}
@@ -0,0 +1,442 @@
+use std::{future::Future, pin::Pin, time::Duration};
+
+use alloy::transports::http::reqwest::Url;
+use evm::EVMClient;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use solana::SolanaClient;
+use tokio::time::Instant;
+use types::{dangling_send_intents, redact_urls, BridgeError, Chains, Pause, PauseState, Result};
+
+use crate::{
+    drain_resumed_events, errors::RequestError, is_admin, process_pending_request,
+    sweep_candidates, AppState,
+};
+
+/// Drain wait of a maintenance begun without `drain_timeout_secs`
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(60);
+pub const MAX_DRAIN_TIMEOUT: Duration = Duration::from_secs(600);
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct MaintenanceInput {
+    pub reason: Option<String>,
+    /// Seconds waited for the work in flight to finish, 60 by default and 600 at most
+    pub drain_timeout_secs: Option<u64>,
+    /// Unix time (secs) the maintenance ends by itself if not ended before
+    pub resume_at: Option<u64>,
+}
+
+/// Work started before the maintenance and not finished yet
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct InFlightWork {
+    /// Mints being built or confirmed
+    pub mints: usize,
+    /// Messages queued to the tx processors
+    pub queued_messages: usize,
+    /// Journaled transactions not known to be sent yet
+    pub send_intents: usize,
+    /// Requests held by a sweep handler
+    pub sweep_claims: usize,
+}
+
+impl InFlightWork {
+    pub fn is_empty(&self) -> bool {
+        *self == InFlightWork::default()
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DrainReport {
+    pub maintenance: Pause,
+    /// Nothing was left in flight before the drain timeout
+    pub drained: bool,
+    /// Work still in flight at the timeout, it goes on and is not canceled
+    pub remaining: InFlightWork,
+}
+
+/// New node URLs, the ones not set are kept
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct EndpointsUpdate {
+    pub evm_rpc_url: Option<String>,
+    pub evm_ws_url: Option<String>,
+    pub solana_rpc_url: Option<String>,
+    pub solana_ws_url: Option<String>,
+}
+
+/// Answers of the probes of the endpoints switched to, None for a chain left as it was
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct EndpointsReport {
+    pub evm_latest_block: Option<u64>,
+    pub solana_latest_slot: Option<u64>,
+}
+
+pub type ProbeFuture<'a> = Pin<Box<dyn Future<Output = Result<u64>> + Send + 'a>>;
+
+/// Connectivity check of the nodes of an endpoints switch, before any client moves to them
+pub trait EndpointProbe: Send + Sync {
+    /// Latest block of the EVM node
+    fn evm<'a>(&'a self, client: &'a EVMClient) -> ProbeFuture<'a>;
+    /// Latest slot of the Solana node
+    fn solana<'a>(&'a self, client: &'a SolanaClient) -> ProbeFuture<'a>;
+}
+
+/// The reads of the startup connectivity checks
+pub struct ChainProbe;
+
+impl EndpointProbe for ChainProbe {
+    fn evm<'a>(&'a self, client: &'a EVMClient) -> ProbeFuture<'a> {
+        Box::pin(evm::get_latest_block_number(client))
+    }
+
+    fn solana<'a>(&'a self, client: &'a SolanaClient) -> ProbeFuture<'a> {
+        Box::pin(solana::get_latest_slot(client))
+    }
+}
+
+fn check_admin(admin_token: Option<&str>, state: &AppState) -> Result<(), RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    Ok(())
+}
+
+/// Work of the relayer still in flight
+pub fn in_flight_work(state: &AppState) -> Result<InFlightWork> {
+    let mut send_intents = 0;
+    for chain in [Chains::EVM, Chains::SOLANA] {
+        send_intents += dangling_send_intents(&state.db, &chain)?.len();
+    }
+    Ok(InFlightWork {
+        mints: state.in_flight.count(),
+        queued_messages: state.channel_metrics.depth(&Chains::EVM)
+            + state.channel_metrics.depth(&Chains::SOLANA),
+        send_intents,
+        sweep_claims: state.work_claims.active_count(&state.db)?,
+    })
+}
+
+/// Waits up to `timeout` for the work in flight to finish, returns whether it did with
+/// what is left
+async fn drain_in_flight(state: &AppState, timeout: Duration) -> Result<(bool, InFlightWork)> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = in_flight_work(state)?;
+        if remaining.is_empty() {
+            return Ok((true, remaining));
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok((false, remaining));
+        }
+        tokio::time::sleep(DRAIN_POLL_INTERVAL.min(deadline - now)).await;
+    }
+}
+
+/// Stops the intake, authorized by the admin token: new requests get a 503, the
+/// NewRequest events are buffered and the sweeps stop claiming requests. Then waits for
+/// the mints, queued messages and journaled sends in flight to finish, up to the drain
+/// timeout. The maintenance goes on whether they drained or not
+pub async fn begin_maintenance(
+    input: MaintenanceInput,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<DrainReport, RequestError> {
+    check_admin(admin_token, state)?;
+    let timeout = input
+        .drain_timeout_secs
+        .map_or(DEFAULT_DRAIN_TIMEOUT, Duration::from_secs)
+        .min(MAX_DRAIN_TIMEOUT);
+    let reason = input
+        .reason
+        .unwrap_or_else(|| "maintenance in progress".to_string());
+    let maintenance = state
+        .pause
+        .begin_maintenance(&state.db, Some(reason), input.resume_at)
+        .map_err(|e| RequestError::MaintenanceFailed(e.to_string()))?;
+
+    let (drained, remaining) = drain_in_flight(state, timeout)
+        .await
+        .map_err(|e| RequestError::MaintenanceFailed(e.to_string()))?;
+    if drained {
+        info!("Maintenance began, nothing left in flight");
+    } else {
+        warn!("Maintenance began with work still in flight after {timeout:?}: {remaining:?}");
+    }
+    Ok(DrainReport {
+        maintenance,
+        drained,
+        remaining,
+    })
+}
+
+/// Resumes the intake, authorized by the admin token. The work held back meanwhile is
+/// picked up by the caller with `resume_held_work`
+pub fn end_maintenance(
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<PauseState, RequestError> {
+    check_admin(admin_token, state)?;
+    state
+        .pause
+        .end_maintenance(&state.db)
+        .map_err(|e| RequestError::MaintenanceFailed(e.to_string()))?;
+    info!("Maintenance ended");
+    Ok(state.pause.state())
+}
+
+/// Processes the events buffered during a maintenance and sweeps the pending requests the
+/// sweeps stopped claiming
+pub async fn resume_held_work(state: &AppState) {
+    drain_resumed_events(state).await;
+    match sweep_candidates(
+        &state.db,
+        state.clock.as_ref(),
+        state.intervals.received_sweep_min_age,
+    ) {
+        Ok(pending) => process_pending_request(pending, state.clone()).await,
+        Err(e) => error!("Could not read pending requests: {e}"),
+    }
+}
+
+fn check_url(field: &str, value: &str, schemes: &[&str]) -> Result<(), RequestError> {
+    let invalid = |reason: String| RequestError::InvalidEndpoint(field.to_string(), reason);
+    let url = Url::parse(value).map_err(|e| invalid(e.to_string()))?;
+    if !schemes.contains(&url.scheme()) {
+        return Err(invalid(format!("expected a {} URL", schemes.join(" or "))));
+    }
+    Ok(())
+}
+
+/// Replacement of `current` by the URLs set in `rpc_url` and `ws_url`, None when neither is
+fn candidate_urls(
+    (rpc_field, rpc_url): (&str, &Option<String>),
+    (ws_field, ws_url): (&str, &Option<String>),
+    current: (String, String),
+) -> Result<Option<(String, String)>, RequestError> {
+    if rpc_url.is_none() && ws_url.is_none() {
+        return Ok(None);
+    }
+    if let Some(url) = rpc_url {
+        check_url(rpc_field, url, &["http", "https"])?;
+    }
+    if let Some(url) = ws_url {
+        check_url(ws_field, url, &["ws", "wss"])?;
+    }
+    Ok(Some((
+        rpc_url.clone().unwrap_or(current.0),
+        ws_url.clone().unwrap_or(current.1),
+    )))
+}
+
+/// Moves the clients to new node URLs during a maintenance, authorized by the admin
+/// token. Every client of the relayer, the tx processors and listeners included, calls
+/// the new nodes once both chains answer their probe. Invalid or unreachable endpoints
+/// leave all the clients on their current nodes
+pub async fn switch_endpoints(
+    update: EndpointsUpdate,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<EndpointsReport, RequestError> {
+    switch_endpoints_with(update, admin_token, state, &ChainProbe).await
+}
+
+/// Same as `switch_endpoints` checking the nodes with `probe`
+pub async fn switch_endpoints_with(
+    update: EndpointsUpdate,
+    admin_token: Option<&str>,
+    state: &AppState,
+    probe: &dyn EndpointProbe,
+) -> Result<EndpointsReport, RequestError> {
+    check_admin(admin_token, state)?;
+    if state.pause.maintenance().is_none() {
+        return Err(RequestError::MaintenanceRequired());
+    }
+
+    let evm = candidate_urls(
+        ("evm_rpc_url", &update.evm_rpc_url),
+        ("evm_ws_url", &update.evm_ws_url),
+        (state.evm_client.rpc_url(), state.evm_client.ws_url()),
+    )?
+    .map(|(rpc, ws)| state.evm_client.with_endpoints(&rpc, &ws));
+    let solana = candidate_urls(
+        ("solana_rpc_url", &update.solana_rpc_url),
+        ("solana_ws_url", &update.solana_ws_url),
+        (
+            state.solana_client.rpc().url(),
+            state.solana_client.ws_url(),
+        ),
+    )?
+    .map(|(rpc, ws)| state.solana_client.with_endpoints(&rpc, &ws));
+    if evm.is_none() && solana.is_none() {
+        return Err(RequestError::InvalidEndpoint(
+            "endpoints".to_string(),
+            "no URL to switch to".to_string(),
+        ));
+    }
+
+    let unreachable = |chain: &str, e: BridgeError| {
+        let reason = redact_urls(&format!("{chain} node: {e}"));
+        error!("Endpoints switch refused, {reason}");
+        RequestError::EndpointUnreachable(reason)
+    };
+    let mut report = EndpointsReport::default();
+    if let Some(candidate) = &evm {
+        let block = probe.evm(candidate).await;
+        report.evm_latest_block = Some(block.map_err(|e| unreachable("EVM", e))?);
+    }
+    if let Some(candidate) = &solana {
+        let slot = probe.solana(candidate).await;
+        report.solana_latest_slot = Some(slot.map_err(|e| unreachable("Solana", e))?);
+    }
+
+    if let Some(candidate) = &evm {
+        state.evm_client.adopt_endpoints(candidate);
+        info!("EVM endpoints switched");
+    }
+    if let Some(candidate) = &solana {
+        state.solana_client.adopt_endpoints(candidate);
+        info!("Solana endpoints switched");
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod maintenance_test {
+    use std::time::Duration;
+
+    use evm::EVMClient;
+    use solana::SolanaClient;
+    use test_support::test_db;
+    use types::{BridgeError, Chains};
+
+    use crate::{
+        begin_maintenance, end_maintenance, errors::RequestError, in_flight_work,
+        switch_endpoints_with, test_utils::test_state, EndpointProbe, EndpointsUpdate,
+        MaintenanceInput, ProbeFuture,
+    };
+
+    /// Nodes answering when their URL names them `healthy`
+    struct MockProbe;
+
+    fn answer(url: String, height: u64) -> ProbeFuture<'static> {
+        Box::pin(async move {
+            if url.contains("healthy") {
+                Ok(height)
+            } else {
+                Err(BridgeError::Other("connection refused".to_string()))
+            }
+        })
+    }
+
+    impl EndpointProbe for MockProbe {
+        fn evm<'a>(&'a self, client: &'a EVMClient) -> ProbeFuture<'a> {
+            answer(client.rpc_url(), 100)
+        }
+
+        fn solana<'a>(&'a self, client: &'a SolanaClient) -> ProbeFuture<'a> {
+            answer(client.rpc().url(), 200)
+        }
+    }
+
+    fn drain_for(secs: u64) -> MaintenanceInput {
+        MaintenanceInput {
+            reason: Some("RPC provider rotation".to_string()),
+            drain_timeout_secs: Some(secs),
+            resume_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_sequence() {
+        let db = test_db();
+        let (mut state, _rx_evm, _rx_sol) = test_state(db.clone());
+        state.admin_token = Some("secret".into());
+        let admin = Some("secret");
+        // Clones held by the tx processors and listeners
+        let (processor_evm, processor_solana) =
+            (state.evm_client.clone(), state.solana_client.clone());
+        let switch = EndpointsUpdate {
+            evm_rpc_url: Some("https://healthy-evm.example".to_string()),
+            evm_ws_url: Some("wss://healthy-evm.example".to_string()),
+            solana_rpc_url: Some("https://healthy-solana.example".to_string()),
+            solana_ws_url: None,
+        };
+
+        assert_eq!(
+            begin_maintenance(drain_for(0), None, &state)
+                .await
+                .unwrap_err(),
+            RequestError::Unauthorized()
+        );
+        assert_eq!(
+            switch_endpoints_with(switch.clone(), admin, &state, &MockProbe)
+                .await
+                .unwrap_err(),
+            RequestError::MaintenanceRequired()
+        );
+
+        // A mint outliving the drain timeout is reported and left going
+        assert!(state.in_flight.try_acquire("0xrequest1", &db));
+        let report = begin_maintenance(drain_for(0), admin, &state)
+            .await
+            .unwrap();
+        assert!(!report.drained);
+        assert_eq!(report.remaining.mints, 1);
+        assert!(state.in_flight.is_in_flight("0xrequest1"));
+        assert!(state.pause.paused(&Chains::EVM).is_some());
+        assert!(state.pause.paused(&Chains::SOLANA).is_some());
+
+        // Drained once the mint is done
+        let releasing = state.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            releasing.in_flight.release("0xrequest1", &releasing.db);
+        });
+        let report = begin_maintenance(drain_for(5), admin, &state)
+            .await
+            .unwrap();
+        assert!(report.drained);
+        assert!(in_flight_work(&state).unwrap().is_empty());
+
+        // Malformed and unreachable endpoints keep the current ones
+        let old_evm = processor_evm.rpc_url();
+        let old_solana = processor_solana.rpc().url();
+        let malformed = EndpointsUpdate {
+            evm_ws_url: Some("https://healthy-evm.example".to_string()),
+            ..switch.clone()
+        };
+        assert!(matches!(
+            switch_endpoints_with(malformed, admin, &state, &MockProbe).await,
+            Err(RequestError::InvalidEndpoint(field, _)) if field == "evm_ws_url"
+        ));
+        let unreachable = EndpointsUpdate {
+            solana_rpc_url: Some("https://dead-solana.example".to_string()),
+            ..switch.clone()
+        };
+        assert!(matches!(
+            switch_endpoints_with(unreachable, admin, &state, &MockProbe).await,
+            Err(RequestError::EndpointUnreachable(_))
+        ));
+        assert_eq!(processor_evm.rpc_url(), old_evm);
+        assert_eq!(processor_solana.rpc().url(), old_solana);
+
+        let report = switch_endpoints_with(switch, admin, &state, &MockProbe)
+            .await
+            .unwrap();
+        assert_eq!(report.evm_latest_block, Some(100));
+        assert_eq!(report.solana_latest_slot, Some(200));
+        assert_eq!(processor_evm.rpc_url(), "https://healthy-evm.example");
+        assert_eq!(processor_evm.ws_url(), "wss://healthy-evm.example");
+        assert_eq!(
+            processor_solana.rpc().url(),
+            "https://healthy-solana.example"
+        );
+        // Not given, kept
+        assert_eq!(processor_solana.ws_url(), "ws://localhost:8900");
+
+        let pause = end_maintenance(admin, &state).unwrap();
+        assert_eq!(pause.maintenance, None);
+        assert!(state.pause.paused(&Chains::EVM).is_none());
+    }
+}
@@ -0,0 +1,92 @@
+use log::info;
+use types::{BRequest, RequestId, Status};
+
+use crate::{errors::RequestError, is_admin, AppState};
+
+/// Drops the metadata snapshot of a request whose mint is not confirmed, its next mint
+/// attempt reads the origin token again. Authorized by the admin token
+pub fn refresh_request_metadata(
+    request_id: &RequestId,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<BRequest, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    let mut request = match types::request_data(request_id, &state.db) {
+        Ok(Some(request)) => request,
+        _ => return Err(RequestError::NoExistingRequest(request_id.to_string())),
+    };
+    if !matches!(request.status, Status::TokenReceived | Status::TokenMinted) {
+        return Err(RequestError::RefreshNotAllowed(format!(
+            "request in status {:?} has no mint to send",
+            request.status
+        )));
+    }
+    if request.metadata_snapshot.is_none() {
+        return Err(RequestError::RefreshNotAllowed(
+            "no metadata was kept yet".to_string(),
+        ));
+    }
+    if state.in_flight.is_in_flight(&request.id) {
+        return Err(RequestError::RefreshNotAllowed(
+            "a transaction is in flight".to_string(),
+        ));
+    }
+
+    request
+        .drop_metadata_snapshot(state.clock.now(), &state.db)
+        .map_err(|e| RequestError::CreationError(e.to_string()))?;
+    info!(
+        "Metadata of request {} refreshed on its next mint",
+        request.id
+    );
+    Ok(request)
+}
+
+#[cfg(test)]
+mod metadata_refresh_test {
+    use test_support::{request_in_status, test_db};
+    use types::{Chains, Status, Timestamp};
+
+    use crate::{errors::RequestError, refresh_request_metadata, test_utils::test_state};
+
+    #[test]
+    fn test_refresh_drops_the_snapshot() {
+        let db = test_db();
+        let (mut state, _, _) = test_state(db.clone());
+        state.admin_token = Some("secret".into());
+
+        let mut request = request_in_status(Chains::EVM, "1", Status::TokenReceived);
+        request.save(&db).unwrap();
+        assert!(matches!(
+            refresh_request_metadata(&request.id, Some("secret"), &state),
+            Err(RequestError::RefreshNotAllowed(_))
+        ));
+        request
+            .keep_metadata_snapshot(
+                "https://origin.example/1.json",
+                "https://origin.example/1.json",
+                Timestamp::from_secs(1_700_000_000),
+                &db,
+            )
+            .unwrap();
+        assert_eq!(
+            refresh_request_metadata(&request.id, None, &state).unwrap_err(),
+            RequestError::Unauthorized()
+        );
+        let refreshed = refresh_request_metadata(&request.id, Some("secret"), &state).unwrap();
+        assert_eq!(refreshed.metadata_snapshot, None);
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.metadata_snapshot, None);
+
+        let mut completed = request_in_status(Chains::EVM, "2", Status::Completed);
+        completed
+            .keep_metadata_snapshot("uri", "uri", Timestamp::from_secs(1_700_000_000), &db)
+            .unwrap();
+        assert!(matches!(
+            refresh_request_metadata(&completed.id, Some("secret"), &state),
+            Err(RequestError::RefreshNotAllowed(_))
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use types::Timestamp;
+
+/// Ids of missing requests remembered at most
+pub const NEGATIVE_CACHE_CAPACITY: usize = 10_000;
+/// Time a missing id is remembered. Requests created outside `new_request`, as the
+/// orphans of the chain events, are found again after it
+pub const NEGATIVE_CACHE_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// Expiry and recency of each id
+    by_id: HashMap<String, (Timestamp, u64)>,
+    /// Ids by recency, the first one is evicted when full
+    by_use: BTreeMap<u64, String>,
+    next_use: u64,
+}
+
+impl Entries {
+    fn remove(&mut self, request_id: &str) {
+        if let Some((_, used)) = self.by_id.remove(request_id) {
+            self.by_use.remove(&used);
+        }
+    }
+
+    fn touch(&mut self, request_id: &str, expires_at: Timestamp) {
+        self.remove(request_id);
+        self.next_use += 1;
+        self.by_id
+            .insert(request_id.to_string(), (expires_at, self.next_use));
+        self.by_use.insert(self.next_use, request_id.to_string());
+    }
+}
+
+/// Least recently used ids of requests not found in the database, so lookup floods of
+/// unknown ids don't reach it
+#[derive(Clone, Debug)]
+pub struct NegativeCache {
+    entries: Arc<Mutex<Entries>>,
+    capacity: usize,
+    ttl: Duration,
+    hits: Arc<AtomicU64>,
+}
+
+impl Default for NegativeCache {
+    fn default() -> Self {
+        NegativeCache::new(NEGATIVE_CACHE_CAPACITY, NEGATIVE_CACHE_TTL)
+    }
+}
+
+impl NegativeCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        NegativeCache {
+            entries: Arc::default(),
+            capacity,
+            ttl,
+            hits: Arc::default(),
+        }
+    }
+
+    /// True when `request_id` was not found less than the TTL ago. A hit makes it the most
+    /// recently used
+    pub fn is_missing(&self, request_id: &str, now: Timestamp) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(&(expires_at, _)) = entries.by_id.get(request_id) else {
+            return false;
+        };
+        if now >= expires_at {
+            entries.remove(request_id);
+            return false;
+        }
+        entries.touch(request_id, expires_at);
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Remembers a request id not found, the least recently used id is evicted when full
+    pub fn insert(&self, request_id: &str, now: Timestamp) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.touch(request_id, now.saturating_add(self.ttl));
+        while entries.by_id.len() > self.capacity {
+            let Some((_, evicted)) = entries.by_use.pop_first() else {
+                break;
+            };
+            entries.by_id.remove(&evicted);
+        }
+    }
+
+    /// Forgets `request_id`, called once a request with it is created
+    pub fn invalidate(&self, request_id: &str) {
+        self.entries.lock().unwrap().remove(request_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().by_id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Lookups answered by the cache
+    pub fn hit_count(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod negative_cache_test {
+    use std::time::Duration;
+
+    use types::Timestamp;
+
+    use crate::NegativeCache;
+
+    const NOW: Timestamp = Timestamp::from_secs(1_700_000_000);
+
+    #[test]
+    fn test_missing_id_expires_after_ttl() {
+        let cache = NegativeCache::new(10, Duration::from_secs(30));
+        assert!(!cache.is_missing("unknown", NOW));
+        cache.insert("unknown", NOW);
+
+        assert!(cache.is_missing("unknown", NOW.saturating_add(Duration::from_secs(29))));
+        assert_eq!(cache.hit_count(), 1);
+        // A hit doesn't extend the TTL
+        assert!(!cache.is_missing("unknown", NOW.saturating_add(Duration::from_secs(30))));
+        assert!(cache.is_empty());
+        assert_eq!(cache.hit_count(), 1);
+    }
+
+    #[test]
+    fn test_invalidated_on_creation() {
+        let cache = NegativeCache::new(10, Duration::from_secs(30));
+        cache.insert("created", NOW);
+        cache.insert("other", NOW);
+
+        cache.invalidate("created");
+        assert!(!cache.is_missing("created", NOW));
+        assert!(cache.is_missing("other", NOW));
+        // Ids never cached are ignored
+        cache.invalidate("never-looked-up");
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_least_recently_used_evicted() {
+        let cache = NegativeCache::new(2, Duration::from_secs(30));
+        cache.insert("first", NOW);
+        cache.insert("second", NOW);
+        // The hit makes the first id the most recently used
+        assert!(cache.is_missing("first", NOW));
+
+        cache.insert("third", NOW);
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.is_missing("second", NOW));
+        assert!(cache.is_missing("first", NOW));
+        assert!(cache.is_missing("third", NOW));
+    }
+}
@@ -0,0 +1,188 @@
+use log::{error, info};
+use serde::Deserialize;
+use storage::db::Database;
+use types::{requests_by_status, BRequest, Chains, RequestId, Result, Status, Timestamp};
+
+use crate::{
+    add_pending_request, check_custody_limit, check_destination_account, errors::RequestError,
+    verify_owner_signature, AppState,
+};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClaimOrphanInput {
+    pub destination_account: String,
+    /// Signature of `claim_message` by the origin token owner, hex for EVM and base58
+    /// for Solana
+    pub owner_signature: String,
+}
+
+/// Message the token owner signs to claim an orphan
+pub fn claim_message(request_id: &str, destination_account: &str) -> String {
+    format!("Claim request {request_id} to {destination_account}")
+}
+
+/// Requests whose token is in custody without a destination
+pub fn get_orphans(db: &Database) -> Result<Vec<BRequest>> {
+    let mut orphans = vec![];
+    for (id, _) in requests_by_status(db, &Status::NeedsDestination, None)? {
+        if let Some(request) = types::request_data(&id, db)? {
+            orphans.push(request);
+        }
+    }
+    Ok(orphans)
+}
+
+/// Sets the destination of an orphan and hands it to the regular processing, starting
+/// from the custody check
+pub fn apply_claim(
+    request: &mut BRequest,
+    destination_account: &str,
+    now: Timestamp,
+    db: &Database,
+) -> Result<()> {
+    request.input.destination_account = destination_account.to_string();
+    request.status = Status::RequestReceived;
+    request.last_update = now;
+    request.record_event(
+        &format!("Claimed by the owner to {destination_account}"),
+        db,
+        now,
+    )?;
+    add_pending_request(&request.id, db)
+}
+
+pub async fn claim_orphan(
+    request_id: &RequestId,
+    input: ClaimOrphanInput,
+    state: &AppState,
+) -> Result<BRequest, RequestError> {
+    let mut request = match types::request_data(request_id, &state.db) {
+        Ok(Some(request)) => request,
+        _ => return Err(RequestError::NoExistingRequest(request_id.to_string())),
+    };
+    if request.status != Status::NeedsDestination {
+        return Err(RequestError::ClaimNotAllowed(format!(
+            "request in status {:?} is not waiting for a destination",
+            request.status
+        )));
+    }
+
+    check_destination_account(&request.input.origin_network, &input.destination_account)?;
+    // A claimed orphan goes on to the mint, it waits for room under the custody cap
+    check_custody_limit(&request.input.origin_network, state)?;
+
+    let owner = match request.input.origin_network {
+        Chains::EVM => request.input.token_owner.clone(),
+        // Solana requests store the token account, the signer is its owner wallet
+        Chains::SOLANA => {
+            solana::token_account_owner(&state.solana_client, &request.input.token_owner)
+                .await
+                .map_err(|_| RequestError::Unauthorized())?
+                .to_string()
+        }
+    };
+    let message = claim_message(&request.id, &input.destination_account);
+    if !verify_owner_signature(
+        &request.input.origin_network,
+        &owner,
+        &message,
+        &input.owner_signature,
+    ) {
+        return Err(RequestError::Unauthorized());
+    }
+
+    apply_claim(
+        &mut request,
+        &input.destination_account,
+        state.clock.now(),
+        &state.db,
+    )
+    .map_err(|e| RequestError::CreationError(e.to_string()))?;
+    info!(
+        "Orphan request {} claimed to {}",
+        request.id, input.destination_account
+    );
+    Ok(request)
+}
+
+/// Orphans not claimed within `types::ORPHAN_CLAIM_WINDOW` become refund eligible
+pub fn expire_unclaimed_orphans(state: &AppState) {
+    match types::expire_orphans(&state.db, state.clock.now(), types::ORPHAN_CLAIM_WINDOW) {
+        Ok(expired) if !expired.is_empty() => {
+            info!("{} unclaimed orphans are refund eligible", expired.len())
+        }
+        Ok(_) => {}
+        Err(e) => error!("Could not expire unclaimed orphans: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod orphans_test {
+    use alloy::signers::{local::PrivateKeySigner, SignerSync};
+    use solana_sdk::pubkey::Pubkey;
+    use test_support::test_db;
+    use types::{record_orphan, BRequest, Chains, InputRequest, Status, Timestamp};
+
+    use crate::{
+        claim_message, claim_orphan, errors::RequestError, get_orphans, get_pending_requests,
+        test_utils::test_state, ClaimOrphanInput,
+    };
+
+    #[tokio::test]
+    async fn test_unknown_request_event_claim_flow() {
+        let db = test_db();
+        let (state, _rx_evm, _rx_sol) = test_state(db.clone());
+        let owner = PrivateKeySigner::random();
+
+        // NewRequest event of an id never submitted to the API
+        let input = InputRequest {
+            contract_or_mint: "0x5fbdb2315678afecb367f032d93f642f64180aa3".to_string(),
+            token_id: "7".to_string(),
+            token_owner: owner.address().to_string(),
+            origin_network: Chains::EVM,
+            destination_account: String::new(),
+        };
+        let id = BRequest::generate_id("0xcontract", "7", "0xsubmitter");
+        record_orphan(&db, &id, input, None, Timestamp::now())
+            .unwrap()
+            .unwrap();
+        let orphans = get_orphans(&db).unwrap();
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].status, Status::NeedsDestination);
+        assert!(get_pending_requests(&db).unwrap_or_default().is_empty());
+
+        let destination = Pubkey::new_unique().to_string();
+        let claim = |signature: String| ClaimOrphanInput {
+            destination_account: destination.clone(),
+            owner_signature: signature,
+        };
+
+        // Signed by someone else
+        let other = PrivateKeySigner::random()
+            .sign_message_sync(claim_message(&id, &destination).as_bytes())
+            .unwrap();
+        assert_eq!(
+            claim_orphan(&id, claim(other.to_string()), &state)
+                .await
+                .unwrap_err(),
+            RequestError::Unauthorized()
+        );
+
+        let signature = owner
+            .sign_message_sync(claim_message(&id, &destination).as_bytes())
+            .unwrap();
+        let claimed = claim_orphan(&id, claim(signature.to_string()), &state)
+            .await
+            .unwrap();
+        assert_eq!(claimed.status, Status::RequestReceived);
+        assert_eq!(claimed.input.destination_account, destination);
+        assert_eq!(get_pending_requests(&db).unwrap(), vec![id.to_string()]);
+        assert!(get_orphans(&db).unwrap().is_empty());
+
+        // Claims only once
+        assert!(matches!(
+            claim_orphan(&id, claim(signature.to_string()), &state).await,
+            Err(RequestError::ClaimNotAllowed(_))
+        ));
+    }
+}
@@ -1,169 +1,561 @@
-use crate::{errors::RequestError, get_pending_requests, AppState};
+use crate::{bridge_error, enqueue_lock, AppState};
 use alloy::primitives::{Address, U256};
-use eyre::Result;
-use log::{error, info};
-use std::{collections::HashMap, str::FromStr, thread::sleep, time::Duration};
-use storage::{
-    db::Database,
-    keys::{PENDING_REQUESTS, PENDING_REQUESTS_INDEX},
+use log::{debug, error, info, warn};
+use solana::AccountsCache;
+use solana_sdk::signature::Signature;
+use std::{collections::HashMap, str::FromStr, time::Duration};
+use storage::db::{Batch, Database};
+use types::{
+    debug_detail, has_status, pending_entries, requests_by_status, stage_pending_addition,
+    stage_pending_removal, BRequest, BridgeError, ChainCursors, Chains, Clock, ErrorClass,
+    ErrorComponent, ErrorRecord, Fee, LoggableRequest, Result, Status, Timestamp, TxState,
 };
-use types::{update_hashmap, update_vector, BRequest, Chains, Status};
 
-pub fn get_pending_request_and_index(
+/// Requests the sweeper has to act on, read from the status buckets instead of the
+/// whole pending queue. Pending entries already completed or canceled are included so
+/// they get removed from the queue. Candidates are ordered by their place in the pending
+/// queue, requests not in it go last
+pub fn sweep_candidates(
     db: &Database,
-) -> (Option<Vec<String>>, Option<HashMap<String, i128>>) {
-    let pending_requests = get_pending_requests(db);
-    let pending_requests_index: Option<HashMap<String, i128>> =
-        db.read(PENDING_REQUESTS_INDEX).unwrap();
-    info!("Reading pending requests: {:?}", &pending_requests);
-    (pending_requests, pending_requests_index)
+    clock: &dyn Clock,
+    received_min_age: Duration,
+) -> Result<Vec<String>> {
+    let received_before = clock.now().saturating_sub(received_min_age);
+
+    // Requests created within the grace period are left to the tx processors and the
+    // event listeners, their lock transaction may not be sent or confirmed yet
+    let mut candidates = vec![];
+    for status in [Status::Initializing, Status::RequestReceived].iter() {
+        for (id, _) in requests_by_status(db, status, None)? {
+            match types::request_data(&id, db)? {
+                Some(request) if request.created_at <= received_before => candidates.push(id),
+                _ => {}
+            }
+        }
+    }
+    for status in [
+        Status::TokenReceived,
+        Status::TokenMinted,
+        Status::AwaitingDestinationAccount,
+    ]
+    .iter()
+    {
+        candidates.extend(
+            requests_by_status(db, status, None)?
+                .into_iter()
+                .map(|(id, _)| id),
+        );
+    }
+
+    let queue = pending_entries(db)?;
+    for (_, id) in queue.iter() {
+        if has_status(db, id, &Status::Completed)?
+            || has_status(db, id, &Status::Canceled)?
+            || has_status(db, id, &Status::Refunded)?
+            || has_status(db, id, &Status::NeedsIntervention)?
+        {
+            candidates.push(id.clone());
+        }
+    }
+
+    let sequences: HashMap<&str, u64> = queue
+        .iter()
+        .map(|(sequence, id)| (id.as_str(), *sequence))
+        .collect();
+    candidates.sort_by_key(|id| sequences.get(id.as_str()).copied().unwrap_or(u64::MAX));
+    Ok(candidates)
 }
 
+/// Adds the request at the end of the pending queue, requests already pending keep
+/// their place
 pub fn add_pending_request(request_id: &str, db: &Database) -> Result<()> {
-    let (pending_requests, pending_requests_index): (
-        Option<Vec<String>>,
-        Option<HashMap<String, i128>>,
-    ) = get_pending_request_and_index(&db);
-    info!("Adding new request to pending: {request_id}");
-
-    if let Some(mut pending) = pending_requests {
-        let index = pending.len();
-        pending.push(request_id.to_string());
-        update_pending_vector(db, pending)?;
-
-        let mut indexes = pending_requests_index.unwrap();
-        indexes.insert(request_id.to_owned(), index as i128);
-        update_pending_hashmap(db, indexes)?;
-    } else {
-        let pending = vec![request_id.to_string()];
-        update_pending_vector(db, pending)?;
-
-        let mut indexes = HashMap::new();
-        indexes.insert(request_id.to_owned(), 0);
-        update_pending_hashmap(db, indexes)?;
+    let mut batch = Batch::default();
+    if stage_pending_addition(request_id, db, &mut batch)? {
+        info!("Adding new request to pending: {request_id}");
+        db.write_batch(batch)?;
     }
     Ok(())
 }
 
+/// Terminal requests already leave the pending queue when saved, this only removes
+/// entries left by older versions
 pub fn remove_pending_request(request_id: &str, db: &Database) -> Result<()> {
-    let (pending_requests, pending_requests_index): (
-        Option<Vec<String>>,
-        Option<HashMap<String, i128>>,
-    ) = get_pending_request_and_index(&db);
-    info!("Removing request from pending: {request_id}");
+    let mut batch = Batch::default();
+    if stage_pending_removal(request_id, db, &mut batch)? {
+        info!("Removing request from pending: {request_id}");
+        db.write_batch(batch)?;
+    }
+    Ok(())
+}
 
-    if let Some(mut pending) = pending_requests {
-        let mut indexes = pending_requests_index.unwrap();
-        let request_index = indexes.remove(request_id).unwrap();
+/// Pending requests are processed in one independent stream per origin chain, so a
+/// backlog on one chain never delays the other
+pub async fn process_pending_request(pending: Vec<String>, state: AppState) {
+    state.in_flight.release_settled(&state.db);
+    let (evm_pending, solana_pending) = split_by_origin(pending, &state.db);
+    info!(
+        "Processing pending requests, {} from EVM and {} from Solana",
+        evm_pending.len(),
+        solana_pending.len()
+    );
 
-        let last_id = pending[pending.len() - 1].clone();
+    // Each stream is paced by its own interval
+    tokio::join!(
+        process_origin_requests(evm_pending, &state, state.intervals.evm_pending_request),
+        process_origin_requests(
+            solana_pending,
+            &state,
+            state.intervals.solana_pending_request
+        )
+    );
+}
 
-        pending.swap_remove(request_index as usize);
-        update_pending_vector(db, pending)?;
+/// Processes the events buffered while their direction was paused
+pub async fn drain_resumed_events(state: &AppState) {
+    let ids = match state.pause.take_resumed_events(&state.db) {
+        Ok(ids) => ids,
+        Err(e) => {
+            error!("Could not read buffered events: {}", e);
+            return;
+        }
+    };
 
-        if let Some(value) = indexes.get_mut(&last_id) {
-            *value = request_index;
+    for id in ids {
+        info!("Processing buffered event of request {id}");
+        let processed = match types::request_data(&id, &state.db) {
+            Ok(Some(request)) => match request.input.origin_network {
+                Chains::EVM => {
+                    evm::check_token_owner(
+                        state.evm_client.clone(),
+                        &state.db,
+                        &state.evm_head,
+                        &id,
+                    )
+                    .await
+                }
+                Chains::SOLANA => {
+                    solana::check_token_owner(&state.db, &state.solana_client, &id).await
+                }
+            },
+            _ => Ok(()),
+        };
+        if let Err(e) = processed {
+            error!("Processing buffered event of request {id}, error {:?}", e);
         }
-        update_pending_hashmap(db, indexes)?;
     }
-    Ok(())
 }
 
-fn update_pending_vector(db: &Database, requests: Vec<String>) -> Result<()> {
-    _ = update_vector(db, PENDING_REQUESTS, requests)
-        .map_err(|e| RequestError::CreationError(e.to_string()));
-    Ok(())
-}
+/// Mints the EVM origin requests waiting for their token URI once their retry is due,
+/// none during a maintenance
+pub async fn retry_pending_metadata(state: &AppState) {
+    if state.pause.maintenance().is_some() {
+        return;
+    }
+    let received = match requests_by_status(&state.db, &Status::TokenReceived, None) {
+        Ok(received) => received,
+        Err(e) => {
+            error!("Could not read requests waiting for their token URI: {}", e);
+            return;
+        }
+    };
 
-fn update_pending_hashmap(db: &Database, indexes: HashMap<String, i128>) -> Result<()> {
-    _ = update_hashmap(db, PENDING_REQUESTS_INDEX, indexes)
-        .map_err(|e| RequestError::CreationError(e.to_string()));
-    Ok(())
+    for (id, _) in received {
+        let Ok(Some(request)) = types::request_data(&id, &state.db) else {
+            continue;
+        };
+        if request.metadata_pending.is_none() || !request.metadata_retry_due(state.clock.now()) {
+            continue;
+        }
+        match state.request_locks.try_lock(&id) {
+            Some(_lock) => {
+                info!("Retrying the token URI of request {id}");
+                if let Err(e) = continue_from_metadata(state, &request).await {
+                    error!("Retrying the token URI of request {id}, error {:?}", e);
+                }
+            }
+            None => info!("Request {id} is already being processed, skipping"),
+        }
+    }
 }
 
-pub async fn process_pending_request(pending: Vec<String>, state: AppState) {
-    for id in pending {
-        if let Some(mut request) = state.db.read::<_, BRequest>(&id).unwrap() {
-            info!("Request in pending: {:?}", request.clone());
+/// Sends the mints of the Solana requests whose custody transfer got finalized since it
+/// was seen confirmed, and rolls back the ones never finalized. Waits for the end of a
+/// maintenance
+pub async fn check_pending_finality(state: &AppState) {
+    if state.pause.maintenance().is_some() {
+        return;
+    }
+    let received = match requests_by_status(&state.db, &Status::TokenReceived, None) {
+        Ok(received) => received,
+        Err(e) => {
+            error!("Could not read requests waiting for finality: {}", e);
+            return;
+        }
+    };
 
-            match request.input.origin_network {
-                Chains::EVM => {
-                    let processed = process_evm_pending_request(request.clone(), &state).await;
-                    if processed.is_err() {
-                        let error_msg = processed.err().unwrap().to_string();
-                        error!(
-                            "Processing pending request {}, error {:?}",
-                            &request.id, &error_msg
-                        );
-                        if error_msg.contains("address") && error_msg.contains("already in use") {
-                            info!("Canceling pending request {}", &request.id);
-                            request.cancel(&state.db).unwrap_or_else(|err| {
-                                error!(
-                                    "Could not cancel pending request {}, error {:?}",
-                                    &request.id, &err
-                                );
-                            });
-                        }
-                    }
+    for (id, _) in received {
+        let Ok(Some(request)) = types::request_data(&id, &state.db) else {
+            continue;
+        };
+        if !request.awaiting_finality() {
+            continue;
+        }
+        match state.request_locks.try_lock(&id) {
+            Some(_lock) => {
+                let now = state.clock.now();
+                if let Err(e) =
+                    solana::check_finality(&state.db, &state.solana_client, &id, now).await
+                {
+                    error!(
+                        "Checking the custody finality of request {id}, error {:?}",
+                        e
+                    );
                 }
-                Chains::SOLANA => {
-                    let processed = process_solana_pending_request(request.clone(), &state).await;
-                    if processed.is_err() {
-                        error!(
-                            "Processing pending request {}, error {:?}",
-                            &request.id,
-                            &processed.err()
-                        );
+            }
+            None => info!("Request {id} is already being processed, skipping"),
+        }
+    }
+}
+
+/// Splits the request ids by origin chain keeping their order
+pub fn split_by_origin(ids: Vec<String>, db: &Database) -> (Vec<String>, Vec<String>) {
+    let mut evm_ids = vec![];
+    let mut solana_ids = vec![];
+    for id in ids {
+        match db.read::<_, BRequest>(&id) {
+            Ok(Some(request)) => match request.input.origin_network {
+                Chains::EVM => evm_ids.push(id),
+                Chains::SOLANA => solana_ids.push(id),
+            },
+            _ => error!("Error reading pending request {id}"),
+        }
+    }
+    (evm_ids, solana_ids)
+}
+
+/// Reads in chunks the accounts of the owner checks of the Solana requests in `ids`, the
+/// checks read the accounts on their own when the chunks fail
+async fn prefetch_owner_check_accounts(ids: &[String], state: &AppState) -> AccountsCache {
+    let pubkeys: Vec<_> = ids
+        .iter()
+        .filter_map(|id| types::request_data(id, &state.db).ok().flatten())
+        .filter(|request| {
+            request.input.origin_network == Chains::SOLANA
+                && request.status == Status::RequestReceived
+        })
+        .flat_map(|request| solana::owner_check_accounts(&state.solana_client, &request))
+        .collect();
+    if pubkeys.is_empty() {
+        return AccountsCache::default();
+    }
+    solana::prefetch_accounts(&state.solana_client, &pubkeys)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Could not prefetch the accounts of the pending requests: {e}");
+            AccountsCache::default()
+        })
+}
+
+/// Whether this tick claimed the request, unreadable claims let the tick go on as the
+/// request lock still keeps the handlers apart
+fn claim_request(id: &str, state: &AppState) -> bool {
+    state
+        .work_claims
+        .try_claim(&state.db, id)
+        .unwrap_or_else(|e| {
+            error!("Could not claim request {id}: {e}");
+            true
+        })
+}
+
+async fn process_origin_requests(ids: Vec<String>, state: &AppState, interval: Duration) {
+    let accounts = prefetch_owner_check_accounts(&ids, state).await;
+    for id in ids {
+        // The requests left are picked up by the next sweep
+        if state.pause.maintenance().is_some() {
+            info!("Maintenance in progress, the sweep stops claiming requests");
+            break;
+        }
+        // Claimed by a tick still handling it, the lease covers a crashed handler
+        if !claim_request(&id, state) {
+            continue;
+        }
+        match state.request_locks.try_lock(&id) {
+            // Read under the lock, the handler that held it may have moved the request on
+            Some(_lock) => match state.db.read::<_, BRequest>(&id) {
+                Ok(Some(request)) => {
+                    info!("Request in pending: {}", LoggableRequest::of(&request));
+                    if let Some(detail) = debug_detail(&request) {
+                        debug!("Pending request {detail}");
                     }
+                    // Errors are logged by the sweep, the next one retries
+                    _ = sweep_request_with(request, state, &accounts).await;
                 }
-            }
-        } else {
-            error!("Error processing pending requests");
+                _ => error!("Error processing pending requests"),
+            },
+            None => info!("Request {id} is already being processed, skipping"),
         }
-        sleep(Duration::from_secs(8));
+        state.work_claims.release(&state.db, &id);
+        tokio::time::sleep(interval).await;
     }
 }
 
+/// How the sweep settles a request whose step failed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SweepFailure {
+    /// Left pending, the next sweep retries it
+    Retry,
+    /// The request data can't be parsed, canceled as corrupted
+    CancelCorrupted,
+    /// The destination account was created by an earlier attempt, canceled
+    Cancel,
+}
+
+pub fn sweep_failure(err: &BridgeError) -> SweepFailure {
+    match err {
+        BridgeError::CorruptedData(_) => SweepFailure::CancelCorrupted,
+        BridgeError::AccountAlreadyInitialized(_) => SweepFailure::Cancel,
+        _ => SweepFailure::Retry,
+    }
+}
+
+/// One sweep step of a pending request, advancing it from its current status. Requests
+/// failing with corrupted data are canceled. The caller holds the request lock
+pub async fn sweep_request(request: BRequest, state: &AppState) -> Result<(), BridgeError> {
+    sweep_request_with(request, state, &AccountsCache::default()).await
+}
+
+/// Same as `sweep_request` reading the Solana accounts prefetched for the sweep page
+/// from `accounts`
+pub async fn sweep_request_with(
+    mut request: BRequest,
+    state: &AppState,
+    accounts: &AccountsCache,
+) -> Result<(), BridgeError> {
+    let processed = match request.input.origin_network {
+        Chains::EVM => process_evm_pending_request(request.clone(), state).await,
+        Chains::SOLANA => process_solana_pending_request(request.clone(), state, accounts).await,
+    };
+    let Err(err) = processed else {
+        return Ok(());
+    };
+    let error_msg = err.to_string();
+    let bridge_err = bridge_error(&err);
+    // Fixed by a resend of the tx processors, no need to alert
+    if ErrorClass::of(&bridge_err).fast_path().is_some() {
+        warn!(
+            "Processing pending request {}, transient error {:?}",
+            &request.id, &err
+        );
+    } else {
+        error!(
+            "Processing pending request {}, error {:?}",
+            &request.id, &err
+        );
+    }
+
+    let err = bridge_err;
+    match sweep_failure(&err) {
+        SweepFailure::CancelCorrupted => cancel_corrupted_request(&mut request, &error_msg, state),
+        SweepFailure::Cancel => {
+            info!("Canceling pending request {}", &request.id);
+            request
+                .cancel(&state.db, state.clock.now())
+                .unwrap_or_else(|err| {
+                    error!(
+                        "Could not cancel pending request {}, error {:?}",
+                        &request.id, &err
+                    );
+                });
+        }
+        SweepFailure::Retry => record_retry_failure(&request.id, &err, state).unwrap_or_else(|e| {
+            error!(
+                "Could not record the failed attempt of request {}, error {:?}",
+                &request.id, &e
+            );
+        }),
+    }
+    Err(err)
+}
+
+/// Keeps the failed attempt on the request. After `MAX_SWEEP_ATTEMPTS` attempts failing on
+/// the request itself in the same status, it is parked in NeedsIntervention with a failure
+/// report and leaves pending. Outages and wallet failures never give a request up
+pub fn record_retry_failure(request_id: &str, err: &BridgeError, state: &AppState) -> Result<()> {
+    let Some(mut request) = types::request_data(request_id, &state.db)? else {
+        return Ok(());
+    };
+    let now = state.clock.now();
+    if ErrorClass::of(err).fast_path().is_some() {
+        // Blockhash expiry and nonce conflicts don't use the retries of the request
+        let record = ErrorRecord::of_bridge_error(ErrorComponent::Sweeper, err, now);
+        return request.record_error_record(record, None, &state.db);
+    }
+    request.record_failed_attempt(err, &state.db, now)?;
+    if !request.retries_exhausted() {
+        return Ok(());
+    }
+
+    let cursors = ChainCursors {
+        evm_block: state
+            .evm_client
+            .event_cursor
+            .get()
+            .map(|cursor| cursor.height),
+        solana_slot: state
+            .solana_client
+            .event_cursor
+            .get()
+            .map(|cursor| cursor.height),
+    };
+    request.park_with_report(&state.db, cursors, now)?;
+    Ok(())
+}
+
+/// Requests with corrupted data fail on every attempt, they are canceled and removed from
+/// pending so the sweep moves on
+fn cancel_corrupted_request(request: &mut BRequest, error: &str, state: &AppState) {
+    info!(
+        "Canceling pending request {} with corrupted data",
+        &request.id
+    );
+    if let Err(e) = request.cancel_corrupted(&state.db, error, state.clock.now()) {
+        error!(
+            "Could not cancel pending request {}, error {:?}",
+            &request.id, &e
+        );
+        return;
+    }
+    remove_pending_request(&request.id, &state.db).unwrap_or_else(|e| {
+        error!(
+            "Could not remove pending request {}, error {:?}",
+            &request.id, &e
+        );
+    });
+}
+
 async fn process_evm_pending_request(mut request: BRequest, state: &AppState) -> Result<()> {
     match request.status {
+        // The lock message was lost, by a restart or a full channel
+        Status::Initializing => enqueue_lock(state, &request).await,
         Status::RequestReceived => {
-            evm::check_token_owner(state.evm_client.clone(), &state.db, &request.id).await?;
+            evm::check_token_owner(
+                state.evm_client.clone(),
+                &state.db,
+                &state.evm_head,
+                &request.id,
+            )
+            .await?;
             Ok(())
         }
         Status::TokenReceived => {
+            if !request.metadata_retry_due(state.clock.now()) {
+                info!("Token URI of request {} not due for a retry", request.id);
+                return Ok(());
+            }
             continue_from_metadata(state, &request).await?;
             Ok(())
         }
-        Status::TokenMinted => {
-            let last_tx = &request.tx_hashes[request.tx_hashes.len() - 1];
-            if solana::get_transaction_data(state.solana_client.clone(), &last_tx)
-                .await
-                .is_err()
+        // The periodic check of the destination token account, the mint is sent once funded
+        Status::AwaitingDestinationAccount => {
+            if solana::recheck_destination_account(
+                &state.solana_client,
+                &state.db,
+                &mut request,
+                state.clock.now(),
+            )
+            .await?
             {
                 continue_from_metadata(state, &request).await?;
-            } else {
-                // If the destination token has metadata it, the process was completed
-                if let Ok(_) = solana::get_metadata(
-                    &state.solana_client.clone(),
-                    &request.output.detination_contract_id_or_mint,
-                ) {
-                    request.update_state(&state.db)?;
-                } else {
-                    // If not exist send the transaction to mint the token again
-                    continue_from_metadata(state, &request).await?;
+            }
+            Ok(())
+        }
+        Status::TokenMinted => {
+            let last_tx = last_mint_tx(&request)?;
+            let tx_state = solana::get_signature_state(&state.solana_client, &last_tx).await?;
+            match mint_tx_step(&tx_state) {
+                MintTxStep::Wait => info!("Mint {last_tx} of request {} pending", request.id),
+                MintTxStep::Resend => resend_mint(state, &request).await?,
+                MintTxStep::VerifyDestination => {
+                    match solana::get_transaction_data(state.solana_client.clone(), &last_tx).await
+                    {
+                        Ok(tx) => {
+                            if let Some(fee) = solana::transaction_fee(&tx) {
+                                // The relayer funds the accounts of the destination token
+                                let rent = solana::transaction_rent(&tx);
+                                let cost =
+                                    rent.and_then(|rent| fee.checked_add(rent)).unwrap_or(fee);
+                                request.record_fee_with_rent(
+                                    &state.db,
+                                    &last_tx,
+                                    Fee::SOLANA(cost),
+                                    rent,
+                                    state.clock.now(),
+                                )?;
+                            }
+                        }
+                        Err(e) => error!("Could not read the fee of mint {last_tx}: {e}"),
+                    }
+                    // If the destination token has metadata it, the process was completed
+                    match solana::get_metadata(
+                        &state.solana_client.clone(),
+                        &request.output.detination_contract_id_or_mint,
+                    )
+                    .await
+                    {
+                        Ok(_) => {
+                            let now = state.clock.now();
+                            if solana::verify_destination_owner(
+                                &state.solana_client,
+                                &state.db,
+                                &mut request,
+                                now,
+                            )
+                            .await?
+                            {
+                                let mint = request.output.detination_contract_id_or_mint.clone();
+                                let account = request.output.detination_token_id_or_account.clone();
+                                request.complete_minted(&state.db, &mint, &account, now)?;
+                            }
+                        }
+                        Err(e) if e.is_corrupted_data() => return Err(e),
+                        // If not exist send the transaction to mint the token again
+                        Err(_) => resend_mint(state, &request).await?,
+                    }
                 }
             }
             Ok(())
         }
         Status::Completed => Ok(remove_pending_request(&request.id, &state.db)?),
-        Status::Canceled => Ok(remove_pending_request(&request.id, &state.db)?),
+        Status::Canceled | Status::Refunded | Status::NeedsIntervention => {
+            Ok(remove_pending_request(&request.id, &state.db)?)
+        }
+        // Orphans are not pending until claimed
+        Status::NeedsDestination | Status::RefundEligible => Ok(()),
     }
 }
 
-async fn process_solana_pending_request(mut request: BRequest, state: &AppState) -> Result<()> {
+async fn process_solana_pending_request(
+    mut request: BRequest,
+    state: &AppState,
+    accounts: &AccountsCache,
+) -> Result<()> {
     match request.status {
+        // The lock message was lost, by a restart or a full channel
+        Status::Initializing => enqueue_lock(state, &request).await,
         Status::RequestReceived => {
-            solana::check_token_owner(&state.db, &state.solana_client, &request.id).await;
+            solana::check_token_owner_cached(
+                &state.db,
+                &state.solana_client,
+                accounts,
+                &request.id,
+            )
+            .await?;
+            Ok(())
+        }
+        Status::TokenReceived if request.awaiting_finality() => {
+            let now = state.clock.now();
+            solana::check_finality(&state.db, &state.solana_client, &request.id, now).await?;
             Ok(())
         }
         Status::TokenReceived => {
@@ -171,65 +563,922 @@ async fn process_solana_pending_request(mut request: BRequest, state: &AppState)
             Ok(())
         }
         Status::TokenMinted => {
-            let last_tx = &request.tx_hashes[request.tx_hashes.len() - 1];
-            if evm::get_transaction_data(state.evm_client.clone(), &last_tx)
-                .await
-                .unwrap()
-                .is_none()
+            let last_tx = last_mint_tx(&request)?;
+            if let Some(fee) =
+                evm::get_transaction_fee(state.evm_client.clone(), &last_tx, &request.id).await?
             {
-                continue_from_metadata(state, &request).await?;
-            } else {
-                let data = evm::get_transaction_data(state.evm_client.clone(), &last_tx)
-                    .await
-                    .unwrap();
-                info!("Transaction data exist {:?}", data);
-                let token_contract =
-                    Address::from_str(&request.output.detination_contract_id_or_mint).unwrap();
-                let token_id: U256 = request
-                    .output
-                    .detination_token_id_or_account
-                    .parse()
-                    .expect("Invalid U256 string");
-
-                // If the destination token has metadata it, the process was completed
-                if evm::get_token_metadata(state.evm_client.clone(), token_contract, token_id)
-                    .await
-                    .is_ok()
-                {
-                    request.update_state(&state.db)?;
-                } else {
-                    // If not exist send the transaction to mint the token again
-                    continue_from_metadata(state, &request).await?;
+                request.record_fee(&state.db, &last_tx, Fee::EVM(fee), state.clock.now())?;
+            }
+            // The TokenMinted log of the receipt is authoritative over the predicted output,
+            // it is emitted by the contract the request was created against
+            let evm_client = state.evm_client.for_request(&request)?;
+            if let Some(minted) = evm::get_minted_token(evm_client, &last_tx, &request.id).await? {
+                complete_evm_mint(
+                    &mut request,
+                    &state.db,
+                    minted.token_contract,
+                    minted.token_id,
+                    minted.to,
+                    state.clock.now(),
+                )?;
+                return Ok(());
+            }
+
+            let tx_state =
+                evm::get_tx_state(state.evm_client.clone(), &last_tx, &request.id).await?;
+            match mint_tx_step(&tx_state) {
+                MintTxStep::Wait => info!("Mint {last_tx} of request {} pending", request.id),
+                MintTxStep::Resend => resend_mint(state, &request).await?,
+                MintTxStep::VerifyDestination => {
+                    let (token_contract, token_id) = destination_evm_token(&request)?;
+
+                    // If the destination token has metadata it, the process was completed
+                    if evm::get_token_metadata(state.evm_client.clone(), token_contract, token_id)
+                        .await
+                        .is_ok()
+                    {
+                        // Without the receipt log the holder is read from the token
+                        let owner = evm::get_token_owner(
+                            state.evm_client.clone(),
+                            token_contract,
+                            token_id,
+                        )
+                        .await?;
+                        complete_evm_mint(
+                            &mut request,
+                            &state.db,
+                            token_contract,
+                            token_id,
+                            owner,
+                            state.clock.now(),
+                        )?;
+                    } else {
+                        // If not exist send the transaction to mint the token again
+                        resend_mint(state, &request).await?;
+                    }
                 }
             }
             Ok(())
         }
         Status::Completed => Ok(remove_pending_request(&request.id, &state.db)?),
-        Status::Canceled => Ok(remove_pending_request(&request.id, &state.db)?),
+        Status::Canceled | Status::Refunded | Status::NeedsIntervention => {
+            Ok(remove_pending_request(&request.id, &state.db)?)
+        }
+        // Only mints on Solana wait for their destination token account
+        Status::NeedsDestination | Status::RefundEligible | Status::AwaitingDestinationAccount => {
+            Ok(())
+        }
     }
 }
 
-async fn continue_from_metadata(state: &AppState, request: &BRequest) -> Result<()> {
+/// Mint transaction of a TokenMinted request. Without one the sweep retries the request
+/// until it is parked for an operator, sending the mint again could mint it twice
+fn last_mint_tx(request: &BRequest) -> Result<String, BridgeError> {
+    request.tx_hashes.last().cloned().ok_or_else(|| {
+        BridgeError::Other(format!(
+            "Request {} is minted without a mint transaction",
+            request.id
+        ))
+    })
+}
+
+/// Completes a request minted on EVM once `recipient` is checked against its destination
+/// account. A token minted to another account parks the request for an operator
+fn complete_evm_mint(
+    request: &mut BRequest,
+    db: &Database,
+    token_contract: Address,
+    token_id: U256,
+    recipient: Address,
+    now: Timestamp,
+) -> Result<()> {
+    if request.verify_mint_recipient(db, &recipient.to_string(), now)? {
+        request.complete_minted(db, &token_contract.to_string(), &token_id.to_string(), now)?;
+    }
+    Ok(())
+}
+
+/// Token minted on EVM for the request. A destination that doesn't parse is corrupted
+/// data, the sweep cancels the request and moves on
+fn destination_evm_token(request: &BRequest) -> Result<(Address, U256), BridgeError> {
+    let token_contract = Address::from_str(&request.output.detination_contract_id_or_mint)
+        .map_err(|e| {
+            BridgeError::CorruptedData(format!(
+                "destination contract {:?}: {e}",
+                request.output.detination_contract_id_or_mint
+            ))
+        })?;
+    let token_id = types::parse_token_id(&request.output.detination_token_id_or_account)?;
+    Ok((token_contract, token_id))
+}
+
+/// Next sweep step of a TokenMinted request from the state of its mint transaction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MintTxStep {
+    /// Still pending, sending it again would only compete with it
+    Wait,
+    /// Dropped, reverted or priced out, the mint is sent again
+    Resend,
+    /// Included, the destination token is checked
+    VerifyDestination,
+}
+
+pub fn mint_tx_step(tx_state: &TxState) -> MintTxStep {
+    match tx_state {
+        TxState::Pending { .. } if tx_state.priced_out() => MintTxStep::Resend,
+        TxState::Pending { .. } => MintTxStep::Wait,
+        TxState::Mined { success: true } => MintTxStep::VerifyDestination,
+        TxState::Mined { success: false } | TxState::Dropped => MintTxStep::Resend,
+    }
+}
+
+/// Mint transaction of a TokenMinted request with its state on the destination chain
+pub async fn mint_tx_state(
+    state: &AppState,
+    request: &BRequest,
+) -> Result<Option<(String, TxState)>> {
+    let Some(last_tx) = request.tx_hashes.last() else {
+        return Ok(None);
+    };
+    if request.status != Status::TokenMinted {
+        return Ok(None);
+    }
+    let tx_state = match request.input.origin_network {
+        Chains::EVM => solana::get_signature_state(&state.solana_client, last_tx).await?,
+        Chains::SOLANA => evm::get_tx_state(state.evm_client.clone(), last_tx, &request.id).await?,
+    };
+    Ok(Some((last_tx.clone(), tx_state)))
+}
+
+/// Sends the mint of the request, with the metadata kept by its first attempt or else
+/// the one read from the origin token
+pub(crate) async fn continue_from_metadata(state: &AppState, request: &BRequest) -> Result<()> {
+    if let Some(snapshot) = &request.metadata_snapshot {
+        info!(
+            "Minting request {} with its metadata snapshot {}",
+            request.id, snapshot.origin_uri
+        );
+        return send_mint(state, request, &snapshot.origin_uri).await;
+    }
+    let Some(metadata) = fetch_origin_metadata(state, request).await? else {
+        return Ok(());
+    };
+    send_mint(state, request, &metadata).await
+}
+
+/// Metadata URI of the origin token, None when it can't be read yet
+async fn fetch_origin_metadata(state: &AppState, request: &BRequest) -> Result<Option<String>> {
     match request.input.origin_network {
         Chains::EVM => {
-            let token_contract = Address::from_str(&request.input.contract_or_mint).unwrap();
-            let token_id: U256 = request.input.token_id.parse().expect("Invalid U256 string");
-            if let Ok(metadata) =
-                evm::get_token_metadata(state.evm_client.clone(), token_contract, token_id).await
+            let mut request = request.clone();
+            // None when the request was parked waiting for the token URI
+            evm::token_uri_for_mint(
+                state.evm_client.clone(),
+                &state.db,
+                &mut request,
+                state.clock.now(),
+            )
+            .await
+        }
+        Chains::SOLANA => {
+            match solana::get_metadata(&state.solana_client, &request.input.contract_or_mint).await
             {
-                solana::mint_new_token(&state.solana_client, &state.db, &request.id, &metadata)
-                    .await?;
+                Ok(metadata) => Ok(Some(metadata)),
+                Err(e) if e.is_corrupted_data() => Err(e),
+                Err(_) => Ok(None),
             }
-            Ok(())
+        }
+    }
+}
+
+/// Mint of a TokenMinted request that failed or vanished, it is no longer in flight
+async fn resend_mint(state: &AppState, request: &BRequest) -> Result<()> {
+    state.in_flight.release(&request.id, &state.db);
+    continue_from_metadata(state, request).await
+}
+
+/// Mint stage, the destination chain keeps the metadata snapshot before sending. Skipped
+/// while a mint of the request is in flight
+async fn send_mint(state: &AppState, request: &BRequest, metadata: &str) -> Result<()> {
+    let (db, id) = (&state.db, &request.id);
+    let sent: Option<Result<bool>> = match request.input.origin_network {
+        Chains::EVM => {
+            state
+                .in_flight
+                .send_mint(id, db, async {
+                    let signature =
+                        solana::mint_new_token(&state.solana_client, db, id, metadata).await?;
+                    Ok(signature != Signature::default())
+                })
+                .await
         }
         Chains::SOLANA => {
-            if let Ok(metadata) =
-                solana::get_metadata(&state.solana_client, &request.input.contract_or_mint)
+            state
+                .in_flight
+                .send_mint(id, db, async {
+                    let tx_hash =
+                        evm::mint_new_token(state.evm_client.clone(), db, id, metadata).await?;
+                    Ok(!tx_hash.is_empty())
+                })
+                .await
+        }
+    };
+    match sent {
+        None => info!("Mint of request {id} already in flight"),
+        Some(Ok(false)) => state.in_flight.release(id, db),
+        Some(Ok(true)) => {}
+        Some(Err(e)) => return Err(e),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod pending_test {
+    use std::{
+        collections::HashMap,
+        str::FromStr,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use alloy::primitives::Address;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::pubkey::Pubkey;
+    use test_support::{
+        accounts_mock_rpc, metadata_account, mock_rpc, request_in_status, requests_in_every_status,
+        signature_status_result, slow_mock_rpc, solana_key, test_db, EVM_ACCOUNT,
+    };
+    use types::{
+        BRequest, BridgeError, CancelReason, Chains, Clock, ErrorClass, FinalityPending,
+        InputRequest, Intervals, MockClock, Status, SuggestedAction, TimeoutError, Timestamp,
+        TxMessage, TxState, WorkClaims, MAX_KEPT_ATTEMPTS, MAX_SWEEP_ATTEMPTS, WORK_CLAIM_LEASE,
+    };
+
+    use super::{complete_evm_mint, continue_from_metadata, destination_evm_token};
+    use crate::{
+        add_pending_request, bridge_error, check_pending_finality, get_pending_requests,
+        get_queue_position, mint_tx_step, process_origin_requests, process_pending_request,
+        record_retry_failure, remove_pending_request, split_by_origin, sweep_candidates,
+        sweep_failure, test_utils::test_state, MintTxStep, SweepFailure,
+    };
+
+    const RECEIVED_MIN_AGE: Duration = Intervals::PRODUCTION.received_sweep_min_age;
+
+    #[test]
+    fn test_sweep_grace_from_creation_time() {
+        let db = test_db();
+        let clock = MockClock::new(Timestamp::from_secs(1_700_000_000));
+        let now = clock.now();
+
+        let mut young = request_in_status(Chains::EVM, "1", Status::RequestReceived);
+        young.created_at = now.saturating_sub(Duration::from_secs(30));
+        young.last_update = now.saturating_sub(Duration::from_secs(3600));
+        young.save(&db).unwrap();
+        // Updated recently, but created before the grace period
+        let mut old = request_in_status(Chains::EVM, "2", Status::RequestReceived);
+        old.created_at = now.saturating_sub(Duration::from_secs(3600));
+        old.last_update = now;
+        old.save(&db).unwrap();
+
+        assert_eq!(
+            sweep_candidates(&db, &clock, RECEIVED_MIN_AGE).unwrap(),
+            vec![old.id.clone()]
+        );
+        clock.advance(RECEIVED_MIN_AGE);
+        let mut candidates = sweep_candidates(&db, &clock, RECEIVED_MIN_AGE).unwrap();
+        candidates.sort();
+        let mut expected = vec![young.id.to_string(), old.id.to_string()];
+        expected.sort();
+        assert_eq!(candidates, expected);
+    }
+
+    #[tokio::test]
+    async fn test_finality_wait_resumed_after_restart() {
+        let db = test_db();
+        // Custody seen confirmed and its metadata read before the relayer stopped
+        let mut request = request_in_status(Chains::SOLANA, "", Status::TokenReceived);
+        request.custody_signature = request.tx_hashes.first().cloned();
+        request.finality_pending = Some(FinalityPending {
+            signature: request.tx_hashes[0].clone(),
+            token_metadata: Some("https://example.com/1.json".to_string()),
+            observed_at: Timestamp::now(),
+        });
+        request.save(&db).unwrap();
+        add_pending_request(&request.id, &db).unwrap();
+
+        // The sweep doesn't mint before the custody is finalized
+        let (mut state, mut rx_evm, _) = test_state(db.clone());
+        state.solana_client.set_rpc(mock_rpc([(
+            RpcRequest::GetSignatureStatuses,
+            signature_status_result(Some("confirmed")),
+        )]));
+        process_origin_requests(vec![request.id.to_string()], &state, Duration::ZERO).await;
+        let no_mint = tokio::time::timeout(Duration::from_millis(50), rx_evm.recv()).await;
+        assert!(no_mint.is_err());
+        let waiting = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert!(waiting.awaiting_finality());
+        drop(state);
+
+        let (mut state, mut rx_evm, _) = test_state(db.clone());
+        state.solana_client.set_rpc(mock_rpc([(
+            RpcRequest::GetSignatureStatuses,
+            signature_status_result(Some("finalized")),
+        )]));
+        check_pending_finality(&state).await;
+        match rx_evm.recv().await.unwrap() {
+            TxMessage::Mint(mint) => {
+                assert_eq!(mint.request_id, request.id);
+                assert_eq!(mint.token_metadata, "https://example.com/1.json");
+            }
+            _ => panic!("expected a mint"),
+        }
+        let finalized = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert!(!finalized.awaiting_finality());
+        assert_eq!(finalized.status, Status::TokenReceived);
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_ticks_skip_claimed_requests() {
+        let db = test_db();
+        let clock = Arc::new(MockClock::new(Timestamp::from_secs(1_700_000_000)));
+        let mut request = request_in_status(Chains::SOLANA, "", Status::TokenReceived);
+        request.custody_signature = request.tx_hashes.first().cloned();
+        request.finality_pending = Some(FinalityPending {
+            signature: request.tx_hashes[0].clone(),
+            token_metadata: Some("https://example.com/1.json".to_string()),
+            observed_at: clock.now(),
+        });
+        request.save(&db).unwrap();
+        let ids = vec![request.id.to_string()];
+
+        let (mut state, _rx_evm, _) = test_state(db.clone());
+        state.clock = clock.clone();
+        state.work_claims = WorkClaims::new(WORK_CLAIM_LEASE, clock.clone());
+        // Each finality check waits on the node
+        state.solana_client.set_rpc(slow_mock_rpc(
+            Duration::from_millis(300),
+            [(
+                RpcRequest::GetSignatureStatuses,
+                signature_status_result(Some("confirmed")),
+            )],
+        ));
+
+        // Tick N+1 reads the entry while the handler of tick N is still running
+        let second_tick = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(state.work_claims.active_count(&db).unwrap(), 1);
+            process_origin_requests(ids.clone(), &state, Duration::ZERO).await;
+        };
+        tokio::join!(
+            process_origin_requests(ids.clone(), &state, Duration::ZERO),
+            second_tick
+        );
+        assert_eq!(state.work_claims.skipped_count(), 1);
+        assert_eq!(state.work_claims.active_count(&db).unwrap(), 0);
+
+        // A handler crashed holding the claim, the entry waits for the lease to expire
+        assert!(state.work_claims.try_claim(&db, &request.id).unwrap());
+        process_origin_requests(ids.clone(), &state, Duration::ZERO).await;
+        assert_eq!(state.work_claims.skipped_count(), 2);
+        clock.advance(WORK_CLAIM_LEASE);
+        process_origin_requests(ids, &state, Duration::ZERO).await;
+        assert_eq!(state.work_claims.skipped_count(), 2);
+        assert_eq!(state.work_claims.active_count(&db).unwrap(), 0);
+        // Handled again, its custody was not finalized in time
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::RequestReceived);
+    }
+
+    #[test]
+    fn test_sweep_follows_pending_queue_order() {
+        let db = test_db();
+        let mut requests: Vec<BRequest> = ["1", "2", "3"]
+            .into_iter()
+            .map(|token_id| request_in_status(Chains::EVM, token_id, Status::TokenReceived))
+            .collect();
+        // Added in reverse id order, the status buckets are in id order
+        requests.sort_by(|a, b| b.id.as_str().cmp(a.id.as_str()));
+        for request in &requests {
+            request.save(&db).unwrap();
+            add_pending_request(&request.id, &db).unwrap();
+        }
+        let [a, b, c] = [&requests[0].id, &requests[1].id, &requests[2].id];
+
+        remove_pending_request(b, &db).unwrap();
+        let clock = MockClock::new(Timestamp::from_secs(1_700_000_000));
+        let candidates = sweep_candidates(&db, &clock, RECEIVED_MIN_AGE).unwrap();
+        assert_eq!(&candidates[..2], &[a.to_string(), c.to_string()]);
+        assert_eq!(
+            get_pending_requests(&db).unwrap(),
+            vec![a.to_string(), c.to_string()]
+        );
+        assert_eq!(get_queue_position(a, &db).unwrap(), Some(1));
+        assert_eq!(get_queue_position(c, &db).unwrap(), Some(2));
+        assert_eq!(get_queue_position(b, &db).unwrap(), None);
+    }
+
+    #[test]
+    fn test_sweep_freshness_with_clock_stepping_back() {
+        let db = test_db();
+        let request = request_in_status(Chains::EVM, "1", Status::RequestReceived);
+        request.save(&db).unwrap();
+        let clock = MockClock::new(request.last_update);
+
+        // Too new for the sweep
+        assert!(sweep_candidates(&db, &clock, RECEIVED_MIN_AGE)
+            .unwrap()
+            .is_empty());
+
+        // Clock stepped back before the request was stored
+        clock.rewind(Duration::from_secs(3600));
+        assert!(sweep_candidates(&db, &clock, RECEIVED_MIN_AGE)
+            .unwrap()
+            .is_empty());
+
+        clock.set(request.last_update);
+        clock.advance(Duration::from_secs(121));
+        assert_eq!(
+            sweep_candidates(&db, &clock, RECEIVED_MIN_AGE).unwrap(),
+            vec![request.id.clone()]
+        );
+        assert!(request.last_update.elapsed_until(clock.now()) > Duration::from_secs(120));
+    }
+
+    #[test]
+    fn test_typed_errors_drive_auto_cancel() {
+        let already_minted = BridgeError::from(solana::SolanaError::AccountAlreadyInitialized(
+            "mint".to_string(),
+        ));
+        assert_eq!(
+            sweep_failure(&bridge_error(&already_minted)),
+            SweepFailure::Cancel
+        );
+        let corrupted = BridgeError::from(solana::SolanaError::InvalidData {
+            field: "signature",
+            value: "0x".to_string(),
+        });
+        assert_eq!(
+            sweep_failure(&bridge_error(&corrupted)),
+            SweepFailure::CancelCorrupted
+        );
+
+        // Messages are not matched, only the variants cancel
+        let untyped = BridgeError::Other(
+            "Allocate: account Address { address: mint } already in use".to_string(),
+        );
+        assert_eq!(sweep_failure(&bridge_error(&untyped)), SweepFailure::Retry);
+        assert_eq!(
+            sweep_failure(&BridgeError::NotAuthorizedBackend("signer".to_string())),
+            SweepFailure::Retry
+        );
+    }
+
+    #[test]
+    fn test_mint_tx_steps() {
+        let pending = |max_fee, base_fee| TxState::Pending {
+            max_fee_per_gas: max_fee,
+            base_fee_per_gas: base_fee,
+        };
+        assert_eq!(mint_tx_step(&pending(None, None)), MintTxStep::Wait);
+        assert_eq!(mint_tx_step(&pending(Some(20), Some(10))), MintTxStep::Wait);
+        // Stuck under the base fee, a new mint replaces it
+        assert_eq!(
+            mint_tx_step(&pending(Some(10), Some(20))),
+            MintTxStep::Resend
+        );
+        assert_eq!(mint_tx_step(&TxState::Dropped), MintTxStep::Resend);
+        assert_eq!(
+            mint_tx_step(&TxState::Mined { success: false }),
+            MintTxStep::Resend
+        );
+        assert_eq!(
+            mint_tx_step(&TxState::Mined { success: true }),
+            MintTxStep::VerifyDestination
+        );
+    }
+
+    #[test]
+    fn test_sweep_candidates_by_status() {
+        let db = test_db();
+        let requests = requests_in_every_status(Chains::SOLANA);
+        for request in &requests {
+            request.save(&db).unwrap();
+        }
+        let id_of = |status: Status| {
+            requests
+                .iter()
+                .find(|request| request.status == status)
+                .map(|request| request.id.clone())
+                .unwrap()
+        };
+
+        // Terminal requests already left the pending queue, orphans are not pending
+        let clock = MockClock::new(requests[0].last_update);
+        clock.advance(RECEIVED_MIN_AGE + Duration::from_secs(1));
+        assert_eq!(
+            sweep_candidates(&db, &clock, RECEIVED_MIN_AGE).unwrap(),
+            vec![
+                id_of(Status::Initializing),
+                id_of(Status::RequestReceived),
+                id_of(Status::TokenReceived),
+                id_of(Status::TokenMinted),
+                id_of(Status::AwaitingDestinationAccount)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sweep_cancels_corrupted_requests() {
+        let db = test_db();
+
+        // EVM style address stored as the Solana mint
+        let bad_mint = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xabc123".to_string(),
+                token_id: "1".to_string(),
+                token_owner: "owner".to_string(),
+                origin_network: Chains::SOLANA,
+                destination_account: "0xdestination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        // Minted on Solana with an invalid transaction signature
+        let mut bad_signature = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xabc123".to_string(),
+                token_id: "2".to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        bad_signature.status = Status::TokenMinted;
+        bad_signature.tx_hashes = vec!["0xlock".to_string(), "not-a-signature".to_string()];
+
+        for request in [&bad_mint, &bad_signature] {
+            request.save(&db).unwrap();
+            add_pending_request(&request.id, &db).unwrap();
+        }
+
+        let (state, _, _) = test_state(db.clone());
+        let (evm_ids, solana_ids) = split_by_origin(get_pending_requests(&db).unwrap(), &db);
+        tokio::join!(
+            process_origin_requests(evm_ids, &state, Duration::ZERO),
+            process_origin_requests(solana_ids, &state, Duration::ZERO)
+        );
+
+        for id in [&bad_mint.id, &bad_signature.id] {
+            let request = types::request_data(id, &db).unwrap().unwrap();
+            assert_eq!(request.status, Status::Canceled);
+            assert!(matches!(
+                request.cancel_reason,
+                Some(CancelReason::DataCorrupted(_))
+            ));
+            assert!(request.last_error.is_some());
+        }
+        assert!(get_pending_requests(&db).unwrap_or_default().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_light_accounts_served_behind_a_flooding_one() {
+        const FLOOD: usize = 100;
+        const LIGHT: u8 = 3;
+        let db = test_db();
+        let (mut state, _, _) = test_state(db.clone());
+        let interval = Duration::from_millis(10);
+        state.intervals.evm_pending_request = interval;
+        state.intervals.solana_pending_request = interval;
+
+        // One EVM account queued a burst before a few Solana accounts queued one request each
+        let flood: Vec<BRequest> = (0..FLOOD)
+            .map(|token_id| {
+                request_in_status(Chains::EVM, &token_id.to_string(), Status::Completed)
+            })
+            .collect();
+        let light: Vec<BRequest> = (0..LIGHT)
+            .map(|seed| {
+                let mut request =
+                    request_in_status(Chains::SOLANA, &seed.to_string(), Status::Completed);
+                request.input.token_owner = solana_key(10 + seed).to_string();
+                request
+            })
+            .collect();
+        for request in flood.iter().chain(&light) {
+            request.save(&db).unwrap();
+            add_pending_request(&request.id, &db).unwrap();
+        }
+
+        let started = Instant::now();
+        let sweep = tokio::spawn(process_pending_request(
+            get_pending_requests(&db).unwrap(),
+            state.clone(),
+        ));
+        let light_served = loop {
+            let pending = get_pending_requests(&db).unwrap_or_default();
+            if light
+                .iter()
+                .all(|request| !pending.contains(&request.id.to_string()))
             {
-                evm::mint_new_token(state.evm_client.clone(), &state.db, &request.id, &metadata)
-                    .await?;
+                break started.elapsed();
             }
-            Ok(())
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "light requests starved"
+            );
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        };
+
+        // Served after their own requests only, far from the whole burst
+        let bound = interval * u32::from(LIGHT) * 10;
+        assert!(light_served < bound, "{light_served:?}");
+        let flood_left = get_pending_requests(&db)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|id| flood.iter().any(|request| request.id.as_str() == id))
+            .count();
+        assert!(flood_left > FLOOD / 2, "{flood_left} flood requests left");
+
+        sweep.await.unwrap();
+        assert!(get_pending_requests(&db).unwrap_or_default().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_origin_streams_paced_apart() {
+        let db = test_db();
+        let (mut state, _, _) = test_state(db.clone());
+        // The EVM stream waits long after each request, the Solana one doesn't wait
+        state.intervals.evm_pending_request = Duration::from_secs(60);
+        state.intervals.solana_pending_request = Duration::ZERO;
+
+        let evm: Vec<BRequest> = (0..3)
+            .map(|token_id| {
+                request_in_status(Chains::EVM, &token_id.to_string(), Status::Completed)
+            })
+            .collect();
+        let solana: Vec<BRequest> = (0..3)
+            .map(|token_id| {
+                request_in_status(Chains::SOLANA, &token_id.to_string(), Status::Completed)
+            })
+            .collect();
+        for request in evm.iter().chain(&solana) {
+            request.save(&db).unwrap();
+            add_pending_request(&request.id, &db).unwrap();
+        }
+
+        let sweep = tokio::spawn(process_pending_request(
+            get_pending_requests(&db).unwrap(),
+            state.clone(),
+        ));
+        let started = Instant::now();
+        loop {
+            let pending = get_pending_requests(&db).unwrap_or_default();
+            if solana
+                .iter()
+                .all(|request| !pending.contains(&request.id.to_string()))
+            {
+                break;
+            }
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "Solana requests held by the EVM interval"
+            );
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        // Only the first EVM request was served, the others wait for its interval
+        let pending = get_pending_requests(&db).unwrap_or_default();
+        let evm_left = evm
+            .iter()
+            .filter(|request| pending.contains(&request.id.to_string()))
+            .count();
+        assert_eq!(evm_left, 2);
+        sweep.abort();
+    }
+
+    #[tokio::test]
+    async fn test_minted_without_tx_hash_retried() {
+        let db = test_db();
+        let requests: Vec<BRequest> = [Chains::EVM, Chains::SOLANA]
+            .into_iter()
+            .map(|origin| {
+                let mut request = request_in_status(origin, "1", Status::TokenMinted);
+                request.tx_hashes.clear();
+                request.save(&db).unwrap();
+                add_pending_request(&request.id, &db).unwrap();
+                request
+            })
+            .collect();
+
+        let (state, _, _) = test_state(db.clone());
+        let (evm_ids, solana_ids) = split_by_origin(get_pending_requests(&db).unwrap(), &db);
+        tokio::join!(
+            process_origin_requests(evm_ids, &state, Duration::ZERO),
+            process_origin_requests(solana_ids, &state, Duration::ZERO)
+        );
+
+        for request in requests {
+            let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+            assert_eq!(stored.status, Status::TokenMinted);
+            assert_eq!(stored.failed_attempts.len(), 1);
+            assert!(get_queue_position(&request.id, &db).unwrap().is_some());
+        }
+    }
+
+    #[test]
+    fn test_unparsable_evm_destination_is_corrupted() {
+        let mut request = request_in_status(Chains::SOLANA, "1", Status::TokenMinted);
+        assert!(destination_evm_token(&request).is_ok());
+
+        request.output.detination_contract_id_or_mint = "not-an-address".to_string();
+        let err = destination_evm_token(&request).unwrap_err();
+        assert!(matches!(err, BridgeError::CorruptedData(_)), "{err:?}");
+        assert_eq!(sweep_failure(&err), SweepFailure::CancelCorrupted);
+
+        let mut request = request_in_status(Chains::SOLANA, "1", Status::TokenMinted);
+        request.output.detination_token_id_or_account = "-1".to_string();
+        assert!(matches!(
+            destination_evm_token(&request),
+            Err(BridgeError::CorruptedData(_))
+        ));
+    }
+
+    #[test]
+    fn test_evm_mint_completed_only_to_the_destination() {
+        let db = test_db();
+        let now = Timestamp::from_secs(1_700_000_000);
+        let mut minted = request_in_status(Chains::SOLANA, "1", Status::TokenMinted);
+        minted.save(&db).unwrap();
+        let (token_contract, token_id) = destination_evm_token(&minted).unwrap();
+
+        // Held by another account, the request is not completed
+        let other = Address::from_str("0x70997970C51812dc3A010C7d01b50e0d17dc79C8").unwrap();
+        complete_evm_mint(&mut minted, &db, token_contract, token_id, other, now).unwrap();
+        let stored = types::request_data(&minted.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::NeedsIntervention);
+
+        let mut request = request_in_status(Chains::SOLANA, "2", Status::TokenMinted);
+        request.save(&db).unwrap();
+        let (token_contract, token_id) = destination_evm_token(&request).unwrap();
+        let destination = Address::from_str(EVM_ACCOUNT).unwrap();
+        complete_evm_mint(
+            &mut request,
+            &db,
+            token_contract,
+            token_id,
+            destination,
+            now,
+        )
+        .unwrap();
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::Completed);
+    }
+
+    #[test]
+    fn test_fast_path_errors_keep_the_retries() {
+        let db = test_db();
+        let (state, _, _) = test_state(db.clone());
+        let request = request_in_status(Chains::SOLANA, "1", Status::TokenReceived);
+        request.save(&db).unwrap();
+        add_pending_request(&request.id, &db).unwrap();
+
+        for message in [
+            "Transaction simulation failed: Blockhash not found",
+            "server returned an error response: error code -32000: nonce too low",
+        ] {
+            for _ in 0..MAX_SWEEP_ATTEMPTS {
+                record_retry_failure(
+                    &request.id,
+                    &BridgeError::Other(message.to_string()),
+                    &state,
+                )
+                .unwrap();
+            }
+        }
+        let pending = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(pending.status, Status::TokenReceived);
+        assert!(pending.failed_attempts.is_empty());
+        assert_eq!(
+            pending.last_error_record.unwrap().category,
+            ErrorClass::NonceConflict
+        );
+
+        record_retry_failure(
+            &request.id,
+            &BridgeError::Other("connection reset".to_string()),
+            &state,
+        )
+        .unwrap();
+        let pending = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(pending.failed_attempts.len(), 1);
+    }
+
+    #[test]
+    fn test_exhausted_retries_park_the_request() {
+        let db = test_db();
+        let (state, _, _) = test_state(db.clone());
+        let request = request_in_status(Chains::EVM, "1", Status::TokenReceived);
+        request.save(&db).unwrap();
+        add_pending_request(&request.id, &db).unwrap();
+        state
+            .evm_client
+            .event_cursor
+            .record(1_234, Timestamp::from_secs(1_700_000_000));
+
+        // An outage and an empty wallet, far longer than the retries, give nothing up
+        let transient = [
+            BridgeError::Other("connection reset".to_string()),
+            BridgeError::Timeout(TimeoutError {
+                operation: "get_transaction_count".to_string(),
+                after: Duration::from_secs(10),
+            }),
+            BridgeError::Other("insufficient lamports 100, need 5000".to_string()),
+            BridgeError::NotAuthorizedBackend("relayer".to_string()),
+        ];
+        for attempt in 0..3 * MAX_SWEEP_ATTEMPTS {
+            record_retry_failure(&request.id, &transient[attempt % 4], &state).unwrap();
+        }
+        let pending = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(pending.status, Status::TokenReceived);
+        assert_eq!(pending.cancel_reason, None);
+        assert!(!pending.refund_pending);
+        assert_eq!(pending.counted_attempts(), 0);
+        assert_eq!(pending.failed_attempts.len(), MAX_KEPT_ATTEMPTS);
+        assert!(pending.failure_report.is_none());
+        assert!(get_queue_position(&request.id, &db).unwrap().is_some());
+
+        let failing = BridgeError::Other(
+            "Transaction simulation failed: custom program error: 0x1".to_string(),
+        );
+        for _ in 0..MAX_SWEEP_ATTEMPTS - 1 {
+            record_retry_failure(&request.id, &failing, &state).unwrap();
+        }
+        let pending = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(pending.status, Status::TokenReceived);
+        assert_eq!(pending.counted_attempts(), MAX_SWEEP_ATTEMPTS - 1);
+
+        record_retry_failure(&request.id, &failing, &state).unwrap();
+        let parked = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(parked.status, Status::NeedsIntervention);
+        assert_eq!(parked.cancel_reason, None);
+        assert!(!parked.refund_pending);
+        assert!(parked.parked_by_retries());
+        assert_eq!(get_queue_position(&request.id, &db).unwrap(), None);
+        assert_eq!(
+            types::custody_exposure(&db).unwrap(),
+            types::CustodyExposure { evm: 1, solana: 0 }
+        );
+
+        let report = parked.failure_report.clone().unwrap();
+        assert_eq!(report.status, Status::TokenReceived);
+        assert_eq!(report.attempts.len(), MAX_KEPT_ATTEMPTS);
+        assert_eq!(report.attempts.last().unwrap().class, ErrorClass::Other);
+        assert_eq!(
+            report.last_simulation.as_deref(),
+            Some("Transaction simulation failed: custom program error: 0x1")
+        );
+        assert_eq!(report.tx_hashes, request.tx_hashes);
+        assert_eq!(report.cursors.evm_block, Some(1_234));
+        assert_eq!(report.cursors.solana_slot, None);
+        for action in [
+            SuggestedAction::CheckRpc,
+            SuggestedAction::Investigate,
+            SuggestedAction::ManualRefund,
+        ] {
+            assert!(report.suggested_actions.contains(&action), "{action:?}");
+        }
+
+        // Resumed in its status with fresh retries once the cause is fixed
+        let mut resumed = parked;
+        resumed.resume_parked(&db, state.clock.now()).unwrap();
+        let resumed = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(resumed.status, Status::TokenReceived);
+        assert!(resumed.failed_attempts.is_empty());
+        assert!(get_queue_position(&request.id, &db).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_mint_retries_read_the_metadata_once() {
+        let db = test_db();
+        let (mut state, _, _) = test_state(db.clone());
+        let request = request_in_status(Chains::SOLANA, "1", Status::TokenReceived);
+        request.save(&db).unwrap();
+        let mint = Pubkey::from_str(&request.input.contract_or_mint).unwrap();
+        let (address, data) = metadata_account(&mint, "https://origin.example/1.json");
+        let (rpc, calls) = accounts_mock_rpc(HashMap::from([(address, data)]));
+        state.solana_client.set_rpc(rpc);
+        // Every mint send fails, after the metadata was kept
+        state.evm_client = state
+            .evm_client
+            .with_endpoints("http://127.0.0.1:1", "ws://127.0.0.1:1");
+
+        for _ in 0..3 {
+            let request = types::request_data(&request.id, &db).unwrap().unwrap();
+            assert!(continue_from_metadata(&state, &request).await.is_err());
         }
+        assert_eq!(calls.count(RpcRequest::GetAccountInfo), 1);
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        let snapshot = stored.metadata_snapshot.unwrap();
+        assert_eq!(snapshot.origin_uri, "https://origin.example/1.json");
     }
 }
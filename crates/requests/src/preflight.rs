@@ -0,0 +1,100 @@
+use std::str::FromStr;
+
+use alloy::primitives::Address;
+use evm::{Ownership, Transferability};
+use serde::{Deserialize, Serialize};
+use types::{BridgeError, CustodyChain};
+
+use crate::{errors::RequestError, AppState};
+
+/// Checks of an EVM token before its owner approves the bridge
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PreflightReport {
+    /// The owner can transfer the token to the bridge, false for locked ERC-5192 tokens
+    /// and transfers reverting
+    pub transferable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Holder of the token and how it authorizes the owner, when the owner doesn't hold it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custody_chain: Option<CustodyChain>,
+}
+
+/// Query of the EVM preflight endpoint
+#[derive(Deserialize, Debug, Clone)]
+pub struct PreflightQuery {
+    pub owner: String,
+}
+
+/// Preflight of bridging token `token_id` of `contract` owned by `owner` from EVM
+pub async fn evm_preflight(
+    contract: &str,
+    token_id: &str,
+    owner: &str,
+    state: &AppState,
+) -> Result<PreflightReport, RequestError> {
+    Address::from_str(contract)
+        .map_err(|_| RequestError::InvalidPreflight(format!("invalid contract {contract}")))?;
+    Address::from_str(owner)
+        .map_err(|_| RequestError::InvalidPreflight(format!("invalid owner {owner}")))?;
+    let token_id = types::normalize_token_id(token_id)
+        .map_err(|e| RequestError::InvalidPreflight(e.to_string()))?;
+    let token_id = token_id.as_str();
+
+    let failed = |e: BridgeError| RequestError::PreflightFailed(e.to_string());
+    let custody_chain =
+        match evm::check_token_ownership(state.evm_client.clone(), contract, owner, token_id)
+            .await
+            .map_err(failed)?
+        {
+            Ownership::Authorized(chain) => Some(chain),
+            Ownership::Owner | Ownership::NotOwner { .. } => None,
+        };
+    // Transferred from the holder by the lock of an authorized owner
+    let from = custody_chain
+        .as_ref()
+        .map_or(owner, |chain| chain.holder.as_str());
+
+    match evm::check_token_transferable(state.evm_client.clone(), contract, from, token_id)
+        .await
+        .map_err(failed)?
+    {
+        Transferability::Transferable => Ok(PreflightReport {
+            transferable: true,
+            reason: None,
+            custody_chain,
+        }),
+        Transferability::NotTransferable(reason) => Ok(PreflightReport {
+            transferable: false,
+            reason: Some(reason),
+            custody_chain,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod preflight_test {
+    use test_support::{test_db, EVM_ACCOUNT, EVM_TOKEN_CONTRACT};
+
+    use crate::{errors::RequestError, evm_preflight, test_utils};
+
+    #[tokio::test]
+    async fn test_preflight_input_and_node_failures() {
+        let (state, _rx_evm, _rx_sol) = test_utils::test_state(test_db());
+
+        let err = evm_preflight(EVM_TOKEN_CONTRACT, "1", "owner", &state)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, RequestError::InvalidPreflight(_)), "{err}");
+        let err = evm_preflight(EVM_TOKEN_CONTRACT, "-1", EVM_ACCOUNT, &state)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, RequestError::InvalidPreflight(_)), "{err}");
+
+        // The node of the test state is not reachable
+        let err = evm_preflight(EVM_TOKEN_CONTRACT, "1", EVM_ACCOUNT, &state)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, RequestError::PreflightFailed(_)), "{err}");
+    }
+}
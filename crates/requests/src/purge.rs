@@ -0,0 +1,91 @@
+use serde::Deserialize;
+use types::{PurgeOutcome, PurgeReceipt};
+
+use crate::{errors::RequestError, is_admin, AppState};
+
+/// Body of an account purge
+#[derive(Deserialize, Debug, Clone)]
+pub struct PurgeInput {
+    /// EVM address or Solana account, as token owner or destination account
+    pub account: String,
+}
+
+/// Replaces the account in its finished requests by a salted hash, authorized by the
+/// admin token. Disabled without `PURGE_SALT`, the markers must stay the same across
+/// purges
+pub fn purge_account_data(
+    input: &PurgeInput,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<PurgeReceipt, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    let salt = state
+        .purge_salt
+        .as_ref()
+        .filter(|salt| !salt.expose().is_empty())
+        .ok_or(RequestError::PurgeDisabled())?;
+    let account = input.account.trim();
+    if account.is_empty() || types::is_purge_marker(account) {
+        return Err(RequestError::InvalidPurgeAccount(account.to_string()));
+    }
+
+    match types::purge_account(
+        &state.db,
+        account,
+        salt.expose().as_bytes(),
+        state.clock.now(),
+    )
+    .map_err(|e| RequestError::CreationError(e.to_string()))?
+    {
+        PurgeOutcome::Purged(receipt) => Ok(receipt),
+        PurgeOutcome::Blocked(requests) => Err(RequestError::PurgeBlocked(requests)),
+    }
+}
+
+#[cfg(test)]
+mod purge_test {
+    use test_support::{input_request, test_db};
+    use types::{BRequest, Chains, Status, Timestamp};
+
+    use crate::{errors::RequestError, purge_account_data, test_utils::test_state, PurgeInput};
+
+    #[test]
+    fn test_purge_authorized_and_configured() {
+        let (mut state, _rx_evm, _rx_sol) = test_state(test_db());
+        let mut request = BRequest::new(input_request(Chains::EVM, "1"), Timestamp::now());
+        request.status = Status::Completed;
+        request.save(&state.db).unwrap();
+        let input = PurgeInput {
+            account: request.input.token_owner.clone(),
+        };
+
+        assert_eq!(
+            purge_account_data(&input, Some("secret"), &state).unwrap_err(),
+            RequestError::Unauthorized()
+        );
+        state.admin_token = Some("secret".into());
+        assert_eq!(
+            purge_account_data(&input, Some("secret"), &state).unwrap_err(),
+            RequestError::PurgeDisabled()
+        );
+        state.purge_salt = Some("salt".into());
+
+        let receipt = purge_account_data(&input, Some("secret"), &state).unwrap();
+        assert_eq!(receipt.requests, vec![request.id.clone()]);
+        let purged = types::request_data(&request.id, &state.db)
+            .unwrap()
+            .unwrap();
+        assert!(types::is_purge_marker(&purged.input.token_owner));
+
+        // The marker itself can't be purged
+        let marker = PurgeInput {
+            account: purged.input.token_owner,
+        };
+        assert!(matches!(
+            purge_account_data(&marker, Some("secret"), &state).unwrap_err(),
+            RequestError::InvalidPurgeAccount(_)
+        ));
+    }
+}
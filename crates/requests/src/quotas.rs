@@ -0,0 +1,146 @@
+use serde::Serialize;
+use types::{quota_account, Clock, Lamports, QuotaLimits, QuotaUsage, Timestamp, Wei};
+
+use crate::{errors::RequestError, is_admin, AppState};
+
+/// Usage of an account against the configured limits
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct QuotaReport {
+    pub account: String,
+    pub requests: usize,
+    pub evm_fees: Wei,
+    pub solana_fees: Lamports,
+    /// Part of `solana_fees` funding the rent of the accounts created by the mints
+    pub solana_rent: Lamports,
+    pub limits: QuotaLimits,
+    /// Time the account is under every limit again, None when no limit is reached
+    pub reset_at: Option<Timestamp>,
+    pub usage: QuotaUsage,
+}
+
+fn quota_report(account: &str, state: &AppState) -> Result<QuotaReport, RequestError> {
+    let usage = types::quota_usage(&state.db, account, state.clock.now())
+        .map_err(|e| RequestError::CreationError(e.to_string()))?;
+    Ok(QuotaReport {
+        account: quota_account(account),
+        requests: usage.requests.len(),
+        evm_fees: usage.evm_fees(),
+        solana_fees: usage.solana_fees(),
+        solana_rent: usage.solana_rent(),
+        limits: state.quota_limits,
+        reset_at: usage.reset_at(&state.quota_limits),
+        usage,
+    })
+}
+
+/// Quota usage of an account, authorized by the admin token
+pub fn get_quota(
+    account: &str,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<QuotaReport, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    quota_report(account, state)
+}
+
+/// Clears the quota usage of an account, authorized by the admin token
+pub fn clear_quota(
+    account: &str,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<QuotaReport, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    types::reset_quota(&state.db, account)
+        .map_err(|e| RequestError::CreationError(e.to_string()))?;
+    quota_report(account, state)
+}
+
+#[cfg(test)]
+mod quotas_test {
+    use test_support::{input_request, request_in_status, test_db};
+    use types::{
+        record_quota_request, Chains, ClientLabels, Clock, Fee, Lamports, QuotaLimits, Status,
+        QUOTA_WINDOW,
+    };
+
+    use crate::{
+        clear_quota, endpoints::new_request, errors::RequestError, get_quota,
+        test_utils::test_state,
+    };
+
+    #[tokio::test]
+    async fn test_quota_rejects_new_requests_until_reset() {
+        let db = test_db();
+        let (mut state, _rx_evm, _rx_sol) = test_state(db.clone());
+        state.admin_token = Some("secret".into());
+        state.quota_limits = QuotaLimits {
+            max_requests: Some(1),
+            ..Default::default()
+        };
+        let now = state.clock.now();
+
+        let input = input_request(Chains::EVM, "1");
+        record_quota_request(&db, &input, now).unwrap();
+        let account = input.destination_account.clone();
+        assert_eq!(
+            new_request(
+                input_request(Chains::EVM, "2"),
+                None,
+                ClientLabels::default(),
+                state.clone(),
+            )
+            .await
+            .unwrap_err(),
+            RequestError::QuotaExceeded(
+                account.clone(),
+                now.saturating_add(QUOTA_WINDOW).as_secs()
+            )
+        );
+
+        assert_eq!(
+            get_quota(&account, Some("wrong"), &state).unwrap_err(),
+            RequestError::Unauthorized()
+        );
+        let report = get_quota(&account, Some("secret"), &state).unwrap();
+        assert_eq!(report.requests, 1);
+        assert_eq!(report.reset_at, Some(now.saturating_add(QUOTA_WINDOW)));
+
+        let report = clear_quota(&account, Some("secret"), &state).unwrap();
+        assert_eq!(report.requests, 0);
+        assert_eq!(report.reset_at, None);
+        // The token owner was counted too
+        let owner = get_quota(&input.token_owner, Some("secret"), &state).unwrap();
+        assert_eq!(owner.requests, 1);
+    }
+
+    #[test]
+    fn test_report_counts_the_rent_of_the_mints() {
+        let db = test_db();
+        let (mut state, _rx_evm, _rx_sol) = test_state(db.clone());
+        state.admin_token = Some("secret".into());
+        let now = state.clock.now();
+
+        let mut request = request_in_status(Chains::EVM, "1", Status::TokenMinted);
+        request.save(&db).unwrap();
+        request
+            .record_fee(&db, "lock", Fee::SOLANA(Lamports::new(5_000)), now)
+            .unwrap();
+        request
+            .record_fee_with_rent(
+                &db,
+                "mint",
+                Fee::SOLANA(Lamports::new(2_044_280)),
+                Some(Lamports::new(2_039_280)),
+                now,
+            )
+            .unwrap();
+
+        let report = get_quota(&request.input.destination_account, Some("secret"), &state).unwrap();
+        assert_eq!(report.solana_fees, Lamports::new(2_049_280));
+        assert_eq!(report.solana_rent, Lamports::new(2_039_280));
+    }
+}
@@ -0,0 +1,513 @@
+use std::{str::FromStr, time::Duration};
+
+use alloy::primitives::{Address, PrimitiveSignature};
+use log::{error, info};
+use serde::Deserialize;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use storage::db::Database;
+use types::{
+    redact_urls, BRequest, BridgeError, CancelReason, Chains, ErrorClass, MessageMint, RequestId,
+    Result, Secret, Status, Timestamp, TxMessage,
+};
+
+use crate::{check_destination_account, errors::RequestError, record_api_error, AppState};
+
+/// A TokenReceived request without a mint for this long is considered stuck
+pub const REDIRECT_STUCK_AGE: Duration = Duration::from_secs(1800);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RedirectMintInput {
+    pub destination_account: String,
+    /// Signature of `redirect_message` by the origin token owner, hex for EVM and
+    /// base58 for Solana
+    pub owner_signature: Option<String>,
+}
+
+/// Message the token owner signs to prove the redirect
+pub fn redirect_message(request_id: &str, destination_account: &str) -> String {
+    format!("Redirect mint of request {request_id} to {destination_account}")
+}
+
+/// Checks an ownership proof, EIP-191 signatures for EVM owners and ed25519 for Solana
+pub fn verify_owner_signature(chain: &Chains, owner: &str, message: &str, signature: &str) -> bool {
+    match chain {
+        Chains::EVM => {
+            let (Ok(owner), Ok(signature)) = (
+                Address::from_str(owner),
+                PrimitiveSignature::from_str(signature),
+            ) else {
+                return false;
+            };
+            signature
+                .recover_address_from_msg(message.as_bytes())
+                .is_ok_and(|signer| signer == owner)
+        }
+        Chains::SOLANA => {
+            let (Ok(owner), Ok(signature)) =
+                (Pubkey::from_str(owner), Signature::from_str(signature))
+            else {
+                return false;
+            };
+            signature.verify(owner.as_ref(), message.as_bytes())
+        }
+    }
+}
+
+/// Whether `admin_token` matches the configured one, never when none is configured. The
+/// tokens are compared in constant time
+pub fn is_admin(
+    admin_token: Option<&str>,
+    configured_admin_token: Option<&Secret<String>>,
+) -> bool {
+    match (admin_token, configured_admin_token) {
+        (Some(token), Some(configured)) => {
+            !configured.expose().is_empty() && configured.matches(token)
+        }
+        _ => false,
+    }
+}
+
+/// Allows the admin token or a signature of the origin token `owner`
+pub fn authorize_redirect(
+    request: &BRequest,
+    input: &RedirectMintInput,
+    admin_token: Option<&str>,
+    configured_admin_token: Option<&Secret<String>>,
+    owner: Option<&str>,
+) -> Result<(), RequestError> {
+    if is_admin(admin_token, configured_admin_token) {
+        return Ok(());
+    }
+
+    if let (Some(signature), Some(owner)) = (&input.owner_signature, owner) {
+        let message = redirect_message(&request.id, &input.destination_account);
+        if verify_owner_signature(&request.input.origin_network, owner, &message, signature) {
+            return Ok(());
+        }
+    }
+
+    Err(RequestError::Unauthorized())
+}
+
+/// Only mints that never landed can be redirected, requests stuck in TokenReceived or
+/// canceled because their data was unusable. A canceled request needs the origin token in
+/// custody, its cancel queued the refund of the token
+pub fn ensure_redirectable(
+    request: &BRequest,
+    now: Timestamp,
+    destination_minted: bool,
+) -> Result<(), RequestError> {
+    let stuck = match request.status {
+        Status::TokenReceived => request.last_update.elapsed_until(now) >= REDIRECT_STUCK_AGE,
+        Status::Canceled => matches!(request.cancel_reason, Some(CancelReason::DataCorrupted(_))),
+        _ => false,
+    };
+    if !stuck {
+        return Err(RequestError::RedirectNotAllowed(format!(
+            "request in status {:?} is not a failed mint",
+            request.status
+        )));
+    }
+    // Canceled before the custody was established, a mint would not be backed by a lock
+    if request.status == Status::Canceled && !request.refund_pending {
+        return Err(RequestError::RedirectNotAllowed(
+            "the origin token is not in custody".to_string(),
+        ));
+    }
+
+    if destination_minted || !request.output.detination_contract_id_or_mint.is_empty() {
+        return Err(RequestError::RedirectNotAllowed(
+            "the destination token was already minted".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Points the request to the new destination and moves it back to TokenReceived,
+/// previous tx hashes are kept and the redirect is recorded in the history. The mint is
+/// left to the caller, the request is not queued for the sweep
+pub fn apply_redirect(
+    request: &mut BRequest,
+    destination_account: &str,
+    now: Timestamp,
+    db: &Database,
+) -> Result<()> {
+    let previous = std::mem::replace(
+        &mut request.input.destination_account,
+        destination_account.to_string(),
+    );
+    request.status = Status::TokenReceived;
+    request.cancel_reason = None;
+    request.last_error = None;
+    request.last_error_context = None;
+    request.output = Default::default();
+    request.last_update = now;
+    if request.refund_pending {
+        request.abandon_refund(db, "the mint was redirected", now)?;
+    }
+    request.record_event(
+        &format!("Mint redirected from {previous} to {destination_account}"),
+        db,
+        now,
+    )?;
+    Ok(())
+}
+
+pub async fn redirect_mint(
+    request_id: &RequestId,
+    input: RedirectMintInput,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<BRequest, RequestError> {
+    // Held until the mint is queued, the sweep can't act on the request meanwhile
+    let Some(_lock) = state.request_locks.try_lock(request_id) else {
+        return Err(RequestError::RedirectNotAllowed(
+            "request is being processed".to_string(),
+        ));
+    };
+    let mut request = match types::request_data(request_id, &state.db) {
+        Ok(Some(request)) => request,
+        _ => return Err(RequestError::NoExistingRequest(request_id.to_string())),
+    };
+
+    check_destination_account(&request.input.origin_network, &input.destination_account)?;
+
+    let owner = match (&input.owner_signature, &request.input.origin_network) {
+        (None, _) => None,
+        (Some(_), Chains::EVM) => Some(request.input.token_owner.clone()),
+        // Solana requests store the token account, the signer is its owner wallet
+        (Some(_), Chains::SOLANA) => {
+            solana::token_account_owner(&state.solana_client, &request.input.token_owner)
+                .await
+                .map(|owner| owner.to_string())
+                .ok()
+        }
+    };
+    authorize_redirect(
+        &request,
+        &input,
+        admin_token,
+        state.admin_token.as_ref(),
+        owner.as_deref(),
+    )?;
+
+    if state.in_flight.is_in_flight(&request.id) {
+        return Err(RequestError::RedirectNotAllowed(
+            "a mint is in flight".to_string(),
+        ));
+    }
+
+    // Re-verify on chain, a mint may have landed without being recorded
+    let destination_minted = match request.input.origin_network {
+        Chains::EVM => solana::destination_minted(
+            &state.solana_client,
+            &request.input.contract_or_mint,
+            &request.input.token_id,
+        )
+        .await
+        .map_err(|e| RequestError::RedirectNotAllowed(redact_urls(&e.to_string())))?,
+        Chains::SOLANA => evm::destination_minted(state.evm_client.clone(), &request)
+            .await
+            .map_err(|e| RequestError::RedirectNotAllowed(redact_urls(&e.to_string())))?,
+    };
+    let now = state.clock.now();
+    ensure_redirectable(&request, now, destination_minted)?;
+
+    let metadata = origin_metadata(state, &request)
+        .await
+        .map_err(|e| RequestError::RedirectNotAllowed(redact_urls(&e.to_string())))?;
+
+    apply_redirect(&mut request, &input.destination_account, now, &state.db)
+        .map_err(|e| RequestError::CreationError(e.to_string()))?;
+    info!(
+        "Request {} mint redirected to {}",
+        request.id, input.destination_account
+    );
+
+    enqueue_mint(state, &request, metadata).await?;
+    Ok(request)
+}
+
+async fn origin_metadata(state: &AppState, request: &BRequest) -> Result<String> {
+    match request.input.origin_network {
+        Chains::EVM => {
+            let token_contract = Address::from_str(&request.input.contract_or_mint)
+                .map_err(BridgeError::from_error)?;
+            let token_id =
+                types::parse_token_id(&request.input.token_id).map_err(BridgeError::from)?;
+            evm::get_token_metadata(state.evm_client.clone(), token_contract, token_id).await
+        }
+        Chains::SOLANA => {
+            solana::get_metadata(&state.solana_client, &request.input.contract_or_mint).await
+        }
+    }
+}
+
+/// Sends the Mint message to the processor of the destination chain, ahead of its
+/// queued messages
+pub async fn enqueue_mint(
+    state: &AppState,
+    request: &BRequest,
+    token_metadata: String,
+) -> Result<(), RequestError> {
+    let message = TxMessage::Mint(MessageMint {
+        request_id: request.id.clone(),
+        token_metadata,
+        trace_context: request.trace_context.clone(),
+    });
+
+    let sent = match request.input.origin_network {
+        Chains::EVM => state.evm_client.tx_channel.send_priority(message).await,
+        Chains::SOLANA => state.solana_client.tx_channel.send_priority(message).await,
+    };
+    sent.map_err(|e| {
+        error!("Could not enqueue mint of request {}: {}", request.id, e);
+        let message = format!("Could not enqueue the mint: {e}");
+        record_api_error(&request.id, ErrorClass::Other, &message, state);
+        RequestError::CreationError(e.to_string())
+    })
+}
+
+#[cfg(test)]
+mod redirect_test {
+    use std::time::Duration;
+
+    use alloy::signers::{local::PrivateKeySigner, SignerSync};
+    use solana_sdk::{signature::Keypair, signer::Signer};
+    use test_support::test_db;
+    use types::{
+        BRequest, CancelReason, Chains, InputRequest, MessageMint, Secret, Status, Timestamp,
+        TxMessage,
+    };
+
+    use crate::{
+        apply_redirect, authorize_redirect, enqueue_mint, ensure_redirectable,
+        errors::RequestError, get_pending_requests, is_admin, redirect_message, redirect_mint,
+        test_utils::test_state, RedirectMintInput, REDIRECT_STUCK_AGE,
+    };
+
+    fn request(origin_network: Chains, token_owner: &str) -> BRequest {
+        BRequest::new(
+            InputRequest {
+                contract_or_mint: "0x5fbdb2315678afecb367f032d93f642f64180aa3".to_string(),
+                token_id: "7".to_string(),
+                token_owner: token_owner.to_string(),
+                origin_network,
+                destination_account: "old-destination".to_string(),
+            },
+            Timestamp::now(),
+        )
+    }
+
+    fn input(signature: Option<String>) -> RedirectMintInput {
+        RedirectMintInput {
+            destination_account: "new-destination".to_string(),
+            owner_signature: signature,
+        }
+    }
+
+    #[test]
+    fn test_admin_token_comparison() {
+        let configured = Secret::from("secret");
+        assert!(is_admin(Some("secret"), Some(&configured)));
+        for token in ["secreT", "secret ", "secre", ""] {
+            assert!(!is_admin(Some(token), Some(&configured)), "{token:?}");
+        }
+        assert!(!is_admin(None, Some(&configured)));
+        assert!(!is_admin(Some("secret"), None));
+    }
+
+    #[test]
+    fn test_redirect_authorization() {
+        let evm_owner = PrivateKeySigner::random();
+        let request = request(Chains::EVM, &evm_owner.address().to_string());
+        let message = redirect_message(&request.id, "new-destination");
+
+        // Admin token
+        let configured = Secret::from("secret");
+        assert!(authorize_redirect(
+            &request,
+            &input(None),
+            Some("secret"),
+            Some(&configured),
+            None
+        )
+        .is_ok());
+        assert_eq!(
+            authorize_redirect(
+                &request,
+                &input(None),
+                Some("wrong"),
+                Some(&configured),
+                None
+            ),
+            Err(RequestError::Unauthorized())
+        );
+        // No admin token configured
+        let empty = Secret::from("");
+        assert!(authorize_redirect(&request, &input(None), Some(""), Some(&empty), None).is_err());
+
+        // EVM owner signature
+        let signature = evm_owner.sign_message_sync(message.as_bytes()).unwrap();
+        let owner = request.input.token_owner.clone();
+        let signed = input(Some(signature.to_string()));
+        assert!(authorize_redirect(&request, &signed, None, None, Some(&owner)).is_ok());
+
+        // Signed by someone else or for another destination
+        let other = PrivateKeySigner::random()
+            .sign_message_sync(message.as_bytes())
+            .unwrap();
+        assert!(authorize_redirect(
+            &request,
+            &input(Some(other.to_string())),
+            None,
+            None,
+            Some(&owner)
+        )
+        .is_err());
+        let mut other_destination = signed.clone();
+        other_destination.destination_account = "attacker".to_string();
+        assert!(
+            authorize_redirect(&request, &other_destination, None, None, Some(&owner)).is_err()
+        );
+
+        // Solana owner signature
+        let solana_owner = Keypair::new();
+        let request = self::request(Chains::SOLANA, "token-account");
+        let message = redirect_message(&request.id, "new-destination");
+        let signature = solana_owner.sign_message(message.as_bytes());
+        let owner = solana_owner.pubkey().to_string();
+        assert!(authorize_redirect(
+            &request,
+            &input(Some(signature.to_string())),
+            None,
+            None,
+            Some(&owner)
+        )
+        .is_ok());
+        assert!(authorize_redirect(
+            &request,
+            &input(Some(signature.to_string())),
+            None,
+            None,
+            Some(&Keypair::new().pubkey().to_string())
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_only_failed_mints_are_redirectable() {
+        let mut request = request(Chains::EVM, "0xowner");
+        let now = request.last_update.saturating_add(REDIRECT_STUCK_AGE);
+
+        // Still waiting for the event listener
+        assert!(ensure_redirectable(&request, now, false).is_err());
+
+        request.status = Status::TokenReceived;
+        assert!(
+            ensure_redirectable(&request, now.saturating_sub(Duration::from_secs(1)), false)
+                .is_err()
+        );
+        // Clock stepped back before the last update
+        assert!(ensure_redirectable(&request, Timestamp::default(), false).is_err());
+        assert!(ensure_redirectable(&request, now, false).is_ok());
+
+        // The original mint landed
+        assert!(matches!(
+            ensure_redirectable(&request, now, true),
+            Err(RequestError::RedirectNotAllowed(_))
+        ));
+        request.output.detination_contract_id_or_mint = "mint".to_string();
+        assert!(ensure_redirectable(&request, now, false).is_err());
+        request.output = Default::default();
+
+        request.status = Status::Canceled;
+        assert!(ensure_redirectable(&request, now, false).is_err());
+        request.cancel_reason = Some(CancelReason::DataCorrupted("destination".to_string()));
+        // Canceled before the token reached custody
+        assert_eq!(
+            ensure_redirectable(&request, now, false),
+            Err(RequestError::RedirectNotAllowed(
+                "the origin token is not in custody".to_string()
+            ))
+        );
+        request.refund_pending = true;
+        assert!(ensure_redirectable(&request, now, false).is_ok());
+
+        request.status = Status::Completed;
+        assert!(ensure_redirectable(&request, now, false).is_err());
+    }
+
+    #[test]
+    fn test_apply_redirect() {
+        let db = test_db();
+        let mut request = request(Chains::EVM, "0xowner");
+        request.tx_hashes = vec!["lock".to_string(), "failed-mint".to_string()];
+        request.status = Status::Canceled;
+        request.cancel_reason = Some(CancelReason::DataCorrupted("destination".to_string()));
+        request.save(&db).unwrap();
+
+        apply_redirect(&mut request, "new-destination", Timestamp::now(), &db).unwrap();
+
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::TokenReceived);
+        assert_eq!(stored.input.destination_account, "new-destination");
+        assert_eq!(stored.cancel_reason, None);
+        assert_eq!(stored.tx_hashes.len(), 2);
+        assert!(stored
+            .history
+            .last()
+            .unwrap()
+            .event
+            .contains("old-destination"));
+        // The redirect sends its own mint, the sweep doesn't send another one
+        assert!(get_pending_requests(&db).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_no_redirect_while_the_request_is_processed() {
+        let db = test_db();
+        let (state, _evm_processor, _solana_processor) = test_state(db.clone());
+        let request = request(Chains::EVM, "0xowner");
+        request.save(&db).unwrap();
+
+        let _lock = state.request_locks.try_lock(&request.id).unwrap();
+        assert_eq!(
+            redirect_mint(&request.id, input(None), None, &state)
+                .await
+                .unwrap_err(),
+            RequestError::RedirectNotAllowed("request is being processed".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redirect_enqueues_mint_on_destination_chain() {
+        let db = test_db();
+        let (state, _evm_processor, mut solana_processor) = test_state(db.clone());
+
+        let queued = self::request(Chains::EVM, "0xqueued");
+        let message = |request: &BRequest| {
+            TxMessage::Mint(MessageMint {
+                request_id: request.id.clone(),
+                token_metadata: "https://example.com/7.json".to_string(),
+                trace_context: None,
+            })
+        };
+        state
+            .evm_client
+            .tx_channel
+            .send(message(&queued))
+            .await
+            .unwrap();
+
+        let request = request(Chains::EVM, "0xowner");
+        enqueue_mint(&state, &request, "https://example.com/7.json".to_string())
+            .await
+            .unwrap();
+
+        // EVM tokens are minted by the Solana processor, the redirect ahead of the queue
+        assert_eq!(solana_processor.recv().await.unwrap(), message(&request));
+        assert_eq!(solana_processor.recv().await.unwrap(), message(&queued));
+        assert_eq!(state.channel_metrics.depth(&Chains::EVM), 0);
+    }
+}
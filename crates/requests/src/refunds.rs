@@ -0,0 +1,208 @@
+use log::{error, info};
+use types::{
+    pending_refunds, BRequest, BridgeError, Chains, MessageRefund, RequestId, Result, TxMessage,
+};
+
+use crate::{errors::RequestError, is_admin, AppState};
+
+/// Whether the destination token of the request was minted, its origin token then
+/// backs the minted one and must not be returned
+async fn destination_minted(state: &AppState, request: &BRequest) -> Result<bool> {
+    match request.input.origin_network {
+        Chains::EVM => {
+            solana::destination_minted(
+                &state.solana_client,
+                &request.input.contract_or_mint,
+                &request.input.token_id,
+            )
+            .await
+        }
+        Chains::SOLANA => {
+            let evm_client = state.evm_client.for_request(request)?;
+            for tx in request.tx_hashes.iter().filter(|tx| tx.starts_with("0x")) {
+                if evm::get_minted_token(evm_client.clone(), tx, &request.id)
+                    .await?
+                    .is_some()
+                {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+    }
+}
+
+/// Sends the Refund message to the processor of the origin chain, once the destination
+/// is verified not minted
+pub async fn enqueue_refund(state: &AppState, request: &mut BRequest) -> Result<()> {
+    if destination_minted(state, request).await? {
+        info!(
+            "Destination token of request {} minted, dropping its refund",
+            request.id
+        );
+        return request.abandon_refund(
+            &state.db,
+            "the destination token was minted",
+            state.clock.now(),
+        );
+    }
+
+    let message = TxMessage::Refund(MessageRefund {
+        request_id: request.id.clone(),
+        trace_context: request.trace_context.clone(),
+    });
+    let sent = match request.input.origin_network {
+        Chains::EVM => state.solana_client.tx_channel.send(message).await,
+        Chains::SOLANA => state.evm_client.tx_channel.send(message).await,
+    };
+    sent.map_err(|e| BridgeError::Other(format!("Could not enqueue refund: {e}")))
+}
+
+/// Enqueues the refunds of the canceled requests still holding their token
+pub async fn process_pending_refunds(state: &AppState) {
+    let refunds = match pending_refunds(&state.db) {
+        Ok(refunds) => refunds,
+        Err(e) => {
+            error!("Could not read pending refunds: {e}");
+            return;
+        }
+    };
+    for request_id in refunds {
+        if state.in_flight.is_in_flight(&request_id) {
+            continue;
+        }
+        let Ok(Some(mut request)) = types::request_data(&request_id, &state.db) else {
+            continue;
+        };
+        if !request.refundable() {
+            continue;
+        }
+        if let Err(e) = enqueue_refund(state, &mut request).await {
+            error!("Refund of request {request_id} not enqueued: {e}");
+        }
+    }
+}
+
+/// Queues the refund of a canceled request with its token in custody or of an unclaimed
+/// orphan, authorized by the admin token
+pub async fn refund_request(
+    request_id: &RequestId,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<BRequest, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    let mut request = match types::request_data(request_id, &state.db) {
+        Ok(Some(request)) => request,
+        _ => return Err(RequestError::NoExistingRequest(request_id.to_string())),
+    };
+    if !request.refundable() {
+        return Err(RequestError::RefundNotAllowed(format!(
+            "request in status {:?} doesn't hold a token to refund",
+            request.status
+        )));
+    }
+    if state.in_flight.is_in_flight(&request.id) {
+        return Err(RequestError::RefundNotAllowed(
+            "a transaction is in flight".to_string(),
+        ));
+    }
+
+    request
+        .queue_refund(&state.db, state.clock.now())
+        .map_err(|e| RequestError::CreationError(e.to_string()))?;
+    enqueue_refund(state, &mut request)
+        .await
+        .map_err(|e| RequestError::RefundNotAllowed(e.to_string()))?;
+    if !request.refund_pending {
+        return Err(RequestError::RefundNotAllowed(
+            "the destination token was already minted".to_string(),
+        ));
+    }
+    info!("Refund of request {} queued", request.id);
+    Ok(request)
+}
+
+#[cfg(test)]
+mod refunds_test {
+    use std::time::Duration;
+
+    use solana_client::rpc_request::RpcRequest;
+    use test_support::{missing_account_result, mock_rpc, request_in_status, test_db};
+    use types::{pending_refunds, Chains, MessageRefund, Status, Timestamp, TxMessage};
+
+    use crate::{
+        errors::RequestError, process_pending_refunds, refund_request, test_utils::test_state,
+    };
+
+    #[tokio::test]
+    async fn test_cancel_with_custody_triggers_refund() {
+        let db = test_db();
+        let (mut state, mut rx_evm, _rx_sol) = test_state(db.clone());
+        // The destination mint doesn't exist
+        state.solana_client.set_rpc(mock_rpc([(
+            RpcRequest::GetAccountInfo,
+            missing_account_result(),
+        )]));
+
+        let mut request = request_in_status(Chains::EVM, "1", Status::TokenReceived);
+        request.save(&db).unwrap();
+        request.cancel(&db, Timestamp::now()).unwrap();
+        assert_eq!(pending_refunds(&db).unwrap(), vec![request.id.clone()]);
+
+        process_pending_refunds(&state).await;
+        // EVM origin tokens are returned by the EVM processor
+        let message = tokio::time::timeout(Duration::from_secs(5), rx_evm.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            message,
+            TxMessage::Refund(MessageRefund {
+                request_id: request.id.clone(),
+                trace_context: None,
+            })
+        );
+
+        let mut refunded = types::request_data(&request.id, &db).unwrap().unwrap();
+        refunded
+            .complete_refund(&db, "0xrefund", Timestamp::now())
+            .unwrap();
+        assert_eq!(refunded.status, Status::Refunded);
+        assert!(pending_refunds(&db).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_manual_refund_requires_custody() {
+        let db = test_db();
+        let (mut state, _rx_evm, _rx_sol) = test_state(db.clone());
+        state.admin_token = Some("secret".into());
+        state.solana_client.set_rpc(mock_rpc([(
+            RpcRequest::GetAccountInfo,
+            missing_account_result(),
+        )]));
+
+        let orphan = request_in_status(Chains::EVM, "1", Status::RefundEligible);
+        orphan.save(&db).unwrap();
+        assert_eq!(
+            refund_request(&orphan.id, None, &state).await.unwrap_err(),
+            RequestError::Unauthorized()
+        );
+        let queued = refund_request(&orphan.id, Some("secret"), &state)
+            .await
+            .unwrap();
+        assert!(queued.refund_pending);
+        assert_eq!(pending_refunds(&db).unwrap(), vec![orphan.id.clone()]);
+
+        // Canceled before the lock, the token never left its owner
+        for (token_id, status) in [("2", Status::Canceled), ("3", Status::Completed)] {
+            let request = request_in_status(Chains::EVM, token_id, status);
+            request.save(&db).unwrap();
+            assert!(matches!(
+                refund_request(&request.id, Some("secret"), &state).await,
+                Err(RequestError::RefundNotAllowed(_))
+            ));
+        }
+    }
+}
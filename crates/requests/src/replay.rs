@@ -0,0 +1,124 @@
+use log::info;
+use storage::db::Database;
+use types::{BridgeError, Chains, ReplayEntry, ReplayInput, Result};
+
+/// Runs a captured input of a request again without the chains, through the same
+/// decisions as the live run: the custody finality checks and the TokenMinted logs. The
+/// other inputs only explain the decisions around them, they are skipped
+pub fn replay_input(db: &Database, request_id: &str, entry: &ReplayEntry) -> Result<()> {
+    if entry.truncated {
+        return Err(BridgeError::Other(format!(
+            "Input of request {request_id} at {} was truncated, it can't be replayed",
+            entry.at.as_millis()
+        )));
+    }
+    match &entry.input {
+        ReplayInput::ChainRead {
+            chain: Chains::SOLANA,
+            operation,
+            ..
+        } if operation == solana::FINALITY_READ => {
+            solana::replay_finality(db, request_id, entry.input.value()?, entry.at)?;
+        }
+        ReplayInput::Event {
+            chain: Chains::EVM,
+            name,
+            ..
+        } if name == evm::TOKEN_MINTED_EVENT => {
+            evm::replay_token_minted(db, request_id, &entry.input.value()?, entry.at)?;
+        }
+        input => info!("Skipping replay input of request {request_id}: {input:?}"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod replay_test {
+    use std::time::Duration;
+
+    use solana_client::rpc_request::RpcRequest;
+    use test_support::{
+        mock_rpc, replay_bundle, request_in_status, signature_status_result, test_db,
+    };
+    use types::{
+        export_replay_bundle, set_replay_capture, Chains, FinalityPending, FinalityStep,
+        ReplayBundle, Status, Timestamp,
+    };
+
+    use crate::{replay_input, test_utils::test_state};
+
+    /// Bundle as written by `bridge_relayer replay` and read back
+    fn exported(db: &storage::db::Database, request_id: &str) -> ReplayBundle {
+        let bundle = export_replay_bundle(db, request_id).unwrap().unwrap();
+        serde_json::from_str(&serde_json::to_string_pretty(&bundle).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_captured_run_replayed() {
+        set_replay_capture(true);
+        let db = test_db();
+        let (mut state, _rx_evm, _rx_sol) = test_state(db.clone());
+
+        // Custody seen confirmed, then finalized
+        let mut custody = request_in_status(Chains::SOLANA, "1", Status::TokenReceived);
+        custody.custody_signature = custody.tx_hashes.first().cloned();
+        custody.finality_pending = Some(FinalityPending {
+            signature: custody.tx_hashes[0].clone(),
+            token_metadata: Some("https://example.com/1.json".to_string()),
+            observed_at: Timestamp::now(),
+        });
+        custody.save(&db).unwrap();
+        for (confirmation, expected) in [
+            ("confirmed", FinalityStep::Wait),
+            ("finalized", FinalityStep::Finalized),
+        ] {
+            state.solana_client.set_rpc(mock_rpc([(
+                RpcRequest::GetSignatureStatuses,
+                signature_status_result(Some(confirmation)),
+            )]));
+            let now = Timestamp::now().saturating_add(Duration::from_secs(5));
+            let step = solana::check_finality(&db, &state.solana_client, &custody.id, now)
+                .await
+                .unwrap();
+            assert_eq!(step, expected);
+        }
+
+        // Minted with another token than predicted
+        let minted = request_in_status(Chains::SOLANA, "2", Status::TokenMinted);
+        minted.save(&db).unwrap();
+        evm::handle_token_minted(
+            &db,
+            &minted.id,
+            evm::TokenMintedInput {
+                token_contract: "0x5FbDB2315678afecb367f032d93F642f64180aa3".to_string(),
+                token_id: "77".to_string(),
+                to: None,
+                log: "block 10 log 1".to_string(),
+            },
+            Timestamp::now(),
+        )
+        .unwrap();
+
+        for (request_id, entries, status) in [
+            (&custody.id, 2, Status::TokenReceived),
+            (&minted.id, 1, Status::Completed),
+        ] {
+            let bundle = exported(&db, request_id);
+            assert_eq!(bundle.entries.len(), entries);
+            let expected = bundle.expected.clone().unwrap();
+            assert_eq!(expected.status, status);
+
+            let replayed =
+                replay_bundle(&bundle, |db, entry| replay_input(db, request_id, entry)).unwrap();
+            assert_eq!(replayed, expected);
+        }
+
+        // The token URI of the snapshot was redacted
+        let bundle = exported(&db, &custody.id);
+        let pending = bundle.initial.finality_pending.unwrap();
+        assert_eq!(
+            pending.token_metadata.as_deref(),
+            Some("https://example.com/[REDACTED]")
+        );
+    }
+}
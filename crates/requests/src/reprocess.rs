@@ -0,0 +1,234 @@
+use std::{future::Future, time::Duration};
+
+use log::{error, info};
+use serde::Serialize;
+use types::{BRequest, BridgeError, ErrorClass, RequestId, Result, Status};
+
+use crate::{errors::RequestError, is_admin, record_api_error, sweep_request, AppState};
+
+/// Time a manual reprocess can take before the handler gives up
+pub const REPROCESS_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReprocessResult {
+    pub request_id: RequestId,
+    /// Status after the sweep step
+    pub status: Status,
+    pub error: Option<String>,
+}
+
+/// Runs the pending sweep step of one request now, authorized by the admin token
+pub async fn reprocess_request(
+    request_id: &RequestId,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<ReprocessResult, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    reprocess_with(request_id, state, REPROCESS_TIMEOUT, |request| {
+        sweep_request(request, state)
+    })
+    .await
+}
+
+/// Runs `sweep` on the request holding its lock. Terminal requests and orphans are
+/// refused, requests parked after their retries are resumed first. Sweep errors are
+/// returned in the result
+pub async fn reprocess_with<F, Fut>(
+    request_id: &RequestId,
+    state: &AppState,
+    timeout: Duration,
+    sweep: F,
+) -> Result<ReprocessResult, RequestError>
+where
+    F: FnOnce(BRequest) -> Fut,
+    Fut: Future<Output = Result<(), BridgeError>>,
+{
+    let mut request = match types::request_data(request_id, &state.db) {
+        Ok(Some(request)) => request,
+        _ => return Err(RequestError::NoExistingRequest(request_id.to_string())),
+    };
+    let parked = request.parked_by_retries();
+    match request.status {
+        Status::Initializing
+        | Status::RequestReceived
+        | Status::TokenReceived
+        | Status::TokenMinted
+        | Status::AwaitingDestinationAccount => {}
+        // Given up after its retries, resumed in the status it failed in
+        Status::NeedsIntervention if parked => {}
+        Status::Completed
+        | Status::Canceled
+        | Status::NeedsDestination
+        | Status::RefundEligible
+        | Status::Refunded
+        | Status::NeedsIntervention => {
+            return Err(RequestError::ReprocessNotAllowed(format!(
+                "request in status {:?} is not pending",
+                request.status
+            )))
+        }
+    }
+
+    let Some(_lock) = state.request_locks.try_lock(request_id) else {
+        return Err(RequestError::ReprocessNotAllowed(
+            "request is already being processed".to_string(),
+        ));
+    };
+    if parked {
+        request
+            .resume_parked(&state.db, state.clock.now())
+            .map_err(|e| RequestError::CreationError(e.to_string()))?;
+    }
+    info!("Reprocessing request {request_id}");
+    let error = match tokio::time::timeout(timeout, sweep(request)).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => {
+            let message = format!("Timed out after {}s", timeout.as_secs());
+            record_api_error(request_id, ErrorClass::Timeout, &message, state);
+            Some(message)
+        }
+    };
+    if let Some(e) = &error {
+        error!("Reprocessing request {request_id} failed: {e}");
+    }
+
+    let status = match types::request_data(request_id, &state.db) {
+        Ok(Some(request)) => request.status,
+        _ => return Err(RequestError::NoExistingRequest(request_id.to_string())),
+    };
+    Ok(ReprocessResult {
+        request_id: request_id.clone(),
+        status,
+        error,
+    })
+}
+
+#[cfg(test)]
+mod reprocess_test {
+    use std::time::Duration;
+
+    use storage::db::Database;
+    use test_support::{request_in_status, test_db};
+    use types::{BRequest, BridgeError, Chains, ErrorClass, ErrorComponent, Status};
+
+    use crate::{
+        errors::RequestError, reprocess_request, reprocess_with, test_utils::test_state,
+        ReprocessResult,
+    };
+
+    fn stored_request(token_id: &str, status: Status, db: &Database) -> BRequest {
+        let request = request_in_status(Chains::EVM, token_id, status);
+        request.save(db).unwrap();
+        request
+    }
+
+    #[tokio::test]
+    async fn test_token_received_request_advanced() {
+        let db = test_db();
+        let (state, _rx_evm, _rx_sol) = test_state(db.clone());
+        let request = stored_request("1", Status::TokenReceived, &db);
+
+        // Mocked sweep step minting the token
+        let result = reprocess_with(
+            &request.id,
+            &state,
+            Duration::from_secs(1),
+            |mut request| {
+                let db = db.clone();
+                async move {
+                    request.status = Status::TokenMinted;
+                    request
+                        .save(&db)
+                        .map_err(|e| BridgeError::Other(e.to_string()))
+                }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            result,
+            ReprocessResult {
+                request_id: request.id.clone(),
+                status: Status::TokenMinted,
+                error: None,
+            }
+        );
+        assert!(!state.request_locks.is_locked(&request.id));
+
+        // Errors of the step are returned with the unchanged status
+        let result = reprocess_with(&request.id, &state, Duration::from_secs(1), |_| async {
+            Err(BridgeError::Other("rpc unavailable".to_string()))
+        })
+        .await
+        .unwrap();
+        assert_eq!(result.status, Status::TokenMinted);
+        assert_eq!(result.error.as_deref(), Some("rpc unavailable"));
+
+        // Not while the sweeper holds the request
+        let _lock = state.request_locks.try_lock(&request.id).unwrap();
+        assert!(matches!(
+            reprocess_with(&request.id, &state, Duration::from_secs(1), |_| async {
+                Ok(())
+            })
+            .await,
+            Err(RequestError::ReprocessNotAllowed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_reprocess_recorded() {
+        let db = test_db();
+        let (state, _rx_evm, _rx_sol) = test_state(db.clone());
+        let request = stored_request("1", Status::TokenReceived, &db);
+
+        let result = reprocess_with(&request.id, &state, Duration::from_millis(10), |_| async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(result.error.as_deref(), Some("Timed out after 0s"));
+
+        let record = types::request_data(&request.id, &db)
+            .unwrap()
+            .unwrap()
+            .last_error_record
+            .unwrap();
+        assert_eq!(record.component, ErrorComponent::Api);
+        assert_eq!(record.category, ErrorClass::Timeout);
+        assert_eq!(record.attempt, 1);
+    }
+
+    #[tokio::test]
+    async fn test_terminal_request_refused() {
+        let db = test_db();
+        let (mut state, _rx_evm, _rx_sol) = test_state(db.clone());
+        state.admin_token = Some("secret".into());
+
+        let pending = stored_request("1", Status::TokenReceived, &db);
+        assert_eq!(
+            reprocess_request(&pending.id, Some("wrong"), &state)
+                .await
+                .unwrap_err(),
+            RequestError::Unauthorized()
+        );
+
+        for (token_id, status) in [("2", Status::Completed), ("3", Status::Canceled)] {
+            let request = stored_request(token_id, status, &db);
+            assert!(matches!(
+                reprocess_request(&request.id, Some("secret"), &state).await,
+                Err(RequestError::ReprocessNotAllowed(_))
+            ));
+        }
+        let unknown = BRequest::generate_id("0xunknown", "1", "0xowner");
+        assert_eq!(
+            reprocess_request(&unknown, Some("secret"), &state)
+                .await
+                .unwrap_err(),
+            RequestError::NoExistingRequest(unknown.to_string())
+        );
+    }
+}
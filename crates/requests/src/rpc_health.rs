@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use types::{EndpointHealth, RpcHealthSample, Timestamp, RPC_HEALTH_RETENTION};
+
+use crate::{errors::RequestError, is_admin, AppState};
+
+/// History returned when the query sets no start
+pub const DEFAULT_RPC_HEALTH_RANGE: Duration = Duration::from_secs(3600);
+
+/// Time range of the history, unix times in seconds
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct RpcHealthQuery {
+    /// `to` minus an hour when not set
+    #[serde(default)]
+    pub from: Option<u64>,
+    /// Now when not set
+    #[serde(default)]
+    pub to: Option<u64>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RpcHealthReport {
+    pub from: Timestamp,
+    pub to: Timestamp,
+    /// Statistics of the endpoints called since startup, over their last calls
+    pub live: Vec<EndpointHealth>,
+    /// Persisted samples of the range, oldest first
+    pub history: Vec<RpcHealthSample>,
+}
+
+/// Statistics of the endpoints of both chains
+pub fn live_rpc_health(state: &AppState) -> Vec<EndpointHealth> {
+    let mut endpoints = state.evm_client.rpc_health.snapshot();
+    endpoints.extend(state.solana_client.rpc_health.snapshot());
+    endpoints
+}
+
+/// Live statistics and history of the RPC endpoints, authorized by the admin token
+pub fn get_rpc_health(
+    query: RpcHealthQuery,
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<RpcHealthReport, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    let to = query
+        .to
+        .map_or_else(|| state.clock.now(), Timestamp::from_secs);
+    let from = query.from.map_or_else(
+        || to.saturating_sub(DEFAULT_RPC_HEALTH_RANGE),
+        Timestamp::from_secs,
+    );
+    if from > to {
+        return Err(RequestError::InvalidTimeRange(format!(
+            "from {} is after to {}",
+            from.as_secs(),
+            to.as_secs()
+        )));
+    }
+    let history = types::rpc_health_history(&state.db, from, to)
+        .map_err(|e| RequestError::CreationError(e.to_string()))?;
+    Ok(RpcHealthReport {
+        from,
+        to,
+        live: live_rpc_health(state),
+        history,
+    })
+}
+
+/// Persists the live statistics into the history
+pub fn sample_rpc_health(state: &AppState) {
+    if let Err(e) = types::persist_rpc_health(&state.db, live_rpc_health(state), state.clock.now())
+    {
+        error!("Could not persist the RPC endpoint health: {e}");
+    }
+}
+
+/// Deletes the history samples older than `RPC_HEALTH_RETENTION`
+pub fn prune_rpc_health(state: &AppState) {
+    match types::prune_rpc_health(&state.db, state.clock.now(), RPC_HEALTH_RETENTION) {
+        Ok(0) => {}
+        Ok(pruned) => info!("Pruned {pruned} RPC health samples"),
+        Err(e) => error!("Could not prune the RPC health history: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod rpc_health_test {
+    use std::{sync::Arc, time::Duration};
+
+    use test_support::test_db;
+    use types::{BreakerState, Chains, MockClock, Timestamp, BREAKER_FAILURE_THRESHOLD};
+
+    use crate::{
+        errors::RequestError, get_rpc_health, prune_rpc_health, relayer_status, sample_rpc_health,
+        test_utils::test_state, RpcHealthQuery,
+    };
+
+    const NOW: Timestamp = Timestamp::from_secs(1_700_000_000);
+
+    #[tokio::test]
+    async fn test_history_and_breaker_read_the_same_numbers() {
+        let clock = MockClock::new(NOW);
+        let (mut state, _, _) = test_state(test_db());
+        state.clock = Arc::new(clock.clone());
+        state.admin_token = Some("secret".into());
+        let url = state.evm_client.rpc_url();
+        for _ in 0..BREAKER_FAILURE_THRESHOLD {
+            state.evm_client.rpc_health.record(
+                &Chains::EVM,
+                &url,
+                false,
+                Duration::from_millis(50),
+                NOW,
+            );
+        }
+        sample_rpc_health(&state);
+        clock.advance(Duration::from_secs(60));
+
+        assert_eq!(
+            get_rpc_health(RpcHealthQuery::default(), None, &state),
+            Err(RequestError::Unauthorized())
+        );
+        let report = get_rpc_health(RpcHealthQuery::default(), Some("secret"), &state).unwrap();
+        assert_eq!(report.history.len(), 1);
+        let sampled = &report.history[0].endpoints[0];
+        assert_eq!(sampled, &report.live[0]);
+        assert_eq!(sampled.success_rate, 0.0);
+        assert_eq!(sampled.breaker(), BreakerState::Open);
+
+        let status = relayer_status(&state).await;
+        let breakers = status.circuit_breakers.unwrap();
+        assert_eq!(breakers[&sampled.label()], BreakerState::Open);
+
+        let outside = RpcHealthQuery {
+            from: Some(NOW.as_secs() + 1),
+            to: None,
+        };
+        let report = get_rpc_health(outside, Some("secret"), &state).unwrap();
+        assert!(report.history.is_empty());
+        let reversed = RpcHealthQuery {
+            from: Some(NOW.as_secs() + 1),
+            to: Some(NOW.as_secs()),
+        };
+        assert!(matches!(
+            get_rpc_health(reversed, Some("secret"), &state),
+            Err(RequestError::InvalidTimeRange(_))
+        ));
+
+        clock.advance(types::RPC_HEALTH_RETENTION);
+        prune_rpc_health(&state);
+        let everything = RpcHealthQuery {
+            from: Some(0),
+            to: None,
+        };
+        let report = get_rpc_health(everything, Some("secret"), &state).unwrap();
+        assert!(report.history.is_empty());
+    }
+}
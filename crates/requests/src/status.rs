@@ -0,0 +1,281 @@
+use std::collections::BTreeMap;
+
+use log::error;
+use serde::Serialize;
+use types::{
+    last_pending_repair, requests_by_status, BreakerState, ChainHeadReceiver, Chains, EventCursor,
+    Lamports, PauseState, PendingRepairReport, SharedEventCursor, Status, Timestamp, Wei,
+};
+
+use crate::{
+    custody_report, errors::RequestError, is_admin, live_rpc_health, AppState, CustodyReport, Role,
+};
+
+/// Event cursor of a chain against its head
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChainStatus {
+    /// Latest block or slot published by the head watcher, None until observed
+    pub head: Option<u64>,
+    /// Last event processed, None until the first event since startup
+    pub last_event: Option<EventCursor>,
+    /// Blocks or slots between the last event and the head
+    pub lag: Option<u64>,
+}
+
+impl ChainStatus {
+    fn new(head: &ChainHeadReceiver, cursor: &SharedEventCursor) -> Self {
+        let head = Some(head.borrow().height).filter(|height| *height > 0);
+        let last_event = cursor.get();
+        ChainStatus {
+            head,
+            last_event,
+            lag: head
+                .zip(last_event)
+                .map(|(head, event)| head.saturating_sub(event.height)),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct WalletBalances {
+    #[serde(rename = "EVM")]
+    pub evm: Option<Wei>,
+    #[serde(rename = "SOLANA")]
+    pub solana: Option<Lamports>,
+}
+
+/// Liveness of the supervised tx processor of a chain
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProcessorStatus {
+    /// Last heartbeat, None until the processor started
+    pub last_heartbeat: Option<Timestamp>,
+    pub restarts: u64,
+    /// Silent for longer than the restart threshold, its supervisor restarts it
+    pub wedged: bool,
+}
+
+/// Operational state of the relayer in one document. Each section is None when its
+/// source can't be read, the rest is still returned
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RelayerStatus {
+    #[serde(rename = "EVM")]
+    pub evm: ChainStatus,
+    #[serde(rename = "SOLANA")]
+    pub solana: ChainStatus,
+    /// Requests waiting on the sweep by status
+    pub pending: Option<BTreeMap<String, usize>>,
+    /// Last startup check of the pending structures, None before the first one
+    pub pending_repair: Option<PendingRepairReport>,
+    pub in_flight_mints: usize,
+    /// Tokens in bridge custody by origin chain and their caps
+    pub custody: Option<CustodyReport>,
+    /// Tx processor messages waiting by destination chain
+    pub outbox: BTreeMap<String, usize>,
+    pub processors: BTreeMap<String, ProcessorStatus>,
+    /// Breaker of each endpoint called, read from its health, None before the first call
+    pub circuit_breakers: Option<BTreeMap<String, BreakerState>>,
+    pub pause: PauseState,
+    pub balances: WalletBalances,
+}
+
+fn pending_by_status(state: &AppState) -> Option<BTreeMap<String, usize>> {
+    let mut pending = BTreeMap::new();
+    for status in [
+        Status::Initializing,
+        Status::RequestReceived,
+        Status::TokenReceived,
+        Status::TokenMinted,
+        Status::AwaitingDestinationAccount,
+    ] {
+        match requests_by_status(&state.db, &status, None) {
+            Ok(requests) => pending.insert(format!("{status:?}"), requests.len()),
+            Err(e) => {
+                error!("Could not count {status:?} requests: {e}");
+                return None;
+            }
+        };
+    }
+    Some(pending)
+}
+
+async fn wallet_balances(state: &AppState) -> WalletBalances {
+    // Read-only relayers hold no wallet and don't reach the chains
+    if state.role == Role::ReadOnly {
+        return WalletBalances {
+            evm: None,
+            solana: None,
+        };
+    }
+    let evm = evm::signer_balance(state.evm_client.clone())
+        .await
+        .inspect_err(|e| error!("Could not read the EVM wallet balance: {e}"))
+        .ok();
+    let solana = state
+        .solana_client
+        .get_balance(&state.solana_client.fee_payer_pubkey())
+        .await
+        .inspect_err(|e| error!("Could not read the Solana wallet balance: {e}"))
+        .ok()
+        .map(Lamports::new);
+    WalletBalances { evm, solana }
+}
+
+pub async fn relayer_status(state: &AppState) -> RelayerStatus {
+    RelayerStatus {
+        evm: ChainStatus::new(&state.evm_head, &state.evm_client.event_cursor),
+        solana: ChainStatus::new(&state.solana_head, &state.solana_client.event_cursor),
+        pending: pending_by_status(state),
+        pending_repair: last_pending_repair(&state.db)
+            .inspect_err(|e| error!("Could not read the pending repair report: {e}"))
+            .ok()
+            .flatten(),
+        in_flight_mints: state.in_flight.count(),
+        custody: custody_report(state),
+        outbox: [Chains::EVM, Chains::SOLANA]
+            .iter()
+            .map(|chain| (format!("{chain:?}"), state.channel_metrics.depth(chain)))
+            .collect(),
+        processors: [Chains::EVM, Chains::SOLANA]
+            .iter()
+            .map(|chain| {
+                let health = state.processor_health.of(chain);
+                let status = ProcessorStatus {
+                    last_heartbeat: health.last_beat(),
+                    restarts: health.restarts(),
+                    wedged: health
+                        .wedged(state.clock.now(), state.intervals.processor_wedged_after),
+                };
+                (format!("{chain:?}"), status)
+            })
+            .collect(),
+        circuit_breakers: Some(
+            live_rpc_health(state)
+                .iter()
+                .map(|endpoint| (endpoint.label(), endpoint.breaker()))
+                .collect::<BTreeMap<_, _>>(),
+        )
+        .filter(|breakers| !breakers.is_empty()),
+        pause: state.pause.state(),
+        balances: wallet_balances(state).await,
+    }
+}
+
+/// Relayer status, authorized by the admin token
+pub async fn get_status(
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<RelayerStatus, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    Ok(relayer_status(state).await)
+}
+
+#[cfg(test)]
+mod status_test {
+    use serde_json::{json, Value};
+    use solana_client::rpc_request::RpcRequest;
+    use test_support::{mock_rpc, request_in_status, test_db};
+    use types::{chain_head_channel, repair_pending_structures, Chains, Status, Timestamp};
+
+    use crate::{errors::RequestError, get_status, test_utils::test_state};
+
+    #[tokio::test]
+    async fn test_status_document_degrades_by_section() {
+        let db = test_db();
+        let (mut state, _rx_evm, _rx_sol) = test_state(db.clone());
+        state.admin_token = Some("secret".into());
+        for (token_id, status) in [
+            ("1", Status::TokenReceived),
+            ("2", Status::TokenReceived),
+            ("3", Status::TokenMinted),
+            ("4", Status::Completed),
+        ] {
+            request_in_status(Chains::EVM, token_id, status)
+                .save(&db)
+                .unwrap();
+        }
+        let (_evm_head_tx, evm_head) = chain_head_channel(120);
+        state.evm_head = evm_head;
+        state
+            .evm_client
+            .event_cursor
+            .record(100, Timestamp::from_millis(1_700_000_000_000));
+        // Silent since long before the system clock of the test state
+        state
+            .processor_health
+            .of(&Chains::EVM)
+            .beat(Timestamp::from_millis(1_700_000_000_000));
+        state.solana_client.set_rpc(mock_rpc([(
+            RpcRequest::GetBalance,
+            json!({ "context": { "slot": 1 }, "value": 5_000 }),
+        )]));
+        // Nothing listens on the EVM node of the test state
+        state.evm_client = state
+            .evm_client
+            .with_endpoints("http://127.0.0.1:1", "ws://127.0.0.1:1");
+
+        assert_eq!(
+            get_status(None, &state).await.unwrap_err(),
+            RequestError::Unauthorized()
+        );
+        let status =
+            serde_json::to_value(get_status(Some("secret"), &state).await.unwrap()).unwrap();
+
+        assert_eq!(
+            status["EVM"],
+            json!({
+                "head": 120,
+                "last_event": { "height": 100, "processed_at": 1_700_000_000_000u64 },
+                "lag": 20,
+            })
+        );
+        // No Solana event yet
+        assert_eq!(
+            status["SOLANA"],
+            json!({ "head": 1, "last_event": null, "lag": null })
+        );
+        assert_eq!(
+            status["pending"],
+            json!({
+                "AwaitingDestinationAccount": 0,
+                "Initializing": 0,
+                "RequestReceived": 0,
+                "TokenMinted": 1,
+                "TokenReceived": 2,
+            })
+        );
+        // No startup check in the test state
+        assert_eq!(status["pending_repair"], Value::Null);
+        assert_eq!(status["in_flight_mints"], 0);
+        assert_eq!(
+            status["custody"]["exposure"],
+            json!({ "EVM": 3, "SOLANA": 0 })
+        );
+        assert_eq!(status["outbox"], json!({ "EVM": 0, "SOLANA": 0 }));
+        assert_eq!(
+            status["processors"]["EVM"],
+            json!({ "last_heartbeat": 1_700_000_000_000u64, "restarts": 0, "wedged": true })
+        );
+        assert_eq!(
+            status["processors"]["SOLANA"],
+            json!({ "last_heartbeat": null, "restarts": 0, "wedged": false })
+        );
+        assert_eq!(status["circuit_breakers"], Value::Null);
+        assert!(status["pause"].is_object());
+        // The unreachable EVM node only empties its balance
+        assert_eq!(status["balances"]["EVM"], Value::Null);
+        assert_eq!(status["balances"]["SOLANA"]["raw"], "5000");
+
+        repair_pending_structures(&db, Timestamp::from_secs(1_700_000_000)).unwrap();
+        let status =
+            serde_json::to_value(get_status(Some("secret"), &state).await.unwrap()).unwrap();
+        assert_eq!(status["pending_repair"]["checked_at"], 1_700_000_000_000u64);
+        assert_eq!(status["pending_repair"]["missing_records"], json!([]));
+        // The failed balance read is in the health of the EVM endpoint
+        assert_eq!(
+            status["circuit_breakers"]["EVM http://127.0.0.1:1"],
+            "closed"
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use evm::{FeeSettings, GasLimits, DEFAULT_BACKFILL_PARALLELISM};
+use solana::{SolanaClient, SolanaNode};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair};
+use storage::db::Database;
+use types::{
+    chain_head_channel, system_clock, tx_channel, ApiMetrics, BackfillProgress, BrandingConfig,
+    BridgePause, CallbackPolicy, CallbackSender, Chains, ChannelMetrics, ConfirmationStrategy,
+    CustodyLimits, EventValidator, IdScheme, InFlightRegistry, Intervals, MissingUriPolicy,
+    ProcessorHealth, QuotaLimits, RequestLocks, RpcHealth, RpcLimiter, RpcTimeouts, Secret,
+    SharedEventCursor, TxAudit, TxReceiver, UriPolicy, WorkClaims, DEFAULT_IDEMPOTENCY_KEY_TTL,
+    IN_FLIGHT_TIMEOUT, WORK_CLAIM_LEASE,
+};
+
+use crate::{AppState, LoadTestRuns, NegativeCache, Role};
+
+/// State with clients pointing to local nodes, no call is made until used. Returns the
+/// receivers of the EVM and Solana tx processors
+pub fn test_state(db: Database) -> (AppState, TxReceiver, TxReceiver) {
+    let channel_metrics = ChannelMetrics::default();
+    let (evm_processor_tx, evm_processor_rx) = tx_channel(Chains::EVM, 10, &channel_metrics);
+    let (solana_processor_tx, solana_processor_rx) =
+        tx_channel(Chains::SOLANA, 10, &channel_metrics);
+    let clock = system_clock();
+
+    let evm_client = evm::evm_initialize(
+        "http://localhost:8545",
+        "ws://localhost:8546",
+        &Secret::from("0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"),
+        "0x5FbDB2315678afecb367f032d93F642f64180aa3",
+        solana_processor_tx,
+        "",
+        UriPolicy::default(),
+        None,
+        BrandingConfig::default(),
+        RpcTimeouts::default(),
+        GasLimits::default(),
+        FeeSettings::default(),
+        MissingUriPolicy::default(),
+        false,
+    )
+    .unwrap()
+    .with_clock(clock.clone());
+    let solana_client = SolanaClient {
+        node: SolanaNode::connect("http://localhost:8899", "ws://localhost:8900").shared(),
+        signer: Arc::new(Keypair::new()),
+        fee_payer: None,
+        bridge_program: Pubkey::new_unique(),
+        bridge_account: Pubkey::new_unique(),
+        tx_channel: evm_processor_tx,
+        block_explorer: String::new(),
+        uri_policy: UriPolicy::default(),
+        metadata_pinning: None,
+        branding: BrandingConfig::default(),
+        timeouts: RpcTimeouts::default(),
+        authorized_backend: Arc::new(true.into()),
+        missing_signers: Arc::default(),
+        blockhash_resends: Arc::default(),
+        dev_mode: false,
+        confirmation: ConfirmationStrategy::default(),
+        event_cursor: SharedEventCursor::default(),
+        rpc_health: RpcHealth::default(),
+        bridge_deployment: None,
+        require_prefunded_ata: false,
+        clock: clock.clone(),
+    };
+    let (_, evm_head) = chain_head_channel(1);
+    let (_, solana_head) = chain_head_channel(1);
+
+    let state = AppState {
+        in_flight: InFlightRegistry::load(&db, IN_FLIGHT_TIMEOUT, clock.clone()),
+        request_locks: RequestLocks::default(),
+        work_claims: WorkClaims::new(WORK_CLAIM_LEASE, clock.clone()),
+        pause: BridgePause::load(&db, clock.clone()),
+        db,
+        solana_client,
+        evm_client,
+        evm_head,
+        solana_head,
+        channel_metrics,
+        event_validator: EventValidator::default(),
+        admin_token: None,
+        purge_salt: None,
+        sync_creation: false,
+        clock,
+        dev_mode: false,
+        dry_run: false,
+        load_tests: LoadTestRuns::default(),
+        intervals: Intervals::PRODUCTION,
+        quota_limits: QuotaLimits::default(),
+        custody_limits: CustodyLimits::default(),
+        callbacks: CallbackSender::new(CallbackPolicy::default()).unwrap(),
+        missing_requests: NegativeCache::default(),
+        id_scheme: IdScheme::default(),
+        rpc_limiter: RpcLimiter::per_second(0),
+        backfill_parallelism: DEFAULT_BACKFILL_PARALLELISM,
+        backfill_progress: BackfillProgress::default(),
+        idempotency_key_ttl: DEFAULT_IDEMPOTENCY_KEY_TTL,
+        processor_health: ProcessorHealth::default(),
+        tx_audit: TxAudit::default(),
+        storage_metrics: None,
+        api_metrics: ApiMetrics::default(),
+        client_ip_header: None,
+        role: Role::Full,
+    };
+    (state, evm_processor_rx, solana_processor_rx)
+}
@@ -0,0 +1,88 @@
+use evm::EVMClient;
+use log::{error, info};
+use solana::SolanaClient;
+use types::{Chains, RpcLimiter, TxAuditReport, TxReadFuture, TxReader};
+
+use crate::{errors::RequestError, is_admin, AppState};
+
+/// Reads the recorded transactions through the RPC nodes of the clients, each RPC request
+/// waits for the limiter shared with the other scans over the chain history
+pub struct ChainTxReader {
+    pub evm_client: EVMClient,
+    pub solana_client: SolanaClient,
+    pub limiter: RpcLimiter,
+}
+
+impl ChainTxReader {
+    pub fn of(state: &AppState) -> Self {
+        ChainTxReader {
+            evm_client: state.evm_client.clone(),
+            solana_client: state.solana_client.clone(),
+            limiter: state.rpc_limiter.clone(),
+        }
+    }
+}
+
+impl TxReader for ChainTxReader {
+    fn read_tx<'a>(&'a self, chain: &'a Chains, tx_hash: &'a str) -> TxReadFuture<'a> {
+        Box::pin(async move {
+            match chain {
+                Chains::EVM => {
+                    evm::recorded_tx(self.evm_client.clone(), tx_hash, &self.limiter).await
+                }
+                Chains::SOLANA => {
+                    solana::recorded_tx(&self.solana_client, tx_hash, &self.limiter).await
+                }
+            }
+        })
+    }
+
+    fn bridge(&self, chain: &Chains) -> String {
+        match (&self.evm_client.bridge_deployment, chain) {
+            (Some(deployment), _) => deployment.legacy.bridge_of(chain).to_string(),
+            (None, Chains::EVM) => self.evm_client.bridge_contract.to_string(),
+            (None, Chains::SOLANA) => self.solana_client.bridge_program.to_string(),
+        }
+    }
+}
+
+/// Scheduled run of the tx audit, skipped while a run from the admin endpoint is going
+pub async fn audit_recorded_txs(state: &AppState) {
+    match state
+        .tx_audit
+        .run(&state.db, &ChainTxReader::of(state), state.clock.now())
+        .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => info!("Tx audit already running, scheduled run skipped"),
+        Err(e) => error!("Tx audit failed: {e}"),
+    }
+}
+
+/// Runs the tx audit on demand, authorized by the admin token. Answers once every hash of
+/// the lookback is read
+pub async fn run_tx_audit(
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<TxAuditReport, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    state
+        .tx_audit
+        .run(&state.db, &ChainTxReader::of(state), state.clock.now())
+        .await
+        .map_err(|e| RequestError::TxAuditFailed(e.to_string()))?
+        .ok_or(RequestError::TxAuditRunning())
+}
+
+/// Report of the last tx audit run, authorized by the admin token
+pub fn get_tx_audit_report(
+    admin_token: Option<&str>,
+    state: &AppState,
+) -> Result<Option<TxAuditReport>, RequestError> {
+    if !is_admin(admin_token, state.admin_token.as_ref()) {
+        return Err(RequestError::Unauthorized());
+    }
+    types::last_tx_audit_report(&state.db).map_err(|e| RequestError::TxAuditFailed(e.to_string()))
+}
@@ -0,0 +1,76 @@
+use storage::db::Database;
+use types::{BRequest, BridgeError, Chains};
+
+use crate::errors::RequestError;
+
+/// Requests that recorded `tx_hash`, with the chain told by its format. Every match is
+/// returned, a hash recorded by several requests points to all of them
+pub fn get_requests_by_tx(
+    tx_hash: &str,
+    db: &Database,
+) -> Result<(Chains, Vec<BRequest>), RequestError> {
+    let tx_hash = tx_hash.trim();
+    let chain = types::tx_hash_chain(tx_hash)
+        .ok_or_else(|| RequestError::InvalidTxHash(tx_hash.to_string()))?;
+    let db_error = |e: BridgeError| RequestError::CreationError(e.to_string());
+
+    let mut requests = vec![];
+    for request_id in types::requests_by_tx(db, tx_hash).map_err(db_error)? {
+        // Entries of removed requests are left to the next rebuild
+        if let Some(request) = types::request_data(&request_id, db).map_err(db_error)? {
+            requests.push(request);
+        }
+    }
+    if requests.is_empty() {
+        return Err(RequestError::UnknownTxHash(tx_hash.to_string()));
+    }
+    Ok((chain, requests))
+}
+
+#[cfg(test)]
+mod tx_lookup_test {
+    use test_support::test_db;
+    use types::{BRequest, Chains, InputRequest, Timestamp};
+
+    use crate::{errors::RequestError, get_requests_by_tx};
+
+    const EVM_TX: &str = "0x5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060";
+    const SOLANA_TX: &str =
+        "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW";
+
+    #[test]
+    fn test_lookup_of_both_hash_formats() {
+        let db = test_db();
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xcontract".to_string(),
+                token_id: "1".to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.save(&db).unwrap();
+        request.add_tx(EVM_TX, &db).unwrap();
+        request.add_tx(SOLANA_TX, &db).unwrap();
+
+        let (chain, found) = get_requests_by_tx(EVM_TX, &db).unwrap();
+        assert_eq!(chain, Chains::EVM);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, request.id);
+        let (chain, found) = get_requests_by_tx(SOLANA_TX, &db).unwrap();
+        assert_eq!(chain, Chains::SOLANA);
+        assert_eq!(found[0].id, request.id);
+
+        let unknown = format!("0x{}", "ab".repeat(32));
+        assert_eq!(
+            get_requests_by_tx(&unknown, &db).unwrap_err(),
+            RequestError::UnknownTxHash(unknown)
+        );
+        assert!(matches!(
+            get_requests_by_tx("0xtx", &db).unwrap_err(),
+            RequestError::InvalidTxHash(_)
+        ));
+    }
+}
@@ -1,10 +1,85 @@
+use std::time::Duration;
+
 use evm::EVMClient;
 use solana::SolanaClient;
-use storage::db::Database;
+use storage::{db::Database, metrics::StorageMetrics};
+use types::{
+    ApiMetrics, BackfillProgress, BridgePause, CallbackSender, ChainHeadReceiver, ChannelMetrics,
+    CustodyLimits, EventValidator, IdScheme, InFlightRegistry, Intervals, ProcessorHealth,
+    QuotaLimits, RequestLocks, RpcLimiter, Secret, SharedClock, TxAudit, WorkClaims,
+};
+
+use crate::{LoadTestRuns, NegativeCache};
+
+/// What a relayer process does with its state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Role {
+    /// Listens to the chains, sends the transactions and serves the whole API
+    #[default]
+    Full,
+    /// Serves the reads of the API from a read-only copy of the database of a full
+    /// relayer. Its chain clients hold throwaway keys and are never connected
+    ReadOnly,
+}
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Database,
     pub solana_client: SolanaClient,
     pub evm_client: EVMClient,
+    pub evm_head: ChainHeadReceiver,
+    pub solana_head: ChainHeadReceiver,
+    pub in_flight: InFlightRegistry,
+    /// Requests being processed by the sweep or a manual reprocess
+    pub request_locks: RequestLocks,
+    /// Requests claimed by a sweeper tick, skipped by the other ticks
+    pub work_claims: WorkClaims,
+    pub channel_metrics: ChannelMetrics,
+    pub pause: BridgePause,
+    pub event_validator: EventValidator,
+    /// Token of the admin endpoints, they are disabled when not set
+    pub admin_token: Option<Secret<String>>,
+    /// Wall clock of the time dependent logic, a `MockClock` in tests
+    pub clock: SharedClock,
+    /// Running against local test validators
+    pub dev_mode: bool,
+    /// Tx processor messages are logged and dropped, no transaction is sent
+    pub dry_run: bool,
+    pub load_tests: LoadTestRuns,
+    pub intervals: Intervals,
+    /// Bridges and fees allowed per account and day
+    pub quota_limits: QuotaLimits,
+    /// Caps of the tokens held in bridge custody, new bridges are refused at the cap
+    pub custody_limits: CustodyLimits,
+    /// Per-request callbacks, validated at creation and sent on terminal statuses
+    pub callbacks: CallbackSender,
+    /// Ids of requests recently looked up and not found
+    pub missing_requests: NegativeCache,
+    /// Id scheme of new requests, they are also reachable by the id of the other one
+    pub id_scheme: IdScheme,
+    /// Rate of the chain RPC requests of the scans over the chain history
+    pub rpc_limiter: RpcLimiter,
+    /// EVM logs of a backfill window looked up at once
+    pub backfill_parallelism: usize,
+    /// Logs processed and deferred by the backfills
+    pub backfill_progress: BackfillProgress,
+    /// Time an Idempotency-Key replays the response of its creation
+    pub idempotency_key_ttl: Duration,
+    /// Heartbeats and restarts of the supervised tx processors
+    pub processor_health: ProcessorHealth,
+    /// Salt of the account hashes of the purged requests, purges are disabled when not set
+    pub purge_salt: Option<Secret<String>>,
+    /// New requests wait for their lock transaction instead of being accepted with it
+    /// queued to the tx processors
+    pub sync_creation: bool,
+    /// Nightly check of the tx hashes recorded on the finalized requests
+    pub tx_audit: TxAudit,
+    /// Latency and size of the database operations, None when `DB_METRICS` is off
+    pub storage_metrics: Option<StorageMetrics>,
+    /// Requests served by the API, by route template
+    pub api_metrics: ApiMetrics,
+    /// Header a trusted proxy puts the client ip in, the access logs use the peer address
+    /// when not set
+    pub client_ip_header: Option<String>,
+    pub role: Role,
 }
@@ -0,0 +1,75 @@
+use serde::Serialize;
+use storage::db::Database;
+use types::{BridgeError, BridgedToken, HistoryEntry, Status, WrappedAsset};
+
+use crate::errors::RequestError;
+
+/// Origin of a bridged token with the history of the request that bridged it
+#[derive(Serialize, Debug, Clone)]
+pub struct WrappedTokenInfo {
+    #[serde(flatten)]
+    pub asset: WrappedAsset,
+    pub status: Status,
+    pub history: Vec<HistoryEntry>,
+}
+
+/// Bridging of `token`, wrapped or origin token of a completed request. None when the
+/// token was never bridged
+pub fn get_wrapped_token(
+    token: &BridgedToken,
+    db: &Database,
+) -> Result<Option<WrappedTokenInfo>, RequestError> {
+    let db_error = |e: BridgeError| RequestError::CreationError(e.to_string());
+    let Some(asset) = types::wrapped_asset(db, token).map_err(db_error)? else {
+        return Ok(None);
+    };
+    let request = types::request_data(&asset.request_id, db).map_err(db_error)?;
+    Ok(request.map(|request| WrappedTokenInfo {
+        asset,
+        status: request.status,
+        history: request.history,
+    }))
+}
+
+#[cfg(test)]
+mod wrapped_test {
+    use test_support::test_db;
+    use types::{BRequest, BridgedToken, Chains, InputRequest, OutputResult, Status, Timestamp};
+
+    use crate::get_wrapped_token;
+
+    #[test]
+    fn test_lookup_from_both_sides() {
+        let db = test_db();
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "SolanaMint111".to_string(),
+                token_id: String::new(),
+                token_owner: "owner".to_string(),
+                origin_network: Chains::SOLANA,
+                destination_account: "0xdestination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.output = OutputResult {
+            detination_token_id_or_account: "7".to_string(),
+            detination_contract_id_or_mint: "0xWrapped".to_string(),
+        };
+        request.status = Status::Completed;
+        request.save(&db).unwrap();
+
+        let wrapped = BridgedToken::new(Chains::EVM, "0xwrapped", "7");
+        let info = get_wrapped_token(&wrapped, &db).unwrap().unwrap();
+        assert_eq!(info.asset.request_id, request.id);
+        assert_eq!(info.asset.origin.contract_or_mint, "SolanaMint111");
+        assert_eq!(info.status, Status::Completed);
+
+        let origin = BridgedToken::new(Chains::SOLANA, "SolanaMint111", "");
+        let info = get_wrapped_token(&origin, &db).unwrap().unwrap();
+        assert_eq!(info.asset.destination.contract_or_mint, "0xWrapped");
+        assert_eq!(info.asset.destination.token_id, "7");
+
+        let other = BridgedToken::new(Chains::EVM, "0xwrapped", "8");
+        assert!(get_wrapped_token(&other, &db).unwrap().is_none());
+    }
+}
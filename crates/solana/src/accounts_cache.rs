@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use mpl_token_metadata::accounts::Metadata;
+use solana_sdk::pubkey::Pubkey;
+use types::{BRequest, BridgeError, Result};
+
+use crate::{parse_pubkey, SolanaClient};
+
+/// Accounts read by one getMultipleAccounts request, the maximum of the RPC nodes
+pub const MULTIPLE_ACCOUNTS_CHUNK: usize = 100;
+
+/// Account data read ahead for the requests of a sweep page, the accounts read and not
+/// found are kept as missing
+#[derive(Debug, Clone, Default)]
+pub struct AccountsCache {
+    accounts: HashMap<Pubkey, Option<Vec<u8>>>,
+}
+
+impl AccountsCache {
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Data of `pubkey` from the cache, read on its own on a miss. Accounts read as
+    /// missing fail as `SolanaClient::get_account_data` does
+    pub async fn account_data(&self, client: &SolanaClient, pubkey: &Pubkey) -> Result<Vec<u8>> {
+        match self.accounts.get(pubkey) {
+            Some(Some(data)) => Ok(data.clone()),
+            Some(None) => Err(BridgeError::Other(format!(
+                "AccountNotFound: pubkey={pubkey}"
+            ))),
+            None => client.get_account_data(pubkey).await,
+        }
+    }
+}
+
+/// Accounts read by the owner check of a Solana origin request: the bridge token account
+/// of its mint and the metadata of the mint, none for an invalid mint
+pub fn owner_check_accounts(client: &SolanaClient, request: &BRequest) -> Vec<Pubkey> {
+    let Ok(mint) = parse_pubkey("mint address", &request.input.contract_or_mint) else {
+        return vec![];
+    };
+    vec![
+        spl_associated_token_account::get_associated_token_address(&client.bridge_account, &mint),
+        Metadata::find_pda(&mint).0,
+    ]
+}
+
+/// Reads `pubkeys` by getMultipleAccounts in chunks of `MULTIPLE_ACCOUNTS_CHUNK`, the
+/// duplicates once
+pub async fn prefetch_accounts(client: &SolanaClient, pubkeys: &[Pubkey]) -> Result<AccountsCache> {
+    let mut unique = Vec::with_capacity(pubkeys.len());
+    for pubkey in pubkeys {
+        if !unique.contains(pubkey) {
+            unique.push(*pubkey);
+        }
+    }
+
+    let mut cache = AccountsCache::default();
+    for chunk in unique.chunks(MULTIPLE_ACCOUNTS_CHUNK) {
+        let accounts = client.get_multiple_accounts(chunk).await?;
+        for (pubkey, account) in chunk.iter().zip(accounts) {
+            cache
+                .accounts
+                .insert(*pubkey, account.map(|account| account.data));
+        }
+    }
+    Ok(cache)
+}
+
+#[cfg(test)]
+mod accounts_cache_test {
+    use std::collections::HashMap;
+
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::pubkey::Pubkey;
+    use spl_associated_token_account::get_associated_token_address;
+    use test_support::{accounts_mock_rpc, input_request, token_account_data};
+    use types::{BRequest, Chains, Timestamp};
+
+    use crate::{
+        bridge_holds_token, bridge_holds_token_cached, get_metadata, get_metadata_cached,
+        owner_check_accounts, prefetch_accounts, test_utils::test_client, MULTIPLE_ACCOUNTS_CHUNK,
+    };
+
+    #[tokio::test]
+    async fn test_sweep_accounts_read_in_chunks() {
+        const REQUESTS: usize = 120;
+        let bridge_account = Pubkey::new_unique();
+        let mints: Vec<Pubkey> = (0..REQUESTS).map(|_| Pubkey::new_unique()).collect();
+
+        // Held, emptied and never created bridge token accounts
+        let mut accounts = HashMap::new();
+        for (i, mint) in mints.iter().enumerate().filter(|(i, _)| i % 3 != 2) {
+            let amount = if i % 3 == 0 { 1 } else { 0 };
+            accounts.insert(
+                get_associated_token_address(&bridge_account, mint),
+                token_account_data(mint, &bridge_account, amount),
+            );
+        }
+        let (rpc, calls) = accounts_mock_rpc(accounts);
+        let mut client = test_client(rpc);
+        client.bridge_account = bridge_account;
+
+        let pubkeys: Vec<Pubkey> = mints
+            .iter()
+            .flat_map(|mint| {
+                let mut input = input_request(Chains::SOLANA, "1");
+                input.contract_or_mint = mint.to_string();
+                owner_check_accounts(&client, &BRequest::new(input, Timestamp::now()))
+            })
+            .collect();
+        let accounts_read = pubkeys.len();
+        assert_eq!(accounts_read, REQUESTS * 2);
+        let cache = prefetch_accounts(&client, &pubkeys).await.unwrap();
+        assert_eq!(cache.len(), accounts_read);
+        assert_eq!(
+            calls.count(RpcRequest::GetMultipleAccounts),
+            accounts_read.div_ceil(MULTIPLE_ACCOUNTS_CHUNK)
+        );
+
+        let mut cached = vec![];
+        for mint in &mints {
+            cached.push((
+                bridge_holds_token_cached(&client, &cache, mint).await.ok(),
+                get_metadata_cached(&client, &cache, &mint.to_string())
+                    .await
+                    .ok(),
+            ));
+        }
+        assert_eq!(calls.count(RpcRequest::GetAccountInfo), 0);
+
+        // Same results as the accounts read one by one
+        for (mint, cached) in mints.iter().zip(cached) {
+            let unbatched = (
+                bridge_holds_token(&client, mint).await.ok(),
+                get_metadata(&client, &mint.to_string()).await.ok(),
+            );
+            assert_eq!(cached, unbatched);
+        }
+        assert_eq!(calls.count(RpcRequest::GetAccountInfo), accounts_read);
+    }
+}
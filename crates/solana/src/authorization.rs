@@ -0,0 +1,113 @@
+use std::sync::atomic::Ordering;
+
+use anchor_lang::AccountDeserialize;
+use log::{error, info};
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+
+use crate::{solana_bridge, SolanaClient, SolanaError};
+
+use solana_bridge::accounts::Bridge;
+use types::{BridgeError, Result};
+
+impl SolanaClient {
+    pub fn is_authorized_backend(&self) -> bool {
+        self.authorized_backend.load(Ordering::Relaxed)
+    }
+
+    /// Fails fast instead of sending transactions the bridge program will reject
+    pub fn ensure_authorized_backend(&self) -> Result<(), SolanaError> {
+        if self.is_authorized_backend() {
+            Ok(())
+        } else {
+            Err(SolanaError::NotAuthorizedBackend(
+                self.signer.pubkey().to_string(),
+            ))
+        }
+    }
+}
+
+/// Reads the bridge account and compares its backend with the relayer signer,
+/// updating the client authorization flag
+pub async fn check_backend_authorization(client: &SolanaClient) -> Result<bool> {
+    let data = client.get_account_data(&client.bridge_account).await?;
+    let backend = bridge_backend(&data)?;
+    Ok(update_authorization(client, &backend))
+}
+
+pub fn update_authorization(client: &SolanaClient, backend: &Pubkey) -> bool {
+    let authorized = *backend == client.signer.pubkey();
+
+    if authorized {
+        info!("Solana relayer wallet is the bridge backend");
+    } else {
+        error!(
+            "UNAUTHORIZED: Solana relayer wallet {} is not the bridge backend {}, transactions will not be sent",
+            client.signer.pubkey(),
+            backend
+        );
+    }
+    client
+        .authorized_backend
+        .store(authorized, Ordering::Relaxed);
+
+    authorized
+}
+
+pub fn bridge_backend(account_data: &[u8]) -> Result<Pubkey> {
+    let bridge =
+        Bridge::try_deserialize(&mut &account_data[..]).map_err(BridgeError::from_error)?;
+    Ok(bridge.backend)
+}
+
+#[cfg(test)]
+mod authorization_test {
+    use anchor_lang::Discriminator;
+    use solana_sdk::{pubkey::Pubkey, signer::Signer};
+    use test_support::mock_rpc;
+
+    use crate::{
+        bridge_backend, solana_bridge::accounts::Bridge, test_utils::test_client,
+        update_authorization, SolanaError,
+    };
+
+    fn bridge_account_data(backend: &Pubkey) -> Vec<u8> {
+        let mut data = Bridge::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(backend.as_ref());
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.push(255);
+        data
+    }
+
+    #[test]
+    fn test_bridge_backend_decoding() {
+        let backend = Pubkey::new_unique();
+        let data = bridge_account_data(&backend);
+        assert_eq!(bridge_backend(&data).unwrap(), backend);
+
+        // Not a bridge account
+        assert!(bridge_backend(&[0u8; 16]).is_err());
+    }
+
+    #[test]
+    fn test_unauthorized_backend_short_circuit() {
+        let client = test_client(mock_rpc([]));
+        assert!(client.ensure_authorized_backend().is_ok());
+
+        // Stored backend differs from the signer
+        let data = bridge_account_data(&Pubkey::new_unique());
+        assert!(!update_authorization(
+            &client,
+            &bridge_backend(&data).unwrap()
+        ));
+
+        assert!(!client.is_authorized_backend());
+        assert!(matches!(
+            client.ensure_authorized_backend(),
+            Err(SolanaError::NotAuthorizedBackend(_))
+        ));
+
+        // Key rotated back to the signer
+        assert!(update_authorization(&client, &client.signer.pubkey()));
+        assert!(client.ensure_authorized_backend().is_ok());
+    }
+}
@@ -0,0 +1,156 @@
+use log::info;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_transaction_status::UiTransactionEncoding;
+use types::{
+    CallContext, Chains, HistoricalEvent, InputRequest, OutputResult, Result, RpcLimiter,
+    WrapCallContext,
+};
+
+use crate::{decode_log, event_discriminators, parse_signature, SolanaClient, SolanaEvent};
+
+/// Signatures of each getSignaturesForAddress page of a backfill, the RPC maximum
+pub const BACKFILL_SIGNATURE_PAGE: usize = 1_000;
+
+/// Bridge events in the log lines of the transaction `tx`
+pub fn historical_events_of_logs(logs: &[String], tx: &str) -> Vec<HistoricalEvent> {
+    let (new_request_discriminator, token_minted_discriminator) = event_discriminators();
+    logs.iter()
+        .filter_map(|log| decode_log(log, &new_request_discriminator, &token_minted_discriminator))
+        .map(|event| match event {
+            SolanaEvent::NewRequest(request_id, event) => HistoricalEvent::NewRequest {
+                request_id,
+                input: InputRequest {
+                    contract_or_mint: event.mint.to_string(),
+                    token_id: String::new(),
+                    token_owner: event.user_token_account.to_string(),
+                    origin_network: Chains::SOLANA,
+                    destination_account: String::new(),
+                },
+                tx: tx.to_string(),
+                locked_at: None,
+            },
+            SolanaEvent::TokenMinted(request_id, event) => HistoricalEvent::TokenMinted {
+                request_id,
+                chain: Chains::SOLANA,
+                output: OutputResult {
+                    detination_token_id_or_account: event.destination_token_account.to_string(),
+                    detination_contract_id_or_mint: event.mint.to_string(),
+                },
+                destination_account: None,
+                tx: tx.to_string(),
+            },
+        })
+        .collect()
+}
+
+/// Bridge events of the bridge program transactions from `from_slot` to the latest, newest
+/// first. Failed transactions are skipped, each RPC request waits for the limiter
+pub async fn historical_events(
+    client: &SolanaClient,
+    from_slot: u64,
+    limiter: &RpcLimiter,
+) -> Result<Vec<HistoricalEvent>> {
+    let mut events = vec![];
+    let mut before = None;
+    loop {
+        limiter.acquire().await;
+        let page = client
+            .get_signatures_for_address_before(
+                &client.bridge_program,
+                before,
+                BACKFILL_SIGNATURE_PAGE,
+            )
+            .await
+            .with_call_context(|| {
+                CallContext::new(Chains::SOLANA, "get_signatures_for_address")
+                    .contract(client.bridge_program)
+            })?;
+        let full_page = page.len() == BACKFILL_SIGNATURE_PAGE;
+
+        let mut reached_start = false;
+        for status in &page {
+            if status.slot < from_slot {
+                reached_start = true;
+                break;
+            }
+            if status.err.is_some() {
+                continue;
+            }
+            limiter.acquire().await;
+            let config = RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Json),
+                commitment: Some(client.processing_commitment()),
+                max_supported_transaction_version: Some(0),
+            };
+            let transaction = client
+                .get_transaction_with_config(&parse_signature(&status.signature)?, config)
+                .await
+                .with_call_context(|| {
+                    CallContext::new(Chains::SOLANA, "get_transaction").contract(&status.signature)
+                })?;
+            let logs: Option<Vec<String>> = transaction
+                .transaction
+                .meta
+                .map(|meta| meta.log_messages.into())
+                .unwrap_or_default();
+            events.extend(historical_events_of_logs(
+                &logs.unwrap_or_default(),
+                &status.signature,
+            ));
+        }
+
+        match page.last() {
+            Some(last) if full_page && !reached_start => {
+                info!("Backfill read Solana signatures down to slot {}", last.slot);
+                before = Some(parse_signature(&last.signature)?);
+            }
+            _ => break,
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod backfill_test {
+    use test_support::{
+        new_request_event_data, program_data_log, solana_key, token_minted_event_data,
+    };
+    use types::{BRequest, Chains, HistoricalEvent};
+
+    use crate::historical_events_of_logs;
+
+    #[test]
+    fn test_program_logs_decode_to_historical_events() {
+        let id = BRequest::generate_id("mint", "", "token-account");
+        let (mint, account) = (solana_key(1), solana_key(2));
+        let logs = vec![
+            "Program log: Instruction: NewRequest".to_string(),
+            program_data_log(&new_request_event_data(&mint, &account, &id)),
+            program_data_log(&token_minted_event_data(&mint, &account, &id)),
+            program_data_log(&new_request_event_data(&mint, &account, "0xrequest1")),
+        ];
+
+        let events = historical_events_of_logs(&logs, "signature");
+        assert_eq!(events.len(), 2);
+        let HistoricalEvent::NewRequest {
+            request_id,
+            input,
+            tx,
+            ..
+        } = &events[0]
+        else {
+            panic!("NewRequest event not decoded");
+        };
+        assert_eq!((request_id, tx.as_str()), (&id, "signature"));
+        assert_eq!(input.contract_or_mint, mint.to_string());
+        assert_eq!(input.token_owner, account.to_string());
+        assert_eq!(input.origin_network, Chains::SOLANA);
+
+        let HistoricalEvent::TokenMinted { chain, output, .. } = &events[1] else {
+            panic!("TokenMinted event not decoded");
+        };
+        assert_eq!(*chain, Chains::SOLANA);
+        assert_eq!(output.detination_contract_id_or_mint, mint.to_string());
+        assert_eq!(output.detination_token_id_or_account, account.to_string());
+    }
+}
@@ -0,0 +1,234 @@
+use log::info;
+use mpl_token_metadata::{
+    accounts::{MasterEdition, Metadata},
+    instructions::{CreateV1Builder, MintV1Builder, SetAndVerifyCollectionBuilder},
+    types::{PrintSupply, TokenStandard},
+};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Keypair, signer::Signer};
+use storage::db::Database;
+use types::{CollectionEntry, Result, WrapCallContext};
+
+use crate::{parse_pubkey, SolanaClient};
+
+/// Symbol of the bridged tokens and their collections
+pub const BRIDGED_SYMBOL: &str = "BNFT";
+/// Name of the bridged tokens, before the branding prefix
+pub const BRIDGED_NAME: &str = "Bridged NFT";
+
+/// Name of the collection of an EVM contract, Metaplex names are limited to 32 bytes
+pub fn collection_name(origin_contract: &str) -> String {
+    let contract = types::collection_contract(origin_contract);
+    if contract.len() <= 12 {
+        return format!("Bridged {contract}");
+    }
+    format!(
+        "Bridged {}...{}",
+        &contract[..6],
+        &contract[contract.len() - 4..]
+    )
+}
+
+/// Instructions creating an unsized collection NFT owned by `authority` and minting it
+/// to the authority token account, the rent paid by `payer`
+pub fn create_collection_instructions(
+    authority: &Pubkey,
+    payer: &Pubkey,
+    collection_mint: &Pubkey,
+    name: &str,
+) -> Vec<Instruction> {
+    let metadata = Metadata::find_pda(collection_mint).0;
+    let master_edition = MasterEdition::find_pda(collection_mint).0;
+    let token_account =
+        spl_associated_token_account::get_associated_token_address(authority, collection_mint);
+
+    let create = CreateV1Builder::new()
+        .metadata(metadata)
+        .master_edition(Some(master_edition))
+        .mint(*collection_mint, true)
+        .authority(*authority)
+        .payer(*payer)
+        .update_authority(*authority, true)
+        .spl_token_program(Some(spl_token::ID))
+        .name(name.to_string())
+        .symbol(BRIDGED_SYMBOL.to_string())
+        .uri(String::new())
+        .seller_fee_basis_points(0)
+        .token_standard(TokenStandard::NonFungible)
+        .print_supply(PrintSupply::Zero)
+        .instruction();
+    let mint = MintV1Builder::new()
+        .token(token_account)
+        .token_owner(Some(*authority))
+        .metadata(metadata)
+        .master_edition(Some(master_edition))
+        .mint(*collection_mint)
+        .authority(*authority)
+        .payer(*payer)
+        .amount(1)
+        .instruction();
+    vec![create, mint]
+}
+
+/// Instruction setting the collection of a minted token and verifying it, signed by
+/// `authority` as update authority of both
+pub fn verify_collection_instruction(
+    authority: &Pubkey,
+    mint: &Pubkey,
+    collection_mint: &Pubkey,
+) -> Instruction {
+    SetAndVerifyCollectionBuilder::new()
+        .metadata(Metadata::find_pda(mint).0)
+        .collection_authority(*authority)
+        .payer(*authority)
+        .update_authority(*authority)
+        .collection_mint(*collection_mint)
+        .collection(Metadata::find_pda(collection_mint).0)
+        .collection_master_edition_account(MasterEdition::find_pda(collection_mint).0)
+        .instruction()
+}
+
+/// Instructions of a destination mint, the bridge program CreateNft has no collection
+/// field so the collection is set and verified in the same transaction
+pub fn mint_instructions(
+    create_nft: Instruction,
+    authority: &Pubkey,
+    mint: &Pubkey,
+    collection_mint: &Pubkey,
+) -> Vec<Instruction> {
+    vec![
+        create_nft,
+        verify_collection_instruction(authority, mint, collection_mint),
+    ]
+}
+
+/// Whether `collection_mint` has a metadata account, required to verify tokens into it
+pub async fn collection_exists(client: &SolanaClient, collection_mint: &Pubkey) -> Result<bool> {
+    client
+        .account_exists(&Metadata::find_pda(collection_mint).0)
+        .await
+}
+
+/// Collection of the tokens bridged from `origin_contract`, created on the first mint
+/// of the contract when the registry has none
+pub async fn collection_mint_for(
+    client: &SolanaClient,
+    db: &Database,
+    origin_contract: &str,
+    request_id: &str,
+) -> Result<Pubkey> {
+    if let Some(entry) = types::get_collection(db, origin_contract)? {
+        return Ok(parse_pubkey("collection mint", &entry.collection_mint)?);
+    }
+
+    let collection_mint = Keypair::new();
+    let instructions = create_collection_instructions(
+        &client.signer.pubkey(),
+        &client.fee_payer_pubkey(),
+        &collection_mint.pubkey(),
+        &collection_name(origin_contract),
+    );
+    let mut transaction = client.new_transaction(&instructions);
+    let recent_blockhash = client
+        .get_latest_blockhash()
+        .await
+        .with_call_context(|| client.call_context("get_latest_blockhash", request_id))?;
+    let mut signers = client.relayer_keypairs();
+    signers.push(&collection_mint);
+    transaction.sign(&signers, recent_blockhash);
+    let signature = client
+        .send_and_confirm_transaction(transaction)
+        .await
+        .with_call_context(|| client.call_context("create_collection", request_id))?;
+    info!(
+        "Collection {} of {origin_contract} created with signature {signature}",
+        collection_mint.pubkey()
+    );
+
+    types::set_collection(
+        db,
+        &CollectionEntry::new(
+            origin_contract,
+            &collection_mint.pubkey().to_string(),
+            true,
+            client.clock.now(),
+        ),
+    )?;
+    Ok(collection_mint.pubkey())
+}
+
+#[cfg(test)]
+mod collection_test {
+    use solana_sdk::{pubkey::Pubkey, signer::Signer};
+    use test_support::{mock_rpc, test_db};
+
+    use crate::{
+        collection_mint_for, collection_name, mint_instructions, test_utils::test_client,
+        BRIDGED_SYMBOL,
+    };
+
+    const CONTRACT: &str = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+
+    #[tokio::test]
+    async fn test_collection_created_once_per_contract() {
+        let db = test_db();
+        let client = test_client(mock_rpc([]));
+
+        let collection = collection_mint_for(&client, &db, CONTRACT, "request1")
+            .await
+            .unwrap();
+        let entry = types::get_collection(&db, CONTRACT).unwrap().unwrap();
+        assert_eq!(entry.collection_mint, collection.to_string());
+        assert_eq!(
+            entry.origin_contract,
+            "0x5fbdb2315678afecb367f032d93f642f64180aa3"
+        );
+        assert!(entry.created_by_relayer);
+
+        // Later mints of the contract reuse it, whatever the address case
+        assert_eq!(
+            collection_mint_for(&client, &db, &CONTRACT.to_lowercase(), "request2")
+                .await
+                .unwrap(),
+            collection
+        );
+        assert_eq!(types::list_collections(&db).unwrap().len(), 1);
+        assert!(collection_name(CONTRACT).len() <= 32);
+        assert_eq!(collection_name(CONTRACT), "Bridged 0x5fbd...0aa3");
+        assert_eq!(BRIDGED_SYMBOL, "BNFT");
+    }
+
+    #[test]
+    fn test_mint_instructions_verify_collection() {
+        let client = test_client(mock_rpc([]));
+        let authority = client.signer.pubkey();
+        let (mint, collection) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let create_nft = solana_sdk::instruction::Instruction::new_with_bytes(
+            client.bridge_program,
+            &[1],
+            vec![],
+        );
+
+        let instructions = mint_instructions(create_nft.clone(), &authority, &mint, &collection);
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(instructions[0], create_nft);
+
+        let verify = &instructions[1];
+        assert_eq!(verify.program_id, mpl_token_metadata::ID);
+        // SetAndVerifyCollection of the token metadata program
+        assert_eq!(verify.data[0], 25);
+        let accounts: Vec<Pubkey> = verify.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(
+            accounts[..7],
+            [
+                mpl_token_metadata::accounts::Metadata::find_pda(&mint).0,
+                authority,
+                authority,
+                authority,
+                collection,
+                mpl_token_metadata::accounts::Metadata::find_pda(&collection).0,
+                mpl_token_metadata::accounts::MasterEdition::find_pda(&collection).0,
+            ]
+        );
+        assert!(verify.accounts[1].is_signer);
+    }
+}
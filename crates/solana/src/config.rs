@@ -1,60 +1,312 @@
 use anchor_lang::declare_program;
-use eyre::Result;
-use solana_client::rpc_client::RpcClient;
+use arc_swap::ArcSwap;
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    instruction::Instruction,
     pubkey::Pubkey,
     signature::{read_keypair_file, Keypair},
+    signer::Signer,
+    transaction::Transaction,
+};
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64},
+        Arc,
+    },
+};
+use types::{
+    system_clock, BRequest, BrandingConfig, BridgeAddresses, BridgeDeployment, BridgeError,
+    CallContext, Chains, ConfirmationStrategy, MetadataPinning, Result, RpcHealth, RpcTimeouts,
+    SharedClock, SharedEventCursor, TxSender, UriPolicy,
 };
-use std::{str::FromStr, sync::Arc};
-use tokio::sync::mpsc::Sender;
-use types::TxMessage;
 
 declare_program!(solana_bridge);
 
-#[derive(Clone)]
-pub struct SolanaClient {
+/// RPC client and websocket URL of the node the client calls
+pub struct SolanaNode {
     pub rpc: Arc<RpcClient>,
     pub ws_url: String,
+}
+
+impl SolanaNode {
+    pub fn new(rpc: RpcClient, ws_url: &str) -> Self {
+        SolanaNode {
+            rpc: Arc::new(rpc),
+            ws_url: ws_url.to_string(),
+        }
+    }
+
+    /// Node of the URLs, nothing is called until the client is used
+    pub fn connect(rpc_url: &str, ws_url: &str) -> Self {
+        SolanaNode::new(
+            RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed()),
+            ws_url,
+        )
+    }
+
+    /// Node to share between the clones of a client
+    pub fn shared(self) -> Arc<ArcSwap<SolanaNode>> {
+        Arc::new(ArcSwap::from_pointee(self))
+    }
+}
+
+#[derive(Clone)]
+pub struct SolanaClient {
+    /// Shared by the clones of the client, the tx processors and listeners included, all
+    /// of them move to the node adopted by one
+    pub node: Arc<ArcSwap<SolanaNode>>,
+    /// Backend authority of the bridge program, signs the bridge instructions
     pub signer: Arc<Keypair>,
+    /// Pays the transaction fees and the rent of the collections when set, the signer
+    /// otherwise
+    pub fee_payer: Option<Arc<Keypair>>,
+    /// Program and account the client calls, the current ones of the deployment unless
+    /// the client is the one of a request created before an upgrade
     pub bridge_program: Pubkey,
     pub bridge_account: Pubkey,
-    pub tx_channel: Sender<TxMessage>,
+    pub tx_channel: TxSender,
     pub block_explorer: String,
+    pub uri_policy: UriPolicy,
+    /// Metadata is pinned before minting when set
+    pub metadata_pinning: Option<MetadataPinning>,
+    /// Name and symbol prefixes of the mints, provenance appended to their token URIs
+    pub branding: BrandingConfig,
+    pub timeouts: RpcTimeouts,
+    /// Cleared when the bridge account backend differs from the signer
+    pub authorized_backend: Arc<AtomicBool>,
+    /// Transactions not sent for a missing signer
+    pub missing_signers: Arc<AtomicU64>,
+    /// Transactions resent with a fresh blockhash after theirs expired
+    pub blockhash_resends: Arc<AtomicU64>,
+    /// Local test validator, finalized commitment is not reached promptly there
+    pub dev_mode: bool,
+    /// Commitment the bridge events are received at
+    pub confirmation: ConfirmationStrategy,
+    /// Slot of the last bridge event processed
+    pub event_cursor: SharedEventCursor,
+    /// Outcomes of the calls to the nodes, shared by the clones of the client
+    pub rpc_health: RpcHealth,
+    /// Bridge addresses of the new requests and the ones they replaced, None when the
+    /// client only knows `bridge_program` and `bridge_account`
+    pub bridge_deployment: Option<Arc<BridgeDeployment>>,
+    /// Mints wait for the destination account to fund its destination token account,
+    /// the relayer doesn't pay its rent
+    pub require_prefunded_ata: bool,
+    /// Wall clock the request times are read from, shared with the relayer state
+    pub clock: SharedClock,
+}
+
+impl SolanaClient {
+    pub fn rpc(&self) -> Arc<RpcClient> {
+        self.node.load().rpc.clone()
+    }
+
+    pub fn ws_url(&self) -> String {
+        self.node.load().ws_url.clone()
+    }
+
+    /// Calls `rpc` from now on, the other clones of the client keep their node
+    pub fn set_rpc(&mut self, rpc: RpcClient) {
+        self.node = SolanaNode::new(rpc, &self.ws_url()).shared();
+    }
+
+    /// Clone of the client calling another node, the other clones keep theirs
+    pub fn with_endpoints(&self, rpc_url: &str, ws_url: &str) -> Self {
+        SolanaClient {
+            node: SolanaNode::connect(rpc_url, ws_url).shared(),
+            ..self.clone()
+        }
+    }
+
+    /// Moves every clone of the client to the node of `candidate`
+    pub fn adopt_endpoints(&self, candidate: &SolanaClient) {
+        self.node.store(candidate.node.load_full());
+    }
+
+    /// Commitment the events and transactions are processed at. With confirmed events
+    /// the mints wait for the custody transfer to be finalized
+    pub fn processing_commitment(&self) -> CommitmentConfig {
+        if self.dev_mode || self.confirmation == ConfirmationStrategy::Confirmed {
+            CommitmentConfig::confirmed()
+        } else {
+            CommitmentConfig::finalized()
+        }
+    }
+
+    /// Account paying the fees of the relayer transactions, its balance is the one to keep
+    /// funded
+    pub fn fee_payer_pubkey(&self) -> Pubkey {
+        self.fee_payer
+            .as_ref()
+            .map_or_else(|| self.signer.pubkey(), |fee_payer| fee_payer.pubkey())
+    }
+
+    /// Keypairs of the relayer in signature order, the fee payer first when it is separate
+    pub fn relayer_keypairs(&self) -> Vec<&Keypair> {
+        self.fee_payer
+            .iter()
+            .map(Arc::as_ref)
+            .chain([self.signer.as_ref()])
+            .collect()
+    }
+
+    /// Unsigned transaction of `instructions` paid by the fee payer
+    pub fn new_transaction(&self, instructions: &[Instruction]) -> Transaction {
+        Transaction::new_with_payer(instructions, Some(&self.fee_payer_pubkey()))
+    }
+
+    /// Context of a bridge program call made for `request_id`
+    pub fn call_context(&self, operation: &str, request_id: &str) -> CallContext {
+        CallContext::new(Chains::SOLANA, operation)
+            .request(request_id)
+            .contract(self.bridge_program)
+    }
+
+    pub fn with_bridge_deployment(mut self, bridge_deployment: Arc<BridgeDeployment>) -> Self {
+        self.bridge_deployment = Some(bridge_deployment);
+        self
+    }
+
+    pub fn with_prefunded_ata(mut self, require_prefunded_ata: bool) -> Self {
+        self.require_prefunded_ata = require_prefunded_ata;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Clone of the client calling the bridge program and account `request` was created
+    /// against. Requests without recorded addresses use the first ones of the deployment
+    pub fn for_request(&self, request: &BRequest) -> Result<Self> {
+        let recorded = match (&request.bridge_addresses, &self.bridge_deployment) {
+            (Some(addresses), _) => addresses,
+            (None, Some(deployment)) => &deployment.legacy,
+            (None, None) => return Ok(self.clone()),
+        };
+        let pubkey = |address: &str| {
+            Pubkey::from_str(address)
+                .map_err(|e| BridgeError::Other(format!("Invalid bridge address {address}: {e}")))
+        };
+        Ok(SolanaClient {
+            bridge_program: pubkey(&recorded.solana_program)?,
+            bridge_account: pubkey(&recorded.solana_account)?,
+            ..self.clone()
+        })
+    }
+
+    /// Addresses of the deployment of the program and account the client calls
+    pub fn bridge_addresses(&self) -> Option<BridgeAddresses> {
+        self.bridge_deployment
+            .as_ref()?
+            .find(|addresses| {
+                addresses.solana_program == self.bridge_program.to_string()
+                    && addresses.solana_account == self.bridge_account.to_string()
+            })
+            .cloned()
+    }
 }
 
 pub fn solana_connection(
     rpc_url: &str,
     ws_url: &str,
     keypair_path: &str,
+    fee_payer_path: Option<&str>,
     bridge_program: &str,
     bridge_account: &str,
-    tx_channel: Sender<TxMessage>,
+    tx_channel: TxSender,
     block_explorer: &str,
+    uri_policy: UriPolicy,
+    metadata_pinning: Option<MetadataPinning>,
+    branding: BrandingConfig,
+    timeouts: RpcTimeouts,
+    dev_mode: bool,
+    confirmation: ConfirmationStrategy,
 ) -> Result<SolanaClient> {
-    let client: RpcClient =
-        RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
-
     let payer = read_keypair_file(keypair_path)
         .map_err(|e| format!("Solana keypair file not found, {}", e))
         .unwrap();
-    let bridge_program_pubkey = Pubkey::from_str(bridge_program)?;
-    let bridge_account_pubkey = Pubkey::from_str(bridge_account)?;
+    let fee_payer = fee_payer_path
+        .map(|path| {
+            read_keypair_file(path).map_err(|e| {
+                BridgeError::Other(format!("Solana fee payer keypair file not found, {}", e))
+            })
+        })
+        .transpose()?
+        // The backend keypair given again pays as without fee payer
+        .filter(|fee_payer| fee_payer.pubkey() != payer.pubkey());
+    let bridge_program_pubkey =
+        Pubkey::from_str(bridge_program).map_err(BridgeError::from_error)?;
+    let bridge_account_pubkey =
+        Pubkey::from_str(bridge_account).map_err(BridgeError::from_error)?;
 
     let solana_client = SolanaClient {
-        rpc: Arc::new(client),
-        ws_url: ws_url.to_string(),
+        node: SolanaNode::connect(rpc_url, ws_url).shared(),
         signer: Arc::new(payer),
+        fee_payer: fee_payer.map(Arc::new),
         bridge_program: bridge_program_pubkey,
         bridge_account: bridge_account_pubkey,
         tx_channel: tx_channel,
         block_explorer: block_explorer.to_string(),
+        uri_policy,
+        metadata_pinning,
+        branding,
+        timeouts,
+        authorized_backend: Arc::new(AtomicBool::new(true)),
+        missing_signers: Arc::default(),
+        blockhash_resends: Arc::default(),
+        dev_mode,
+        confirmation,
+        event_cursor: SharedEventCursor::default(),
+        rpc_health: RpcHealth::default(),
+        bridge_deployment: None,
+        require_prefunded_ata: false,
+        clock: system_clock(),
     };
 
     Ok(solana_client)
 }
 
+/// Client of a read-only relayer: a throwaway keypair instead of the wallet file, the
+/// node is never called by the reads it serves
+pub fn solana_read_only_client(
+    rpc_url: &str,
+    ws_url: &str,
+    bridge_program: &str,
+    bridge_account: &str,
+    tx_channel: TxSender,
+    block_explorer: &str,
+    timeouts: RpcTimeouts,
+) -> Result<SolanaClient> {
+    Ok(SolanaClient {
+        node: SolanaNode::connect(rpc_url, ws_url).shared(),
+        signer: Arc::new(Keypair::new()),
+        fee_payer: None,
+        bridge_program: Pubkey::from_str(bridge_program).map_err(BridgeError::from_error)?,
+        bridge_account: Pubkey::from_str(bridge_account).map_err(BridgeError::from_error)?,
+        tx_channel,
+        block_explorer: block_explorer.to_string(),
+        uri_policy: UriPolicy::default(),
+        metadata_pinning: None,
+        branding: BrandingConfig::default(),
+        timeouts,
+        authorized_backend: Arc::new(AtomicBool::new(true)),
+        missing_signers: Arc::default(),
+        blockhash_resends: Arc::default(),
+        dev_mode: false,
+        confirmation: ConfirmationStrategy::default(),
+        event_cursor: SharedEventCursor::default(),
+        rpc_health: RpcHealth::default(),
+        bridge_deployment: None,
+        require_prefunded_ata: false,
+        clock: system_clock(),
+    })
+}
+
 pub async fn get_latest_slot(client: &SolanaClient) -> Result<u64> {
-    let latest_slot = client.rpc.get_slot()?;
+    let latest_slot = client.get_slot().await?;
     Ok(latest_slot)
 }
@@ -0,0 +1,360 @@
+use std::time::Duration;
+
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey, signature::Signature};
+use types::{BridgeError, Result, Timestamp};
+
+use crate::{bridge_holds_token_cached, AccountsCache, SolanaClient, SolanaError};
+
+/// Latest transactions of the bridge token account read to date its custody
+pub const CUSTODY_SIGNATURE_LIMIT: usize = 10;
+/// Transfers dated up to this long before the request still establish its custody, the
+/// relayer clock may run ahead of the validators
+pub const CUSTODY_CLOCK_SKEW: Duration = Duration::from_secs(30);
+
+/// Custody of the token of a request in the bridge token account
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolanaCustody {
+    NotHeld,
+    /// Held since before the request was created, or since an undated transfer. The
+    /// balance is left by an earlier flow of the mint, not by a transfer for this request
+    Stale(Option<String>),
+    /// Transferred after the request was created, by the transaction of the signature
+    Established(String),
+}
+
+/// Custody of a token held by the bridge from the transactions of its token account,
+/// newest first. The last successful one established the balance: the lock transaction
+/// of the request whatever its time, else a transfer not older than the request by more
+/// than `CUSTODY_CLOCK_SKEW`
+pub fn dated_custody(
+    signatures: &[RpcConfirmedTransactionStatusWithSignature],
+    created_at: Timestamp,
+    lock_tx: Option<&str>,
+) -> SolanaCustody {
+    let Some(latest) = signatures.iter().find(|status| status.err.is_none()) else {
+        return SolanaCustody::Stale(None);
+    };
+    if lock_tx == Some(latest.signature.as_str()) {
+        return SolanaCustody::Established(latest.signature.clone());
+    }
+    let earliest = created_at
+        .as_secs()
+        .saturating_sub(CUSTODY_CLOCK_SKEW.as_secs());
+    match latest.block_time.and_then(|time| u64::try_from(time).ok()) {
+        Some(block_time) if block_time >= earliest => {
+            SolanaCustody::Established(latest.signature.clone())
+        }
+        _ => SolanaCustody::Stale(Some(latest.signature.clone())),
+    }
+}
+
+/// Custody of `mint` for a request created at `created_at` with the lock transaction
+/// `lock_tx`. The balance of the bridge token account only counts when the transfer that
+/// set it is the lock or is dated after the request, see `dated_custody`
+pub async fn bridge_custody(
+    client: &SolanaClient,
+    mint: &Pubkey,
+    created_at: Timestamp,
+    lock_tx: Option<&str>,
+) -> Result<SolanaCustody> {
+    bridge_custody_cached(client, &AccountsCache::default(), mint, created_at, lock_tx).await
+}
+
+/// Same as `bridge_custody` reading the balance from `cache` when prefetched, the
+/// signatures of the token account are always read
+pub async fn bridge_custody_cached(
+    client: &SolanaClient,
+    cache: &AccountsCache,
+    mint: &Pubkey,
+    created_at: Timestamp,
+    lock_tx: Option<&str>,
+) -> Result<SolanaCustody> {
+    if !bridge_holds_token_cached(client, cache, mint).await? {
+        return Ok(SolanaCustody::NotHeld);
+    }
+
+    let bridge_token_account =
+        spl_associated_token_account::get_associated_token_address(&client.bridge_account, mint);
+    let signatures = client
+        .get_signatures_for_address(&bridge_token_account, CUSTODY_SIGNATURE_LIMIT)
+        .await?;
+    Ok(dated_custody(&signatures, created_at, lock_tx))
+}
+
+/// Amount of `mint` in the bridge token account, 0 once the account is closed
+pub async fn bridge_token_amount(client: &SolanaClient, mint: &Pubkey) -> Result<u64> {
+    let bridge_token_account =
+        spl_associated_token_account::get_associated_token_address(&client.bridge_account, mint);
+    let Some(account) = client.get_account(&bridge_token_account).await? else {
+        return Ok(0);
+    };
+    let token_account =
+        spl_token::state::Account::unpack(&account.data).map_err(BridgeError::from_error)?;
+    Ok(token_account.amount)
+}
+
+/// Checks the release `signature` took the token of `mint` out of the bridge token
+/// account. A confirmed release leaving the balance in custody is an error, the request
+/// is not refunded and its send intent is kept for the retry
+pub async fn confirm_custody_released(
+    client: &SolanaClient,
+    mint: &Pubkey,
+    signature: &Signature,
+) -> Result<()> {
+    if bridge_token_amount(client, mint).await? > 0 {
+        return Err(SolanaError::CustodyNotReleased {
+            mint: mint.to_string(),
+            signature: signature.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod custody_test {
+    use std::sync::Arc;
+
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::signature::Signature;
+    use test_support::{
+        missing_account_result, mock_rpc, request_in_status, signatures_for_address_result,
+        solana_key, test_db, token_account_result,
+    };
+    use types::{Chains, MockClock, Status, Timestamp, RECEIVED_EXPIRY};
+
+    use crate::{
+        bridge_custody, check_token_owner, confirm_custody_released, test_utils::test_client,
+        SolanaCustody, CUSTODY_CLOCK_SKEW,
+    };
+
+    const CREATED_AT: u64 = 1_700_000_000;
+
+    async fn custody(signatures: &[(&str, Option<i64>, bool)]) -> SolanaCustody {
+        custody_of_lock(signatures, None).await
+    }
+
+    async fn custody_of_lock(
+        signatures: &[(&str, Option<i64>, bool)],
+        lock_tx: Option<&str>,
+    ) -> SolanaCustody {
+        let mint = solana_key(1);
+        let mut client = test_client(mock_rpc([]));
+        let bridge = client.bridge_account;
+        client.set_rpc(mock_rpc([
+            (
+                RpcRequest::GetAccountInfo,
+                token_account_result(&mint, &bridge, 1),
+            ),
+            (
+                RpcRequest::GetSignaturesForAddress,
+                signatures_for_address_result(signatures),
+            ),
+        ]));
+        bridge_custody(
+            &client,
+            &mint,
+            Timestamp::from_millis(CREATED_AT * 1000 + 400),
+            lock_tx,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fresh_custody_is_established() {
+        let created = CREATED_AT as i64;
+        assert_eq!(
+            custody(&[
+                ("fresh", Some(created + 30), false),
+                ("old", Some(created - 3600), false)
+            ])
+            .await,
+            SolanaCustody::Established("fresh".to_string())
+        );
+        // A failed transaction after the transfer changed no balance
+        assert_eq!(
+            custody(&[
+                ("failed", Some(created + 60), true),
+                ("fresh", Some(created + 5), false)
+            ])
+            .await,
+            SolanaCustody::Established("fresh".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stale_custody_is_not_accepted() {
+        let created = CREATED_AT as i64;
+        // Balance left by an earlier bridge of the same mint
+        assert_eq!(
+            custody(&[("old", Some(created - 3600), false)]).await,
+            SolanaCustody::Stale(Some("old".to_string()))
+        );
+        assert_eq!(
+            custody(&[("undated", None, false)]).await,
+            SolanaCustody::Stale(Some("undated".to_string()))
+        );
+        assert_eq!(custody(&[]).await, SolanaCustody::Stale(None));
+
+        let mint = solana_key(1);
+        let client = test_client(mock_rpc([(
+            RpcRequest::GetAccountInfo,
+            token_account_result(&mint, &solana_key(6), 1),
+        )]));
+        assert_eq!(
+            bridge_custody(&client, &mint, Timestamp::from_secs(CREATED_AT), None)
+                .await
+                .unwrap(),
+            SolanaCustody::NotHeld
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custody_boundary_at_creation() {
+        let created = CREATED_AT as i64;
+        // Same second as the creation, its block time is truncated
+        assert_eq!(
+            custody(&[("same-second", Some(created), false)]).await,
+            SolanaCustody::Established("same-second".to_string())
+        );
+        assert_eq!(
+            custody(&[("seconds-after", Some(created + 2), false)]).await,
+            SolanaCustody::Established("seconds-after".to_string())
+        );
+        // The relayer clock ahead of the validators by up to the skew
+        let skew = CUSTODY_CLOCK_SKEW.as_secs() as i64;
+        assert_eq!(
+            custody(&[("second-before", Some(created - 1), false)]).await,
+            SolanaCustody::Established("second-before".to_string())
+        );
+        assert_eq!(
+            custody(&[("skew-before", Some(created - skew), false)]).await,
+            SolanaCustody::Established("skew-before".to_string())
+        );
+        assert_eq!(
+            custody(&[("past-skew", Some(created - skew - 1), false)]).await,
+            SolanaCustody::Stale(Some("past-skew".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_release_confirmed_by_the_custody_balance() {
+        let mint = solana_key(1);
+        let release = Signature::from([3u8; 64]);
+        let mut client = test_client(mock_rpc([]));
+        let bridge = client.bridge_account;
+
+        // Still held after the release, the refund is not recorded
+        client.set_rpc(mock_rpc([(
+            RpcRequest::GetAccountInfo,
+            token_account_result(&mint, &bridge, 1),
+        )]));
+        let err = confirm_custody_released(&client, &mint, &release)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("still holds"), "{err}");
+
+        client.set_rpc(mock_rpc([(
+            RpcRequest::GetAccountInfo,
+            token_account_result(&mint, &bridge, 0),
+        )]));
+        confirm_custody_released(&client, &mint, &release)
+            .await
+            .unwrap();
+
+        // The bridge token account closed by the release
+        client.set_rpc(mock_rpc([(
+            RpcRequest::GetAccountInfo,
+            missing_account_result(),
+        )]));
+        confirm_custody_released(&client, &mint, &release)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_lock_tx_establishes_custody_whatever_its_time() {
+        let created = CREATED_AT as i64;
+        assert_eq!(
+            custody_of_lock(&[("lock", Some(created - 3600), false)], Some("lock")).await,
+            SolanaCustody::Established("lock".to_string())
+        );
+        assert_eq!(
+            custody_of_lock(&[("lock", None, false)], Some("lock")).await,
+            SolanaCustody::Established("lock".to_string())
+        );
+        // Another transfer set the balance after the lock
+        assert_eq!(
+            custody_of_lock(
+                &[
+                    ("other", Some(created - 3600), false),
+                    ("lock", None, false)
+                ],
+                Some("lock")
+            )
+            .await,
+            SolanaCustody::Stale(Some("other".to_string()))
+        );
+        // A failed lock set no balance
+        assert_eq!(
+            custody_of_lock(&[("lock", Some(created), true)], Some("lock")).await,
+            SolanaCustody::Stale(None)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stale_custody_keeps_request_received() {
+        let db = test_db();
+        let request = request_in_status(Chains::SOLANA, "", Status::RequestReceived);
+        request.save(&db).unwrap();
+        let created = request.created_at.as_secs() as i64;
+
+        let mut client = test_client(mock_rpc([]));
+        let bridge = client.bridge_account;
+        client.set_rpc(mock_rpc([
+            (
+                RpcRequest::GetAccountInfo,
+                token_account_result(&solana_key(1), &bridge, 1),
+            ),
+            (
+                RpcRequest::GetSignaturesForAddress,
+                signatures_for_address_result(&[("earlier-flow", Some(created - 600), false)]),
+            ),
+        ]));
+        check_token_owner(&db, &client, &request.id).await.unwrap();
+
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::RequestReceived);
+        assert_eq!(stored.custody_signature, None);
+    }
+    #[tokio::test]
+    async fn test_custody_expiry_follows_the_client_clock() {
+        let db = test_db();
+        let mut request = request_in_status(Chains::SOLANA, "", Status::RequestReceived);
+        request.created_at = Timestamp::from_secs(CREATED_AT);
+        request.save(&db).unwrap();
+        let not_held = || {
+            mock_rpc([(
+                RpcRequest::GetAccountInfo,
+                token_account_result(&solana_key(1), &solana_key(6), 1),
+            )])
+        };
+
+        // The clock stepped back before the creation, the request keeps waiting
+        let clock = MockClock::new(Timestamp::from_secs(CREATED_AT - 3600));
+        let mut client = test_client(not_held()).with_clock(Arc::new(clock.clone()));
+        check_token_owner(&db, &client, &request.id).await.unwrap();
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::RequestReceived);
+
+        let expired_at = request.created_at.saturating_add(RECEIVED_EXPIRY);
+        clock.set(expired_at);
+        client.set_rpc(not_held());
+        check_token_owner(&db, &client, &request.id).await.unwrap();
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::Canceled);
+        assert_eq!(stored.history.last().unwrap().time, expired_at);
+    }
+}
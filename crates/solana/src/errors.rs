@@ -0,0 +1,89 @@
+use types::BridgeError;
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum SolanaError {
+    #[error("Relayer wallet {0} is not the authorized backend of the bridge program")]
+    NotAuthorizedBackend(String),
+    #[error("Invalid {field} {value:?} in request data")]
+    InvalidData { field: &'static str, value: String },
+    #[error("Could not decode metadata account of mint {0}")]
+    MetadataDecode(String),
+    /// Transaction creating an account that already exists
+    #[error("Account {0} already in use")]
+    AccountAlreadyInitialized(String),
+    /// Transaction needing the signature of a key the relayer doesn't hold
+    #[error("Transaction needs a signature of {0} the relayer does not hold")]
+    MissingSigner(String),
+    /// Fees paid by a separate wallet while the bridge program makes the backend pay
+    #[error(
+        "Bridge program needs the backend {backend} to pay, fee payer {fee_payer} not accepted"
+    )]
+    FeePayerNotAccepted { fee_payer: String, backend: String },
+    /// Program event data not in the layout of the bridge events
+    #[error("Malformed event data: {0}")]
+    MalformedEvent(String),
+    /// Release confirmed while the bridge token account still holds the token
+    #[error("Bridge token account of mint {mint} still holds the token after release {signature}")]
+    CustodyNotReleased { mint: String, signature: String },
+}
+
+impl From<SolanaError> for BridgeError {
+    fn from(err: SolanaError) -> Self {
+        match err {
+            SolanaError::NotAuthorizedBackend(signer) => BridgeError::NotAuthorizedBackend(signer),
+            SolanaError::InvalidData { .. } => BridgeError::CorruptedData(err.to_string()),
+            SolanaError::AccountAlreadyInitialized(account) => {
+                BridgeError::AccountAlreadyInitialized(account)
+            }
+            SolanaError::MissingSigner(signer) => BridgeError::MissingSigner(signer),
+            SolanaError::MetadataDecode(_)
+            | SolanaError::FeePayerNotAccepted { .. }
+            | SolanaError::MalformedEvent(_)
+            | SolanaError::CustodyNotReleased { .. } => BridgeError::Other(err.to_string()),
+        }
+    }
+}
+
+/// Account of the "already in use" error the system program logs when a transaction
+/// creates an existing account
+pub fn already_in_use_account(logs: &[String]) -> Option<String> {
+    let log = logs.iter().find(|log| log.ends_with("already in use"))?;
+    let account = log
+        .split_once("address: ")
+        .and_then(|(_, rest)| rest.split([',', ' ']).next())
+        .unwrap_or_default();
+    Some(account.to_string())
+}
+
+#[cfg(test)]
+mod errors_test {
+    use types::BridgeError;
+
+    use crate::{already_in_use_account, SolanaError};
+
+    #[test]
+    fn test_already_in_use_logs_and_conversion() {
+        let logs = [
+            "Program 11111111111111111111111111111111 invoke [2]".to_string(),
+            "Create Account: account Address { address: 7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU, base: None } already in use".to_string(),
+            "Program 11111111111111111111111111111111 failed: custom program error: 0x0".to_string(),
+        ];
+        assert_eq!(
+            already_in_use_account(&logs).as_deref(),
+            Some("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+        );
+        assert_eq!(already_in_use_account(&logs[..1]), None);
+
+        assert_eq!(
+            BridgeError::from(SolanaError::AccountAlreadyInitialized("mint".to_string())),
+            BridgeError::AccountAlreadyInitialized("mint".to_string())
+        );
+        assert!(matches!(
+            BridgeError::from(SolanaError::InvalidData {
+                field: "signature",
+                value: "0x".to_string()
+            }),
+            BridgeError::CorruptedData(_)
+        ));
+    }
+}
@@ -0,0 +1,277 @@
+use std::time::Duration;
+
+use log::info;
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+use storage::db::Database;
+use types::{
+    capture_replay_input, Chains, FinalityStep, MessageMint, ReplayInput, Result, Timestamp,
+    TxMessage, WrapCallContext, FINALITY_TIMEOUT,
+};
+
+use crate::{get_metadata, parse_signature, SolanaClient};
+
+/// Operation of the signature status reads of the finality checks in the replay bundles
+pub const FINALITY_READ: &str = "get_signature_statuses";
+
+/// Finality of a custody transfer from its signature status, `waited` after it was seen
+/// confirmed. Transfers unknown to the node or still confirmed are waited for until
+/// `FINALITY_TIMEOUT`, their fork may still be finalized
+pub fn finality_step(status: Option<&TransactionStatus>, waited: Duration) -> FinalityStep {
+    match status {
+        Some(status) if status.err.is_some() => {
+            FinalityStep::RolledBack("custody transaction failed".to_string())
+        }
+        Some(status)
+            if status.confirmation_status == Some(TransactionConfirmationStatus::Finalized) =>
+        {
+            FinalityStep::Finalized
+        }
+        // Rooted transactions of old nodes have neither a confirmation status nor a count
+        Some(status) if status.confirmation_status.is_none() && status.confirmations.is_none() => {
+            FinalityStep::Finalized
+        }
+        _ if waited >= FINALITY_TIMEOUT => {
+            FinalityStep::RolledBack(format!("not finalized after {} seconds", waited.as_secs()))
+        }
+        _ => FinalityStep::Wait,
+    }
+}
+
+/// Second phase of a request whose custody was seen at confirmed commitment. Once the
+/// custody transfer is finalized the mint is sent, a rolled back transfer returns the
+/// request to RequestReceived
+pub async fn check_finality(
+    db: &Database,
+    client: &SolanaClient,
+    request_id: &str,
+    now: Timestamp,
+) -> Result<FinalityStep> {
+    let Some(mut request) = types::request_data(request_id, db)? else {
+        return Ok(FinalityStep::Wait);
+    };
+    let Some(pending) = request.finality_pending.clone() else {
+        return Ok(FinalityStep::Wait);
+    };
+    if !request.awaiting_finality() {
+        return Ok(FinalityStep::Wait);
+    }
+
+    let signature = parse_signature(&pending.signature)?;
+    let status = client
+        .get_signature_status_with_history(&signature)
+        .await
+        .with_call_context(|| client.call_context("get_signature_statuses", request_id))?;
+    capture_replay_input(
+        db,
+        &request,
+        now,
+        ReplayInput::ChainRead {
+            chain: Chains::SOLANA,
+            operation: FINALITY_READ.to_string(),
+            result: serde_json::to_value(&status)?,
+        },
+    );
+    let step = finality_step(status.as_ref(), pending.observed_at.elapsed_until(now));
+    match &step {
+        FinalityStep::Wait => info!("Custody of request {request_id} not finalized yet"),
+        FinalityStep::RolledBack(reason) => request.roll_back_custody(db, reason, now)?,
+        FinalityStep::Finalized => {
+            let metadata = match pending.token_metadata {
+                Some(metadata) => metadata,
+                None => get_metadata(client, &request.input.contract_or_mint)
+                    .await
+                    .with_call_context(|| client.call_context("get_metadata", request_id))?,
+            };
+            request.finalize_custody(db, now)?;
+            client
+                .tx_channel
+                .send(TxMessage::Mint(MessageMint {
+                    request_id: request.id.clone(),
+                    token_metadata: metadata,
+                    trace_context: request.trace_context.clone(),
+                }))
+                .await?;
+        }
+    }
+    Ok(step)
+}
+
+/// Replays a custody finality check from the signature status it read, without the chain.
+/// A finalized custody is recorded on the request, the mint is left out
+pub fn replay_finality(
+    db: &Database,
+    request_id: &str,
+    status: Option<TransactionStatus>,
+    now: Timestamp,
+) -> Result<FinalityStep> {
+    let Some(mut request) = types::request_data(request_id, db)? else {
+        return Ok(FinalityStep::Wait);
+    };
+    let Some(pending) = request.finality_pending.clone() else {
+        return Ok(FinalityStep::Wait);
+    };
+    if !request.awaiting_finality() {
+        return Ok(FinalityStep::Wait);
+    }
+
+    let step = finality_step(status.as_ref(), pending.observed_at.elapsed_until(now));
+    match &step {
+        FinalityStep::Wait => {}
+        FinalityStep::RolledBack(reason) => request.roll_back_custody(db, reason, now)?,
+        FinalityStep::Finalized => request.finalize_custody(db, now)?,
+    }
+    Ok(step)
+}
+
+/// Keeps the token URI read while the custody waits for finality, for the mint
+pub(crate) fn keep_finality_metadata(
+    db: &Database,
+    request_id: &str,
+    metadata: String,
+) -> Result<()> {
+    let Some(mut request) = types::request_data(request_id, db)? else {
+        return Ok(());
+    };
+    if let Some(pending) = request.finality_pending.as_mut() {
+        pending.token_metadata = Some(metadata);
+        request.save(db)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod finality_test {
+    use std::time::Duration;
+
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::{signature::Signature, transaction::TransactionError};
+    use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+    use storage::db::Database;
+    use test_support::{mock_rpc, request_in_status, signature_status_result, test_db};
+    use types::{
+        tx_channel, BRequest, Chains, ChannelMetrics, FinalityPending, FinalityStep, Status,
+        Timestamp, TxMessage, FINALITY_TIMEOUT,
+    };
+
+    use crate::{check_finality, finality_step, test_utils::test_client};
+
+    const SEEN: Timestamp = Timestamp::from_secs(1_700_000_000);
+
+    fn status(
+        confirmation: Option<TransactionConfirmationStatus>,
+        err: Option<TransactionError>,
+    ) -> TransactionStatus {
+        TransactionStatus {
+            slot: 1,
+            confirmations: confirmation.as_ref().map(|_| 1),
+            status: Ok(()),
+            err,
+            confirmation_status: confirmation,
+        }
+    }
+
+    fn awaiting(db: &Database) -> BRequest {
+        let mut request = request_in_status(Chains::SOLANA, "", Status::TokenReceived);
+        let signature = Signature::from([3u8; 64]).to_string();
+        request.custody_signature = Some(signature.clone());
+        request.finality_pending = Some(FinalityPending {
+            signature,
+            token_metadata: Some("https://example.com/1.json".to_string()),
+            observed_at: SEEN,
+        });
+        request.save(db).unwrap();
+        request
+    }
+
+    #[test]
+    fn test_finality_steps() {
+        let confirmed = status(Some(TransactionConfirmationStatus::Confirmed), None);
+        let finalized = status(Some(TransactionConfirmationStatus::Finalized), None);
+        let failed = status(
+            Some(TransactionConfirmationStatus::Confirmed),
+            Some(TransactionError::AccountNotFound),
+        );
+        let second = Duration::from_secs(1);
+        assert_eq!(
+            finality_step(Some(&finalized), second),
+            FinalityStep::Finalized
+        );
+        assert_eq!(
+            finality_step(Some(&status(None, None)), second),
+            FinalityStep::Finalized
+        );
+        assert_eq!(finality_step(Some(&confirmed), second), FinalityStep::Wait);
+        assert_eq!(finality_step(None, second), FinalityStep::Wait);
+        assert!(matches!(
+            finality_step(Some(&failed), second),
+            FinalityStep::RolledBack(_)
+        ));
+        assert!(matches!(
+            finality_step(None, FINALITY_TIMEOUT),
+            FinalityStep::RolledBack(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mint_sent_once_finalized() {
+        let db = test_db();
+        let request = awaiting(&db);
+        let mut client = test_client(mock_rpc([(
+            RpcRequest::GetSignatureStatuses,
+            signature_status_result(Some("finalized")),
+        )]));
+        let (tx, mut rx) = tx_channel(Chains::EVM, 1, &ChannelMetrics::default());
+        client.tx_channel = tx;
+
+        let now = SEEN.saturating_add(Duration::from_secs(13));
+        let step = check_finality(&db, &client, &request.id, now)
+            .await
+            .unwrap();
+        assert_eq!(step, FinalityStep::Finalized);
+        match rx.recv().await.unwrap() {
+            TxMessage::Mint(mint) => {
+                assert_eq!(mint.request_id, request.id);
+                assert_eq!(mint.token_metadata, "https://example.com/1.json");
+            }
+            _ => panic!("expected a mint"),
+        }
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::TokenReceived);
+        assert!(!stored.awaiting_finality());
+        assert_eq!(stored.custody_signature, request.custody_signature);
+    }
+
+    #[tokio::test]
+    async fn test_never_finalized_custody_rolled_back() {
+        let db = test_db();
+        let request = awaiting(&db);
+        let confirmed = || {
+            test_client(mock_rpc([(
+                RpcRequest::GetSignatureStatuses,
+                signature_status_result(Some("confirmed")),
+            )]))
+        };
+
+        let before = SEEN.saturating_add(FINALITY_TIMEOUT - Duration::from_secs(1));
+        let step = check_finality(&db, &confirmed(), &request.id, before)
+            .await
+            .unwrap();
+        assert_eq!(step, FinalityStep::Wait);
+        assert!(types::request_data(&request.id, &db)
+            .unwrap()
+            .unwrap()
+            .awaiting_finality());
+
+        let after = SEEN.saturating_add(FINALITY_TIMEOUT);
+        let step = check_finality(&db, &confirmed(), &request.id, after)
+            .await
+            .unwrap();
+        assert!(matches!(step, FinalityStep::RolledBack(_)));
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::RequestReceived);
+        assert_eq!(
+            (stored.custody_signature, stored.finality_pending),
+            (None, None)
+        );
+    }
+}
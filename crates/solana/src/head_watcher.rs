@@ -0,0 +1,21 @@
+use std::time::Duration;
+
+use log::error;
+use types::{ChainHead, ChainHeadSender};
+
+use crate::{get_latest_slot, SolanaClient};
+
+const SLOT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Publishes the latest Solana slot
+pub async fn watch_chain_head(client: SolanaClient, head_tx: ChainHeadSender) {
+    loop {
+        match get_latest_slot(&client).await {
+            Ok(slot) => {
+                head_tx.send_replace(ChainHead::new(slot));
+            }
+            Err(e) => error!("Could not read Solana slot: {}", e),
+        }
+        tokio::time::sleep(SLOT_POLL_INTERVAL).await;
+    }
+}
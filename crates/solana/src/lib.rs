@@ -9,3 +9,39 @@ pub use read_account::*;
 
 pub mod sol_events;
 pub use sol_events::*;
+
+pub mod head_watcher;
+pub use head_watcher::*;
+
+pub mod errors;
+pub use errors::*;
+
+pub mod authorization;
+pub use authorization::*;
+
+pub mod rpc;
+pub use rpc::*;
+
+pub mod collection;
+pub use collection::*;
+
+pub mod signers;
+pub use signers::*;
+
+pub mod custody;
+pub use custody::*;
+
+pub mod backfill;
+pub use backfill::*;
+
+pub mod finality;
+pub use finality::*;
+
+pub mod accounts_cache;
+pub use accounts_cache::*;
+
+pub mod send_lookup;
+pub use send_lookup::*;
+
+#[cfg(test)]
+mod test_utils;
@@ -1,88 +1,577 @@
 use std::str::FromStr;
 
-use eyre::Result;
-use log::info;
+use log::{error, info};
 use mpl_token_metadata::accounts::Metadata;
 use solana_client::rpc_config::RpcTransactionConfig;
 use solana_sdk::{
     commitment_config::CommitmentConfig, program_pack::Pack, pubkey::Pubkey, signature::Signature,
+    transaction::VersionedTransaction,
+};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, TransactionConfirmationStatus, TransactionStatus,
+    UiTransactionEncoding,
 };
-use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding};
 use storage::db::Database;
-use types::{MessageMint, Status, TxMessage};
+use types::{
+    custody_step, BRequest, BridgeError, CallContext, Chains, ConfirmationStrategy, CustodyStep,
+    FinalityPending, InputRequest, Lamports, MessageMint, OnChainTx, RequestId, Result, RpcLimiter,
+    Status, Timestamp, TxMessage, TxState, WrapCallContext, TOKEN_ACCOUNT_RENT,
+};
+
+use crate::{
+    bridge_custody_cached, derive_mint, keep_finality_metadata, AccountsCache, SolanaClient,
+    SolanaCustody, SolanaError,
+};
+
+pub fn parse_pubkey(field: &'static str, value: &str) -> Result<Pubkey, SolanaError> {
+    Pubkey::from_str(value).map_err(|_| SolanaError::InvalidData {
+        field,
+        value: value.to_string(),
+    })
+}
 
-use crate::SolanaClient;
+pub fn parse_signature(value: &str) -> Result<Signature, SolanaError> {
+    Signature::from_str(value).map_err(|_| SolanaError::InvalidData {
+        field: "signature",
+        value: value.to_string(),
+    })
+}
+
+pub async fn get_metadata(client: &SolanaClient, token_mint: &str) -> Result<String> {
+    get_metadata_cached(client, &AccountsCache::default(), token_mint).await
+}
 
-pub fn get_metadata(client: &SolanaClient, token_mint: &str) -> Result<String> {
-    let mint_pubkey = Pubkey::from_str(token_mint).expect("Invalid mint address");
+/// Same as `get_metadata` reading the metadata account from `cache` when prefetched
+pub async fn get_metadata_cached(
+    client: &SolanaClient,
+    cache: &AccountsCache,
+    token_mint: &str,
+) -> Result<String> {
+    let mint_pubkey = parse_pubkey("mint address", token_mint)?;
 
     let (metadata_pda, _) = Metadata::find_pda(&mint_pubkey);
 
     // Fetch account data
-    let metadata_account = client
-        .rpc
-        .get_account_data(&metadata_pda)
-        .expect("Failed to get account data");
+    let metadata_account = cache.account_data(client, &metadata_pda).await?;
 
     // Deserialize Metadata
     let metadata = Metadata::from_bytes(&mut metadata_account.as_ref())
-        .expect("Failed to deserialize metadata");
+        .map_err(|_| SolanaError::MetadataDecode(token_mint.to_string()))?;
 
     Ok(metadata.uri.trim_matches('\0').to_owned())
 }
 
-pub async fn check_token_owner(db: &Database, client: &SolanaClient, request_id: &str) {
+/// Wallet owning the token account
+pub async fn token_account_owner(client: &SolanaClient, token_account: &str) -> Result<Pubkey> {
+    let token_account = parse_pubkey("token account", token_account)?;
+    let data = client.get_account_data(&token_account).await?;
+    Ok(spl_token::state::Account::unpack(&data)
+        .map_err(BridgeError::from_error)?
+        .owner)
+}
+
+/// Checks the destination token account of an EVM request is owned by its destination
+/// account, a TokenMinted request minted to another wallet is held for an operator
+pub async fn verify_destination_owner(
+    client: &SolanaClient,
+    db: &Database,
+    request: &mut BRequest,
+    now: Timestamp,
+) -> Result<bool> {
+    let owner = token_account_owner(client, &request.output.detination_token_id_or_account).await?;
+    request.verify_mint_recipient(db, &owner.to_string(), now)
+}
+
+/// Associated token account of the destination account of an EVM request for its bridged
+/// mint, the account the mint creates
+pub fn destination_token_account(client: &SolanaClient, request: &BRequest) -> Result<Pubkey> {
+    let destination = parse_pubkey("destination account", &request.input.destination_account)?;
+    let mint = derive_mint(
+        client,
+        &request.input.contract_or_mint,
+        &request.input.token_id,
+    )?;
+    Ok(spl_associated_token_account::get_associated_token_address(
+        &destination,
+        &mint,
+    ))
+}
+
+/// True when `token_account` holds the rent of a token account, the mint creates it with
+/// these lamports
+pub async fn token_account_prefunded(
+    client: &SolanaClient,
+    token_account: &Pubkey,
+) -> Result<bool> {
+    Ok(Lamports::new(client.get_balance(token_account).await?) >= TOKEN_ACCOUNT_RENT)
+}
+
+/// Resumes a request waiting for its destination token account once the account is
+/// prefunded, or right away when prefunding is no longer required. Returns whether the
+/// request was resumed
+pub async fn recheck_destination_account(
+    client: &SolanaClient,
+    db: &Database,
+    request: &mut BRequest,
+    now: Timestamp,
+) -> Result<bool> {
+    if request.status != Status::AwaitingDestinationAccount {
+        return Ok(false);
+    }
+    if client.require_prefunded_ata {
+        if let Some(pending) = &request.destination_account_pending {
+            let token_account = parse_pubkey("destination token account", &pending.token_account)?;
+            if !token_account_prefunded(client, &token_account).await? {
+                info!(
+                    "Destination token account {} of request {} not funded yet",
+                    token_account, request.id
+                );
+                return Ok(false);
+            }
+        }
+    }
+    request.destination_account_funded(db, now)
+}
+
+/// True once the bridge program created the destination mint of an EVM token
+pub async fn destination_minted(
+    client: &SolanaClient,
+    origin_contract: &str,
+    token_id: &str,
+) -> Result<bool> {
+    let mint = derive_mint(client, origin_contract, token_id)?;
+    client.account_exists(&mint).await
+}
+
+/// True when the bridge token account of `mint` holds the token
+pub async fn bridge_holds_token(client: &SolanaClient, mint: &Pubkey) -> Result<bool> {
+    bridge_holds_token_cached(client, &AccountsCache::default(), mint).await
+}
+
+/// Same as `bridge_holds_token` reading the bridge token account from `cache` when
+/// prefetched
+pub async fn bridge_holds_token_cached(
+    client: &SolanaClient,
+    cache: &AccountsCache,
+    mint: &Pubkey,
+) -> Result<bool> {
+    let bridge_token_account =
+        spl_associated_token_account::get_associated_token_address(&client.bridge_account, mint);
+    let data = cache.account_data(client, &bridge_token_account).await?;
+    Ok(spl_token::state::Account::unpack(&data)
+        .is_ok_and(|account| account.owner == client.bridge_account && account.amount == 1))
+}
+
+/// Records the custody of a NewRequest event without local request. The owner is the
+/// token account of the event, as in the requests created by the API
+pub async fn record_orphan_request(
+    db: &Database,
+    client: &SolanaClient,
+    request_id: &RequestId,
+    mint: &Pubkey,
+    user_token_account: &Pubkey,
+) -> Result<()> {
+    let in_custody = bridge_holds_token(client, mint)
+        .await
+        .with_call_context(|| client.call_context("get_account_data", request_id))?;
+    if !in_custody {
+        info!("Token {mint} of unknown request {request_id} is not in custody");
+        return Ok(());
+    }
+
+    types::record_orphan(
+        db,
+        request_id,
+        InputRequest {
+            contract_or_mint: mint.to_string(),
+            token_id: String::new(),
+            token_owner: user_token_account.to_string(),
+            origin_network: Chains::SOLANA,
+            destination_account: String::new(),
+        },
+        client.bridge_addresses(),
+        client.clock.now(),
+    )?;
+    Ok(())
+}
+
+pub async fn check_token_owner(
+    db: &Database,
+    client: &SolanaClient,
+    request_id: &str,
+) -> Result<()> {
+    check_token_owner_cached(db, client, &AccountsCache::default(), request_id).await
+}
+
+/// Same as `check_token_owner` reading the accounts prefetched in `cache` for a sweep page,
+/// the accounts missed are read on their own
+pub async fn check_token_owner_cached(
+    db: &Database,
+    client: &SolanaClient,
+    cache: &AccountsCache,
+    request_id: &str,
+) -> Result<()> {
     if let Ok(Some(mut request)) = types::request_data(request_id, db) {
         info!("Checking owner");
+        // Custody of the bridge account the token was locked to
+        let client = &client.for_request(&request)?;
         if request.status == Status::RequestReceived {
-            let token_mint_pubkey = Pubkey::from_str(&request.input.contract_or_mint).unwrap();
-            let bridge_token_account_pubkey =
-                spl_associated_token_account::get_associated_token_address(
-                    &client.bridge_account,
-                    &token_mint_pubkey,
+            let token_mint_pubkey = parse_pubkey("mint address", &request.input.contract_or_mint)?;
+            let lock_tx = request.tx_hashes.first().map(String::as_str);
+            let custody = match bridge_custody_cached(
+                client,
+                cache,
+                &token_mint_pubkey,
+                request.created_at,
+                lock_tx,
+            )
+            .await
+            {
+                Ok(custody) => custody,
+                Err(e) => {
+                    error!("Could not read bridge token account {}", e);
+                    return Ok(());
+                }
+            };
+            if let SolanaCustody::Stale(signature) = &custody {
+                info!(
+                    "Token of request {request_id} held since {signature:?}, before the request was created"
                 );
-            let data = client
-                .rpc
-                .get_account_data(&bridge_token_account_pubkey)
-                .unwrap();
-            if let Ok(token_data) = spl_token::state::Account::unpack(&data) {
-                if token_data.owner == client.bridge_account && token_data.amount == 1 {
-                    request.update_state(db).unwrap();
-
-                    let metadata = get_metadata(client, &request.input.contract_or_mint).unwrap();
-
-                    client
-                        .tx_channel
-                        .send(TxMessage {
-                            accion: types::Function::Mint,
-                            mint_data: Some(MessageMint {
-                                request_id: (request_id).to_string(),
-                                token_metadata: metadata,
-                            }),
-                            request_data: None,
-                        })
-                        .await
-                        .unwrap();
+            }
+
+            // Stale custody waits for a transfer like a token not in custody, until expiry
+            let now = client.clock.now();
+            let age = request.created_at.elapsed_until(now);
+            let signature = match custody {
+                SolanaCustody::Established(signature) => Some(signature),
+                SolanaCustody::NotHeld | SolanaCustody::Stale(_) => None,
+            };
+            match custody_step(signature.is_some(), None, age) {
+                CustodyStep::InCustody => {}
+                CustodyStep::Wait => return Ok(()),
+                CustodyStep::Cancel(reason) => {
+                    info!("Canceling request {request_id}, {reason:?}");
+                    request.cancel_with_reason(db, reason, now)?;
+                    return Ok(());
                 }
             }
+            if types::reject_custody_conflict(&mut request, db, now)? {
+                return Ok(());
+            }
+            // Saved with the status, a restart resumes the wait for finality
+            if client.confirmation == ConfirmationStrategy::Confirmed {
+                request.finality_pending = signature.clone().map(|signature| FinalityPending {
+                    signature,
+                    token_metadata: None,
+                    observed_at: now,
+                });
+            }
+            request.custody_signature = signature;
+            request.update_state(db, now)?;
+
+            let metadata = get_metadata_cached(client, cache, &request.input.contract_or_mint)
+                .await
+                .with_call_context(|| client.call_context("get_metadata", request_id))?;
+
+            if request.finality_pending.is_some() {
+                info!("Mint of request {request_id} waits for the finality of its custody");
+                return keep_finality_metadata(db, request_id, metadata);
+            }
+            client
+                .tx_channel
+                .send(TxMessage::Mint(MessageMint {
+                    request_id: request.id.clone(),
+                    token_metadata: metadata,
+                    trace_context: request.trace_context.clone(),
+                }))
+                .await?;
         } else {
             info!("Request id already processed");
         }
     } else {
         info!("Not request id db");
     }
+    Ok(())
 }
 
 pub async fn get_transaction_data(
     client: SolanaClient,
     tx: &str,
 ) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
-    let signature = Signature::from_str(tx).expect("Invalid signature");
+    let signature = parse_signature(tx)?;
     let config = RpcTransactionConfig {
         encoding: Some(UiTransactionEncoding::Json),
-        commitment: Some(CommitmentConfig::finalized()),
+        commitment: Some(client.processing_commitment()),
         max_supported_transaction_version: Some(0),
     };
-    let get_transaction_with_config = client.rpc.get_transaction_with_config(&signature, config)?;
+    let get_transaction_with_config = client
+        .get_transaction_with_config(&signature, config)
+        .await?;
     return Ok(get_transaction_with_config);
 }
+
+/// Fee paid by the fee payer of a confirmed transaction
+pub fn transaction_fee(tx: &EncodedConfirmedTransactionWithStatusMeta) -> Option<Lamports> {
+    tx.transaction
+        .meta
+        .as_ref()
+        .map(|meta| Lamports::new(meta.fee))
+}
+
+/// Lamports the fee payer of a confirmed transaction put in the rent exemption of the
+/// accounts it created, its balance decrease beyond the fee. A mint funds its mint,
+/// metadata, edition and destination token accounts
+pub fn transaction_rent(tx: &EncodedConfirmedTransactionWithStatusMeta) -> Option<Lamports> {
+    let meta = tx.transaction.meta.as_ref()?;
+    let spent = meta
+        .pre_balances
+        .first()?
+        .saturating_sub(*meta.post_balances.first()?);
+    Some(Lamports::new(spent.saturating_sub(meta.fee))).filter(|rent| *rent > Lamports::ZERO)
+}
+
+/// State of a transaction from its signature status, processed transactions can still be
+/// dropped with their fork
+pub fn classify_signature_status(status: Option<&TransactionStatus>) -> TxState {
+    match status {
+        None => TxState::Dropped,
+        Some(status) if status.err.is_some() => TxState::Mined { success: false },
+        Some(status) => match status.confirmation_status {
+            Some(TransactionConfirmationStatus::Confirmed)
+            | Some(TransactionConfirmationStatus::Finalized) => TxState::Mined { success: true },
+            // Rooted transactions of old nodes have neither a confirmation status nor a count
+            None if status.confirmations.is_none() => TxState::Mined { success: true },
+            _ => TxState::Pending {
+                max_fee_per_gas: None,
+                base_fee_per_gas: None,
+            },
+        },
+    }
+}
+
+/// Whether a relayer transaction is confirmed, still processing or dropped
+pub async fn get_signature_state(client: &SolanaClient, tx: &str) -> Result<TxState> {
+    let signature = parse_signature(tx)?;
+    let status = client.get_signature_status_with_history(&signature).await?;
+    Ok(classify_signature_status(status.as_ref()))
+}
+
+/// Programs invoked by the instructions of a transaction, in order
+pub fn invoked_programs(transaction: &VersionedTransaction) -> Vec<String> {
+    let keys = transaction.message.static_account_keys();
+    transaction
+        .message
+        .instructions()
+        .iter()
+        .map(|instruction| instruction.program_id(keys).to_string())
+        .collect()
+}
+
+/// Transaction recorded on a request as the tx audit reads it, missing until confirmed.
+/// Each RPC request waits for the limiter
+pub async fn recorded_tx(
+    client: &SolanaClient,
+    tx: &str,
+    limiter: &RpcLimiter,
+) -> Result<OnChainTx> {
+    let signature = parse_signature(tx)?;
+    limiter.acquire().await;
+    let status = client
+        .get_signature_status_with_history(&signature)
+        .await
+        .with_call_context(|| {
+            CallContext::new(Chains::SOLANA, "get_signature_statuses").contract(tx)
+        })?;
+    let TxState::Mined { success } = classify_signature_status(status.as_ref()) else {
+        return Ok(OnChainTx::Missing);
+    };
+
+    limiter.acquire().await;
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    };
+    let transaction = client
+        .get_transaction_with_config(&signature, config)
+        .await
+        .with_call_context(|| CallContext::new(Chains::SOLANA, "get_transaction").contract(tx))?;
+    let called = transaction
+        .transaction
+        .transaction
+        .decode()
+        .map(|transaction| invoked_programs(&transaction))
+        .unwrap_or_default();
+    Ok(OnChainTx::Found { success, called })
+}
+
+#[cfg(test)]
+mod read_account_test {
+    use serde_json::Value;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::signature::Signature;
+    use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+    use test_support::{
+        balance_result, missing_account_result, mock_rpc, request_in_status,
+        signature_status_result, slot_result, solana_key, test_db, token_account_result,
+        transaction_result, FIXTURE_SLOT,
+    };
+    use types::{BRequest, Chains, Lamports, Status, Timestamp, TxState, TOKEN_ACCOUNT_RENT};
+
+    use crate::{
+        get_latest_slot, get_signature_state, recheck_destination_account, record_orphan_request,
+        test_utils::test_client, token_account_owner, transaction_fee, transaction_rent,
+    };
+
+    fn transaction(result: Value) -> EncodedConfirmedTransactionWithStatusMeta {
+        serde_json::from_value(result).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reads_with_canned_rpc_responses() {
+        let client = test_client(mock_rpc([(RpcRequest::GetSlot, slot_result())]));
+        assert_eq!(get_latest_slot(&client).await.unwrap(), FIXTURE_SLOT);
+
+        let (mint, owner) = (solana_key(1), solana_key(6));
+        let client = test_client(mock_rpc([(
+            RpcRequest::GetAccountInfo,
+            token_account_result(&mint, &owner, 1),
+        )]));
+        assert_eq!(
+            token_account_owner(&client, &solana_key(2).to_string())
+                .await
+                .unwrap(),
+            owner
+        );
+    }
+
+    #[tokio::test]
+    async fn test_orphan_recorded_only_in_custody() {
+        let db = test_db();
+        let (mint, user_token_account) = (solana_key(1), solana_key(2));
+        let not_held = BRequest::generate_id("not-held", "", "");
+        let held = BRequest::generate_id("held", "", "");
+
+        let client = test_client(mock_rpc([(
+            RpcRequest::GetAccountInfo,
+            missing_account_result(),
+        )]));
+        assert!(
+            record_orphan_request(&db, &client, &not_held, &mint, &user_token_account)
+                .await
+                .is_err()
+        );
+        assert!(types::request_data(&not_held, &db).unwrap().is_none());
+
+        let mut client = test_client(mock_rpc([]));
+        let bridge = client.bridge_account;
+        client.set_rpc(mock_rpc([(
+            RpcRequest::GetAccountInfo,
+            token_account_result(&mint, &bridge, 1),
+        )]));
+        record_orphan_request(&db, &client, &held, &mint, &user_token_account)
+            .await
+            .unwrap();
+        let orphan = types::request_data(&held, &db).unwrap().unwrap();
+        assert_eq!(orphan.status, Status::NeedsDestination);
+        assert_eq!(orphan.input.token_owner, user_token_account.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_signature_states() {
+        let signature = Signature::from([7u8; 64]).to_string();
+        for (result, expected) in [
+            (
+                signature_status_result(Some("processed")),
+                TxState::Pending {
+                    max_fee_per_gas: None,
+                    base_fee_per_gas: None,
+                },
+            ),
+            (
+                signature_status_result(Some("finalized")),
+                TxState::Mined { success: true },
+            ),
+            (signature_status_result(None), TxState::Dropped),
+        ] {
+            let client = test_client(mock_rpc([(RpcRequest::GetSignatureStatuses, result)]));
+            assert_eq!(
+                get_signature_state(&client, &signature).await.unwrap(),
+                expected
+            );
+        }
+
+        // Not a signature, the stored hash is corrupted
+        let client = test_client(mock_rpc([]));
+        let err = get_signature_state(&client, "0xnot-a-signature")
+            .await
+            .unwrap_err();
+        assert!(err.is_corrupted_data());
+    }
+
+    #[test]
+    fn test_rent_is_the_payer_spend_beyond_the_fee() {
+        // Mint creating a token account funded by the fee payer
+        let mint = transaction(transaction_result(
+            5_000,
+            &[10_000_000, 0, 1],
+            &[7_955_720, 2_039_280, 1],
+        ));
+        assert_eq!(transaction_fee(&mint), Some(Lamports::new(5_000)));
+        assert_eq!(transaction_rent(&mint), Some(Lamports::new(2_039_280)));
+
+        // The payer only paid the fee
+        let transfer = transaction(transaction_result(5_000, &[10_000_000, 1], &[9_995_000, 1]));
+        assert_eq!(transaction_fee(&transfer), Some(Lamports::new(5_000)));
+        assert_eq!(transaction_rent(&transfer), None);
+        // Credited by the transaction, nothing spent
+        let credited = transaction(transaction_result(5_000, &[10_000_000], &[10_500_000]));
+        assert_eq!(transaction_rent(&credited), None);
+
+        // Without meta neither is known
+        let mut result = transaction_result(5_000, &[10_000_000], &[9_995_000]);
+        result["meta"] = Value::Null;
+        let without_meta = transaction(result);
+        assert_eq!(transaction_fee(&without_meta), None);
+        assert_eq!(transaction_rent(&without_meta), None);
+    }
+
+    #[tokio::test]
+    async fn test_parked_request_resumed_once_funded() {
+        let db = test_db();
+        let now = Timestamp::from_secs(1_700_000_000);
+        let request = request_in_status(Chains::EVM, "1", Status::AwaitingDestinationAccount);
+        request.save(&db).unwrap();
+
+        let mut client = test_client(mock_rpc([(RpcRequest::GetBalance, balance_result(0))]));
+        client.require_prefunded_ata = true;
+        let mut parked = request.clone();
+        assert!(!recheck_destination_account(&client, &db, &mut parked, now)
+            .await
+            .unwrap());
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::AwaitingDestinationAccount);
+
+        let rent = TOKEN_ACCOUNT_RENT.raw();
+        client.set_rpc(mock_rpc([(RpcRequest::GetBalance, balance_result(rent))]));
+        assert!(recheck_destination_account(&client, &db, &mut parked, now)
+            .await
+            .unwrap());
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::TokenReceived);
+        assert_eq!(stored.destination_account_pending, None);
+        // Only parked requests are rechecked
+        assert!(!recheck_destination_account(&client, &db, &mut parked, now)
+            .await
+            .unwrap());
+
+        // Prefunding no longer required, the parked request resumes without a read
+        let mut parked = request_in_status(Chains::EVM, "2", Status::AwaitingDestinationAccount);
+        parked.save(&db).unwrap();
+        let client = test_client(mock_rpc([(RpcRequest::GetBalance, balance_result(0))]));
+        assert!(recheck_destination_account(&client, &db, &mut parked, now)
+            .await
+            .unwrap());
+        assert_eq!(parked.status, Status::TokenReceived);
+    }
+}
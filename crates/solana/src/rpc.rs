@@ -0,0 +1,284 @@
+use std::{future::IntoFuture, time::Duration};
+
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::{RpcSimulateTransactionConfig, RpcTransactionConfig},
+    rpc_request::{RpcError, RpcResponseErrorData},
+    rpc_response::{RpcConfirmedTransactionStatusWithSignature, RpcSimulateTransactionResult},
+};
+use solana_sdk::{
+    account::Account, hash::Hash, pubkey::Pubkey, signature::Signature, transaction::Transaction,
+};
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, TransactionStatus};
+use types::{with_timeout, BridgeError, Chains, Result};
+
+use crate::{already_in_use_account, SolanaClient, SolanaError};
+
+/// Error of a failed send, typed when the simulation shows the transaction creates an
+/// existing account
+fn send_error(err: ClientError) -> BridgeError {
+    let account = match err.kind() {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+            ..
+        }) => result.logs.as_deref().and_then(already_in_use_account),
+        _ => None,
+    };
+    match account {
+        Some(account) => SolanaError::AccountAlreadyInitialized(account).into(),
+        None => BridgeError::from_error(err),
+    }
+}
+
+/// Whether the node answered the failed call, its transport errors count against its
+/// health
+fn node_answered(err: &ClientError) -> bool {
+    !matches!(
+        err.kind(),
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_)
+    )
+}
+
+/// RpcClient calls awaited with the configured timeouts
+impl SolanaClient {
+    /// Call to the current node, recorded in the health of its endpoint
+    async fn observed<T, F>(&self, operation: &str, after: Duration, call: F) -> Result<T>
+    where
+        F: IntoFuture<Output = ClientResult<T>>,
+    {
+        self.rpc_health
+            .observe(
+                &Chains::SOLANA,
+                &self.rpc().url(),
+                operation,
+                after,
+                call,
+                node_answered,
+            )
+            .await
+    }
+
+    pub async fn get_slot(&self) -> Result<u64> {
+        self.observed("get_slot", self.timeouts.read, self.rpc().get_slot())
+            .await
+    }
+
+    pub async fn get_account_data(&self, pubkey: &Pubkey) -> Result<Vec<u8>> {
+        self.observed(
+            "get_account_data",
+            self.timeouts.read,
+            self.rpc().get_account_data(pubkey),
+        )
+        .await
+    }
+
+    /// Accounts of `pubkeys` in their order, None for the ones that don't exist
+    pub async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        self.observed(
+            "get_multiple_accounts",
+            self.timeouts.read,
+            self.rpc().get_multiple_accounts(pubkeys),
+        )
+        .await
+    }
+
+    pub async fn account_exists(&self, pubkey: &Pubkey) -> Result<bool> {
+        Ok(self.get_account(pubkey).await?.is_some())
+    }
+
+    /// Account of `pubkey`, None when it doesn't exist
+    pub async fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        let account = self
+            .observed(
+                "get_account",
+                self.timeouts.read,
+                self.rpc()
+                    .get_account_with_commitment(pubkey, self.rpc().commitment()),
+            )
+            .await?;
+        Ok(account.value)
+    }
+
+    pub async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64> {
+        self.observed(
+            "get_balance",
+            self.timeouts.read,
+            self.rpc().get_balance(pubkey),
+        )
+        .await
+    }
+
+    /// Only available on test validators and devnet
+    pub async fn request_airdrop(&self, pubkey: &Pubkey, lamports: u64) -> Result<Signature> {
+        self.observed(
+            "request_airdrop",
+            self.timeouts.read,
+            self.rpc().request_airdrop(pubkey, lamports),
+        )
+        .await
+    }
+
+    pub async fn confirm_transaction(&self, signature: &Signature) -> Result<bool> {
+        self.observed(
+            "confirm_transaction",
+            self.timeouts.read,
+            self.rpc().confirm_transaction(signature),
+        )
+        .await
+    }
+
+    pub async fn get_latest_blockhash(&self) -> Result<Hash> {
+        self.observed(
+            "get_latest_blockhash",
+            self.timeouts.read,
+            self.rpc().get_latest_blockhash(),
+        )
+        .await
+    }
+
+    /// Whether a transaction signed with `blockhash` can still land
+    pub async fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool> {
+        self.observed(
+            "is_blockhash_valid",
+            self.timeouts.read,
+            self.rpc()
+                .is_blockhash_valid(blockhash, self.rpc().commitment()),
+        )
+        .await
+    }
+
+    pub async fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
+        self.observed(
+            "get_transaction",
+            self.timeouts.read,
+            self.rpc().get_transaction_with_config(signature, config),
+        )
+        .await
+    }
+
+    /// Status of a signature, searching the transaction history past the recent slots
+    pub async fn get_signature_status_with_history(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<TransactionStatus>> {
+        let statuses = self
+            .observed(
+                "get_signature_statuses",
+                self.timeouts.read,
+                self.rpc()
+                    .get_signature_statuses_with_history(&[*signature]),
+            )
+            .await?;
+        Ok(statuses.value.into_iter().next().flatten())
+    }
+
+    /// Latest `limit` transactions touching `address`, newest first
+    pub async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        limit: usize,
+    ) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        self.get_signatures_for_address_before(address, None, limit)
+            .await
+    }
+
+    /// Same as `get_signatures_for_address` starting before the `before` transaction, to
+    /// page back through the history
+    pub async fn get_signatures_for_address_before(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        limit: usize,
+    ) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        let config = GetConfirmedSignaturesForAddress2Config {
+            before,
+            limit: Some(limit),
+            commitment: Some(self.processing_commitment()),
+            ..Default::default()
+        };
+        self.observed(
+            "get_signatures_for_address",
+            self.timeouts.read,
+            self.rpc()
+                .get_signatures_for_address_with_config(address, config),
+        )
+        .await
+    }
+
+    /// Simulation verifying the signatures of the transaction
+    pub async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<RpcSimulateTransactionResult> {
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: true,
+            commitment: Some(self.rpc().commitment()),
+            ..Default::default()
+        };
+        let simulation = self
+            .observed(
+                "simulate_transaction",
+                self.timeouts.read,
+                self.rpc()
+                    .simulate_transaction_with_config(transaction, config),
+            )
+            .await?;
+        Ok(simulation.value)
+    }
+
+    pub async fn send_and_confirm_transaction(
+        &self,
+        transaction: Transaction,
+    ) -> Result<Signature> {
+        with_timeout("send_and_confirm_transaction", self.timeouts.send, async {
+            self.rpc()
+                .send_and_confirm_transaction(&transaction)
+                .await
+                .map_err(send_error)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod rpc_test {
+    use std::{
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use solana_client::rpc_request::RpcRequest;
+    use test_support::{slot_result, slow_mock_rpc, FIXTURE_SLOT};
+
+    use crate::test_utils::test_client;
+
+    /// Single threaded runtime, a call blocking its thread would stop every other task
+    #[tokio::test]
+    async fn test_slow_call_leaves_the_runtime_running() {
+        let delay = Duration::from_millis(300);
+        let client = test_client(slow_mock_rpc(delay, [(RpcRequest::GetSlot, slot_result())]));
+        let ticks = Arc::new(AtomicU32::new(0));
+        let ticker = tokio::spawn({
+            let ticks = ticks.clone();
+            async move {
+                loop {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    ticks.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+
+        assert_eq!(client.get_slot().await.unwrap(), FIXTURE_SLOT);
+        ticker.abort();
+        let ticks = ticks.load(Ordering::SeqCst);
+        assert!(ticks >= 10, "{ticks} ticks during the call");
+    }
+}
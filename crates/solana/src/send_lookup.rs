@@ -0,0 +1,165 @@
+use std::str::FromStr;
+
+use solana_sdk::{hash::Hash, signature::Signature};
+use types::{
+    BridgeError, IntentLookup, IntentLookupFuture, Result, SendIntent, SendLookup, WrapCallContext,
+};
+
+use crate::{parse_pubkey, SolanaClient};
+
+/// Latest transactions of the destination of an intent read to find its transaction
+pub const SEND_LOOKUP_SIGNATURES: usize = 20;
+
+/// Looks for the transaction of a Solana intent: by its signature, then among the
+/// transactions of its destination since the intent was recorded. A transaction whose
+/// blockhash is still valid may land, one signed with an expired blockhash never will
+pub async fn find_sent_tx(client: &SolanaClient, intent: &SendIntent) -> Result<IntentLookup> {
+    let request_id = &intent.request_id;
+
+    if let Some(signature) = &intent.tx_hash {
+        let status = client
+            .get_signature_status_with_history(
+                &Signature::from_str(signature).map_err(BridgeError::from_error)?,
+            )
+            .await
+            .with_call_context(|| client.call_context("get_signature_statuses", request_id))?;
+        if let Some(status) = status {
+            return Ok(match status.err {
+                None => IntentLookup::Found(signature.clone()),
+                Some(_) => IntentLookup::NotFound,
+            });
+        }
+    }
+
+    if let Some(destination) = &intent.destination {
+        let destination = parse_pubkey("intent destination", destination)?;
+        let signatures = client
+            .get_signatures_for_address(&destination, SEND_LOOKUP_SIGNATURES)
+            .await
+            .with_call_context(|| client.call_context("get_signatures_for_address", request_id))?;
+        let since = intent.recorded_at.as_secs() as i64;
+        // Newest first, the first one landed after the intent is its transaction
+        let landed = signatures.iter().rev().find(|signature| {
+            signature.err.is_none() && signature.block_time.is_some_and(|time| time >= since)
+        });
+        if let Some(landed) = landed {
+            return Ok(IntentLookup::Found(landed.signature.clone()));
+        }
+    }
+
+    let Some(blockhash) = &intent.recent_blockhash else {
+        return Ok(IntentLookup::NotFound);
+    };
+    let valid = client
+        .is_blockhash_valid(&Hash::from_str(blockhash).map_err(BridgeError::from_error)?)
+        .await
+        .with_call_context(|| client.call_context("is_blockhash_valid", request_id))?;
+    Ok(if valid {
+        IntentLookup::Undecided
+    } else {
+        IntentLookup::NotFound
+    })
+}
+
+/// Looks up the Solana intents through the RPC node of the client
+pub struct SolanaSendLookup {
+    pub client: SolanaClient,
+}
+
+impl SendLookup for SolanaSendLookup {
+    fn lookup<'a>(&'a self, intent: &'a SendIntent) -> IntentLookupFuture<'a> {
+        Box::pin(find_sent_tx(&self.client, intent))
+    }
+}
+
+#[cfg(test)]
+mod send_lookup_test {
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::{hash::Hash, signature::Signature};
+    use test_support::{
+        blockhash_valid_result, mock_rpc, signature_status_result, signatures_for_address_result,
+        solana_key,
+    };
+    use types::{Chains, IntentLookup, SendIntent, SendOperation, Timestamp};
+
+    use crate::{find_sent_tx, test_utils::test_client};
+
+    const RECORDED_AT: u64 = 1_700_000_000;
+
+    fn mint_intent() -> SendIntent {
+        let mut intent = SendIntent::new("request", Chains::SOLANA, SendOperation::Mint);
+        intent.recent_blockhash = Some(Hash::new_from_array([9u8; 32]).to_string());
+        intent.destination = Some(solana_key(1).to_string());
+        intent.recorded_at = Timestamp::from_secs(RECORDED_AT);
+        intent
+    }
+
+    async fn lookup(
+        intent: &SendIntent,
+        mocks: impl IntoIterator<Item = (RpcRequest, serde_json::Value)>,
+    ) -> IntentLookup {
+        find_sent_tx(&test_client(mock_rpc(mocks)), intent)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_mint_found_on_its_derived_mint() {
+        let created = Signature::from([1u8; 64]).to_string();
+        let earlier = Signature::from([2u8; 64]).to_string();
+        let found = lookup(
+            &mint_intent(),
+            [(
+                RpcRequest::GetSignaturesForAddress,
+                signatures_for_address_result(&[
+                    (&Signature::from([3u8; 64]).to_string(), None, false),
+                    (&created, Some(RECORDED_AT as i64 + 2), false),
+                    (&earlier, Some(RECORDED_AT as i64 - 60), false),
+                ]),
+            )],
+        )
+        .await;
+        assert_eq!(found, IntentLookup::Found(created));
+
+        // Sent with its signature known
+        let signature = Signature::from([4u8; 64]).to_string();
+        let mut intent = mint_intent();
+        intent.tx_hash = Some(signature.clone());
+        let found = lookup(
+            &intent,
+            [(
+                RpcRequest::GetSignatureStatuses,
+                signature_status_result(Some("confirmed")),
+            )],
+        )
+        .await;
+        assert_eq!(found, IntentLookup::Found(signature));
+    }
+
+    #[tokio::test]
+    async fn test_mint_not_found_sent_again_once_its_blockhash_expired() {
+        let not_landed = [
+            (
+                RpcRequest::GetSignaturesForAddress,
+                signatures_for_address_result(&[]),
+            ),
+            (RpcRequest::IsBlockhashValid, blockhash_valid_result(true)),
+        ];
+        assert_eq!(
+            lookup(&mint_intent(), not_landed).await,
+            IntentLookup::Undecided
+        );
+
+        let expired = [
+            (
+                RpcRequest::GetSignaturesForAddress,
+                signatures_for_address_result(&[]),
+            ),
+            (RpcRequest::IsBlockhashValid, blockhash_valid_result(false)),
+        ];
+        assert_eq!(
+            lookup(&mint_intent(), expired).await,
+            IntentLookup::NotFound
+        );
+    }
+}
@@ -0,0 +1,336 @@
+use std::sync::atomic::Ordering;
+
+use log::error;
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_sdk::{
+    hash::Hash,
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::Signer,
+    transaction::{Transaction, TransactionError},
+};
+
+use crate::{SolanaClient, SolanaError};
+use types::{BridgeError, Result};
+
+/// First required signer of the transaction without a valid signature
+pub fn missing_signer(transaction: &Transaction) -> Option<Pubkey> {
+    transaction
+        .verify_with_results()
+        .into_iter()
+        .zip(&transaction.message.account_keys)
+        .find(|(valid, _)| !valid)
+        .map(|(_, key)| *key)
+}
+
+/// Signer the runtime logs when an instruction uses an account that didn't sign
+pub fn missing_signer_in_logs(logs: &[String]) -> Option<String> {
+    logs.iter()
+        .find_map(|log| log.strip_suffix("'s signer privilege escalated"))
+        .and_then(|rest| rest.split_whitespace().last())
+        .map(str::to_string)
+}
+
+/// Signer of a simulation failing the signature verification, "unknown" when the logs
+/// don't name it
+pub fn simulated_missing_signer(result: &RpcSimulateTransactionResult) -> Option<String> {
+    match result.err {
+        Some(TransactionError::SignatureFailure)
+        | Some(TransactionError::InstructionError(_, InstructionError::MissingRequiredSignature)) => {
+            Some(
+                result
+                    .logs
+                    .as_deref()
+                    .and_then(missing_signer_in_logs)
+                    .unwrap_or_else(|| "unknown".to_string()),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Whether a simulation paid by a separate fee payer failed because the bridge program
+/// wants the backend to pay: a transfer short of lamports from the unfunded backend, or
+/// a constraint of the program (Anchor errors 2000 to 2999)
+pub fn payer_must_be_backend(result: &RpcSimulateTransactionResult) -> bool {
+    let insufficient_lamports = result.logs.as_deref().is_some_and(|logs| {
+        logs.iter()
+            .any(|log| log.contains("Transfer: insufficient lamports"))
+    });
+    let constraint = matches!(
+        result.err,
+        Some(TransactionError::InstructionError(_, InstructionError::Custom(code)))
+            if (2000..3000).contains(&code)
+    );
+    insufficient_lamports || constraint
+}
+
+impl SolanaClient {
+    /// Signs `transaction` with the relayer keypairs that are required signers, the fee
+    /// payer and the backend. Returns the first signer still missing, the fee payer when
+    /// the relayer doesn't hold it
+    pub fn sign_with_relayer_keys(
+        &self,
+        transaction: &mut Transaction,
+        recent_blockhash: Hash,
+    ) -> Result<Option<Pubkey>> {
+        let keypairs = self.relayer_keypairs();
+        let fee_payer = transaction.message.account_keys.first().copied();
+        if fee_payer.is_some_and(|payer| !keypairs.iter().any(|key| key.pubkey() == payer)) {
+            return Ok(fee_payer);
+        }
+        let signer_keys = transaction.message.signer_keys();
+        let keypairs: Vec<_> = keypairs
+            .into_iter()
+            .filter(|key| signer_keys.contains(&&key.pubkey()))
+            .collect();
+        transaction
+            .try_partial_sign(&keypairs, recent_blockhash)
+            .map_err(BridgeError::from_error)?;
+        Ok(missing_signer(transaction))
+    }
+
+    /// Signs `transaction` with the relayer keypairs and sends it once every required
+    /// signature is there, checked locally then by a simulation verifying the signatures.
+    /// A signer the relayer doesn't hold fails with `MissingSigner` and nothing is sent.
+    /// With a separate fee payer, a simulation showing that the program wants the backend
+    /// to pay fails with `FeePayerNotAccepted`
+    pub async fn sign_and_send(
+        &self,
+        mut transaction: Transaction,
+        recent_blockhash: Hash,
+    ) -> Result<Signature> {
+        let missing = match self.sign_with_relayer_keys(&mut transaction, recent_blockhash)? {
+            Some(key) => Some(key.to_string()),
+            None => {
+                let simulation = self.simulate_transaction(&transaction).await?;
+                let missing = simulated_missing_signer(&simulation);
+                if missing.is_none()
+                    && self.fee_payer.is_some()
+                    && payer_must_be_backend(&simulation)
+                {
+                    let (fee_payer, backend) = (self.fee_payer_pubkey(), self.signer.pubkey());
+                    error!("FEE PAYER NOT ACCEPTED: the bridge program needs the backend {backend} to pay, fee payer {fee_payer} can't be used, not sent");
+                    return Err(SolanaError::FeePayerNotAccepted {
+                        fee_payer: fee_payer.to_string(),
+                        backend: backend.to_string(),
+                    }
+                    .into());
+                }
+                missing
+            }
+        };
+
+        if let Some(signer) = missing {
+            self.missing_signers.fetch_add(1, Ordering::Relaxed);
+            error!("MISSING SIGNER: transaction needs a signature of {signer}, not sent");
+            return Err(SolanaError::MissingSigner(signer).into());
+        }
+        self.send_and_confirm_transaction(transaction).await
+    }
+
+    pub fn missing_signer_count(&self) -> u64 {
+        self.missing_signers.load(Ordering::Relaxed)
+    }
+
+    pub fn blockhash_resend_count(&self) -> u64 {
+        self.blockhash_resends.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod signers_test {
+    use std::sync::Arc;
+
+    use serde_json::{json, Value};
+    use solana_client::{rpc_request::RpcRequest, rpc_response::RpcSimulateTransactionResult};
+    use solana_sdk::{
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+        signature::Keypair,
+        signer::Signer,
+        transaction::Transaction,
+    };
+    use test_support::mock_rpc;
+    use types::BridgeError;
+
+    use crate::{
+        missing_signer, missing_signer_in_logs, payer_must_be_backend, simulated_missing_signer,
+        test_utils::test_client, SolanaError,
+    };
+
+    /// Instruction needing the signature of `signer` besides the relayer
+    fn instruction_signed_by(relayer: &Pubkey, signer: &Pubkey) -> Instruction {
+        Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![
+                AccountMeta::new(*relayer, true),
+                AccountMeta::new_readonly(*signer, true),
+            ],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_absent_signer_is_not_sent() {
+        // The mock accepts any send, the error comes from the check before it
+        let client = test_client(mock_rpc([]));
+        let relayer = client.signer.pubkey();
+        let absent = Keypair::new().pubkey();
+        let transaction = Transaction::new_with_payer(
+            &[instruction_signed_by(&relayer, &absent)],
+            Some(&relayer),
+        );
+
+        let err = client
+            .sign_and_send(transaction, Hash::new_unique())
+            .await
+            .unwrap_err();
+        assert_eq!(err, BridgeError::MissingSigner(absent.to_string()));
+        assert_eq!(client.missing_signer_count(), 1);
+
+        // Fee paid by another wallet
+        let payer = Keypair::new().pubkey();
+        let transaction =
+            Transaction::new_with_payer(&[instruction_signed_by(&relayer, &payer)], Some(&payer));
+        let err = client
+            .sign_and_send(transaction, Hash::new_unique())
+            .await
+            .unwrap_err();
+        assert_eq!(err, BridgeError::MissingSigner(payer.to_string()));
+        assert_eq!(client.missing_signer_count(), 2);
+    }
+
+    #[test]
+    fn test_separate_fee_payer_signs_first() {
+        let mut client = test_client(mock_rpc([]));
+        let backend = client.signer.pubkey();
+        let instruction = Instruction::new_with_bytes(
+            client.bridge_program,
+            &[],
+            vec![AccountMeta::new_readonly(backend, true)],
+        );
+
+        let mut transaction = client.new_transaction(&[instruction.clone()]);
+        let signed = client.sign_with_relayer_keys(&mut transaction, Hash::new_unique());
+        assert_eq!(signed.unwrap(), None);
+        assert_eq!(transaction.signatures.len(), 1);
+
+        let fee_payer = Keypair::new();
+        client.fee_payer = Some(Arc::new(fee_payer.insecure_clone()));
+        assert_eq!(client.fee_payer_pubkey(), fee_payer.pubkey());
+        let mut transaction = client.new_transaction(&[instruction]);
+        let signed = client.sign_with_relayer_keys(&mut transaction, Hash::new_unique());
+        assert_eq!(signed.unwrap(), None);
+        // The fee payer signs first, the backend stays in the instruction accounts
+        assert_eq!(
+            transaction.message.account_keys[..2],
+            [fee_payer.pubkey(), backend]
+        );
+        assert_eq!(transaction.signatures.len(), 2);
+        let message = transaction.message_data();
+        assert!(transaction.signatures[0].verify(fee_payer.pubkey().as_ref(), &message));
+        assert!(transaction.signatures[1].verify(backend.as_ref(), &message));
+        assert!(transaction.verify().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_fee_payer_rejected_by_the_program() {
+        let simulation = json!({
+            "context": { "slot": 1 },
+            "value": {
+                "err": { "InstructionError": [0, { "Custom": 2003 }] },
+                "logs": ["Program log: AnchorError caused by account: backend. Error Code: ConstraintRaw."],
+            },
+        });
+        let mut client = test_client(mock_rpc([(RpcRequest::SimulateTransaction, simulation)]));
+        client.fee_payer = Some(Arc::new(Keypair::new()));
+        let backend = client.signer.pubkey();
+        let transaction = client.new_transaction(&[Instruction::new_with_bytes(
+            client.bridge_program,
+            &[],
+            vec![AccountMeta::new_readonly(backend, true)],
+        )]);
+
+        let err = client
+            .sign_and_send(transaction, Hash::new_unique())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            SolanaError::FeePayerNotAccepted {
+                fee_payer: client.fee_payer_pubkey().to_string(),
+                backend: backend.to_string(),
+            }
+            .into()
+        );
+
+        let result = |err: Value, logs: Value| -> RpcSimulateTransactionResult {
+            serde_json::from_value(json!({ "err": err, "logs": logs })).unwrap()
+        };
+        assert!(payer_must_be_backend(&result(
+            json!({ "InstructionError": [0, { "Custom": 1 }] }),
+            json!(["Transfer: insufficient lamports 0, need 1461600"])
+        )));
+        assert!(!payer_must_be_backend(&result(
+            json!({ "InstructionError": [0, { "Custom": 6000 }] }),
+            json!([])
+        )));
+        assert!(!payer_must_be_backend(&result(Value::Null, json!([]))));
+    }
+
+    #[test]
+    fn test_missing_signer_in_signer_order() {
+        let relayer = Keypair::new();
+        let other = Keypair::new();
+        let instruction = instruction_signed_by(&relayer.pubkey(), &other.pubkey());
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&relayer.pubkey()));
+        assert_eq!(missing_signer(&transaction), Some(relayer.pubkey()));
+
+        let blockhash = Hash::new_unique();
+        transaction.partial_sign(&[&relayer], blockhash);
+        assert_eq!(missing_signer(&transaction), Some(other.pubkey()));
+        transaction.partial_sign(&[&other], blockhash);
+        assert_eq!(missing_signer(&transaction), None);
+    }
+
+    #[test]
+    fn test_simulated_signature_failures() {
+        let logs = vec![
+            "Program BPFLoaderUpgradeab1e11111111111111111111111 invoke [1]".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU's signer privilege escalated".to_string(),
+            "Program BPFLoaderUpgradeab1e11111111111111111111111 failed: Cross-program invocation with unauthorized signer or writable account".to_string(),
+        ];
+        assert_eq!(
+            missing_signer_in_logs(&logs).as_deref(),
+            Some("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+        );
+        assert_eq!(missing_signer_in_logs(&logs[..1]), None);
+
+        let result = |err: Value, logs: Value| -> RpcSimulateTransactionResult {
+            serde_json::from_value(json!({ "err": err, "logs": logs })).unwrap()
+        };
+        assert_eq!(
+            simulated_missing_signer(&result(
+                json!({ "InstructionError": [0, "MissingRequiredSignature"] }),
+                json!(logs)
+            ))
+            .as_deref(),
+            Some("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+        );
+        assert_eq!(
+            simulated_missing_signer(&result(json!("SignatureFailure"), Value::Null)).as_deref(),
+            Some("unknown")
+        );
+        assert_eq!(
+            simulated_missing_signer(&result(json!("AccountNotFound"), Value::Null)),
+            None
+        );
+        assert_eq!(
+            simulated_missing_signer(&result(Value::Null, json!(logs))),
+            None
+        );
+    }
+}
@@ -2,133 +2,272 @@ use std::str;
 
 use anchor_lang::Discriminator;
 use base64::{prelude::BASE64_STANDARD, Engine};
-use borsh::BorshDeserialize;
-use eyre::Result;
 use futures_util::StreamExt;
 use log::{error, info};
 use solana_client::nonblocking::pubsub_client::PubsubClient;
-use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use solana_sdk::pubkey::Pubkey;
 use storage::db::Database;
-use types::Status;
+use tracing::Instrument;
+use types::{
+    event_span, log_account, with_timeout, BridgePause, Chains, EventValidator, KeyedTaskPool,
+    RequestId, Result, Status, Timestamp,
+};
 
-use crate::{check_token_owner, solana_bridge, SolanaClient};
+use crate::{
+    cancel_corrupted_request, check_token_owner, record_orphan_request, solana_bridge,
+    verify_destination_owner, SolanaClient, SolanaError,
+};
 
 use solana_bridge::events::{NewRequestEvent, TokenMintedEvent};
 
-pub async fn subscribe_event(client: &SolanaClient, db: &Database) -> Result<()> {
-    // let mut event_commit: HashSet<String> = HashSet::new();
+/// Queued and running event handlers, the subscription is not read while the pool is full
+pub const SOLANA_EVENT_WORKERS: usize = 64;
 
+/// Decoded bridge program event with its request id, events with a malformed id are
+/// dropped when decoded
+pub(crate) enum SolanaEvent {
+    NewRequest(RequestId, NewRequestEvent),
+    TokenMinted(RequestId, TokenMintedEvent),
+}
+
+impl SolanaEvent {
+    fn request_id(&self) -> &RequestId {
+        match self {
+            SolanaEvent::NewRequest(request_id, _) => request_id,
+            SolanaEvent::TokenMinted(request_id, _) => request_id,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            SolanaEvent::NewRequest(..) => "NewRequest",
+            SolanaEvent::TokenMinted(..) => "TokenMinted",
+        }
+    }
+}
+
+pub async fn subscribe_event(
+    client: &SolanaClient,
+    db: &Database,
+    pause: &BridgePause,
+    validator: &EventValidator,
+) -> Result<()> {
     let (new_request_discriminator, token_minted_discriminator) = event_discriminators();
 
-    let pubsub_client = PubsubClient::new(&client.ws_url).await.unwrap();
-    let (mut subscription, _unsubscribe) = pubsub_client
-        .logs_subscribe(
+    let pubsub_client = with_timeout(
+        "pubsub_connect",
+        client.timeouts.read,
+        PubsubClient::new(&client.ws_url()),
+    )
+    .await?;
+    let (mut subscription, _unsubscribe) = with_timeout(
+        "logs_subscribe",
+        client.timeouts.read,
+        pubsub_client.logs_subscribe(
             solana_client::rpc_config::RpcTransactionLogsFilter::All,
             solana_client::rpc_config::RpcTransactionLogsConfig {
-                commitment: Some(CommitmentConfig::finalized()),
+                commitment: Some(client.processing_commitment()),
             },
-        )
-        .await
-        .unwrap();
+        ),
+    )
+    .await?;
 
     info!("Listening for solana events...");
 
+    // Events are decoded here and handled concurrently across requests, in order per request
+    let pool = KeyedTaskPool::new(SOLANA_EVENT_WORKERS);
     while let Some(logs) = subscription.next().await {
+        let slot = logs.context.slot;
         for log in logs.value.logs {
-            if log.contains(&new_request_discriminator) {
-                match event_new_request(log.as_str()) {
-                    Ok(event) => {
-                        info!("EVENT New Solana request received, request id {} token mint {} token account {}", &event.request_id, &event.mint, &event.user_token_account);
-                        // if event_commit.get(&event.request_id).is_some() {
-                        // info!("Event received for FINALIZED {:?}", event);
-                        check_token_owner(db, client, &event.request_id).await;
-                        // event_commit.remove(&event.request_id);
-                        // } else {
-                        // info!("Event received for CONFIRMED {:?}", event);
-                        // Event is received in the commitment of the transaction but we want to process it when it is finalized
-                        // event_commit.insert(event.request_id);
-                        // }
-                    }
-                    Err(e) => {
-                        error!("Failed to decode event: {}", e);
-                    }
+            let Some(event) = decode_log(
+                &log,
+                &new_request_discriminator,
+                &token_minted_discriminator,
+            ) else {
+                continue;
+            };
+            let request_id = event.request_id().to_string();
+            let (client, db, pause, validator) =
+                (client.clone(), db.clone(), pause.clone(), validator.clone());
+            pool.dispatch(&request_id, async move {
+                let span = event_span(&Chains::SOLANA, event.name(), event.request_id(), &db);
+                if let Err(e) = handle_event(&client, &db, &pause, &validator, event, slot)
+                    .instrument(span)
+                    .await
+                {
+                    error!("Handling Solana event of request {request_id} failed: {e}");
+                }
+            })
+            .await;
+            client.event_cursor.record(slot, client.clock.now());
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn decode_log(
+    log: &str,
+    new_request_discriminator: &str,
+    token_minted_discriminator: &str,
+) -> Option<SolanaEvent> {
+    let event = if log.contains(new_request_discriminator) {
+        event_new_request(log)
+    } else if log.contains(token_minted_discriminator) {
+        event_token_minted(log)
+    } else {
+        return None;
+    };
+    event
+        .inspect_err(|e| error!("Failed to decode event: {}", e))
+        .ok()
+}
+
+async fn handle_event(
+    client: &SolanaClient,
+    db: &Database,
+    pause: &BridgePause,
+    validator: &EventValidator,
+    event: SolanaEvent,
+    slot: u64,
+) -> Result<()> {
+    match event {
+        SolanaEvent::NewRequest(request_id, event) => {
+            info!(
+                "EVENT New Solana request received, request id {} token mint {} token account {}",
+                &request_id,
+                &event.mint,
+                log_account(&event.user_token_account.to_string())
+            );
+            if !validator.validate(
+                db,
+                &Chains::SOLANA,
+                &request_id,
+                &event.mint.to_string(),
+                None,
+                client.clock.now(),
+            )? {
+                return Ok(());
+            }
+            // Tokens transferred without an API request wait for their owner to claim them
+            let Some(mut request) = types::request_data(&request_id, db)? else {
+                return record_orphan_request(
+                    db,
+                    client,
+                    &request_id,
+                    &event.mint,
+                    &event.user_token_account,
+                )
+                .await;
+            };
+            if request.lock_block != Some(slot) {
+                request.lock_block = Some(slot);
+                request.save(db)?;
+            }
+            if pause.paused(&Chains::SOLANA).is_some() {
+                pause.buffer_event(db, &request_id)?;
+                return Ok(());
+            }
+            if let Err(e) = check_token_owner(db, client, &request_id).await {
+                error!("Checking owner of request {}, error {}", &request_id, e);
+                if e.is_corrupted_data() {
+                    cancel_corrupted_request(db, &request_id, &e.to_string(), client.clock.now());
                 }
             }
-            if log.contains(&token_minted_discriminator) {
-                match event_token_minted(log.as_str()) {
-                    Ok(event) => {
-                        info!("EVENT New Solana token minted for request Id {} with token mint {} token account {}", &event.request_id, &event.mint, &event.destination_token_account);
-                        // if event_commit.get(&event.request_id).is_some() {
-                        // info!("Event received for FINALIZED second time {:?}", event);
-                        if let Ok(Some(mut request)) = types::request_data(&event.request_id, db) {
-                            if request.status == Status::TokenMinted {
-                                if request.output.detination_contract_id_or_mint
-                                    == event.mint.to_string()
-                                    && request.output.detination_token_id_or_account
-                                        == event.destination_token_account.to_string()
-                                {
-                                    request.update_state(db)?;
-                                }
-                            }
-                        }
-                        // event_commit.remove(&event.request_id);
-                        // } else {
-                        // info!("Event received for CONFIRMED {:?}", event);
-                        // Event is received in the commitment of the transaction but we want to process it when it is finalized
-                        // event_commit.insert(event.request_id);
-                        // }
-                    }
-                    Err(e) => {
-                        error!("Failed to decode event: {}", e);
-                    }
+        }
+        SolanaEvent::TokenMinted(request_id, event) => {
+            info!(
+                "EVENT New Solana token minted for request Id {} with token mint {} token account {}",
+                &request_id,
+                &event.mint,
+                log_account(&event.destination_token_account.to_string())
+            );
+            if let Ok(Some(mut request)) = types::request_data(&request_id, db) {
+                let now = client.clock.now();
+                if request.status == Status::TokenMinted
+                    && request.output.detination_contract_id_or_mint == event.mint.to_string()
+                    && request.output.detination_token_id_or_account
+                        == event.destination_token_account.to_string()
+                    && verify_destination_owner(client, db, &mut request, now).await?
+                {
+                    request.complete_minted(
+                        db,
+                        &event.mint.to_string(),
+                        &event.destination_token_account.to_string(),
+                        now,
+                    )?;
                 }
             }
         }
     }
-
     Ok(())
 }
 
-fn event_new_request(base64_data: &str) -> Result<NewRequestEvent> {
-    let decoder_data = decode_event(base64_data)?;
-    Ok(NewRequestEvent {
-        mint: decoder_data.0,
-        user_token_account: decoder_data.1,
-        request_id: decoder_data.2,
-    })
+fn event_new_request(base64_data: &str) -> Result<SolanaEvent> {
+    let (mint, user_token_account, request_id) = decode_event(base64_data)?;
+    let event = NewRequestEvent {
+        mint,
+        user_token_account,
+        request_id: request_id.to_string(),
+    };
+    Ok(SolanaEvent::NewRequest(request_id, event))
 }
 
-fn event_token_minted(base64_data: &str) -> Result<TokenMintedEvent> {
-    let decoder_data = decode_event(base64_data)?;
-    Ok(TokenMintedEvent {
-        mint: decoder_data.0,
-        destination_token_account: decoder_data.1,
-        request_id: decoder_data.2,
-    })
+fn event_token_minted(base64_data: &str) -> Result<SolanaEvent> {
+    let (mint, destination_token_account, request_id) = decode_event(base64_data)?;
+    let event = TokenMintedEvent {
+        mint,
+        destination_token_account,
+        request_id: request_id.to_string(),
+    };
+    Ok(SolanaEvent::TokenMinted(request_id, event))
 }
 
-pub fn decode_event(base64_data: &str) -> Result<(Pubkey, Pubkey, String)> {
-    let log_data: String = base64_data.replace("Program data: ", "");
-    let decoded_data = BASE64_STANDARD.decode(log_data)?;
-    let trim: Vec<u8> = decoded_data[8..decoded_data.len()].to_vec();
+/// Anchor discriminator, mint and token account, then the length of the request id
+const EVENT_HEADER_LEN: usize = 8 + 32 + 32 + 4;
 
-    // Mint + token account size
-    let expected_size = 64;
-    let (token_data, request_id_data) = trim.split_at(expected_size);
+fn malformed(reason: impl Into<String>) -> SolanaError {
+    SolanaError::MalformedEvent(reason.into())
+}
 
-    let mint = Pubkey::try_from_slice(&token_data[0..32])?;
-    let token_account = Pubkey::try_from_slice(&token_data[32..64])?;
+/// Decodes the data of a bridge program event log, its mint, token account and request
+/// id. Data of another layout is a `MalformedEvent` error, whatever its length
+pub fn decode_event(base64_data: &str) -> Result<(Pubkey, Pubkey, RequestId), SolanaError> {
+    let log_data: String = base64_data.replace("Program data: ", "");
+    let data = BASE64_STANDARD
+        .decode(log_data)
+        .map_err(|e| malformed(format!("not base64, {e}")))?;
+    if data.len() < EVENT_HEADER_LEN {
+        return Err(malformed(format!(
+            "{} bytes, shorter than the {EVENT_HEADER_LEN} bytes before the request id",
+            data.len()
+        )));
+    }
 
-    // The rest is request id
-    let request_id = str::from_utf8(request_id_data)?.to_string();
-    let id_trimmed: String = request_id[1..request_id.len()]
-        .trim_matches('\0')
-        .to_string();
+    let key = |range: std::ops::Range<usize>| {
+        Pubkey::try_from(&data[range]).map_err(|e| malformed(e.to_string()))
+    };
+    let mint = key(8..40)?;
+    let token_account = key(40..72)?;
 
-    Ok((mint, token_account, id_trimmed))
+    // Borsh string, its length then its bytes
+    let mut length = [0u8; 4];
+    length.copy_from_slice(&data[72..EVENT_HEADER_LEN]);
+    let length = u32::from_le_bytes(length) as usize;
+    let request_id_data = data[EVENT_HEADER_LEN..].get(..length).ok_or_else(|| {
+        malformed(format!(
+            "request id of {length} bytes, {} left",
+            data.len() - EVENT_HEADER_LEN
+        ))
+    })?;
+    let request_id = str::from_utf8(request_id_data)
+        .map_err(|e| malformed(format!("request id is not UTF-8, {e}")))?;
+    let request_id = RequestId::parse(request_id).map_err(|e| malformed(e.to_string()))?;
+
+    Ok((mint, token_account, request_id))
 }
 
-fn event_discriminators() -> (String, String) {
+pub(crate) fn event_discriminators() -> (String, String) {
     // Encoding adds at the end "4=" that is not needed
     let mut new_request_discriminator = BASE64_STANDARD
         .encode(NewRequestEvent::DISCRIMINATOR)
@@ -144,3 +283,201 @@ fn event_discriminators() -> (String, String) {
 
     (new_request_discriminator, token_minted_discriminator)
 }
+
+#[cfg(test)]
+mod sol_events_test {
+    use anchor_lang::Discriminator;
+    use proptest::prelude::*;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::pubkey::Pubkey;
+    use test_support::{
+        mock_rpc, new_request_event_data, program_data_log, solana_key, test_db,
+        token_account_result, token_minted_event_data, EVM_ACCOUNT,
+        NEW_REQUEST_EVENT_DISCRIMINATOR, TOKEN_MINTED_EVENT_DISCRIMINATOR,
+    };
+    use types::{
+        request_data, system_clock, BRequest, BridgePause, Chains, ErrorClass, EventValidator,
+        InputRequest, Status, Timestamp,
+    };
+
+    use super::{decode_event, decode_log, event_discriminators, handle_event, SolanaEvent};
+    use crate::{
+        solana_bridge::events::{NewRequestEvent, TokenMintedEvent},
+        test_utils::test_client,
+        SolanaError,
+    };
+
+    /// Encoding of either event, then cut, given another request id length, with a byte
+    /// changed or with bytes appended. Mutation 4 leaves it valid
+    fn near_valid_event() -> impl Strategy<Value = (Vec<u8>, bool)> {
+        (
+            any::<[u8; 32]>(),
+            any::<[u8; 32]>(),
+            any::<[u8; 32]>(),
+            any::<bool>(),
+            0u8..5,
+            any::<usize>(),
+            any::<u32>(),
+            any::<u8>(),
+        )
+            .prop_map(
+                |(mint, account, id, minted, mutation, position, length, byte)| {
+                    let request_id: String = id.iter().map(|b| format!("{b:02x}")).collect();
+                    let encode = if minted {
+                        token_minted_event_data
+                    } else {
+                        new_request_event_data
+                    };
+                    let mut data = encode(
+                        &Pubkey::new_from_array(mint),
+                        &Pubkey::new_from_array(account),
+                        &format!("0x{request_id}"),
+                    );
+                    match mutation {
+                        0 => data.truncate(position % data.len()),
+                        1 => data[72..76].copy_from_slice(&length.to_le_bytes()),
+                        2 => {
+                            let at = position % data.len();
+                            data[at] = byte;
+                        }
+                        3 => data.extend(vec![byte; position % 64 + 1]),
+                        _ => {}
+                    }
+                    (data, mutation == 4)
+                },
+            )
+    }
+
+    fn assert_typed(result: Result<(Pubkey, Pubkey, types::RequestId), SolanaError>) {
+        if let Err(e) = result {
+            assert!(matches!(e, SolanaError::MalformedEvent(_)), "{e}");
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_arbitrary_event_data_never_panics(
+            data in proptest::collection::vec(any::<u8>(), 0..256)
+        ) {
+            let log = program_data_log(&data);
+            assert_typed(decode_event(&log));
+            let (new_request, token_minted) = event_discriminators();
+            let _ = decode_log(&log, &new_request, &token_minted);
+        }
+
+        #[test]
+        fn test_arbitrary_log_text_never_panics(log in "\\PC{0,200}") {
+            assert_typed(decode_event(&log));
+        }
+
+        #[test]
+        fn test_near_valid_events_decode_or_fail_typed((data, valid) in near_valid_event()) {
+            let log = program_data_log(&data);
+            let result = decode_event(&log);
+            prop_assert!(!valid || result.is_ok());
+            assert_typed(result);
+            let (new_request, token_minted) = event_discriminators();
+            let decoded = decode_log(&log, &new_request, &token_minted);
+            prop_assert!(!valid || decoded.is_some());
+        }
+    }
+
+    #[test]
+    fn test_fixture_discriminators_match_program() {
+        assert_eq!(
+            NewRequestEvent::DISCRIMINATOR,
+            NEW_REQUEST_EVENT_DISCRIMINATOR
+        );
+        assert_eq!(
+            TokenMintedEvent::DISCRIMINATOR,
+            TOKEN_MINTED_EVENT_DISCRIMINATOR
+        );
+    }
+
+    #[test]
+    fn test_event_logs_decode_round_trip() {
+        let (new_request, token_minted) = event_discriminators();
+        let request_id = format!("0x{}", "ab".repeat(32));
+        let (mint, account) = (solana_key(1), solana_key(2));
+
+        let log = program_data_log(&new_request_event_data(&mint, &account, &request_id));
+        match decode_log(&log, &new_request, &token_minted) {
+            Some(SolanaEvent::NewRequest(id, event)) => {
+                assert_eq!(id, request_id);
+                assert_eq!(event.mint, mint);
+                assert_eq!(event.user_token_account, account);
+                assert_eq!(event.request_id, request_id);
+            }
+            _ => panic!("Expected a NewRequest event"),
+        }
+
+        let log = program_data_log(&token_minted_event_data(&mint, &account, &request_id));
+        match decode_log(&log, &new_request, &token_minted) {
+            Some(SolanaEvent::TokenMinted(id, event)) => {
+                assert_eq!(id, request_id);
+                assert_eq!(event.mint, mint);
+                assert_eq!(event.destination_token_account, account);
+                assert_eq!(event.request_id, request_id);
+            }
+            _ => panic!("Expected a TokenMinted event"),
+        }
+
+        // Other program logs and events with a malformed request id are skipped
+        for log in [
+            "Program log: Instruction: NewRequest".to_string(),
+            program_data_log(&[1u8; 80]),
+            program_data_log(&new_request_event_data(&mint, &account, "request123")),
+        ] {
+            assert!(decode_log(&log, &new_request, &token_minted).is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_minted_to_another_wallet_is_held() {
+        let db = test_db();
+        let (mint, token_account) = (solana_key(1), solana_key(2));
+        let (destination, other) = (solana_key(3), solana_key(4));
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: EVM_ACCOUNT.to_string(),
+                token_id: "7".to_string(),
+                token_owner: EVM_ACCOUNT.to_string(),
+                origin_network: Chains::EVM,
+                destination_account: destination.to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.status = Status::TokenMinted;
+        request.output.detination_contract_id_or_mint = mint.to_string();
+        request.output.detination_token_id_or_account = token_account.to_string();
+        request.save(&db).unwrap();
+
+        let client = test_client(mock_rpc([(
+            RpcRequest::GetAccountInfo,
+            token_account_result(&mint, &other, 1),
+        )]));
+        let event = SolanaEvent::TokenMinted(
+            request.id.clone(),
+            TokenMintedEvent {
+                mint,
+                destination_token_account: token_account,
+                request_id: request.id.to_string(),
+            },
+        );
+        handle_event(
+            &client,
+            &db,
+            &BridgePause::load(&db, system_clock()),
+            &EventValidator::default(),
+            event,
+            10,
+        )
+        .await
+        .unwrap();
+
+        let stored = request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::NeedsIntervention);
+        let report = stored.failure_report.unwrap();
+        assert_eq!(report.attempts[0].class, ErrorClass::DestinationMismatch);
+    }
+}
@@ -1,25 +1,91 @@
-use std::str::FromStr;
+use std::{str::FromStr, time::Instant};
 
 use anchor_client::{Client, Cluster};
-use eyre::Result;
-use log::info;
+use log::{debug, error, info};
 use solana_sdk::{pubkey::Pubkey, signature::Signature, signer::Signer, transaction::Transaction};
 use storage::db::Database;
-use tokio::sync::mpsc::Receiver;
-use types::{Status, TxMessage};
+use tracing::Instrument;
+use types::{
+    clear_send_intent, debug_detail, is_timeout, mark_intent_sent, pin_token_uri,
+    record_send_intent, resolve_send_intent, sanitize_token_uri, with_fast_path, BRequest,
+    BridgeError, CancelReason, Chains, ErrorComponent, FastPath, InFlightRegistry, LoggableMessage,
+    MessageNewRequest, RequestId, Result, SendIntent, SendOperation, Status, TaskHealth, Timestamp,
+    TxMessage, TxReceiver, WrapCallContext, PROCESSOR_IDLE_TICK,
+};
 
-use crate::{solana_bridge, SolanaClient};
+use crate::{
+    collection_mint_for, confirm_custody_released, mint_instructions, parse_pubkey, solana_bridge,
+    token_account_prefunded, SolanaClient, SolanaError, SolanaSendLookup, BRIDGED_NAME,
+    BRIDGED_SYMBOL,
+};
 
 use solana_bridge::client::args;
 
+/// Signs `transaction` with the latest blockhash and sends it. An expired blockhash is
+/// replaced at once by a fresh one, see `FastPath::FreshBlockhash`.
+///
+/// The send is journaled with `intent`: the intent left by an earlier send is looked up
+/// first and its transaction taken as sent when found, then the intent is recorded with
+/// the blockhash of each attempt before it is sent. The caller clears it once the
+/// signature is recorded on the request
+async fn send_with_fresh_blockhash(
+    client: &SolanaClient,
+    db: &Database,
+    transaction: Transaction,
+    operation: &str,
+    intent: SendIntent,
+) -> Result<Signature> {
+    let (request_id, journaled) = (&intent.request_id, intent.operation);
+    let lookup = SolanaSendLookup {
+        client: client.clone(),
+    };
+    if let Some(signature) = resolve_send_intent(db, &lookup, request_id, journaled)
+        .await
+        .with_call_context(|| client.call_context(operation, request_id))?
+    {
+        return Signature::from_str(&signature).map_err(BridgeError::from_error);
+    }
+
+    let sent = with_fast_path(FastPath::FreshBlockhash, &client.blockhash_resends, |_| {
+        let transaction = transaction.clone();
+        let mut intent = intent.clone();
+        async move {
+            let recent_blockhash = client
+                .get_latest_blockhash()
+                .await
+                .with_call_context(|| client.call_context("get_latest_blockhash", request_id))?;
+            intent.recent_blockhash = Some(recent_blockhash.to_string());
+            intent.recorded_at = client.clock.now();
+            record_send_intent(db, &intent)?;
+            client
+                .sign_and_send(transaction, recent_blockhash)
+                .await
+                .with_call_context(|| client.call_context(operation, request_id))
+        }
+    })
+    .await;
+
+    match &sent {
+        Ok(signature) => mark_intent_sent(db, request_id, journaled, &signature.to_string())?,
+        // A send timing out may have reached the node, its intent is looked up before the
+        // next one
+        Err(e) if is_timeout(e) => {}
+        Err(_) => clear_send_intent(db, request_id, journaled),
+    }
+    sent
+}
+
 pub async fn initialize_request(
     client: &SolanaClient,
+    db: &Database,
     mint_account: &str,
     user_account: &str,
-    request_id: &str,
+    request_id: &RequestId,
 ) -> Result<Signature> {
-    let token_mint_pubkey = Pubkey::from_str(mint_account)?;
-    let user_token_account_pubkey = Pubkey::from_str(user_account)?;
+    client.ensure_authorized_backend()?;
+    let token_mint_pubkey = Pubkey::from_str(mint_account).map_err(BridgeError::from_error)?;
+    let user_token_account_pubkey =
+        Pubkey::from_str(user_account).map_err(BridgeError::from_error)?;
     let bridge_token_account_pubkey = spl_associated_token_account::get_associated_token_address(
         &client.bridge_account,
         &token_mint_pubkey,
@@ -28,11 +94,13 @@ pub async fn initialize_request(
     info!("Bridge token account {}", bridge_token_account_pubkey);
 
     let program_client = Client::new(
-        Cluster::Custom(client.rpc.url(), client.ws_url.clone()),
+        Cluster::Custom(client.rpc().url(), client.ws_url()),
         client.signer.clone(),
     );
 
-    let program = program_client.program(client.bridge_program)?;
+    let program = program_client
+        .program(client.bridge_program)
+        .map_err(BridgeError::from_error)?;
 
     let instruction = program
         .request()
@@ -49,50 +117,107 @@ pub async fn initialize_request(
         .args(args::NewRequest {
             request_id: request_id.to_string(),
         })
-        .instructions()?
+        .instructions()
+        .map_err(BridgeError::from_error)?
         .remove(0);
 
     // Create a transaction and add the instruction
-    let mut transaction =
-        Transaction::new_with_payer(&[instruction], Some(&client.signer.pubkey()));
+    let transaction = client.new_transaction(&[instruction]);
 
-    // Sign the transaction
-    let recent_blockhash = client.rpc.get_latest_blockhash()?;
-    transaction.sign(&[&client.signer], recent_blockhash);
-
-    // Send the transaction
-    let signature = client.rpc.send_and_confirm_transaction(&transaction)?;
+    // Sign with a fresh blockhash, check the signers and send the transaction
+    let mut intent = SendIntent::new(request_id, Chains::SOLANA, SendOperation::Lock);
+    intent.destination = Some(bridge_token_account_pubkey.to_string());
+    let signature =
+        send_with_fresh_blockhash(client, db, transaction, "new_request", intent).await?;
 
     info!("Transaction successful with signature: {}", signature);
 
     Ok(signature)
 }
 
+/// Sends the lock transaction of a request accepted by the API before it, None when the
+/// request is no longer initializing. A lock that can't be sent cancels the request
+pub async fn lock_request(
+    client: &SolanaClient,
+    db: &Database,
+    message: &MessageNewRequest,
+) -> Result<Option<Signature>> {
+    let Some(mut request) = types::request_data(&message.request_id, db)? else {
+        return Ok(None);
+    };
+    if request.status != Status::Initializing {
+        return Ok(None);
+    }
+    let client = &client.for_request(&request)?;
+    match initialize_request(
+        client,
+        db,
+        &message.token_contract,
+        &message.token_owner,
+        &message.request_id,
+    )
+    .await
+    {
+        Ok(signature) => {
+            request.lock_sent(&signature.to_string(), db, client.clock.now())?;
+            clear_send_intent(db, &request.id, SendOperation::Lock);
+            Ok(Some(signature))
+        }
+        Err(e) => {
+            request.lock_not_sent(db, CancelReason::LockFailed, &e, client.clock.now())?;
+            Err(e)
+        }
+    }
+}
+
 pub async fn mint_new_token(
     client: &SolanaClient,
     db: &Database,
-    request_id: &str,
+    request_id: &RequestId,
     token_metadata: &str,
 ) -> Result<Signature> {
     if let Ok(Some(mut request)) = types::request_data(request_id, db) {
+        let client = &client.for_request(&request)?;
+        client.ensure_authorized_backend()?;
+        let now = client.clock.now();
+        // A retried mint sends the metadata of the first attempt, read and pinned once
+        let token_metadata = match request.metadata_snapshot.clone() {
+            Some(snapshot) => snapshot.token_uri,
+            None => {
+                let Some(sanitized) =
+                    sanitize_token_uri(&client.uri_policy, &mut request, token_metadata, db, now)?
+                else {
+                    return Ok(Signature::default());
+                };
+                let token_uri = pin_token_uri(
+                    client.metadata_pinning.as_ref(),
+                    &mut request,
+                    token_metadata,
+                    sanitized,
+                    db,
+                    now,
+                )
+                .await?;
+                request.keep_metadata_snapshot(token_metadata, &token_uri, now, db)?;
+                token_uri
+            }
+        };
+        let branded = client.branding.brand_mint(
+            &mut request,
+            BRIDGED_NAME,
+            BRIDGED_SYMBOL,
+            &token_metadata,
+            client.uri_policy.max_length,
+        );
+
         let origin_contract = &request.input.contract_or_mint;
         let detination_account = &request.input.destination_account;
         let token_id = &request.input.token_id;
 
-        let destination_pubkey = Pubkey::from_str(&detination_account)?;
-        let token_id_i64 = u64::from_str(&token_id).unwrap();
+        let destination_pubkey = parse_pubkey("destination account", &detination_account)?;
+        let token_id_i64 = parse_token_id(token_id)?;
         let contract_seeds = origin_contract.split_at(origin_contract.len() / 2);
-
-        let mint_pubkey = Pubkey::find_program_address(
-            &[
-                b"mint",
-                contract_seeds.0.as_bytes(),
-                contract_seeds.1.as_bytes(),
-                &token_id_i64.to_le_bytes(),
-            ],
-            &client.bridge_program,
-        )
-        .0;
+        let mint_pubkey = derive_mint(client, origin_contract, token_id)?;
 
         let user_token_account_pubkey = spl_associated_token_account::get_associated_token_address(
             &destination_pubkey,
@@ -103,6 +228,11 @@ pub async fn mint_new_token(
             "User token account {} for mint {}",
             user_token_account_pubkey, mint_pubkey
         );
+        if !destination_account_ready(client, db, &mut request, &user_token_account_pubkey, now)
+            .await?
+        {
+            return Ok(Signature::default());
+        }
 
         let metadata_pubkey = Pubkey::find_program_address(
             &[
@@ -126,11 +256,13 @@ pub async fn mint_new_token(
         .0;
 
         let program_client = Client::new(
-            Cluster::Custom(client.rpc.url(), client.ws_url.clone()),
+            Cluster::Custom(client.rpc().url(), client.ws_url()),
             client.signer.clone(),
         );
 
-        let program = program_client.program(client.bridge_program)?;
+        let program = program_client
+            .program(client.bridge_program)
+            .map_err(BridgeError::from_error)?;
 
         let instruction = program
             .request()
@@ -152,35 +284,47 @@ pub async fn mint_new_token(
                 id: token_id_i64,
                 seed_p1: contract_seeds.0.to_string(),
                 seed_p2: contract_seeds.1.to_string(),
-                name: "Bridged NFT".to_string(),
-                symbol: "BNFT".to_string(),
-                uri: token_metadata.to_string(),
+                name: branded.name,
+                symbol: branded.symbol,
+                uri: branded.uri.clone(),
                 request_id: request_id.to_string(),
             })
-            .instructions()?
+            .instructions()
+            .map_err(BridgeError::from_error)?
             .remove(0);
 
-        // Create a transaction and add the instruction
-        let mut transaction =
-            Transaction::new_with_payer(&[instruction], Some(&client.signer.pubkey()));
+        // Marketplaces group the tokens of the origin contract by this collection
+        let collection_mint = collection_mint_for(client, db, origin_contract, request_id).await?;
+        let instructions = mint_instructions(
+            instruction,
+            &client.signer.pubkey(),
+            &mint_pubkey,
+            &collection_mint,
+        );
 
-        // Sign the transaction
-        let recent_blockhash = client.rpc.get_latest_blockhash()?;
-        transaction.sign(&[&client.signer], recent_blockhash);
+        // Create a transaction and add the instructions
+        let transaction = client.new_transaction(&instructions);
 
-        // Send the transaction
-        let signature = client.rpc.send_and_confirm_transaction(&transaction)?;
+        // Sign with a fresh blockhash, check the signers and send the transaction
+        let mut intent = SendIntent::new(request_id, Chains::SOLANA, SendOperation::Mint);
+        intent.destination = Some(mint_pubkey.to_string());
+        let signature =
+            send_with_fresh_blockhash(client, db, transaction, "create_nft", intent).await?;
 
         info!("Transaction successful with signature: {}", signature);
 
+        request.minted_token_uri = Some(branded.uri);
         request.add_tx(&signature.to_string(), db)?;
+        clear_send_intent(db, request_id, SendOperation::Mint);
+        let sent_at = client.clock.now();
         if request.status == Status::TokenReceived {
-            request.update_state(db)?;
+            request.update_state(db, sent_at)?;
         }
         request.finalize(
             db,
             &mint_pubkey.to_string(),
             &user_token_account_pubkey.to_string(),
+            sent_at,
         )?;
 
         return Ok(signature);
@@ -188,39 +332,320 @@ pub async fn mint_new_token(
     Ok(Signature::default())
 }
 
+/// Whether the mint of the request can be sent. When the relayer requires prefunded
+/// destination token accounts, a TokenReceived request whose `token_account` doesn't hold
+/// its rent yet is parked in AwaitingDestinationAccount. Parked requests are resumed by
+/// `recheck_destination_account`
+async fn destination_account_ready(
+    client: &SolanaClient,
+    db: &Database,
+    request: &mut BRequest,
+    token_account: &Pubkey,
+    now: Timestamp,
+) -> Result<bool> {
+    match request.status {
+        Status::AwaitingDestinationAccount => Ok(false),
+        Status::TokenReceived if client.require_prefunded_ata => {
+            if token_account_prefunded(client, token_account).await? {
+                return Ok(true);
+            }
+            info!(
+                "Request {} waits for its destination token account {}",
+                request.id, token_account
+            );
+            request.await_destination_account(db, &token_account.to_string(), now)?;
+            Ok(false)
+        }
+        _ => Ok(true),
+    }
+}
+
+/// Transfers the origin token of a canceled Solana request from the bridge token account
+/// back to the token account it was locked from
+pub async fn refund_token(
+    client: &SolanaClient,
+    db: &Database,
+    request_id: &RequestId,
+) -> Result<Signature> {
+    let Some(mut request) = types::request_data(request_id, db)? else {
+        return Ok(Signature::default());
+    };
+    if !request.refundable() {
+        info!("Request {request_id} is not refundable, skipping the refund");
+        return Ok(Signature::default());
+    }
+    let client = &client.for_request(&request)?;
+    client.ensure_authorized_backend()?;
+    let mint_pubkey = parse_pubkey("token mint", &request.input.contract_or_mint)?;
+    let user_token_account_pubkey = parse_pubkey("token account", &request.input.token_owner)?;
+    let bridge_token_account_pubkey = spl_associated_token_account::get_associated_token_address(
+        &client.bridge_account,
+        &mint_pubkey,
+    );
+
+    let program_client = Client::new(
+        Cluster::Custom(client.rpc().url(), client.ws_url()),
+        client.signer.clone(),
+    );
+
+    let program = program_client
+        .program(client.bridge_program)
+        .map_err(BridgeError::from_error)?;
+
+    let instruction = program
+        .request()
+        .accounts(solana_bridge::client::accounts::ReleaseToken {
+            bridge: client.bridge_account,
+            mint: mint_pubkey,
+            bridge_token_account: bridge_token_account_pubkey,
+            user_token_account: user_token_account_pubkey,
+            backend: client.signer.pubkey(),
+            token_program: spl_token::ID,
+        })
+        .args(args::ReleaseToken {
+            request_id: request_id.to_string(),
+        })
+        .instructions()
+        .map_err(BridgeError::from_error)?
+        .remove(0);
+
+    let transaction = client.new_transaction(&[instruction]);
+
+    // Sign with a fresh blockhash, check the signers and send the transaction
+    let mut intent = SendIntent::new(request_id, Chains::SOLANA, SendOperation::Refund);
+    intent.destination = Some(user_token_account_pubkey.to_string());
+    let signature =
+        send_with_fresh_blockhash(client, db, transaction, "release_token", intent).await?;
+
+    // The refund is recorded once the bridge token account no longer holds the token
+    confirm_custody_released(client, &mint_pubkey, &signature).await?;
+    info!("Token of request {request_id} refunded with signature: {signature}");
+    request.complete_refund(db, &signature.to_string(), client.clock.now())?;
+    clear_send_intent(db, request_id, SendOperation::Refund);
+    Ok(signature)
+}
+
+/// Mint created by the bridge program for an EVM token, derived from its contract and id
+pub fn derive_mint(client: &SolanaClient, origin_contract: &str, token_id: &str) -> Result<Pubkey> {
+    let token_id = parse_token_id(token_id)?;
+    let contract_seeds = origin_contract.split_at(origin_contract.len() / 2);
+
+    Ok(Pubkey::find_program_address(
+        &[
+            b"mint",
+            contract_seeds.0.as_bytes(),
+            contract_seeds.1.as_bytes(),
+            &token_id.to_le_bytes(),
+        ],
+        &client.bridge_program,
+    )
+    .0)
+}
+
+fn parse_token_id(token_id: &str) -> Result<u64, SolanaError> {
+    u64::from_str(token_id).map_err(|_| SolanaError::InvalidData {
+        field: "token id",
+        value: token_id.to_string(),
+    })
+}
+
+/// Sends the transactions of the messages of `rx_channel` until it is closed, beating
+/// `health` after each message and each `PROCESSOR_IDLE_TICK` without message
 pub async fn process_message(
     client: SolanaClient,
     db: &Database,
-    mut rx_channel: Receiver<TxMessage>,
+    rx_channel: &mut TxReceiver,
+    in_flight: InFlightRegistry,
+    health: TaskHealth,
 ) {
-    while let Some(message) = rx_channel.recv().await {
-        info!("Message received in solana tx processor {:?}", &message);
-        match message.accion {
-            types::Function::Mint => {
-                if let Some(mint_data) = message.mint_data {
-                    let tx_result = mint_new_token(
-                        &client,
-                        db,
+    loop {
+        health.beat(Timestamp::now());
+        let message = match tokio::time::timeout(PROCESSOR_IDLE_TICK, rx_channel.recv()).await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(_) => continue,
+        };
+        info!(
+            "Message received in solana tx processor {}",
+            LoggableMessage::new(&message)
+        );
+        if let Some(detail) = debug_detail(&message) {
+            debug!("Full message in solana tx processor {detail}");
+        }
+        let received_at = Instant::now();
+        match &message {
+            TxMessage::Mint(mint_data) => {
+                // The mint stays in flight until the sweep sees it settled, so a queued
+                // duplicate is never sent while the first one is unconfirmed
+                let sent = in_flight
+                    .send_mint(
                         &mint_data.request_id,
-                        &mint_data.token_metadata,
+                        db,
+                        mint_new_token(
+                            &client,
+                            db,
+                            &mint_data.request_id,
+                            &mint_data.token_metadata,
+                        )
+                        .instrument(message.span(&Chains::SOLANA)),
                     )
                     .await;
-                    info!("Transaction result {:?}", tx_result);
+                let Some(tx_result) = sent else {
+                    info!(
+                        "Dropping duplicated mint message for request {}",
+                        &mint_data.request_id
+                    );
+                    continue;
+                };
+                rx_channel.record_processing(&message.function(), received_at);
+                match tx_result {
+                    // Nothing was sent
+                    Ok(signature) if signature == Signature::default() => {
+                        in_flight.release(&mint_data.request_id, db)
+                    }
+                    Ok(signature) => info!("Transaction result {signature}"),
+                    Err(e) if e.is_corrupted_data() => cancel_corrupted_request(
+                        db,
+                        &mint_data.request_id,
+                        &e.to_string(),
+                        client.clock.now(),
+                    ),
+                    Err(e) => types::record_request_error(
+                        db,
+                        &mint_data.request_id,
+                        ErrorComponent::Solana,
+                        &e,
+                        client.clock.now(),
+                    ),
                 }
             }
-            // TODO not used yet
-            types::Function::NewRequest => {
-                if let Some(request_data) = message.request_data {
-                    initialize_request(
-                        &client,
-                        &request_data.token_contract,
-                        &request_data.token_id,
-                        &request_data.request_id,
-                    )
-                    .await
-                    .unwrap();
+            TxMessage::Refund(refund_data) => {
+                if !in_flight.try_acquire(&refund_data.request_id, db) {
+                    info!(
+                        "Dropping duplicated refund message for request {}",
+                        &refund_data.request_id
+                    );
+                    continue;
+                }
+                let tx_result = refund_token(&client, db, &refund_data.request_id)
+                    .instrument(message.span(&Chains::SOLANA))
+                    .await;
+                in_flight.release(&refund_data.request_id, db);
+                rx_channel.record_processing(&message.function(), received_at);
+                match tx_result {
+                    Ok(signature) => info!("Refund transaction result {signature}"),
+                    Err(e) => types::record_request_error(
+                        db,
+                        &refund_data.request_id,
+                        ErrorComponent::Solana,
+                        &e,
+                        client.clock.now(),
+                    ),
+                }
+            }
+            TxMessage::NewRequest(request_data) => {
+                if !in_flight.try_acquire(&request_data.request_id, db) {
+                    info!(
+                        "Dropping duplicated lock message for request {}",
+                        &request_data.request_id
+                    );
+                    continue;
+                }
+                let tx_result = lock_request(&client, db, request_data)
+                    .instrument(message.span(&Chains::SOLANA))
+                    .await;
+                in_flight.release(&request_data.request_id, db);
+                rx_channel.record_processing(&message.function(), received_at);
+                match tx_result {
+                    Ok(Some(signature)) => info!("Lock transaction result {signature}"),
+                    Ok(None) => info!(
+                        "Lock of request {} already handled",
+                        &request_data.request_id
+                    ),
+                    Err(e) => error!(
+                        "Lock of request {} not sent, error {:?}",
+                        &request_data.request_id, e
+                    ),
                 }
             }
         }
     }
 }
+
+/// Cancels a request whose data can't be parsed, the pending sweep removes it from the list
+pub fn cancel_corrupted_request(db: &Database, request_id: &str, error: &str, now: Timestamp) {
+    if let Ok(Some(mut request)) = types::request_data(request_id, db) {
+        error!("Canceling request {request_id} with corrupted data: {error}");
+        request
+            .cancel_corrupted(db, error, now)
+            .unwrap_or_else(|err| error!("Could not cancel request {request_id}, error {err:?}"));
+    }
+}
+
+#[cfg(test)]
+mod sol_txs_test {
+    use solana_client::rpc_request::RpcRequest;
+    use test_support::{balance_result, mock_rpc, request_in_status, solana_key, test_db};
+    use types::{Chains, Status, Timestamp, TOKEN_ACCOUNT_RENT};
+
+    use super::destination_account_ready;
+    use crate::test_utils::test_client;
+
+    const NOW: Timestamp = Timestamp::from_secs(1_700_000_000);
+
+    #[tokio::test]
+    async fn test_mint_parked_until_the_destination_account_is_funded() {
+        let db = test_db();
+        let token_account = solana_key(5);
+        let mut request = request_in_status(Chains::EVM, "1", Status::TokenReceived);
+        request.save(&db).unwrap();
+
+        let mut client = test_client(mock_rpc([(RpcRequest::GetBalance, balance_result(0))]));
+        client.require_prefunded_ata = true;
+        assert!(
+            !destination_account_ready(&client, &db, &mut request, &token_account, NOW)
+                .await
+                .unwrap()
+        );
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::AwaitingDestinationAccount);
+        let pending = stored.destination_account_pending.unwrap();
+        assert_eq!(pending.token_account, token_account.to_string());
+        assert_eq!(pending.lamports, TOKEN_ACCOUNT_RENT);
+
+        // A parked request is not minted, even when funded meanwhile
+        let rent = TOKEN_ACCOUNT_RENT.raw();
+        client.set_rpc(mock_rpc([(RpcRequest::GetBalance, balance_result(rent))]));
+        assert!(
+            !destination_account_ready(&client, &db, &mut request, &token_account, NOW)
+                .await
+                .unwrap()
+        );
+
+        let mut funded = request_in_status(Chains::EVM, "2", Status::TokenReceived);
+        funded.save(&db).unwrap();
+        assert!(
+            destination_account_ready(&client, &db, &mut funded, &token_account, NOW)
+                .await
+                .unwrap()
+        );
+        assert_eq!(funded.status, Status::TokenReceived);
+    }
+
+    #[tokio::test]
+    async fn test_mint_not_parked_when_prefunding_is_disabled() {
+        let db = test_db();
+        let mut request = request_in_status(Chains::EVM, "1", Status::TokenReceived);
+        request.save(&db).unwrap();
+
+        let client = test_client(mock_rpc([(RpcRequest::GetBalance, balance_result(0))]));
+        assert!(
+            destination_account_ready(&client, &db, &mut request, &solana_key(5), NOW)
+                .await
+                .unwrap()
+        );
+        let stored = types::request_data(&request.id, &db).unwrap().unwrap();
+        assert_eq!(stored.status, Status::TokenReceived);
+        assert_eq!(stored.destination_account_pending, None);
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, signature::Keypair};
+use types::{
+    system_clock, tx_channel, BrandingConfig, Chains, ChannelMetrics, ConfirmationStrategy,
+    RpcHealth, RpcTimeouts, SharedEventCursor, UriPolicy,
+};
+
+use crate::{SolanaClient, SolanaNode};
+
+/// Client over `rpc`, a `test_support::mock_rpc` for calls without network access
+pub fn test_client(rpc: RpcClient) -> SolanaClient {
+    let (tx_channel, _rx_channel) = tx_channel(Chains::EVM, 1, &ChannelMetrics::default());
+    SolanaClient {
+        node: SolanaNode::new(rpc, "ws://localhost:8900").shared(),
+        signer: Arc::new(Keypair::new()),
+        fee_payer: None,
+        bridge_program: Pubkey::new_unique(),
+        bridge_account: Pubkey::new_unique(),
+        tx_channel,
+        block_explorer: String::new(),
+        uri_policy: UriPolicy::default(),
+        metadata_pinning: None,
+        branding: BrandingConfig::default(),
+        timeouts: RpcTimeouts::default(),
+        authorized_backend: Arc::new(true.into()),
+        missing_signers: Arc::default(),
+        blockhash_resends: Arc::default(),
+        dev_mode: false,
+        confirmation: ConfirmationStrategy::default(),
+        event_cursor: SharedEventCursor::default(),
+        rpc_health: RpcHealth::default(),
+        bridge_deployment: None,
+        require_prefunded_ata: false,
+        clock: system_clock(),
+    }
+}
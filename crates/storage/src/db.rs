@@ -1,54 +1,422 @@
-use log::trace;
-use rocksdb::{Options, DB};
+use log::{info, trace};
 use serde::{Deserialize, Serialize};
-use std::{path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 
-use crate::errors::DbError;
+#[cfg(feature = "rocksdb")]
+use std::path::Path;
+
+#[cfg(any(test, feature = "testing"))]
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(feature = "rocksdb")]
+use crate::rocks::RocksEngine;
+use crate::{
+    engine::{BatchOp, MemoryEngine, StorageEngine},
+    errors::DbError,
+    keys::{ColumnFamily, ALIAS_PREFIX, SCHEMA_VERSION_KEY},
+    metrics::{DbOperation, OperationSample, StorageRecorder, StorageSample},
+};
+
+/// Key of the alias record of `alias`, alias records don't collide with the data keys
+pub fn alias_key(alias: &str) -> String {
+    format!("{ALIAS_PREFIX}{alias}")
+}
+
+/// Version of the stored data layout understood by this build. Version 2 keeps the
+/// pending requests in a queue keyed by sequence instead of a list and its index,
+/// version 3 keeps each class of data in its column family
+pub const SCHEMA_VERSION: u32 = 3;
+/// Keys moved per batch when the flat keyspace is split into column families
+const MIGRATION_BATCH_SIZE: usize = 1_000;
 
 #[derive(Clone, Debug)]
 pub struct Database {
-    db: Arc<DB>,
+    engine: Arc<dyn StorageEngine>,
+    /// Held by the conditional writes of every clone, from their check to their write
+    conditional_writes: Arc<Mutex<()>>,
+    /// Next value of each sequence allocated by `next_sequence`, shared by the clones
+    sequences: Arc<Mutex<HashMap<String, u64>>>,
+    /// Writes fail with `DbError::ReadOnly`
+    read_only: bool,
+    /// Measures the engine operations, nothing is timed without it
+    recorder: Option<Arc<dyn StorageRecorder>>,
+    /// Batches fail while set, to test the all or nothing writes
+    #[cfg(any(test, feature = "testing"))]
+    fail_batches: Arc<AtomicBool>,
 }
 
 impl Database {
+    /// Opens the RocksDB database in `path`, created when missing
+    #[cfg(feature = "rocksdb")]
     pub fn open(path: impl AsRef<Path>) -> Result<Self, DbError> {
-        let path_str = path
-            .as_ref()
-            .to_str()
-            .ok_or_else(|| DbError::InvalidPath(format!("{:?}", path.as_ref())))?;
+        Self::with_engine(Arc::new(RocksEngine::open(path)?))
+    }
 
-        let mut opts = Options::default();
-        opts.create_if_missing(true);
+    /// Opens the RocksDB database of a primary relayer in `path` read-only, as a secondary
+    /// instance with its files in `secondary_path`. `catch_up` reads the writes of the
+    /// primary made since. The schema must be the current one, it is never migrated
+    #[cfg(feature = "rocksdb")]
+    pub fn open_read_only(
+        path: impl AsRef<Path>,
+        secondary_path: impl AsRef<Path>,
+    ) -> Result<Self, DbError> {
+        Self::read_only_with_engine(Arc::new(RocksEngine::open_secondary(path, secondary_path)?))
+    }
 
-        let db = DB::open(&opts, path_str).map_err(|e| DbError::RocksDb(e.to_string()))?;
-        Ok(Self { db: Arc::new(db) })
+    /// Empty database kept in memory, its content is lost with the last clone
+    pub fn open_in_memory() -> Self {
+        Self::with_engine(Arc::new(MemoryEngine::default()))
+            .expect("schema version of an empty database")
     }
 
+    /// Database over any engine, the schema version is checked as for `open`
+    pub fn with_engine(engine: Arc<dyn StorageEngine>) -> Result<Self, DbError> {
+        let database = Self {
+            engine,
+            conditional_writes: Arc::default(),
+            sequences: Arc::default(),
+            read_only: false,
+            recorder: None,
+            #[cfg(any(test, feature = "testing"))]
+            fail_batches: Arc::new(AtomicBool::new(false)),
+        };
+        database.check_schema_version()?;
+        Ok(database)
+    }
+
+    /// Read-only database over any engine, refused when its schema is not the current one
+    pub fn read_only_with_engine(engine: Arc<dyn StorageEngine>) -> Result<Self, DbError> {
+        let database = Self {
+            engine,
+            conditional_writes: Arc::default(),
+            sequences: Arc::default(),
+            read_only: true,
+            recorder: None,
+            #[cfg(any(test, feature = "testing"))]
+            fail_batches: Arc::new(AtomicBool::new(false)),
+        };
+        match database.schema_version()? {
+            Some(found) if found > SCHEMA_VERSION => Err(DbError::NewerSchema {
+                found,
+                supported: SCHEMA_VERSION,
+            }),
+            Some(SCHEMA_VERSION) => Ok(database),
+            found => Err(DbError::OlderSchema {
+                found: found.unwrap_or(0),
+                supported: SCHEMA_VERSION,
+            }),
+        }
+    }
+
+    /// Read-only handle on the same data, the writes of `self` are seen at once
+    pub fn as_read_only(&self) -> Database {
+        Database {
+            read_only: true,
+            ..self.clone()
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Handle on the same data whose operations are measured by `recorder`
+    pub fn with_recorder(&self, recorder: Arc<dyn StorageRecorder>) -> Database {
+        Database {
+            recorder: Some(recorder),
+            ..self.clone()
+        }
+    }
+
+    /// Runs an engine operation, timed and reported with the bytes `run` counted when
+    /// there is a recorder
+    fn measured<T>(
+        &self,
+        operation: DbOperation,
+        cf: Option<ColumnFamily>,
+        run: impl FnOnce(&mut usize) -> Result<T, DbError>,
+    ) -> Result<T, DbError> {
+        let Some(recorder) = &self.recorder else {
+            return run(&mut 0);
+        };
+        let mut bytes = 0;
+        let started = Instant::now();
+        let result = run(&mut bytes);
+        recorder.record_operation(&OperationSample {
+            operation,
+            cf,
+            elapsed: started.elapsed(),
+            bytes,
+        });
+        result
+    }
+
+    /// Approximate number of keys of each column family and size of the database on
+    /// disk, also given to the recorder
+    pub fn sample_storage(&self) -> Result<StorageSample, DbError> {
+        let records = ColumnFamily::ALL
+            .into_iter()
+            .map(|cf| Ok((cf, self.engine.approximate_count(cf)?)))
+            .collect::<Result<Vec<_>, DbError>>()?;
+        let sample = StorageSample {
+            records,
+            disk_bytes: self.engine.disk_size()?,
+        };
+        if let Some(recorder) = &self.recorder {
+            recorder.record_sample(&sample);
+        }
+        Ok(sample)
+    }
+
+    /// Reads the writes made by the primary since the open or the last call, nothing to
+    /// do for the databases not following another process
+    pub fn catch_up(&self) -> Result<(), DbError> {
+        self.engine.catch_up()
+    }
+
+    fn check_writable(&self) -> Result<(), DbError> {
+        if self.read_only {
+            return Err(DbError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Refuses databases written by a newer schema and stamps the current one otherwise
+    fn check_schema_version(&self) -> Result<(), DbError> {
+        match self.read::<_, u32>(SCHEMA_VERSION_KEY)? {
+            Some(found) if found > SCHEMA_VERSION => Err(DbError::NewerSchema {
+                found,
+                supported: SCHEMA_VERSION,
+            }),
+            Some(found) if found == SCHEMA_VERSION => Ok(()),
+            _ => {
+                self.migrate_to_column_families()?;
+                self.write_value(SCHEMA_VERSION_KEY, &SCHEMA_VERSION)
+            }
+        }
+    }
+
+    /// Moves the keys of each class from the default column family, where the flat
+    /// keyspace of the older schemas kept them, to the column family of their class. An
+    /// interrupted migration goes on at the next open, the schema is stamped after it
+    fn migrate_to_column_families(&self) -> Result<(), DbError> {
+        let mut start = Vec::new();
+        let mut moved = 0;
+        loop {
+            let mut ops = vec![];
+            let mut last = None;
+            self.engine
+                .iterate_prefix(ColumnFamily::Default, b"", &start, &mut |key, value| {
+                    let cf = ColumnFamily::of_key(key);
+                    if cf != ColumnFamily::Default {
+                        ops.push(BatchOp::Put {
+                            cf,
+                            key: key.to_vec(),
+                            value: value.to_vec(),
+                        });
+                        ops.push(BatchOp::Delete {
+                            cf: ColumnFamily::Default,
+                            key: key.to_vec(),
+                        });
+                        last = Some(key.to_vec());
+                    }
+                    Ok(ops.len() < 2 * MIGRATION_BATCH_SIZE)
+                })?;
+            // Moved keys are gone from the default column family, the next scan starts
+            // at the last one
+            let Some(last) = last else {
+                break;
+            };
+            moved += ops.len() / 2;
+            self.engine.write_batch(ops)?;
+            start = last;
+        }
+        if moved > 0 {
+            info!("Moved {moved} keys to their column families");
+        }
+        Ok(())
+    }
+
+    pub fn schema_version(&self) -> Result<Option<u32>, DbError> {
+        self.read(SCHEMA_VERSION_KEY)
+    }
+
+    /// Writes `value` under `key` in the column family of its class
     pub fn write_value<K: AsRef<[u8]>, V: Serialize>(
         &self,
         key: K,
         value: &V,
+    ) -> Result<(), DbError> {
+        self.write_value_cf(ColumnFamily::of_key(key.as_ref()), key, value)
+    }
+
+    pub fn write_value_cf<K: AsRef<[u8]>, V: Serialize>(
+        &self,
+        cf: ColumnFamily,
+        key: K,
+        value: &V,
     ) -> Result<(), DbError> {
         let serialized =
             serde_json::to_string(value).map_err(|e| DbError::Serialization(e.to_string()))?;
 
         trace!("Value to write {}", serialized);
 
-        self.db
-            .put(key, serialized)
-            .map_err(|e| DbError::WriteDb(e.to_string()))?;
-        Ok(())
+        self.check_writable()?;
+        self.measured(DbOperation::Put, Some(cf), |bytes| {
+            *bytes = key.as_ref().len() + serialized.len();
+            self.engine.put(cf, key.as_ref(), serialized.as_bytes())
+        })
+    }
+
+    pub fn delete<K: AsRef<[u8]>>(&self, key: K) -> Result<(), DbError> {
+        self.delete_cf(ColumnFamily::of_key(key.as_ref()), key)
+    }
+
+    pub fn delete_cf<K: AsRef<[u8]>>(&self, cf: ColumnFamily, key: K) -> Result<(), DbError> {
+        self.check_writable()?;
+        self.measured(DbOperation::Delete, Some(cf), |bytes| {
+            *bytes = key.as_ref().len();
+            self.engine.delete(cf, key.as_ref())
+        })
+    }
+
+    /// Applies all the writes of the batch atomically
+    pub fn write_batch(&self, batch: Batch) -> Result<(), DbError> {
+        #[cfg(any(test, feature = "testing"))]
+        if self.fail_batches.load(Ordering::SeqCst) {
+            return Err(DbError::Batch("injected failure".to_string()));
+        }
+        self.check_writable()?;
+        let cf = batch.column_family();
+        self.measured(DbOperation::Batch, cf, |bytes| {
+            *bytes = batch.ops.iter().map(BatchOp::size).sum();
+            self.engine.write_batch(batch.ops)
+        })
+    }
+
+    /// Writes `batch` only when `condition` holds for the value stored under `key`, a
+    /// compare and set: no other conditional write runs between the check and the write.
+    /// Returns whether the batch was written
+    pub fn write_batch_if<K: AsRef<[u8]>, V: for<'a> Deserialize<'a>>(
+        &self,
+        key: K,
+        condition: impl FnOnce(Option<V>) -> bool,
+        batch: Batch,
+    ) -> Result<bool, DbError> {
+        let _guard = self.conditional_writes.lock().unwrap();
+        if !condition(self.read(key)?) {
+            return Ok(false);
+        }
+        self.write_batch(batch)?;
+        Ok(true)
+    }
+
+    /// Allocates the next value of the sequence `name`, never the same twice to the clones
+    /// of this database even when their writes land out of order. The first allocation
+    /// starts at `seed`, read from the stored data, the caller persists what it needs to
+    /// seed it again after a restart
+    pub fn next_sequence(
+        &self,
+        name: &str,
+        seed: impl FnOnce(&Database) -> Result<u64, DbError>,
+    ) -> Result<u64, DbError> {
+        let mut sequences = self.sequences.lock().unwrap();
+        let next = match sequences.get(name) {
+            Some(next) => *next,
+            None => seed(self)?,
+        };
+        sequences.insert(name.to_string(), next + 1);
+        Ok(next)
+    }
+
+    /// Makes the following batches fail without writing anything until cleared
+    #[cfg(any(test, feature = "testing"))]
+    pub fn set_failing_batches(&self, fail: bool) {
+        self.fail_batches.store(fail, Ordering::SeqCst);
+    }
+
+    /// Returns the keys starting with `prefix` and their values in key order
+    pub fn scan_prefix<V: for<'a> Deserialize<'a>>(
+        &self,
+        prefix: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<(String, V)>, DbError> {
+        self.scan_prefix_after(prefix, None, limit)
+    }
+
+    /// Same as `scan_prefix` starting after the `after` key, used to paginate scans
+    pub fn scan_prefix_after<V: for<'a> Deserialize<'a>>(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<(String, V)>, DbError> {
+        self.scan_prefix_cf(
+            ColumnFamily::of_key(prefix.as_bytes()),
+            prefix,
+            after,
+            limit,
+        )
+    }
+
+    /// Same as `scan_prefix_after` in the `cf` column family, keys of the other column
+    /// families are never visited
+    pub fn scan_prefix_cf<V: for<'a> Deserialize<'a>>(
+        &self,
+        cf: ColumnFamily,
+        prefix: &str,
+        after: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<(String, V)>, DbError> {
+        let mut values = vec![];
+        let start = after.unwrap_or(prefix);
+
+        self.measured(DbOperation::Iterate, Some(cf), |read| {
+            self.engine.iterate_prefix(
+                cf,
+                prefix.as_bytes(),
+                start.as_bytes(),
+                &mut |key, bytes| {
+                    if limit.is_some_and(|limit| values.len() >= limit) {
+                        return Ok(false);
+                    }
+                    if after.is_some_and(|after| key == after.as_bytes()) {
+                        return Ok(true);
+                    }
+                    *read += key.len() + bytes.len();
+                    let key = String::from_utf8_lossy(key).to_string();
+                    let value: V = serde_json::from_slice(bytes)
+                        .map_err(|e| DbError::ReadDb(e.to_string()))?;
+                    values.push((key, value));
+                    Ok(true)
+                },
+            )
+        })?;
+        Ok(values)
     }
 
     pub fn read<K: AsRef<[u8]>, V: for<'a> Deserialize<'a>>(
         &self,
         key: K,
     ) -> Result<Option<V>, DbError> {
-        if let Some(bytes) = self
-            .db
-            .get(key)
-            .map_err(|e| DbError::WriteDb(e.to_string()))?
-        {
+        self.read_cf(ColumnFamily::of_key(key.as_ref()), key)
+    }
+
+    pub fn read_cf<K: AsRef<[u8]>, V: for<'a> Deserialize<'a>>(
+        &self,
+        cf: ColumnFamily,
+        key: K,
+    ) -> Result<Option<V>, DbError> {
+        let stored = self.measured(DbOperation::Get, Some(cf), |bytes| {
+            let stored = self.engine.get(cf, key.as_ref())?;
+            *bytes = key.as_ref().len() + stored.as_ref().map_or(0, Vec::len);
+            Ok(stored)
+        })?;
+        if let Some(bytes) = stored {
             let value: V =
                 serde_json::from_slice(&bytes).map_err(|e| DbError::ReadDb(e.to_string()))?;
             Ok(Some(value))
@@ -56,13 +424,121 @@ impl Database {
             Ok(None)
         }
     }
+
+    /// Values of `keys` of the column family `cf` in their order, read in one engine call
+    pub fn read_many_cf<K: AsRef<[u8]>, V: for<'a> Deserialize<'a>>(
+        &self,
+        cf: ColumnFamily,
+        keys: &[K],
+    ) -> Result<Vec<Option<V>>, DbError> {
+        let keys: Vec<&[u8]> = keys.iter().map(AsRef::as_ref).collect();
+        let stored = self.measured(DbOperation::MultiGet, Some(cf), |bytes| {
+            let stored = self.engine.multi_get(cf, &keys)?;
+            *bytes = keys.iter().map(|key| key.len()).sum::<usize>()
+                + stored.iter().flatten().map(Vec::len).sum::<usize>();
+            Ok(stored)
+        })?;
+        stored
+            .into_iter()
+            .map(|value| {
+                value
+                    .map(|bytes| {
+                        serde_json::from_slice(&bytes).map_err(|e| DbError::ReadDb(e.to_string()))
+                    })
+                    .transpose()
+            })
+            .collect()
+    }
+
+    /// Makes `alias` resolve to the `canonical` key
+    pub fn write_alias(&self, alias: &str, canonical: &str) -> Result<(), DbError> {
+        self.write_value(alias_key(alias), &canonical)
+    }
+
+    /// Canonical key `alias` resolves to, None when it is not an alias
+    pub fn resolve_alias(&self, alias: &str) -> Result<Option<String>, DbError> {
+        self.read(alias_key(alias))
+    }
+
+    /// Reads `key`, or the canonical key it is an alias of when nothing is stored under
+    /// it. A single alias is followed, aliases of aliases are not resolved
+    pub fn read_following_alias<V: for<'a> Deserialize<'a>>(
+        &self,
+        key: &str,
+    ) -> Result<Option<V>, DbError> {
+        if let Some(value) = self.read(key)? {
+            return Ok(Some(value));
+        }
+        match self.resolve_alias(key)? {
+            Some(canonical) => self.read(canonical),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Group of writes applied atomically by `Database::write_batch`, across column families
+#[derive(Default)]
+pub struct Batch {
+    ops: Vec<BatchOp>,
+}
+
+impl Batch {
+    /// Column family of every write of the batch, None when they span several
+    fn column_family(&self) -> Option<ColumnFamily> {
+        let mut cfs = self.ops.iter().map(BatchOp::cf);
+        let first = cfs.next()?;
+        cfs.all(|cf| cf == first).then_some(first)
+    }
+
+    /// Puts `value` under `key` in the column family of its class
+    pub fn put<K: AsRef<[u8]>, V: Serialize>(&mut self, key: K, value: &V) -> Result<(), DbError> {
+        self.put_cf(ColumnFamily::of_key(key.as_ref()), key, value)
+    }
+
+    pub fn put_cf<K: AsRef<[u8]>, V: Serialize>(
+        &mut self,
+        cf: ColumnFamily,
+        key: K,
+        value: &V,
+    ) -> Result<(), DbError> {
+        let serialized =
+            serde_json::to_string(value).map_err(|e| DbError::Serialization(e.to_string()))?;
+        self.ops.push(BatchOp::Put {
+            cf,
+            key: key.as_ref().to_vec(),
+            value: serialized.into_bytes(),
+        });
+        Ok(())
+    }
+
+    pub fn delete<K: AsRef<[u8]>>(&mut self, key: K) {
+        self.delete_cf(ColumnFamily::of_key(key.as_ref()), key)
+    }
+
+    pub fn delete_cf<K: AsRef<[u8]>>(&mut self, cf: ColumnFamily, key: K) {
+        self.ops.push(BatchOp::Delete {
+            cf,
+            key: key.as_ref().to_vec(),
+        });
+    }
+
+    /// Same as `Database::write_alias` in the batch
+    pub fn put_alias(&mut self, alias: &str, canonical: &str) -> Result<(), DbError> {
+        self.put(alias_key(alias), &canonical)
+    }
 }
 
 #[cfg(test)]
 mod db_tests {
-    use crate::{db::Database, errors::DbError};
+    use crate::{
+        db::{Batch, Database, MIGRATION_BATCH_SIZE, SCHEMA_VERSION},
+        engine::{MemoryEngine, StorageEngine},
+        errors::DbError,
+        keys::{ColumnFamily, SCHEMA_VERSION_KEY},
+        testing::each_engine,
+    };
     use serde::{Deserialize, Serialize};
-    use tempfile::tempdir;
+    use std::sync::Arc;
 
     #[derive(Debug, Serialize, Deserialize, PartialEq)]
     struct TestStruct {
@@ -71,40 +547,66 @@ mod db_tests {
     }
 
     #[test]
+    #[cfg(feature = "rocksdb")]
     fn test_database_open() {
-        let temp_dir = tempdir().unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
         let db = Database::open(temp_dir.path());
         assert!(db.is_ok());
     }
 
     #[test]
     fn test_write_and_read_value() {
-        let temp_dir = tempdir().unwrap();
-        let db = Database::open(temp_dir.path()).unwrap();
-
-        let test_data = TestStruct {
-            field1: "test".to_string(),
-            field2: 42,
-        };
+        for db in each_engine() {
+            let test_data = TestStruct {
+                field1: "test".to_string(),
+                field2: 42,
+            };
 
-        // Write value
-        db.write_value(b"test_key", &test_data).unwrap();
+            // Write value
+            db.write_value(b"test_key", &test_data).unwrap();
 
-        // Read value
-        let read_data: TestStruct = db.read(b"test_key").unwrap().unwrap();
-        assert_eq!(read_data, test_data);
+            // Read value
+            let read_data: TestStruct = db.read(b"test_key").unwrap().unwrap();
+            assert_eq!(read_data, test_data, "{:?}", db.engine);
+        }
     }
 
     #[test]
     fn test_read_nonexistent_key() {
-        let temp_dir = tempdir().unwrap();
-        let db = Database::open(temp_dir.path()).unwrap();
+        for db in each_engine() {
+            let result: Option<TestStruct> = db.read(b"nonexistent_key").unwrap();
+            assert!(result.is_none());
+        }
+    }
 
-        let result: Option<TestStruct> = db.read(b"nonexistent_key").unwrap();
-        assert!(result.is_none());
+    #[test]
+    fn test_read_many_keeps_key_order() {
+        for db in each_engine() {
+            for (key, field2) in [("0x01", 1), ("0x03", 3)] {
+                let value = TestStruct {
+                    field1: key.to_string(),
+                    field2,
+                };
+                db.write_value(key, &value).unwrap();
+            }
+
+            let read: Vec<Option<TestStruct>> = db
+                .read_many_cf(ColumnFamily::Requests, &["0x03", "0x02", "0x01"])
+                .unwrap();
+            let fields: Vec<Option<i32>> = read
+                .iter()
+                .map(|value| value.as_ref().map(|value| value.field2))
+                .collect();
+            assert_eq!(fields, vec![Some(3), None, Some(1)], "{:?}", db.engine);
+            assert!(db
+                .read_many_cf::<&str, TestStruct>(ColumnFamily::Requests, &[])
+                .unwrap()
+                .is_empty());
+        }
     }
 
     #[test]
+    #[cfg(feature = "rocksdb")]
     fn test_invalid_path() {
         let result = Database::open("/nonexistent/path/that/should/fail");
         assert!(result.is_err());
@@ -113,66 +615,469 @@ mod db_tests {
 
     #[test]
     fn test_write_multiple_values() {
-        let temp_dir = tempdir().unwrap();
-        let db = Database::open(temp_dir.path()).unwrap();
-
-        let test_data1 = TestStruct {
-            field1: "test1".to_string(),
-            field2: 42,
-        };
-        let test_data2 = TestStruct {
-            field1: "test2".to_string(),
-            field2: 84,
-        };
+        for db in each_engine() {
+            let test_data1 = TestStruct {
+                field1: "test1".to_string(),
+                field2: 42,
+            };
+            let test_data2 = TestStruct {
+                field1: "test2".to_string(),
+                field2: 84,
+            };
 
-        // Write values
-        db.write_value(b"test_key1", &test_data1).unwrap();
-        db.write_value(b"test_key2", &test_data2).unwrap();
+            // Write values
+            db.write_value(b"test_key1", &test_data1).unwrap();
+            db.write_value(b"test_key2", &test_data2).unwrap();
 
-        // Read values
-        let read_data1: TestStruct = db.read(b"test_key1").unwrap().unwrap();
-        let read_data2: TestStruct = db.read(b"test_key2").unwrap().unwrap();
+            // Read values
+            let read_data1: TestStruct = db.read(b"test_key1").unwrap().unwrap();
+            let read_data2: TestStruct = db.read(b"test_key2").unwrap().unwrap();
 
-        assert_eq!(read_data1, test_data1);
-        assert_eq!(read_data2, test_data2);
+            assert_eq!(read_data1, test_data1);
+            assert_eq!(read_data2, test_data2);
+        }
     }
 
     #[test]
     fn test_overwrite_value() {
-        let temp_dir = tempdir().unwrap();
-        let db = Database::open(temp_dir.path()).unwrap();
+        for db in each_engine() {
+            let test_data1 = TestStruct {
+                field1: "test1".to_string(),
+                field2: 42,
+            };
+            let test_data2 = TestStruct {
+                field1: "test2".to_string(),
+                field2: 84,
+            };
 
-        let test_data1 = TestStruct {
-            field1: "test1".to_string(),
-            field2: 42,
-        };
-        let test_data2 = TestStruct {
-            field1: "test2".to_string(),
-            field2: 84,
-        };
+            // Write initial value
+            db.write_value(b"test_key", &test_data1).unwrap();
 
-        // Write initial value
-        db.write_value(b"test_key", &test_data1).unwrap();
-        
-        // Overwrite with new value
-        db.write_value(b"test_key", &test_data2).unwrap();
+            // Overwrite with new value
+            db.write_value(b"test_key", &test_data2).unwrap();
 
-        // Read value
-        let read_data: TestStruct = db.read(b"test_key").unwrap().unwrap();
-        assert_eq!(read_data, test_data2);
+            // Read value
+            let read_data: TestStruct = db.read(b"test_key").unwrap().unwrap();
+            assert_eq!(read_data, test_data2);
+        }
     }
 
     #[test]
     fn test_invalid_deserialization() {
-        let temp_dir = tempdir().unwrap();
+        for db in each_engine() {
+            // Write a string value
+            db.write_value(b"test_key", &"invalid_data").unwrap();
+
+            // Try to read it as TestStruct
+            let result: Result<Option<TestStruct>, _> = db.read(b"test_key");
+            assert!(result.is_err());
+            assert!(matches!(result.unwrap_err(), DbError::ReadDb(_)));
+        }
+    }
+
+    #[test]
+    fn test_schema_version_stamped() {
+        for db in each_engine() {
+            assert_eq!(db.schema_version().unwrap(), Some(SCHEMA_VERSION));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rocksdb")]
+    fn test_newer_schema_refused() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        {
+            let db = Database::open(temp_dir.path()).unwrap();
+            // Schema record written by a future relayer
+            db.write_value(SCHEMA_VERSION_KEY, &(SCHEMA_VERSION + 1))
+                .unwrap();
+        }
+
+        let result = Database::open(temp_dir.path());
+        assert!(matches!(
+            result.unwrap_err(),
+            DbError::NewerSchema { found, supported } if found == SCHEMA_VERSION + 1 && supported == SCHEMA_VERSION
+        ));
+    }
+
+    #[test]
+    fn test_newer_schema_refused_by_engine() {
+        // Engine already holding data, as a reopened directory
+        let engine = Arc::new(MemoryEngine::default());
+        Database::with_engine(engine.clone())
+            .unwrap()
+            .write_value(SCHEMA_VERSION_KEY, &(SCHEMA_VERSION + 1))
+            .unwrap();
+        assert!(matches!(
+            Database::with_engine(engine),
+            Err(DbError::NewerSchema { .. })
+        ));
+    }
+
+    #[test]
+    fn test_write_batch_and_scan_prefix() {
+        for db in each_engine() {
+            db.write_value(b"idx:b", &2).unwrap();
+            let mut batch = Batch::default();
+            batch.put(b"idx:a", &1).unwrap();
+            batch.put(b"idx:c", &3).unwrap();
+            batch.put(b"other", &4).unwrap();
+            batch.delete(b"idx:b");
+            db.write_batch(batch).unwrap();
+
+            let values: Vec<(String, i32)> = db.scan_prefix("idx:", None).unwrap();
+            assert_eq!(
+                values,
+                vec![("idx:a".to_string(), 1), ("idx:c".to_string(), 3)],
+                "{:?}",
+                db.engine
+            );
+
+            let limited: Vec<(String, i32)> = db.scan_prefix("idx:", Some(1)).unwrap();
+            assert_eq!(limited.len(), 1);
+
+            let after: Vec<(String, i32)> =
+                db.scan_prefix_after("idx:", Some("idx:a"), None).unwrap();
+            assert_eq!(after, vec![("idx:c".to_string(), 3)]);
+
+            db.delete(b"idx:a").unwrap();
+            let values: Vec<(String, i32)> = db.scan_prefix("idx:", None).unwrap();
+            assert_eq!(values, vec![("idx:c".to_string(), 3)]);
+        }
+    }
+
+    #[test]
+    fn test_scan_prefix_stops_at_prefix_end() {
+        for db in each_engine() {
+            db.write_value(b"ida", &0).unwrap();
+            db.write_value(b"idx:a", &1).unwrap();
+            db.write_value(b"idy:a", &2).unwrap();
+
+            let values: Vec<(String, i32)> = db.scan_prefix("idx:", None).unwrap();
+            assert_eq!(values, vec![("idx:a".to_string(), 1)], "{:?}", db.engine);
+            let values: Vec<(String, i32)> = db.scan_prefix("idz:", None).unwrap();
+            assert!(values.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_failing_batch_writes_nothing() {
+        for db in each_engine() {
+            let mut batch = Batch::default();
+            batch.put(b"first", &1).unwrap();
+            batch.put(b"second", &2).unwrap();
+            db.set_failing_batches(true);
+            assert!(matches!(db.write_batch(batch), Err(DbError::Batch(_))));
+            assert_eq!(db.read::<_, i32>(b"first").unwrap(), None);
+            assert_eq!(db.read::<_, i32>(b"second").unwrap(), None);
+
+            db.set_failing_batches(false);
+            let mut batch = Batch::default();
+            batch.put(b"first", &1).unwrap();
+            db.write_batch(batch).unwrap();
+            assert_eq!(db.read::<_, i32>(b"first").unwrap(), Some(1));
+        }
+    }
+
+    /// Flat keyspace of schema 2, everything in the default column family
+    fn legacy_entries() -> Vec<(String, String)> {
+        let mut entries = vec![
+            (SCHEMA_VERSION_KEY.to_string(), "2".to_string()),
+            ("PauseState".to_string(), "{}".to_string()),
+            ("0xabc".to_string(), "\"request\"".to_string()),
+            ("status:Completed:0xabc".to_string(), "1".to_string()),
+            ("pending_queue:7".to_string(), "\"0xabc\"".to_string()),
+            ("Completed".to_string(), "[\"0xabc\"]".to_string()),
+            ("idempotency:key".to_string(), "{}".to_string()),
+        ];
+        // More than a migration batch
+        for i in 0..MIGRATION_BATCH_SIZE + 10 {
+            entries.push((format!("tx:{i:05}:0xabc"), "\"0xabc\"".to_string()));
+        }
+        entries
+    }
+
+    fn assert_migrated(db: &Database) {
+        assert_eq!(db.schema_version().unwrap(), Some(SCHEMA_VERSION));
+        for (key, cf) in [
+            ("PauseState", ColumnFamily::Default),
+            ("0xabc", ColumnFamily::Requests),
+            ("status:Completed:0xabc", ColumnFamily::Indexes),
+            ("pending_queue:7", ColumnFamily::Outbox),
+            ("Completed", ColumnFamily::Activity),
+            ("idempotency:key", ColumnFamily::Cache),
+        ] {
+            assert_eq!(ColumnFamily::of_key(key.as_bytes()), cf);
+            assert!(
+                db.engine.get(cf, key.as_bytes()).unwrap().is_some(),
+                "{key} not in {cf:?}"
+            );
+            if cf != ColumnFamily::Default {
+                assert_eq!(
+                    db.engine.get(ColumnFamily::Default, key.as_bytes()),
+                    Ok(None)
+                );
+            }
+        }
+        assert_eq!(
+            db.read::<_, String>("0xabc").unwrap(),
+            Some("request".to_string())
+        );
+        let indexed: Vec<(String, String)> = db.scan_prefix("tx:", None).unwrap();
+        assert_eq!(indexed.len(), MIGRATION_BATCH_SIZE + 10);
+        // Only the keys of no class are left in the default column family
+        let left: Vec<(String, serde_json::Value)> = db
+            .scan_prefix_cf(ColumnFamily::Default, "", None, None)
+            .unwrap();
+        let left: Vec<&str> = left.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(left, vec!["PauseState", SCHEMA_VERSION_KEY]);
+    }
+
+    #[test]
+    fn test_flat_keyspace_migrated_to_column_families() {
+        let engine = Arc::new(MemoryEngine::default());
+        for (key, value) in legacy_entries() {
+            engine
+                .put(ColumnFamily::Default, key.as_bytes(), value.as_bytes())
+                .unwrap();
+        }
+        let db = Database::with_engine(engine.clone()).unwrap();
+        assert_migrated(&db);
+
+        // Reopening finds the current schema and moves nothing
+        let db = Database::with_engine(engine).unwrap();
+        assert_migrated(&db);
+    }
+
+    #[test]
+    #[cfg(feature = "rocksdb")]
+    fn test_rocksdb_flat_keyspace_migrated() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        {
+            // Database of a previous relayer, without column families
+            let mut opts = rocksdb::Options::default();
+            opts.create_if_missing(true);
+            let legacy = rocksdb::DB::open(&opts, temp_dir.path()).unwrap();
+            for (key, value) in legacy_entries() {
+                legacy.put(key, value).unwrap();
+            }
+        }
+
         let db = Database::open(temp_dir.path()).unwrap();
+        assert_migrated(&db);
+        drop(db);
+        assert_migrated(&Database::open(temp_dir.path()).unwrap());
+    }
 
-        // Write a string value
-        db.write_value(b"test_key", &"invalid_data").unwrap();
+    #[test]
+    fn test_batch_atomic_across_column_families() {
+        for db in each_engine() {
+            let mut batch = Batch::default();
+            batch.put("0xabc", &1).unwrap();
+            batch.put("status:Completed:0xabc", &2).unwrap();
+            batch.put_cf(ColumnFamily::Cache, "0xabc", &3).unwrap();
+            db.set_failing_batches(true);
+            assert!(matches!(db.write_batch(batch), Err(DbError::Batch(_))));
+            db.set_failing_batches(false);
+            assert_eq!(db.read::<_, i32>("0xabc").unwrap(), None);
+            assert_eq!(db.read::<_, i32>("status:Completed:0xabc").unwrap(), None);
+            assert_eq!(
+                db.read_cf::<_, i32>(ColumnFamily::Cache, "0xabc").unwrap(),
+                None
+            );
 
-        // Try to read it as TestStruct
-        let result: Result<Option<TestStruct>, _> = db.read(b"test_key");
-        assert!(result.is_err());
-        assert!(matches!(result.unwrap_err(), DbError::ReadDb(_)));
+            let mut batch = Batch::default();
+            batch.put("0xabc", &1).unwrap();
+            batch.put("status:Completed:0xabc", &2).unwrap();
+            batch.put_cf(ColumnFamily::Cache, "0xabc", &3).unwrap();
+            db.write_batch(batch).unwrap();
+            assert_eq!(
+                db.read::<_, i32>("0xabc").unwrap(),
+                Some(1),
+                "{:?}",
+                db.engine
+            );
+            assert_eq!(
+                db.read::<_, i32>("status:Completed:0xabc").unwrap(),
+                Some(2)
+            );
+            assert_eq!(
+                db.read_cf::<_, i32>(ColumnFamily::Cache, "0xabc").unwrap(),
+                Some(3)
+            );
+
+            let mut batch = Batch::default();
+            batch.delete("0xabc");
+            batch.delete_cf(ColumnFamily::Cache, "0xabc");
+            db.write_batch(batch).unwrap();
+            assert_eq!(db.read::<_, i32>("0xabc").unwrap(), None);
+            assert_eq!(
+                db.read_cf::<_, i32>(ColumnFamily::Cache, "0xabc").unwrap(),
+                None
+            );
+            assert_eq!(
+                db.read::<_, i32>("status:Completed:0xabc").unwrap(),
+                Some(2)
+            );
+        }
+    }
+
+    #[test]
+    fn test_scan_stays_in_column_family() {
+        for db in each_engine() {
+            db.write_value("0xaa", &1).unwrap();
+            db.write_value("tx:0x01:0xaa", &2).unwrap();
+            db.write_value("quota:0xaa", &3).unwrap();
+            // Same key in another column family
+            db.write_value_cf(ColumnFamily::Indexes, "0xbb", &4)
+                .unwrap();
+
+            let requests: Vec<(String, i32)> = db
+                .scan_prefix_cf(ColumnFamily::Requests, "", None, None)
+                .unwrap();
+            assert_eq!(requests, vec![("0xaa".to_string(), 1)], "{:?}", db.engine);
+            let by_prefix: Vec<(String, i32)> = db.scan_prefix("0x", None).unwrap();
+            assert_eq!(by_prefix, vec![("0xaa".to_string(), 1)]);
+            let indexes: Vec<(String, i32)> = db
+                .scan_prefix_cf(ColumnFamily::Indexes, "", None, None)
+                .unwrap();
+            assert_eq!(
+                indexes,
+                vec![("0xbb".to_string(), 4), ("tx:0x01:0xaa".to_string(), 2)]
+            );
+            assert_eq!(db.read::<_, i32>("0xbb").unwrap(), None);
+            db.delete_cf(ColumnFamily::Indexes, "0xbb").unwrap();
+            assert_eq!(
+                db.read_cf::<_, i32>(ColumnFamily::Indexes, "0xbb").unwrap(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_conditional_batch() {
+        for db in each_engine() {
+            db.write_value("0xabc", &1).unwrap();
+            let mut batch = Batch::default();
+            batch.put("0xabc", &2).unwrap();
+            batch.put("status:Completed:0xabc", &2).unwrap();
+            assert!(!db
+                .write_batch_if("0xabc", |stored: Option<i32>| stored == Some(0), batch)
+                .unwrap());
+            assert_eq!(db.read::<_, i32>("0xabc").unwrap(), Some(1));
+            assert_eq!(db.read::<_, i32>("status:Completed:0xabc").unwrap(), None);
+
+            let mut batch = Batch::default();
+            batch.put("0xabc", &2).unwrap();
+            assert!(db
+                .write_batch_if("0xabc", |stored: Option<i32>| stored == Some(1), batch)
+                .unwrap());
+            assert_eq!(
+                db.read::<_, i32>("0xabc").unwrap(),
+                Some(2),
+                "{:?}",
+                db.engine
+            );
+        }
+    }
+
+    #[test]
+    fn test_alias_resolves_to_canonical_key() {
+        for db in each_engine() {
+            let test_data = TestStruct {
+                field1: "canonical".to_string(),
+                field2: 7,
+            };
+            let mut batch = Batch::default();
+            batch.put("0xnew", &test_data).unwrap();
+            batch.put_alias("0xold", "0xnew").unwrap();
+            db.write_batch(batch).unwrap();
+
+            assert_eq!(
+                db.resolve_alias("0xold").unwrap(),
+                Some("0xnew".to_string())
+            );
+            assert_eq!(db.resolve_alias("0xnew").unwrap(), None);
+            let by_alias: TestStruct = db.read_following_alias("0xold").unwrap().unwrap();
+            assert_eq!(by_alias, test_data, "{:?}", db.engine);
+            let by_key: TestStruct = db.read_following_alias("0xnew").unwrap().unwrap();
+            assert_eq!(by_key, test_data);
+            // A plain read doesn't follow the alias
+            assert_eq!(db.read::<_, TestStruct>("0xold").unwrap(), None);
+            assert_eq!(
+                db.read_following_alias::<TestStruct>("0xunknown").unwrap(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_alias_follows_a_single_hop() {
+        for db in each_engine() {
+            db.write_value("target", &1).unwrap();
+            db.write_alias("middle", "target").unwrap();
+            db.write_alias("first", "middle").unwrap();
+            // Dangling alias
+            db.write_alias("dangling", "missing").unwrap();
+
+            assert_eq!(db.read_following_alias::<i32>("middle").unwrap(), Some(1));
+            assert_eq!(db.read_following_alias::<i32>("first").unwrap(), None);
+            assert_eq!(db.read_following_alias::<i32>("dangling").unwrap(), None);
+            // Data stored under the key wins over an alias of the same key
+            db.write_value("middle", &2).unwrap();
+            assert_eq!(db.read_following_alias::<i32>("middle").unwrap(), Some(2));
+        }
+    }
+
+    #[test]
+    fn test_read_only_database_refuses_writes() {
+        for db in each_engine() {
+            db.write_value("request1", &1u32).unwrap();
+            let read_only = db.as_read_only();
+            assert!(read_only.is_read_only());
+            assert_eq!(read_only.read::<_, u32>("request1").unwrap(), Some(1));
+            assert_eq!(
+                read_only.write_value("request1", &2u32),
+                Err(DbError::ReadOnly)
+            );
+            assert_eq!(read_only.delete("request1"), Err(DbError::ReadOnly));
+            let mut batch = Batch::default();
+            batch.put("request2", &2u32).unwrap();
+            assert_eq!(read_only.write_batch(batch), Err(DbError::ReadOnly));
+
+            // Writes of the primary are seen
+            db.write_value("request1", &3u32).unwrap();
+            assert_eq!(read_only.read::<_, u32>("request1").unwrap(), Some(3));
+        }
+
+        // Databases not migrated are refused
+        let engine = Arc::new(MemoryEngine::default());
+        assert_eq!(
+            Database::read_only_with_engine(engine).unwrap_err(),
+            DbError::OlderSchema {
+                found: 0,
+                supported: SCHEMA_VERSION
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "rocksdb")]
+    fn test_secondary_catches_up_with_primary() {
+        let primary_dir = tempfile::tempdir().unwrap();
+        let secondary_dir = tempfile::tempdir().unwrap();
+        let primary = Database::open(primary_dir.path()).unwrap();
+        primary.write_value("request1", &1u32).unwrap();
+
+        let secondary = Database::open_read_only(primary_dir.path(), secondary_dir.path()).unwrap();
+        assert_eq!(secondary.read::<_, u32>("request1").unwrap(), Some(1));
+        assert_eq!(
+            secondary.write_value("request1", &2u32),
+            Err(DbError::ReadOnly)
+        );
+
+        primary.write_value("request2", &2u32).unwrap();
+        secondary.catch_up().unwrap();
+        assert_eq!(secondary.read::<_, u32>("request2").unwrap(), Some(2));
     }
 }
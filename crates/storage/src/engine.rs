@@ -0,0 +1,163 @@
+use std::{collections::BTreeMap, fmt, sync::RwLock};
+
+use crate::{errors::DbError, keys::ColumnFamily};
+
+/// Write of a batch, applied by the engine with the others of the batch or not at all,
+/// whatever their column family
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchOp {
+    Put {
+        cf: ColumnFamily,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Delete {
+        cf: ColumnFamily,
+        key: Vec<u8>,
+    },
+}
+
+impl BatchOp {
+    pub fn cf(&self) -> ColumnFamily {
+        match self {
+            BatchOp::Put { cf, .. } | BatchOp::Delete { cf, .. } => *cf,
+        }
+    }
+
+    /// Bytes of the key and value written
+    pub fn size(&self) -> usize {
+        match self {
+            BatchOp::Put { key, value, .. } => key.len() + value.len(),
+            BatchOp::Delete { key, .. } => key.len(),
+        }
+    }
+}
+
+/// Ordered key value store under `Database`, values are the serialized JSON bytes. Each
+/// column family is a keyspace of its own
+pub trait StorageEngine: Send + Sync + fmt::Debug {
+    fn get(&self, cf: ColumnFamily, key: &[u8]) -> Result<Option<Vec<u8>>, DbError>;
+
+    /// Values of `keys` in their order, read in one call by the engines that can
+    fn multi_get(&self, cf: ColumnFamily, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>, DbError> {
+        keys.iter().map(|key| self.get(cf, key)).collect()
+    }
+
+    fn put(&self, cf: ColumnFamily, key: &[u8], value: &[u8]) -> Result<(), DbError>;
+
+    fn delete(&self, cf: ColumnFamily, key: &[u8]) -> Result<(), DbError>;
+
+    /// Visits the entries of `cf` starting with `prefix` in key order from the `from` key,
+    /// until `visit` returns false
+    fn iterate_prefix(
+        &self,
+        cf: ColumnFamily,
+        prefix: &[u8],
+        from: &[u8],
+        visit: &mut dyn FnMut(&[u8], &[u8]) -> Result<bool, DbError>,
+    ) -> Result<(), DbError>;
+
+    /// Applies all the operations or none
+    fn write_batch(&self, ops: Vec<BatchOp>) -> Result<(), DbError>;
+
+    /// Number of keys of `cf`, an estimate for the engines that can't count them cheaply
+    fn approximate_count(&self, cf: ColumnFamily) -> Result<u64, DbError>;
+
+    /// Bytes taken on disk, None for the engines without files
+    fn disk_size(&self) -> Result<Option<u64>, DbError> {
+        Ok(None)
+    }
+
+    /// Reads the writes of the primary made since the last call, for the engines
+    /// following the database of another process
+    fn catch_up(&self) -> Result<(), DbError> {
+        Ok(())
+    }
+}
+
+/// Engine keeping the entries in memory, for the tests and embedded uses without
+/// persistence. Nothing is kept once the last `Database` clone is dropped
+#[derive(Debug, Default)]
+pub struct MemoryEngine {
+    entries: RwLock<BTreeMap<ColumnFamily, BTreeMap<Vec<u8>, Vec<u8>>>>,
+}
+
+impl StorageEngine for MemoryEngine {
+    fn get(&self, cf: ColumnFamily, key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        Ok(self
+            .entries
+            .read()
+            .unwrap()
+            .get(&cf)
+            .and_then(|entries| entries.get(key).cloned()))
+    }
+
+    /// A single read lock, the values are read as of one moment
+    fn multi_get(&self, cf: ColumnFamily, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>, DbError> {
+        let entries = self.entries.read().unwrap();
+        let entries = entries.get(&cf);
+        Ok(keys
+            .iter()
+            .map(|key| entries.and_then(|entries| entries.get(*key).cloned()))
+            .collect())
+    }
+
+    fn put(&self, cf: ColumnFamily, key: &[u8], value: &[u8]) -> Result<(), DbError> {
+        self.entries
+            .write()
+            .unwrap()
+            .entry(cf)
+            .or_default()
+            .insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, cf: ColumnFamily, key: &[u8]) -> Result<(), DbError> {
+        if let Some(entries) = self.entries.write().unwrap().get_mut(&cf) {
+            entries.remove(key);
+        }
+        Ok(())
+    }
+
+    fn iterate_prefix(
+        &self,
+        cf: ColumnFamily,
+        prefix: &[u8],
+        from: &[u8],
+        visit: &mut dyn FnMut(&[u8], &[u8]) -> Result<bool, DbError>,
+    ) -> Result<(), DbError> {
+        let entries = self.entries.read().unwrap();
+        let Some(entries) = entries.get(&cf) else {
+            return Ok(());
+        };
+        for (key, value) in entries.range(from.to_vec()..) {
+            if !key.starts_with(prefix) || !visit(key, value)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_batch(&self, ops: Vec<BatchOp>) -> Result<(), DbError> {
+        // A single write lock, readers never see part of the batch
+        let mut entries = self.entries.write().unwrap();
+        for op in ops {
+            match op {
+                BatchOp::Put { cf, key, value } => {
+                    entries.entry(cf).or_default().insert(key, value);
+                }
+                BatchOp::Delete { cf, key } => {
+                    if let Some(entries) = entries.get_mut(&cf) {
+                        entries.remove(&key);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn approximate_count(&self, cf: ColumnFamily) -> Result<u64, DbError> {
+        let entries = self.entries.read().unwrap();
+        Ok(entries.get(&cf).map_or(0, |entries| entries.len() as u64))
+    }
+}
@@ -9,9 +9,21 @@ pub enum DbError {
     #[error("Error reading db: {0}")]
     ReadDb(String),
 
+    #[error("Error writting batch: {0}")]
+    Batch(String),
+
     #[error("Serialization error: {0}")]
     Serialization(String),
 
     #[error("Invalid path: {0}")]
     InvalidPath(String),
+
+    #[error("Database schema version {found} is newer than the supported version {supported}, upgrade the relayer")]
+    NewerSchema { found: u32, supported: u32 },
+
+    #[error("Database schema version {found} is older than the supported version {supported}, a primary relayer migrates it")]
+    OlderSchema { found: u32, supported: u32 },
+
+    #[error("Database is opened read-only")]
+    ReadOnly,
 }
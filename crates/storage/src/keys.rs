@@ -1,3 +1,159 @@
+/// Pending list and index of older versions, moved to the pending queue at startup
 pub const PENDING_REQUESTS: &str = "Pending";
 pub const PENDING_REQUESTS_INDEX: &str = "PendingIndex";
+pub const PENDING_QUEUE_PREFIX: &str = "pending_queue:";
+pub const PENDING_SEQUENCE_PREFIX: &str = "pending_seq:";
+pub const PENDING_NEXT_SEQUENCE: &str = "PendingNextSequence";
+/// Report of the last integrity check of the pending queue, run at startup
+pub const PENDING_REPAIR_REPORT: &str = "PendingRepairReport";
+/// Completed list of older versions, moved to the completed set at startup
 pub const COMPLETED_REQUESTS: &str = "Completed";
+/// Completed set, by request id, its value is the time the request was added
+pub const COMPLETED_PREFIX: &str = "completed:";
+pub const IN_FLIGHT_MINTS: &str = "InFlightMints";
+pub const SCHEMA_VERSION_KEY: &str = "SchemaVersion";
+pub const STATUS_INDEX_PREFIX: &str = "status:";
+/// Schema version the status indexes were last rebuilt under
+pub const STATUS_INDEX_SCHEMA: &str = "StatusIndexSchema";
+pub const REQUEST_KEY_PREFIX: &str = "0x";
+pub const PAUSE_STATE: &str = "PauseState";
+/// Bridge addresses of the new requests and the ones they replaced
+pub const BRIDGE_DEPLOYMENT: &str = "BridgeDeployment";
+pub const QUARANTINE_PREFIX: &str = "quarantine:";
+pub const CUSTODY_INDEX_PREFIX: &str = "custody:";
+/// Requests holding their origin token in custody, by origin chain and request id, its
+/// value is the last update of the request
+pub const CUSTODY_EXPOSURE_PREFIX: &str = "custody_exposure:";
+pub const PENDING_REFUNDS: &str = "PendingRefunds";
+pub const LOADTEST_PREFIX: &str = "loadtest:";
+pub const QUOTA_PREFIX: &str = "quota:";
+pub const COLLECTION_PREFIX: &str = "collection:";
+pub const CALLBACK_PREFIX: &str = "callback:";
+pub const PROCESSED_LOG_PREFIX: &str = "processed_log:";
+/// Alias keys, their value is the canonical key they resolve to
+pub const ALIAS_PREFIX: &str = "alias:";
+pub const IDEMPOTENCY_PREFIX: &str = "idempotency:";
+/// Wrapped asset registry, keyed by the minted token and by the origin token
+pub const WRAPPED_PREFIX: &str = "wrapped:";
+pub const WRAPPED_ORIGIN_PREFIX: &str = "wrapped_origin:";
+/// Errors of a request moved out of the ring kept on it, by request id and error number
+pub const ERROR_HISTORY_PREFIX: &str = "error_history:";
+/// Tx hash index, by tx hash and request id, its value is the request id
+pub const TX_INDEX_PREFIX: &str = "tx:";
+/// Replay bundles of the requests, inputs of their decisions captured under REPLAY_CAPTURE
+pub const REPLAY_PREFIX: &str = "replay:";
+/// Account index, by token owner or destination account and request id, its value is
+/// the request id
+pub const ACCOUNT_INDEX_PREFIX: &str = "account:";
+/// Receipts of the personal data purges, by salted hash of the purged account
+pub const PURGE_RECEIPT_PREFIX: &str = "purge:";
+/// Tx hashes found on their chain by the tx audit, by chain and hash, not read again
+pub const TX_VERIFIED_PREFIX: &str = "tx_verified:";
+/// Report of the last tx audit run
+pub const TX_AUDIT_REPORT: &str = "TxAuditReport";
+/// Requests claimed by a sweeper tick, by request id
+pub const WORK_CLAIM_PREFIX: &str = "claim:";
+/// Client reference index, by hex encoded reference and request id, its value is the
+/// request id
+pub const CLIENT_REFERENCE_PREFIX: &str = "client_ref:";
+/// Persisted statistics of the chain RPC endpoints, by time of the sample (millis)
+pub const RPC_HEALTH_PREFIX: &str = "rpc_health:";
+/// Transactions about to be sent or sent and not yet recorded on their request, by
+/// request id and operation
+pub const SEND_INTENT_PREFIX: &str = "send_intent:";
+
+/// Column family of a class of data. Prefix scans of a class don't iterate over the
+/// others and each class is compacted apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ColumnFamily {
+    /// Settings, schema version and keys of no class
+    Default,
+    /// Requests and the records kept per request
+    Requests,
+    /// Secondary indexes, aliases and registries
+    Indexes,
+    /// Work waiting to be done: pending queue, callbacks, refunds, in flight mints, sweeper
+    /// claims, send intents
+    Outbox,
+    /// Per account activity, load test runs, purge receipts and tx audits
+    Activity,
+    /// Dedupe entries with a limited life, compacted periodically
+    Cache,
+}
+
+/// Key prefixes of each class, the other keys of a class are listed in `CLASS_KEYS`
+const CLASS_PREFIXES: &[(&str, ColumnFamily)] = &[
+    (REQUEST_KEY_PREFIX, ColumnFamily::Requests),
+    (ERROR_HISTORY_PREFIX, ColumnFamily::Requests),
+    (REPLAY_PREFIX, ColumnFamily::Requests),
+    (STATUS_INDEX_PREFIX, ColumnFamily::Indexes),
+    (CUSTODY_INDEX_PREFIX, ColumnFamily::Indexes),
+    (CUSTODY_EXPOSURE_PREFIX, ColumnFamily::Indexes),
+    (TX_INDEX_PREFIX, ColumnFamily::Indexes),
+    (ACCOUNT_INDEX_PREFIX, ColumnFamily::Indexes),
+    (ALIAS_PREFIX, ColumnFamily::Indexes),
+    (WRAPPED_PREFIX, ColumnFamily::Indexes),
+    (WRAPPED_ORIGIN_PREFIX, ColumnFamily::Indexes),
+    (COLLECTION_PREFIX, ColumnFamily::Indexes),
+    (CLIENT_REFERENCE_PREFIX, ColumnFamily::Indexes),
+    (PENDING_QUEUE_PREFIX, ColumnFamily::Outbox),
+    (PENDING_SEQUENCE_PREFIX, ColumnFamily::Outbox),
+    (CALLBACK_PREFIX, ColumnFamily::Outbox),
+    (QUARANTINE_PREFIX, ColumnFamily::Outbox),
+    (WORK_CLAIM_PREFIX, ColumnFamily::Outbox),
+    (SEND_INTENT_PREFIX, ColumnFamily::Outbox),
+    (QUOTA_PREFIX, ColumnFamily::Activity),
+    (COMPLETED_PREFIX, ColumnFamily::Activity),
+    (LOADTEST_PREFIX, ColumnFamily::Activity),
+    (PURGE_RECEIPT_PREFIX, ColumnFamily::Activity),
+    (TX_VERIFIED_PREFIX, ColumnFamily::Activity),
+    (RPC_HEALTH_PREFIX, ColumnFamily::Activity),
+    (IDEMPOTENCY_PREFIX, ColumnFamily::Cache),
+    (PROCESSED_LOG_PREFIX, ColumnFamily::Cache),
+];
+
+const CLASS_KEYS: &[(&str, ColumnFamily)] = &[
+    (PENDING_NEXT_SEQUENCE, ColumnFamily::Outbox),
+    (PENDING_REPAIR_REPORT, ColumnFamily::Outbox),
+    (PENDING_REFUNDS, ColumnFamily::Outbox),
+    (IN_FLIGHT_MINTS, ColumnFamily::Outbox),
+    (STATUS_INDEX_SCHEMA, ColumnFamily::Indexes),
+    (COMPLETED_REQUESTS, ColumnFamily::Activity),
+    (TX_AUDIT_REPORT, ColumnFamily::Activity),
+];
+
+impl ColumnFamily {
+    pub const ALL: [ColumnFamily; 6] = [
+        ColumnFamily::Default,
+        ColumnFamily::Requests,
+        ColumnFamily::Indexes,
+        ColumnFamily::Outbox,
+        ColumnFamily::Activity,
+        ColumnFamily::Cache,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColumnFamily::Default => "default",
+            ColumnFamily::Requests => "requests",
+            ColumnFamily::Indexes => "indexes",
+            ColumnFamily::Outbox => "outbox",
+            ColumnFamily::Activity => "activity",
+            ColumnFamily::Cache => "cache",
+        }
+    }
+
+    /// Column family of a key or of a scan prefix from the key schema, keys of no class
+    /// stay in the default one
+    pub fn of_key(key: &[u8]) -> ColumnFamily {
+        CLASS_KEYS
+            .iter()
+            .find(|(class_key, _)| key == class_key.as_bytes())
+            .or_else(|| {
+                CLASS_PREFIXES
+                    .iter()
+                    .find(|(prefix, _)| key.starts_with(prefix.as_bytes()))
+            })
+            .map_or(ColumnFamily::Default, |(_, cf)| *cf)
+    }
+}
@@ -1,3 +1,9 @@
 pub mod db;
-mod errors;
-pub mod keys;
\ No newline at end of file
+pub mod engine;
+pub mod errors;
+pub mod keys;
+pub mod metrics;
+#[cfg(feature = "rocksdb")]
+pub mod rocks;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
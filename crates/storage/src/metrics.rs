@@ -0,0 +1,352 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::warn;
+
+use crate::keys::ColumnFamily;
+
+/// Upper bounds of the latency buckets, in seconds
+pub const LATENCY_BUCKETS: [f64; 10] = [
+    0.0001, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.1, 0.5, 1.0,
+];
+/// Upper bounds of the size buckets, in bytes
+pub const SIZE_BUCKETS: [f64; 8] = [
+    64.0, 256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0,
+];
+
+/// Engine operations measured by `Database`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DbOperation {
+    Get,
+    /// Reads of several keys in one call
+    MultiGet,
+    Put,
+    Delete,
+    /// Prefix scan, measured over the whole scan
+    Iterate,
+    Batch,
+}
+
+impl DbOperation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DbOperation::Get => "get",
+            DbOperation::MultiGet => "multi_get",
+            DbOperation::Put => "put",
+            DbOperation::Delete => "delete",
+            DbOperation::Iterate => "iterate",
+            DbOperation::Batch => "batch",
+        }
+    }
+}
+
+/// Label of the column family of an operation, batches writing to several are `multiple`
+pub fn cf_label(cf: Option<ColumnFamily>) -> &'static str {
+    cf.map_or("multiple", |cf| cf.name())
+}
+
+/// One engine operation: its duration and the bytes of the keys and values it read or wrote
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OperationSample {
+    pub operation: DbOperation,
+    /// None for the batches over several column families
+    pub cf: Option<ColumnFamily>,
+    pub elapsed: Duration,
+    pub bytes: usize,
+}
+
+/// Periodic reading of the size of the database
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StorageSample {
+    /// Approximate number of keys of each column family
+    pub records: Vec<(ColumnFamily, u64)>,
+    /// Size of the database directory, None for the engines without one
+    pub disk_bytes: Option<u64>,
+}
+
+/// Receives the measurements of a `Database`. A database without a recorder takes none
+pub trait StorageRecorder: Send + Sync + fmt::Debug {
+    fn record_operation(&self, sample: &OperationSample);
+
+    fn record_sample(&self, _sample: &StorageSample) {}
+}
+
+/// Cumulative histogram over fixed bucket bounds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Observations under each bound, not cumulative
+    counts: Vec<u64>,
+    pub sum: f64,
+    pub count: u64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        if let Some(bucket) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// Upper bound of each bucket with the observations up to it, the `+Inf` bucket is
+    /// `count`
+    pub fn cumulative_buckets(&self) -> Vec<(f64, u64)> {
+        let mut total = 0;
+        self.bounds
+            .iter()
+            .zip(&self.counts)
+            .map(|(bound, count)| {
+                total += count;
+                (*bound, total)
+            })
+            .collect()
+    }
+}
+
+/// Latency and size histograms of an operation on a column family
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationHistograms {
+    pub operation: DbOperation,
+    pub cf: Option<ColumnFamily>,
+    /// Seconds
+    pub latency: Histogram,
+    pub bytes: Histogram,
+}
+
+#[derive(Debug, Default)]
+struct Recorded {
+    histograms: BTreeMap<(DbOperation, Option<ColumnFamily>), OperationHistograms>,
+    sample: StorageSample,
+}
+
+/// Recorder of the relayer, histograms per operation and column family and the last
+/// storage sample, exposed by `/metrics`. Operations slower than the threshold are logged
+#[derive(Debug, Clone, Default)]
+pub struct StorageMetrics {
+    recorded: Arc<Mutex<Recorded>>,
+    slow_threshold: Option<Duration>,
+}
+
+impl StorageMetrics {
+    pub fn with_slow_threshold(mut self, threshold: Option<Duration>) -> Self {
+        self.slow_threshold = threshold;
+        self
+    }
+
+    /// Histograms of the operations seen, by operation and column family
+    pub fn histograms(&self) -> Vec<OperationHistograms> {
+        let recorded = self.recorded.lock().unwrap();
+        recorded.histograms.values().cloned().collect()
+    }
+
+    pub fn last_sample(&self) -> StorageSample {
+        self.recorded.lock().unwrap().sample.clone()
+    }
+}
+
+impl StorageRecorder for StorageMetrics {
+    fn record_operation(&self, sample: &OperationSample) {
+        if self
+            .slow_threshold
+            .is_some_and(|threshold| sample.elapsed >= threshold)
+        {
+            warn!(
+                "Slow database {} on {}: {:?} for {} bytes",
+                sample.operation.name(),
+                cf_label(sample.cf),
+                sample.elapsed,
+                sample.bytes
+            );
+        }
+        let mut recorded = self.recorded.lock().unwrap();
+        let histograms = recorded
+            .histograms
+            .entry((sample.operation, sample.cf))
+            .or_insert_with(|| OperationHistograms {
+                operation: sample.operation,
+                cf: sample.cf,
+                latency: Histogram::new(&LATENCY_BUCKETS),
+                bytes: Histogram::new(&SIZE_BUCKETS),
+            });
+        histograms.latency.observe(sample.elapsed.as_secs_f64());
+        histograms.bytes.observe(sample.bytes as f64);
+    }
+
+    fn record_sample(&self, sample: &StorageSample) {
+        self.recorded.lock().unwrap().sample = sample.clone();
+    }
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use crate::{
+        db::Batch,
+        keys::{ColumnFamily, STATUS_INDEX_PREFIX},
+        metrics::{
+            DbOperation, Histogram, OperationSample, StorageMetrics, StorageRecorder,
+            StorageSample, LATENCY_BUCKETS, SIZE_BUCKETS,
+        },
+        testing::each_engine,
+    };
+
+    /// Keeps every measurement it receives
+    #[derive(Debug, Default)]
+    struct RecordingRecorder {
+        operations: Mutex<Vec<OperationSample>>,
+        samples: Mutex<Vec<StorageSample>>,
+    }
+
+    impl RecordingRecorder {
+        fn recorded(&self) -> Vec<(DbOperation, Option<ColumnFamily>, usize)> {
+            let operations = self.operations.lock().unwrap();
+            operations
+                .iter()
+                .map(|sample| (sample.operation, sample.cf, sample.bytes))
+                .collect()
+        }
+    }
+
+    impl StorageRecorder for RecordingRecorder {
+        fn record_operation(&self, sample: &OperationSample) {
+            self.operations.lock().unwrap().push(*sample);
+        }
+
+        fn record_sample(&self, sample: &StorageSample) {
+            self.samples.lock().unwrap().push(sample.clone());
+        }
+    }
+
+    #[test]
+    fn test_each_operation_is_recorded() {
+        for db in each_engine() {
+            let recorder = Arc::new(RecordingRecorder::default());
+            let db = db.with_recorder(recorder.clone());
+            let status_key = format!("{STATUS_INDEX_PREFIX}Completed:0x01");
+
+            db.write_value("0x01", &"request").unwrap();
+            let _: Option<String> = db.read("0x01").unwrap();
+            let _: Vec<(String, String)> = db.scan_prefix("0x", None).unwrap();
+            db.delete("0x01").unwrap();
+            let mut batch = Batch::default();
+            batch.put("0x02", &"request").unwrap();
+            batch.put(&status_key, &"0x02").unwrap();
+            db.write_batch(batch).unwrap();
+            let mut batch = Batch::default();
+            batch.delete(&status_key);
+            db.write_batch(batch).unwrap();
+
+            // "request" is 9 bytes serialized
+            assert_eq!(
+                recorder.recorded(),
+                vec![
+                    (DbOperation::Put, Some(ColumnFamily::Requests), 4 + 9),
+                    (DbOperation::Get, Some(ColumnFamily::Requests), 4 + 9),
+                    (DbOperation::Iterate, Some(ColumnFamily::Requests), 4 + 9),
+                    (DbOperation::Delete, Some(ColumnFamily::Requests), 4),
+                    (DbOperation::Batch, None, 4 + 9 + status_key.len() + 6),
+                    (
+                        DbOperation::Batch,
+                        Some(ColumnFamily::Indexes),
+                        status_key.len()
+                    ),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_sample_counts_records() {
+        for db in each_engine() {
+            let recorder = Arc::new(RecordingRecorder::default());
+            let db = db.with_recorder(recorder.clone());
+            db.write_value("0x01", &"request").unwrap();
+            db.write_value("0x02", &"request").unwrap();
+
+            let sample = db.sample_storage().unwrap();
+
+            assert!(sample
+                .records
+                .iter()
+                .any(|(cf, count)| *cf == ColumnFamily::Requests && *count >= 1));
+            assert_eq!(sample.records.len(), ColumnFamily::ALL.len());
+            assert_eq!(*recorder.samples.lock().unwrap(), vec![sample]);
+        }
+    }
+
+    #[test]
+    fn test_no_recorder_records_nothing() {
+        for db in each_engine() {
+            let recorder = Arc::new(RecordingRecorder::default());
+            let recorded = db.with_recorder(recorder.clone());
+            // The database the recorder was added to is a clone, the original has none
+            db.write_value("0x01", &"request").unwrap();
+
+            assert!(recorder.recorded().is_empty());
+            drop(recorded);
+        }
+    }
+
+    #[test]
+    fn test_histogram_buckets() {
+        let mut histogram = Histogram::new(&SIZE_BUCKETS);
+        histogram.observe(10.0);
+        histogram.observe(300.0);
+        histogram.observe(5_000_000.0);
+
+        let buckets = histogram.cumulative_buckets();
+        assert_eq!(buckets[0], (64.0, 1));
+        assert_eq!(buckets[1], (256.0, 1));
+        assert_eq!(buckets[2], (1024.0, 2));
+        assert_eq!(buckets.last(), Some(&(1048576.0, 2)));
+        assert_eq!(histogram.count, 3);
+        assert_eq!(histogram.sum, 5_000_310.0);
+    }
+
+    #[test]
+    fn test_storage_metrics_histograms() {
+        let metrics = StorageMetrics::default().with_slow_threshold(Some(Duration::from_secs(1)));
+        for elapsed in [Duration::from_micros(50), Duration::from_secs(2)] {
+            metrics.record_operation(&OperationSample {
+                operation: DbOperation::Get,
+                cf: Some(ColumnFamily::Requests),
+                elapsed,
+                bytes: 100,
+            });
+        }
+
+        let histograms = metrics.histograms();
+        assert_eq!(histograms.len(), 1);
+        assert_eq!(histograms[0].operation, DbOperation::Get);
+        assert_eq!(histograms[0].latency.count, 2);
+        assert_eq!(
+            histograms[0].latency.cumulative_buckets()[0],
+            (LATENCY_BUCKETS[0], 1)
+        );
+        // Slower than the last bound, only in the +Inf bucket
+        assert_eq!(
+            histograms[0].latency.cumulative_buckets().last(),
+            Some(&(1.0, 1))
+        );
+        assert_eq!(histograms[0].bytes.cumulative_buckets()[1], (256.0, 2));
+    }
+}
@@ -0,0 +1,177 @@
+use std::{fmt, fs, path::Path, time::Duration};
+
+use rocksdb::{ColumnFamilyDescriptor, Direction, IteratorMode, Options, WriteBatch, DB};
+
+use crate::{
+    engine::{BatchOp, StorageEngine},
+    errors::DbError,
+    keys::ColumnFamily,
+};
+
+/// Files of the cache column family older than this are compacted again, dropping the
+/// dedupe entries deleted since they were written
+pub const CACHE_COMPACTION_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Engine of the relayer, a RocksDB database in a directory with a column family per
+/// class of data
+pub struct RocksEngine {
+    db: DB,
+}
+
+fn cf_options(cf: ColumnFamily) -> Options {
+    let mut opts = Options::default();
+    if cf == ColumnFamily::Cache {
+        opts.set_periodic_compaction_seconds(CACHE_COMPACTION_PERIOD.as_secs());
+    }
+    opts
+}
+
+impl RocksEngine {
+    /// Opens the database in `path`, the database and its missing column families are
+    /// created
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DbError> {
+        let path_str = path
+            .as_ref()
+            .to_str()
+            .ok_or_else(|| DbError::InvalidPath(format!("{:?}", path.as_ref())))?;
+
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+
+        let descriptors = ColumnFamily::ALL
+            .into_iter()
+            .map(|cf| ColumnFamilyDescriptor::new(cf.name(), cf_options(cf)));
+        let db = DB::open_cf_descriptors(&opts, path_str, descriptors)
+            .map_err(|e| DbError::RocksDb(e.to_string()))?;
+        Ok(RocksEngine { db })
+    }
+
+    /// Opens the database in `path` as a secondary instance keeping its own files in
+    /// `secondary_path`. Reads see the primary as of the open or the last `catch_up`, writes
+    /// are refused by RocksDB
+    pub fn open_secondary(
+        path: impl AsRef<Path>,
+        secondary_path: impl AsRef<Path>,
+    ) -> Result<Self, DbError> {
+        let mut opts = Options::default();
+        // Required by secondary instances, the files of the primary change under them
+        opts.set_max_open_files(-1);
+
+        let names = ColumnFamily::ALL.into_iter().map(|cf| cf.name());
+        let db = DB::open_cf_as_secondary(&opts, path.as_ref(), secondary_path.as_ref(), names)
+            .map_err(|e| DbError::RocksDb(e.to_string()))?;
+        Ok(RocksEngine { db })
+    }
+
+    fn handle(&self, cf: ColumnFamily) -> Result<&rocksdb::ColumnFamily, DbError> {
+        self.db
+            .cf_handle(cf.name())
+            .ok_or_else(|| DbError::RocksDb(format!("missing column family {}", cf.name())))
+    }
+}
+
+impl fmt::Debug for RocksEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RocksEngine")
+            .field("path", &self.db.path())
+            .finish()
+    }
+}
+
+impl StorageEngine for RocksEngine {
+    fn get(&self, cf: ColumnFamily, key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        self.db
+            .get_cf(self.handle(cf)?, key)
+            .map_err(|e| DbError::WriteDb(e.to_string()))
+    }
+
+    fn multi_get(&self, cf: ColumnFamily, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>, DbError> {
+        let handle = self.handle(cf)?;
+        self.db
+            .multi_get_cf(keys.iter().map(|key| (handle, *key)))
+            .into_iter()
+            .map(|value| value.map_err(|e| DbError::ReadDb(e.to_string())))
+            .collect()
+    }
+
+    fn put(&self, cf: ColumnFamily, key: &[u8], value: &[u8]) -> Result<(), DbError> {
+        self.db
+            .put_cf(self.handle(cf)?, key, value)
+            .map_err(|e| DbError::WriteDb(e.to_string()))
+    }
+
+    fn delete(&self, cf: ColumnFamily, key: &[u8]) -> Result<(), DbError> {
+        self.db
+            .delete_cf(self.handle(cf)?, key)
+            .map_err(|e| DbError::WriteDb(e.to_string()))
+    }
+
+    fn iterate_prefix(
+        &self,
+        cf: ColumnFamily,
+        prefix: &[u8],
+        from: &[u8],
+        visit: &mut dyn FnMut(&[u8], &[u8]) -> Result<bool, DbError>,
+    ) -> Result<(), DbError> {
+        let iter = self.db.iterator_cf(
+            self.handle(cf)?,
+            IteratorMode::From(from, Direction::Forward),
+        );
+        for item in iter {
+            let (key, value) = item.map_err(|e| DbError::ReadDb(e.to_string()))?;
+            if !key.starts_with(prefix) || !visit(&key, &value)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_batch(&self, ops: Vec<BatchOp>) -> Result<(), DbError> {
+        // A single write batch, atomic across the column families
+        let mut batch = WriteBatch::default();
+        for op in ops {
+            match op {
+                BatchOp::Put { cf, key, value } => batch.put_cf(self.handle(cf)?, key, value),
+                BatchOp::Delete { cf, key } => batch.delete_cf(self.handle(cf)?, key),
+            }
+        }
+        self.db
+            .write(batch)
+            .map_err(|e| DbError::Batch(e.to_string()))
+    }
+
+    fn approximate_count(&self, cf: ColumnFamily) -> Result<u64, DbError> {
+        self.db
+            .property_int_value_cf(self.handle(cf)?, "rocksdb.estimate-num-keys")
+            .map(|count| count.unwrap_or(0))
+            .map_err(|e| DbError::ReadDb(e.to_string()))
+    }
+
+    fn disk_size(&self) -> Result<Option<u64>, DbError> {
+        directory_size(self.db.path())
+            .map(Some)
+            .map_err(|e| DbError::ReadDb(e.to_string()))
+    }
+
+    fn catch_up(&self) -> Result<(), DbError> {
+        self.db
+            .try_catch_up_with_primary()
+            .map_err(|e| DbError::ReadDb(e.to_string()))
+    }
+}
+
+/// Bytes of the files under `path`
+fn directory_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            directory_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
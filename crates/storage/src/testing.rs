@@ -0,0 +1,79 @@
+use std::{env, ops::Deref};
+
+use crate::db::Database;
+
+/// Environment variable choosing the engine of `test_database`: `memory`, the default,
+/// or `rocksdb`
+pub const TEST_ENGINE_VAR: &str = "STORAGE_TEST_ENGINE";
+
+/// Engines the tests can run against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestEngine {
+    Memory,
+    #[cfg(feature = "rocksdb")]
+    RocksDb,
+}
+
+/// Every engine of the build
+pub fn engines() -> Vec<TestEngine> {
+    vec![
+        TestEngine::Memory,
+        #[cfg(feature = "rocksdb")]
+        TestEngine::RocksDb,
+    ]
+}
+
+/// Database of a test, a RocksDB one lives in a temporary directory removed when dropped
+#[derive(Debug)]
+pub struct TestDatabase {
+    pub engine: TestEngine,
+    db: Database,
+    #[cfg(feature = "rocksdb")]
+    _dir: Option<tempfile::TempDir>,
+}
+
+impl TestDatabase {
+    pub fn new(engine: TestEngine) -> Self {
+        match engine {
+            TestEngine::Memory => TestDatabase {
+                engine,
+                db: Database::open_in_memory(),
+                #[cfg(feature = "rocksdb")]
+                _dir: None,
+            },
+            #[cfg(feature = "rocksdb")]
+            TestEngine::RocksDb => {
+                let dir = tempfile::tempdir().expect("temporary directory");
+                TestDatabase {
+                    engine,
+                    db: Database::open(dir.path()).expect("test database"),
+                    _dir: Some(dir),
+                }
+            }
+        }
+    }
+}
+
+impl Deref for TestDatabase {
+    type Target = Database;
+
+    fn deref(&self) -> &Database {
+        &self.db
+    }
+}
+
+/// Database on the engine of `STORAGE_TEST_ENGINE`, in memory when unset
+pub fn test_database() -> TestDatabase {
+    let engine = match env::var(TEST_ENGINE_VAR).as_deref() {
+        Ok("memory") | Err(_) => TestEngine::Memory,
+        #[cfg(feature = "rocksdb")]
+        Ok("rocksdb") => TestEngine::RocksDb,
+        Ok(other) => panic!("Unknown {TEST_ENGINE_VAR} engine {other}"),
+    };
+    TestDatabase::new(engine)
+}
+
+/// A fresh database on each engine of the build, to run the same test against all of them
+pub fn each_engine() -> impl Iterator<Item = TestDatabase> {
+    engines().into_iter().map(TestDatabase::new)
+}
@@ -0,0 +1,8 @@
+use storage::testing::{test_database, TestDatabase};
+
+/// Database of a test on the engine of `STORAGE_TEST_ENGINE`, in memory by default
+pub type TestDb = TestDatabase;
+
+pub fn test_db() -> TestDb {
+    test_database()
+}
@@ -0,0 +1,66 @@
+use alloy::{
+    primitives::{address, Address, Bytes, Log as PrimitiveLog, LogData, B256, U256},
+    rpc::types::Log,
+    sol,
+    sol_types::SolEvent,
+};
+
+sol! {
+    /// Events of the bridge contract, same signatures as the deployed one
+    contract MockBridge {
+        event NewRequest(string requestId, address tokenContract, uint256 tokenId);
+        event TokenMinted(string requestId, address tokenContract, address to, uint256 tokenId);
+    }
+}
+
+/// Address of the bridge contract in the fixtures, the first anvil deployment
+pub const BRIDGE_CONTRACT: Address = address!("5fbdb2315678afecb367f032d93f642f64180aa3");
+
+/// NewRequest log emitted by the bridge contract at (block, index)
+pub fn new_request_log(
+    request_id: &str,
+    token_contract: Address,
+    token_id: U256,
+    position: (u64, u64),
+) -> Log {
+    let event = MockBridge::NewRequest {
+        requestId: request_id.to_string(),
+        tokenContract: token_contract,
+        tokenId: token_id,
+    };
+    bridge_log(event.encode_log_data(), position)
+}
+
+/// TokenMinted log emitted by the bridge contract at (block, index)
+pub fn token_minted_log(
+    request_id: &str,
+    token_contract: Address,
+    to: Address,
+    token_id: U256,
+    position: (u64, u64),
+) -> Log {
+    let event = MockBridge::TokenMinted {
+        requestId: request_id.to_string(),
+        tokenContract: token_contract,
+        to,
+        tokenId: token_id,
+    };
+    bridge_log(event.encode_log_data(), position)
+}
+
+/// Bridge contract log of any topics and data, valid or not, for the decoder tests
+pub fn raw_bridge_log(topics: Vec<B256>, data: Vec<u8>) -> Log {
+    bridge_log(LogData::new_unchecked(topics, Bytes::from(data)), (0, 0))
+}
+
+fn bridge_log(data: LogData, (block, index): (u64, u64)) -> Log {
+    Log {
+        inner: PrimitiveLog {
+            address: BRIDGE_CONTRACT,
+            data,
+        },
+        block_number: Some(block),
+        log_index: Some(index),
+        ..Default::default()
+    }
+}
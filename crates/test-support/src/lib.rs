@@ -0,0 +1,20 @@
+//! Fixtures of the bridge contracts, chain events and requests, built in code so the
+//! tests of every crate run without network access or the deployed contracts
+
+pub mod db;
+pub use db::*;
+
+pub mod evm_logs;
+pub use evm_logs::*;
+
+pub mod solana_events;
+pub use solana_events::*;
+
+pub mod requests;
+pub use requests::*;
+
+pub mod rpc;
+pub use rpc::*;
+
+pub mod replay;
+pub use replay::*;
@@ -0,0 +1,25 @@
+use storage::db::Database;
+use types::{BridgeError, ReplayBundle, ReplayEntry, ReplayTransitions, Result};
+
+use crate::test_db;
+
+/// Runs the inputs of a replay bundle through `step` on a fresh database holding the
+/// request as it was before them, no chain is called. Returns the transitions of the
+/// replayed request, to compare with the `expected` ones of the bundle
+pub fn replay_bundle<F>(bundle: &ReplayBundle, mut step: F) -> Result<ReplayTransitions>
+where
+    F: FnMut(&Database, &ReplayEntry) -> Result<()>,
+{
+    let db = test_db();
+    bundle.initial.save(&db)?;
+    for entry in &bundle.entries {
+        step(&db, entry)?;
+    }
+    let request = types::request_data(&bundle.request_id, &db)?.ok_or_else(|| {
+        BridgeError::Other(format!(
+            "Request {} missing after its replay",
+            bundle.request_id
+        ))
+    })?;
+    Ok(ReplayTransitions::of(&request))
+}
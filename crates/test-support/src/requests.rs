@@ -0,0 +1,102 @@
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use types::{
+    BRequest, CancelReason, Chains, DestinationAccountPending, InputRequest, Status, Timestamp,
+    TOKEN_ACCOUNT_RENT,
+};
+
+/// ERC721 contract of the EVM fixtures, the second anvil deployment
+pub const EVM_TOKEN_CONTRACT: &str = "0xe7f1725e7734ce288f8367e1bb143e90bb3f0512";
+/// First of the default anvil accounts
+pub const EVM_ACCOUNT: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+
+/// Deterministic Solana account, the same `seed` gives the same key
+pub fn solana_key(seed: u8) -> Pubkey {
+    Pubkey::new_from_array([seed; 32])
+}
+
+/// Input of a request from `origin`, with valid accounts on both chains
+pub fn input_request(origin: Chains, token_id: &str) -> InputRequest {
+    match origin {
+        Chains::EVM => InputRequest {
+            contract_or_mint: EVM_TOKEN_CONTRACT.to_string(),
+            token_id: token_id.to_string(),
+            token_owner: EVM_ACCOUNT.to_string(),
+            origin_network: Chains::EVM,
+            destination_account: solana_key(3).to_string(),
+        },
+        Chains::SOLANA => InputRequest {
+            contract_or_mint: solana_key(1).to_string(),
+            token_id: token_id.to_string(),
+            token_owner: solana_key(2).to_string(),
+            origin_network: Chains::SOLANA,
+            destination_account: EVM_ACCOUNT.to_string(),
+        },
+    }
+}
+
+/// Request from `origin` in `status`, with the transactions, output and reasons a
+/// request has once it reaches it
+pub fn request_in_status(origin: Chains, token_id: &str, status: Status) -> BRequest {
+    let mut request = BRequest::new(input_request(origin.clone(), token_id), Timestamp::now());
+    let evm_tx = format!("0x{}", "ab".repeat(32));
+    let solana_tx = Signature::from([7u8; 64]).to_string();
+    let (lock_tx, mint_tx) = match origin {
+        Chains::EVM => (evm_tx, solana_tx),
+        Chains::SOLANA => (solana_tx, evm_tx),
+    };
+
+    match status {
+        Status::Initializing | Status::RequestReceived => {}
+        Status::NeedsDestination | Status::RefundEligible => {
+            request.input.destination_account = String::new();
+        }
+        Status::TokenReceived => request.tx_hashes = vec![lock_tx],
+        Status::AwaitingDestinationAccount => {
+            request.tx_hashes = vec![lock_tx];
+            request.destination_account_pending = Some(DestinationAccountPending {
+                token_account: solana_key(5).to_string(),
+                lamports: TOKEN_ACCOUNT_RENT,
+                since: request.last_update,
+            });
+        }
+        Status::TokenMinted | Status::Completed | Status::NeedsIntervention => {
+            request.tx_hashes = vec![lock_tx, mint_tx];
+            let (contract_or_mint, token_id_or_account) = match origin {
+                Chains::EVM => (solana_key(4).to_string(), solana_key(5).to_string()),
+                Chains::SOLANA => (EVM_TOKEN_CONTRACT.to_string(), token_id.to_string()),
+            };
+            request.output.detination_contract_id_or_mint = contract_or_mint;
+            request.output.detination_token_id_or_account = token_id_or_account;
+        }
+        Status::Canceled => {
+            request.cancel_reason =
+                Some(CancelReason::MetadataInvalid("javascript: URI".to_string()));
+        }
+        Status::Refunded => {
+            let refund_tx = match origin {
+                Chains::EVM => format!("0x{}", "cd".repeat(32)),
+                Chains::SOLANA => Signature::from([9u8; 64]).to_string(),
+            };
+            request.tx_hashes = vec![lock_tx, refund_tx];
+        }
+    }
+    if matches!(
+        status,
+        Status::Completed | Status::Canceled | Status::Refunded
+    ) {
+        request.finalized_at = Some(request.last_update);
+    }
+    request.status = status;
+    request
+}
+
+/// One request from `origin` per status, each for a different token
+pub fn requests_in_every_status(origin: Chains) -> Vec<BRequest> {
+    Status::ALL
+        .iter()
+        .enumerate()
+        .map(|(index, status)| {
+            request_in_status(origin.clone(), &(index + 1).to_string(), status.clone())
+        })
+        .collect()
+}
@@ -0,0 +1,285 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde_json::{json, Value};
+use solana_client::{
+    client_error::Result as ClientResult,
+    mock_sender::{MockSender, Mocks},
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, hash::Hash, program_pack::Pack, pubkey::Pubkey,
+};
+use spl_token::state::{Account, AccountState};
+
+/// Slot of the canned responses
+pub const FIXTURE_SLOT: u64 = 350_000_000;
+
+/// Metaplex Token Metadata program
+pub const TOKEN_METADATA_PROGRAM: &str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
+
+/// RpcClient answering `mocks` without network access, each canned result is returned
+/// once and the calls without one get the defaults of the mock sender
+pub fn mock_rpc(mocks: impl IntoIterator<Item = (RpcRequest, Value)>) -> RpcClient {
+    RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks.into_iter().collect::<Mocks>())
+}
+
+/// Mock sender answering after `delay`, an RPC node slow to respond
+struct SlowSender {
+    delay: Duration,
+    sender: MockSender,
+}
+
+#[async_trait]
+impl RpcSender for SlowSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        tokio::time::sleep(self.delay).await;
+        self.sender.send(request, params).await
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.sender.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.sender.url()
+    }
+}
+
+/// Same as `mock_rpc` with every call answered after `delay`
+pub fn slow_mock_rpc(
+    delay: Duration,
+    mocks: impl IntoIterator<Item = (RpcRequest, Value)>,
+) -> RpcClient {
+    let sender = MockSender::new_with_mocks("succeeds", mocks.into_iter().collect::<Mocks>());
+    RpcClient::new_sender(
+        SlowSender { delay, sender },
+        RpcClientConfig::with_commitment(CommitmentConfig::default()),
+    )
+}
+
+/// Calls received by an `accounts_mock_rpc` client per method
+#[derive(Debug, Clone, Default)]
+pub struct RpcCalls(Arc<Mutex<HashMap<RpcRequest, usize>>>);
+
+impl RpcCalls {
+    pub fn count(&self, request: RpcRequest) -> usize {
+        self.0.lock().unwrap().get(&request).copied().unwrap_or(0)
+    }
+}
+
+/// Mock sender serving getAccountInfo and getMultipleAccounts from `accounts`, counting the
+/// calls of every method
+struct AccountsSender {
+    accounts: HashMap<Pubkey, Vec<u8>>,
+    calls: RpcCalls,
+    sender: MockSender,
+}
+
+impl AccountsSender {
+    fn account(&self, pubkey: &Value) -> Value {
+        pubkey
+            .as_str()
+            .and_then(|pubkey| pubkey.parse::<Pubkey>().ok())
+            .and_then(|pubkey| self.accounts.get(&pubkey))
+            .map(|data| account_json(data, &spl_token::id()))
+            .unwrap_or(Value::Null)
+    }
+}
+
+#[async_trait]
+impl RpcSender for AccountsSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        *self.calls.0.lock().unwrap().entry(request).or_default() += 1;
+        match request {
+            RpcRequest::GetAccountInfo => Ok(with_context(self.account(&params[0]))),
+            RpcRequest::GetMultipleAccounts => {
+                let accounts = params[0].as_array().cloned().unwrap_or_default();
+                Ok(with_context(json!(accounts
+                    .iter()
+                    .map(|pubkey| self.account(pubkey))
+                    .collect::<Vec<_>>())))
+            }
+            _ => self.sender.send(request, params).await,
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.sender.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.sender.url()
+    }
+}
+
+/// RpcClient holding the SPL token owned `accounts`, the others don't exist. The calls
+/// are counted in the returned `RpcCalls`, the ones not reading accounts get the defaults
+/// of the mock sender
+pub fn accounts_mock_rpc(accounts: HashMap<Pubkey, Vec<u8>>) -> (RpcClient, RpcCalls) {
+    let calls = RpcCalls::default();
+    let sender = AccountsSender {
+        accounts,
+        calls: calls.clone(),
+        sender: MockSender::new("succeeds"),
+    };
+    let rpc = RpcClient::new_sender(
+        sender,
+        RpcClientConfig::with_commitment(CommitmentConfig::default()),
+    );
+    (rpc, calls)
+}
+
+fn with_context(value: Value) -> Value {
+    json!({ "context": { "slot": FIXTURE_SLOT }, "value": value })
+}
+
+/// Result of getSlot
+pub fn slot_result() -> Value {
+    json!(FIXTURE_SLOT)
+}
+
+/// Result of getLatestBlockhash
+pub fn latest_blockhash_result() -> Value {
+    with_context(json!({
+        "blockhash": Hash::new_from_array([9u8; 32]).to_string(),
+        "lastValidBlockHeight": FIXTURE_SLOT + 150,
+    }))
+}
+
+/// Result of isBlockhashValid
+pub fn blockhash_valid_result(valid: bool) -> Value {
+    with_context(json!(valid))
+}
+
+/// Result of getBalance
+pub fn balance_result(lamports: u64) -> Value {
+    with_context(json!(lamports))
+}
+
+/// Result of getSignatureStatuses for one signature, unknown to the node without a
+/// `confirmation` level
+pub fn signature_status_result(confirmation: Option<&str>) -> Value {
+    let status = confirmation.map(|confirmation| {
+        // Rooted transactions have no confirmation count
+        let confirmations = match confirmation {
+            "finalized" => Value::Null,
+            _ => json!(1),
+        };
+        json!({
+            "slot": FIXTURE_SLOT,
+            "confirmations": confirmations,
+            "err": null,
+            "status": { "Ok": null },
+            "confirmationStatus": confirmation,
+        })
+    });
+    with_context(json!([status]))
+}
+
+/// Result of getSignaturesForAddress, `(signature, block time, failed)` newest first
+pub fn signatures_for_address_result(signatures: &[(&str, Option<i64>, bool)]) -> Value {
+    json!(signatures
+        .iter()
+        .map(|(signature, block_time, failed)| json!({
+            "signature": signature,
+            "slot": FIXTURE_SLOT,
+            "err": failed.then(|| json!({ "InstructionError": [0, { "Custom": 1 }] })),
+            "memo": null,
+            "blockTime": block_time,
+            "confirmationStatus": "finalized",
+        }))
+        .collect::<Vec<_>>())
+}
+
+/// Result of getTransaction of a successful transaction paying `fee`, with the balances of
+/// its accounts before and after it, fee payer first
+pub fn transaction_result(fee: u64, pre_balances: &[u64], post_balances: &[u64]) -> Value {
+    json!({
+        "slot": FIXTURE_SLOT,
+        "blockTime": null,
+        "transaction": ["", "base64"],
+        "meta": {
+            "err": null,
+            "status": { "Ok": null },
+            "fee": fee,
+            "preBalances": pre_balances,
+            "postBalances": post_balances,
+            "innerInstructions": null,
+            "logMessages": null,
+            "preTokenBalances": null,
+            "postTokenBalances": null,
+            "rewards": null,
+        },
+    })
+}
+
+/// Result of getAccountInfo of an account holding `data`
+pub fn account_info_result(data: &[u8], owner: &Pubkey) -> Value {
+    with_context(account_json(data, owner))
+}
+
+fn account_json(data: &[u8], owner: &Pubkey) -> Value {
+    json!({
+        "lamports": 2_039_280,
+        "data": [BASE64_STANDARD.encode(data), "base64"],
+        "owner": owner.to_string(),
+        "executable": false,
+        "rentEpoch": 0,
+        "space": data.len(),
+    })
+}
+
+/// Result of getAccountInfo of an account that doesn't exist
+pub fn missing_account_result() -> Value {
+    with_context(Value::Null)
+}
+
+/// Data of an initialized SPL token account
+pub fn token_account_data(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Vec<u8> {
+    let account = Account {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        state: AccountState::Initialized,
+        ..Default::default()
+    };
+    let mut data = vec![0; Account::LEN];
+    Account::pack(account, &mut data).expect("token account packing");
+    data
+}
+
+/// Result of getAccountInfo of an SPL token account
+pub fn token_account_result(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Value {
+    account_info_result(&token_account_data(mint, owner, amount), &spl_token::id())
+}
+
+/// Metaplex metadata account of `mint` holding `uri`, with its address
+pub fn metadata_account(mint: &Pubkey, uri: &str) -> (Pubkey, Vec<u8>) {
+    let program = Pubkey::from_str(TOKEN_METADATA_PROGRAM).expect("metadata program id");
+    let (address, _) =
+        Pubkey::find_program_address(&[b"metadata", program.as_ref(), mint.as_ref()], &program);
+    // Key, update authority, mint, name, symbol, uri and royalties
+    let mut data = borsh::to_vec(&(
+        4u8,
+        [0u8; 32],
+        mint.to_bytes(),
+        "Fixture".to_string(),
+        "FIX".to_string(),
+        uri.to_string(),
+        0u16,
+    ))
+    .expect("metadata serialization");
+    // No creators, primary sale not happened, mutable, then the optional fields unset
+    data.extend([0, 0, 1, 0, 0, 0, 0, 0, 0]);
+    (address, data)
+}
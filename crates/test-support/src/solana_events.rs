@@ -0,0 +1,65 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
+use borsh::BorshSerialize;
+use solana_sdk::pubkey::Pubkey;
+
+/// Discriminators of the bridge program events, as in `idls/solana_bridge.json`
+pub const NEW_REQUEST_EVENT_DISCRIMINATOR: [u8; 8] = [111, 137, 237, 170, 219, 37, 129, 110];
+pub const TOKEN_MINTED_EVENT_DISCRIMINATOR: [u8; 8] = [136, 51, 151, 241, 53, 48, 38, 62];
+
+/// Layout shared by NewRequestEvent and TokenMintedEvent
+#[derive(BorshSerialize)]
+struct EventFields {
+    mint: [u8; 32],
+    token_account: [u8; 32],
+    request_id: String,
+}
+
+fn event_data(
+    discriminator: [u8; 8],
+    mint: &Pubkey,
+    account: &Pubkey,
+    request_id: &str,
+) -> Vec<u8> {
+    let mut data = discriminator.to_vec();
+    EventFields {
+        mint: mint.to_bytes(),
+        token_account: account.to_bytes(),
+        request_id: request_id.to_string(),
+    }
+    .serialize(&mut data)
+    .expect("event serialization");
+    data
+}
+
+/// Anchor encoded NewRequestEvent
+pub fn new_request_event_data(
+    mint: &Pubkey,
+    user_token_account: &Pubkey,
+    request_id: &str,
+) -> Vec<u8> {
+    event_data(
+        NEW_REQUEST_EVENT_DISCRIMINATOR,
+        mint,
+        user_token_account,
+        request_id,
+    )
+}
+
+/// Anchor encoded TokenMintedEvent
+pub fn token_minted_event_data(
+    mint: &Pubkey,
+    destination_token_account: &Pubkey,
+    request_id: &str,
+) -> Vec<u8> {
+    event_data(
+        TOKEN_MINTED_EVENT_DISCRIMINATOR,
+        mint,
+        destination_token_account,
+        request_id,
+    )
+}
+
+/// Transaction log line of an event emitted by the program
+pub fn program_data_log(data: &[u8]) -> String {
+    format!("Program data: {}", BASE64_STANDARD.encode(data))
+}
@@ -0,0 +1,108 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use storage::metrics::Histogram;
+
+/// Upper bounds of the API latency buckets, in seconds
+pub const API_LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+/// Route label of the requests matching no route, the raw paths are never labels
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Requests served on a route, by status class, and their latency
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteMetrics {
+    pub method: String,
+    /// Route template, e.g. `/bridge/requests/{id}`
+    pub route: String,
+    /// Count of each status class (`2xx`, `4xx`, ...)
+    pub statuses: BTreeMap<&'static str, u64>,
+    /// Seconds
+    pub latency: Histogram,
+}
+
+impl RouteMetrics {
+    pub fn requests(&self) -> u64 {
+        self.latency.count
+    }
+}
+
+/// Requests served by the API, by method and route template, exposed by `/metrics`
+#[derive(Debug, Clone, Default)]
+pub struct ApiMetrics {
+    routes: Arc<Mutex<BTreeMap<(String, String), RouteMetrics>>>,
+}
+
+impl ApiMetrics {
+    pub fn record(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let mut routes = self.routes.lock().unwrap();
+        let metrics = routes
+            .entry((route.to_string(), method.to_string()))
+            .or_insert_with(|| RouteMetrics {
+                method: method.to_string(),
+                route: route.to_string(),
+                statuses: BTreeMap::new(),
+                latency: Histogram::new(&API_LATENCY_BUCKETS),
+            });
+        *metrics.statuses.entry(status_class(status)).or_default() += 1;
+        metrics.latency.observe(elapsed.as_secs_f64());
+    }
+
+    /// Metrics of the routes served, ordered by route and method
+    pub fn routes(&self) -> Vec<RouteMetrics> {
+        self.routes.lock().unwrap().values().cloned().collect()
+    }
+}
+
+/// Class of an HTTP status, `1xx` to `5xx`
+pub fn status_class(status: u16) -> &'static str {
+    match status {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
+
+#[cfg(test)]
+mod api_metrics_test {
+    use std::time::Duration;
+
+    use crate::ApiMetrics;
+
+    #[test]
+    fn test_requests_counted_by_route_and_status_class() {
+        let metrics = ApiMetrics::default();
+        metrics.record(
+            "GET",
+            "/bridge/requests/{id}",
+            200,
+            Duration::from_millis(3),
+        );
+        metrics.record(
+            "GET",
+            "/bridge/requests/{id}",
+            404,
+            Duration::from_millis(1),
+        );
+        metrics.record("GET", "/bridge/requests/{id}", 204, Duration::from_secs(20));
+        metrics.record("POST", "/admin/pause", 401, Duration::from_millis(2));
+
+        let routes = metrics.routes();
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].route, "/admin/pause");
+        let requests = &routes[1];
+        assert_eq!(requests.requests(), 3);
+        assert_eq!(requests.statuses["2xx"], 2);
+        assert_eq!(requests.statuses["4xx"], 1);
+        // The slow request is only in the `+Inf` bucket
+        let buckets = requests.latency.cumulative_buckets();
+        assert_eq!(buckets[0], (0.005, 2));
+        assert_eq!(buckets.last().unwrap().1, 2);
+    }
+}
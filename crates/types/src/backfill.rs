@@ -0,0 +1,374 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use storage::db::{Batch, Database};
+
+use crate::{
+    request_data, stage_completed_request, BRequest, Chains, HistoryEntry, InputRequest,
+    OutputResult, RequestId, Result, Status, Timestamp,
+};
+
+/// Bridge event read back from the history of a chain
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoricalEvent {
+    /// Token locked by the bridge on its origin chain, the input has no destination
+    NewRequest {
+        request_id: RequestId,
+        input: InputRequest,
+        tx: String,
+        /// Time of the block of the lock when it was read
+        locked_at: Option<Timestamp>,
+    },
+    /// Token minted on `chain`, the destination of the request
+    TokenMinted {
+        request_id: RequestId,
+        chain: Chains,
+        output: OutputResult,
+        /// Owner of the minted token when the event names it
+        destination_account: Option<String>,
+        tx: String,
+    },
+}
+
+impl HistoricalEvent {
+    pub fn request_id(&self) -> &RequestId {
+        match self {
+            HistoricalEvent::NewRequest { request_id, .. } => request_id,
+            HistoricalEvent::TokenMinted { request_id, .. } => request_id,
+        }
+    }
+}
+
+/// Outcome of a backfill run. The event counts are the same on every run over the same
+/// range, `written` drops to zero once the records are stored
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct BackfillReport {
+    /// Locks with their mint on the other chain, reconstructed Completed
+    pub complete: usize,
+    /// Locks without a mint, reconstructed as orphans waiting for a destination
+    pub partial: usize,
+    /// Mints without their lock in the range, nothing is written for them
+    pub unmatched: usize,
+    /// Records created or completed by this run
+    pub written: usize,
+    /// Records left as stored, known to the relayer or already reconstructed as far
+    pub unchanged: usize,
+    /// Logs left out after their lookups kept failing, a later run over their blocks
+    /// picks them up
+    pub deferred: usize,
+}
+
+/// Logs the backfills went through since the start, read by `/metrics`. The clones share
+/// the counts
+#[derive(Debug, Clone, Default)]
+pub struct BackfillProgress {
+    processed: Arc<AtomicU64>,
+    deferred: Arc<AtomicU64>,
+}
+
+impl BackfillProgress {
+    pub fn record(&self, deferred: bool) {
+        self.processed.fetch_add(1, Ordering::Relaxed);
+        if deferred {
+            self.deferred.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Logs processed, deferred ones included
+    pub fn processed(&self) -> u64 {
+        self.processed.load(Ordering::Relaxed)
+    }
+
+    pub fn deferred(&self) -> u64 {
+        self.deferred.load(Ordering::Relaxed)
+    }
+}
+
+/// Lock and mint of a request found in the events
+#[derive(Default)]
+struct EventPair<'a> {
+    lock: Option<(&'a InputRequest, &'a str, Option<Timestamp>)>,
+    mint: Option<(&'a Chains, &'a OutputResult, Option<&'a str>, &'a str)>,
+}
+
+/// Writes the requests of the events, matching the NewRequest and TokenMinted events of
+/// a request id across the chains. Requests the relayer already knows are never
+/// touched, a reconstructed orphan is only completed, so runs over overlapping ranges
+/// don't duplicate or regress records
+pub fn reconstruct_requests(
+    db: &Database,
+    events: &[HistoricalEvent],
+    now: Timestamp,
+) -> Result<BackfillReport> {
+    let mut pairs: BTreeMap<&RequestId, EventPair> = BTreeMap::new();
+    for event in events {
+        let pair = pairs.entry(event.request_id()).or_default();
+        match event {
+            HistoricalEvent::NewRequest {
+                input,
+                tx,
+                locked_at,
+                ..
+            } => pair.lock = Some((input, tx, *locked_at)),
+            HistoricalEvent::TokenMinted {
+                chain,
+                output,
+                destination_account,
+                tx,
+                ..
+            } => pair.mint = Some((chain, output, destination_account.as_deref(), tx)),
+        }
+    }
+
+    let mut report = BackfillReport::default();
+    for (request_id, pair) in pairs {
+        let Some((input, lock_tx, locked_at)) = pair.lock else {
+            report.unmatched += 1;
+            continue;
+        };
+        // A mint on the origin chain is not the mint of this request
+        let mint = pair
+            .mint
+            .filter(|(chain, ..)| **chain != input.origin_network);
+        if pair.mint.is_some() && mint.is_none() {
+            report.unmatched += 1;
+        }
+        match mint {
+            Some(_) => report.complete += 1,
+            None => report.partial += 1,
+        }
+
+        let existing = request_data(request_id, db)?;
+        let mut request = match existing {
+            None => reconstructed(request_id, input, lock_tx, locked_at, now),
+            Some(stored) if stored.reconstructed && stored.status == Status::NeedsDestination => {
+                if mint.is_none() {
+                    report.unchanged += 1;
+                    continue;
+                }
+                stored
+            }
+            Some(_) => {
+                report.unchanged += 1;
+                continue;
+            }
+        };
+
+        match mint {
+            Some((_, output, destination_account, mint_tx)) => {
+                if let Some(destination_account) = destination_account {
+                    request.input.destination_account = destination_account.to_string();
+                }
+                request.output = output.clone();
+                request.status = Status::Completed;
+                request.tx_hashes.push(mint_tx.to_string());
+                request.finalized_at = Some(now);
+                request.last_update = now;
+                request.history.push(HistoryEntry {
+                    time: now,
+                    event: format!("Reconstructed mint from transaction {mint_tx}"),
+                });
+                let mut batch = Batch::default();
+                stage_completed_request(&request.id, db, &mut batch, now)?;
+                request.save_with(db, batch)?;
+            }
+            None => request.save(db)?,
+        }
+        info!(
+            "Request {} reconstructed in status {:?}",
+            request.id, request.status
+        );
+        report.written += 1;
+    }
+    Ok(report)
+}
+
+/// Orphan of a lock found in the chain history, it waits for its owner to claim it as
+/// the orphans recorded from live events. Created at the time of the lock when known
+fn reconstructed(
+    request_id: &RequestId,
+    input: &InputRequest,
+    lock_tx: &str,
+    locked_at: Option<Timestamp>,
+    now: Timestamp,
+) -> BRequest {
+    let mut request = BRequest::new(input.clone(), now);
+    request.id = request_id.clone();
+    request.status = Status::NeedsDestination;
+    request.input.destination_account = String::new();
+    request.reconstructed = true;
+    request.trace_context = None;
+    request.created_at = locked_at.unwrap_or(now);
+    request.last_update = now;
+    request.tx_hashes = vec![lock_tx.to_string()];
+    request.history.push(HistoryEntry {
+        time: now,
+        event: format!(
+            "Reconstructed from the {:?} NewRequest transaction {lock_tx}",
+            input.origin_network
+        ),
+    });
+    request
+}
+
+#[cfg(test)]
+mod backfill_test {
+    use storage::testing::each_engine;
+
+    use crate::{
+        completed_requests, reconstruct_requests, request_data, BRequest, BackfillReport, Chains,
+        HistoricalEvent, InputRequest, OutputResult, RequestId, Status, Timestamp,
+    };
+
+    const NOW: Timestamp = Timestamp::from_secs(1_700_000_000);
+
+    fn request_id(token_id: &str) -> RequestId {
+        BRequest::generate_id("0xcontract", token_id, "0xowner")
+    }
+
+    fn lock(token_id: &str) -> HistoricalEvent {
+        HistoricalEvent::NewRequest {
+            request_id: request_id(token_id),
+            input: InputRequest {
+                contract_or_mint: "0xcontract".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: String::new(),
+            },
+            tx: format!("0xlock{token_id}"),
+            locked_at: None,
+        }
+    }
+
+    fn mint(token_id: &str, chain: Chains) -> HistoricalEvent {
+        HistoricalEvent::TokenMinted {
+            request_id: request_id(token_id),
+            chain,
+            output: OutputResult {
+                detination_token_id_or_account: format!("account{token_id}"),
+                detination_contract_id_or_mint: format!("mint{token_id}"),
+            },
+            destination_account: None,
+            tx: format!("mint-signature{token_id}"),
+        }
+    }
+
+    #[test]
+    fn test_matched_pairs_and_orphan_locks() {
+        for db in each_engine() {
+            let events = [
+                lock("1"),
+                mint("1", Chains::SOLANA),
+                lock("2"),
+                mint("3", Chains::SOLANA),
+                // Minted on the origin chain, not the mint of the request
+                lock("4"),
+                mint("4", Chains::EVM),
+            ];
+            let report = reconstruct_requests(&db, &events, NOW).unwrap();
+            assert_eq!(
+                report,
+                BackfillReport {
+                    complete: 1,
+                    partial: 2,
+                    unmatched: 2,
+                    written: 3,
+                    unchanged: 0,
+                    deferred: 0,
+                }
+            );
+
+            let complete = request_data(&request_id("1"), &db).unwrap().unwrap();
+            assert_eq!(complete.status, Status::Completed);
+            assert!(complete.reconstructed);
+            assert_eq!(complete.output.detination_contract_id_or_mint, "mint1");
+            assert_eq!(complete.tx_hashes, vec!["0xlock1", "mint-signature1"]);
+            assert_eq!(complete.finalized_at, Some(NOW));
+            assert_eq!(
+                completed_requests(&db),
+                Some(vec![request_id("1").to_string()])
+            );
+
+            let orphan = request_data(&request_id("2"), &db).unwrap().unwrap();
+            assert_eq!(orphan.status, Status::NeedsDestination);
+            assert_eq!(orphan.input.destination_account, "");
+            assert!(request_data(&request_id("3"), &db).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_reruns_dont_duplicate_or_regress() {
+        for db in each_engine() {
+            let first = [lock("1"), lock("2"), mint("2", Chains::SOLANA)];
+            reconstruct_requests(&db, &first, NOW).unwrap();
+
+            // Same range again
+            let report = reconstruct_requests(&db, &first, NOW).unwrap();
+            assert_eq!((report.complete, report.partial), (1, 1));
+            assert_eq!((report.written, report.unchanged), (0, 2));
+
+            // Overlapping range reaching the mint of the orphan, and only the lock of
+            // the completed request
+            let later = Timestamp::from_secs(1_700_000_600);
+            let overlap = [lock("1"), mint("1", Chains::SOLANA), lock("2")];
+            let report = reconstruct_requests(&db, &overlap, later).unwrap();
+            assert_eq!((report.complete, report.partial), (1, 1));
+            assert_eq!((report.written, report.unchanged), (1, 1));
+
+            for token_id in ["1", "2"] {
+                let request = request_data(&request_id(token_id), &db).unwrap().unwrap();
+                assert_eq!(request.status, Status::Completed, "{token_id}");
+                assert_eq!(request.tx_hashes.len(), 2);
+            }
+            assert_eq!(completed_requests(&db).unwrap().len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_lock_time_dates_the_request() {
+        for db in each_engine() {
+            let locked_at = Timestamp::from_secs(1_600_000_000);
+            let mut dated = lock("1");
+            if let HistoricalEvent::NewRequest { locked_at: at, .. } = &mut dated {
+                *at = Some(locked_at);
+            }
+            reconstruct_requests(&db, &[dated, lock("2")], NOW).unwrap();
+
+            let request = request_data(&request_id("1"), &db).unwrap().unwrap();
+            assert_eq!(request.created_at, locked_at);
+            assert_eq!(request.last_update, NOW);
+            let request = request_data(&request_id("2"), &db).unwrap().unwrap();
+            assert_eq!(request.created_at, NOW);
+        }
+    }
+
+    #[test]
+    fn test_known_requests_are_not_touched() {
+        for db in each_engine() {
+            let HistoricalEvent::NewRequest { input, .. } = lock("1") else {
+                unreachable!()
+            };
+            let mut live = BRequest::new(input, Timestamp::now());
+            live.status = Status::TokenMinted;
+            live.save(&db).unwrap();
+
+            let report =
+                reconstruct_requests(&db, &[lock("1"), mint("1", Chains::SOLANA)], NOW).unwrap();
+            assert_eq!(
+                (report.complete, report.written, report.unchanged),
+                (1, 0, 1)
+            );
+            let stored = request_data(&live.id, &db).unwrap().unwrap();
+            assert_eq!(stored.status, Status::TokenMinted);
+            assert!(!stored.reconstructed);
+        }
+    }
+}
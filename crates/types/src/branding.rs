@@ -0,0 +1,302 @@
+use serde::{Deserialize, Serialize};
+
+use crate::BRequest;
+
+/// Longest name of a Metaplex token metadata, in bytes
+pub const METAPLEX_MAX_NAME_LEN: usize = 32;
+/// Longest symbol of a Metaplex token metadata, in bytes
+pub const METAPLEX_MAX_SYMBOL_LEN: usize = 10;
+/// Query parameter with the request id appended to the minted token URIs
+pub const PROVENANCE_PARAM: &str = "bridge_request";
+
+/// Marks of the bridged assets: prefixes of the Solana token name and symbol, and the
+/// request id appended to the minted token URIs of both chains
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrandingConfig {
+    pub name_prefix: String,
+    pub symbol_prefix: String,
+    pub append_provenance: bool,
+    /// Bytes of the prefixed name, at most `METAPLEX_MAX_NAME_LEN`
+    pub max_name_len: usize,
+    /// Bytes of the prefixed symbol, at most `METAPLEX_MAX_SYMBOL_LEN`
+    pub max_symbol_len: usize,
+}
+
+/// Branding applied to the destination token of a request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AppliedBranding {
+    /// Name and symbol of the Solana mint, EVM tokens get theirs from the contract
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    /// The request id was appended to the token URI
+    pub provenance: bool,
+}
+
+/// Name, symbol and token URI of a Solana mint after branding
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrandedMint {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
+impl Default for BrandingConfig {
+    fn default() -> Self {
+        BrandingConfig {
+            name_prefix: String::new(),
+            symbol_prefix: String::new(),
+            append_provenance: false,
+            max_name_len: METAPLEX_MAX_NAME_LEN,
+            max_symbol_len: METAPLEX_MAX_SYMBOL_LEN,
+        }
+    }
+}
+
+/// `text` cut to `max` bytes on a char boundary
+fn truncate(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text[..end].to_string()
+}
+
+impl BrandingConfig {
+    /// Builds the branding from the optional deployment settings, the lengths are capped
+    /// at the Metaplex limits
+    pub fn from_config(
+        name_prefix: Option<&str>,
+        symbol_prefix: Option<&str>,
+        append_provenance: Option<bool>,
+        max_name_len: Option<usize>,
+        max_symbol_len: Option<usize>,
+    ) -> Self {
+        let default = BrandingConfig::default();
+        BrandingConfig {
+            name_prefix: name_prefix.unwrap_or_default().to_string(),
+            symbol_prefix: symbol_prefix.unwrap_or_default().to_string(),
+            append_provenance: append_provenance.unwrap_or(default.append_provenance),
+            max_name_len: max_name_len
+                .map_or(default.max_name_len, |len| len.min(METAPLEX_MAX_NAME_LEN)),
+            max_symbol_len: max_symbol_len.map_or(default.max_symbol_len, |len| {
+                len.min(METAPLEX_MAX_SYMBOL_LEN)
+            }),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.name_prefix.is_empty() || !self.symbol_prefix.is_empty() || self.append_provenance
+    }
+
+    /// `name` after the name prefix, cut to `max_name_len` bytes
+    pub fn name(&self, name: &str) -> String {
+        truncate(&format!("{}{name}", self.name_prefix), self.max_name_len)
+    }
+
+    /// `symbol` after the symbol prefix, cut to `max_symbol_len` bytes
+    pub fn symbol(&self, symbol: &str) -> String {
+        truncate(
+            &format!("{}{symbol}", self.symbol_prefix),
+            self.max_symbol_len,
+        )
+    }
+
+    /// `uri` with the request id in its query under `PROVENANCE_PARAM`, before its
+    /// fragment. data: URIs, URIs already carrying the parameter and URIs that would grow
+    /// past `max_uri_len` are kept as they are
+    pub fn uri(&self, uri: &str, request_id: &str, max_uri_len: usize) -> String {
+        if !self.append_provenance || uri.starts_with("data:") {
+            return uri.to_string();
+        }
+        let (base, fragment) = match uri.split_once('#') {
+            Some((base, fragment)) => (base, Some(fragment)),
+            None => (uri, None),
+        };
+        let param = format!("{PROVENANCE_PARAM}={request_id}");
+        let branded = match base.split_once('?') {
+            Some((_, query)) if query.split('&').any(|pair| pair.starts_with(&param)) => {
+                return uri.to_string();
+            }
+            Some((_, query)) if query.is_empty() || query.ends_with('&') => {
+                format!("{base}{param}")
+            }
+            Some(_) => format!("{base}&{param}"),
+            None => format!("{base}?{param}"),
+        };
+        let branded = match fragment {
+            Some(fragment) => format!("{branded}#{fragment}"),
+            None => branded,
+        };
+        if branded.len() > max_uri_len {
+            return uri.to_string();
+        }
+        branded
+    }
+
+    /// Name, symbol and URI of the Solana mint of `request`, the branding is recorded on
+    /// the request
+    pub fn brand_mint(
+        &self,
+        request: &mut BRequest,
+        name: &str,
+        symbol: &str,
+        uri: &str,
+        max_uri_len: usize,
+    ) -> BrandedMint {
+        let branded = BrandedMint {
+            name: self.name(name),
+            symbol: self.symbol(symbol),
+            uri: self.uri(uri, &request.id, max_uri_len),
+        };
+        if self.enabled() {
+            request.branding = Some(AppliedBranding {
+                name: Some(branded.name.clone()),
+                symbol: Some(branded.symbol.clone()),
+                provenance: branded.uri != uri,
+            });
+        }
+        branded
+    }
+
+    /// URI of the EVM mint of `request`, the branding is recorded on the request
+    pub fn brand_uri(&self, request: &mut BRequest, uri: &str, max_uri_len: usize) -> String {
+        let branded = self.uri(uri, &request.id, max_uri_len);
+        if self.enabled() {
+            request.branding = Some(AppliedBranding {
+                name: None,
+                symbol: None,
+                provenance: branded != uri,
+            });
+        }
+        branded
+    }
+}
+
+#[cfg(test)]
+mod branding_test {
+    use crate::{
+        AppliedBranding, BRequest, BrandingConfig, Chains, InputRequest, Timestamp,
+        METAPLEX_MAX_NAME_LEN,
+    };
+
+    const ID: &str = "0x2a";
+
+    fn branding() -> BrandingConfig {
+        BrandingConfig::from_config(Some("[Bridged] "), Some("b"), Some(true), None, None)
+    }
+
+    #[test]
+    fn test_prefixed_names_truncated() {
+        let branding = branding();
+        assert_eq!(branding.name("Bridged NFT"), "[Bridged] Bridged NFT");
+        assert_eq!(branding.symbol("BNFT"), "bBNFT");
+
+        let long = branding.name(&"A".repeat(40));
+        assert_eq!(long.len(), METAPLEX_MAX_NAME_LEN);
+        assert!(long.starts_with("[Bridged] AAA"));
+        // Cut on a char boundary
+        let name = branding.name(&"é".repeat(20));
+        assert!(name.len() <= METAPLEX_MAX_NAME_LEN);
+        assert_eq!(name, format!("[Bridged] {}", "é".repeat(11)));
+
+        // Configured lengths never go past the Metaplex limits
+        let capped = BrandingConfig::from_config(Some("x"), None, None, Some(64), Some(4));
+        assert_eq!(capped.max_name_len, METAPLEX_MAX_NAME_LEN);
+        assert_eq!(capped.symbol("BNFT"), "BNFT");
+        assert_eq!(BrandingConfig::default().name("Bridged NFT"), "Bridged NFT");
+    }
+
+    #[test]
+    fn test_provenance_appended_to_uris() {
+        let branding = branding();
+        assert_eq!(
+            branding.uri("https://example.com/1.json", ID, 2048),
+            "https://example.com/1.json?bridge_request=0x2a"
+        );
+        assert_eq!(
+            branding.uri("https://example.com/1.json?v=2", ID, 2048),
+            "https://example.com/1.json?v=2&bridge_request=0x2a"
+        );
+        assert_eq!(
+            branding.uri("https://example.com/1.json?", ID, 2048),
+            "https://example.com/1.json?bridge_request=0x2a"
+        );
+        assert_eq!(
+            branding.uri("https://example.com/1.json?v=2#top", ID, 2048),
+            "https://example.com/1.json?v=2&bridge_request=0x2a#top"
+        );
+        // Already appended by an earlier attempt
+        let branded = branding.uri("ipfs://cid/1.json", ID, 2048);
+        assert_eq!(branding.uri(&branded, ID, 2048), branded);
+        // Kept as they are
+        assert_eq!(
+            branding.uri("data:application/json,{}", ID, 2048),
+            "data:application/json,{}"
+        );
+        assert_eq!(
+            branding.uri("https://example.com/1.json", ID, 30),
+            "https://example.com/1.json"
+        );
+        assert_eq!(
+            BrandingConfig::default().uri("https://example.com/1.json", ID, 2048),
+            "https://example.com/1.json"
+        );
+    }
+
+    #[test]
+    fn test_branding_recorded_on_request() {
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xcontract".to_string(),
+                token_id: "1".to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        let branded = branding().brand_mint(
+            &mut request,
+            "Bridged NFT",
+            "BNFT",
+            "ipfs://cid/1.json",
+            2048,
+        );
+        assert_eq!(
+            branded.uri,
+            format!("ipfs://cid/1.json?bridge_request={}", request.id)
+        );
+        assert_eq!(
+            request.branding,
+            Some(AppliedBranding {
+                name: Some("[Bridged] Bridged NFT".to_string()),
+                symbol: Some("bBNFT".to_string()),
+                provenance: true,
+            })
+        );
+
+        let mut request = request.clone();
+        request.branding = None;
+        let uri = branding().brand_uri(&mut request, "data:application/json,{}", 2048);
+        assert_eq!(uri, "data:application/json,{}");
+        assert_eq!(
+            request.branding.unwrap(),
+            AppliedBranding {
+                name: None,
+                symbol: None,
+                provenance: false,
+            }
+        );
+
+        // Nothing recorded without branding
+        let mut request = request.clone();
+        request.branding = None;
+        BrandingConfig::default().brand_uri(&mut request, "ipfs://cid/1.json", 2048);
+        assert_eq!(request.branding, None);
+    }
+}
@@ -0,0 +1,206 @@
+use std::{iter, time::Duration};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use storage::{db::Database, keys::BRIDGE_DEPLOYMENT};
+
+use crate::{BRequest, Chains, Result, Timestamp};
+
+/// Time the events of the replaced bridge addresses are still listened to by default
+pub const DEFAULT_BRIDGE_TRANSITION: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Bridge contract (EVM) and program accounts (Solana) a request is processed against
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BridgeAddresses {
+    pub evm_contract: String,
+    pub solana_program: String,
+    pub solana_account: String,
+}
+
+impl BridgeAddresses {
+    /// Contract or program the transactions of `chain` call
+    pub fn bridge_of(&self, chain: &Chains) -> &str {
+        match chain {
+            Chains::EVM => &self.evm_contract,
+            Chains::SOLANA => &self.solana_program,
+        }
+    }
+}
+
+/// Addresses replaced by an upgrade
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RetiredBridge {
+    pub addresses: BridgeAddresses,
+    pub retired_at: Timestamp,
+    /// Their events are listened to until then
+    pub listen_until: Timestamp,
+}
+
+/// Bridge addresses given to the new requests and the ones they replaced, persisted
+/// across restarts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BridgeDeployment {
+    pub current: BridgeAddresses,
+    /// Latest retired first
+    #[serde(default)]
+    pub retired: Vec<RetiredBridge>,
+    /// First addresses the relayer ran with, the requests saved before the addresses were
+    /// recorded on them are processed against them
+    pub legacy: BridgeAddresses,
+}
+
+impl BridgeDeployment {
+    pub fn new(current: BridgeAddresses) -> Self {
+        BridgeDeployment {
+            legacy: current.clone(),
+            current,
+            retired: vec![],
+        }
+    }
+
+    /// Gives `addresses` to the new requests, the current ones are listened to for
+    /// `transition` more. False when they are already the current ones
+    pub fn upgrade(
+        &mut self,
+        addresses: BridgeAddresses,
+        now: Timestamp,
+        transition: Duration,
+    ) -> bool {
+        if addresses == self.current {
+            return false;
+        }
+        // Going back to retired addresses makes them current again
+        self.retired
+            .retain(|retired| retired.addresses != addresses);
+        let replaced = std::mem::replace(&mut self.current, addresses);
+        self.retired.insert(
+            0,
+            RetiredBridge {
+                addresses: replaced,
+                retired_at: now,
+                listen_until: now.saturating_add(transition),
+            },
+        );
+        true
+    }
+
+    /// Addresses `request` is processed against
+    pub fn of_request<'a>(&'a self, request: &'a BRequest) -> &'a BridgeAddresses {
+        request.bridge_addresses.as_ref().unwrap_or(&self.legacy)
+    }
+
+    /// Addresses whose events are listened to at `now`, the current ones first
+    pub fn listened(&self, now: Timestamp) -> Vec<&BridgeAddresses> {
+        iter::once(&self.current)
+            .chain(
+                self.retired
+                    .iter()
+                    .filter(|retired| now < retired.listen_until)
+                    .map(|retired| &retired.addresses),
+            )
+            .collect()
+    }
+
+    /// Addresses the relayer ran with that `matches`, the latest first
+    pub fn find(&self, matches: impl Fn(&BridgeAddresses) -> bool) -> Option<&BridgeAddresses> {
+        iter::once(&self.current)
+            .chain(self.retired.iter().map(|retired| &retired.addresses))
+            .chain(iter::once(&self.legacy))
+            .find(|addresses| matches(addresses))
+    }
+}
+
+/// Deployment of a relayer configured with `configured`: the stored one upgraded to them
+/// when they differ, a new one on the first start
+pub fn load_bridge_deployment(
+    db: &Database,
+    configured: BridgeAddresses,
+    now: Timestamp,
+    transition: Duration,
+) -> Result<BridgeDeployment> {
+    let Some(mut deployment) = db.read::<_, BridgeDeployment>(BRIDGE_DEPLOYMENT)? else {
+        let deployment = BridgeDeployment::new(configured);
+        db.write_value(BRIDGE_DEPLOYMENT, &deployment)?;
+        return Ok(deployment);
+    };
+    if deployment.upgrade(configured, now, transition) {
+        info!(
+            "Bridge addresses upgraded to {:?}, the requests created before keep {:?}",
+            deployment.current, deployment.retired[0].addresses
+        );
+        db.write_value(BRIDGE_DEPLOYMENT, &deployment)?;
+    }
+    Ok(deployment)
+}
+
+#[cfg(test)]
+mod bridge_deployment_test {
+    use std::time::Duration;
+
+    use storage::testing::each_engine;
+
+    use crate::{
+        load_bridge_deployment, BRequest, BridgeAddresses, Chains, InputRequest, Timestamp,
+    };
+
+    const NOW: Timestamp = Timestamp::from_secs(1_700_000_000);
+    const TRANSITION: Duration = Duration::from_secs(3600);
+
+    fn addresses(name: &str) -> BridgeAddresses {
+        BridgeAddresses {
+            evm_contract: format!("0x{name}"),
+            solana_program: format!("program {name}"),
+            solana_account: format!("account {name}"),
+        }
+    }
+
+    #[test]
+    fn test_upgrade_keeps_the_previous_addresses() {
+        for db in each_engine() {
+            let first = load_bridge_deployment(&db, addresses("a"), NOW, TRANSITION).unwrap();
+            assert_eq!(first.current, addresses("a"));
+            assert_eq!(first.legacy, addresses("a"));
+            let restarted = load_bridge_deployment(&db, addresses("a"), NOW, TRANSITION).unwrap();
+            assert_eq!(restarted, first);
+
+            let upgraded = load_bridge_deployment(&db, addresses("b"), NOW, TRANSITION).unwrap();
+            assert_eq!(upgraded.current, addresses("b"));
+            assert_eq!(upgraded.retired[0].addresses, addresses("a"));
+            assert_eq!(
+                upgraded.listened(NOW),
+                vec![&addresses("b"), &addresses("a")]
+            );
+            assert_eq!(
+                upgraded.listened(NOW.saturating_add(TRANSITION)),
+                vec![&addresses("b")]
+            );
+
+            // Requests keep the addresses they were created with, the older ones get the
+            // first addresses
+            let mut request = BRequest::new(
+                InputRequest {
+                    contract_or_mint: "0xcontract".to_string(),
+                    token_id: "1".to_string(),
+                    token_owner: "0xowner".to_string(),
+                    origin_network: Chains::EVM,
+                    destination_account: "destination".to_string(),
+                },
+                Timestamp::now(),
+            );
+            assert_eq!(upgraded.of_request(&request), &addresses("a"));
+            request.bridge_addresses = Some(addresses("b"));
+            assert_eq!(upgraded.of_request(&request), &addresses("b"));
+            assert_eq!(
+                upgraded.find(|found| found.evm_contract == "0xa"),
+                Some(&addresses("a"))
+            );
+
+            // Going back makes the retired addresses current again
+            let rolled_back = load_bridge_deployment(&db, addresses("a"), NOW, TRANSITION).unwrap();
+            assert_eq!(rolled_back.current, addresses("a"));
+            assert_eq!(rolled_back.retired.len(), 1);
+            assert_eq!(rolled_back.retired[0].addresses, addresses("b"));
+            assert_eq!(rolled_back.legacy, addresses("a"));
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::error::Error;
+
+use storage::errors::DbError;
+use tokio::sync::mpsc::error::SendError;
+
+use crate::{CallContext, TimeoutError, TxMessage};
+
+/// Result of the relayer crates, failing with a `BridgeError` unless another error is named
+pub type Result<T, E = BridgeError> = std::result::Result<T, E>;
+
+/// Failure of a bridge operation typed for the callers deciding on it. The chain crates
+/// convert their errors into it, errors of untyped sources keep their message in `Other`
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum BridgeError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error(transparent)]
+    Timeout(#[from] TimeoutError),
+
+    /// The request data can't be parsed, the request fails on every retry
+    #[error("Corrupted request data: {0}")]
+    CorruptedData(String),
+
+    /// The account a transaction creates already exists, an earlier attempt created it
+    #[error("Account {0} already initialized")]
+    AccountAlreadyInitialized(String),
+
+    #[error("Relayer is not the authorized backend: {0}")]
+    NotAuthorizedBackend(String),
+
+    #[error("Gas limit exceeded: {0}")]
+    GasLimitExceeded(String),
+
+    /// Transaction not sent, a required signer is not the relayer wallet
+    #[error("Missing signer {0}")]
+    MissingSigner(String),
+
+    /// Failed chain call, the request and the operation it was made for around its error
+    #[error("{context}: {source}")]
+    Call {
+        context: CallContext,
+        source: Box<BridgeError>,
+    },
+
+    #[error("{0}")]
+    Other(String),
+}
+
+impl BridgeError {
+    /// Error of a source returning any error type. The relayer errors keep their type,
+    /// the others are kept as their message followed by the ones of their sources
+    pub fn from_error<E: Error + Send + Sync + 'static>(err: E) -> Self {
+        let err: Box<dyn Error + Send + Sync> = Box::new(err);
+        let err = match err.downcast::<BridgeError>() {
+            Ok(err) => return *err,
+            Err(err) => err,
+        };
+        let err = match err.downcast::<DbError>() {
+            Ok(err) => return BridgeError::Db(*err),
+            Err(err) => err,
+        };
+        match err.downcast::<TimeoutError>() {
+            Ok(err) => BridgeError::Timeout(*err),
+            Err(err) => BridgeError::Other(message_chain(err.as_ref())),
+        }
+    }
+
+    /// The error under the call contexts wrapping it
+    pub fn root(&self) -> &BridgeError {
+        match self {
+            BridgeError::Call { source, .. } => source.root(),
+            err => err,
+        }
+    }
+
+    /// The request data can't be parsed, the request will fail on every retry
+    pub fn is_corrupted_data(&self) -> bool {
+        matches!(self.root(), BridgeError::CorruptedData(_))
+    }
+}
+
+impl From<serde_json::Error> for BridgeError {
+    fn from(err: serde_json::Error) -> Self {
+        BridgeError::Other(err.to_string())
+    }
+}
+
+impl From<SendError<TxMessage>> for BridgeError {
+    fn from(err: SendError<TxMessage>) -> Self {
+        BridgeError::Other(err.to_string())
+    }
+}
+
+impl From<reqwest::Error> for BridgeError {
+    fn from(err: reqwest::Error) -> Self {
+        BridgeError::Other(message_chain(&err))
+    }
+}
+
+/// Message of `err` followed by the ones of its sources
+fn message_chain(err: &(dyn Error + 'static)) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        let next = err.to_string();
+        // Transparent wrappers repeat the message of their source
+        if !message.ends_with(&next) {
+            message.push_str(": ");
+            message.push_str(&next);
+        }
+        source = err.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod bridge_error_test {
+    use std::{io, time::Duration};
+
+    use storage::errors::DbError;
+
+    use crate::{BridgeError, CallContext, Chains, TimeoutError};
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("request failed")]
+    struct Untyped(#[source] io::Error);
+
+    #[test]
+    fn test_typed_sources_keep_their_variant() {
+        let timeout = TimeoutError {
+            operation: "get_slot".to_string(),
+            after: Duration::from_secs(1),
+        };
+        assert_eq!(
+            BridgeError::from_error(timeout.clone()),
+            BridgeError::Timeout(timeout)
+        );
+        let corrupted = BridgeError::CorruptedData("signature".to_string());
+        assert_eq!(BridgeError::from_error(corrupted.clone()), corrupted);
+        assert!(matches!(
+            BridgeError::from_error(DbError::ReadOnly),
+            BridgeError::Db(_)
+        ));
+
+        let untyped = Untyped(io::Error::other("connection refused"));
+        assert_eq!(
+            BridgeError::from_error(untyped),
+            BridgeError::Other("request failed: connection refused".to_string())
+        );
+    }
+
+    #[test]
+    fn test_call_context_wraps_the_root_error() {
+        let context = CallContext::new(Chains::SOLANA, "send_transaction").request("0xrequest");
+        let err = BridgeError::Call {
+            context: context.clone(),
+            source: Box::new(BridgeError::AccountAlreadyInitialized("mint".to_string())),
+        };
+        assert_eq!(
+            err.root(),
+            &BridgeError::AccountAlreadyInitialized("mint".to_string())
+        );
+        assert_eq!(CallContext::of(&err), Some(&context));
+        assert_eq!(
+            err.to_string(),
+            "SOLANA send_transaction failed for request 0xrequest: Account mint already initialized"
+        );
+        assert!(!err.is_corrupted_data());
+    }
+}
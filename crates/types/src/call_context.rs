@@ -0,0 +1,136 @@
+use std::{error::Error, fmt};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+use storage::db::Database;
+
+use crate::{private_text, request_data, BridgeError, Chains, ErrorComponent, Result, Timestamp};
+
+/// Chain call an error comes from, attached to the error chain so the logs and the
+/// stored `last_error` identify the request and the operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CallContext {
+    pub chain: Chains,
+    pub operation: String,
+    pub request_id: Option<String>,
+    /// Contract or program called
+    pub contract: Option<String>,
+}
+
+impl CallContext {
+    pub fn new(chain: Chains, operation: &str) -> Self {
+        CallContext {
+            chain,
+            operation: operation.to_string(),
+            request_id: None,
+            contract: None,
+        }
+    }
+
+    pub fn request(mut self, request_id: &str) -> Self {
+        self.request_id = Some(request_id.to_string());
+        self
+    }
+
+    pub fn contract(mut self, contract: impl ToString) -> Self {
+        self.contract = Some(contract.to_string());
+        self
+    }
+
+    /// Outermost call context in the chain of the error
+    pub fn of(err: &BridgeError) -> Option<&CallContext> {
+        match err {
+            BridgeError::Call { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CallContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {} failed", self.chain, self.operation)?;
+        if let Some(request_id) = &self.request_id {
+            write!(f, " for request {request_id}")?;
+        }
+        if let Some(contract) = &self.contract {
+            write!(f, " on {contract}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Attaches a `CallContext` to the error of a chain call
+pub trait WrapCallContext<T> {
+    fn with_call_context<F: FnOnce() -> CallContext>(self, context: F) -> Result<T>;
+}
+
+impl<T, E: Error + Send + Sync + 'static> WrapCallContext<T> for Result<T, E> {
+    fn with_call_context<F: FnOnce() -> CallContext>(self, context: F) -> Result<T> {
+        self.map_err(|err| BridgeError::Call {
+            context: context(),
+            source: Box::new(BridgeError::from_error(err)),
+        })
+    }
+}
+
+/// Logs the full error chain of a failed request operation and keeps it in the request
+pub fn record_request_error(
+    db: &Database,
+    request_id: &str,
+    component: ErrorComponent,
+    err: &BridgeError,
+    now: Timestamp,
+) {
+    error!(
+        "Request {request_id} failed: {}",
+        private_text(&err.to_string())
+    );
+    if let Ok(Some(mut request)) = request_data(request_id, db) {
+        if let Err(e) = request.record_error(component, err, db, now) {
+            error!("Could not record the error of request {request_id}: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod call_context_test {
+    use std::time::Duration;
+
+    use crate::{with_timeout, BridgeError, CallContext, Chains, WrapCallContext};
+
+    fn context() -> CallContext {
+        CallContext::new(Chains::EVM, "eth_call")
+            .request("0xrequest")
+            .contract("0xbridge")
+    }
+
+    #[tokio::test]
+    async fn test_failing_call_names_request_and_operation() {
+        let failing = async {
+            Err::<(), _>(BridgeError::Other(
+                "server returned an error response".to_string(),
+            ))
+        };
+        let err = with_timeout("eth_call", Duration::from_secs(1), failing)
+            .await
+            .with_call_context(context)
+            .unwrap_err();
+
+        let formatted = err.to_string();
+        assert!(formatted.contains("0xrequest"), "{formatted}");
+        assert!(formatted.contains("eth_call"), "{formatted}");
+        assert!(formatted.contains("server returned an error response"));
+        assert_eq!(CallContext::of(&err), Some(&context()));
+
+        // The typed errors are still found under the context
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok::<(), BridgeError>(())
+        };
+        let err = with_timeout("send_transaction", Duration::from_millis(1), slow)
+            .await
+            .with_call_context(context)
+            .unwrap_err();
+        assert!(matches!(err.root(), BridgeError::Timeout(_)));
+    }
+}
@@ -0,0 +1,566 @@
+use std::time::Duration;
+
+use alloy::primitives::hex;
+use hmac::{Hmac, Mac};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use storage::{
+    db::{Batch, Database},
+    keys::CALLBACK_PREFIX,
+};
+
+use crate::{
+    debug_detail, is_private_hostname, private_text, request_data, BRequest, BridgeError,
+    CancelReason, FailureReport, HistoryEntry, OutputResult, RequestId, Result, Secret, Status,
+    Timestamp,
+};
+
+pub const MAX_CALLBACK_URL_LENGTH: usize = 2048;
+/// Time allowed to each callback POST
+pub const CALLBACK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Failed deliveries after which the callback is dead-lettered
+pub const CALLBACK_MAX_ATTEMPTS: u32 = 8;
+/// Wait before the first retry of a callback, doubled on every failed attempt
+pub const CALLBACK_RETRY_BASE: Duration = Duration::from_secs(30);
+pub const CALLBACK_RETRY_MAX: Duration = Duration::from_secs(3600);
+/// Header with the hex HMAC-SHA256 of the body, `sha256=<hex>`
+pub const CALLBACK_SIGNATURE_HEADER: &str = "X-Bridge-Signature";
+
+/// Deployment rules of the per-request callbacks
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallbackPolicy {
+    /// Requests with a callback URL are rejected when disabled
+    pub enabled: bool,
+    /// Hosts callbacks can be sent to, any public host when empty
+    pub allowed_hosts: Vec<String>,
+    /// Key of the body signature, unsigned when not set
+    pub signing_secret: Option<Secret<String>>,
+}
+
+impl Default for CallbackPolicy {
+    fn default() -> Self {
+        CallbackPolicy {
+            enabled: true,
+            allowed_hosts: vec![],
+            signing_secret: None,
+        }
+    }
+}
+
+impl CallbackPolicy {
+    /// Builds the policy from the deployment settings, `allowed_hosts` is comma separated
+    pub fn from_config(
+        enabled: bool,
+        allowed_hosts: Option<&str>,
+        signing_secret: Option<Secret<String>>,
+    ) -> Self {
+        CallbackPolicy {
+            enabled,
+            allowed_hosts: allowed_hosts
+                .map(|hosts| {
+                    hosts
+                        .split(',')
+                        .map(|host| host.trim().to_lowercase())
+                        .filter(|host| !host.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            signing_secret,
+        }
+    }
+
+    fn allows_host(&self, host: &str) -> bool {
+        self.allowed_hosts.is_empty() || self.allowed_hosts.iter().any(|allowed| allowed == host)
+    }
+
+    /// Checks the callback URL of a new request, returns the URL to store
+    pub fn validate(&self, url: &str) -> Result<String, String> {
+        if !self.enabled {
+            return Err("Per-request callbacks are disabled".to_string());
+        }
+        let url = url.trim();
+        if url.len() > MAX_CALLBACK_URL_LENGTH {
+            return Err(format!(
+                "Callback URL of {} bytes exceeds the maximum of {MAX_CALLBACK_URL_LENGTH}",
+                url.len()
+            ));
+        }
+        let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid callback URL: {e}"))?;
+        if parsed.scheme() != "https" {
+            return Err("Callback URL must be https".to_string());
+        }
+        let host = callback_host(&parsed);
+        if is_private_hostname(&host) {
+            return Err("Callback URL points to a private network".to_string());
+        }
+        if !self.allows_host(&host) {
+            return Err(format!("Callback host {host} not allowed"));
+        }
+        Ok(url.to_string())
+    }
+}
+
+fn callback_host(url: &reqwest::Url) -> String {
+    url.host_str()
+        .unwrap_or_default()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_lowercase()
+}
+
+/// Callback of a request and the state of its delivery, sent once the request reaches a
+/// terminal status
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CallbackDelivery {
+    pub url: String,
+    /// Failed deliveries
+    pub attempts: u32,
+    /// Retry of a failed delivery, None until the first attempt
+    pub next_attempt_at: Option<Timestamp>,
+    pub delivered_at: Option<Timestamp>,
+    /// No more attempts are made
+    pub dead_lettered: bool,
+    pub last_error: Option<String>,
+}
+
+impl CallbackDelivery {
+    pub fn new(url: String) -> Self {
+        CallbackDelivery {
+            url,
+            attempts: 0,
+            next_attempt_at: None,
+            delivered_at: None,
+            dead_lettered: false,
+            last_error: None,
+        }
+    }
+
+    fn pending(&self) -> bool {
+        self.delivered_at.is_none() && !self.dead_lettered
+    }
+}
+
+/// Body POSTed to the callback URL
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CallbackPayload {
+    pub request_id: RequestId,
+    pub status: Status,
+    pub cancel_reason: Option<CancelReason>,
+    pub output: OutputResult,
+    pub tx_hashes: Vec<String>,
+    pub finalized_at: Option<Timestamp>,
+    /// Attempts and suggested actions of a request given up after its last retry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_report: Option<FailureReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_reference: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl From<&BRequest> for CallbackPayload {
+    fn from(request: &BRequest) -> Self {
+        CallbackPayload {
+            request_id: request.id.clone(),
+            status: request.status.clone(),
+            cancel_reason: request.cancel_reason.clone(),
+            output: request.output.clone(),
+            tx_hashes: request.tx_hashes.clone(),
+            finalized_at: request.finalized_at,
+            failure_report: request.failure_report.clone(),
+            client_reference: request.client_reference.clone(),
+            tags: request.tags.clone(),
+        }
+    }
+}
+
+/// Wait after the `attempts` failed delivery, capped to `CALLBACK_RETRY_MAX`
+pub fn callback_retry_delay(attempts: u32) -> Duration {
+    let doublings = attempts.saturating_sub(1).min(16);
+    CALLBACK_RETRY_BASE
+        .saturating_mul(1 << doublings)
+        .min(CALLBACK_RETRY_MAX)
+}
+
+/// Hex HMAC-SHA256 of `body`
+pub fn callback_signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key size");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn callback_key(request_id: &str) -> String {
+    format!("{CALLBACK_PREFIX}{request_id}")
+}
+
+/// Adds the callback index writes of `request` to the batch saving it. Requests in a
+/// terminal status with an undelivered callback are indexed by their next attempt
+pub(crate) fn update_callback_index(request: &BRequest, batch: &mut Batch) -> Result<()> {
+    let key = callback_key(&request.id);
+    let terminal = matches!(
+        request.status,
+        Status::Completed | Status::Canceled | Status::Refunded
+    );
+    match &request.callback {
+        Some(callback) if terminal && callback.pending() => {
+            batch.put(key, &callback.next_attempt_at.unwrap_or_default())?;
+        }
+        _ => batch.delete(key),
+    }
+    Ok(())
+}
+
+/// Ids of the requests whose callback delivery is due at `now`
+pub fn due_callbacks(db: &Database, now: Timestamp) -> Result<Vec<String>> {
+    let due = db
+        .scan_prefix::<Timestamp>(CALLBACK_PREFIX, None)?
+        .into_iter()
+        .filter(|(_, next_attempt_at)| *next_attempt_at <= now)
+        .map(|(key, _)| key[CALLBACK_PREFIX.len()..].to_string())
+        .collect();
+    Ok(due)
+}
+
+/// Sends the callbacks of finished requests
+#[derive(Debug, Clone)]
+pub struct CallbackSender {
+    http: reqwest::Client,
+    pub policy: CallbackPolicy,
+}
+
+impl CallbackSender {
+    pub fn new(policy: CallbackPolicy) -> Result<Self> {
+        Ok(CallbackSender {
+            http: reqwest::Client::builder()
+                .timeout(CALLBACK_TIMEOUT)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()?,
+            policy,
+        })
+    }
+
+    /// Attempts every due callback, returns the number delivered
+    pub async fn deliver_due(&self, db: &Database, now: Timestamp) -> Result<usize> {
+        let mut delivered = 0;
+        for id in due_callbacks(db, now)? {
+            let Some(mut request) = request_data(&id, db)? else {
+                continue;
+            };
+            if self.deliver(db, &mut request, now).await? {
+                delivered += 1;
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// Sends the callback of the request recording the outcome on it, returns whether it
+    /// was delivered
+    pub async fn deliver(
+        &self,
+        db: &Database,
+        request: &mut BRequest,
+        now: Timestamp,
+    ) -> Result<bool> {
+        let Some(mut callback) = request.callback.clone().filter(CallbackDelivery::pending) else {
+            return Ok(false);
+        };
+
+        // Only the outcomes ending the delivery go to the history, retries stay in `callback`
+        let mut event = None;
+        match self.post(&callback.url, request).await {
+            Ok(()) => {
+                info!("Callback of request {} delivered", request.id);
+                callback.delivered_at = Some(now);
+                callback.next_attempt_at = None;
+                event = Some("Callback delivered".to_string());
+            }
+            Err(e) => {
+                callback.attempts += 1;
+                callback.last_error = Some(e.to_string());
+                if callback.attempts >= CALLBACK_MAX_ATTEMPTS || !self.allowed(&callback.url) {
+                    error!(
+                        "Callback of request {} dead-lettered: {}",
+                        request.id,
+                        private_text(&e.to_string())
+                    );
+                    callback.dead_lettered = true;
+                    callback.next_attempt_at = None;
+                    event = Some(format!(
+                        "Callback dead-lettered after {} attempts: {e}",
+                        callback.attempts
+                    ));
+                } else {
+                    let retry_at = now.saturating_add(callback_retry_delay(callback.attempts));
+                    error!(
+                        "Callback of request {} failed, attempt {}, retrying at {}: {}",
+                        request.id,
+                        callback.attempts,
+                        retry_at.as_secs(),
+                        private_text(&e.to_string())
+                    );
+                    callback.next_attempt_at = Some(retry_at);
+                }
+            }
+        }
+
+        let delivered = callback.delivered_at.is_some();
+        request.callback = Some(callback);
+        if let Some(event) = event {
+            request.history.push(HistoryEntry { time: now, event });
+        }
+        request.save(db)?;
+        Ok(delivered)
+    }
+
+    /// Whether callbacks are enabled and the host of `url` is allowed. Checked again on
+    /// delivery, the settings may have changed since the request was created
+    fn allowed(&self, url: &str) -> bool {
+        self.policy.enabled
+            && reqwest::Url::parse(url)
+                .is_ok_and(|url| self.policy.allows_host(&callback_host(&url)))
+    }
+
+    async fn post(&self, url: &str, request: &BRequest) -> Result<()> {
+        if !self.allowed(url) {
+            return Err(BridgeError::Other(format!(
+                "Callbacks to {url} are not allowed"
+            )));
+        }
+        let payload = CallbackPayload::from(request);
+        if let Some(detail) = debug_detail(&payload) {
+            debug!("Callback payload of request {}: {detail}", request.id);
+        }
+        let body = serde_json::to_vec(&payload)?;
+        let mut post = self
+            .http
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = &self.policy.signing_secret {
+            post = post.header(
+                CALLBACK_SIGNATURE_HEADER,
+                format!("sha256={}", callback_signature(secret.expose(), &body)),
+            );
+        }
+        post.body(body).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod callback_test {
+    use std::time::Duration;
+
+    use storage::{db::Database, testing::each_engine};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    use crate::{
+        callback_signature, due_callbacks, request_data, BRequest, CallbackDelivery,
+        CallbackPayload, CallbackPolicy, CallbackSender, Chains, InputRequest, Status, Timestamp,
+        CALLBACK_MAX_ATTEMPTS, MAX_CALLBACK_URL_LENGTH,
+    };
+
+    fn policy(allowed_hosts: Option<&str>) -> CallbackPolicy {
+        CallbackPolicy::from_config(true, allowed_hosts, Some("secret".into()))
+    }
+
+    fn completed_request(db: &Database, url: &str) -> BRequest {
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0x5fbdb2315678afecb367f032d93f642f64180aa3".to_string(),
+                token_id: "1".to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.callback = Some(CallbackDelivery::new(url.to_string()));
+        request.client_reference = Some("order-1".to_string());
+        request.tags = vec!["vip".to_string()];
+        request.save(db).unwrap();
+        request.status = Status::Completed;
+        request.save(db).unwrap();
+        request
+    }
+
+    /// Answers each connection with the next status, returns the raw requests received
+    async fn mock_server(statuses: Vec<u16>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hooks/job-1", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut received = vec![];
+            for status in statuses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buffer = [0; 4096];
+                loop {
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|line| {
+                                line.to_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|length| length.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if body.len() >= length {
+                            break;
+                        }
+                    }
+                    if read == 0 {
+                        break;
+                    }
+                }
+                socket
+                    .write_all(
+                        format!("HTTP/1.1 {status} OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                            .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+                received.push(String::from_utf8_lossy(&request).to_string());
+            }
+            received
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn test_callback_url_validation() {
+        let policy = policy(None);
+        assert_eq!(
+            policy.validate(" https://hooks.example.com/job/1 "),
+            Ok("https://hooks.example.com/job/1".to_string())
+        );
+        for invalid in [
+            "http://hooks.example.com/job/1",
+            "not a url",
+            "https://localhost/job",
+            "https://127.0.0.1/job",
+            "https://10.0.0.8/job",
+            "https://[::1]/job",
+            "https://user@192.168.1.1/job",
+        ] {
+            assert!(policy.validate(invalid).is_err(), "{invalid}");
+        }
+        let long = format!(
+            "https://hooks.example.com/{}",
+            "a".repeat(MAX_CALLBACK_URL_LENGTH)
+        );
+        assert!(policy.validate(&long).unwrap_err().contains("exceeds"));
+
+        let disabled = CallbackPolicy::from_config(false, None, None);
+        assert!(disabled
+            .validate("https://hooks.example.com/job/1")
+            .is_err());
+    }
+
+    #[test]
+    fn test_allowlist_rejects_other_hosts() {
+        let policy = policy(Some("hooks.example.com, Partner.io"));
+        assert!(policy.validate("https://hooks.example.com/job").is_ok());
+        assert!(policy.validate("https://partner.io/job").is_ok());
+        assert_eq!(
+            policy.validate("https://evil.example.com/job"),
+            Err("Callback host evil.example.com not allowed".to_string())
+        );
+        assert!(policy
+            .validate("https://hooks.example.com.evil.io/job")
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delivery_to_mock_server_with_retry() {
+        for db in each_engine() {
+            let (url, server) = mock_server(vec![503, 200]).await;
+            let request = completed_request(&db, &url);
+            let sender = CallbackSender::new(policy(None)).unwrap();
+            let now = Timestamp::from_secs(1_700_000_000);
+
+            assert_eq!(
+                due_callbacks(&db, now).unwrap(),
+                vec![request.id.to_string()]
+            );
+            assert_eq!(sender.deliver_due(&db, now).await.unwrap(), 0);
+            let stored = request_data(&request.id, &db).unwrap().unwrap();
+            let callback = stored.callback.unwrap();
+            assert_eq!(callback.attempts, 1);
+            let retry_at = now.saturating_add(Duration::from_secs(30));
+            assert_eq!(callback.next_attempt_at, Some(retry_at));
+            assert!(due_callbacks(&db, now).unwrap().is_empty());
+
+            assert_eq!(sender.deliver_due(&db, retry_at).await.unwrap(), 1);
+            let stored = request_data(&request.id, &db).unwrap().unwrap();
+            assert_eq!(stored.callback.unwrap().delivered_at, Some(retry_at));
+            assert_eq!(stored.history.last().unwrap().event, "Callback delivered");
+            assert!(due_callbacks(&db, retry_at).unwrap().is_empty());
+
+            let received = server.await.unwrap();
+            let (head, body) = received[1].split_once("\r\n\r\n").unwrap();
+            assert!(head.starts_with("POST /hooks/job-1 "));
+            let signature = format!("sha256={}", callback_signature("secret", body.as_bytes()));
+            assert!(head
+                .to_lowercase()
+                .contains(&format!("x-bridge-signature: {signature}")));
+            let payload: CallbackPayload = serde_json::from_str(body).unwrap();
+            assert_eq!(payload.request_id, request.id);
+            assert_eq!(payload.status, Status::Completed);
+            assert_eq!(payload.client_reference.as_deref(), Some("order-1"));
+            assert_eq!(payload.tags, vec!["vip".to_string()]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_after_max_attempts() {
+        for db in each_engine() {
+            // Nothing listens on the port once the listener is dropped
+            let url = {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                format!("http://{}/hook", listener.local_addr().unwrap())
+            };
+            let request = completed_request(&db, &url);
+            let sender = CallbackSender::new(policy(None)).unwrap();
+
+            let now =
+                Timestamp::from_secs(1_700_000_000).saturating_add(Duration::from_secs(86_400));
+            for _ in 0..CALLBACK_MAX_ATTEMPTS {
+                let mut stored = request_data(&request.id, &db).unwrap().unwrap();
+                assert!(!sender.deliver(&db, &mut stored, now).await.unwrap());
+            }
+            let stored = request_data(&request.id, &db).unwrap().unwrap();
+            let callback = stored.callback.unwrap();
+            assert!(callback.dead_lettered);
+            assert_eq!(callback.attempts, CALLBACK_MAX_ATTEMPTS);
+            assert!(due_callbacks(&db, now).unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delivery_to_host_removed_from_allowlist() {
+        for db in each_engine() {
+            let (url, server) = mock_server(vec![]).await;
+            let request = completed_request(&db, &url);
+            let sender = CallbackSender::new(policy(Some("hooks.example.com"))).unwrap();
+
+            let now = Timestamp::from_secs(1_700_000_000);
+            assert_eq!(sender.deliver_due(&db, now).await.unwrap(), 0);
+            let callback = request_data(&request.id, &db)
+                .unwrap()
+                .unwrap()
+                .callback
+                .unwrap();
+            assert!(callback.dead_lettered);
+            assert_eq!(callback.attempts, 1);
+            assert!(server.await.unwrap().is_empty());
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+
+/// Age after which a published chain head is considered stale
+pub const HEAD_STALE_THRESHOLD: Duration = Duration::from_secs(60);
+
+/// Latest height seen on a chain (block number on EVM, slot on Solana)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainHead {
+    pub height: u64,
+    pub observed_at: Instant,
+}
+
+pub type ChainHeadSender = watch::Sender<ChainHead>;
+pub type ChainHeadReceiver = watch::Receiver<ChainHead>;
+
+impl ChainHead {
+    pub fn new(height: u64) -> Self {
+        ChainHead {
+            height,
+            observed_at: Instant::now(),
+        }
+    }
+
+    pub fn age(&self) -> Duration {
+        self.observed_at.elapsed()
+    }
+
+    /// A head that has never been observed (height 0) is always stale
+    pub fn is_stale(&self, threshold: Duration) -> bool {
+        self.height == 0 || self.age() > threshold
+    }
+}
+
+pub fn chain_head_channel(initial_height: u64) -> (ChainHeadSender, ChainHeadReceiver) {
+    watch::channel(ChainHead::new(initial_height))
+}
+
+#[cfg(test)]
+mod chain_head_test {
+    use crate::{chain_head_channel, ChainHead};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_chain_head_staleness() {
+        let head = ChainHead::new(100);
+        assert!(!head.is_stale(Duration::from_secs(60)));
+
+        // Head observed in the past
+        let old_head = ChainHead {
+            height: 100,
+            observed_at: Instant::now() - Duration::from_secs(120),
+        };
+        assert!(old_head.is_stale(Duration::from_secs(60)));
+        assert!(!old_head.is_stale(Duration::from_secs(300)));
+
+        // Never observed
+        let empty_head = ChainHead::new(0);
+        assert!(empty_head.is_stale(Duration::from_secs(60)));
+    }
+
+    #[tokio::test]
+    async fn test_chain_head_propagation() {
+        let (head_tx, mut head_rx) = chain_head_channel(10);
+        let mut other_rx = head_rx.clone();
+        assert_eq!(head_rx.borrow().height, 10);
+
+        head_tx.send_replace(ChainHead::new(11));
+
+        head_rx.changed().await.unwrap();
+        assert_eq!(head_rx.borrow_and_update().height, 11);
+        other_rx.changed().await.unwrap();
+        assert_eq!(other_rx.borrow_and_update().height, 11);
+    }
+}
@@ -0,0 +1,188 @@
+use alloy::primitives::hex;
+use serde::{Deserialize, Serialize};
+use storage::{
+    db::{Batch, Database},
+    keys::CLIENT_REFERENCE_PREFIX,
+};
+
+use crate::{BRequest, RequestId, Result};
+
+/// Characters of a client reference
+pub const MAX_CLIENT_REFERENCE_LEN: usize = 128;
+/// Tags of a request
+pub const MAX_TAGS: usize = 10;
+/// Characters of a tag
+pub const MAX_TAG_LEN: usize = 64;
+
+/// Reference and tags an integrator attaches to its requests, opaque to the relayer
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ClientLabels {
+    #[serde(default)]
+    pub client_reference: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Field of the labels rejected by their validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidLabel {
+    pub field: &'static str,
+    pub reason: String,
+}
+
+fn check_label(field: &'static str, value: &str, max_len: usize) -> Result<(), InvalidLabel> {
+    let invalid = |reason: String| Err(InvalidLabel { field, reason });
+    if value.is_empty() {
+        return invalid("must not be empty".to_string());
+    }
+    if value.chars().count() > max_len {
+        return invalid(format!("exceeds {max_len} characters"));
+    }
+    if value.chars().any(char::is_control) {
+        return invalid("must not contain control characters".to_string());
+    }
+    Ok(())
+}
+
+impl ClientLabels {
+    pub fn is_empty(&self) -> bool {
+        self.client_reference.is_none() && self.tags.is_empty()
+    }
+
+    /// Labels with their values trimmed, rejected with the first field out of bounds
+    pub fn validate(self) -> Result<ClientLabels, InvalidLabel> {
+        let client_reference = match self.client_reference {
+            Some(reference) => {
+                let reference = reference.trim().to_string();
+                check_label("client_reference", &reference, MAX_CLIENT_REFERENCE_LEN)?;
+                Some(reference)
+            }
+            None => None,
+        };
+        if self.tags.len() > MAX_TAGS {
+            return Err(InvalidLabel {
+                field: "tags",
+                reason: format!("exceeds {MAX_TAGS} tags"),
+            });
+        }
+        let mut tags = Vec::with_capacity(self.tags.len());
+        for tag in self.tags {
+            let tag = tag.trim().to_string();
+            check_label("tags", &tag, MAX_TAG_LEN)?;
+            tags.push(tag);
+        }
+        Ok(ClientLabels {
+            client_reference,
+            tags,
+        })
+    }
+}
+
+/// Index key of a request with `reference`. Hex encoded, a reference holding the
+/// separator is never the prefix of another one
+fn client_reference_key(reference: &str, request_id: &str) -> String {
+    format!(
+        "{CLIENT_REFERENCE_PREFIX}{}:{request_id}",
+        hex::encode(reference.as_bytes())
+    )
+}
+
+/// Adds the index entry of the client reference of a request to the batch saving it
+pub(crate) fn update_client_reference_index(request: &BRequest, batch: &mut Batch) -> Result<()> {
+    if let Some(reference) = &request.client_reference {
+        batch.put(client_reference_key(reference, &request.id), &request.id)?;
+    }
+    Ok(())
+}
+
+/// Requests created with `reference`, references are not unique and every match is
+/// returned
+pub fn requests_by_client_reference(db: &Database, reference: &str) -> Result<Vec<RequestId>> {
+    let prefix = client_reference_key(reference, "");
+    Ok(db
+        .scan_prefix::<RequestId>(&prefix, None)?
+        .into_iter()
+        .map(|(_, request_id)| request_id)
+        .collect())
+}
+
+#[cfg(test)]
+mod client_labels_test {
+    use storage::testing::each_engine;
+
+    use crate::{
+        requests_by_client_reference, BRequest, Chains, ClientLabels, InputRequest, InvalidLabel,
+        Timestamp, MAX_CLIENT_REFERENCE_LEN, MAX_TAGS, MAX_TAG_LEN,
+    };
+
+    fn labels(client_reference: Option<&str>, tags: &[&str]) -> ClientLabels {
+        ClientLabels {
+            client_reference: client_reference.map(str::to_string),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_labels_bounds() {
+        assert_eq!(
+            labels(Some(" order-1 "), &[" vip", "eu "]).validate(),
+            Ok(labels(Some("order-1"), &["vip", "eu"]))
+        );
+        let reference = "r".repeat(MAX_CLIENT_REFERENCE_LEN);
+        assert!(labels(Some(&reference), &[]).validate().is_ok());
+        let tags = vec!["t".repeat(MAX_TAG_LEN); MAX_TAGS];
+        let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+        assert!(labels(None, &tags).validate().is_ok());
+
+        let too_long = format!("{reference}r");
+        for (invalid, field) in [
+            (labels(Some(&too_long), &[]), "client_reference"),
+            (labels(Some("  "), &[]), "client_reference"),
+            (labels(Some("order\n1"), &[]), "client_reference"),
+            (labels(None, &[&"t".repeat(MAX_TAG_LEN + 1)]), "tags"),
+            (labels(None, &["vip", ""]), "tags"),
+            (labels(None, &["tag"; MAX_TAGS + 1]), "tags"),
+        ] {
+            let InvalidLabel {
+                field: rejected, ..
+            } = invalid.clone().validate().unwrap_err();
+            assert_eq!(rejected, field, "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn test_lookup_by_client_reference() {
+        for db in each_engine() {
+            let mut ids = vec![];
+            for (token_id, reference) in [("1", "order:1"), ("2", "order:1"), ("3", "order")] {
+                let mut request = BRequest::new(
+                    InputRequest {
+                        contract_or_mint: "0xcontract".to_string(),
+                        token_id: token_id.to_string(),
+                        token_owner: "0xowner".to_string(),
+                        origin_network: Chains::EVM,
+                        destination_account: "destination".to_string(),
+                    },
+                    Timestamp::now(),
+                );
+                request.client_reference = Some(reference.to_string());
+                request.save(&db).unwrap();
+                ids.push(request.id);
+            }
+
+            let mut found = requests_by_client_reference(&db, "order:1").unwrap();
+            found.sort();
+            let mut expected = ids[..2].to_vec();
+            expected.sort();
+            assert_eq!(found, expected);
+            // A reference prefixing another one doesn't match it
+            assert_eq!(
+                requests_by_client_reference(&db, "order").unwrap(),
+                vec![ids[2].clone()]
+            );
+            assert!(requests_by_client_reference(&db, "unknown")
+                .unwrap()
+                .is_empty());
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Unix time in milliseconds. Elapsed times saturate at zero, a clock stepping
+/// backwards never makes them negative
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(transparent)]
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    pub const fn from_millis(millis: u64) -> Self {
+        Timestamp(millis)
+    }
+
+    pub const fn from_secs(secs: u64) -> Self {
+        Timestamp(secs.saturating_mul(1000))
+    }
+
+    /// Current time of the system clock
+    pub fn now() -> Self {
+        SystemClock.now()
+    }
+
+    pub const fn as_millis(&self) -> u64 {
+        self.0
+    }
+
+    pub const fn as_secs(&self) -> u64 {
+        self.0 / 1000
+    }
+
+    /// Time between this timestamp and `now`, zero when `now` is earlier
+    pub fn elapsed_until(&self, now: Timestamp) -> Duration {
+        Duration::from_millis(now.0.saturating_sub(self.0))
+    }
+
+    pub fn saturating_add(&self, duration: Duration) -> Self {
+        let millis = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+        Timestamp(self.0.saturating_add(millis))
+    }
+
+    pub fn saturating_sub(&self, duration: Duration) -> Self {
+        let millis = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+        Timestamp(self.0.saturating_sub(millis))
+    }
+}
+
+impl From<Duration> for Timestamp {
+    /// Duration since the unix epoch
+    fn from(duration: Duration) -> Self {
+        Timestamp(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+    }
+}
+
+/// Timestamps written before the millis encoding were serialized as a `Duration`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TimestampEncoding {
+    Millis(u64),
+    Duration { secs: u64, nanos: u32 },
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match TimestampEncoding::deserialize(deserializer)? {
+            TimestampEncoding::Millis(millis) => Timestamp(millis),
+            TimestampEncoding::Duration { secs, nanos } => Duration::new(secs, nanos).into(),
+        })
+    }
+}
+
+/// Source of the wall clock time, replaced by a `MockClock` in tests
+pub trait Clock: Send + Sync + Debug {
+    fn now(&self) -> Timestamp;
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    /// A system clock before the epoch reads as the epoch instead of panicking
+    fn now(&self) -> Timestamp {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(Timestamp::from)
+            .unwrap_or_default()
+    }
+}
+
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// Clock moved by hand, clones share the same time
+#[derive(Clone, Debug, Default)]
+pub struct MockClock {
+    millis: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    pub fn new(now: Timestamp) -> Self {
+        MockClock {
+            millis: Arc::new(AtomicU64::new(now.as_millis())),
+        }
+    }
+
+    pub fn set(&self, now: Timestamp) {
+        self.millis.store(now.as_millis(), Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.set(self.now().saturating_add(duration));
+    }
+
+    /// Steps the clock backwards, like an NTP correction
+    pub fn rewind(&self, duration: Duration) {
+        self.set(self.now().saturating_sub(duration));
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Timestamp {
+        Timestamp(self.millis.load(Ordering::SeqCst))
+    }
+}
+
+#[cfg(test)]
+mod clock_test {
+    use std::time::Duration;
+
+    use crate::{Clock, MockClock, Timestamp};
+
+    #[test]
+    fn test_elapsed_saturates_when_clock_steps_back() {
+        let clock = MockClock::new(Timestamp::from_secs(1_700_000_000));
+        let start = clock.now();
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(start.elapsed_until(clock.now()), Duration::from_secs(30));
+
+        clock.rewind(Duration::from_secs(3600));
+        assert_eq!(start.elapsed_until(clock.now()), Duration::ZERO);
+
+        // Rewinding past the epoch stops at the epoch
+        clock.rewind(Duration::MAX);
+        assert_eq!(clock.now(), Timestamp::default());
+        clock.advance(Duration::MAX);
+        assert_eq!(clock.now().as_millis(), u64::MAX);
+    }
+
+    #[test]
+    fn test_timestamp_serde() {
+        let timestamp = Timestamp::from_millis(1_700_000_000_123);
+        assert_eq!(serde_json::to_string(&timestamp).unwrap(), "1700000000123");
+        assert_eq!(
+            serde_json::from_str::<Timestamp>("1700000000123").unwrap(),
+            timestamp
+        );
+
+        // Encoding of the Duration used before
+        let legacy = serde_json::to_string(&Duration::new(1_700_000_000, 123_456_789)).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Timestamp>(&legacy).unwrap(),
+            timestamp
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use storage::{db::Database, keys::COLLECTION_PREFIX};
+
+use crate::{Result, Timestamp};
+
+/// Solana collection NFT the tokens bridged from an EVM contract are verified into
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CollectionEntry {
+    /// Lowercase EVM contract
+    pub origin_contract: String,
+    pub collection_mint: String,
+    /// Created by the relayer, false when supplied by an admin
+    pub created_by_relayer: bool,
+    pub updated_at: Timestamp,
+}
+
+impl CollectionEntry {
+    pub fn new(
+        origin_contract: &str,
+        collection_mint: &str,
+        created_by_relayer: bool,
+        now: Timestamp,
+    ) -> Self {
+        CollectionEntry {
+            origin_contract: collection_contract(origin_contract),
+            collection_mint: collection_mint.to_string(),
+            created_by_relayer,
+            updated_at: now,
+        }
+    }
+}
+
+/// EVM contracts are case insensitive, every spelling shares the same collection
+pub fn collection_contract(origin_contract: &str) -> String {
+    origin_contract.trim().to_ascii_lowercase()
+}
+
+fn collection_key(origin_contract: &str) -> String {
+    format!(
+        "{COLLECTION_PREFIX}{}",
+        collection_contract(origin_contract)
+    )
+}
+
+pub fn get_collection(db: &Database, origin_contract: &str) -> Result<Option<CollectionEntry>> {
+    Ok(db.read(collection_key(origin_contract))?)
+}
+
+pub fn set_collection(db: &Database, entry: &CollectionEntry) -> Result<()> {
+    db.write_value(collection_key(&entry.origin_contract), entry)?;
+    info!(
+        "Collection of {} set to {}",
+        entry.origin_contract, entry.collection_mint
+    );
+    Ok(())
+}
+
+/// Removes the collection of a contract, the next mint creates a new one
+pub fn remove_collection(db: &Database, origin_contract: &str) -> Result<()> {
+    db.delete(collection_key(origin_contract))?;
+    info!(
+        "Collection of {} removed",
+        collection_contract(origin_contract)
+    );
+    Ok(())
+}
+
+pub fn list_collections(db: &Database) -> Result<Vec<CollectionEntry>> {
+    Ok(db
+        .scan_prefix::<CollectionEntry>(COLLECTION_PREFIX, None)?
+        .into_iter()
+        .map(|(_, entry)| entry)
+        .collect())
+}
+
+#[cfg(test)]
+mod collection_test {
+    use storage::testing::each_engine;
+
+    use crate::{
+        get_collection, list_collections, remove_collection, set_collection, CollectionEntry,
+        Timestamp,
+    };
+
+    #[test]
+    fn test_collection_registry_by_contract() {
+        for db in each_engine() {
+            let contract = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+
+            assert_eq!(get_collection(&db, contract).unwrap(), None);
+            let entry = CollectionEntry::new(contract, "mint", false, Timestamp::from_millis(1));
+            set_collection(&db, &entry).unwrap();
+
+            assert_eq!(
+                entry.origin_contract,
+                "0x5fbdb2315678afecb367f032d93f642f64180aa3"
+            );
+            assert_eq!(
+                get_collection(&db, &contract.to_uppercase().replace("0X", "0x")).unwrap(),
+                Some(entry.clone())
+            );
+            assert_eq!(list_collections(&db).unwrap(), vec![entry]);
+
+            remove_collection(&db, contract).unwrap();
+            assert!(list_collections(&db).unwrap().is_empty());
+        }
+    }
+}
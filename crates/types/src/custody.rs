@@ -0,0 +1,366 @@
+use std::time::Duration;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use storage::{
+    db::{Batch, Database},
+    keys::CUSTODY_INDEX_PREFIX,
+};
+
+use crate::{
+    normalize_token_id, request_data, BRequest, CancelReason, Chains, InputRequest, Result, Status,
+    Timestamp, TxState, LOG_CONFIRMATION_DEPTH,
+};
+
+/// RequestReceived requests whose token is still not in custody after this age are
+/// canceled, whatever the state of their lock transaction
+pub const RECEIVED_EXPIRY: Duration = Duration::from_secs(24 * 3600);
+
+/// Index key of the origin token of a request. EVM contracts are case insensitive and
+/// token ids are compared as numbers, so equivalent inputs share the same entry
+pub fn custody_key(input: &InputRequest) -> String {
+    let contract_or_mint = input.contract_or_mint.trim();
+    let contract_or_mint = match input.origin_network {
+        Chains::EVM => contract_or_mint.to_ascii_lowercase(),
+        Chains::SOLANA => contract_or_mint.to_string(),
+    };
+    let token_id = input.token_id.trim();
+    let token_id = normalize_token_id(token_id).unwrap_or_else(|_| token_id.to_string());
+
+    format!(
+        "{CUSTODY_INDEX_PREFIX}{:?}:{contract_or_mint}:{token_id}",
+        input.origin_network
+    )
+}
+
+/// Canceled requests keep the custody until their token is refunded
+fn is_active(request: &BRequest) -> bool {
+    match request.status {
+        Status::Completed | Status::Refunded => false,
+        Status::Canceled => request.refund_pending,
+        _ => true,
+    }
+}
+
+/// Active request other than `request_id` bridging the same origin token
+pub fn custody_conflict(
+    db: &Database,
+    input: &InputRequest,
+    request_id: &str,
+) -> Result<Option<String>> {
+    let Some(holder) = db.read::<_, String>(custody_key(input))? else {
+        return Ok(None);
+    };
+    if holder == request_id {
+        return Ok(None);
+    }
+    // Entries of requests finished without going through `save` are stale
+    match request_data(&holder, db)? {
+        Some(request) if is_active(&request) => Ok(Some(holder)),
+        _ => Ok(None),
+    }
+}
+
+/// Cancels `request` when its token is held for another active request, before the
+/// bridge custody confirms it. Returns true when the request was rejected
+pub fn reject_custody_conflict(
+    request: &mut BRequest,
+    db: &Database,
+    now: Timestamp,
+) -> Result<bool> {
+    let Some(conflicting) = custody_conflict(db, &request.input, &request.id)? else {
+        return Ok(false);
+    };
+    warn!(
+        "Skipping custody of request {}, token already bridging in request {conflicting}",
+        request.id
+    );
+    request.cancel_with_reason(db, CancelReason::TokenAlreadyBridging(conflicting), now)?;
+    Ok(true)
+}
+
+/// Next step of a RequestReceived request from the custody of its token
+#[derive(Debug, Clone, PartialEq)]
+pub enum CustodyStep {
+    /// The bridge holds the token, the mint goes on
+    InCustody,
+    /// Not transferred yet, the lock transaction is pending or not seen by the node
+    Wait,
+    /// Transferred elsewhere or never will be
+    Cancel(CancelReason),
+}
+
+/// Custody step of a request `age` old, with the state of its lock transaction read when
+/// the token is not in custody. Precedence, first match wins:
+/// 1. A token in custody goes on, even past the expiry
+/// 2. A mined lock transaction without the token in custody cancels, it reverted or the
+///    token was moved after
+/// 3. Past `RECEIVED_EXPIRY` the request is canceled
+/// 4. Otherwise it waits, the lock transaction can still confirm
+pub fn custody_step(in_custody: bool, lock_tx: Option<&TxState>, age: Duration) -> CustodyStep {
+    if in_custody {
+        return CustodyStep::InCustody;
+    }
+    match lock_tx {
+        Some(TxState::Mined { success: false }) => CustodyStep::Cancel(CancelReason::LockFailed(
+            "lock transaction reverted".to_string(),
+        )),
+        Some(TxState::Mined { success: true }) => CustodyStep::Cancel(CancelReason::LockFailed(
+            "lock transaction mined but the token is not in custody".to_string(),
+        )),
+        _ if age >= RECEIVED_EXPIRY => CustodyStep::Cancel(CancelReason::Expired(format!(
+            "token not in custody after {} seconds",
+            age.as_secs()
+        ))),
+        _ => CustodyStep::Wait,
+    }
+}
+
+/// State of a lock transaction from the block its NewRequest log was seen in and the
+/// chain head, without reading the transaction again. The log can still be reorged out
+/// until `LOG_CONFIRMATION_DEPTH` blocks deep, the lock is pending until then
+pub fn stored_lock_state(lock_block: u64, head: u64) -> TxState {
+    if head.saturating_sub(lock_block) >= LOG_CONFIRMATION_DEPTH {
+        TxState::Mined { success: true }
+    } else {
+        TxState::Pending {
+            max_fee_per_gas: None,
+            base_fee_per_gas: None,
+        }
+    }
+}
+
+/// How the token owner named by a request may move a token another address holds
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OwnerAuthorization {
+    /// Operator of the holder approved by `isApprovedForAll`
+    Operator,
+    /// Delegate of the holder in the delegate registry at `registry`
+    Delegate { registry: String },
+}
+
+/// Holder of an EVM token bridged by an authorized owner, a vault or wrapper contract
+/// holding it for them. The lock moves the token from the holder
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CustodyChain {
+    pub holder: String,
+    pub authorization: OwnerAuthorization,
+}
+
+impl BRequest {
+    /// Address the lock transaction moves the token from: the holder of the custody chain
+    /// when the token owner is only authorized by it
+    pub fn lock_owner(&self) -> &str {
+        match &self.custody_chain {
+            Some(chain) => &chain.holder,
+            None => &self.input.token_owner,
+        }
+    }
+}
+
+/// Adds the custody index writes of `request` to the batch saving it. Active requests
+/// take the entry when no other active request holds it, terminal ones release it
+pub(crate) fn update_custody_index(
+    request: &BRequest,
+    db: &Database,
+    batch: &mut Batch,
+) -> Result<()> {
+    let key = custody_key(&request.input);
+    if is_active(request) {
+        if custody_conflict(db, &request.input, &request.id)?.is_none() {
+            batch.put(key, &request.id)?;
+        }
+    } else if db.read::<_, String>(&key)?.as_deref() == Some(request.id.as_str()) {
+        batch.delete(key);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod custody_test {
+    use storage::testing::each_engine;
+
+    use std::time::Duration;
+
+    use crate::{
+        custody_conflict, custody_key, custody_step, reject_custody_conflict, stored_lock_state,
+        BRequest, CancelReason, Chains, CustodyStep, InputRequest, Status, Timestamp, TxState,
+        LOG_CONFIRMATION_DEPTH, RECEIVED_EXPIRY,
+    };
+
+    fn input(contract: &str, token_id: &str, owner: &str) -> InputRequest {
+        InputRequest {
+            contract_or_mint: contract.to_string(),
+            token_id: token_id.to_string(),
+            token_owner: owner.to_string(),
+            origin_network: Chains::EVM,
+            destination_account: "destination789".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_custody_key_normalization() {
+        assert_eq!(
+            custody_key(&input("0xABC123", "0x2a", "0xowner")),
+            custody_key(&input(" 0xabc123", "42", "0xother"))
+        );
+        assert_ne!(
+            custody_key(&input("0xabc123", "42", "0xowner")),
+            custody_key(&input("0xabc123", "43", "0xowner"))
+        );
+
+        let mut solana = input("Mint111", "", "account");
+        solana.origin_network = Chains::SOLANA;
+        let mut other_case = solana.clone();
+        other_case.contract_or_mint = "mint111".to_string();
+        assert_ne!(custody_key(&solana), custody_key(&other_case));
+    }
+
+    #[test]
+    fn test_index_follows_request_lifecycle() {
+        for db in each_engine() {
+            let mut first = BRequest::new(input("0xabc123", "42", "0xowner"), Timestamp::now());
+            first.save(&db).unwrap();
+            let second = BRequest::new(input("0xABC123", "42", "0xother"), Timestamp::now());
+            assert_eq!(
+                custody_conflict(&db, &second.input, &second.id).unwrap(),
+                Some(first.id.to_string())
+            );
+            assert_eq!(
+                custody_conflict(&db, &first.input, &first.id).unwrap(),
+                None
+            );
+
+            // The conflicting request doesn't take the entry when saved
+            second.save(&db).unwrap();
+            assert_eq!(
+                db.read::<_, String>(custody_key(&first.input)).unwrap(),
+                Some(first.id.to_string())
+            );
+
+            first.update_state(&db, Timestamp::now()).unwrap();
+            first.update_state(&db, Timestamp::now()).unwrap();
+            first
+                .complete_minted(&db, "mint", "account", Timestamp::now())
+                .unwrap();
+            assert_eq!(
+                db.read::<_, String>(custody_key(&first.input)).unwrap(),
+                None
+            );
+            assert_eq!(
+                custody_conflict(&db, &second.input, &second.id).unwrap(),
+                None
+            );
+
+            let mut third = BRequest::new(input("0xabc123", "42", "0xthird"), Timestamp::now());
+            third.save(&db).unwrap();
+            third.cancel(&db, Timestamp::now()).unwrap();
+            assert_eq!(
+                db.read::<_, String>(custody_key(&third.input)).unwrap(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_custody_confirmation_skips_conflicting_request() {
+        for db in each_engine() {
+            let mut holder = BRequest::new(input("0xabc123", "42", "0xowner"), Timestamp::now());
+            holder.save(&db).unwrap();
+            let mut conflicting =
+                BRequest::new(input("0xabc123", "42", "0xother"), Timestamp::now());
+            conflicting.save(&db).unwrap();
+
+            assert!(!reject_custody_conflict(&mut holder, &db, Timestamp::now()).unwrap());
+            assert!(reject_custody_conflict(&mut conflicting, &db, Timestamp::now()).unwrap());
+            assert_eq!(conflicting.status, Status::Canceled);
+            assert_eq!(
+                conflicting.cancel_reason,
+                Some(CancelReason::TokenAlreadyBridging(holder.id.to_string()))
+            );
+
+            // Canceling the conflicting request leaves the holder entry in place
+            assert_eq!(
+                db.read::<_, String>(custody_key(&holder.input)).unwrap(),
+                Some(holder.id.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_custody_step_precedence() {
+        let young = Duration::from_secs(30);
+        let pending = TxState::Pending {
+            max_fee_per_gas: Some(10),
+            base_fee_per_gas: Some(5),
+        };
+        let reverted = TxState::Mined { success: false };
+
+        // Lock transaction not confirmed yet, or not seen by the node
+        assert_eq!(
+            custody_step(false, Some(&pending), young),
+            CustodyStep::Wait
+        );
+        assert_eq!(
+            custody_step(false, Some(&TxState::Dropped), young),
+            CustodyStep::Wait
+        );
+        assert_eq!(custody_step(false, None, young), CustodyStep::Wait);
+
+        // An old unconfirmed request is only canceled once its lock is known failed
+        let old = RECEIVED_EXPIRY - Duration::from_secs(1);
+        assert_eq!(custody_step(false, Some(&pending), old), CustodyStep::Wait);
+        assert!(matches!(
+            custody_step(false, Some(&reverted), old),
+            CustodyStep::Cancel(CancelReason::LockFailed(_))
+        ));
+        assert!(matches!(
+            custody_step(false, Some(&TxState::Mined { success: true }), young),
+            CustodyStep::Cancel(CancelReason::LockFailed(_))
+        ));
+
+        // Or once it expires
+        assert!(matches!(
+            custody_step(false, Some(&pending), RECEIVED_EXPIRY),
+            CustodyStep::Cancel(CancelReason::Expired(_))
+        ));
+        // The lock failure decides over the expiry, the custody over both
+        assert!(matches!(
+            custody_step(false, Some(&reverted), RECEIVED_EXPIRY),
+            CustodyStep::Cancel(CancelReason::LockFailed(_))
+        ));
+        assert_eq!(
+            custody_step(true, None, RECEIVED_EXPIRY * 2),
+            CustodyStep::InCustody
+        );
+    }
+
+    #[test]
+    fn test_stored_lock_block_decides_custody() {
+        let young = Duration::from_secs(30);
+        let lock_block = 1_000;
+
+        // A lock log under the confirmation depth can still be reorged out
+        let shallow = stored_lock_state(lock_block, lock_block + LOG_CONFIRMATION_DEPTH - 1);
+        assert_eq!(
+            custody_step(false, Some(&shallow), young),
+            CustodyStep::Wait
+        );
+        // Behind the head of a lagging node
+        let behind = stored_lock_state(lock_block, lock_block - 10);
+        assert_eq!(custody_step(false, Some(&behind), young), CustodyStep::Wait);
+
+        // Deep enough, the token left the custody after the lock
+        let deep = stored_lock_state(lock_block, lock_block + LOG_CONFIRMATION_DEPTH);
+        assert_eq!(deep, TxState::Mined { success: true });
+        assert!(matches!(
+            custody_step(false, Some(&deep), young),
+            CustodyStep::Cancel(CancelReason::LockFailed(_))
+        ));
+        assert_eq!(
+            custody_step(true, Some(&deep), young),
+            CustodyStep::InCustody
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use storage::{
+    db::{Batch, Database},
+    keys::{CUSTODY_EXPOSURE_PREFIX, REQUEST_KEY_PREFIX},
+};
+
+use crate::{BRequest, Chains, Result, Status};
+
+/// Caps of the tokens held in bridge custody for requests, unset and zero caps are not
+/// enforced
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct CustodyLimits {
+    /// Tokens of both directions
+    pub max_total: Option<usize>,
+    /// Tokens locked on EVM, minted on Solana
+    pub max_evm_to_solana: Option<usize>,
+    /// Tokens locked on Solana, minted on EVM
+    pub max_solana_to_evm: Option<usize>,
+}
+
+/// Cap reached, its scope (`total` or the direction) and value
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CustodyLimitReached {
+    pub scope: String,
+    pub limit: usize,
+}
+
+impl CustodyLimits {
+    pub fn is_enforced(&self) -> bool {
+        [
+            self.max_total,
+            self.max_evm_to_solana,
+            self.max_solana_to_evm,
+        ]
+        .iter()
+        .any(|limit| limit.is_some_and(|limit| limit > 0))
+    }
+
+    /// First cap reached by `exposure`, the one of the direction first, for a new request
+    /// originated in `origin`. None when it can be accepted
+    pub fn reached(
+        &self,
+        origin: &Chains,
+        exposure: &CustodyExposure,
+    ) -> Option<CustodyLimitReached> {
+        let direction = match origin {
+            Chains::EVM => ("evm_to_solana", self.max_evm_to_solana),
+            Chains::SOLANA => ("solana_to_evm", self.max_solana_to_evm),
+        };
+        [
+            (direction.0, direction.1, exposure.of(origin)),
+            ("total", self.max_total, exposure.total()),
+        ]
+        .into_iter()
+        .find_map(|(scope, limit, held)| match limit {
+            Some(limit) if limit > 0 && held >= limit => Some(CustodyLimitReached {
+                scope: scope.to_string(),
+                limit,
+            }),
+            _ => None,
+        })
+    }
+}
+
+/// Tokens held in bridge custody for requests, by origin chain
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct CustodyExposure {
+    #[serde(rename = "EVM")]
+    pub evm: usize,
+    #[serde(rename = "SOLANA")]
+    pub solana: usize,
+}
+
+impl CustodyExposure {
+    pub fn of(&self, origin: &Chains) -> usize {
+        match origin {
+            Chains::EVM => self.evm,
+            Chains::SOLANA => self.solana,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.evm + self.solana
+    }
+}
+
+/// The bridge confirmed the custody of the origin token and the request did not complete
+/// or return it yet. Orphans are counted once claimed, requests held for an operator when
+/// they were held in custody
+pub fn holds_custody(request: &BRequest) -> bool {
+    match request.status {
+        Status::TokenReceived | Status::TokenMinted | Status::AwaitingDestinationAccount => true,
+        Status::NeedsIntervention => request.failure_report.as_ref().is_none_or(|report| {
+            matches!(
+                report.status,
+                Status::TokenReceived | Status::TokenMinted | Status::AwaitingDestinationAccount
+            )
+        }),
+        Status::Canceled => request.refund_pending,
+        _ => false,
+    }
+}
+
+fn exposure_prefix(origin: &Chains) -> String {
+    format!("{CUSTODY_EXPOSURE_PREFIX}{origin:?}:")
+}
+
+fn exposure_key(origin: &Chains, request_id: &str) -> String {
+    format!("{}{request_id}", exposure_prefix(origin))
+}
+
+/// Counts the request in the exposure of its origin while it holds the custody, in the
+/// batch of its status
+pub(crate) fn stage_custody_exposure(request: &BRequest, batch: &mut Batch) -> Result<()> {
+    let key = exposure_key(&request.input.origin_network, &request.id);
+    if holds_custody(request) {
+        batch.put(key, &request.last_update.as_secs())?;
+    } else {
+        batch.delete(key);
+    }
+    Ok(())
+}
+
+/// Tokens currently held in custody by origin chain
+pub fn custody_exposure(db: &Database) -> Result<CustodyExposure> {
+    let count = |origin: &Chains| -> Result<usize> {
+        Ok(db.scan_prefix::<u64>(&exposure_prefix(origin), None)?.len())
+    };
+    Ok(CustodyExposure {
+        evm: count(&Chains::EVM)?,
+        solana: count(&Chains::SOLANA)?,
+    })
+}
+
+/// Drops the exposure entries and counts them again from the stored requests, entries
+/// written by an older version or missed by a write outside `save` can't drift
+pub fn rebuild_custody_exposure(db: &Database) -> Result<CustodyExposure> {
+    for (key, _) in db.scan_prefix::<u64>(CUSTODY_EXPOSURE_PREFIX, None)? {
+        db.delete(key)?;
+    }
+
+    let mut batch = Batch::default();
+    for (_, request) in db.scan_prefix::<BRequest>(REQUEST_KEY_PREFIX, None)? {
+        if holds_custody(&request) {
+            stage_custody_exposure(&request, &mut batch)?;
+        }
+    }
+    db.write_batch(batch)?;
+
+    let exposure = custody_exposure(db)?;
+    info!(
+        "Tokens in bridge custody: {} from EVM, {} from Solana",
+        exposure.evm, exposure.solana
+    );
+    Ok(exposure)
+}
+
+#[cfg(test)]
+mod custody_limit_test {
+    use storage::testing::each_engine;
+
+    use crate::{
+        custody_exposure, rebuild_custody_exposure, BRequest, CancelReason, Chains,
+        CustodyExposure, CustodyLimitReached, CustodyLimits, InputRequest, Status, Timestamp,
+    };
+
+    fn request(origin: Chains, token_id: &str) -> BRequest {
+        BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xcontract".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: origin,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        )
+    }
+
+    #[test]
+    fn test_exposure_follows_custody() {
+        for db in each_engine() {
+            let mut minted = request(Chains::EVM, "1");
+            minted.status = Status::RequestReceived;
+            minted.save(&db).unwrap();
+            assert_eq!(custody_exposure(&db).unwrap(), CustodyExposure::default());
+
+            minted.update_state(&db, Timestamp::now()).unwrap();
+            let mut refunded = request(Chains::SOLANA, "2");
+            refunded.status = Status::TokenReceived;
+            refunded.save(&db).unwrap();
+            assert_eq!(
+                custody_exposure(&db).unwrap(),
+                CustodyExposure { evm: 1, solana: 1 }
+            );
+
+            // Completion and refund release the custody, a cancel keeps it until refunded
+            minted.update_state(&db, Timestamp::now()).unwrap();
+            minted
+                .complete_minted(&db, "mint", "account", Timestamp::now())
+                .unwrap();
+            refunded
+                .cancel_with_reason(
+                    &db,
+                    CancelReason::Expired("test".to_string()),
+                    Timestamp::now(),
+                )
+                .unwrap();
+            assert_eq!(
+                custody_exposure(&db).unwrap(),
+                CustodyExposure { evm: 0, solana: 1 }
+            );
+            refunded.status = Status::Refunded;
+            refunded.save(&db).unwrap();
+            assert_eq!(custody_exposure(&db).unwrap(), CustodyExposure::default());
+        }
+    }
+
+    #[test]
+    fn test_rebuild_matches_the_statuses() {
+        for db in each_engine() {
+            let statuses = [
+                (Chains::EVM, Status::TokenReceived),
+                (Chains::EVM, Status::NeedsIntervention),
+                (Chains::EVM, Status::AwaitingDestinationAccount),
+                (Chains::EVM, Status::Completed),
+                (Chains::SOLANA, Status::TokenMinted),
+                (Chains::SOLANA, Status::NeedsDestination),
+            ];
+            for (index, (origin, status)) in statuses.into_iter().enumerate() {
+                let mut request = request(origin, &index.to_string());
+                request.status = status;
+                // Written without its indexes, as by a version without the exposure
+                db.write_value(&request.id, &request).unwrap();
+            }
+            // Entry of a request no longer in custody
+            let mut stale = request(Chains::SOLANA, "stale");
+            stale.status = Status::TokenReceived;
+            stale.save(&db).unwrap();
+            stale.status = Status::Completed;
+            db.write_value(&stale.id, &stale).unwrap();
+            assert_eq!(
+                custody_exposure(&db).unwrap(),
+                CustodyExposure { evm: 0, solana: 1 }
+            );
+
+            let rebuilt = rebuild_custody_exposure(&db).unwrap();
+            assert_eq!(rebuilt, CustodyExposure { evm: 3, solana: 1 });
+            assert_eq!(custody_exposure(&db).unwrap(), rebuilt);
+        }
+    }
+
+    #[test]
+    fn test_direction_and_total_caps() {
+        let limits = CustodyLimits {
+            max_total: Some(3),
+            max_evm_to_solana: Some(2),
+            max_solana_to_evm: Some(0),
+        };
+        let exposure = CustodyExposure { evm: 2, solana: 0 };
+        assert_eq!(
+            limits.reached(&Chains::EVM, &exposure),
+            Some(CustodyLimitReached {
+                scope: "evm_to_solana".to_string(),
+                limit: 2
+            })
+        );
+        // Zero is not enforced, the total still is
+        assert_eq!(limits.reached(&Chains::SOLANA, &exposure), None);
+        let exposure = CustodyExposure { evm: 1, solana: 2 };
+        assert_eq!(
+            limits.reached(&Chains::SOLANA, &exposure),
+            Some(CustodyLimitReached {
+                scope: "total".to_string(),
+                limit: 3
+            })
+        );
+        assert!(!CustodyLimits::default().is_enforced());
+    }
+}
@@ -0,0 +1,156 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use storage::db::{Batch, Database};
+
+use crate::{request_data, BRequest, HistoryEntry, Lamports, Result, Status, Timestamp};
+
+/// Rent exemption of a 165 bytes token account, the lamports a destination token account
+/// is prefunded with
+pub const TOKEN_ACCOUNT_RENT: Lamports = Lamports::new(2_039_280);
+
+/// Destination token account the mint of a request waits for. The destination account
+/// funds its address, the mint creates the account with these lamports
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DestinationAccountPending {
+    /// Associated token account of the destination account for the bridged mint
+    pub token_account: String,
+    /// Lamports the address must hold before the mint
+    pub lamports: Lamports,
+    pub since: Timestamp,
+}
+
+impl BRequest {
+    /// Parks a TokenReceived request until `token_account` is funded by its destination
+    /// account. Written as a compare and set, returns whether the request was parked
+    pub fn await_destination_account(
+        &mut self,
+        db: &Database,
+        token_account: &str,
+        now: Timestamp,
+    ) -> Result<bool> {
+        if self.status != Status::TokenReceived {
+            return Ok(false);
+        }
+        let pending = DestinationAccountPending {
+            token_account: token_account.to_string(),
+            lamports: TOKEN_ACCOUNT_RENT,
+            since: now,
+        };
+        self.history.push(HistoryEntry {
+            time: now,
+            event: format!(
+                "Waiting for the destination token account {} to hold {}",
+                pending.token_account, pending.lamports
+            ),
+        });
+        self.destination_account_pending = Some(pending);
+        self.status = Status::AwaitingDestinationAccount;
+        self.last_update = now;
+        self.write_if(db, Status::TokenReceived)
+    }
+
+    /// Destination token account funded, the request goes back to TokenReceived and its
+    /// mint can be sent. Returns whether the request was resumed
+    pub fn destination_account_funded(&mut self, db: &Database, now: Timestamp) -> Result<bool> {
+        if self.status != Status::AwaitingDestinationAccount {
+            return Ok(false);
+        }
+        let Some(pending) = self.destination_account_pending.take() else {
+            return Ok(false);
+        };
+        info!(
+            "Destination token account {} of request {} funded",
+            pending.token_account, self.id
+        );
+        self.history.push(HistoryEntry {
+            time: now,
+            event: format!("Destination token account {} funded", pending.token_account),
+        });
+        self.status = Status::TokenReceived;
+        self.last_update = now;
+        self.write_if(db, Status::AwaitingDestinationAccount)
+    }
+
+    /// Saves the request while the stored one is in `expected`, else reloads it
+    fn write_if(&mut self, db: &Database, expected: Status) -> Result<bool> {
+        if self.save_with_if(db, Batch::default(), expected)? {
+            info!("Request id {} status updated {:?}", self.id, self.status);
+            return Ok(true);
+        }
+        if let Some(stored) = request_data(&self.id, db)? {
+            *self = stored;
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod destination_account_test {
+    use storage::testing::each_engine;
+
+    use crate::{
+        request_data, BRequest, Chains, InputRequest, Status, Timestamp, TOKEN_ACCOUNT_RENT,
+    };
+
+    const NOW: Timestamp = Timestamp::from_secs(1_700_000_000);
+
+    fn received_request() -> BRequest {
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0x5FbDB2315678afecb367f032d93F642f64180aa3".to_string(),
+                token_id: "1".to_string(),
+                token_owner: "0x70997970C51812dc3A010C7d01b50e0d17dc79C8".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.status = Status::TokenReceived;
+        request
+    }
+
+    #[test]
+    fn test_parked_until_the_account_is_funded() {
+        for db in each_engine() {
+            let mut request = received_request();
+            request.save(&db).unwrap();
+
+            assert!(request
+                .await_destination_account(&db, "token-account", NOW)
+                .unwrap());
+            let stored = request_data(&request.id, &db).unwrap().unwrap();
+            assert_eq!(stored.status, Status::AwaitingDestinationAccount);
+            let pending = stored.destination_account_pending.unwrap();
+            assert_eq!(pending.token_account, "token-account");
+            assert_eq!(pending.lamports, TOKEN_ACCOUNT_RENT);
+            // Parked once
+            assert!(!request
+                .await_destination_account(&db, "token-account", NOW)
+                .unwrap());
+
+            assert!(request.destination_account_funded(&db, NOW).unwrap());
+            let stored = request_data(&request.id, &db).unwrap().unwrap();
+            assert_eq!(stored.status, Status::TokenReceived);
+            assert_eq!(stored.destination_account_pending, None);
+            assert!(!request.destination_account_funded(&db, NOW).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_stale_copy_does_not_park_a_moved_request() {
+        for db in each_engine() {
+            let mut request = received_request();
+            request.save(&db).unwrap();
+            let mut stale = request.clone();
+            request.status = Status::TokenMinted;
+            request.save(&db).unwrap();
+
+            assert!(!stale
+                .await_destination_account(&db, "token-account", NOW)
+                .unwrap());
+            assert_eq!(stale.status, Status::TokenMinted);
+            let stored = request_data(&request.id, &db).unwrap().unwrap();
+            assert_eq!(stored.status, Status::TokenMinted);
+        }
+    }
+}
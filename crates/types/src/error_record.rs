@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use storage::{
+    db::{Batch, Database},
+    keys::ERROR_HISTORY_PREFIX,
+};
+
+use crate::{redact_urls, BRequest, BridgeError, CallContext, ErrorClass, Result, Timestamp};
+
+/// Longest error message kept on a request, RPC errors can quote whole responses
+pub const MAX_ERROR_MESSAGE_LEN: usize = 2048;
+/// Errors before the latest one kept on the request, older ones are moved to their own
+/// records
+pub const ERROR_RING_SIZE: usize = 10;
+
+/// Part of the relayer an error comes from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorComponent {
+    /// EVM tx processor
+    Evm,
+    /// Solana tx processor
+    Solana,
+    Api,
+    Sweeper,
+}
+
+/// Error recorded on a request, its message bounded to `MAX_ERROR_MESSAGE_LEN`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorRecord {
+    pub at: Timestamp,
+    pub component: ErrorComponent,
+    pub category: ErrorClass,
+    pub message: String,
+    /// The message was cut at `MAX_ERROR_MESSAGE_LEN`
+    #[serde(default)]
+    pub truncated: bool,
+    /// Number of the error among the errors of the request, from 1
+    pub attempt: u32,
+}
+
+impl ErrorRecord {
+    /// Record of `message` with its URLs redacted, `attempt` is set when it is recorded
+    pub fn new(
+        component: ErrorComponent,
+        category: ErrorClass,
+        message: &str,
+        at: Timestamp,
+    ) -> Self {
+        let (message, truncated) = truncate_message(&redact_urls(message));
+        ErrorRecord {
+            at,
+            component,
+            category,
+            message,
+            truncated,
+            attempt: 0,
+        }
+    }
+
+    pub fn of_bridge_error(component: ErrorComponent, err: &BridgeError, at: Timestamp) -> Self {
+        Self::new(component, ErrorClass::of(err), &err.to_string(), at)
+    }
+}
+
+/// `message` cut to `MAX_ERROR_MESSAGE_LEN` bytes on a char boundary, and whether it was
+/// cut
+pub fn truncate_message(message: &str) -> (String, bool) {
+    if message.len() <= MAX_ERROR_MESSAGE_LEN {
+        return (message.to_string(), false);
+    }
+    let mut end = MAX_ERROR_MESSAGE_LEN;
+    while !message.is_char_boundary(end) {
+        end -= 1;
+    }
+    (message[..end].to_string(), true)
+}
+
+fn error_history_key(request_id: &str, attempt: u32) -> String {
+    format!("{ERROR_HISTORY_PREFIX}{request_id}:{attempt:010}")
+}
+
+/// Errors moved out of the ring of the request, oldest first
+pub fn spilled_errors(db: &Database, request_id: &str) -> Result<Vec<ErrorRecord>> {
+    let prefix = format!("{ERROR_HISTORY_PREFIX}{request_id}:");
+    Ok(db
+        .scan_prefix(&prefix, None)?
+        .into_iter()
+        .map(|(_, record)| record)
+        .collect())
+}
+
+impl BRequest {
+    /// Makes `record` the latest error, numbered after the previous one. The previous
+    /// error joins the ring, the oldest of a full ring is written to its own record in
+    /// `batch`
+    pub(crate) fn push_error(&mut self, mut record: ErrorRecord, batch: &mut Batch) -> Result<()> {
+        record.attempt = self
+            .last_error_record
+            .as_ref()
+            .map_or(1, |last| last.attempt + 1);
+        self.last_error = Some(record.message.clone());
+        if let Some(previous) = self.last_error_record.replace(record) {
+            self.previous_errors.push(previous);
+        }
+        while self.previous_errors.len() > ERROR_RING_SIZE {
+            let oldest = self.previous_errors.remove(0);
+            batch.put(error_history_key(&self.id, oldest.attempt), &oldest)?;
+        }
+        Ok(())
+    }
+
+    /// Keeps `record` on the request, the request stays retryable
+    pub fn record_error_record(
+        &mut self,
+        record: ErrorRecord,
+        context: Option<CallContext>,
+        db: &Database,
+    ) -> Result<()> {
+        let mut batch = Batch::default();
+        self.push_error(record, &mut batch)?;
+        self.last_error_context = context;
+        self.save_with(db, batch)
+    }
+}
+
+#[cfg(test)]
+mod error_record_test {
+    use storage::{keys::ERROR_HISTORY_PREFIX, testing::each_engine};
+
+    use crate::{
+        spilled_errors, truncate_message, BRequest, BridgeError, Chains, ErrorClass,
+        ErrorComponent, ErrorRecord, InputRequest, Timestamp, ERROR_RING_SIZE,
+        MAX_ERROR_MESSAGE_LEN,
+    };
+
+    fn request() -> BRequest {
+        BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xcontract".to_string(),
+                token_id: "1".to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        )
+    }
+
+    fn record(message: &str) -> ErrorRecord {
+        ErrorRecord::new(
+            ErrorComponent::Sweeper,
+            ErrorClass::Other,
+            message,
+            Timestamp::from_secs(1_700_000_000),
+        )
+    }
+
+    #[test]
+    fn test_messages_are_truncated() {
+        let long = "é".repeat(MAX_ERROR_MESSAGE_LEN);
+        let (message, truncated) = truncate_message(&long);
+        assert!(truncated);
+        assert!(message.len() <= MAX_ERROR_MESSAGE_LEN);
+        assert!(long.starts_with(&message));
+
+        let record = record(&format!("calling https://rpc.example.com/key {long}"));
+        assert!(record.truncated);
+        assert!(record
+            .message
+            .starts_with("calling https://rpc.example.com/[REDACTED]"));
+        assert_eq!(truncate_message("short"), ("short".to_string(), false));
+
+        let err = BridgeError::Other("insufficient funds for gas".to_string());
+        let record = ErrorRecord::of_bridge_error(
+            ErrorComponent::Evm,
+            &err,
+            Timestamp::from_secs(1_700_000_000),
+        );
+        assert_eq!(record.category, ErrorClass::InsufficientFunds);
+        assert!(!record.truncated);
+    }
+
+    #[test]
+    fn test_ring_rolls_over_and_spills() {
+        for db in each_engine() {
+            let mut request = request();
+            let total = ERROR_RING_SIZE + 4;
+            for attempt in 1..=total {
+                request
+                    .record_error_record(record(&format!("error {attempt}")), None, &db)
+                    .unwrap();
+            }
+
+            let stored = crate::request_data(&request.id, &db).unwrap().unwrap();
+            let last = stored.last_error_record.unwrap();
+            assert_eq!(last.attempt as usize, total);
+            assert_eq!(stored.last_error, Some(format!("error {total}")));
+            assert_eq!(stored.previous_errors.len(), ERROR_RING_SIZE);
+            assert_eq!(stored.previous_errors[0].attempt, 4);
+            assert_eq!(
+                stored.previous_errors.last().unwrap().attempt as usize,
+                total - 1
+            );
+
+            let spilled = spilled_errors(&db, &request.id).unwrap();
+            let attempts: Vec<u32> = spilled.iter().map(|record| record.attempt).collect();
+            assert_eq!(attempts, vec![1, 2, 3]);
+            assert_eq!(spilled[0].message, "error 1");
+            assert!(db
+                .scan_prefix::<ErrorRecord>(ERROR_HISTORY_PREFIX, None)
+                .unwrap()
+                .iter()
+                .all(|(key, _)| key.contains(request.id.as_str())));
+        }
+    }
+
+    #[test]
+    fn test_requests_stored_without_error_records() {
+        // Stored before the error records, with the plain last error only
+        let mut value = serde_json::to_value(request()).unwrap();
+        let object = value.as_object_mut().unwrap();
+        object.remove("last_error_record");
+        object.remove("previous_errors");
+        object.insert("last_error".to_string(), "connection reset".into());
+        let old: BRequest = serde_json::from_value(value).unwrap();
+        assert_eq!(old.last_error_record, None);
+        assert!(old.previous_errors.is_empty());
+
+        for db in each_engine() {
+            let mut old = old.clone();
+            old.record_error_record(record("timeout"), None, &db)
+                .unwrap();
+            let stored = crate::request_data(&old.id, &db).unwrap().unwrap();
+            assert_eq!(stored.last_error_record.unwrap().attempt, 1);
+            assert!(stored.previous_errors.is_empty());
+        }
+
+        // Records written without the truncation flag
+        let record: ErrorRecord = serde_json::from_str(
+            r#"{"at":1700000000000,"component":"evm","category":"TIMEOUT","message":"m","attempt":2}"#,
+        )
+        .unwrap();
+        assert!(!record.truncated);
+        assert_eq!(record.component, ErrorComponent::Evm);
+    }
+}
@@ -0,0 +1,62 @@
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::Timestamp;
+
+/// Last bridge event processed on a chain, at a block number on EVM and a slot on Solana
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct EventCursor {
+    pub height: u64,
+    pub processed_at: Timestamp,
+}
+
+/// Event cursor of a chain client, shared by its clones and moved by its event listener
+#[derive(Clone, Debug, Default)]
+pub struct SharedEventCursor(Arc<Mutex<Option<EventCursor>>>);
+
+impl SharedEventCursor {
+    /// Records an event processed at `height`, the cursor never moves back
+    pub fn record(&self, height: u64, now: Timestamp) {
+        let mut cursor = self.0.lock().unwrap();
+        if cursor.is_some_and(|cursor| cursor.height > height) {
+            return;
+        }
+        *cursor = Some(EventCursor {
+            height,
+            processed_at: now,
+        });
+    }
+
+    /// None until the first event since startup
+    pub fn get(&self) -> Option<EventCursor> {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod event_cursor_test {
+    use crate::{EventCursor, SharedEventCursor, Timestamp};
+
+    #[test]
+    fn test_cursor_only_moves_forward() {
+        let cursor = SharedEventCursor::default();
+        assert_eq!(cursor.get(), None);
+
+        cursor.clone().record(100, Timestamp::from_millis(1_000));
+        // Late events of an earlier block don't move it back
+        cursor.record(90, Timestamp::from_millis(2_000));
+        assert_eq!(
+            cursor.get(),
+            Some(EventCursor {
+                height: 100,
+                processed_at: Timestamp::from_millis(1_000),
+            })
+        );
+        cursor.record(100, Timestamp::from_millis(3_000));
+        assert_eq!(
+            cursor.get().unwrap().processed_at,
+            Timestamp::from_millis(3_000)
+        );
+    }
+}
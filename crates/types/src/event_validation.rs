@@ -0,0 +1,301 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use alloy::primitives::U256;
+use log::error;
+use serde::{Deserialize, Serialize};
+use storage::{db::Database, keys::QUARANTINE_PREFIX};
+
+use crate::{request_data, BRequest, Chains, Result, Timestamp};
+
+/// Chain event whose token data differs from the stored request, kept for investigation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuarantinedEvent {
+    pub request_id: String,
+    pub chain: Chains,
+    pub event_contract_or_mint: String,
+    pub event_token_id: Option<String>,
+    pub stored_contract_or_mint: String,
+    pub stored_token_id: String,
+    pub time: Timestamp,
+}
+
+pub fn quarantine_key(chain: &Chains, request_id: &str) -> String {
+    format!("{QUARANTINE_PREFIX}{:?}:{request_id}", chain)
+}
+
+pub fn quarantined_events(db: &Database, limit: Option<usize>) -> Result<Vec<QuarantinedEvent>> {
+    let events = db
+        .scan_prefix::<QuarantinedEvent>(QUARANTINE_PREFIX, limit)?
+        .into_iter()
+        .map(|(_, event)| event)
+        .collect();
+    Ok(events)
+}
+
+/// Compares the token data of a NewRequest event with the stored request input. Contracts
+/// are compared case insensitive and token ids as numbers, Solana events have no token id
+pub fn event_matches_request(
+    request: &BRequest,
+    contract_or_mint: &str,
+    token_id: Option<&str>,
+) -> bool {
+    let stored_contract = request.input.contract_or_mint.trim();
+    let contract_matches = match request.input.origin_network {
+        Chains::EVM => stored_contract.eq_ignore_ascii_case(contract_or_mint.trim()),
+        Chains::SOLANA => stored_contract == contract_or_mint.trim(),
+    };
+
+    let token_id_matches = match token_id {
+        Some(token_id) => match (
+            U256::from_str(request.input.token_id.trim()),
+            U256::from_str(token_id.trim()),
+        ) {
+            (Ok(stored), Ok(event)) => stored == event,
+            _ => false,
+        },
+        None => true,
+    };
+
+    contract_matches && token_id_matches
+}
+
+/// Checks NewRequest events against the stored requests, counting and quarantining mismatches
+#[derive(Clone, Debug, Default)]
+pub struct EventValidator {
+    evm_mismatched: Arc<AtomicU64>,
+    solana_mismatched: Arc<AtomicU64>,
+}
+
+impl EventValidator {
+    /// Returns false when the event must be skipped. Events of unknown requests pass,
+    /// the custody check already ignores them. The event id may be the id of the request
+    /// or its alias under the other id scheme
+    pub fn validate(
+        &self,
+        db: &Database,
+        chain: &Chains,
+        request_id: &str,
+        contract_or_mint: &str,
+        token_id: Option<&str>,
+        now: Timestamp,
+    ) -> Result<bool> {
+        let Some(request) = request_data(request_id, db)? else {
+            return Ok(true);
+        };
+        if request.answers_to(request_id)
+            && event_matches_request(&request, contract_or_mint, token_id)
+        {
+            return Ok(true);
+        }
+
+        error!(
+            "{:?} event for request {} does not match the stored request, event token {} {:?}, stored token {} {}",
+            chain,
+            request_id,
+            contract_or_mint,
+            token_id,
+            request.input.contract_or_mint,
+            request.input.token_id
+        );
+        self.counter(chain).fetch_add(1, Ordering::Relaxed);
+
+        let event = QuarantinedEvent {
+            request_id: request_id.to_string(),
+            chain: chain.clone(),
+            event_contract_or_mint: contract_or_mint.to_string(),
+            event_token_id: token_id.map(|token_id| token_id.to_string()),
+            stored_contract_or_mint: request.input.contract_or_mint.clone(),
+            stored_token_id: request.input.token_id.clone(),
+            time: now,
+        };
+        db.write_value(quarantine_key(chain, request_id), &event)?;
+        Ok(false)
+    }
+
+    /// Mismatched events since startup
+    pub fn mismatched_count(&self, chain: &Chains) -> u64 {
+        self.counter(chain).load(Ordering::Relaxed)
+    }
+
+    fn counter(&self, chain: &Chains) -> &AtomicU64 {
+        match chain {
+            Chains::EVM => &self.evm_mismatched,
+            Chains::SOLANA => &self.solana_mismatched,
+        }
+    }
+}
+
+#[cfg(test)]
+mod event_validation_test {
+    use crate::{
+        quarantined_events, BRequest, Chains, EventValidator, IdScheme, InputRequest, Timestamp,
+    };
+    use storage::{db::Database, testing::each_engine};
+
+    fn saved_request(db: &Database, contract_or_mint: &str, origin_network: Chains) -> BRequest {
+        let request = BRequest::new(
+            InputRequest {
+                contract_or_mint: contract_or_mint.to_string(),
+                token_id: "42".to_string(),
+                token_owner: "owner".to_string(),
+                origin_network,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.save(db).unwrap();
+        request
+    }
+
+    #[test]
+    fn test_evm_events_are_validated() {
+        for db in each_engine() {
+            let validator = EventValidator::default();
+            let contract = "0x5fbdb2315678afecb367f032d93f642f64180aa3";
+            let request = saved_request(&db, contract, Chains::EVM);
+
+            // Checksummed address and hex token id are the same token
+            assert!(validator
+                .validate(
+                    &db,
+                    &Chains::EVM,
+                    &request.id,
+                    "0x5FbDB2315678afecb367f032d93F642f64180aa3",
+                    Some("0x2a"),
+                    Timestamp::now(),
+                )
+                .unwrap());
+
+            // Other token id
+            assert!(!validator
+                .validate(
+                    &db,
+                    &Chains::EVM,
+                    &request.id,
+                    contract,
+                    Some("43"),
+                    Timestamp::now()
+                )
+                .unwrap());
+            // Other contract
+            assert!(!validator
+                .validate(
+                    &db,
+                    &Chains::EVM,
+                    &request.id,
+                    "0x0000000000000000000000000000000000000001",
+                    Some("42"),
+                    Timestamp::now(),
+                )
+                .unwrap());
+
+            assert_eq!(validator.mismatched_count(&Chains::EVM), 2);
+            assert_eq!(validator.mismatched_count(&Chains::SOLANA), 0);
+            let quarantined = quarantined_events(&db, None).unwrap();
+            assert_eq!(quarantined.len(), 1);
+            assert_eq!(quarantined[0].request_id, request.id);
+
+            // Unknown requests are left to the custody check
+            assert!(validator
+                .validate(
+                    &db,
+                    &Chains::EVM,
+                    "0xunknown",
+                    contract,
+                    Some("1"),
+                    Timestamp::now()
+                )
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn test_solana_events_are_validated() {
+        for db in each_engine() {
+            let validator = EventValidator::default();
+            let mint = "So11111111111111111111111111111111111111112";
+            let request = saved_request(&db, mint, Chains::SOLANA);
+
+            assert!(validator
+                .validate(
+                    &db,
+                    &Chains::SOLANA,
+                    &request.id,
+                    mint,
+                    None,
+                    Timestamp::now()
+                )
+                .unwrap());
+            // Base58 is case sensitive
+            assert!(!validator
+                .validate(
+                    &db,
+                    &Chains::SOLANA,
+                    &request.id,
+                    &mint.to_lowercase(),
+                    None,
+                    Timestamp::now()
+                )
+                .unwrap());
+            assert_eq!(validator.mismatched_count(&Chains::SOLANA), 1);
+            assert_eq!(quarantined_events(&db, None).unwrap().len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_events_match_either_id_scheme() {
+        for db in each_engine() {
+            let validator = EventValidator::default();
+            let contract = "0x5fbdb2315678afecb367f032d93f642f64180aa3";
+            for scheme in IdScheme::ALL {
+                let input = InputRequest {
+                    contract_or_mint: contract.to_string(),
+                    token_id: "42".to_string(),
+                    token_owner: format!("{scheme:?}"),
+                    origin_network: Chains::EVM,
+                    destination_account: "destination".to_string(),
+                };
+                let request = BRequest::with_id_scheme(input, scheme, Timestamp::now());
+                request.save(&db).unwrap();
+
+                for id in [&request.id, request.alias_id.as_ref().unwrap()] {
+                    assert!(
+                        validator
+                            .validate(
+                                &db,
+                                &Chains::EVM,
+                                id,
+                                contract,
+                                Some("42"),
+                                Timestamp::now()
+                            )
+                            .unwrap(),
+                        "{scheme:?} {id}"
+                    );
+                }
+            }
+
+            // Alias resolving to a request that doesn't answer to the event id
+            let request = saved_request(&db, contract, Chains::EVM);
+            let stray = IdScheme::ChainAware.request_id(&request.input);
+            db.write_alias(&stray, &request.id).unwrap();
+            assert!(!validator
+                .validate(
+                    &db,
+                    &Chains::EVM,
+                    &stray,
+                    contract,
+                    Some("42"),
+                    Timestamp::now()
+                )
+                .unwrap());
+            assert_eq!(validator.mismatched_count(&Chains::EVM), 1);
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use std::fmt;
+
+use crate::Chains;
+
+/// Placeholder of the transaction hash in a block explorer template
+pub const EXPLORER_PLACEHOLDER: &str = "{}";
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ExplorerError {
+    #[error("Block explorer {0:?} is not an https URL")]
+    NotHttps(String),
+
+    #[error("Block explorer {0:?} has an invalid host")]
+    InvalidHost(String),
+
+    #[error("Block explorer {0:?} can't have credentials, a fragment or unescaped characters")]
+    UnsafeCharacters(String),
+
+    #[error("Block explorer {0:?} can't have more than one {{}} placeholder")]
+    InvalidPlaceholder(String),
+}
+
+/// Validated block explorer template, an https URL with one `{}` placeholder for the
+/// transaction hash. A base without placeholder links to `<base>/tx/{}`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExplorerBase(String);
+
+impl ExplorerBase {
+    pub fn parse(url: &str) -> Result<Self, ExplorerError> {
+        let url = url.trim();
+        let Some(rest) = url.strip_prefix("https://") else {
+            return Err(ExplorerError::NotHttps(url.to_string()));
+        };
+        if !rest.chars().all(is_template_char) {
+            return Err(ExplorerError::UnsafeCharacters(url.to_string()));
+        }
+
+        let host = rest.split(['/', '?']).next().unwrap_or_default();
+        let hostname = host.split_once(':').map_or(host, |(hostname, _)| hostname);
+        if hostname.is_empty()
+            || hostname.starts_with(['.', '-'])
+            || hostname.ends_with('-')
+            || !hostname
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.'))
+        {
+            return Err(ExplorerError::InvalidHost(url.to_string()));
+        }
+
+        let placeholders = url.matches(EXPLORER_PLACEHOLDER).count();
+        let braces = url.matches(['{', '}']).count();
+        if placeholders > 1 || braces != placeholders * 2 {
+            return Err(ExplorerError::InvalidPlaceholder(url.to_string()));
+        }
+        if placeholders == 1 {
+            return Ok(ExplorerBase(url.to_string()));
+        }
+
+        let (path, query) = match url.split_once('?') {
+            Some((path, query)) => (path, format!("?{query}")),
+            None => (url, String::new()),
+        };
+        Ok(ExplorerBase(format!(
+            "{}/tx/{EXPLORER_PLACEHOLDER}{query}",
+            path.trim_end_matches('/')
+        )))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ExplorerBase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Unreserved characters, the path and query separators, escapes and the placeholder
+fn is_template_char(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || matches!(
+            c,
+            '-' | '.' | '_' | '~' | '/' | ':' | '?' | '=' | '&' | '%' | '{' | '}'
+        )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExplorerItem {
+    Tx,
+    Address,
+}
+
+/// Transaction hashes and addresses of each chain, the only values put in explorer links
+pub fn valid_explorer_value(chain: &Chains, item: ExplorerItem, value: &str) -> bool {
+    match chain {
+        Chains::EVM => {
+            let digits = match item {
+                ExplorerItem::Tx => 64,
+                ExplorerItem::Address => 40,
+            };
+            value.strip_prefix("0x").is_some_and(|hex| {
+                hex.len() == digits && hex.chars().all(|c| c.is_ascii_hexdigit())
+            })
+        }
+        Chains::SOLANA => {
+            let lengths = match item {
+                ExplorerItem::Tx => 64..=88,
+                ExplorerItem::Address => 32..=44,
+            };
+            lengths.contains(&value.len()) && value.chars().all(is_base58_char)
+        }
+    }
+}
+
+fn is_base58_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() && !matches!(c, '0' | 'O' | 'I' | 'l')
+}
+
+/// Percent encodes everything but the unreserved characters
+fn encode_path_segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Explorer link of a transaction, None without an explorer or when the hash is not a
+/// transaction hash of the chain
+pub fn explorer_link(base: Option<&ExplorerBase>, chain: &Chains, tx_hash: &str) -> Option<String> {
+    let base = base?;
+    if !valid_explorer_value(chain, ExplorerItem::Tx, tx_hash) {
+        return None;
+    }
+    Some(
+        base.as_str()
+            .replacen(EXPLORER_PLACEHOLDER, &encode_path_segment(tx_hash), 1),
+    )
+}
+
+/// Link of a transaction of either chain, told apart by the hash format
+pub fn tx_explorer_link(
+    evm: Option<&ExplorerBase>,
+    solana: Option<&ExplorerBase>,
+    tx_hash: &str,
+) -> Option<String> {
+    explorer_link(evm, &Chains::EVM, tx_hash)
+        .or_else(|| explorer_link(solana, &Chains::SOLANA, tx_hash))
+}
+
+/// Explorer link of a block (EVM) or slot (Solana). The `/tx/{}` path of the template
+/// points to `/block/<height>`, the block page of Etherscan, Solscan and the Solana
+/// Explorer alike. None without an explorer or for templates without a `/tx/{}` path
+pub fn block_explorer_link(base: Option<&ExplorerBase>, height: u64) -> Option<String> {
+    let base = base?.as_str();
+    let tx_path = format!("/tx/{EXPLORER_PLACEHOLDER}");
+    base.contains(&tx_path)
+        .then(|| base.replacen(&tx_path, &format!("/block/{height}"), 1))
+}
+
+#[cfg(test)]
+mod explorer_test {
+    use crate::{
+        block_explorer_link, explorer_link, tx_explorer_link, valid_explorer_value, Chains,
+        ExplorerBase, ExplorerError, ExplorerItem,
+    };
+
+    const EVM_TX: &str = "0x5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060";
+    const SOLANA_TX: &str =
+        "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW";
+
+    #[test]
+    fn test_explorer_base_validation() {
+        assert_eq!(
+            ExplorerBase::parse(" https://etherscan.io/ ")
+                .unwrap()
+                .as_str(),
+            "https://etherscan.io/tx/{}"
+        );
+        assert_eq!(
+            ExplorerBase::parse("https://explorer.solana.com?cluster=devnet")
+                .unwrap()
+                .as_str(),
+            "https://explorer.solana.com/tx/{}?cluster=devnet"
+        );
+        assert_eq!(
+            ExplorerBase::parse("https://solscan.io/tx/{}?cluster=devnet")
+                .unwrap()
+                .as_str(),
+            "https://solscan.io/tx/{}?cluster=devnet"
+        );
+
+        for (url, error) in [
+            (
+                "http://etherscan.io",
+                ExplorerError::NotHttps("http://etherscan.io".into()),
+            ),
+            (
+                "javascript:alert(1)",
+                ExplorerError::NotHttps("javascript:alert(1)".into()),
+            ),
+            (
+                "https://etherscan.io/tx/{}#frag",
+                ExplorerError::UnsafeCharacters("https://etherscan.io/tx/{}#frag".into()),
+            ),
+            (
+                "https://user@evil.example",
+                ExplorerError::UnsafeCharacters("https://user@evil.example".into()),
+            ),
+            (
+                "https://etherscan.io/\"><script>",
+                ExplorerError::UnsafeCharacters("https://etherscan.io/\"><script>".into()),
+            ),
+            (
+                "https://{}.evil.example",
+                ExplorerError::InvalidHost("https://{}.evil.example".into()),
+            ),
+            (
+                "https:///tx",
+                ExplorerError::InvalidHost("https:///tx".into()),
+            ),
+            (
+                "https://etherscan.io/tx/{}/{}",
+                ExplorerError::InvalidPlaceholder("https://etherscan.io/tx/{}/{}".into()),
+            ),
+            (
+                "https://etherscan.io/tx/{hash}",
+                ExplorerError::InvalidPlaceholder("https://etherscan.io/tx/{hash}".into()),
+            ),
+        ] {
+            assert_eq!(ExplorerBase::parse(url), Err(error), "{url}");
+        }
+    }
+
+    #[test]
+    fn test_links_of_malicious_values_are_null() {
+        let evm = ExplorerBase::parse("https://testnet.bscscan.com/tx/{}").ok();
+        let solana = ExplorerBase::parse("https://solscan.io/tx/{}?cluster=devnet").ok();
+
+        assert_eq!(
+            explorer_link(evm.as_ref(), &Chains::EVM, EVM_TX),
+            Some(format!("https://testnet.bscscan.com/tx/{EVM_TX}"))
+        );
+        assert_eq!(
+            explorer_link(solana.as_ref(), &Chains::SOLANA, SOLANA_TX),
+            Some(format!("https://solscan.io/tx/{SOLANA_TX}?cluster=devnet"))
+        );
+
+        for value in [
+            "javascript:alert(document.cookie)",
+            "../../admin",
+            "0x5c50\"><script>alert(1)</script>",
+            &format!("{EVM_TX}?redirect=evil"),
+            &format!("{EVM_TX}#"),
+            &format!("{EVM_TX}/../../admin"),
+            "",
+        ] {
+            assert_eq!(
+                explorer_link(evm.as_ref(), &Chains::EVM, value),
+                None,
+                "{value}"
+            );
+            assert_eq!(
+                explorer_link(solana.as_ref(), &Chains::SOLANA, value),
+                None,
+                "{value}"
+            );
+        }
+        // Base58 has no 0, O, I or l
+        let not_base58 = SOLANA_TX.replace('5', "0");
+        assert_eq!(
+            explorer_link(solana.as_ref(), &Chains::SOLANA, &not_base58),
+            None
+        );
+
+        assert_eq!(
+            tx_explorer_link(evm.as_ref(), solana.as_ref(), SOLANA_TX),
+            Some(format!("https://solscan.io/tx/{SOLANA_TX}?cluster=devnet"))
+        );
+        assert_eq!(
+            tx_explorer_link(evm.as_ref(), solana.as_ref(), "0xtx123"),
+            None
+        );
+
+        // No explorer configured
+        assert_eq!(explorer_link(None, &Chains::EVM, EVM_TX), None);
+    }
+
+    #[test]
+    fn test_explorer_addresses() {
+        assert!(valid_explorer_value(
+            &Chains::EVM,
+            ExplorerItem::Address,
+            "0x5FbDB2315678afecb367f032d93F642f64180aa3"
+        ));
+        assert!(valid_explorer_value(
+            &Chains::SOLANA,
+            ExplorerItem::Address,
+            "11111111111111111111111111111111"
+        ));
+        for address in [
+            "0x5FbDB2315678afecb367f032d93F642f64180aa3/../x",
+            EVM_TX,
+            "<svg/onload=1>",
+        ] {
+            assert!(!valid_explorer_value(
+                &Chains::EVM,
+                ExplorerItem::Address,
+                address
+            ));
+            assert!(!valid_explorer_value(
+                &Chains::SOLANA,
+                ExplorerItem::Address,
+                address
+            ));
+        }
+    }
+
+    #[test]
+    fn test_block_links() {
+        let evm = ExplorerBase::parse("https://etherscan.io").ok();
+        let solana = ExplorerBase::parse("https://explorer.solana.com?cluster=devnet").ok();
+        assert_eq!(
+            block_explorer_link(evm.as_ref(), 19_000_000),
+            Some("https://etherscan.io/block/19000000".to_string())
+        );
+        assert_eq!(
+            block_explorer_link(solana.as_ref(), 250_000_123),
+            Some("https://explorer.solana.com/block/250000123?cluster=devnet".to_string())
+        );
+
+        // Templates without a tx path have no known block page
+        let custom = ExplorerBase::parse("https://explorer.example/search?q={}").ok();
+        assert_eq!(block_explorer_link(custom.as_ref(), 1), None);
+        assert_eq!(block_explorer_link(None, 1), None);
+    }
+}
@@ -0,0 +1,409 @@
+use log::error;
+use serde::{Deserialize, Serialize};
+use storage::db::{Batch, Database};
+
+use crate::{
+    fast_path::{is_blockhash_expired, is_nonce_conflict},
+    stage_pending_addition, truncate_message, BRequest, BridgeError, ErrorComponent, ErrorRecord,
+    FastPath, HistoryEntry, RequestId, Result, Status, Timestamp,
+};
+
+/// Failed sweep attempts of the same status after which the request is given up, only the
+/// attempts failing on the request itself count, see `ErrorClass::is_transient`
+pub const MAX_SWEEP_ATTEMPTS: usize = 20;
+/// Failed attempts kept on a request, the oldest transient ones are dropped first
+pub const MAX_KEPT_ATTEMPTS: usize = 50;
+
+/// Class of the error of a failed attempt, drives the suggested actions
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorClass {
+    InsufficientFunds,
+    NotAuthorizedBackend,
+    MissingSigner,
+    GasLimitExceeded,
+    Timeout,
+    /// Endpoint refusing or dropping the connection, or answering a gateway error
+    Unreachable,
+    Storage,
+    CorruptedData,
+    AccountAlreadyInitialized,
+    /// Solana blockhash not found or expired, handled by `FastPath::FreshBlockhash`
+    BlockhashExpired,
+    /// EVM nonce too low or transaction already known, handled by `FastPath::NonceResync`
+    NonceConflict,
+    /// Token minted to another account than the destination, found by
+    /// `verify_mint_recipient` and never by `ErrorClass::of`
+    DestinationMismatch,
+    Other,
+}
+
+impl ErrorClass {
+    pub fn of(err: &BridgeError) -> Self {
+        match err {
+            BridgeError::Call { source, .. } => ErrorClass::of(source),
+            BridgeError::Db(_) => ErrorClass::Storage,
+            BridgeError::Timeout(_) => ErrorClass::Timeout,
+            BridgeError::CorruptedData(_) => ErrorClass::CorruptedData,
+            BridgeError::AccountAlreadyInitialized(_) => ErrorClass::AccountAlreadyInitialized,
+            BridgeError::NotAuthorizedBackend(_) => ErrorClass::NotAuthorizedBackend,
+            BridgeError::GasLimitExceeded(_) => ErrorClass::GasLimitExceeded,
+            BridgeError::MissingSigner(_) => ErrorClass::MissingSigner,
+            BridgeError::Other(message) if is_insufficient_funds(message) => {
+                ErrorClass::InsufficientFunds
+            }
+            BridgeError::Other(message) if is_blockhash_expired(message) => {
+                ErrorClass::BlockhashExpired
+            }
+            BridgeError::Other(message) if is_nonce_conflict(message) => ErrorClass::NonceConflict,
+            BridgeError::Other(message) if is_unreachable(message) => ErrorClass::Unreachable,
+            BridgeError::Other(_) => ErrorClass::Other,
+        }
+    }
+
+    /// Failures of the relayer environment (endpoints, storage, wallet) failing every
+    /// request alike until fixed. They don't use the retries of the request, an outage or
+    /// an empty wallet gives no request up
+    pub fn is_transient(self) -> bool {
+        matches!(
+            self,
+            ErrorClass::InsufficientFunds
+                | ErrorClass::NotAuthorizedBackend
+                | ErrorClass::MissingSigner
+                | ErrorClass::Timeout
+                | ErrorClass::Unreachable
+                | ErrorClass::Storage
+                | ErrorClass::BlockhashExpired
+                | ErrorClass::NonceConflict
+        )
+    }
+
+    /// Fast path of the tx processors for the errors of this class
+    pub fn fast_path(self) -> Option<FastPath> {
+        match self {
+            ErrorClass::BlockhashExpired => Some(FastPath::FreshBlockhash),
+            ErrorClass::NonceConflict => Some(FastPath::NonceResync),
+            _ => None,
+        }
+    }
+}
+
+/// Node errors of a wallet that can't pay for the transaction, on both chains
+fn is_insufficient_funds(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("insufficient funds") || message.contains("insufficient lamports")
+}
+
+/// Transport errors and gateway answers of an endpoint that can't be reached
+fn is_unreachable(message: &str) -> bool {
+    let message = message.to_lowercase();
+    [
+        "connection reset",
+        "connection refused",
+        "connection closed",
+        "broken pipe",
+        "error sending request",
+        "dns error",
+        "timed out",
+        "429 too many requests",
+        "502 bad gateway",
+        "503 service unavailable",
+        "504 gateway timeout",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+/// Errors carrying the output of a transaction simulation or an `eth_call`
+fn is_simulation_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("simulation failed") || message.contains("execution reverted")
+}
+
+/// What an operator should look at for a failed request
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SuggestedAction {
+    /// Fund the relayer wallet
+    CheckBalance,
+    /// Check the relayer wallet is the backend of the bridge and the transaction signers
+    CheckAuthority,
+    /// Check the chain RPC endpoints
+    CheckRpc,
+    /// The stored request data can't be used as is
+    InspectRequestData,
+    /// Return the token in custody by hand, the queued refund is sent by the same wallet
+    ManualRefund,
+    /// Move the token minted to the wrong account to the destination, or burn it and
+    /// return the token in custody
+    RecoverMintedToken,
+    /// No known cause, read the attempts
+    Investigate,
+}
+
+impl SuggestedAction {
+    fn of(class: ErrorClass) -> Self {
+        match class {
+            ErrorClass::InsufficientFunds => SuggestedAction::CheckBalance,
+            ErrorClass::NotAuthorizedBackend | ErrorClass::MissingSigner => {
+                SuggestedAction::CheckAuthority
+            }
+            ErrorClass::Timeout
+            | ErrorClass::Unreachable
+            | ErrorClass::BlockhashExpired
+            | ErrorClass::NonceConflict => SuggestedAction::CheckRpc,
+            ErrorClass::CorruptedData => SuggestedAction::InspectRequestData,
+            ErrorClass::DestinationMismatch => SuggestedAction::RecoverMintedToken,
+            ErrorClass::GasLimitExceeded
+            | ErrorClass::Storage
+            | ErrorClass::AccountAlreadyInitialized
+            | ErrorClass::Other => SuggestedAction::Investigate,
+        }
+    }
+}
+
+/// Failed sweep attempt of a request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FailedAttempt {
+    pub time: Timestamp,
+    pub status: Status,
+    pub class: ErrorClass,
+    pub error: String,
+}
+
+/// Heights of the last chain events processed when the report was generated
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct ChainCursors {
+    pub evm_block: Option<u64>,
+    pub solana_slot: Option<u64>,
+}
+
+/// What was attempted for a request given up after its last retry
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FailureReport {
+    pub request_id: RequestId,
+    pub generated_at: Timestamp,
+    /// Status the attempts failed in
+    pub status: Status,
+    pub attempts: Vec<FailedAttempt>,
+    /// Last error with the output of a simulation or a call
+    pub last_simulation: Option<String>,
+    pub tx_hashes: Vec<String>,
+    pub cursors: ChainCursors,
+    pub suggested_actions: Vec<SuggestedAction>,
+}
+
+/// Actions for the classes of `attempts` in the order they first failed. A manual refund
+/// is added when the token is in custody and the wallet itself is the problem
+pub fn suggested_actions(
+    attempts: &[FailedAttempt],
+    holds_origin_token: bool,
+) -> Vec<SuggestedAction> {
+    let mut actions = vec![];
+    for attempt in attempts {
+        let action = SuggestedAction::of(attempt.class);
+        if !actions.contains(&action) {
+            actions.push(action);
+        }
+    }
+    let wallet_failure = actions.iter().any(|action| {
+        matches!(
+            action,
+            SuggestedAction::CheckBalance | SuggestedAction::CheckAuthority
+        )
+    });
+    if holds_origin_token && wallet_failure {
+        actions.push(SuggestedAction::ManualRefund);
+    }
+    actions
+}
+
+impl BRequest {
+    /// Keeps a failed sweep attempt, attempts of an earlier status are dropped, and its
+    /// error record. Returns the number of attempts in the current status counting toward
+    /// `MAX_SWEEP_ATTEMPTS`
+    pub fn record_failed_attempt(
+        &mut self,
+        err: &BridgeError,
+        db: &Database,
+        now: Timestamp,
+    ) -> Result<usize> {
+        let status = self.status.clone();
+        self.failed_attempts
+            .retain(|attempt| attempt.status == status);
+        self.failed_attempts.push(FailedAttempt {
+            time: now,
+            status,
+            class: ErrorClass::of(err),
+            error: truncate_message(&err.to_string()).0,
+        });
+        if self.failed_attempts.len() > MAX_KEPT_ATTEMPTS {
+            let dropped = self
+                .failed_attempts
+                .iter()
+                .position(|attempt| attempt.class.is_transient())
+                .unwrap_or(0);
+            self.failed_attempts.remove(dropped);
+        }
+        let mut batch = Batch::default();
+        self.push_error(
+            ErrorRecord::of_bridge_error(ErrorComponent::Sweeper, err, now),
+            &mut batch,
+        )?;
+        self.save_with(db, batch)?;
+        Ok(self.counted_attempts())
+    }
+
+    /// Failed attempts of the current status counting toward `MAX_SWEEP_ATTEMPTS`
+    pub fn counted_attempts(&self) -> usize {
+        self.failed_attempts
+            .iter()
+            .filter(|attempt| !attempt.class.is_transient())
+            .count()
+    }
+
+    pub fn retries_exhausted(&self) -> bool {
+        self.counted_attempts() >= MAX_SWEEP_ATTEMPTS
+    }
+
+    /// Gives the request up: the report of its attempts is kept on it and it is parked in
+    /// NeedsIntervention for an operator, nothing is canceled or refunded. The sweep leaves
+    /// it alone until reprocessed
+    pub fn park_with_report(
+        &mut self,
+        db: &Database,
+        cursors: ChainCursors,
+        now: Timestamp,
+    ) -> Result<FailureReport> {
+        let attempts = self.failed_attempts.clone();
+        let report = FailureReport {
+            request_id: self.id.clone(),
+            generated_at: now,
+            status: self.status.clone(),
+            last_simulation: attempts
+                .iter()
+                .rev()
+                .find(|attempt| is_simulation_error(&attempt.error))
+                .map(|attempt| attempt.error.clone()),
+            tx_hashes: self.tx_hashes.clone(),
+            cursors,
+            suggested_actions: suggested_actions(&attempts, self.holds_origin_token()),
+            attempts,
+        };
+        error!(
+            "ALERT request {} held for an operator after {} failed attempts in {:?}, suggested actions {:?}",
+            self.id,
+            self.counted_attempts(),
+            self.status,
+            report.suggested_actions
+        );
+        self.history.push(HistoryEntry {
+            time: now,
+            event: format!(
+                "Held for an operator after {} failed attempts in {:?}",
+                self.counted_attempts(),
+                self.status
+            ),
+        });
+        self.failure_report = Some(report.clone());
+        self.status = Status::NeedsIntervention;
+        self.last_update = now;
+        self.save(db)?;
+        Ok(report)
+    }
+
+    /// Request parked by `park_with_report`, resumable once the cause is fixed. Requests
+    /// minted to the wrong account are not
+    pub fn parked_by_retries(&self) -> bool {
+        self.status == Status::NeedsIntervention
+            && self.failure_report.as_ref().is_some_and(|report| {
+                !report
+                    .attempts
+                    .iter()
+                    .any(|attempt| attempt.class == ErrorClass::DestinationMismatch)
+            })
+    }
+
+    /// Puts a request parked by `park_with_report` back in the status it failed in, with
+    /// its retries reset, and back in the pending queue
+    pub fn resume_parked(&mut self, db: &Database, now: Timestamp) -> Result<()> {
+        let Some(report) = self.failure_report.take() else {
+            return Ok(());
+        };
+        self.history.push(HistoryEntry {
+            time: now,
+            event: format!("Resumed in {:?} by an operator", report.status),
+        });
+        self.status = report.status;
+        self.failed_attempts.clear();
+        self.last_update = now;
+        let mut batch = Batch::default();
+        stage_pending_addition(&self.id, db, &mut batch)?;
+        self.save_with(db, batch)
+    }
+}
+
+#[cfg(test)]
+mod failure_report_test {
+    use crate::{
+        suggested_actions, BridgeError, ErrorClass, FailedAttempt, Status, SuggestedAction,
+        Timestamp,
+    };
+
+    fn attempt(err: BridgeError) -> FailedAttempt {
+        FailedAttempt {
+            time: Timestamp::from_secs(1_700_000_000),
+            status: Status::TokenReceived,
+            class: ErrorClass::of(&err),
+            error: err.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_error_classes_and_actions() {
+        assert_eq!(
+            ErrorClass::of(&BridgeError::Other(
+                "Transfer: insufficient lamports 10, need 5000".to_string()
+            )),
+            ErrorClass::InsufficientFunds
+        );
+        assert_eq!(
+            ErrorClass::of(&BridgeError::Other("connection reset".to_string())),
+            ErrorClass::Unreachable
+        );
+        assert!(ErrorClass::Unreachable.is_transient());
+        assert!(ErrorClass::InsufficientFunds.is_transient());
+        assert!(!ErrorClass::Other.is_transient());
+        assert!(!ErrorClass::AccountAlreadyInitialized.is_transient());
+
+        let attempts = [
+            attempt(BridgeError::Other("execution reverted".to_string())),
+            attempt(BridgeError::MissingSigner("signer".to_string())),
+            attempt(BridgeError::NotAuthorizedBackend("signer".to_string())),
+        ];
+        assert_eq!(
+            suggested_actions(&attempts, false),
+            vec![
+                SuggestedAction::Investigate,
+                SuggestedAction::CheckAuthority
+            ]
+        );
+        assert_eq!(
+            suggested_actions(&attempts, true),
+            vec![
+                SuggestedAction::Investigate,
+                SuggestedAction::CheckAuthority,
+                SuggestedAction::ManualRefund
+            ]
+        );
+        // The refund is left to the relayer when the wallet works
+        assert_eq!(
+            suggested_actions(&attempts[..1], true),
+            vec![SuggestedAction::Investigate]
+        );
+        assert_eq!(
+            serde_json::to_value(SuggestedAction::CheckBalance).unwrap(),
+            "CHECK_BALANCE"
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use log::warn;
+
+use crate::{private_text, BridgeError, ErrorClass, Result};
+
+/// Immediate resends of a transaction failing with a fast path error, the error then goes
+/// to the regular retries of the sweep
+pub const MAX_FAST_PATH_RESENDS: usize = 3;
+
+/// Transient send failures the tx processors fix themselves, without backoff and without
+/// using the retries of the request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FastPath {
+    /// The Solana blockhash expired before the transaction landed, it is signed again
+    /// with a fresh one
+    FreshBlockhash,
+    /// The EVM nonce was already used, the nonce is read again from the node
+    NonceResync,
+}
+
+impl FastPath {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FastPath::FreshBlockhash => "fresh_blockhash",
+            FastPath::NonceResync => "nonce_resync",
+        }
+    }
+
+    /// Fast path of an error of the chain crates
+    pub fn of(err: &BridgeError) -> Option<Self> {
+        ErrorClass::of(err).fast_path()
+    }
+}
+
+/// Solana node errors of a transaction whose blockhash is unknown or too old
+pub(crate) fn is_blockhash_expired(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("blockhash not found")
+        || message.contains("block height exceeded")
+        || message.contains("blockhash expired")
+}
+
+/// EVM node errors of a nonce already used, by a mined transaction or one in the mempool
+pub(crate) fn is_nonce_conflict(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("nonce too low") || is_already_known(&message)
+}
+
+/// EVM node error of a transaction sent again, the node already has it
+pub fn is_already_known(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("already known") || message.contains("known transaction")
+}
+
+/// Runs `send` and, while it fails with an error of `path`, runs it again at once up to
+/// `MAX_FAST_PATH_RESENDS` times. `send` gets the number of the attempt from 0 and
+/// rebuilds what `path` fixes from the second one. Each resend is counted in `counter`
+pub async fn with_fast_path<T, F, Fut>(
+    path: FastPath,
+    counter: &AtomicU64,
+    mut send: F,
+) -> Result<T>
+where
+    F: FnMut(usize) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match send(attempt).await {
+            Err(e) if attempt < MAX_FAST_PATH_RESENDS && FastPath::of(&e) == Some(path) => {
+                counter.fetch_add(1, Ordering::Relaxed);
+                attempt += 1;
+                warn!(
+                    "Resending with the {} fast path, attempt {attempt}: {}",
+                    path.name(),
+                    private_text(&e.to_string())
+                );
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod fast_path_test {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use crate::{
+        with_fast_path, BridgeError, ErrorClass, FastPath, SuggestedAction, MAX_FAST_PATH_RESENDS,
+    };
+
+    fn class(message: &str) -> ErrorClass {
+        ErrorClass::of(&BridgeError::Other(message.to_string()))
+    }
+
+    #[test]
+    fn test_canonical_errors_classified() {
+        for message in [
+            "Transaction simulation failed: Blockhash not found",
+            "RPC response error -32002: Transaction simulation failed: Blockhash not found; 0 log messages",
+            "Transaction failed: block height exceeded",
+            "unable to confirm transaction. This can happen in situations such as transaction expiration and insufficient fee-payer funds: blockhash expired",
+        ] {
+            assert_eq!(class(message), ErrorClass::BlockhashExpired, "{message}");
+            assert_eq!(class(message).fast_path(), Some(FastPath::FreshBlockhash));
+        }
+        for message in [
+            "server returned an error response: error code -32000: nonce too low: next nonce 12, tx nonce 11",
+            "server returned an error response: error code -32000: already known",
+            "known transaction: 0x5c50",
+        ] {
+            assert_eq!(class(message), ErrorClass::NonceConflict, "{message}");
+            assert_eq!(class(message).fast_path(), Some(FastPath::NonceResync));
+        }
+        assert_eq!(class("nonce too high").fast_path(), None);
+        assert_eq!(class("connection reset").fast_path(), None);
+        assert_eq!(
+            class("Transaction simulation failed: insufficient lamports 10, need 5000"),
+            ErrorClass::InsufficientFunds
+        );
+        assert_eq!(
+            crate::suggested_actions(
+                &[crate::FailedAttempt {
+                    time: crate::Timestamp::from_secs(1_700_000_000),
+                    status: crate::Status::TokenReceived,
+                    class: ErrorClass::BlockhashExpired,
+                    error: "Blockhash not found".to_string(),
+                }],
+                false
+            ),
+            vec![SuggestedAction::CheckRpc]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fast_path_resends_at_once() {
+        // Expired blockhash twice, the third send lands
+        let counter = AtomicU64::new(0);
+        let mut sent = vec![];
+        let result = with_fast_path(FastPath::FreshBlockhash, &counter, |attempt| {
+            sent.push(attempt);
+            async move {
+                match attempt {
+                    0 | 1 => Err(BridgeError::Other(
+                        "Transaction simulation failed: Blockhash not found".to_string(),
+                    )),
+                    _ => Ok("signature"),
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), "signature");
+        assert_eq!(sent, vec![0, 1, 2]);
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+
+        // Errors of the other chain or no fast path are returned as they are
+        let counter = AtomicU64::new(0);
+        let mut sends = 0;
+        let err = with_fast_path(FastPath::FreshBlockhash, &counter, |_| {
+            sends += 1;
+            async { Err::<(), _>(BridgeError::Other("nonce too low".to_string())) }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "nonce too low");
+        assert_eq!(sends, 1);
+        assert_eq!(counter.load(Ordering::Relaxed), 0);
+
+        // Nonce conflicts resync until the resends run out
+        let counter = AtomicU64::new(0);
+        let mut sends = 0;
+        let err = with_fast_path(FastPath::NonceResync, &counter, |_| {
+            sends += 1;
+            async {
+                Err::<(), _>(BridgeError::Other(
+                    "nonce too low: next nonce 12, tx nonce 11".to_string(),
+                ))
+            }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(FastPath::of(&err), Some(FastPath::NonceResync));
+        assert_eq!(sends, MAX_FAST_PATH_RESENDS + 1);
+        assert_eq!(
+            counter.load(Ordering::Relaxed),
+            MAX_FAST_PATH_RESENDS as u64
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use storage::db::Database;
+
+use crate::{BRequest, HistoryEntry, Result, Status, Timestamp};
+
+/// Custody transfers not finalized after this time were rolled back with their fork,
+/// finalization takes around 13 seconds and a blockhash expires after about a minute
+pub const FINALITY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Commitment of the Solana events the mints to EVM are started from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfirmationStrategy {
+    /// Events are received once finalized, the mint is sent right away
+    #[default]
+    Finalized,
+    /// Events are received once confirmed and processed up to the mint, which waits for
+    /// the custody transfer to be finalized
+    Confirmed,
+}
+
+/// Request whose token custody was seen at confirmed commitment, its mint waits for the
+/// custody transfer to be finalized
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FinalityPending {
+    /// Custody transfer transaction
+    pub signature: String,
+    /// Token URI read while waiting, read again when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_metadata: Option<String>,
+    pub observed_at: Timestamp,
+}
+
+/// Next step of a request waiting for the finality of its custody transfer
+#[derive(Debug, Clone, PartialEq)]
+pub enum FinalityStep {
+    /// The mint can be sent
+    Finalized,
+    Wait,
+    /// Failed or dropped with its fork, with the reason
+    RolledBack(String),
+}
+
+impl BRequest {
+    /// Whether the mint waits for the finality of the custody transfer
+    pub fn awaiting_finality(&self) -> bool {
+        self.status == Status::TokenReceived && self.finality_pending.is_some()
+    }
+
+    /// Custody transfer finalized, the mint can be sent
+    pub fn finalize_custody(&mut self, db: &Database, now: Timestamp) -> Result<()> {
+        let Some(pending) = self.finality_pending.take() else {
+            return Ok(());
+        };
+        info!("Custody of request {} finalized", self.id);
+        self.history.push(HistoryEntry {
+            time: now,
+            event: format!("Custody transaction {} finalized", pending.signature),
+        });
+        self.save(db)
+    }
+
+    /// Custody transfer rolled back, the request goes back to RequestReceived and its
+    /// custody is checked again
+    pub fn roll_back_custody(&mut self, db: &Database, reason: &str, now: Timestamp) -> Result<()> {
+        let Some(pending) = self.finality_pending.take() else {
+            return Ok(());
+        };
+        info!("Custody of request {} rolled back, {reason}", self.id);
+        self.status = Status::RequestReceived;
+        self.custody_signature = None;
+        self.last_update = now;
+        self.history.push(HistoryEntry {
+            time: now,
+            event: format!(
+                "Custody transaction {} not finalized, {reason}",
+                pending.signature
+            ),
+        });
+        self.save(db)
+    }
+}
+
+#[cfg(test)]
+mod finality_test {
+    use storage::testing::each_engine;
+
+    use crate::{request_data, BRequest, Chains, FinalityPending, InputRequest, Status, Timestamp};
+
+    const NOW: Timestamp = Timestamp::from_secs(1_700_000_000);
+
+    fn awaiting(db: &storage::db::Database) -> BRequest {
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "mint".to_string(),
+                token_id: String::new(),
+                token_owner: "token-account".to_string(),
+                origin_network: Chains::SOLANA,
+                destination_account: "0xdestination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.status = Status::TokenReceived;
+        request.custody_signature = Some("custody-signature".to_string());
+        request.finality_pending = Some(FinalityPending {
+            signature: "custody-signature".to_string(),
+            token_metadata: None,
+            observed_at: NOW,
+        });
+        request.save(db).unwrap();
+        request
+    }
+
+    #[test]
+    fn test_finalized_and_rolled_back_custody() {
+        for db in each_engine() {
+            let mut request = awaiting(&db);
+            assert!(request.awaiting_finality());
+            request.finalize_custody(&db, NOW).unwrap();
+            let stored = request_data(&request.id, &db).unwrap().unwrap();
+            assert!(!stored.awaiting_finality());
+            assert_eq!(stored.status, Status::TokenReceived);
+
+            let mut request = awaiting(&db);
+            request.roll_back_custody(&db, "dropped", NOW).unwrap();
+            let stored = request_data(&request.id, &db).unwrap().unwrap();
+            assert_eq!(stored.status, Status::RequestReceived);
+            assert_eq!(
+                (stored.custody_signature, stored.finality_pending),
+                (None, None)
+            );
+            assert!(stored.history.last().unwrap().event.contains("dropped"));
+        }
+    }
+}
@@ -1,159 +1,420 @@
-use std::collections::HashMap;
-
-use eyre::Result;
+use log::info;
 use storage::{
-    db::Database,
-    keys::{COMPLETED_REQUESTS, PENDING_REQUESTS},
+    db::{Batch, Database, SCHEMA_VERSION},
+    keys::{
+        ColumnFamily, COMPLETED_PREFIX, COMPLETED_REQUESTS, REQUEST_KEY_PREFIX,
+        STATUS_INDEX_PREFIX, STATUS_INDEX_SCHEMA,
+    },
 };
 
-use crate::BRequest;
+use crate::{BRequest, Result, Status, Timestamp};
 
+/// Request stored under `request_id`, or the one `request_id` is an alias of
 pub fn request_data(request_id: &str, db: &Database) -> Result<Option<BRequest>> {
-    let request = db.read::<_, BRequest>(request_id)?;
+    let request = db.read_following_alias::<BRequest>(request_id)?;
     Ok(request)
 }
 
-pub fn pending_requests(db: &Database) -> Option<Vec<String>> {
-    db.read(PENDING_REQUESTS).unwrap()
+/// Requests of `request_ids` in their order, read in one pass. Ids found under no key
+/// are looked up again as aliases
+pub fn requests_data<K: AsRef<str>>(
+    request_ids: &[K],
+    db: &Database,
+) -> Result<Vec<Option<BRequest>>> {
+    let keys: Vec<&str> = request_ids.iter().map(AsRef::as_ref).collect();
+    let mut requests = db.read_many_cf::<_, BRequest>(ColumnFamily::Requests, &keys)?;
+    for (request, request_id) in requests.iter_mut().zip(keys) {
+        if request.is_none() {
+            *request = request_data(request_id, db)?;
+        }
+    }
+    Ok(requests)
+}
+
+fn completed_key(request_id: &str) -> String {
+    format!("{COMPLETED_PREFIX}{request_id}")
 }
 
+/// Completed request ids in the order they were added, None when there is none
 pub fn completed_requests(db: &Database) -> Option<Vec<String>> {
-    db.read(COMPLETED_REQUESTS).unwrap()
+    let mut completed = db.scan_prefix::<Timestamp>(COMPLETED_PREFIX, None).unwrap();
+    completed.sort_by(|(key_a, added_a), (key_b, added_b)| {
+        added_a.cmp(added_b).then_with(|| key_a.cmp(key_b))
+    });
+    let ids: Vec<String> = completed
+        .into_iter()
+        .map(|(key, _)| key[COMPLETED_PREFIX.len()..].to_string())
+        .collect();
+    (!ids.is_empty()).then_some(ids)
+}
+
+pub fn add_completed_request(request_id: &str, db: &Database, now: Timestamp) -> Result<()> {
+    let mut batch = Batch::default();
+    stage_completed_request(request_id, db, &mut batch, now)?;
+    db.write_batch(batch)?;
+    Ok(())
 }
 
-pub fn add_completed_request(request_id: &str, db: &Database) -> Result<()> {
-    if let Ok(Some(mut completed)) = db.read::<_, Vec<String>>(COMPLETED_REQUESTS) {
-        completed.push(request_id.to_owned());
-        update_vector(db, COMPLETED_REQUESTS, completed)?;
-    } else {
-        let completed = vec![request_id.to_owned()];
-        update_vector(db, COMPLETED_REQUESTS, completed)?;
+/// Adds the request to the completed set in `batch`. Each request has its own key, adding
+/// it again can't duplicate it and concurrent additions don't overwrite each other
+pub fn stage_completed_request(
+    request_id: &str,
+    db: &Database,
+    batch: &mut Batch,
+    now: Timestamp,
+) -> Result<()> {
+    let key = completed_key(request_id);
+    // The first addition keeps its place
+    if db.read::<_, Timestamp>(&key)?.is_none() {
+        batch.put(key, &now)?;
     }
     Ok(())
 }
 
+/// Moves the completed list of older versions to the completed set keeping its order,
+/// ahead of the requests completed since. Returns the number of requests moved
+pub fn migrate_completed_list(db: &Database) -> Result<usize> {
+    let Some(legacy) = db.read::<_, Vec<String>>(COMPLETED_REQUESTS)? else {
+        return Ok(0);
+    };
+
+    let mut batch = Batch::default();
+    let mut moved = 0;
+    for (index, id) in legacy.iter().enumerate() {
+        let key = completed_key(id);
+        if legacy[..index].contains(id) || db.read::<_, Timestamp>(&key)?.is_some() {
+            continue;
+        }
+        batch.put(key, &Timestamp::from_millis(index as u64))?;
+        moved += 1;
+    }
+    batch.delete(COMPLETED_REQUESTS);
+    db.write_batch(batch)?;
+    info!("Moved {moved} completed requests to the completed set");
+    Ok(moved)
+}
+
 pub fn update_vector(db: &Database, key: &str, requests: Vec<String>) -> Result<()> {
     _ = db.write_value(key, &requests)?;
     Ok(())
 }
 
-pub fn update_hashmap(db: &Database, key: &str, indexes: HashMap<String, i128>) -> Result<()> {
-    _ = db.write_value(key, &indexes)?;
-    Ok(())
+pub fn status_index_key(status: &Status, request_id: &str) -> String {
+    format!("{}{}", status_index_prefix(status), request_id)
 }
 
-#[cfg(test)]
-mod types_test {
-    use crate::{
-        add_completed_request, completed_requests, pending_requests, update_hashmap, update_vector,
-    };
-    use std::collections::HashMap;
-    use storage::db::Database;
-    use storage::keys::{COMPLETED_REQUESTS, PENDING_REQUESTS};
-    use tempfile::tempdir;
+fn status_index_prefix(status: &Status) -> String {
+    format!("{}{:?}:", STATUS_INDEX_PREFIX, status)
+}
 
-    // Helper function to create a test database
-    fn setup_test_db() -> Database {
-        let dir = tempdir().unwrap();
-        let path = dir.path().to_str().unwrap();
-        Database::open(path).unwrap()
+/// Request ids in the status bucket, with the last update (unix secs) of each one
+pub fn requests_by_status(
+    db: &Database,
+    status: &Status,
+    limit: Option<usize>,
+) -> Result<Vec<(String, u64)>> {
+    requests_by_status_after(db, status, None, limit)
+}
+
+/// Page of the status bucket starting after the `after` request id
+pub fn requests_by_status_after(
+    db: &Database,
+    status: &Status,
+    after: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Vec<(String, u64)>> {
+    let prefix = status_index_prefix(status);
+    let after_key = after.map(|id| status_index_key(status, id));
+    let entries = db.scan_prefix_after::<u64>(&prefix, after_key.as_deref(), limit)?;
+
+    Ok(entries
+        .into_iter()
+        .map(|(key, last_update)| (key[prefix.len()..].to_string(), last_update))
+        .collect())
+}
+
+pub fn has_status(db: &Database, request_id: &str, status: &Status) -> Result<bool> {
+    Ok(db
+        .read::<_, u64>(status_index_key(status, request_id))?
+        .is_some())
+}
+
+/// Drops every status index entry and rebuilds them from the stored requests
+pub fn rebuild_status_indexes(db: &Database) -> Result<usize> {
+    for (key, _) in db.scan_prefix::<u64>(STATUS_INDEX_PREFIX, None)? {
+        db.delete(key)?;
     }
 
-    #[test]
-    fn test_pending_and_completed_requests() {
-        let db = setup_test_db();
+    let requests = db.scan_prefix::<BRequest>(REQUEST_KEY_PREFIX, None)?;
+    for (_, request) in requests.iter() {
+        db.write_value(
+            status_index_key(&request.status, &request.id),
+            &request.last_update.as_secs(),
+        )?;
+    }
 
-        // Initially there should be no pending or completed requests
-        assert!(pending_requests(&db).is_none());
-        assert!(completed_requests(&db).is_none());
+    db.write_value(STATUS_INDEX_SCHEMA, &SCHEMA_VERSION)?;
+    info!("Rebuilt status indexes for {} requests", requests.len());
+    Ok(requests.len())
+}
 
-        // Add a pending request
-        let pending = vec!["request1".to_string()];
-        update_vector(&db, PENDING_REQUESTS, pending.clone()).unwrap();
+/// Rebuilds the status indexes when they were built under another schema version, or
+/// when they are empty while requests are stored. The sweep only finds the requests
+/// through them. Returns whether they were rebuilt
+pub fn ensure_status_indexes(db: &Database) -> Result<bool> {
+    let built_under = db.read::<_, u32>(STATUS_INDEX_SCHEMA)?;
+    let missing = db
+        .scan_prefix::<u64>(STATUS_INDEX_PREFIX, Some(1))?
+        .is_empty()
+        && !db
+            .scan_prefix::<BRequest>(REQUEST_KEY_PREFIX, Some(1))?
+            .is_empty();
+    if built_under == Some(SCHEMA_VERSION) && !missing {
+        return Ok(false);
+    }
+    info!(
+        "Rebuilding request status indexes, built under schema {:?}",
+        built_under
+    );
+    rebuild_status_indexes(db)?;
+    Ok(true)
+}
 
-        // Check that the pending request was added
-        let retrieved_pending = pending_requests(&db).unwrap();
-        assert_eq!(retrieved_pending, pending);
+#[cfg(test)]
+mod types_test {
+    use crate::{
+        add_completed_request, completed_requests, ensure_status_indexes, has_status,
+        migrate_completed_list, rebuild_status_indexes, requests_by_status, requests_data,
+        status_index_key, update_vector, BRequest, Chains, InputRequest, Status, Timestamp,
+    };
+    use storage::db::{Database, SCHEMA_VERSION};
+    use storage::keys::{COMPLETED_REQUESTS, STATUS_INDEX_SCHEMA};
+    use storage::testing::each_engine;
 
-        // Add a completed request
-        let completed = vec!["request2".to_string()];
-        update_vector(&db, COMPLETED_REQUESTS, completed.clone()).unwrap();
+    // Helper function to create a test database
 
-        // Check that the completed request was added
-        let retrieved_completed = completed_requests(&db).unwrap();
-        assert_eq!(retrieved_completed, completed);
+    #[test]
+    fn test_completed_requests() {
+        for db in each_engine() {
+            // Initially there should be no completed requests
+            assert!(completed_requests(&db).is_none());
+
+            // Completed list of an older version
+            let completed = vec![
+                "request2".to_string(),
+                "request1".to_string(),
+                "request2".to_string(),
+            ];
+            update_vector(&db, COMPLETED_REQUESTS, completed).unwrap();
+            add_completed_request("request3", &db, Timestamp::now()).unwrap();
+            assert_eq!(migrate_completed_list(&db).unwrap(), 2);
+            assert_eq!(migrate_completed_list(&db).unwrap(), 0);
+
+            // Moved in its order ahead of the later ones, without the duplicate
+            let retrieved_completed = completed_requests(&db).unwrap();
+            assert_eq!(
+                retrieved_completed,
+                vec!["request2", "request1", "request3"]
+            );
+            assert_eq!(db.read::<_, Vec<String>>(COMPLETED_REQUESTS).unwrap(), None);
+        }
     }
 
     #[test]
     fn test_add_completed_request() {
-        let db = setup_test_db();
+        for db in each_engine() {
+            // Initially there should be no completed requests
+            assert!(completed_requests(&db).is_none());
+
+            // Add a completed request
+            add_completed_request("request1", &db, Timestamp::now()).unwrap();
+
+            // Check that the completed request was added
+            let completed = completed_requests(&db).unwrap();
+            assert_eq!(completed.len(), 1);
+            assert_eq!(completed[0], "request1");
+
+            // Add another completed request
+            add_completed_request("request2", &db, Timestamp::now()).unwrap();
+
+            // Check that both completed requests are there
+            let completed = completed_requests(&db).unwrap();
+            assert_eq!(completed.len(), 2);
+            assert!(completed.contains(&"request1".to_string()));
+            assert!(completed.contains(&"request2".to_string()));
+
+            // Adding a request again doesn't duplicate it
+            add_completed_request("request1", &db, Timestamp::now()).unwrap();
+            assert_eq!(completed_requests(&db).unwrap(), completed);
+        }
+    }
 
-        // Initially there should be no completed requests
-        assert!(completed_requests(&db).is_none());
+    #[test]
+    fn test_update_vector() {
+        for db in each_engine() {
+            let key = "test_vector";
 
-        // Add a completed request
-        add_completed_request("request1", &db).unwrap();
+            // Update with an initial vector
+            let initial = vec!["item1".to_string(), "item2".to_string()];
+            update_vector(&db, key, initial.clone()).unwrap();
 
-        // Check that the completed request was added
-        let completed = completed_requests(&db).unwrap();
-        assert_eq!(completed.len(), 1);
-        assert_eq!(completed[0], "request1");
+            // Check that the vector was saved
+            let retrieved: Vec<String> = db.read(key).unwrap().unwrap();
+            assert_eq!(retrieved, initial);
 
-        // Add another completed request
-        add_completed_request("request2", &db).unwrap();
+            // Update with a new vector
+            let updated = vec!["item3".to_string(), "item4".to_string()];
+            update_vector(&db, key, updated.clone()).unwrap();
 
-        // Check that both completed requests are there
-        let completed = completed_requests(&db).unwrap();
-        assert_eq!(completed.len(), 2);
-        assert!(completed.contains(&"request1".to_string()));
-        assert!(completed.contains(&"request2".to_string()));
+            // Check that the vector was updated
+            let retrieved: Vec<String> = db.read(key).unwrap().unwrap();
+            assert_eq!(retrieved, updated);
+        }
     }
 
-    #[test]
-    fn test_update_vector() {
-        let db = setup_test_db();
-        let key = "test_vector";
+    fn create_test_request(token_id: &str) -> BRequest {
+        BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xabc123".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner456".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination789".to_string(),
+            },
+            Timestamp::now(),
+        )
+    }
 
-        // Update with an initial vector
-        let initial = vec!["item1".to_string(), "item2".to_string()];
-        update_vector(&db, key, initial.clone()).unwrap();
+    fn bucket(db: &Database, status: &Status) -> Vec<String> {
+        requests_by_status(db, status, None)
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
 
-        // Check that the vector was saved
-        let retrieved: Vec<String> = db.read(key).unwrap().unwrap();
-        assert_eq!(retrieved, initial);
+    #[test]
+    fn test_status_buckets_follow_transitions() {
+        for db in each_engine() {
+            let mut request = create_test_request("1");
+            let other = create_test_request("2");
+            other.save(&db).unwrap();
+
+            request.add_tx("0xtx", &db).unwrap();
+            assert_eq!(bucket(&db, &Status::RequestReceived).len(), 2);
+            assert!(has_status(&db, &request.id, &Status::RequestReceived).unwrap());
+
+            let steps = [
+                Status::TokenReceived,
+                Status::TokenMinted,
+                Status::Completed,
+            ];
+            for status in steps.iter() {
+                if *status == Status::Completed {
+                    request
+                        .complete_minted(&db, "mint", "account", Timestamp::now())
+                        .unwrap();
+                } else {
+                    request.update_state(&db, Timestamp::now()).unwrap();
+                }
+                for bucket_status in Status::ALL.iter() {
+                    assert_eq!(
+                        bucket(&db, bucket_status).contains(&request.id.to_string()),
+                        bucket_status == status,
+                        "request in {:?} bucket after moving to {:?}",
+                        bucket_status,
+                        status
+                    );
+                }
+            }
+
+            // The other request is not moved
+            assert_eq!(
+                bucket(&db, &Status::RequestReceived),
+                vec![other.id.clone()]
+            );
+            assert_eq!(
+                requests_by_status(&db, &Status::RequestReceived, Some(0))
+                    .unwrap()
+                    .len(),
+                0
+            );
+        }
+    }
 
-        // Update with a new vector
-        let updated = vec!["item3".to_string(), "item4".to_string()];
-        update_vector(&db, key, updated.clone()).unwrap();
+    #[test]
+    fn test_rebuild_status_indexes() {
+        for db in each_engine() {
+            let mut canceled = create_test_request("1");
+            canceled.cancel(&db, Timestamp::now()).unwrap();
+            let received = create_test_request("2");
+            received.save(&db).unwrap();
+
+            // Simulate corruption: stale entry and a missing one
+            db.write_value(status_index_key(&Status::TokenMinted, &canceled.id), &0u64)
+                .unwrap();
+            db.delete(status_index_key(&Status::RequestReceived, &received.id))
+                .unwrap();
+
+            assert_eq!(rebuild_status_indexes(&db).unwrap(), 2);
+            assert_eq!(bucket(&db, &Status::Canceled), vec![canceled.id.clone()]);
+            assert_eq!(
+                bucket(&db, &Status::RequestReceived),
+                vec![received.id.clone()]
+            );
+            assert!(bucket(&db, &Status::TokenMinted).is_empty());
+        }
+    }
 
-        // Check that the vector was updated
-        let retrieved: Vec<String> = db.read(key).unwrap().unwrap();
-        assert_eq!(retrieved, updated);
+    #[test]
+    fn test_status_indexes_rebuilt_on_schema_bump_or_when_missing() {
+        for db in each_engine() {
+            let received = create_test_request("1");
+            received.save(&db).unwrap();
+            // Indexes of a database never rebuilt
+            assert!(ensure_status_indexes(&db).unwrap());
+            assert!(!ensure_status_indexes(&db).unwrap());
+
+            // Built under an older schema
+            db.delete(status_index_key(&Status::RequestReceived, &received.id))
+                .unwrap();
+            db.write_value(STATUS_INDEX_SCHEMA, &(SCHEMA_VERSION - 1))
+                .unwrap();
+            assert!(ensure_status_indexes(&db).unwrap());
+            assert_eq!(
+                bucket(&db, &Status::RequestReceived),
+                vec![received.id.clone()]
+            );
+            assert_eq!(
+                db.read::<_, u32>(STATUS_INDEX_SCHEMA).unwrap(),
+                Some(SCHEMA_VERSION)
+            );
+
+            // Empty while requests are stored
+            db.delete(status_index_key(&Status::RequestReceived, &received.id))
+                .unwrap();
+            assert!(ensure_status_indexes(&db).unwrap());
+            assert_eq!(bucket(&db, &Status::RequestReceived), vec![received.id]);
+        }
     }
 
     #[test]
-    fn test_update_hashmap() {
-        let db = setup_test_db();
-        let key = "test_hashmap";
-
-        // Update with an initial hashmap
-        let mut initial = HashMap::new();
-        initial.insert("key1".to_string(), 100);
-        initial.insert("key2".to_string(), 200);
-        update_hashmap(&db, key, initial.clone()).unwrap();
-
-        // Check that the hashmap was saved
-        let retrieved: HashMap<String, i128> = db.read(key).unwrap().unwrap();
-        assert_eq!(retrieved, initial);
-
-        // Update with a new hashmap
-        let mut updated = HashMap::new();
-        updated.insert("key3".to_string(), 300);
-        updated.insert("key4".to_string(), 400);
-        update_hashmap(&db, key, updated.clone()).unwrap();
-
-        // Check that the hashmap was updated
-        let retrieved: HashMap<String, i128> = db.read(key).unwrap().unwrap();
-        assert_eq!(retrieved, updated);
+    fn test_requests_data_in_order_following_aliases() {
+        for db in each_engine() {
+            let first = create_test_request("1");
+            first.save(&db).unwrap();
+            let mut aliased = create_test_request("2");
+            aliased.alias_id = Some(create_test_request("3").id);
+            aliased.save(&db).unwrap();
+            let unknown = create_test_request("4").id;
+
+            let ids = [aliased.alias_id.clone().unwrap(), unknown, first.id.clone()];
+            let found: Vec<_> = requests_data(&ids, &db)
+                .unwrap()
+                .into_iter()
+                .map(|request| request.map(|request| request.id))
+                .collect();
+            assert_eq!(found, vec![Some(aliased.id), None, Some(first.id)]);
+        }
     }
 }
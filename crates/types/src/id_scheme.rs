@@ -0,0 +1,202 @@
+use alloy::primitives::keccak256;
+use serde::{Deserialize, Serialize};
+use storage::{
+    db::Database,
+    keys::{ALIAS_PREFIX, REQUEST_KEY_PREFIX},
+};
+
+use crate::{BRequest, InputRequest, RequestId, Result, Timestamp};
+
+/// Scheme of the ids of new requests. While the ids migrate, a request is stored under
+/// the id of the configured scheme with an alias under the id of the other one
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IdScheme {
+    /// keccak256 of the contract or mint, the token id and the owner
+    #[default]
+    Legacy,
+    /// Also hashes the origin chain, the same token and owner bridged from both chains
+    /// get different ids
+    ChainAware,
+}
+
+impl IdScheme {
+    pub const ALL: [IdScheme; 2] = [IdScheme::Legacy, IdScheme::ChainAware];
+
+    pub fn other(&self) -> IdScheme {
+        match self {
+            IdScheme::Legacy => IdScheme::ChainAware,
+            IdScheme::ChainAware => IdScheme::Legacy,
+        }
+    }
+
+    /// Id of a request for `input` under this scheme
+    pub fn request_id(&self, input: &InputRequest) -> RequestId {
+        match self {
+            IdScheme::Legacy => {
+                BRequest::generate_id(&input.contract_or_mint, &input.token_id, &input.token_owner)
+            }
+            IdScheme::ChainAware => {
+                let mut data = Vec::new();
+                data.extend_from_slice(format!("{:?}", input.origin_network).as_bytes());
+                data.extend_from_slice(input.contract_or_mint.as_bytes());
+                data.extend_from_slice(input.token_id.as_bytes());
+                data.extend_from_slice(input.token_owner.as_bytes());
+                keccak256(&data).into()
+            }
+        }
+    }
+
+    /// Scheme `request_id` was generated with from `input`, None for ids not derived from
+    /// the input as the ones of the orphans recorded from chain events
+    pub fn of(request_id: &str, input: &InputRequest) -> Option<IdScheme> {
+        IdScheme::ALL
+            .into_iter()
+            .find(|scheme| scheme.request_id(input) == request_id)
+    }
+}
+
+impl BRequest {
+    /// New request stored under the id of `scheme`, the id of the other scheme is kept as
+    /// its alias so lookups by either id find it
+    pub fn with_id_scheme(input: InputRequest, scheme: IdScheme, now: Timestamp) -> Self {
+        let mut request = BRequest::new(input, now);
+        request.id = scheme.request_id(&request.input);
+        request.alias_id = Some(scheme.other().request_id(&request.input));
+        request
+    }
+
+    /// True when `request_id` is the id of the request or its alias
+    pub fn answers_to(&self, request_id: &str) -> bool {
+        self.id == request_id
+            || self
+                .alias_id
+                .as_ref()
+                .is_some_and(|alias| alias == request_id)
+    }
+}
+
+/// Stored requests counted by the scheme of the id they are stored under
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct IdMigrationReport {
+    pub legacy: usize,
+    pub chain_aware: usize,
+    /// Ids not derived from the request input, the orphans of chain events
+    pub other: usize,
+    /// Requests also reachable by the id of the other scheme
+    pub aliased: usize,
+    /// Alias records in the database
+    pub aliases: usize,
+}
+
+pub fn id_migration_report(db: &Database) -> Result<IdMigrationReport> {
+    let mut report = IdMigrationReport::default();
+    for (_, request) in db.scan_prefix::<BRequest>(REQUEST_KEY_PREFIX, None)? {
+        match IdScheme::of(&request.id, &request.input) {
+            Some(IdScheme::Legacy) => report.legacy += 1,
+            Some(IdScheme::ChainAware) => report.chain_aware += 1,
+            None => report.other += 1,
+        }
+        if request.alias_id.is_some() {
+            report.aliased += 1;
+        }
+    }
+    report.aliases = db.scan_prefix::<String>(ALIAS_PREFIX, None)?.len();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod id_scheme_test {
+    use alloy::primitives::B256;
+    use storage::testing::each_engine;
+
+    use crate::{
+        id_migration_report, request_data, BRequest, Chains, IdMigrationReport, IdScheme,
+        InputRequest, RequestId, Timestamp,
+    };
+
+    fn input(origin_network: Chains) -> InputRequest {
+        InputRequest {
+            contract_or_mint: "0x5fbdb2315678afecb367f032d93f642f64180aa3".to_string(),
+            token_id: "42".to_string(),
+            token_owner: "owner".to_string(),
+            origin_network,
+            destination_account: "destination".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_schemes_generate_distinct_ids() {
+        let evm = input(Chains::EVM);
+        let legacy = IdScheme::Legacy.request_id(&evm);
+        let chain_aware = IdScheme::ChainAware.request_id(&evm);
+        assert_eq!(legacy, BRequest::new(evm.clone(), Timestamp::now()).id);
+        assert_ne!(legacy, chain_aware);
+        // Only the chain aware id depends on the origin chain
+        let solana = input(Chains::SOLANA);
+        assert_eq!(IdScheme::Legacy.request_id(&solana), legacy);
+        assert_ne!(IdScheme::ChainAware.request_id(&solana), chain_aware);
+
+        assert_eq!(IdScheme::of(&legacy, &evm), Some(IdScheme::Legacy));
+        assert_eq!(IdScheme::of(&chain_aware, &evm), Some(IdScheme::ChainAware));
+        assert_eq!(IdScheme::of(&chain_aware, &solana), None);
+        assert_eq!(
+            serde_json::from_str::<IdScheme>("\"chain_aware\"").unwrap(),
+            IdScheme::ChainAware
+        );
+    }
+
+    #[test]
+    fn test_lookup_by_either_id() {
+        for db in each_engine() {
+            for scheme in IdScheme::ALL {
+                let request =
+                    BRequest::with_id_scheme(input(Chains::EVM), scheme, Timestamp::now());
+                request.save(&db).unwrap();
+                let alias = request.alias_id.clone().unwrap();
+                assert_eq!(request.id, scheme.request_id(&request.input));
+                assert_eq!(alias, scheme.other().request_id(&request.input));
+
+                for id in [&request.id, &alias] {
+                    let stored = request_data(id, &db).unwrap().unwrap();
+                    assert_eq!(stored.id, request.id, "{scheme:?} {:?}", db.engine);
+                    assert!(stored.answers_to(id));
+                }
+                assert!(!request.answers_to(&RequestId::from(B256::ZERO)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_migration_report_counts_schemes() {
+        for db in each_engine() {
+            BRequest::new(input(Chains::EVM), Timestamp::now())
+                .save(&db)
+                .unwrap();
+            let mut legacy = input(Chains::SOLANA);
+            legacy.token_id = "44".to_string();
+            BRequest::with_id_scheme(legacy, IdScheme::Legacy, Timestamp::now())
+                .save(&db)
+                .unwrap();
+            let mut chain_aware = input(Chains::EVM);
+            chain_aware.token_id = "43".to_string();
+            BRequest::with_id_scheme(chain_aware, IdScheme::ChainAware, Timestamp::now())
+                .save(&db)
+                .unwrap();
+            let mut orphan = BRequest::new(input(Chains::EVM), Timestamp::now());
+            orphan.id = RequestId::from(B256::repeat_byte(1));
+            orphan.save(&db).unwrap();
+
+            assert_eq!(
+                id_migration_report(&db).unwrap(),
+                IdMigrationReport {
+                    legacy: 2,
+                    chain_aware: 1,
+                    other: 1,
+                    aliased: 2,
+                    aliases: 2,
+                }
+            );
+        }
+    }
+}
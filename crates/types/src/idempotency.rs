@@ -0,0 +1,245 @@
+use std::time::Duration;
+
+use alloy::primitives::hex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use storage::{db::Database, keys::IDEMPOTENCY_PREFIX};
+
+use crate::{ClientLabels, InputRequest, RequestId, Result, Timestamp};
+
+/// Longest Idempotency-Key header accepted
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+/// Time a key replays its response when not configured
+pub const DEFAULT_IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Creations running for longer were interrupted, well above the time of their chain
+/// transaction. Their key can be used again
+pub const IDEMPOTENCY_IN_PROGRESS_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Use of an idempotency key by a request creation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IdempotencyRecord {
+    /// Id derived from the body, the creation under the key makes this request
+    pub request_id: RequestId,
+    /// Hash of the body the key was first used with
+    pub body_hash: String,
+    /// Status of the response to replay, not set while the creation runs
+    pub response_status: Option<u16>,
+    pub created_at: Timestamp,
+    pub expires_at: Timestamp,
+}
+
+impl IdempotencyRecord {
+    /// Creation started too long ago to still be running
+    pub fn interrupted(&self, now: Timestamp) -> bool {
+        self.response_status.is_none()
+            && self.created_at.elapsed_until(now) >= IDEMPOTENCY_IN_PROGRESS_TIMEOUT
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdempotencyClaim {
+    /// Key not in use, the creation runs and then completes or releases it
+    New,
+    /// Key used before with the same body
+    Replay(IdempotencyRecord),
+    /// Key in use with another body
+    Conflict,
+}
+
+/// Visible ASCII of at most `MAX_IDEMPOTENCY_KEY_LEN` characters
+pub fn valid_idempotency_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_IDEMPOTENCY_KEY_LEN
+        && key.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+/// Hash of the fields of a creation body, the key formatting of the JSON doesn't change it.
+/// Bodies without labels hash as before the labels existed
+pub fn idempotency_body_hash(
+    input: &InputRequest,
+    callback_url: Option<&str>,
+    labels: &ClientLabels,
+) -> String {
+    let body = if labels.is_empty() {
+        serde_json::to_vec(&(input, callback_url))
+    } else {
+        serde_json::to_vec(&(input, callback_url, labels))
+    }
+    .unwrap_or_default();
+    hex::encode(Sha256::digest(body))
+}
+
+fn record_key(key: &str) -> String {
+    format!("{IDEMPOTENCY_PREFIX}{key}")
+}
+
+pub fn idempotency_record(db: &Database, key: &str) -> Result<Option<IdempotencyRecord>> {
+    Ok(db.read(record_key(key))?)
+}
+
+/// Records the use of `key` by the creation of `request_id` unless the key is in use.
+/// Expired records and the records of interrupted creations don't hold their key
+pub fn claim_idempotency_key(
+    db: &Database,
+    key: &str,
+    request_id: &RequestId,
+    body_hash: &str,
+    now: Timestamp,
+    ttl: Duration,
+) -> Result<IdempotencyClaim> {
+    if let Some(record) = idempotency_record(db, key)? {
+        if record.expires_at > now {
+            if record.body_hash == body_hash {
+                return Ok(IdempotencyClaim::Replay(record));
+            }
+            if !record.interrupted(now) {
+                return Ok(IdempotencyClaim::Conflict);
+            }
+        }
+    }
+    let record = IdempotencyRecord {
+        request_id: request_id.clone(),
+        body_hash: body_hash.to_string(),
+        response_status: None,
+        created_at: now,
+        expires_at: now.saturating_add(ttl),
+    };
+    db.write_value(record_key(key), &record)?;
+    Ok(IdempotencyClaim::New)
+}
+
+/// Stores the status of the response of the creation, replayed on the next uses of `key`
+pub fn complete_idempotency_key(db: &Database, key: &str, response_status: u16) -> Result<()> {
+    if let Some(mut record) = idempotency_record(db, key)? {
+        record.response_status = Some(response_status);
+        db.write_value(record_key(key), &record)?;
+    }
+    Ok(())
+}
+
+/// Frees `key` after a failed creation, a retry runs the creation again
+pub fn release_idempotency_key(db: &Database, key: &str) -> Result<()> {
+    db.delete(record_key(key))?;
+    Ok(())
+}
+
+/// Deletes the expired records, returns the number deleted
+pub fn prune_idempotency_keys(db: &Database, now: Timestamp) -> Result<usize> {
+    let mut pruned = 0;
+    for (key, record) in db.scan_prefix::<IdempotencyRecord>(IDEMPOTENCY_PREFIX, None)? {
+        if record.expires_at <= now {
+            db.delete(&key)?;
+            pruned += 1;
+        }
+    }
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod idempotency_test {
+    use std::time::Duration;
+
+    use storage::testing::each_engine;
+
+    use crate::{
+        claim_idempotency_key, complete_idempotency_key, idempotency_body_hash, idempotency_record,
+        prune_idempotency_keys, release_idempotency_key, valid_idempotency_key, BRequest, Chains,
+        ClientLabels, IdempotencyClaim, InputRequest, Timestamp, IDEMPOTENCY_IN_PROGRESS_TIMEOUT,
+    };
+
+    const NOW: Timestamp = Timestamp::from_secs(1_700_000_000);
+    const TTL: Duration = Duration::from_secs(3600);
+
+    fn input(token_id: &str) -> InputRequest {
+        InputRequest {
+            contract_or_mint: "0xcontract".to_string(),
+            token_id: token_id.to_string(),
+            token_owner: "0xowner".to_string(),
+            origin_network: Chains::EVM,
+            destination_account: "destination".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_key_format() {
+        assert!(valid_idempotency_key("4f9c2a1e-retry_1"));
+        assert!(!valid_idempotency_key(""));
+        assert!(!valid_idempotency_key("key with spaces"));
+        assert!(!valid_idempotency_key(&"k".repeat(256)));
+    }
+
+    #[test]
+    fn test_claim_replay_conflict_and_expiry() {
+        for db in each_engine() {
+            let (first, other) = (input("1"), input("2"));
+            let id = BRequest::new(first.clone(), Timestamp::now()).id;
+            let hash = idempotency_body_hash(&first, None, &ClientLabels::default());
+            let other_hash = idempotency_body_hash(&other, None, &ClientLabels::default());
+            assert_ne!(
+                hash,
+                idempotency_body_hash(&first, Some("https://hooks"), &ClientLabels::default())
+            );
+            let labels = ClientLabels {
+                client_reference: Some("order-1".to_string()),
+                tags: vec![],
+            };
+            assert_ne!(hash, idempotency_body_hash(&first, None, &labels));
+
+            let claim = claim_idempotency_key(&db, "key", &id, &hash, NOW, TTL).unwrap();
+            assert_eq!(claim, IdempotencyClaim::New);
+            complete_idempotency_key(&db, "key", 200).unwrap();
+
+            let later = NOW.saturating_add(Duration::from_secs(60));
+            let IdempotencyClaim::Replay(record) =
+                claim_idempotency_key(&db, "key", &id, &hash, later, TTL).unwrap()
+            else {
+                panic!("same body not replayed");
+            };
+            assert_eq!((record.request_id, record.response_status), (id, Some(200)));
+            assert_eq!(
+                claim_idempotency_key(&db, "key", &id, &other_hash, later, TTL).unwrap(),
+                IdempotencyClaim::Conflict
+            );
+
+            // Expired, the key is used again and the old record is pruned
+            let expired = NOW.saturating_add(TTL);
+            assert_eq!(
+                claim_idempotency_key(&db, "key", &id, &other_hash, expired, TTL).unwrap(),
+                IdempotencyClaim::New
+            );
+            claim_idempotency_key(&db, "old", &id, &hash, NOW, TTL).unwrap();
+            assert_eq!(prune_idempotency_keys(&db, expired).unwrap(), 1);
+            assert!(idempotency_record(&db, "old").unwrap().is_none());
+            assert!(idempotency_record(&db, "key").unwrap().is_some());
+        }
+    }
+
+    #[test]
+    fn test_failed_and_interrupted_creations_free_the_key() {
+        for db in each_engine() {
+            let (first, other) = (input("1"), input("2"));
+            let id = BRequest::new(first.clone(), Timestamp::now()).id;
+            let hash = idempotency_body_hash(&first, None, &ClientLabels::default());
+            let other_hash = idempotency_body_hash(&other, None, &ClientLabels::default());
+
+            claim_idempotency_key(&db, "key", &id, &hash, NOW, TTL).unwrap();
+            release_idempotency_key(&db, "key").unwrap();
+            assert_eq!(
+                claim_idempotency_key(&db, "key", &id, &other_hash, NOW, TTL).unwrap(),
+                IdempotencyClaim::New
+            );
+
+            // Still running, another body conflicts until the creation is interrupted
+            let running = NOW.saturating_add(Duration::from_secs(1));
+            assert_eq!(
+                claim_idempotency_key(&db, "key", &id, &hash, running, TTL).unwrap(),
+                IdempotencyClaim::Conflict
+            );
+            let interrupted = NOW.saturating_add(IDEMPOTENCY_IN_PROGRESS_TIMEOUT);
+            assert_eq!(
+                claim_idempotency_key(&db, "key", &id, &hash, interrupted, TTL).unwrap(),
+                IdempotencyClaim::New
+            );
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use log::{error, info};
+use storage::{db::Database, keys::IN_FLIGHT_MINTS};
+
+use crate::{request_data, SharedClock, Status};
+
+/// Time after which an in flight mint is considered abandoned and can be retried
+pub const IN_FLIGHT_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Request ids with a mint transaction currently being built or confirmed,
+/// with the unix time (secs) the attempt started
+#[derive(Clone, Debug)]
+pub struct InFlightRegistry {
+    entries: Arc<Mutex<HashMap<String, u64>>>,
+    dropped: Arc<AtomicU64>,
+    timeout: Duration,
+    clock: SharedClock,
+}
+
+impl InFlightRegistry {
+    /// Loads the entries persisted by a previous run
+    pub fn load(db: &Database, timeout: Duration, clock: SharedClock) -> Self {
+        let entries = db
+            .read::<_, HashMap<String, u64>>(IN_FLIGHT_MINTS)
+            .unwrap_or_else(|err| {
+                error!("Could not read in flight mints {:?}", err);
+                None
+            })
+            .unwrap_or_default();
+
+        InFlightRegistry {
+            entries: Arc::new(Mutex::new(entries)),
+            dropped: Arc::new(AtomicU64::new(0)),
+            timeout,
+            clock,
+        }
+    }
+
+    /// Marks the request as in flight, returns false if a non expired attempt already exists
+    pub fn try_acquire(&self, request_id: &str, db: &Database) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let now = self.clock.now().as_secs();
+
+        if let Some(started) = entries.get(request_id) {
+            if now.saturating_sub(*started) < self.timeout.as_secs() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                info!("Mint already in flight for request {request_id}");
+                return false;
+            }
+            info!("In flight mint for request {request_id} expired, retrying");
+        }
+
+        entries.insert(request_id.to_owned(), now);
+        persist(&entries, db);
+        true
+    }
+
+    pub fn release(&self, request_id: &str, db: &Database) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.remove(request_id).is_some() {
+            persist(&entries, db);
+        }
+    }
+
+    /// Runs the mint `send` unless a mint of the request is already in flight, None when
+    /// the message is dropped. A failed mint is released so it can be retried, a sent one
+    /// stays in flight until the sweep sees it settled
+    pub async fn send_mint<T, E, F>(
+        &self,
+        request_id: &str,
+        db: &Database,
+        send: F,
+    ) -> Option<Result<T, E>>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        if !self.try_acquire(request_id, db) {
+            return None;
+        }
+        let result = send.await;
+        if result.is_err() {
+            self.release(request_id, db);
+        }
+        Some(result)
+    }
+
+    /// Releases the mints of the requests completed, canceled, refunded, parked or deleted
+    /// since they were sent. A request waiting for its destination token account has no
+    /// mint sent
+    pub fn release_settled(&self, db: &Database) {
+        let ids: Vec<String> = self.entries.lock().unwrap().keys().cloned().collect();
+        for id in ids {
+            let settled = match request_data(&id, db) {
+                Ok(Some(request)) => matches!(
+                    request.status,
+                    Status::Completed
+                        | Status::Canceled
+                        | Status::RefundEligible
+                        | Status::Refunded
+                        | Status::NeedsIntervention
+                        | Status::AwaitingDestinationAccount
+                ),
+                Ok(None) => true,
+                Err(err) => {
+                    error!("Could not read in flight request {id}: {:?}", err);
+                    false
+                }
+            };
+            if settled {
+                info!("Mint of request {id} settled, no longer in flight");
+                self.release(&id, db);
+            }
+        }
+    }
+
+    pub fn is_in_flight(&self, request_id: &str) -> bool {
+        self.entries.lock().unwrap().contains_key(request_id)
+    }
+
+    /// Number of mints in flight, expired attempts included until retried
+    pub fn count(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Number of duplicated mint messages dropped since startup
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+fn persist(entries: &HashMap<String, u64>, db: &Database) {
+    if let Err(err) = db.write_value(IN_FLIGHT_MINTS, entries) {
+        error!("Could not persist in flight mints {:?}", err);
+    }
+}
+
+#[cfg(test)]
+mod in_flight_test {
+    use crate::{
+        system_clock, tx_channel, BRequest, Chains, ChannelMetrics, InFlightRegistry, InputRequest,
+        MessageMint, MockClock, Status, Timestamp, TxMessage, IN_FLIGHT_TIMEOUT,
+    };
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+    use storage::{keys::IN_FLIGHT_MINTS, testing::each_engine};
+
+    #[test]
+    fn test_duplicate_mint_is_dropped() {
+        for db in each_engine() {
+            let registry = InFlightRegistry::load(&db, IN_FLIGHT_TIMEOUT, system_clock());
+
+            // Two mints back to back, only the first one goes through
+            assert!(registry.try_acquire("request1", &db));
+            assert!(!registry.try_acquire("request1", &db));
+            assert_eq!(registry.dropped_count(), 1);
+
+            // Other requests are not affected
+            assert!(registry.try_acquire("request2", &db));
+
+            // Once released it can be minted again
+            registry.release("request1", &db);
+            assert!(!registry.is_in_flight("request1"));
+            assert!(registry.try_acquire("request1", &db));
+        }
+    }
+
+    #[test]
+    fn test_in_flight_survives_restart() {
+        for db in each_engine() {
+            let registry = InFlightRegistry::load(&db, IN_FLIGHT_TIMEOUT, system_clock());
+            assert!(registry.try_acquire("request1", &db));
+
+            let reloaded = InFlightRegistry::load(&db, IN_FLIGHT_TIMEOUT, system_clock());
+            assert!(reloaded.is_in_flight("request1"));
+            assert!(!reloaded.try_acquire("request1", &db));
+        }
+    }
+
+    #[test]
+    fn test_in_flight_entry_expires() {
+        for db in each_engine() {
+            // Attempt started long ago and never released
+            let mut entries = HashMap::new();
+            entries.insert("request1".to_string(), 0u64);
+            db.write_value(IN_FLIGHT_MINTS, &entries).unwrap();
+
+            let registry = InFlightRegistry::load(&db, Duration::from_secs(60), system_clock());
+            assert!(registry.is_in_flight("request1"));
+            assert!(registry.try_acquire("request1", &db));
+            assert_eq!(registry.dropped_count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_clock_stepping_back_keeps_entry() {
+        for db in each_engine() {
+            let clock = MockClock::new(Timestamp::from_secs(1_700_000_000));
+            let registry =
+                InFlightRegistry::load(&db, Duration::from_secs(60), Arc::new(clock.clone()));
+            assert!(registry.try_acquire("request1", &db));
+
+            // A backwards step neither panics nor expires the attempt
+            clock.rewind(Duration::from_secs(3600));
+            assert!(!registry.try_acquire("request1", &db));
+
+            clock.advance(Duration::from_secs(3600 + 60));
+            assert!(registry.try_acquire("request1", &db));
+        }
+    }
+
+    #[test]
+    fn test_settled_statuses_release_the_mint() {
+        for db in each_engine() {
+            let registry = InFlightRegistry::load(&db, IN_FLIGHT_TIMEOUT, system_clock());
+            let statuses = [
+                (Status::TokenReceived, false),
+                (Status::TokenMinted, false),
+                (Status::Completed, true),
+                (Status::Canceled, true),
+                (Status::RefundEligible, true),
+                (Status::Refunded, true),
+                (Status::NeedsIntervention, true),
+                (Status::AwaitingDestinationAccount, true),
+            ];
+            for (token_id, (status, settled)) in statuses.into_iter().enumerate() {
+                let mut request = BRequest::new(
+                    InputRequest {
+                        contract_or_mint: "0xcontract".to_string(),
+                        token_id: token_id.to_string(),
+                        token_owner: "0xowner".to_string(),
+                        origin_network: Chains::SOLANA,
+                        destination_account: "destination".to_string(),
+                    },
+                    Timestamp::now(),
+                );
+                request.status = status.clone();
+                request.save(&db).unwrap();
+                assert!(registry.try_acquire(&request.id, &db));
+
+                registry.release_settled(&db);
+                assert_eq!(!registry.is_in_flight(&request.id), settled, "{status:?}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_back_to_back_mints_send_once() {
+        for db in each_engine() {
+            let registry = InFlightRegistry::load(&db, IN_FLIGHT_TIMEOUT, system_clock());
+            let mut request = BRequest::new(
+                InputRequest {
+                    contract_or_mint: "0xcontract".to_string(),
+                    token_id: "1".to_string(),
+                    token_owner: "0xowner".to_string(),
+                    origin_network: Chains::SOLANA,
+                    destination_account: "destination".to_string(),
+                },
+                Timestamp::now(),
+            );
+            request.status = Status::TokenReceived;
+            request.save(&db).unwrap();
+
+            let (tx, mut rx) = tx_channel(Chains::EVM, 10, &ChannelMetrics::default());
+            for _ in 0..2 {
+                let message = TxMessage::Mint(MessageMint {
+                    request_id: request.id.clone(),
+                    token_metadata: "https://example.com/1.json".to_string(),
+                    trace_context: None,
+                });
+                tx.send(message).await.unwrap();
+            }
+
+            // The first mint is sent and confirmed later, the second one is dropped
+            let sends = AtomicUsize::new(0);
+            for _ in 0..2 {
+                let message = rx.recv().await.unwrap();
+                let sent = registry
+                    .send_mint(message.request_id(), &db, async {
+                        sends.fetch_add(1, Ordering::Relaxed);
+                        Ok::<_, ()>("0xmint")
+                    })
+                    .await;
+                assert_eq!(sent.is_some(), sends.load(Ordering::Relaxed) == 1);
+            }
+            assert_eq!(sends.load(Ordering::Relaxed), 1);
+            assert_eq!(registry.dropped_count(), 1);
+
+            // Minted but not confirmed yet
+            request.update_state(&db, Timestamp::now()).unwrap();
+            registry.release_settled(&db);
+            assert!(registry.is_in_flight(&request.id));
+
+            request.status = Status::Completed;
+            request.save(&db).unwrap();
+            registry.release_settled(&db);
+            assert!(!registry.is_in_flight(&request.id));
+
+            // A failed mint is released right away
+            let failed = registry
+                .send_mint(&request.id, &db, async { Err::<(), _>("reverted") })
+                .await;
+            assert_eq!(failed, Some(Err("reverted")));
+            assert!(!registry.is_in_flight(&request.id));
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+/// Sweep, retry and polling intervals of the background tasks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Intervals {
+    /// Pause between pending requests of EVM origin, paced apart from the Solana ones
+    pub evm_pending_request: Duration,
+    /// Pause between pending requests of Solana origin
+    pub solana_pending_request: Duration,
+    /// RequestReceived requests created more recently are left to the event listeners
+    pub received_sweep_min_age: Duration,
+    /// Wait before restarting a failed event listener
+    pub listener_backoff: Duration,
+    pub paused_events_drain: Duration,
+    pub authorization_check: Duration,
+    /// Check for orphans not claimed in time
+    pub orphan_expiry: Duration,
+    /// Enqueue the refunds of canceled requests holding their token
+    pub pending_refunds: Duration,
+    /// Check for requests whose token URI retry is due, each has its own backoff
+    pub metadata_retry: Duration,
+    /// Check for request callbacks due, each has its own backoff
+    pub callback_delivery: Duration,
+    /// Deletion of the expired idempotency keys
+    pub idempotency_key_prune: Duration,
+    /// Check of the custody transfers waiting for finality
+    pub finality_check: Duration,
+    /// First wait before restarting a tx processor, doubled on each restart
+    pub processor_restart_backoff: Duration,
+    /// Heartbeat silence after which a tx processor is restarted
+    pub processor_wedged_after: Duration,
+    /// Check of the tx hashes recorded on the finalized requests
+    pub tx_audit: Duration,
+    /// Catch up of a read-only database with the writes of its primary
+    pub db_catch_up: Duration,
+    /// Sampling of the record counts and disk size of the database for the metrics
+    pub db_metrics_sample: Duration,
+    /// Persistence of the RPC endpoint statistics into their history
+    pub rpc_health_sample: Duration,
+}
+
+impl Intervals {
+    pub const PRODUCTION: Intervals = Intervals {
+        evm_pending_request: Duration::from_secs(8),
+        solana_pending_request: Duration::from_secs(8),
+        received_sweep_min_age: Duration::from_secs(120),
+        listener_backoff: Duration::from_secs(5),
+        paused_events_drain: Duration::from_secs(30),
+        authorization_check: Duration::from_secs(300),
+        orphan_expiry: Duration::from_secs(600),
+        pending_refunds: Duration::from_secs(60),
+        metadata_retry: Duration::from_secs(30),
+        callback_delivery: Duration::from_secs(15),
+        idempotency_key_prune: Duration::from_secs(3600),
+        finality_check: Duration::from_secs(4),
+        processor_restart_backoff: Duration::from_secs(1),
+        processor_wedged_after: Duration::from_secs(600),
+        tx_audit: Duration::from_secs(24 * 3600),
+        db_catch_up: Duration::from_secs(1),
+        db_metrics_sample: Duration::from_secs(60),
+        rpc_health_sample: Duration::from_secs(60),
+    };
+
+    /// Local validators produce blocks on demand, there is nothing to wait for
+    pub const DEV: Intervals = Intervals {
+        evm_pending_request: Duration::from_millis(500),
+        solana_pending_request: Duration::from_millis(500),
+        received_sweep_min_age: Duration::from_secs(5),
+        listener_backoff: Duration::from_secs(1),
+        paused_events_drain: Duration::from_secs(5),
+        authorization_check: Duration::from_secs(30),
+        orphan_expiry: Duration::from_secs(30),
+        pending_refunds: Duration::from_secs(5),
+        metadata_retry: Duration::from_secs(5),
+        callback_delivery: Duration::from_secs(2),
+        idempotency_key_prune: Duration::from_secs(60),
+        finality_check: Duration::from_secs(1),
+        processor_restart_backoff: Duration::from_millis(200),
+        processor_wedged_after: Duration::from_secs(120),
+        tx_audit: Duration::from_secs(600),
+        db_catch_up: Duration::from_millis(500),
+        db_metrics_sample: Duration::from_secs(10),
+        rpc_health_sample: Duration::from_secs(10),
+    };
+
+    pub fn for_mode(dev_mode: bool) -> Self {
+        if dev_mode {
+            Intervals::DEV
+        } else {
+            Intervals::PRODUCTION
+        }
+    }
+}
+
+impl Default for Intervals {
+    fn default() -> Self {
+        Intervals::PRODUCTION
+    }
+}
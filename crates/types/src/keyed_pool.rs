@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::{mpsc, Semaphore};
+
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Runs jobs concurrently across keys and in dispatch order for the same key. Each key
+/// with queued work has one worker task, dropped once its queue is drained. Queued and
+/// running jobs are bounded, `dispatch` waits for a free slot when the pool is full
+#[derive(Clone, Debug)]
+pub struct KeyedTaskPool {
+    queues: Arc<Mutex<HashMap<String, mpsc::UnboundedSender<Job>>>>,
+    slots: Arc<Semaphore>,
+}
+
+impl KeyedTaskPool {
+    pub fn new(max_jobs: usize) -> Self {
+        KeyedTaskPool {
+            queues: Arc::new(Mutex::new(HashMap::new())),
+            slots: Arc::new(Semaphore::new(max_jobs)),
+        }
+    }
+
+    /// Queues `job` after the jobs already dispatched for `key`
+    pub async fn dispatch<F>(&self, key: &str, job: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let slot = self
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Task pool semaphore closed");
+        let job: Job = Box::pin(async move {
+            job.await;
+            drop(slot);
+        });
+
+        let mut queues = self.queues.lock().unwrap();
+        // A worker that panicked left a closed queue, replaced by a new worker below
+        let job = match queues.get(key) {
+            Some(queue) => match queue.send(job) {
+                Ok(()) => return,
+                Err(mpsc::error::SendError(job)) => job,
+            },
+            None => job,
+        };
+
+        let (queue, jobs) = mpsc::unbounded_channel();
+        let _ = queue.send(job);
+        queues.insert(key.to_string(), queue);
+        tokio::spawn(run_worker(key.to_string(), jobs, self.queues.clone()));
+    }
+
+    /// Keys with queued or running jobs
+    pub fn active_keys(&self) -> usize {
+        self.queues.lock().unwrap().len()
+    }
+}
+
+async fn run_worker(
+    key: String,
+    mut jobs: mpsc::UnboundedReceiver<Job>,
+    queues: Arc<Mutex<HashMap<String, mpsc::UnboundedSender<Job>>>>,
+) {
+    loop {
+        // Checked under the lock so a job dispatched meanwhile is not left without worker
+        let job = {
+            let mut queues = queues.lock().unwrap();
+            match jobs.try_recv() {
+                Ok(job) => job,
+                Err(_) => {
+                    queues.remove(&key);
+                    return;
+                }
+            }
+        };
+        job.await;
+    }
+}
+
+#[cfg(test)]
+mod keyed_pool_test {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use tokio::sync::oneshot;
+
+    use crate::KeyedTaskPool;
+
+    #[tokio::test]
+    async fn test_slow_key_does_not_block_other_keys() {
+        let pool = KeyedTaskPool::new(8);
+        let processed = Arc::new(Mutex::new(Vec::new()));
+        let (release, released) = oneshot::channel::<()>();
+
+        let log = processed.clone();
+        pool.dispatch("slow", async move {
+            let _ = released.await;
+            log.lock().unwrap().push("slow 1");
+        })
+        .await;
+        let log = processed.clone();
+        pool.dispatch("slow", async move {
+            log.lock().unwrap().push("slow 2");
+        })
+        .await;
+
+        let (done, fast_done) = oneshot::channel();
+        let log = processed.clone();
+        pool.dispatch("fast", async move {
+            log.lock().unwrap().push("fast");
+            let _ = done.send(());
+        })
+        .await;
+
+        tokio::time::timeout(Duration::from_secs(1), fast_done)
+            .await
+            .expect("Other request waited for the slow one")
+            .unwrap();
+        assert_eq!(*processed.lock().unwrap(), vec!["fast"]);
+
+        // The second event of the slow request runs after the first one
+        release.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while pool.active_keys() > 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(*processed.lock().unwrap(), vec!["fast", "slow 1", "slow 2"]);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_waits_when_pool_is_full() {
+        let pool = KeyedTaskPool::new(1);
+        let (release, released) = oneshot::channel::<()>();
+        pool.dispatch("first", async move {
+            let _ = released.await;
+        })
+        .await;
+
+        let blocked =
+            tokio::time::timeout(Duration::from_millis(50), pool.dispatch("second", async {}))
+                .await;
+        assert!(blocked.is_err());
+
+        release.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), pool.dispatch("second", async {}))
+            .await
+            .unwrap();
+    }
+}
@@ -3,3 +3,185 @@ pub use types::*;
 
 pub mod functions;
 pub use functions::*;
+
+pub mod chain_head;
+pub use chain_head::*;
+
+pub mod in_flight;
+pub use in_flight::*;
+
+pub mod version;
+pub use version::*;
+
+pub mod uri_policy;
+pub use uri_policy::*;
+
+pub mod timeout;
+pub use timeout::*;
+
+pub mod tx_channel;
+pub use tx_channel::*;
+
+pub mod pause;
+pub use pause::*;
+
+pub mod event_validation;
+pub use event_validation::*;
+
+pub mod units;
+pub use units::*;
+
+pub mod status_feed;
+pub use status_feed::*;
+
+pub mod clock;
+pub use clock::*;
+
+pub mod intervals;
+pub use intervals::*;
+
+pub mod custody;
+pub use custody::*;
+
+pub mod call_context;
+pub use call_context::*;
+
+pub mod explorer;
+pub use explorer::*;
+
+pub mod keyed_pool;
+pub use keyed_pool::*;
+
+pub mod orphan;
+pub use orphan::*;
+
+pub mod metadata_store;
+pub use metadata_store::*;
+
+pub mod request_lock;
+pub use request_lock::*;
+
+pub mod refund;
+pub use refund::*;
+
+pub mod quota;
+pub use quota::*;
+
+pub mod tx_state;
+pub use tx_state::*;
+
+pub mod collection;
+pub use collection::*;
+
+pub mod bridge_error;
+pub use bridge_error::*;
+
+pub mod event_cursor;
+pub use event_cursor::*;
+
+pub mod request_id;
+pub use request_id::*;
+
+pub mod metadata_retry;
+pub use metadata_retry::*;
+
+pub mod trace_context;
+pub use trace_context::*;
+
+pub mod processed_logs;
+pub use processed_logs::*;
+
+pub mod callback;
+pub use callback::*;
+
+pub mod pending_queue;
+pub use pending_queue::*;
+
+pub mod pending_repair;
+pub use pending_repair::*;
+
+pub mod failure_report;
+pub use failure_report::*;
+
+pub mod id_scheme;
+pub use id_scheme::*;
+
+pub mod rpc_limiter;
+pub use rpc_limiter::*;
+
+pub mod rpc_health;
+pub use rpc_health::*;
+
+pub mod bridge_deployment;
+pub use bridge_deployment::*;
+
+pub mod custody_limit;
+pub use custody_limit::*;
+
+pub mod metadata_snapshot;
+pub use metadata_snapshot::*;
+
+pub mod backfill;
+pub use backfill::*;
+pub mod metadata_translation;
+pub use metadata_translation::*;
+
+pub mod idempotency;
+pub use idempotency::*;
+
+pub mod secret;
+pub use secret::*;
+
+pub mod wrapped;
+pub use wrapped::*;
+
+pub mod finality;
+pub use finality::*;
+
+pub mod error_record;
+pub use error_record::*;
+
+pub mod log_privacy;
+pub use log_privacy::*;
+
+pub mod supervisor;
+pub use supervisor::*;
+
+pub mod tx_index;
+pub use tx_index::*;
+
+pub mod replay;
+pub use replay::*;
+
+pub mod branding;
+pub use branding::*;
+
+pub mod purge;
+pub use purge::*;
+
+pub mod fast_path;
+pub use fast_path::*;
+
+pub mod token_id;
+pub use token_id::*;
+
+pub mod tx_audit;
+pub use tx_audit::*;
+
+pub mod work_claim;
+pub use work_claim::*;
+
+pub mod client_labels;
+pub use client_labels::*;
+
+pub mod send_journal;
+pub use send_journal::*;
+
+pub mod mint_recipient;
+pub use mint_recipient::*;
+
+pub mod api_metrics;
+pub use api_metrics::*;
+
+pub mod destination_account;
+pub use destination_account::*;
@@ -0,0 +1,304 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{redact_urls, BRequest, InputRequest, TxMessage};
+
+/// Longest field of a request or message written at info level, URIs and malformed
+/// inputs can be of any size
+pub const MAX_LOGGED_FIELD_LEN: usize = 64;
+/// Characters of an account kept at each end when it is masked
+const MASK_KEEP: usize = 4;
+
+static LOG_PRIVACY: AtomicBool = AtomicBool::new(false);
+
+/// Masks the accounts and URLs of the logs from now on, and keeps the full request
+/// details out of the debug logs. Set once on startup from `LOG_PRIVACY`
+pub fn set_log_privacy(enabled: bool) {
+    LOG_PRIVACY.store(enabled, Ordering::Relaxed);
+}
+
+pub fn log_privacy() -> bool {
+    LOG_PRIVACY.load(Ordering::Relaxed)
+}
+
+/// `field` cut to `MAX_LOGGED_FIELD_LEN` bytes, followed by the number of bytes left out
+fn bounded(field: &str) -> String {
+    if field.len() <= MAX_LOGGED_FIELD_LEN {
+        return field.to_string();
+    }
+    let mut end = MAX_LOGGED_FIELD_LEN;
+    while !field.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…(+{} bytes)", &field[..end], field.len() - end)
+}
+
+/// `account` with only its first and last characters, the `0x` of EVM addresses kept
+pub fn mask_account(account: &str) -> String {
+    let prefix = if account.starts_with("0x") { 2 } else { 0 };
+    let chars: Vec<char> = account.chars().collect();
+    if chars.len() <= prefix + 2 * MASK_KEEP {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..prefix + MASK_KEEP].iter().collect();
+    let tail: String = chars[chars.len() - MASK_KEEP..].iter().collect();
+    format!("{head}…{tail}")
+}
+
+fn account_with(account: &str, private: bool) -> String {
+    if private {
+        mask_account(account)
+    } else {
+        bounded(account)
+    }
+}
+
+/// Account for an info log, masked under `LOG_PRIVACY`
+pub fn log_account(account: &str) -> String {
+    account_with(account, log_privacy())
+}
+
+/// Whether a word of a log text is an account: an EVM address or a Solana key
+fn is_account(word: &str) -> bool {
+    if let Some(hex) = word.strip_prefix("0x") {
+        return hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit());
+    }
+    (32..=44).contains(&word.len())
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() && !matches!(c, '0' | 'O' | 'I' | 'l'))
+}
+
+fn private_text_with(text: &str, private: bool) -> String {
+    if !private {
+        return text.to_string();
+    }
+    let text = redact_urls(text);
+    let mut masked = String::with_capacity(text.len());
+    let mut word_start = None;
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (c.is_ascii_alphanumeric(), word_start) {
+            (true, None) => word_start = Some(i),
+            (true, Some(_)) => {}
+            (false, start) => {
+                if let Some(start) = start {
+                    let word = &text[start..i];
+                    if is_account(word) {
+                        masked.push_str(&mask_account(word));
+                    } else {
+                        masked.push_str(word);
+                    }
+                    word_start = None;
+                }
+                if i < text.len() {
+                    masked.push(c);
+                }
+            }
+        }
+    }
+    masked
+}
+
+/// Error message or payload for the logs. Under `LOG_PRIVACY` its URLs are redacted and
+/// its EVM addresses and Solana keys masked
+pub fn private_text(text: &str) -> String {
+    private_text_with(text, log_privacy())
+}
+
+/// Full `Debug` form of a value for a debug log, None under `LOG_PRIVACY`
+pub fn debug_detail<T: fmt::Debug>(value: &T) -> Option<String> {
+    debug_detail_with(value, log_privacy())
+}
+
+fn debug_detail_with<T: fmt::Debug>(value: &T, private: bool) -> Option<String> {
+    (!private).then(|| format!("{value:?}"))
+}
+
+/// Request input for info logs, with bounded fields and its accounts masked under
+/// `LOG_PRIVACY`. The full input goes to the debug logs through `debug_detail`
+pub struct LoggableRequest<'a> {
+    request_id: Option<&'a str>,
+    input: &'a InputRequest,
+    private: bool,
+}
+
+impl<'a> LoggableRequest<'a> {
+    pub fn new(input: &'a InputRequest) -> Self {
+        LoggableRequest {
+            request_id: None,
+            input,
+            private: log_privacy(),
+        }
+    }
+
+    pub fn of(request: &'a BRequest) -> Self {
+        LoggableRequest {
+            request_id: Some(request.id.as_str()),
+            ..Self::new(&request.input)
+        }
+    }
+
+    pub fn with_privacy(mut self, private: bool) -> Self {
+        self.private = private;
+        self
+    }
+}
+
+impl fmt::Display for LoggableRequest<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(request_id) = self.request_id {
+            write!(f, "request {request_id} ")?;
+        }
+        write!(
+            f,
+            "from {:?} token {} of {}, owner {}, destination {}",
+            self.input.origin_network,
+            bounded(&self.input.token_id),
+            bounded(&self.input.contract_or_mint),
+            account_with(&self.input.token_owner, self.private),
+            account_with(&self.input.destination_account, self.private),
+        )
+    }
+}
+
+/// Tx processor message for info logs, without its metadata URI. The owner of a bridge
+/// request is masked under `LOG_PRIVACY`
+pub struct LoggableMessage<'a> {
+    message: &'a TxMessage,
+    private: bool,
+}
+
+impl<'a> LoggableMessage<'a> {
+    pub fn new(message: &'a TxMessage) -> Self {
+        LoggableMessage {
+            message,
+            private: log_privacy(),
+        }
+    }
+
+    pub fn with_privacy(mut self, private: bool) -> Self {
+        self.private = private;
+        self
+    }
+}
+
+impl fmt::Display for LoggableMessage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.message {
+            TxMessage::Mint(mint) => write!(
+                f,
+                "Mint of request {} with a {} bytes URI",
+                mint.request_id,
+                mint.token_metadata.len()
+            ),
+            TxMessage::NewRequest(request) => write!(
+                f,
+                "NewRequest of request {} for token {} of {}, owner {}",
+                request.request_id,
+                bounded(&request.token_id),
+                bounded(&request.token_contract),
+                account_with(&request.token_owner, self.private),
+            ),
+            TxMessage::Refund(refund) => write!(f, "Refund of request {}", refund.request_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod log_privacy_test {
+    use alloy::primitives::B256;
+
+    use crate::{
+        log_privacy::{debug_detail_with, private_text_with},
+        mask_account, Chains, InputRequest, LoggableMessage, LoggableRequest, MessageMint,
+        MessageNewRequest, RequestId, TxMessage,
+    };
+
+    const EVM_OWNER: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+    const SOLANA_DESTINATION: &str = "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU";
+
+    fn input() -> InputRequest {
+        InputRequest {
+            contract_or_mint: "0x5FbDB2315678afecb367f032d93F642f64180aa3".to_string(),
+            token_id: "9".repeat(80),
+            token_owner: EVM_OWNER.to_string(),
+            origin_network: Chains::EVM,
+            destination_account: SOLANA_DESTINATION.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_masked_request_format() {
+        assert_eq!(mask_account(EVM_OWNER), "0xf39F…2266");
+        assert_eq!(mask_account(SOLANA_DESTINATION), "7xKX…gAsU");
+        assert_eq!(mask_account("short"), "*****");
+
+        let input = input();
+        let masked = LoggableRequest::new(&input).with_privacy(true).to_string();
+        assert_eq!(
+            masked,
+            format!(
+                "from EVM token {}…(+16 bytes) of 0x5FbDB2315678afecb367f032d93F642f64180aa3, \
+                 owner 0xf39F…2266, destination 7xKX…gAsU",
+                "9".repeat(64)
+            )
+        );
+
+        // Bounded without the flag, the accounts kept
+        let plain = LoggableRequest::new(&input).with_privacy(false).to_string();
+        assert!(plain.contains(EVM_OWNER), "{plain}");
+        assert!(plain.contains(SOLANA_DESTINATION), "{plain}");
+        assert!(!plain.contains(&input.token_id), "{plain}");
+
+        let new_request = TxMessage::NewRequest(MessageNewRequest {
+            token_contract: input.contract_or_mint.clone(),
+            token_owner: EVM_OWNER.to_string(),
+            token_id: "1".to_string(),
+            request_id: RequestId::from(B256::repeat_byte(0xab)),
+            trace_context: None,
+        });
+        let logged = LoggableMessage::new(&new_request)
+            .with_privacy(true)
+            .to_string();
+        assert!(logged.ends_with("owner 0xf39F…2266"), "{logged}");
+        let mint = TxMessage::Mint(MessageMint {
+            request_id: RequestId::from(B256::repeat_byte(0xab)),
+            token_metadata: "ipfs://metadata/1.json".to_string(),
+            trace_context: None,
+        });
+        assert_eq!(
+            LoggableMessage::new(&mint).to_string(),
+            format!("Mint of request 0x{} with a 22 bytes URI", "ab".repeat(32))
+        );
+    }
+
+    #[test]
+    fn test_debug_detail_only_without_privacy() {
+        let input = input();
+        let full = debug_detail_with(&input, false).unwrap();
+        assert_eq!(full, format!("{input:?}"));
+        assert!(full.contains(&input.token_id));
+        assert_eq!(debug_detail_with(&input, true), None);
+    }
+
+    #[test]
+    fn test_private_text_masks_accounts_and_urls() {
+        let error = format!(
+            "transfer from {EVM_OWNER} to {SOLANA_DESTINATION} failed calling \
+             https://rpc.example.com/key, request 0x{}",
+            "ab".repeat(32)
+        );
+        let masked = private_text_with(&error, true);
+        assert_eq!(
+            masked,
+            format!(
+                "transfer from 0xf39F…2266 to 7xKX…gAsU failed calling \
+                 https://rpc.example.com/[REDACTED], request 0x{}",
+                "ab".repeat(32)
+            )
+        );
+        assert_eq!(private_text_with(&error, false), error);
+    }
+}
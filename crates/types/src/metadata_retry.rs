@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use storage::db::Database;
+
+use crate::{BRequest, HistoryEntry, Result, Timestamp};
+
+/// Wait before the first retry of a token URI, doubled on every failed attempt
+pub const METADATA_RETRY_BASE: Duration = Duration::from_secs(60);
+pub const METADATA_RETRY_MAX: Duration = Duration::from_secs(6 * 3600);
+
+/// What the mint of an EVM origin token does when its `tokenURI` reverts or is empty,
+/// usual for collections revealed after the mint
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum MissingUriPolicy {
+    /// The request waits in TokenReceived and the URI is read again on a backoff schedule
+    #[default]
+    Retry,
+    /// The destination token is minted with this URI
+    Placeholder(String),
+}
+
+impl MissingUriPolicy {
+    pub fn from_config(placeholder: Option<&str>) -> Self {
+        match placeholder {
+            Some(uri) => MissingUriPolicy::Placeholder(uri.to_string()),
+            None => MissingUriPolicy::Retry,
+        }
+    }
+}
+
+/// Request waiting for the URI of its origin token
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MetadataPending {
+    /// Reads of the URI that failed
+    pub attempts: u32,
+    pub retry_at: Timestamp,
+}
+
+/// Wait after the `attempts` failed read, capped to `METADATA_RETRY_MAX`
+pub fn metadata_retry_delay(attempts: u32) -> Duration {
+    let doublings = attempts.saturating_sub(1).min(16);
+    METADATA_RETRY_BASE
+        .saturating_mul(1 << doublings)
+        .min(METADATA_RETRY_MAX)
+}
+
+impl BRequest {
+    /// Parks the request until the URI of its origin token can be read, the reason is
+    /// kept in `last_error`
+    pub fn park_metadata(&mut self, db: &Database, reason: &str, now: Timestamp) -> Result<()> {
+        let attempts = self.metadata_pending.map_or(0, |pending| pending.attempts) + 1;
+        let retry_at = now.saturating_add(metadata_retry_delay(attempts));
+        info!(
+            "Token URI of request {} unavailable, attempt {attempts}, retrying at {}",
+            self.id,
+            retry_at.as_secs()
+        );
+        if self.metadata_pending.is_none() {
+            self.history.push(HistoryEntry {
+                time: now,
+                event: "Token URI unavailable, waiting for it".to_string(),
+            });
+        }
+        self.metadata_pending = Some(MetadataPending { attempts, retry_at });
+        self.last_error = Some(reason.to_string());
+        self.last_error_context = None;
+        self.save(db)
+    }
+
+    /// Whether the URI can be read, always for requests not parked
+    pub fn metadata_retry_due(&self, now: Timestamp) -> bool {
+        match self.metadata_pending {
+            Some(pending) => pending.retry_at <= now,
+            None => true,
+        }
+    }
+
+    /// Ends the wait once the URI was read
+    pub fn clear_metadata_pending(&mut self, db: &Database) -> Result<()> {
+        if self.metadata_pending.take().is_some() {
+            self.save(db)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod metadata_retry_test {
+    use std::time::Duration;
+
+    use storage::testing::each_engine;
+
+    use crate::{
+        metadata_retry_delay, request_data, BRequest, Chains, InputRequest, MetadataPending,
+        MissingUriPolicy, Timestamp, METADATA_RETRY_MAX,
+    };
+
+    #[test]
+    fn test_retry_delay_doubles_up_to_max() {
+        assert_eq!(metadata_retry_delay(1), Duration::from_secs(60));
+        assert_eq!(metadata_retry_delay(2), Duration::from_secs(120));
+        assert_eq!(metadata_retry_delay(4), Duration::from_secs(480));
+        assert_eq!(metadata_retry_delay(10), METADATA_RETRY_MAX);
+        assert_eq!(metadata_retry_delay(u32::MAX), METADATA_RETRY_MAX);
+
+        assert_eq!(MissingUriPolicy::from_config(None), MissingUriPolicy::Retry);
+        assert_eq!(
+            MissingUriPolicy::from_config(Some("ipfs://placeholder")),
+            MissingUriPolicy::Placeholder("ipfs://placeholder".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parked_request_waits_for_retry() {
+        for db in each_engine() {
+            let mut request = BRequest::new(
+                InputRequest {
+                    contract_or_mint: "0x5fbdb2315678afecb367f032d93f642f64180aa3".to_string(),
+                    token_id: "1".to_string(),
+                    token_owner: "0xowner".to_string(),
+                    origin_network: Chains::EVM,
+                    destination_account: "destination".to_string(),
+                },
+                Timestamp::now(),
+            );
+            let now = Timestamp::from_secs(1_700_000_000);
+            assert!(request.metadata_retry_due(now));
+
+            request
+                .park_metadata(&db, "tokenURI reverted", now)
+                .unwrap();
+            request.park_metadata(&db, "tokenURI empty", now).unwrap();
+            let stored = request_data(&request.id, &db).unwrap().unwrap();
+            let retry_at = now.saturating_add(Duration::from_secs(120));
+            assert_eq!(
+                stored.metadata_pending,
+                Some(MetadataPending {
+                    attempts: 2,
+                    retry_at
+                })
+            );
+            assert_eq!(stored.last_error.as_deref(), Some("tokenURI empty"));
+            assert_eq!(stored.history.len(), 1);
+            assert!(!stored.metadata_retry_due(now));
+            assert!(stored.metadata_retry_due(retry_at));
+
+            request.clear_metadata_pending(&db).unwrap();
+            let stored = request_data(&request.id, &db).unwrap().unwrap();
+            assert_eq!(stored.metadata_pending, None);
+            assert!(stored.metadata_retry_due(now));
+        }
+    }
+}
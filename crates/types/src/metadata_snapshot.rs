@@ -0,0 +1,108 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use storage::db::Database;
+
+use crate::{BRequest, HistoryEntry, Result, Timestamp};
+
+/// Metadata of the origin token as first obtained for the mint, the retries of the mint
+/// send it again instead of reading the origin token
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MetadataSnapshot {
+    /// URI read from the origin token
+    pub origin_uri: String,
+    /// URI given to the mint before its branding: accepted or rewritten by the URI policy,
+    /// then pinned or translated
+    pub token_uri: String,
+    pub taken_at: Timestamp,
+}
+
+impl BRequest {
+    /// Keeps the metadata of the first mint attempt, saved before the mint is sent
+    pub fn keep_metadata_snapshot(
+        &mut self,
+        origin_uri: &str,
+        token_uri: &str,
+        now: Timestamp,
+        db: &Database,
+    ) -> Result<()> {
+        self.metadata_snapshot = Some(MetadataSnapshot {
+            origin_uri: origin_uri.to_string(),
+            token_uri: token_uri.to_string(),
+            taken_at: now,
+        });
+        self.history.push(HistoryEntry {
+            time: now,
+            event: format!("Metadata {origin_uri} kept for the mint as {token_uri}"),
+        });
+        self.save(db)
+    }
+
+    /// Drops the snapshot, the next mint attempt reads the metadata of the origin token
+    /// again
+    pub fn drop_metadata_snapshot(&mut self, now: Timestamp, db: &Database) -> Result<()> {
+        let Some(snapshot) = self.metadata_snapshot.take() else {
+            return Ok(());
+        };
+        info!(
+            "Metadata snapshot {} of request {} dropped",
+            snapshot.origin_uri, self.id
+        );
+        self.history.push(HistoryEntry {
+            time: now,
+            event: format!(
+                "Metadata snapshot {} dropped, read again on the next mint",
+                snapshot.origin_uri
+            ),
+        });
+        self.save(db)
+    }
+}
+
+#[cfg(test)]
+mod metadata_snapshot_test {
+    use std::sync::Arc;
+
+    use storage::{db::Database, engine::MemoryEngine};
+
+    use crate::{request_data, BRequest, Chains, InputRequest, Status, Timestamp};
+
+    const NOW: Timestamp = Timestamp::from_secs(1_700_000_000);
+
+    #[test]
+    fn test_snapshot_survives_a_restart() {
+        let engine = Arc::new(MemoryEngine::default());
+        let db = Database::with_engine(engine.clone()).unwrap();
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xcontract".to_string(),
+                token_id: "1".to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.status = Status::TokenReceived;
+        request
+            .keep_metadata_snapshot(
+                "https://origin.example/1.json",
+                "ipfs://bafkreimock",
+                NOW,
+                &db,
+            )
+            .unwrap();
+        drop(db);
+
+        let restarted = Database::with_engine(engine).unwrap();
+        let mut stored = request_data(&request.id, &restarted).unwrap().unwrap();
+        let snapshot = stored.metadata_snapshot.clone().unwrap();
+        assert_eq!(snapshot.origin_uri, "https://origin.example/1.json");
+        assert_eq!(snapshot.token_uri, "ipfs://bafkreimock");
+        assert_eq!(snapshot.taken_at, NOW);
+
+        stored.drop_metadata_snapshot(NOW, &restarted).unwrap();
+        let stored = request_data(&request.id, &restarted).unwrap().unwrap();
+        assert_eq!(stored.metadata_snapshot, None);
+        assert!(stored.history.last().unwrap().event.contains("dropped"));
+    }
+}
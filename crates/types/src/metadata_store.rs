@@ -0,0 +1,436 @@
+use std::{fmt::Debug, future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use storage::db::Database;
+
+use crate::{BRequest, BridgeError, Result, Secret, Timestamp, UriDecision, UriPolicy};
+
+/// Time allowed to each download and pinning call
+pub const PINNING_TIMEOUT: Duration = Duration::from_secs(60);
+/// Largest metadata JSON downloaded for pinning
+pub const MAX_PINNED_METADATA_SIZE: usize = 1024 * 1024;
+/// Largest image downloaded for pinning
+pub const MAX_PINNED_IMAGE_SIZE: usize = 20 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PinnedMetadata {
+    pub cid: String,
+}
+
+impl PinnedMetadata {
+    pub fn uri(&self) -> String {
+        format!("ipfs://{}", self.cid)
+    }
+}
+
+pub type PinFuture<'a> = Pin<Box<dyn Future<Output = Result<PinnedMetadata>> + Send + 'a>>;
+pub type FetchFuture<'a> = Pin<Box<dyn Future<Output = Result<Value>> + Send + 'a>>;
+
+/// Storage the token metadata is copied to before minting, so the destination token
+/// doesn't depend on the origin hosting
+pub trait MetadataStore: Send + Sync + Debug {
+    /// Copies the metadata JSON at `uri` and returns where it was stored
+    fn pin<'a>(&'a self, request_id: &'a str, uri: &'a str) -> PinFuture<'a>;
+    /// Downloads the metadata JSON at `uri`
+    fn fetch<'a>(&'a self, uri: &'a str) -> FetchFuture<'a>;
+    /// Stores a metadata JSON built by the relayer and returns where it was stored
+    fn pin_document<'a>(&'a self, request_id: &'a str, document: &'a Value) -> PinFuture<'a>;
+}
+
+/// Metadata store of the clients, in strict mode a failed pinning fails the mint so it
+/// is retried, otherwise the origin URI is minted
+#[derive(Clone, Debug)]
+pub struct MetadataPinning {
+    pub store: Arc<dyn MetadataStore>,
+    pub strict: bool,
+    /// Metaplex metadata of Solana tokens is translated to ERC721 before the EVM mint
+    pub translate: bool,
+}
+
+/// Pins the metadata of http(s) origin URIs and returns the URI to mint. The origin URI
+/// and the CID are kept on the request, a retried mint reuses the CID
+pub async fn pin_token_uri(
+    pinning: Option<&MetadataPinning>,
+    request: &mut BRequest,
+    origin_uri: &str,
+    uri: String,
+    db: &Database,
+    now: Timestamp,
+) -> Result<String> {
+    let Some(pinning) = pinning else {
+        return Ok(uri);
+    };
+    let scheme = origin_uri
+        .split_once(':')
+        .map(|(scheme, _)| scheme.to_lowercase());
+    if !matches!(scheme.as_deref(), Some("http" | "https")) {
+        return Ok(uri);
+    }
+
+    if let (Some(original), Some(cid)) = (&request.original_token_uri, &request.pinned_metadata_cid)
+    {
+        if original == origin_uri {
+            return Ok(PinnedMetadata { cid: cid.clone() }.uri());
+        }
+    }
+
+    match pinning.store.pin(&request.id, &uri).await {
+        Ok(pinned) => {
+            info!("Request {} metadata pinned to {}", request.id, pinned.uri());
+            request.original_token_uri = Some(origin_uri.to_string());
+            request.pinned_metadata_cid = Some(pinned.cid.clone());
+            request.record_event(
+                &format!("Token metadata {origin_uri} pinned to {}", pinned.uri()),
+                db,
+                now,
+            )?;
+            Ok(pinned.uri())
+        }
+        Err(e) if pinning.strict => Err(BridgeError::Other(format!(
+            "Pinning token metadata failed: {e}"
+        ))),
+        Err(e) => {
+            error!(
+                "Pinning metadata of request {} failed, minting the origin URI: {e}",
+                request.id
+            );
+            request.record_event(
+                &format!("Pinning failed, minting the origin URI {uri}: {e}"),
+                db,
+                now,
+            )?;
+            Ok(uri)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PinResponse {
+    #[serde(rename = "IpfsHash")]
+    ipfs_hash: String,
+}
+
+/// Pinata compatible pinning API. The metadata JSON is pinned as is, with its http(s)
+/// image pinned too when `pin_images` is set
+#[derive(Clone, Debug)]
+pub struct IpfsPinStore {
+    http: reqwest::Client,
+    api_url: String,
+    jwt: Secret<String>,
+    pin_images: bool,
+    /// Image URIs are checked as the token URIs before being downloaded
+    image_policy: UriPolicy,
+}
+
+impl IpfsPinStore {
+    pub fn new(
+        api_url: &str,
+        jwt: &Secret<String>,
+        pin_images: bool,
+        image_policy: UriPolicy,
+    ) -> Result<Self> {
+        if !api_url.starts_with("https://") {
+            return Err(BridgeError::Other(format!(
+                "Pinning API {api_url} is not an https URL"
+            )));
+        }
+        Ok(IpfsPinStore {
+            http: reqwest::Client::builder()
+                .timeout(PINNING_TIMEOUT)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()?,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            jwt: jwt.clone(),
+            pin_images,
+            image_policy,
+        })
+    }
+
+    async fn download(&self, uri: &str, max_size: usize) -> Result<Vec<u8>> {
+        let mut response = self.http.get(uri).send().await?.error_for_status()?;
+        let mut body = vec![];
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > max_size {
+                return Err(BridgeError::Other(format!(
+                    "{uri} is larger than {max_size} bytes"
+                )));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+
+    async fn pin_json(&self, name: &str, content: &Value) -> Result<String> {
+        let response: PinResponse = self
+            .http
+            .post(format!("{}/pinning/pinJSONToIPFS", self.api_url))
+            .bearer_auth(self.jwt.expose())
+            .json(&json!({ "pinataContent": content, "pinataMetadata": { "name": name } }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response.ipfs_hash)
+    }
+
+    async fn pin_file(&self, name: &str, content: Vec<u8>) -> Result<String> {
+        let form = reqwest::multipart::Form::new()
+            .part(
+                "file",
+                reqwest::multipart::Part::bytes(content).file_name(name.to_string()),
+            )
+            .text("pinataMetadata", json!({ "name": name }).to_string());
+        let response: PinResponse = self
+            .http
+            .post(format!("{}/pinning/pinFileToIPFS", self.api_url))
+            .bearer_auth(self.jwt.expose())
+            .multipart(form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response.ipfs_hash)
+    }
+
+    async fn fetch_metadata(&self, uri: &str) -> Result<Value> {
+        let body = self.download(uri, MAX_PINNED_METADATA_SIZE).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    async fn pin_metadata(&self, request_id: &str, uri: &str) -> Result<PinnedMetadata> {
+        let metadata = self.fetch_metadata(uri).await?;
+        self.pin_value(request_id, metadata).await
+    }
+
+    async fn pin_value(&self, request_id: &str, mut metadata: Value) -> Result<PinnedMetadata> {
+        if self.pin_images {
+            if let Some(image) = metadata.get("image").and_then(Value::as_str) {
+                if let Some(image) = self.pinnable_image(image) {
+                    let content = self.download(&image, MAX_PINNED_IMAGE_SIZE).await?;
+                    let cid = self
+                        .pin_file(&format!("{request_id}-image"), content)
+                        .await?;
+                    metadata["image"] = Value::String(PinnedMetadata { cid }.uri());
+                }
+            }
+        }
+
+        let cid = self.pin_json(request_id, &metadata).await?;
+        Ok(PinnedMetadata { cid })
+    }
+
+    /// http(s) image URIs accepted by the policy, other schemes are left as they are
+    fn pinnable_image(&self, image: &str) -> Option<String> {
+        let scheme = image
+            .split_once(':')
+            .map(|(scheme, _)| scheme.to_lowercase());
+        if !matches!(scheme.as_deref(), Some("http" | "https")) {
+            return None;
+        }
+        match self.image_policy.apply(image) {
+            UriDecision::Accept(image) | UriDecision::Rewrite(image) => Some(image),
+            UriDecision::Reject(reason) => {
+                error!("Token image {image} not pinned: {reason}");
+                None
+            }
+        }
+    }
+}
+
+impl MetadataStore for IpfsPinStore {
+    fn pin<'a>(&'a self, request_id: &'a str, uri: &'a str) -> PinFuture<'a> {
+        Box::pin(self.pin_metadata(request_id, uri))
+    }
+
+    fn fetch<'a>(&'a self, uri: &'a str) -> FetchFuture<'a> {
+        Box::pin(self.fetch_metadata(uri))
+    }
+
+    fn pin_document<'a>(&'a self, request_id: &'a str, document: &'a Value) -> PinFuture<'a> {
+        Box::pin(self.pin_value(request_id, document.clone()))
+    }
+}
+
+#[cfg(test)]
+mod metadata_store_test {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::Value;
+    use storage::{db::Database, testing::each_engine};
+
+    use crate::{
+        pin_token_uri, BRequest, BridgeError, Chains, FetchFuture, InputRequest, MetadataPinning,
+        MetadataStore, PinFuture, PinnedMetadata, Timestamp,
+    };
+
+    #[derive(Debug, Default)]
+    struct MockStore {
+        fail: bool,
+        pinned: Mutex<Vec<String>>,
+    }
+
+    impl MetadataStore for MockStore {
+        fn pin<'a>(&'a self, _request_id: &'a str, uri: &'a str) -> PinFuture<'a> {
+            Box::pin(async move {
+                if self.fail {
+                    return Err(BridgeError::Other(
+                        "pinning service unavailable".to_string(),
+                    ));
+                }
+                self.pinned.lock().unwrap().push(uri.to_string());
+                Ok(PinnedMetadata {
+                    cid: "bafkreimock".to_string(),
+                })
+            })
+        }
+
+        fn fetch<'a>(&'a self, _uri: &'a str) -> FetchFuture<'a> {
+            Box::pin(async { Err(BridgeError::Other("no metadata served".to_string())) })
+        }
+
+        fn pin_document<'a>(&'a self, _request_id: &'a str, _document: &'a Value) -> PinFuture<'a> {
+            Box::pin(async { Err(BridgeError::Other("no document pinned".to_string())) })
+        }
+    }
+
+    fn request(db: &Database) -> BRequest {
+        let request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0x5fbdb2315678afecb367f032d93f642f64180aa3".to_string(),
+                token_id: "7".to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.save(db).unwrap();
+        request
+    }
+
+    fn pinning(store: &Arc<MockStore>, strict: bool) -> MetadataPinning {
+        MetadataPinning {
+            store: store.clone(),
+            strict,
+            translate: false,
+        }
+    }
+
+    const ORIGIN: &str = "https://example.com/token/7.json";
+
+    #[tokio::test]
+    async fn test_pinned_uri_replaces_origin() {
+        for db in each_engine() {
+            let mut request = request(&db);
+            let store = Arc::new(MockStore::default());
+            let pinning = pinning(&store, true);
+
+            let uri = pin_token_uri(
+                Some(&pinning),
+                &mut request,
+                ORIGIN,
+                ORIGIN.to_string(),
+                &db,
+                Timestamp::now(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(uri, "ipfs://bafkreimock");
+
+            let stored = crate::request_data(&request.id, &db).unwrap().unwrap();
+            assert_eq!(stored.original_token_uri.as_deref(), Some(ORIGIN));
+            assert_eq!(stored.pinned_metadata_cid.as_deref(), Some("bafkreimock"));
+
+            // A retried mint reuses the pinned CID
+            let uri = pin_token_uri(
+                Some(&pinning),
+                &mut request,
+                ORIGIN,
+                ORIGIN.to_string(),
+                &db,
+                Timestamp::now(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(uri, "ipfs://bafkreimock");
+            assert_eq!(store.pinned.lock().unwrap().len(), 1);
+
+            // Content already on IPFS or inline is not pinned
+            let mut other = self::request(&db);
+            for origin in ["ipfs://QmOrigin", "data:application/json,{}"] {
+                let uri = pin_token_uri(
+                    Some(&pinning),
+                    &mut other,
+                    origin,
+                    origin.to_string(),
+                    &db,
+                    Timestamp::now(),
+                )
+                .await
+                .unwrap();
+                assert_eq!(uri, origin);
+            }
+            assert_eq!(store.pinned.lock().unwrap().len(), 1);
+
+            // Without a store the URI is minted as it is
+            let uri = pin_token_uri(
+                None,
+                &mut other,
+                ORIGIN,
+                ORIGIN.to_string(),
+                &db,
+                Timestamp::now(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(uri, ORIGIN);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_pinning_fallback() {
+        for db in each_engine() {
+            let mut request = request(&db);
+            let store = Arc::new(MockStore {
+                fail: true,
+                ..Default::default()
+            });
+
+            let lenient = pinning(&store, false);
+            let uri = pin_token_uri(
+                Some(&lenient),
+                &mut request,
+                ORIGIN,
+                ORIGIN.to_string(),
+                &db,
+                Timestamp::now(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(uri, ORIGIN);
+            let stored = crate::request_data(&request.id, &db).unwrap().unwrap();
+            assert_eq!(stored.pinned_metadata_cid, None);
+            assert!(stored
+                .history
+                .last()
+                .is_some_and(|entry| entry.event.starts_with("Pinning failed")));
+
+            let strict = pinning(&store, true);
+            let err = pin_token_uri(
+                Some(&strict),
+                &mut request,
+                ORIGIN,
+                ORIGIN.to_string(),
+                &db,
+                Timestamp::now(),
+            )
+            .await
+            .unwrap_err();
+            assert!(err.to_string().contains("pinning service unavailable"));
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use log::{error, info};
+use serde_json::{json, Map, Value};
+use storage::db::Database;
+
+use crate::{
+    pin_token_uri, BRequest, BridgeError, MetadataPinning, PinnedMetadata, Result, Timestamp,
+};
+
+/// ERC721 metadata of a Metaplex token metadata document: name, description, image and
+/// the trait_type/value attributes. The image falls back to the first image file of the
+/// properties, documents without a name or an image can't be translated
+pub fn erc721_metadata(metaplex: &Value) -> Result<Value> {
+    let metadata = metaplex
+        .as_object()
+        .ok_or_else(|| BridgeError::Other("metadata is not a JSON object".to_string()))?;
+    let name = non_empty_str(metadata, "name")
+        .ok_or_else(|| BridgeError::Other("metadata has no name".to_string()))?;
+    let image = non_empty_str(metadata, "image")
+        .or_else(|| image_file(metadata))
+        .ok_or_else(|| BridgeError::Other("metadata has no image".to_string()))?;
+
+    let attributes: Vec<Value> = metadata
+        .get("attributes")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|attribute| {
+            let trait_type = attribute.get("trait_type")?.as_str()?;
+            let value = attribute.get("value")?;
+            (value.is_string() || value.is_number() || value.is_boolean())
+                .then(|| json!({ "trait_type": trait_type, "value": value }))
+        })
+        .collect();
+
+    let mut erc721 = json!({
+        "name": name,
+        "description": metadata.get("description").and_then(Value::as_str).unwrap_or_default(),
+        "image": image,
+        "attributes": attributes,
+    });
+    for field in ["external_url", "animation_url"] {
+        if let Some(url) = non_empty_str(metadata, field) {
+            erc721[field] = Value::String(url.to_string());
+        }
+    }
+    Ok(erc721)
+}
+
+fn non_empty_str<'a>(metadata: &'a Map<String, Value>, field: &str) -> Option<&'a str> {
+    metadata
+        .get(field)
+        .and_then(Value::as_str)
+        .filter(|value| !value.is_empty())
+}
+
+/// First `properties.files` entry with an image content type
+fn image_file(metadata: &Map<String, Value>) -> Option<&str> {
+    metadata
+        .get("properties")?
+        .get("files")?
+        .as_array()?
+        .iter()
+        .find(|file| {
+            file.get("type")
+                .and_then(Value::as_str)
+                .is_some_and(|content_type| content_type.starts_with("image/"))
+        })?
+        .get("uri")?
+        .as_str()
+        .filter(|uri| !uri.is_empty())
+}
+
+/// URI to mint on EVM for a Solana token. With translation enabled the Metaplex metadata
+/// at `uri` is translated to ERC721 and the document is pinned, the origin URI and the
+/// CID are kept on the request. Untranslatable metadata is pinned as `pin_token_uri` does
+pub async fn translate_token_uri(
+    pinning: Option<&MetadataPinning>,
+    request: &mut BRequest,
+    origin_uri: &str,
+    uri: String,
+    db: &Database,
+    now: Timestamp,
+) -> Result<String> {
+    let Some(pinning) = pinning.filter(|pinning| pinning.translate) else {
+        return pin_token_uri(pinning, request, origin_uri, uri, db, now).await;
+    };
+    let scheme = uri.split_once(':').map(|(scheme, _)| scheme.to_lowercase());
+    if !matches!(scheme.as_deref(), Some("http" | "https")) {
+        return pin_token_uri(Some(pinning), request, origin_uri, uri, db, now).await;
+    }
+
+    if let (Some(original), Some(cid)) = (&request.original_token_uri, &request.pinned_metadata_cid)
+    {
+        if original == origin_uri {
+            return Ok(PinnedMetadata { cid: cid.clone() }.uri());
+        }
+    }
+
+    let document = pinning
+        .store
+        .fetch(&uri)
+        .await
+        .and_then(|metaplex| erc721_metadata(&metaplex));
+    let document = match document {
+        Ok(document) => document,
+        Err(e) => {
+            info!(
+                "Metadata of request {} not translated, keeping the origin metadata: {e}",
+                request.id
+            );
+            request.record_event(
+                &format!("Token metadata {origin_uri} not translated: {e}"),
+                db,
+                now,
+            )?;
+            return pin_token_uri(Some(pinning), request, origin_uri, uri, db, now).await;
+        }
+    };
+
+    match pinning.store.pin_document(&request.id, &document).await {
+        Ok(pinned) => {
+            info!(
+                "Request {} metadata translated to ERC721 at {}",
+                request.id,
+                pinned.uri()
+            );
+            request.original_token_uri = Some(origin_uri.to_string());
+            request.pinned_metadata_cid = Some(pinned.cid.clone());
+            request.record_event(
+                &format!(
+                    "Token metadata {origin_uri} translated to ERC721 at {}",
+                    pinned.uri()
+                ),
+                db,
+                now,
+            )?;
+            Ok(pinned.uri())
+        }
+        Err(e) if pinning.strict => Err(BridgeError::Other(format!(
+            "Pinning translated token metadata failed: {e}"
+        ))),
+        Err(e) => {
+            error!(
+                "Pinning translated metadata of request {} failed, minting the origin URI: {e}",
+                request.id
+            );
+            request.record_event(
+                &format!("Pinning translated metadata failed, minting the origin URI {uri}: {e}"),
+                db,
+                now,
+            )?;
+            Ok(uri)
+        }
+    }
+}
+
+#[cfg(test)]
+mod metadata_translation_test {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::{json, Value};
+    use storage::{db::Database, testing::each_engine};
+
+    use crate::{
+        erc721_metadata, translate_token_uri, BRequest, Chains, FetchFuture, InputRequest,
+        MetadataPinning, MetadataStore, PinFuture, PinnedMetadata, Timestamp,
+    };
+
+    /// Store serving `served` as the metadata of every URI
+    #[derive(Debug)]
+    struct MockStore {
+        served: Value,
+        pinned: Mutex<Vec<Value>>,
+    }
+
+    impl MetadataStore for MockStore {
+        fn pin<'a>(&'a self, _request_id: &'a str, _uri: &'a str) -> PinFuture<'a> {
+            Box::pin(async {
+                Ok(PinnedMetadata {
+                    cid: "bafkreiorigin".to_string(),
+                })
+            })
+        }
+
+        fn fetch<'a>(&'a self, _uri: &'a str) -> FetchFuture<'a> {
+            Box::pin(async { Ok(self.served.clone()) })
+        }
+
+        fn pin_document<'a>(&'a self, _request_id: &'a str, document: &'a Value) -> PinFuture<'a> {
+            Box::pin(async move {
+                self.pinned.lock().unwrap().push(document.clone());
+                Ok(PinnedMetadata {
+                    cid: "bafkreitranslated".to_string(),
+                })
+            })
+        }
+    }
+
+    fn metaplex() -> Value {
+        json!({
+            "name": "Degen Ape #42",
+            "symbol": "DAPE",
+            "description": "An ape bridged from Solana",
+            "seller_fee_basis_points": 500,
+            "image": "",
+            "external_url": "https://example.com/apes/42",
+            "attributes": [
+                { "trait_type": "Background", "value": "Blue" },
+                { "trait_type": "Level", "value": 3 },
+                { "value": "no trait type" },
+                { "trait_type": "Nested", "value": { "a": 1 } }
+            ],
+            "properties": {
+                "files": [
+                    { "uri": "https://example.com/apes/42.mp4", "type": "video/mp4" },
+                    { "uri": "https://example.com/apes/42.png", "type": "image/png" }
+                ],
+                "category": "image",
+                "creators": [{ "address": "creator", "share": 100 }]
+            }
+        })
+    }
+
+    #[test]
+    fn test_metaplex_fields_map_to_erc721() {
+        assert_eq!(
+            erc721_metadata(&metaplex()).unwrap(),
+            json!({
+                "name": "Degen Ape #42",
+                "description": "An ape bridged from Solana",
+                "image": "https://example.com/apes/42.png",
+                "external_url": "https://example.com/apes/42",
+                "attributes": [
+                    { "trait_type": "Background", "value": "Blue" },
+                    { "trait_type": "Level", "value": 3 }
+                ]
+            })
+        );
+
+        let minimal = json!({ "name": "Token", "image": "ipfs://QmImage" });
+        assert_eq!(
+            erc721_metadata(&minimal).unwrap(),
+            json!({ "name": "Token", "description": "", "image": "ipfs://QmImage", "attributes": [] })
+        );
+
+        for untranslatable in [
+            json!([]),
+            json!({ "image": "ipfs://QmImage" }),
+            json!({ "name": "No image", "properties": { "files": [] } }),
+        ] {
+            assert!(
+                erc721_metadata(&untranslatable).is_err(),
+                "{untranslatable}"
+            );
+        }
+    }
+
+    fn request(db: &Database) -> BRequest {
+        let request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "mint".to_string(),
+                token_id: String::new(),
+                token_owner: "token-account".to_string(),
+                origin_network: Chains::SOLANA,
+                destination_account: "0xdestination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.save(db).unwrap();
+        request
+    }
+
+    const ORIGIN: &str = "https://arweave.net/metadata.json";
+
+    async fn translate(store: &Arc<MockStore>, translate: bool, db: &Database) -> String {
+        let pinning = MetadataPinning {
+            store: store.clone(),
+            strict: false,
+            translate,
+        };
+        let mut request = request(db);
+        translate_token_uri(
+            Some(&pinning),
+            &mut request,
+            ORIGIN,
+            ORIGIN.to_string(),
+            db,
+            Timestamp::now(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_translated_metadata_is_minted() {
+        for db in each_engine() {
+            let store = Arc::new(MockStore {
+                served: metaplex(),
+                pinned: Mutex::default(),
+            });
+            assert_eq!(
+                translate(&store, true, &db).await,
+                "ipfs://bafkreitranslated"
+            );
+            let pinned = store.pinned.lock().unwrap().clone();
+            assert_eq!(pinned.len(), 1);
+            assert_eq!(pinned[0]["image"], "https://example.com/apes/42.png");
+
+            // Disabled, the origin metadata is pinned as it is
+            assert_eq!(translate(&store, false, &db).await, "ipfs://bafkreiorigin");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_untranslatable_metadata_falls_back_to_origin() {
+        for db in each_engine() {
+            let store = Arc::new(MockStore {
+                served: json!({ "name": "No image" }),
+                pinned: Mutex::default(),
+            });
+            assert_eq!(translate(&store, true, &db).await, "ipfs://bafkreiorigin");
+            assert!(store.pinned.lock().unwrap().is_empty());
+            let id = BRequest::generate_id("mint", "", "token-account");
+            let stored = crate::request_data(&id, &db).unwrap().unwrap();
+            assert!(stored.history.iter().any(|entry| entry
+                .event
+                .contains("not translated: metadata has no image")));
+
+            let mut request = request(&db);
+            let uri = translate_token_uri(
+                None,
+                &mut request,
+                ORIGIN,
+                ORIGIN.to_string(),
+                &db,
+                Timestamp::now(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(uri, ORIGIN);
+        }
+    }
+}
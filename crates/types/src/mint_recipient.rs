@@ -0,0 +1,156 @@
+use std::str::FromStr;
+
+use alloy::primitives::Address;
+use log::error;
+use storage::db::{Batch, Database};
+
+use crate::{
+    log_account, BRequest, ChainCursors, Chains, ErrorClass, FailedAttempt, FailureReport,
+    HistoryEntry, Result, Status, SuggestedAction, Timestamp,
+};
+
+/// Whether `account` is the account `expected` on `chain`. EVM addresses are compared
+/// without their checksum case, Solana keys as they are
+pub fn same_account(chain: &Chains, expected: &str, account: &str) -> bool {
+    let (expected, account) = (expected.trim(), account.trim());
+    match chain {
+        Chains::EVM => match (Address::from_str(expected), Address::from_str(account)) {
+            (Ok(expected), Ok(account)) => expected == account,
+            _ => expected.eq_ignore_ascii_case(account),
+        },
+        Chains::SOLANA => expected == account,
+    }
+}
+
+impl BRequest {
+    /// Checks a mint of the request went to its destination account: the `to` of the EVM
+    /// TokenMinted log, the owner of the Solana destination token account. A TokenMinted
+    /// request minted to another account is not completed, it moves to
+    /// NeedsIntervention with a DestinationMismatch failure report. Returns whether the
+    /// recipient is the destination
+    pub fn verify_mint_recipient(
+        &mut self,
+        db: &Database,
+        recipient: &str,
+        now: Timestamp,
+    ) -> Result<bool> {
+        let destination = match self.input.origin_network {
+            Chains::EVM => Chains::SOLANA,
+            Chains::SOLANA => Chains::EVM,
+        };
+        if same_account(&destination, &self.input.destination_account, recipient) {
+            return Ok(true);
+        }
+        if self.status != Status::TokenMinted {
+            return Ok(false);
+        }
+
+        error!(
+            "ALERT request {} minted to {} instead of its destination {}, held for an operator",
+            self.id,
+            log_account(recipient),
+            log_account(&self.input.destination_account)
+        );
+        let attempt = FailedAttempt {
+            time: now,
+            status: self.status.clone(),
+            class: ErrorClass::DestinationMismatch,
+            error: format!(
+                "Minted to {recipient} instead of the destination {}",
+                self.input.destination_account
+            ),
+        };
+        self.history.push(HistoryEntry {
+            time: now,
+            event: attempt.error.clone(),
+        });
+        self.failure_report = Some(FailureReport {
+            request_id: self.id.clone(),
+            generated_at: now,
+            status: self.status.clone(),
+            attempts: vec![attempt],
+            last_simulation: None,
+            tx_hashes: self.tx_hashes.clone(),
+            cursors: ChainCursors::default(),
+            suggested_actions: vec![SuggestedAction::RecoverMintedToken],
+        });
+        self.status = Status::NeedsIntervention;
+        self.last_update = now;
+        // A completion with the right recipient can't be overwritten
+        self.save_with_if(db, Batch::default(), Status::TokenMinted)?;
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod mint_recipient_test {
+    use storage::{db::Batch, testing::each_engine};
+
+    use crate::{
+        pending_requests, request_data, same_account, stage_pending_addition, BRequest, Chains,
+        ErrorClass, InputRequest, Status, SuggestedAction, Timestamp,
+    };
+
+    const DESTINATION: &str = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+    const NOW: Timestamp = Timestamp::from_secs(1_700_000_000);
+
+    fn minted_request() -> BRequest {
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "mint".to_string(),
+                token_id: String::new(),
+                token_owner: "owner".to_string(),
+                origin_network: Chains::SOLANA,
+                destination_account: DESTINATION.to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.status = Status::TokenMinted;
+        request
+    }
+
+    #[test]
+    fn test_accounts_are_normalized() {
+        assert!(same_account(
+            &Chains::EVM,
+            DESTINATION,
+            &DESTINATION.to_lowercase()
+        ));
+        assert!(!same_account(
+            &Chains::EVM,
+            DESTINATION,
+            "0x3C44CdDdB6a900fa2b585dd299e03d12FA4293BC"
+        ));
+        assert!(same_account(&Chains::SOLANA, "key ", "key"));
+        assert!(!same_account(&Chains::SOLANA, "key", "KEY"));
+    }
+
+    #[test]
+    fn test_mismatched_recipient_is_held_for_an_operator() {
+        for db in each_engine() {
+            let mut request = minted_request();
+            let mut batch = Batch::default();
+            stage_pending_addition(&request.id, &db, &mut batch).unwrap();
+            request.save_with(&db, batch).unwrap();
+            assert_eq!(pending_requests(&db), Some(vec![request.id.to_string()]));
+            assert!(request
+                .verify_mint_recipient(&db, &DESTINATION.to_lowercase(), NOW)
+                .unwrap());
+            assert_eq!(request.status, Status::TokenMinted);
+
+            let other = "0x3C44CdDdB6a900fa2b585dd299e03d12FA4293BC";
+            assert!(!request.verify_mint_recipient(&db, other, NOW).unwrap());
+
+            let stored = request_data(&request.id, &db).unwrap().unwrap();
+            assert_eq!(stored.status, Status::NeedsIntervention);
+            let report = stored.failure_report.unwrap();
+            assert_eq!(report.attempts[0].class, ErrorClass::DestinationMismatch);
+            assert!(report.attempts[0].error.contains(other));
+            assert_eq!(
+                report.suggested_actions,
+                vec![SuggestedAction::RecoverMintedToken]
+            );
+            assert_eq!(pending_requests(&db), Some(vec![]));
+        }
+    }
+}
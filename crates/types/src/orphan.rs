@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use log::info;
+use storage::db::Database;
+
+use crate::{
+    request_data, requests_by_status, BRequest, BridgeAddresses, InputRequest, RequestId, Result,
+    Status, Timestamp,
+};
+
+/// Time the owner of a pre-transferred token has to claim it before it becomes refund
+/// eligible
+pub const ORPHAN_CLAIM_WINDOW: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Records the custody of a NewRequest event whose request id has no local record. The
+/// input is rebuilt from chain data and has no destination until the owner claims it,
+/// `bridge_addresses` are the ones of the bridge holding the token.
+/// Returns None when the request already exists
+pub fn record_orphan(
+    db: &Database,
+    request_id: &RequestId,
+    input: InputRequest,
+    bridge_addresses: Option<BridgeAddresses>,
+    now: Timestamp,
+) -> Result<Option<BRequest>> {
+    if request_data(request_id, db)?.is_some() {
+        return Ok(None);
+    }
+
+    let mut request = BRequest::new(input, now);
+    request.id = request_id.clone();
+    request.bridge_addresses = bridge_addresses;
+    request.status = Status::NeedsDestination;
+    request.input.destination_account = String::new();
+    info!(
+        "Token {} {} in custody without request {}, waiting for a destination",
+        request.input.contract_or_mint, request.input.token_id, request.id
+    );
+    request.record_event("Custody detected without a bridge request", db, now)?;
+    Ok(Some(request))
+}
+
+/// Moves the orphans created before `now - window` to RefundEligible. Returns their ids
+pub fn expire_orphans(db: &Database, now: Timestamp, window: Duration) -> Result<Vec<String>> {
+    let created_before = now.saturating_sub(window);
+    let mut expired = vec![];
+    for (id, _) in requests_by_status(db, &Status::NeedsDestination, None)? {
+        let Some(mut request) = request_data(&id, db)? else {
+            continue;
+        };
+        if request.status != Status::NeedsDestination || request.created_at > created_before {
+            continue;
+        }
+        request.status = Status::RefundEligible;
+        request.last_update = now;
+        request.record_event("Not claimed in time, eligible for a refund", db, now)?;
+        info!("Orphan request {id} expired, eligible for a refund");
+        expired.push(id);
+    }
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod orphan_test {
+    use std::time::Duration;
+
+    use storage::testing::each_engine;
+
+    use crate::{
+        expire_orphans, record_orphan, request_data, requests_by_status, BRequest, Chains,
+        InputRequest, Status, Timestamp,
+    };
+
+    fn input() -> InputRequest {
+        InputRequest {
+            contract_or_mint: "0x5fbdb2315678afecb367f032d93f642f64180aa3".to_string(),
+            token_id: "7".to_string(),
+            token_owner: "0xowner".to_string(),
+            origin_network: Chains::EVM,
+            destination_account: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_orphan_recorded_once_and_expires() {
+        for db in each_engine() {
+            // Id of the on-chain request, not derived from the rebuilt input
+            let id = BRequest::generate_id("0xcontract", "7", "0xsubmitter");
+            let orphan = record_orphan(&db, &id, input(), None, Timestamp::now())
+                .unwrap()
+                .unwrap();
+            assert_eq!(orphan.id, id);
+            assert_eq!(orphan.status, Status::NeedsDestination);
+            assert!(record_orphan(&db, &id, input(), None, Timestamp::now())
+                .unwrap()
+                .is_none());
+            assert_eq!(
+                requests_by_status(&db, &Status::NeedsDestination, None)
+                    .unwrap()
+                    .len(),
+                1
+            );
+
+            let window = Duration::from_secs(60);
+            assert!(expire_orphans(&db, orphan.created_at, window)
+                .unwrap()
+                .is_empty());
+            let later = orphan.created_at.saturating_add(window);
+            assert_eq!(
+                expire_orphans(&db, later, window).unwrap(),
+                vec![id.to_string()]
+            );
+            let stored = request_data(&id, &db).unwrap().unwrap();
+            assert_eq!(stored.status, Status::RefundEligible);
+            assert_eq!(stored.history.len(), 2);
+        }
+    }
+}
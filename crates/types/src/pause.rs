@@ -0,0 +1,393 @@
+use std::sync::{Arc, Mutex};
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use storage::{db::Database, keys::PAUSE_STATE};
+
+use crate::{request_data, Chains, Result, SharedClock};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BridgeDirection {
+    All,
+    EvmToSolana,
+    SolanaToEvm,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Pause {
+    pub reason: Option<String>,
+    /// Unix time (secs) the pause started
+    pub since: u64,
+    /// Unix time (secs) after which the pause no longer applies
+    pub resume_at: Option<u64>,
+}
+
+impl Pause {
+    fn is_active(&self, now: u64) -> bool {
+        self.resume_at.is_none_or(|resume_at| now < resume_at)
+    }
+}
+
+/// Admin request to pause or resume a direction
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct PauseUpdate {
+    pub direction: BridgeDirection,
+    pub paused: bool,
+    pub reason: Option<String>,
+    pub resume_at: Option<u64>,
+}
+
+/// Pauses set by the operators, global and per direction, persisted across restarts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PauseState {
+    pub all: Option<Pause>,
+    pub evm_to_solana: Option<Pause>,
+    pub solana_to_evm: Option<Pause>,
+    /// Maintenance window of the operators, pauses both directions and the sweep until
+    /// it ends or its `resume_at`
+    #[serde(default)]
+    pub maintenance: Option<Pause>,
+    /// Request ids of the events received while their direction was paused
+    #[serde(default)]
+    pub buffered_events: Vec<String>,
+}
+
+impl PauseState {
+    fn slot(&mut self, direction: BridgeDirection) -> &mut Option<Pause> {
+        match direction {
+            BridgeDirection::All => &mut self.all,
+            BridgeDirection::EvmToSolana => &mut self.evm_to_solana,
+            BridgeDirection::SolanaToEvm => &mut self.solana_to_evm,
+        }
+    }
+
+    /// Active pause for requests originated in `origin`, the maintenance and the global
+    /// one first
+    pub fn active_pause(&self, origin: &Chains, now: u64) -> Option<&Pause> {
+        let direction = match origin {
+            Chains::EVM => &self.evm_to_solana,
+            Chains::SOLANA => &self.solana_to_evm,
+        };
+        [&self.maintenance, &self.all, direction]
+            .into_iter()
+            .flatten()
+            .find(|pause| pause.is_active(now))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BridgePause {
+    state: Arc<Mutex<PauseState>>,
+    clock: SharedClock,
+}
+
+impl BridgePause {
+    /// Loads the pause state persisted by a previous run
+    pub fn load(db: &Database, clock: SharedClock) -> Self {
+        let state = db
+            .read::<_, PauseState>(PAUSE_STATE)
+            .unwrap_or_else(|err| {
+                error!("Could not read pause state {:?}", err);
+                None
+            })
+            .unwrap_or_default();
+
+        BridgePause {
+            state: Arc::new(Mutex::new(state)),
+            clock,
+        }
+    }
+
+    pub fn state(&self) -> PauseState {
+        self.state.lock().unwrap().clone()
+    }
+
+    pub fn pause(
+        &self,
+        db: &Database,
+        direction: BridgeDirection,
+        reason: Option<String>,
+        resume_at: Option<u64>,
+    ) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        info!("Pausing bridge {:?}, reason {:?}", direction, reason);
+        *state.slot(direction) = Some(Pause {
+            reason,
+            since: self.unix_time(),
+            resume_at,
+        });
+        persist(&state, db)
+    }
+
+    pub fn apply(&self, db: &Database, update: PauseUpdate) -> Result<()> {
+        if update.paused {
+            self.pause(db, update.direction, update.reason, update.resume_at)
+        } else {
+            self.resume(db, update.direction)
+        }
+    }
+
+    pub fn resume(&self, db: &Database, direction: BridgeDirection) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        info!("Resuming bridge {:?}", direction);
+        *state.slot(direction) = None;
+        persist(&state, db)
+    }
+
+    /// Starts a maintenance window, replacing a running one
+    pub fn begin_maintenance(
+        &self,
+        db: &Database,
+        reason: Option<String>,
+        resume_at: Option<u64>,
+    ) -> Result<Pause> {
+        let mut state = self.state.lock().unwrap();
+        info!("Beginning maintenance, reason {:?}", reason);
+        let maintenance = Pause {
+            reason,
+            since: self.unix_time(),
+            resume_at,
+        };
+        state.maintenance = Some(maintenance.clone());
+        persist(&state, db)?;
+        Ok(maintenance)
+    }
+
+    pub fn end_maintenance(&self, db: &Database) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        info!("Ending maintenance");
+        state.maintenance = None;
+        persist(&state, db)
+    }
+
+    /// Maintenance window in progress, None once ended or past its `resume_at`
+    pub fn maintenance(&self) -> Option<Pause> {
+        let state = self.state.lock().unwrap();
+        let now = self.unix_time();
+        state
+            .maintenance
+            .as_ref()
+            .filter(|maintenance| maintenance.is_active(now))
+            .cloned()
+    }
+
+    /// Pause applying to new requests and events originated in `origin`
+    pub fn paused(&self, origin: &Chains) -> Option<Pause> {
+        let state = self.state.lock().unwrap();
+        state.active_pause(origin, self.unix_time()).cloned()
+    }
+
+    /// Keeps the event of a paused direction to be processed on resume
+    pub fn buffer_event(&self, db: &Database, request_id: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.buffered_events.iter().any(|id| id == request_id) {
+            info!("Buffering event of paused request {request_id}");
+            state.buffered_events.push(request_id.to_string());
+        }
+        persist(&state, db)
+    }
+
+    /// Takes the buffered events whose direction is no longer paused
+    pub fn take_resumed_events(&self, db: &Database) -> Result<Vec<String>> {
+        let mut state = self.state.lock().unwrap();
+        if state.buffered_events.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let now = self.unix_time();
+        let mut resumed = vec![];
+        let mut still_paused = vec![];
+        for id in std::mem::take(&mut state.buffered_events) {
+            match request_data(&id, db) {
+                Ok(Some(request)) => {
+                    if state
+                        .active_pause(&request.input.origin_network, now)
+                        .is_some()
+                    {
+                        still_paused.push(id);
+                    } else {
+                        resumed.push(id);
+                    }
+                }
+                _ => error!("Dropping buffered event of unknown request {id}"),
+            }
+        }
+        state.buffered_events = still_paused;
+        persist(&state, db)?;
+        Ok(resumed)
+    }
+
+    fn unix_time(&self) -> u64 {
+        self.clock.now().as_secs()
+    }
+}
+
+fn persist(state: &PauseState, db: &Database) -> Result<()> {
+    db.write_value(PAUSE_STATE, state)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod pause_test {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{
+        system_clock, BRequest, BridgeDirection, BridgePause, Chains, Clock, InputRequest,
+        MockClock, Timestamp,
+    };
+    use storage::{db::Database, testing::each_engine};
+
+    fn saved_request(db: &Database, token_id: &str, origin_network: Chains) -> String {
+        let request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xabc123".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner456".to_string(),
+                origin_network,
+                destination_account: "destination789".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.save(db).unwrap();
+        request.id
+    }
+
+    #[test]
+    fn test_pause_survives_restart() {
+        for db in each_engine() {
+            let pause = BridgePause::load(&db, system_clock());
+            assert!(pause.paused(&Chains::EVM).is_none());
+
+            pause
+                .pause(
+                    &db,
+                    BridgeDirection::EvmToSolana,
+                    Some("Solana program upgrade".to_string()),
+                    None,
+                )
+                .unwrap();
+
+            // Simulated restart
+            drop(pause);
+            let pause = BridgePause::load(&db, system_clock());
+            let active = pause.paused(&Chains::EVM).unwrap();
+            assert_eq!(active.reason, Some("Solana program upgrade".to_string()));
+            // The other direction is live
+            assert!(pause.paused(&Chains::SOLANA).is_none());
+
+            pause.resume(&db, BridgeDirection::EvmToSolana).unwrap();
+            assert!(BridgePause::load(&db, system_clock())
+                .paused(&Chains::EVM)
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn test_global_pause_and_auto_resume() {
+        for db in each_engine() {
+            let pause = BridgePause::load(&db, system_clock());
+
+            pause.pause(&db, BridgeDirection::All, None, None).unwrap();
+            assert!(pause.paused(&Chains::EVM).is_some());
+            assert!(pause.paused(&Chains::SOLANA).is_some());
+
+            // Resume time already passed
+            pause
+                .pause(&db, BridgeDirection::All, None, Some(1))
+                .unwrap();
+            assert!(pause.paused(&Chains::EVM).is_none());
+            assert!(pause.paused(&Chains::SOLANA).is_none());
+        }
+    }
+
+    #[test]
+    fn test_resume_time_with_clock_stepping_back() {
+        for db in each_engine() {
+            let clock = MockClock::new(Timestamp::from_secs(1_700_000_000));
+            let pause = BridgePause::load(&db, Arc::new(clock.clone()));
+
+            let resume_at = clock.now().as_secs() + 60;
+            pause
+                .pause(&db, BridgeDirection::All, None, Some(resume_at))
+                .unwrap();
+            assert!(pause.paused(&Chains::EVM).is_some());
+
+            clock.advance(Duration::from_secs(61));
+            assert!(pause.paused(&Chains::EVM).is_none());
+
+            // The pause applies again while the clock is before the resume time
+            clock.rewind(Duration::from_secs(3600));
+            assert!(pause.paused(&Chains::EVM).is_some());
+        }
+    }
+
+    #[test]
+    fn test_maintenance_pauses_both_directions_until_ended() {
+        for db in each_engine() {
+            let clock = MockClock::new(Timestamp::from_secs(1_700_000_000));
+            let pause = BridgePause::load(&db, Arc::new(clock.clone()));
+            pause
+                .pause(&db, BridgeDirection::EvmToSolana, None, None)
+                .unwrap();
+
+            let resume_at = clock.now().as_secs() + 600;
+            pause
+                .begin_maintenance(&db, Some("RPC switch".to_string()), Some(resume_at))
+                .unwrap();
+            assert!(pause.maintenance().is_some());
+            let active = pause.paused(&Chains::SOLANA).unwrap();
+            assert_eq!(active.reason, Some("RPC switch".to_string()));
+            assert!(BridgePause::load(&db, Arc::new(clock.clone()))
+                .maintenance()
+                .is_some());
+
+            // The pause of the operators outlives the maintenance
+            pause.end_maintenance(&db).unwrap();
+            assert!(pause.maintenance().is_none());
+            assert!(pause.paused(&Chains::SOLANA).is_none());
+            assert!(pause.paused(&Chains::EVM).is_some());
+
+            // A forgotten window ends by itself
+            pause.begin_maintenance(&db, None, Some(resume_at)).unwrap();
+            clock.advance(Duration::from_secs(601));
+            assert!(pause.maintenance().is_none());
+            assert!(pause.paused(&Chains::SOLANA).is_none());
+        }
+    }
+
+    #[test]
+    fn test_buffered_events_drain_on_resume() {
+        for db in each_engine() {
+            let pause = BridgePause::load(&db, system_clock());
+            let evm_request = saved_request(&db, "1", Chains::EVM);
+            let solana_request = saved_request(&db, "2", Chains::SOLANA);
+
+            pause
+                .pause(&db, BridgeDirection::EvmToSolana, None, None)
+                .unwrap();
+            pause
+                .pause(&db, BridgeDirection::SolanaToEvm, None, None)
+                .unwrap();
+            pause.buffer_event(&db, &evm_request).unwrap();
+            pause.buffer_event(&db, &evm_request).unwrap();
+            pause.buffer_event(&db, &solana_request).unwrap();
+            assert!(pause.take_resumed_events(&db).unwrap().is_empty());
+
+            pause.resume(&db, BridgeDirection::EvmToSolana).unwrap();
+            assert_eq!(pause.take_resumed_events(&db).unwrap(), vec![evm_request]);
+            assert!(pause.take_resumed_events(&db).unwrap().is_empty());
+
+            // The buffer is persisted
+            let pause = BridgePause::load(&db, system_clock());
+            assert_eq!(pause.state().buffered_events, vec![solana_request.clone()]);
+
+            pause.resume(&db, BridgeDirection::SolanaToEvm).unwrap();
+            assert_eq!(
+                pause.take_resumed_events(&db).unwrap(),
+                vec![solana_request]
+            );
+            assert!(pause.state().buffered_events.is_empty());
+        }
+    }
+}
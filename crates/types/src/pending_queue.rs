@@ -0,0 +1,247 @@
+use crate::Result;
+use log::info;
+use storage::{
+    db::{Batch, Database},
+    keys::{
+        PENDING_NEXT_SEQUENCE, PENDING_QUEUE_PREFIX, PENDING_REQUESTS, PENDING_REQUESTS_INDEX,
+        PENDING_SEQUENCE_PREFIX,
+    },
+};
+
+/// Queue entry of a pending request. Sequences only grow, removing an entry never moves
+/// the others
+pub(crate) fn queue_key(sequence: u64) -> String {
+    format!("{PENDING_QUEUE_PREFIX}{sequence:020}")
+}
+
+pub(crate) fn sequence_key(request_id: &str) -> String {
+    format!("{PENDING_SEQUENCE_PREFIX}{request_id}")
+}
+
+/// Pending request ids with their sequence, oldest first
+pub fn pending_entries(db: &Database) -> Result<Vec<(u64, String)>> {
+    let entries = db
+        .scan_prefix::<String>(PENDING_QUEUE_PREFIX, None)?
+        .into_iter()
+        .filter_map(|(key, id)| {
+            key[PENDING_QUEUE_PREFIX.len()..]
+                .parse::<u64>()
+                .ok()
+                .map(|sequence| (sequence, id))
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Pending request ids in the order they were added
+pub fn pending_requests(db: &Database) -> Option<Vec<String>> {
+    pending_entries(db)
+        .ok()
+        .map(|entries| entries.into_iter().map(|(_, id)| id).collect())
+}
+
+/// Position of the request in the pending queue starting at 1, None when not pending
+pub fn pending_position(db: &Database, request_id: &str) -> Result<Option<usize>> {
+    if db.read::<_, u64>(sequence_key(request_id))?.is_none() {
+        return Ok(None);
+    }
+    Ok(pending_entries(db)?
+        .iter()
+        .position(|(_, id)| id == request_id)
+        .map(|index| index + 1))
+}
+
+/// Adds the request at the end of the pending queue in `batch`, once. Returns false when
+/// it is already pending
+pub fn stage_pending_addition(request_id: &str, db: &Database, batch: &mut Batch) -> Result<bool> {
+    if db.read::<_, u64>(sequence_key(request_id))?.is_some() {
+        return Ok(false);
+    }
+    let sequence = next_pending_sequence(db)?;
+    batch.put(queue_key(sequence), &request_id)?;
+    batch.put(sequence_key(request_id), &sequence)?;
+    batch.put(PENDING_NEXT_SEQUENCE, &(sequence + 1))?;
+    Ok(true)
+}
+
+/// Allocated in memory so that concurrent additions never get the same sequence, seeded
+/// from the stored next sequence and the last queue entry since the batches writing them
+/// may land out of order
+fn next_pending_sequence(db: &Database) -> Result<u64> {
+    let sequence = db.next_sequence(PENDING_NEXT_SEQUENCE, |db| {
+        let stored = db
+            .read::<_, u64>(PENDING_NEXT_SEQUENCE)?
+            .unwrap_or_default();
+        let after_last = db
+            .scan_prefix::<String>(PENDING_QUEUE_PREFIX, None)?
+            .last()
+            .and_then(|(key, _)| key[PENDING_QUEUE_PREFIX.len()..].parse::<u64>().ok())
+            .map_or(0, |sequence| sequence + 1);
+        Ok(stored.max(after_last))
+    })?;
+    Ok(sequence)
+}
+
+/// Removes the request from the pending queue in `batch`, the other entries keep their
+/// order. Returns false when the request is not pending
+pub fn stage_pending_removal(request_id: &str, db: &Database, batch: &mut Batch) -> Result<bool> {
+    let Some(sequence) = db.read::<_, u64>(sequence_key(request_id))? else {
+        return Ok(false);
+    };
+    batch.delete(queue_key(sequence));
+    batch.delete(sequence_key(request_id));
+    Ok(true)
+}
+
+/// Moves the pending list of older versions to the queue keeping its order, the list and
+/// its index are deleted in the same write. Returns the number of requests moved
+pub fn migrate_pending_list(db: &Database) -> Result<usize> {
+    let Some(legacy) = db.read::<_, Vec<String>>(PENDING_REQUESTS)? else {
+        return Ok(0);
+    };
+
+    let mut batch = Batch::default();
+    let mut moved = 0;
+    for (index, id) in legacy.iter().enumerate() {
+        let duplicate = legacy[..index].contains(id);
+        if duplicate || db.read::<_, u64>(sequence_key(id))?.is_some() {
+            continue;
+        }
+        let sequence = next_pending_sequence(db)?;
+        batch.put(queue_key(sequence), id)?;
+        batch.put(sequence_key(id), &sequence)?;
+        batch.put(PENDING_NEXT_SEQUENCE, &(sequence + 1))?;
+        moved += 1;
+    }
+    batch.delete(PENDING_REQUESTS);
+    batch.delete(PENDING_REQUESTS_INDEX);
+    db.write_batch(batch)?;
+    info!("Moved {moved} pending requests to the pending queue");
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod pending_queue_test {
+    use std::collections::{HashMap, HashSet};
+
+    use storage::{
+        db::{Batch, Database},
+        keys::{PENDING_REQUESTS, PENDING_REQUESTS_INDEX},
+        testing::each_engine,
+    };
+
+    use crate::{
+        migrate_pending_list, pending_entries, pending_position, pending_requests,
+        stage_pending_addition, stage_pending_removal,
+    };
+
+    fn add(db: &Database, id: &str) -> bool {
+        let mut batch = Batch::default();
+        let added = stage_pending_addition(id, db, &mut batch).unwrap();
+        db.write_batch(batch).unwrap();
+        added
+    }
+
+    fn remove(db: &Database, id: &str) -> bool {
+        let mut batch = Batch::default();
+        let removed = stage_pending_removal(id, db, &mut batch).unwrap();
+        db.write_batch(batch).unwrap();
+        removed
+    }
+
+    #[test]
+    fn test_removal_keeps_fifo_order() {
+        for db in each_engine() {
+            assert!(pending_requests(&db).unwrap().is_empty());
+
+            for id in ["A", "B", "C"] {
+                assert!(add(&db, id));
+            }
+            assert!(!add(&db, "A"));
+            assert!(remove(&db, "B"));
+            assert!(!remove(&db, "B"));
+
+            assert_eq!(pending_requests(&db).unwrap(), vec!["A", "C"]);
+            assert_eq!(pending_position(&db, "A").unwrap(), Some(1));
+            assert_eq!(pending_position(&db, "C").unwrap(), Some(2));
+            assert_eq!(pending_position(&db, "B").unwrap(), None);
+
+            // Requests added later go behind, even after a removal
+            assert!(add(&db, "D"));
+            assert!(add(&db, "B"));
+            assert_eq!(pending_requests(&db).unwrap(), vec!["A", "C", "D", "B"]);
+            let sequences: Vec<u64> = pending_entries(&db)
+                .unwrap()
+                .into_iter()
+                .map(|(sequence, _)| sequence)
+                .collect();
+            assert_eq!(sequences, vec![0, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn test_concurrent_additions_get_distinct_sequences() {
+        for db in each_engine() {
+            assert!(add(&db, "first"));
+            let threads: Vec<_> = (0..8)
+                .map(|thread| {
+                    let db = db.clone();
+                    std::thread::spawn(move || {
+                        for index in 0..25 {
+                            assert!(add(&db, &format!("{thread}-{index}")));
+                        }
+                    })
+                })
+                .collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+
+            let entries = pending_entries(&db).unwrap();
+            assert_eq!(entries.len(), 201);
+            let sequences: HashSet<u64> = entries.iter().map(|(sequence, _)| *sequence).collect();
+            assert_eq!(sequences.len(), 201);
+            assert_eq!(entries[0], (0, "first".to_string()));
+            for thread in 0..8 {
+                for index in 0..25 {
+                    let id = format!("{thread}-{index}");
+                    assert!(pending_position(&db, &id).unwrap().is_some(), "{id}");
+                }
+            }
+            // A new addition goes behind all of them
+            assert!(add(&db, "last"));
+            assert_eq!(pending_position(&db, "last").unwrap(), Some(202));
+        }
+    }
+
+    #[test]
+    fn test_migration_keeps_list_order() {
+        for db in each_engine() {
+            assert_eq!(migrate_pending_list(&db).unwrap(), 0);
+
+            let legacy = vec!["C", "A", "B", "A"];
+            db.write_value(PENDING_REQUESTS, &legacy).unwrap();
+            db.write_value(
+                PENDING_REQUESTS_INDEX,
+                &HashMap::from([("C", 0i128), ("A", 1), ("B", 2)]),
+            )
+            .unwrap();
+
+            assert_eq!(migrate_pending_list(&db).unwrap(), 3);
+            assert_eq!(pending_requests(&db).unwrap(), vec!["C", "A", "B"]);
+            assert!(db
+                .read::<_, Vec<String>>(PENDING_REQUESTS)
+                .unwrap()
+                .is_none());
+            assert!(db
+                .read::<_, HashMap<String, i128>>(PENDING_REQUESTS_INDEX)
+                .unwrap()
+                .is_none());
+
+            // New requests continue after the migrated ones
+            assert!(add(&db, "D"));
+            assert_eq!(pending_position(&db, "D").unwrap(), Some(4));
+            assert_eq!(migrate_pending_list(&db).unwrap(), 0);
+        }
+    }
+}
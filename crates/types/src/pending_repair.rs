@@ -0,0 +1,390 @@
+use std::collections::{HashMap, HashSet};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use storage::{
+    db::{Batch, Database},
+    keys::{
+        PENDING_NEXT_SEQUENCE, PENDING_REPAIR_REPORT, PENDING_REQUESTS, PENDING_REQUESTS_INDEX,
+        PENDING_SEQUENCE_PREFIX,
+    },
+};
+
+use crate::{pending_entries, queue_key, request_data, sequence_key, Result, Timestamp};
+
+/// Outcome of the integrity check of the pending structures, the last one is kept under
+/// `PENDING_REPAIR_REPORT`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PendingRepairReport {
+    pub checked_at: Timestamp,
+    /// The pending list of older versions was found, checked before its migration
+    pub legacy_layout: bool,
+    /// Pending requests after the repair
+    pub pending: usize,
+    /// Requests whose index entry was missing or mismatched, rebuilt from the list
+    pub reindexed: Vec<String>,
+    /// Index entries of requests not in the list, deleted
+    pub orphan_index_entries: Vec<String>,
+    /// Requests listed more than once, the oldest entry is kept
+    pub duplicates: Vec<String>,
+    /// Listed requests without a stored record, dropped
+    pub missing_records: Vec<String>,
+    /// The next sequence was behind the queue and was moved past it
+    pub next_sequence_raised: bool,
+}
+
+impl PendingRepairReport {
+    /// Whether anything had to be fixed
+    pub fn repaired(&self) -> bool {
+        !self.reindexed.is_empty()
+            || !self.orphan_index_entries.is_empty()
+            || !self.duplicates.is_empty()
+            || !self.missing_records.is_empty()
+            || self.next_sequence_raised
+    }
+}
+
+/// Report of the last check of the pending structures, None before the first one
+pub fn last_pending_repair(db: &Database) -> Result<Option<PendingRepairReport>> {
+    Ok(db.read(PENDING_REPAIR_REPORT)?)
+}
+
+fn record_missing(
+    request_id: &str,
+    db: &Database,
+    report: &mut PendingRepairReport,
+) -> Result<bool> {
+    if request_data(request_id, db)?.is_some() {
+        return Ok(false);
+    }
+    warn!("Pending request {request_id} has no stored record, dropping it from the pending list");
+    report.missing_records.push(request_id.to_string());
+    Ok(true)
+}
+
+/// The list of older versions is the reference, its index is rebuilt from it
+fn repair_legacy_list(db: &Database, report: &mut PendingRepairReport) -> Result<()> {
+    let Some(listed) = db.read::<_, Vec<String>>(PENDING_REQUESTS)? else {
+        return Ok(());
+    };
+    report.legacy_layout = true;
+    // An index that can't be read is rebuilt whole
+    let index = db
+        .read::<_, HashMap<String, i128>>(PENDING_REQUESTS_INDEX)
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+
+    let mut seen = HashSet::new();
+    let mut list: Vec<String> = Vec::with_capacity(listed.len());
+    for id in &listed {
+        if !seen.insert(id) {
+            report.duplicates.push(id.clone());
+        } else if !record_missing(id, db, report)? {
+            list.push(id.clone());
+        }
+    }
+    let rebuilt: HashMap<String, i128> = list
+        .iter()
+        .enumerate()
+        .map(|(position, id)| (id.clone(), position as i128))
+        .collect();
+    for (id, position) in list.iter().zip(0i128..) {
+        if index.get(id) != Some(&position) {
+            report.reindexed.push(id.clone());
+        }
+    }
+    let mut orphans: Vec<String> = index
+        .keys()
+        .filter(|id| !listed.contains(id))
+        .cloned()
+        .collect();
+    orphans.sort();
+    report.orphan_index_entries.extend(orphans);
+
+    if list != listed || rebuilt != index {
+        let mut batch = Batch::default();
+        batch.put(PENDING_REQUESTS, &list)?;
+        batch.put(PENDING_REQUESTS_INDEX, &rebuilt)?;
+        db.write_batch(batch)?;
+    }
+    Ok(())
+}
+
+/// The queue is the reference, the sequence of each request is rebuilt from it
+fn repair_queue(db: &Database, report: &mut PendingRepairReport) -> Result<()> {
+    let queue = pending_entries(db)?;
+    let sequences: HashMap<String, u64> = db
+        .scan_prefix::<u64>(PENDING_SEQUENCE_PREFIX, None)?
+        .into_iter()
+        .map(|(key, sequence)| (key[PENDING_SEQUENCE_PREFIX.len()..].to_string(), sequence))
+        .collect();
+
+    let mut batch = Batch::default();
+    let mut kept = HashSet::new();
+    let mut dropped = HashSet::new();
+    let mut changed = false;
+    for (sequence, id) in &queue {
+        if kept.contains(id) || dropped.contains(id) {
+            report.duplicates.push(id.clone());
+            batch.delete(queue_key(*sequence));
+            changed = true;
+            continue;
+        }
+        if record_missing(id, db, report)? {
+            batch.delete(queue_key(*sequence));
+            batch.delete(sequence_key(id));
+            dropped.insert(id.clone());
+            changed = true;
+            continue;
+        }
+        if sequences.get(id) != Some(sequence) {
+            report.reindexed.push(id.clone());
+            batch.put(sequence_key(id), sequence)?;
+            changed = true;
+        }
+        kept.insert(id.clone());
+    }
+
+    let mut orphans: Vec<&String> = sequences
+        .keys()
+        .filter(|id| !kept.contains(*id) && !dropped.contains(*id))
+        .collect();
+    orphans.sort();
+    for id in orphans {
+        batch.delete(sequence_key(id));
+        report.orphan_index_entries.push(id.clone());
+        changed = true;
+    }
+
+    // Sequences of dropped entries aren't handed out again either
+    let highest = queue.iter().map(|(sequence, _)| *sequence).max();
+    let next = db
+        .read::<_, u64>(PENDING_NEXT_SEQUENCE)?
+        .unwrap_or_default();
+    if let Some(highest) = highest.filter(|highest| *highest >= next) {
+        report.next_sequence_raised = true;
+        batch.put(PENDING_NEXT_SEQUENCE, &(highest + 1))?;
+        changed = true;
+    }
+    report.pending += kept.len();
+    if changed {
+        db.write_batch(batch)?;
+    }
+    Ok(())
+}
+
+/// Checks the pending structures of both layouts against each other and against the
+/// stored requests, run at startup before the pending list is migrated. Indexes are
+/// rebuilt from their list, entries of requests without a record are dropped, a
+/// duplicate keeps its oldest entry. Repairs are deterministic, a second run finds
+/// nothing. Healthy data costs a scan of the queue and a read per pending request
+pub fn repair_pending_structures(db: &Database, now: Timestamp) -> Result<PendingRepairReport> {
+    let mut report = PendingRepairReport {
+        checked_at: now,
+        ..Default::default()
+    };
+    repair_legacy_list(db, &mut report)?;
+    repair_queue(db, &mut report)?;
+    if report.legacy_layout {
+        report.pending += db
+            .read::<_, Vec<String>>(PENDING_REQUESTS)?
+            .map_or(0, |list| list.len());
+    }
+
+    if report.repaired() {
+        warn!(
+            "Repaired the pending structures: {} reindexed, {} orphan index entries, {} duplicates, {} without a record",
+            report.reindexed.len(),
+            report.orphan_index_entries.len(),
+            report.duplicates.len(),
+            report.missing_records.len()
+        );
+    } else {
+        info!(
+            "Pending structures checked, {} pending requests",
+            report.pending
+        );
+    }
+    db.write_value(PENDING_REPAIR_REPORT, &report)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod pending_repair_test {
+    use std::collections::HashMap;
+
+    use storage::{
+        db::{Batch, Database},
+        keys::{PENDING_NEXT_SEQUENCE, PENDING_REQUESTS, PENDING_REQUESTS_INDEX},
+        testing::each_engine,
+    };
+
+    use crate::{
+        last_pending_repair, migrate_pending_list, pending_entries, pending_position, queue_key,
+        repair_pending_structures, sequence_key, stage_pending_addition, BRequest, Chains,
+        InputRequest, PendingRepairReport, Timestamp,
+    };
+
+    const NOW: Timestamp = Timestamp::from_secs(1_700_000_000);
+
+    /// Stores a request and returns its id
+    fn stored(db: &Database, token_id: &str) -> String {
+        let request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xcontract".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: String::new(),
+            },
+            Timestamp::now(),
+        );
+        request.save(db).unwrap();
+        request.id.to_string()
+    }
+
+    fn add(db: &Database, id: &str) {
+        let mut batch = Batch::default();
+        stage_pending_addition(id, db, &mut batch).unwrap();
+        db.write_batch(batch).unwrap();
+    }
+
+    /// Each queued request has the sequence of its entry and the next one is past them all
+    fn assert_consistent(db: &Database) {
+        let entries = pending_entries(db).unwrap();
+        for (sequence, id) in &entries {
+            assert_eq!(
+                db.read::<_, u64>(sequence_key(id)).unwrap(),
+                Some(*sequence)
+            );
+        }
+        let next = db.read::<_, u64>(PENDING_NEXT_SEQUENCE).unwrap().unwrap();
+        assert!(entries.iter().all(|(sequence, _)| *sequence < next));
+        let repeated = repair_pending_structures(db, NOW).unwrap();
+        assert!(!repeated.repaired(), "{repeated:?}");
+    }
+
+    #[test]
+    fn test_healthy_queue_is_left_alone() {
+        for db in each_engine() {
+            let report = repair_pending_structures(&db, NOW).unwrap();
+            assert_eq!(
+                report,
+                PendingRepairReport {
+                    checked_at: NOW,
+                    ..Default::default()
+                }
+            );
+
+            let (a, b) = (stored(&db, "1"), stored(&db, "2"));
+            add(&db, &a);
+            add(&db, &b);
+            let report = repair_pending_structures(&db, NOW).unwrap();
+            assert!(!report.repaired());
+            assert_eq!(report.pending, 2);
+            assert_eq!(last_pending_repair(&db).unwrap(), Some(report));
+            assert_eq!(pending_position(&db, &b).unwrap(), Some(2));
+        }
+    }
+
+    #[test]
+    fn test_queue_index_rebuilt_from_the_queue() {
+        for db in each_engine() {
+            let (a, b, c) = (stored(&db, "1"), stored(&db, "2"), stored(&db, "3"));
+            for id in [&a, &b, &c] {
+                add(&db, id);
+            }
+            // Crash shapes: an entry without its sequence, a sequence of another entry and a
+            // sequence left by a removed entry
+            db.delete(sequence_key(&a)).unwrap();
+            db.write_value(sequence_key(&b), &7u64).unwrap();
+            db.delete(queue_key(2)).unwrap();
+
+            let report = repair_pending_structures(&db, NOW).unwrap();
+            assert_eq!(report.reindexed, vec![a.clone(), b.clone()]);
+            assert_eq!(report.orphan_index_entries, vec![c.clone()]);
+            assert_eq!(report.pending, 2);
+            assert_eq!(pending_position(&db, &b).unwrap(), Some(2));
+            assert_eq!(pending_position(&db, &c).unwrap(), None);
+            assert_consistent(&db);
+        }
+    }
+
+    #[test]
+    fn test_duplicates_and_missing_records_dropped() {
+        for db in each_engine() {
+            let a = stored(&db, "1");
+            add(&db, &a);
+            add(&db, "0xmissing");
+            let mut batch = Batch::default();
+            batch.put(queue_key(2), &a).unwrap();
+            db.write_batch(batch).unwrap();
+            // The next sequence was written before the entries
+            db.write_value(PENDING_NEXT_SEQUENCE, &1u64).unwrap();
+
+            let report = repair_pending_structures(&db, NOW).unwrap();
+            assert_eq!(report.duplicates, vec![a.clone()]);
+            assert_eq!(report.missing_records, vec!["0xmissing".to_string()]);
+            assert!(report.next_sequence_raised);
+            assert_eq!(pending_entries(&db).unwrap(), vec![(0, a.clone())]);
+            assert_eq!(db.read::<_, u64>(sequence_key("0xmissing")).unwrap(), None);
+            assert_eq!(db.read::<_, u64>(PENDING_NEXT_SEQUENCE).unwrap(), Some(3));
+            assert_consistent(&db);
+        }
+    }
+
+    #[test]
+    fn test_legacy_list_repaired_before_migration() {
+        for db in each_engine() {
+            let (a, b, c) = (stored(&db, "1"), stored(&db, "2"), stored(&db, "3"));
+            // b missing from the index, an index entry without a list entry, a listed
+            // request without a record and a duplicate
+            db.write_value(
+                PENDING_REQUESTS,
+                &vec![
+                    a.clone(),
+                    b.clone(),
+                    "0xmissing".to_string(),
+                    c.clone(),
+                    a.clone(),
+                ],
+            )
+            .unwrap();
+            db.write_value(
+                PENDING_REQUESTS_INDEX,
+                &HashMap::from([
+                    (a.clone(), 0i128),
+                    ("0xgone".to_string(), 1),
+                    (c.clone(), 3),
+                ]),
+            )
+            .unwrap();
+
+            let report = repair_pending_structures(&db, NOW).unwrap();
+            assert!(report.legacy_layout);
+            assert_eq!(report.reindexed, vec![b.clone(), c.clone()]);
+            assert_eq!(report.orphan_index_entries, vec!["0xgone".to_string()]);
+            assert_eq!(report.missing_records, vec!["0xmissing".to_string()]);
+            assert_eq!(report.duplicates, vec![a.clone()]);
+            assert_eq!(report.pending, 3);
+            assert_eq!(
+                db.read::<_, Vec<String>>(PENDING_REQUESTS).unwrap(),
+                Some(vec![a.clone(), b.clone(), c.clone()])
+            );
+            assert_eq!(
+                db.read::<_, HashMap<String, i128>>(PENDING_REQUESTS_INDEX)
+                    .unwrap(),
+                Some(HashMap::from([
+                    (a.clone(), 0),
+                    (b.clone(), 1),
+                    (c.clone(), 2)
+                ]))
+            );
+            assert!(!repair_pending_structures(&db, NOW).unwrap().repaired());
+
+            assert_eq!(migrate_pending_list(&db).unwrap(), 3);
+            assert_consistent(&db);
+        }
+    }
+}
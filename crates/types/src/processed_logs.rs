@@ -0,0 +1,164 @@
+use std::fmt;
+
+use alloy::{primitives::B256, rpc::types::Log};
+use storage::{db::Database, keys::PROCESSED_LOG_PREFIX};
+
+use crate::{Result, Timestamp};
+
+/// Blocks after which an EVM log is not expected to be reorged anymore
+pub const LOG_CONFIRMATION_DEPTH: u64 = 64;
+/// Blocks processed logs are kept for past the confirmation depth, covers replays of the
+/// subscription after a reconnection
+pub const PROCESSED_LOG_MARGIN: u64 = 128;
+
+/// Position of an EVM log. The block hash tells a log apart from its replay in another
+/// block after a reorg, which has the same tx hash and log index
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogMeta {
+    pub block_number: u64,
+    pub block_hash: B256,
+    pub tx_hash: B256,
+    pub log_index: u64,
+}
+
+impl LogMeta {
+    /// None for pending logs, which have no block yet
+    pub fn from_log(log: &Log) -> Option<Self> {
+        Some(LogMeta {
+            block_number: log.block_number?,
+            block_hash: log.block_hash?,
+            tx_hash: log.transaction_hash?,
+            log_index: log.log_index?,
+        })
+    }
+
+    /// Keys are ordered by block number so the oldest entries are pruned first
+    fn key(&self) -> String {
+        format!(
+            "{PROCESSED_LOG_PREFIX}{:020}:{}:{}:{}",
+            self.block_number, self.block_hash, self.tx_hash, self.log_index
+        )
+    }
+}
+
+impl fmt::Display for LogMeta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "block {} ({}), tx {}, log index {}",
+            self.block_number, self.block_hash, self.tx_hash, self.log_index
+        )
+    }
+}
+
+/// Whether the log was already handled, replays of the same log in the same block are
+/// skipped while a log moved to another block by a reorg is handled again
+pub fn log_processed(db: &Database, meta: &LogMeta) -> Result<bool> {
+    Ok(db.read::<_, Timestamp>(meta.key())?.is_some())
+}
+
+pub fn mark_log_processed(db: &Database, meta: &LogMeta, now: Timestamp) -> Result<()> {
+    db.write_value(meta.key(), &now)?;
+    Ok(())
+}
+
+/// Deletes the logs older than the confirmation depth plus margin from `latest_block`,
+/// returns the number of logs deleted
+pub fn prune_processed_logs(db: &Database, latest_block: u64) -> Result<usize> {
+    let Some(oldest_kept) = latest_block.checked_sub(LOG_CONFIRMATION_DEPTH + PROCESSED_LOG_MARGIN)
+    else {
+        return Ok(0);
+    };
+    let mut pruned = 0;
+    for (key, _) in db.scan_prefix::<Timestamp>(PROCESSED_LOG_PREFIX, None)? {
+        let block = key[PROCESSED_LOG_PREFIX.len()..]
+            .split(':')
+            .next()
+            .and_then(|block| block.parse::<u64>().ok());
+        match block {
+            Some(block) if block >= oldest_kept => break,
+            _ => {
+                db.delete(&key)?;
+                pruned += 1;
+            }
+        }
+    }
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod processed_logs_test {
+    use alloy::{primitives::B256, rpc::types::Log};
+    use storage::testing::each_engine;
+
+    use crate::{
+        log_processed, mark_log_processed, prune_processed_logs, LogMeta, Timestamp,
+        LOG_CONFIRMATION_DEPTH, PROCESSED_LOG_MARGIN,
+    };
+
+    fn meta(block_number: u64, block_hash: u8, log_index: u64) -> LogMeta {
+        LogMeta {
+            block_number,
+            block_hash: B256::with_last_byte(block_hash),
+            tx_hash: B256::with_last_byte(0xaa),
+            log_index,
+        }
+    }
+
+    #[test]
+    fn test_meta_of_pending_log() {
+        let mut log = Log {
+            block_number: Some(10),
+            block_hash: Some(B256::with_last_byte(1)),
+            transaction_hash: Some(B256::with_last_byte(0xaa)),
+            log_index: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(LogMeta::from_log(&log), Some(meta(10, 1, 2)));
+        log.block_hash = None;
+        assert_eq!(LogMeta::from_log(&log), None);
+    }
+
+    #[test]
+    fn test_duplicates_suppressed_and_reorg_replays_accepted() {
+        for db in each_engine() {
+            let now = Timestamp::from_secs(1_700_000_000);
+            let log = meta(10, 1, 0);
+
+            assert!(!log_processed(&db, &log).unwrap());
+            mark_log_processed(&db, &log, now).unwrap();
+            assert!(log_processed(&db, &log).unwrap());
+
+            // Same transaction and index reincluded in another block after a reorg
+            assert!(!log_processed(&db, &meta(10, 2, 0)).unwrap());
+            assert!(!log_processed(&db, &meta(11, 3, 0)).unwrap());
+            assert!(!log_processed(&db, &meta(10, 1, 1)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_prune_logs_past_confirmation_depth() {
+        for db in each_engine() {
+            let now = Timestamp::from_secs(1_700_000_000);
+            let retention = LOG_CONFIRMATION_DEPTH + PROCESSED_LOG_MARGIN;
+            let logs = [
+                meta(9, 1, 0),
+                meta(10, 2, 0),
+                meta(10, 2, 1),
+                meta(100, 3, 0),
+            ];
+            for log in &logs {
+                mark_log_processed(&db, log, now).unwrap();
+            }
+
+            assert_eq!(prune_processed_logs(&db, 5).unwrap(), 0);
+            assert_eq!(prune_processed_logs(&db, 10 + retention).unwrap(), 1);
+            assert!(!log_processed(&db, &logs[0]).unwrap());
+            assert!(log_processed(&db, &logs[1]).unwrap());
+
+            assert_eq!(prune_processed_logs(&db, 11 + retention).unwrap(), 2);
+            assert!(!log_processed(&db, &logs[2]).unwrap());
+            assert!(log_processed(&db, &logs[3]).unwrap());
+        }
+    }
+}
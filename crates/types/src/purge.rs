@@ -0,0 +1,427 @@
+use alloy::primitives::hex;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use storage::{
+    db::{Batch, Database},
+    keys::{ACCOUNT_INDEX_PREFIX, ERROR_HISTORY_PREFIX, PURGE_RECEIPT_PREFIX, REQUEST_KEY_PREFIX},
+};
+
+use crate::{
+    quota_account, quota_key, request_data, BRequest, HistoryEntry, RequestId, Result, Status,
+    Timestamp,
+};
+
+/// Start of the values replacing a purged account, followed by its salted hash
+pub const PURGE_MARKER_PREFIX: &str = "purged:";
+/// Hex digits of the salted hash kept in a purge marker
+const PURGE_MARKER_HASH_LEN: usize = 32;
+
+/// Record of the purges of an account, the account itself is only known by its hash
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PurgeReceipt {
+    pub account_hash: String,
+    /// Requests whose data was purged, by any purge of the account
+    pub requests: Vec<RequestId>,
+    pub first_purged_at: Timestamp,
+    pub purged_at: Timestamp,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PurgeOutcome {
+    Purged(PurgeReceipt),
+    /// Nothing was purged, these requests of the account are not finished
+    Blocked(Vec<RequestId>),
+}
+
+/// Salted hash of an account, the same for every form of an EVM address
+pub fn purge_account_hash(salt: &[u8], account: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update([0]);
+    hasher.update(quota_account(account).as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// Value replacing `account` in the purged requests
+pub fn purge_marker(salt: &[u8], account: &str) -> String {
+    let hash = purge_account_hash(salt, account);
+    format!("{PURGE_MARKER_PREFIX}{}", &hash[..PURGE_MARKER_HASH_LEN])
+}
+
+pub fn is_purge_marker(value: &str) -> bool {
+    value.starts_with(PURGE_MARKER_PREFIX)
+}
+
+fn account_index_key(account: &str, request_id: &str) -> String {
+    format!(
+        "{ACCOUNT_INDEX_PREFIX}{}:{request_id}",
+        quota_account(account)
+    )
+}
+
+fn purge_receipt_key(account_hash: &str) -> String {
+    format!("{PURGE_RECEIPT_PREFIX}{account_hash}")
+}
+
+/// Adds the index entries of the token owner and destination account of a request to
+/// the batch saving it. Purge markers are not indexed
+pub(crate) fn update_account_index(request: &BRequest, batch: &mut Batch) -> Result<()> {
+    for account in [
+        &request.input.token_owner,
+        &request.input.destination_account,
+    ] {
+        if !account.trim().is_empty() && !is_purge_marker(account) {
+            batch.put(account_index_key(account, &request.id), &request.id)?;
+        }
+    }
+    Ok(())
+}
+
+/// Requests with `account` as token owner or destination account, EVM addresses in any
+/// case
+pub fn requests_by_account(db: &Database, account: &str) -> Result<Vec<RequestId>> {
+    let prefix = account_index_key(account, "");
+    Ok(db
+        .scan_prefix::<RequestId>(&prefix, None)?
+        .into_iter()
+        .map(|(_, request_id)| request_id)
+        .collect())
+}
+
+/// Drops the account index and writes it again from every request, covering the requests
+/// saved before the index existed. Returns the number of requests indexed
+pub fn rebuild_account_index(db: &Database) -> Result<usize> {
+    let mut batch = Batch::default();
+    for (key, _) in db.scan_prefix::<RequestId>(ACCOUNT_INDEX_PREFIX, None)? {
+        batch.delete(key);
+    }
+
+    let mut rebuilt = 0;
+    for (_, request) in db.scan_prefix::<BRequest>(REQUEST_KEY_PREFIX, None)? {
+        update_account_index(&request, &mut batch)?;
+        rebuilt += 1;
+    }
+    db.write_batch(batch)?;
+
+    info!("Rebuilt the account index for {rebuilt} requests");
+    Ok(rebuilt)
+}
+
+/// Finished for good, no transaction of the relayer can still need its accounts
+fn purgeable(request: &BRequest) -> bool {
+    match request.status {
+        Status::Completed | Status::Refunded => true,
+        Status::Canceled => !request.refund_pending,
+        Status::Initializing
+        | Status::RequestReceived
+        | Status::TokenReceived
+        | Status::TokenMinted
+        | Status::NeedsDestination
+        | Status::RefundEligible
+        | Status::NeedsIntervention
+        | Status::AwaitingDestinationAccount => false,
+    }
+}
+
+/// `text` with every occurrence of `account` replaced by `marker`, EVM addresses matched
+/// in any case
+fn scrub_text(text: &str, account: &str, marker: &str) -> Option<String> {
+    // ASCII lowercasing keeps the byte offsets of the text
+    let haystack = if account.starts_with("0x") {
+        text.to_ascii_lowercase()
+    } else {
+        text.to_string()
+    };
+    if !haystack.contains(account) {
+        return None;
+    }
+    let mut scrubbed = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, _) in haystack.match_indices(account) {
+        scrubbed.push_str(&text[last..start]);
+        scrubbed.push_str(marker);
+        last = start + account.len();
+    }
+    scrubbed.push_str(&text[last..]);
+    Some(scrubbed)
+}
+
+/// Replaces `account` in every string of a stored value, its structure is kept. Returns
+/// whether anything was replaced
+fn scrub_value(value: &mut Value, account: &str, marker: &str) -> bool {
+    match value {
+        Value::String(text) => match scrub_text(text, account, marker) {
+            Some(scrubbed) => {
+                *text = scrubbed;
+                true
+            }
+            None => false,
+        },
+        Value::Array(items) => items.iter_mut().fold(false, |changed, item| {
+            scrub_value(item, account, marker) || changed
+        }),
+        Value::Object(fields) => fields.values_mut().fold(false, |changed, field| {
+            scrub_value(field, account, marker) || changed
+        }),
+        _ => false,
+    }
+}
+
+/// Replaces the account in a request and its spilled errors, the writes go to `batch`.
+/// Returns whether the request held the account
+fn purge_request(
+    request: &BRequest,
+    account: &str,
+    marker: &str,
+    now: Timestamp,
+    db: &Database,
+    batch: &mut Batch,
+) -> Result<Option<BRequest>> {
+    let prefix = format!("{ERROR_HISTORY_PREFIX}{}:", request.id);
+    for (key, mut record) in db.scan_prefix::<Value>(&prefix, None)? {
+        if scrub_value(&mut record, account, marker) {
+            batch.put(key, &record)?;
+        }
+    }
+
+    let mut value = serde_json::to_value(request)?;
+    if !scrub_value(&mut value, account, marker) {
+        return Ok(None);
+    }
+    let mut purged: BRequest = serde_json::from_value(value)?;
+    purged.purged_at = Some(now);
+    purged.history.push(HistoryEntry {
+        time: now,
+        event: "Account data purged".to_string(),
+    });
+    Ok(Some(purged))
+}
+
+/// Replaces `account` by its salted purge marker in every request it is part of, drops
+/// its index entries and quota usage and records a receipt. Nothing is purged while one
+/// of its requests is not finished. Purging an account again finds nothing left to
+/// replace and returns its receipt
+pub fn purge_account(
+    db: &Database,
+    account: &str,
+    salt: &[u8],
+    now: Timestamp,
+) -> Result<PurgeOutcome> {
+    let account = quota_account(account);
+    let mut requests = vec![];
+    let mut blocked = vec![];
+    for request_id in requests_by_account(db, &account)? {
+        // Entries of removed requests are dropped with the others
+        let Some(request) = request_data(&request_id, db)? else {
+            continue;
+        };
+        if !purgeable(&request) {
+            blocked.push(request.id.clone());
+        }
+        requests.push(request);
+    }
+    if !blocked.is_empty() {
+        blocked.sort();
+        return Ok(PurgeOutcome::Blocked(blocked));
+    }
+
+    let account_hash = purge_account_hash(salt, &account);
+    let marker = purge_marker(salt, &account);
+    for request in requests.iter() {
+        let mut batch = Batch::default();
+        match purge_request(request, &account, &marker, now, db, &mut batch)? {
+            Some(purged) => purged.save_with(db, batch)?,
+            None => db.write_batch(batch)?,
+        }
+    }
+
+    let mut receipt = db
+        .read::<_, PurgeReceipt>(purge_receipt_key(&account_hash))?
+        .unwrap_or(PurgeReceipt {
+            account_hash: account_hash.clone(),
+            requests: vec![],
+            first_purged_at: now,
+            purged_at: now,
+        });
+    receipt.purged_at = now;
+    for request in requests {
+        if !receipt.requests.contains(&request.id) {
+            receipt.requests.push(request.id);
+        }
+    }
+    receipt.requests.sort();
+
+    let mut batch = Batch::default();
+    for (key, _) in db.scan_prefix::<RequestId>(&account_index_key(&account, ""), None)? {
+        batch.delete(key);
+    }
+    batch.delete(quota_key(&account));
+    batch.put(purge_receipt_key(&account_hash), &receipt)?;
+    db.write_batch(batch)?;
+
+    info!(
+        "Account {PURGE_MARKER_PREFIX}{} purged from {} requests",
+        &account_hash[..PURGE_MARKER_HASH_LEN],
+        receipt.requests.len()
+    );
+    Ok(PurgeOutcome::Purged(receipt))
+}
+
+#[cfg(test)]
+mod purge_test {
+    use storage::{keys::ACCOUNT_INDEX_PREFIX, testing::each_engine};
+
+    use crate::{
+        is_purge_marker, purge_account, purge_marker, rebuild_account_index, request_data,
+        requests_by_account, spilled_errors, BRequest, Chains, ErrorClass, ErrorComponent,
+        ErrorRecord, InputRequest, PurgeOutcome, RequestId, Status, Timestamp, ERROR_RING_SIZE,
+    };
+
+    const OWNER: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+    const DESTINATION: &str = "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU";
+    const SALT: &[u8] = b"salt";
+
+    fn request(token_id: &str, status: Status) -> BRequest {
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0x5FbDB2315678afecb367f032d93F642f64180aa3".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: OWNER.to_lowercase(),
+                origin_network: Chains::EVM,
+                destination_account: DESTINATION.to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.status = status;
+        request
+    }
+
+    fn now() -> Timestamp {
+        Timestamp::from_secs(1_700_000_000)
+    }
+
+    #[test]
+    fn test_purge_across_requests_is_idempotent() {
+        for db in each_engine() {
+            let mut first = request("1", Status::Completed);
+            first.history.push(crate::HistoryEntry {
+                time: now(),
+                event: format!("Token received from {OWNER}"),
+            });
+            first.save(&db).unwrap();
+            // Errors naming the owner, some spilled out of the ring
+            for attempt in 0..ERROR_RING_SIZE + 2 {
+                let record = ErrorRecord::new(
+                    ErrorComponent::Evm,
+                    ErrorClass::Other,
+                    &format!(
+                        "transfer from {} failed {attempt}",
+                        OWNER.to_uppercase().replace("0X", "0x")
+                    ),
+                    now(),
+                );
+                first.record_error_record(record, None, &db).unwrap();
+            }
+            let mut second = request("2", Status::Canceled);
+            second.save(&db).unwrap();
+            // Another account, untouched
+            let mut other = request("3", Status::Completed);
+            other.input.token_owner = "0x5FbDB2315678afecb367f032d93F642f64180aa3".to_string();
+            other.input.destination_account = "destination".to_string();
+            other.save(&db).unwrap();
+
+            let mut ids = requests_by_account(&db, OWNER).unwrap();
+            ids.sort();
+            let mut expected = vec![first.id.clone(), second.id.clone()];
+            expected.sort();
+            assert_eq!(ids, expected);
+
+            let PurgeOutcome::Purged(receipt) = purge_account(&db, OWNER, SALT, now()).unwrap()
+            else {
+                panic!("purge blocked");
+            };
+            assert_eq!(receipt.requests, expected);
+            assert!(!receipt.account_hash.contains(&OWNER.to_lowercase()));
+
+            let marker = purge_marker(SALT, OWNER);
+            assert!(is_purge_marker(&marker));
+            assert_eq!(marker, purge_marker(SALT, &OWNER.to_lowercase()));
+            assert_ne!(marker, purge_marker(b"other salt", OWNER));
+            for id in [&first.id, &second.id] {
+                let purged = request_data(id, &db).unwrap().unwrap();
+                assert_eq!(purged.input.token_owner, marker);
+                assert_eq!(purged.input.destination_account, DESTINATION);
+                assert_eq!(purged.purged_at, Some(now()));
+                let stored = serde_json::to_string(&purged).unwrap().to_lowercase();
+                assert!(!stored.contains(&OWNER.to_lowercase()), "{stored}");
+            }
+            let purged = request_data(&first.id, &db).unwrap().unwrap();
+            assert_eq!(purged.status, Status::Completed);
+            assert_eq!(purged.input.token_id, "1");
+            assert!(purged.last_error.unwrap().contains(&marker));
+            for record in spilled_errors(&db, &first.id).unwrap() {
+                assert!(record.message.contains(&marker), "{}", record.message);
+            }
+            let untouched = request_data(&other.id, &db).unwrap().unwrap();
+            assert_eq!(untouched.purged_at, None);
+
+            // Index entries of the account are gone, the destination is still indexed
+            assert!(requests_by_account(&db, OWNER).unwrap().is_empty());
+            assert_eq!(requests_by_account(&db, DESTINATION).unwrap().len(), 2);
+            assert!(db
+                .scan_prefix::<RequestId>(ACCOUNT_INDEX_PREFIX, None)
+                .unwrap()
+                .iter()
+                .all(|(key, _)| !key.contains(&OWNER.to_lowercase())));
+
+            // Purged again, the same records and receipt
+            let later = now().saturating_add(std::time::Duration::from_secs(60));
+            let PurgeOutcome::Purged(again) = purge_account(&db, OWNER, SALT, later).unwrap()
+            else {
+                panic!("purge blocked");
+            };
+            assert_eq!(again.requests, receipt.requests);
+            assert_eq!(again.account_hash, receipt.account_hash);
+            assert_eq!(again.first_purged_at, now());
+            assert_eq!(
+                request_data(&first.id, &db).unwrap().unwrap().purged_at,
+                Some(now())
+            );
+
+            // A rebuild doesn't index the markers
+            rebuild_account_index(&db).unwrap();
+            assert!(requests_by_account(&db, &marker).unwrap().is_empty());
+            assert_eq!(requests_by_account(&db, DESTINATION).unwrap().len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_unfinished_requests_block_the_purge() {
+        for db in each_engine() {
+            let completed = request("1", Status::Completed);
+            completed.save(&db).unwrap();
+            let minted = request("2", Status::TokenMinted);
+            minted.save(&db).unwrap();
+            let mut refunding = request("3", Status::Canceled);
+            refunding.refund_pending = true;
+            refunding.save(&db).unwrap();
+
+            let PurgeOutcome::Blocked(blocked) =
+                purge_account(&db, DESTINATION, SALT, now()).unwrap()
+            else {
+                panic!("purged with unfinished requests");
+            };
+            let mut expected = vec![minted.id.clone(), refunding.id.clone()];
+            expected.sort();
+            assert_eq!(blocked, expected);
+
+            // Nothing was changed
+            let stored = request_data(&completed.id, &db).unwrap().unwrap();
+            assert_eq!(stored.input.destination_account, DESTINATION);
+            assert_eq!(stored.purged_at, None);
+            assert_eq!(requests_by_account(&db, DESTINATION).unwrap().len(), 3);
+        }
+    }
+}
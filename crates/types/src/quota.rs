@@ -0,0 +1,442 @@
+use std::time::Duration;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use storage::{
+    db::{Batch, Database},
+    keys::QUOTA_PREFIX,
+};
+
+use crate::{BRequest, HistoryEntry, InputRequest, Lamports, Result, Timestamp, UnitsError, Wei};
+
+/// Sliding window of the per account quotas
+pub const QUOTA_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Limits per account over `QUOTA_WINDOW`, unset and zero limits are not enforced
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct QuotaLimits {
+    pub max_requests: Option<u32>,
+    pub max_evm_fees: Option<Wei>,
+    pub max_solana_fees: Option<Lamports>,
+}
+
+impl QuotaLimits {
+    /// Limits from the config values, fee limits in ETH and SOL
+    pub fn from_config(
+        max_requests: Option<u32>,
+        max_evm_fees: Option<&str>,
+        max_solana_fees: Option<&str>,
+    ) -> Result<Self, UnitsError> {
+        Ok(QuotaLimits {
+            max_requests,
+            max_evm_fees: max_evm_fees.map(Wei::from_human).transpose()?,
+            max_solana_fees: max_solana_fees.map(Lamports::from_human).transpose()?,
+        })
+    }
+}
+
+/// Relayer fee paid for a request transaction, in the native token of its chain
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Fee {
+    EVM(Wei),
+    SOLANA(Lamports),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FeeSpent {
+    pub time: Timestamp,
+    pub tx_hash: String,
+    pub fee: Fee,
+    /// Part of `fee` funding the rent exemption of the accounts the transaction created,
+    /// the destination token account of a Solana mint among them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rent: Option<Lamports>,
+}
+
+/// Bridges initiated and fees spent by an account, oldest first
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct QuotaUsage {
+    pub requests: Vec<Timestamp>,
+    pub fees: Vec<FeeSpent>,
+}
+
+impl QuotaUsage {
+    /// Drops the entries out of the window ending at `now`
+    fn prune(&mut self, now: Timestamp) {
+        let start = now.saturating_sub(QUOTA_WINDOW);
+        self.requests.retain(|time| *time > start);
+        self.fees.retain(|spent| spent.time > start);
+    }
+
+    pub fn evm_fees(&self) -> Wei {
+        self.fees
+            .iter()
+            .filter_map(|spent| match spent.fee {
+                Fee::EVM(fee) => Some(fee),
+                Fee::SOLANA(_) => None,
+            })
+            .fold(Wei::ZERO, |total, fee| {
+                total.checked_add(fee).unwrap_or(Wei::MAX)
+            })
+    }
+
+    pub fn solana_fees(&self) -> Lamports {
+        self.fees
+            .iter()
+            .filter_map(|spent| match spent.fee {
+                Fee::SOLANA(fee) => Some(fee),
+                Fee::EVM(_) => None,
+            })
+            .fold(Lamports::ZERO, |total, fee| {
+                total.checked_add(fee).unwrap_or(Lamports::MAX)
+            })
+    }
+
+    /// Rent of the Solana accounts created for the account, counted in `solana_fees`
+    pub fn solana_rent(&self) -> Lamports {
+        self.fees
+            .iter()
+            .filter_map(|spent| spent.rent)
+            .fold(Lamports::ZERO, |total, rent| {
+                total.checked_add(rent).unwrap_or(Lamports::MAX)
+            })
+    }
+
+    /// Time the usage falls back under every reached limit, None when no limit is reached
+    pub fn reset_at(&self, limits: &QuotaLimits) -> Option<Timestamp> {
+        let mut reset_at: Option<Timestamp> = None;
+        let mut reached = |time: Timestamp| {
+            let time = time.saturating_add(QUOTA_WINDOW);
+            reset_at = Some(reset_at.map_or(time, |reset_at| reset_at.max(time)));
+        };
+
+        if let Some(max) = limits.max_requests {
+            // Under the limit once the requests up to this one leave the window
+            let last_over = self.requests.len().checked_sub(max as usize);
+            if let Some(time) = last_over.and_then(|index| self.requests.get(index)) {
+                reached(*time);
+            }
+        }
+        if let Some(max) = limits.max_evm_fees {
+            if let Some(time) = self.spend_reset(max.raw(), |fee| match fee {
+                Fee::EVM(fee) => Some(fee.raw()),
+                Fee::SOLANA(_) => None,
+            }) {
+                reached(time);
+            }
+        }
+        if let Some(max) = limits.max_solana_fees {
+            if let Some(time) = self.spend_reset(u128::from(max.raw()), |fee| match fee {
+                Fee::SOLANA(fee) => Some(u128::from(fee.raw())),
+                Fee::EVM(_) => None,
+            }) {
+                reached(time);
+            }
+        }
+        reset_at
+    }
+
+    /// Time of the fee whose expiry takes the spend of one chain under `max`, None when
+    /// the spend is already under it
+    fn spend_reset(&self, max: u128, raw: impl Fn(&Fee) -> Option<u128>) -> Option<Timestamp> {
+        let fees: Vec<(Timestamp, u128)> = self
+            .fees
+            .iter()
+            .filter_map(|spent| raw(&spent.fee).map(|fee| (spent.time, fee)))
+            .collect();
+        let mut spent = fees
+            .iter()
+            .fold(0u128, |total, (_, fee)| total.saturating_add(*fee));
+        if spent < max {
+            return None;
+        }
+        for (time, fee) in fees {
+            spent -= fee;
+            if spent < max {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+/// Account reaching its quota with the time it resets
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct QuotaExceeded {
+    pub account: String,
+    pub reset_at: Timestamp,
+}
+
+/// Quota key of an account, EVM addresses are case insensitive
+pub fn quota_account(account: &str) -> String {
+    let account = account.trim();
+    if account.starts_with("0x") {
+        account.to_lowercase()
+    } else {
+        account.to_string()
+    }
+}
+
+/// Accounts charged for a request, its destination account and token owner
+pub fn quota_accounts(input: &InputRequest) -> Vec<String> {
+    let mut accounts = vec![quota_account(&input.destination_account)];
+    let owner = quota_account(&input.token_owner);
+    if !accounts.contains(&owner) {
+        accounts.push(owner);
+    }
+    accounts
+}
+
+pub(crate) fn quota_key(account: &str) -> String {
+    format!("{QUOTA_PREFIX}{}", quota_account(account))
+}
+
+/// Usage of the account in the window ending at `now`
+pub fn quota_usage(db: &Database, account: &str, now: Timestamp) -> Result<QuotaUsage> {
+    let mut usage = db
+        .read::<_, QuotaUsage>(quota_key(account))?
+        .unwrap_or_default();
+    usage.prune(now);
+    Ok(usage)
+}
+
+/// First account of the request over one of the limits
+pub fn check_quota(
+    db: &Database,
+    input: &InputRequest,
+    limits: &QuotaLimits,
+    now: Timestamp,
+) -> Result<Option<QuotaExceeded>> {
+    for account in quota_accounts(input) {
+        if let Some(reset_at) = quota_usage(db, &account, now)?.reset_at(limits) {
+            return Ok(Some(QuotaExceeded { account, reset_at }));
+        }
+    }
+    Ok(None)
+}
+
+/// Counts a bridge initiated by the accounts of the request
+pub fn record_quota_request(db: &Database, input: &InputRequest, now: Timestamp) -> Result<()> {
+    let mut batch = Batch::default();
+    for account in quota_accounts(input) {
+        let mut usage = quota_usage(db, &account, now)?;
+        usage.requests.push(now);
+        batch.put(quota_key(&account), &usage)?;
+    }
+    db.write_batch(batch)?;
+    Ok(())
+}
+
+/// Clears the usage of the account
+pub fn reset_quota(db: &Database, account: &str) -> Result<()> {
+    db.delete(quota_key(account))?;
+    info!("Quota of {} reset", quota_account(account));
+    Ok(())
+}
+
+impl BRequest {
+    /// Records the fee paid for `tx_hash` and charges it to the quotas of the request
+    /// accounts in the same write. A fee already recorded is not charged again
+    pub fn record_fee(
+        &mut self,
+        db: &Database,
+        tx_hash: &str,
+        fee: Fee,
+        now: Timestamp,
+    ) -> Result<()> {
+        self.record_fee_with_rent(db, tx_hash, fee, None, now)
+    }
+
+    /// Same as `record_fee` for a transaction creating accounts, `rent` is the part of
+    /// `fee` funding their rent exemption
+    pub fn record_fee_with_rent(
+        &mut self,
+        db: &Database,
+        tx_hash: &str,
+        fee: Fee,
+        rent: Option<Lamports>,
+        now: Timestamp,
+    ) -> Result<()> {
+        let mut batch = Batch::default();
+        let mut recorded = false;
+        for account in quota_accounts(&self.input) {
+            let mut usage = quota_usage(db, &account, now)?;
+            if usage.fees.iter().any(|spent| spent.tx_hash == tx_hash) {
+                continue;
+            }
+            usage.fees.push(FeeSpent {
+                time: now,
+                tx_hash: tx_hash.to_string(),
+                fee,
+                rent,
+            });
+            batch.put(quota_key(&account), &usage)?;
+            recorded = true;
+        }
+        if !recorded {
+            return Ok(());
+        }
+
+        let paid = match fee {
+            Fee::EVM(fee) => fee.to_string(),
+            Fee::SOLANA(fee) => fee.to_string(),
+        };
+        let event = match rent {
+            Some(rent) => format!("Fee of {paid} paid in {tx_hash}, {rent} of it in rent"),
+            None => format!("Fee of {paid} paid in {tx_hash}"),
+        };
+        self.history.push(HistoryEntry { time: now, event });
+        self.save_with(db, batch)
+    }
+}
+
+#[cfg(test)]
+mod quota_test {
+    use std::time::Duration;
+
+    use storage::testing::each_engine;
+
+    use crate::{
+        check_quota, quota_usage, record_quota_request, reset_quota, BRequest, Chains, Fee,
+        InputRequest, Lamports, QuotaLimits, Timestamp, Wei, QUOTA_WINDOW,
+    };
+
+    const DESTINATION: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+
+    fn input(token_id: &str) -> InputRequest {
+        InputRequest {
+            contract_or_mint: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            token_id: token_id.to_string(),
+            token_owner: "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM".to_string(),
+            origin_network: Chains::SOLANA,
+            destination_account: DESTINATION.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_request_limit_rolls_over_with_the_window() {
+        for db in each_engine() {
+            let limits = QuotaLimits {
+                max_requests: Some(2),
+                ..Default::default()
+            };
+            let start = Timestamp::from_secs(1_700_000_000);
+            let later = start.saturating_add(Duration::from_secs(3600));
+
+            record_quota_request(&db, &input("1"), start).unwrap();
+            assert_eq!(check_quota(&db, &input("2"), &limits, later).unwrap(), None);
+            record_quota_request(&db, &input("2"), later).unwrap();
+
+            // Same destination with other token ids, the destination reached its limit
+            let exceeded = check_quota(&db, &input("3"), &limits, later)
+                .unwrap()
+                .unwrap();
+            assert_eq!(exceeded.account, DESTINATION.to_lowercase());
+            assert_eq!(exceeded.reset_at, start.saturating_add(QUOTA_WINDOW));
+
+            // The first request leaves the window
+            let rolled = start.saturating_add(QUOTA_WINDOW);
+            assert_eq!(
+                check_quota(&db, &input("3"), &limits, rolled).unwrap(),
+                None
+            );
+            assert_eq!(
+                quota_usage(&db, DESTINATION, rolled).unwrap().requests,
+                vec![later]
+            );
+        }
+    }
+
+    #[test]
+    fn test_fee_limit_and_admin_reset() {
+        for db in each_engine() {
+            let limits = QuotaLimits {
+                max_evm_fees: Some(Wei::from_human("0.01").unwrap()),
+                ..Default::default()
+            };
+            let now = Timestamp::from_secs(1_700_000_000);
+            let mut request = BRequest::new(input("1"), Timestamp::now());
+            request.save(&db).unwrap();
+
+            request
+                .record_fee(
+                    &db,
+                    "0xmint",
+                    Fee::EVM(Wei::from_human("0.006").unwrap()),
+                    now,
+                )
+                .unwrap();
+            // Solana fees count against their own limit
+            request
+                .record_fee(&db, "sig", Fee::SOLANA(Lamports::new(5_000)), now)
+                .unwrap();
+            assert_eq!(check_quota(&db, &input("2"), &limits, now).unwrap(), None);
+
+            let later = now.saturating_add(Duration::from_secs(60));
+            request
+                .record_fee(
+                    &db,
+                    "0xretry",
+                    Fee::EVM(Wei::from_human("0.006").unwrap()),
+                    later,
+                )
+                .unwrap();
+            // Recording the same transaction again is not charged twice
+            request
+                .record_fee(
+                    &db,
+                    "0xretry",
+                    Fee::EVM(Wei::from_human("0.006").unwrap()),
+                    later,
+                )
+                .unwrap();
+            let usage = quota_usage(&db, DESTINATION, later).unwrap();
+            assert_eq!(usage.evm_fees(), Wei::from_human("0.012").unwrap());
+            assert_eq!(usage.solana_fees(), Lamports::new(5_000));
+
+            let exceeded = check_quota(&db, &input("2"), &limits, later)
+                .unwrap()
+                .unwrap();
+            // Back under the limit once the first fee leaves the window
+            assert_eq!(exceeded.reset_at, now.saturating_add(QUOTA_WINDOW));
+            let stored = crate::request_data(&request.id, &db).unwrap().unwrap();
+            assert_eq!(
+                stored
+                    .history
+                    .iter()
+                    .filter(|entry| entry.event.starts_with("Fee of"))
+                    .count(),
+                3
+            );
+
+            // The rent of the accounts created by a mint is charged with its fee
+            request
+                .record_fee_with_rent(
+                    &db,
+                    "mint-sig",
+                    Fee::SOLANA(Lamports::new(2_044_280)),
+                    Some(Lamports::new(2_039_280)),
+                    later,
+                )
+                .unwrap();
+            let usage = quota_usage(&db, DESTINATION, later).unwrap();
+            assert_eq!(usage.solana_fees(), Lamports::new(2_049_280));
+            assert_eq!(usage.solana_rent(), Lamports::new(2_039_280));
+            let stored = crate::request_data(&request.id, &db).unwrap().unwrap();
+            assert!(stored
+                .history
+                .last()
+                .unwrap()
+                .event
+                .ends_with(&format!("{} of it in rent", Lamports::new(2_039_280))));
+
+            reset_quota(&db, DESTINATION).unwrap();
+            // The token owner was charged the same fees
+            assert!(check_quota(&db, &input("2"), &limits, later)
+                .unwrap()
+                .is_some_and(|exceeded| exceeded.account == input("2").token_owner));
+            reset_quota(&db, &input("2").token_owner).unwrap();
+            assert_eq!(check_quota(&db, &input("2"), &limits, later).unwrap(), None);
+        }
+    }
+}
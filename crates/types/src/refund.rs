@@ -0,0 +1,168 @@
+use log::info;
+use storage::{
+    db::{Batch, Database},
+    keys::PENDING_REFUNDS,
+};
+
+use crate::{BRequest, HistoryEntry, Result, Status, Timestamp};
+
+/// Canceled requests whose origin token is still held by the bridge, in cancel order
+pub fn pending_refunds(db: &Database) -> Result<Vec<String>> {
+    Ok(db
+        .read::<_, Vec<String>>(PENDING_REFUNDS)?
+        .unwrap_or_default())
+}
+
+/// Adds the request to the pending refunds in `batch`, once
+pub fn stage_pending_refund(request_id: &str, db: &Database, batch: &mut Batch) -> Result<()> {
+    let mut refunds = pending_refunds(db)?;
+    if !refunds.iter().any(|id| id == request_id) {
+        refunds.push(request_id.to_owned());
+        batch.put(PENDING_REFUNDS, &refunds)?;
+    }
+    Ok(())
+}
+
+fn stage_refund_removal(request_id: &str, db: &Database, batch: &mut Batch) -> Result<()> {
+    let mut refunds = pending_refunds(db)?;
+    if let Some(position) = refunds.iter().position(|id| id == request_id) {
+        refunds.remove(position);
+        batch.put(PENDING_REFUNDS, &refunds)?;
+    }
+    Ok(())
+}
+
+impl BRequest {
+    /// The bridge holds the origin token and no mint completed
+    pub fn holds_origin_token(&self) -> bool {
+        matches!(
+            self.status,
+            Status::TokenReceived | Status::TokenMinted | Status::AwaitingDestinationAccount
+        )
+    }
+
+    /// Whether the origin token can be returned to its owner, canceled requests with the
+    /// token in custody and unclaimed orphans
+    pub fn refundable(&self) -> bool {
+        match self.status {
+            Status::Canceled => self.refund_pending,
+            Status::RefundEligible => true,
+            _ => false,
+        }
+    }
+
+    /// Queues the refund of a refundable request, the custody is kept until refunded
+    pub fn queue_refund(&mut self, db: &Database, now: Timestamp) -> Result<()> {
+        let mut batch = Batch::default();
+        stage_pending_refund(&self.id, db, &mut batch)?;
+        if !self.refund_pending {
+            self.refund_pending = true;
+            self.push_event("Refund queued, the token is in custody", now);
+        }
+        self.save_with(db, batch)
+    }
+
+    /// Token returned to its owner in `tx_hash`
+    pub fn complete_refund(&mut self, db: &Database, tx_hash: &str, now: Timestamp) -> Result<()> {
+        let mut batch = Batch::default();
+        stage_refund_removal(&self.id, db, &mut batch)?;
+        self.tx_hashes.push(tx_hash.to_string());
+        self.status = Status::Refunded;
+        self.refund_pending = false;
+        self.last_update = now;
+        self.finalized_at = Some(self.last_update);
+        self.push_event(&format!("Token refunded to its owner in {tx_hash}"), now);
+        info!("Request {} refunded in {tx_hash}", self.id);
+        self.save_with(db, batch)
+    }
+
+    /// Removes a queued refund that must not be sent, e.g. the destination was minted
+    pub fn abandon_refund(&mut self, db: &Database, reason: &str, now: Timestamp) -> Result<()> {
+        let mut batch = Batch::default();
+        stage_refund_removal(&self.id, db, &mut batch)?;
+        self.refund_pending = false;
+        self.push_event(&format!("Refund abandoned: {reason}"), now);
+        self.save_with(db, batch)
+    }
+
+    fn push_event(&mut self, event: &str, now: Timestamp) {
+        self.history.push(HistoryEntry {
+            time: now,
+            event: event.to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod refund_test {
+    use storage::{db::Database, testing::each_engine};
+
+    use crate::{pending_refunds, BRequest, Chains, InputRequest, Status, Timestamp};
+
+    fn request(token_id: &str, status: Status, db: &Database) -> BRequest {
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0x5fbdb2315678afecb367f032d93f642f64180aa3".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.status = status;
+        request.save(db).unwrap();
+        request
+    }
+
+    #[test]
+    fn test_cancel_with_custody_queues_refund() {
+        for db in each_engine() {
+            // Token not transferred yet, nothing to return
+            let mut received = request("1", Status::RequestReceived, &db);
+            received.cancel(&db, Timestamp::now()).unwrap();
+            assert!(!received.refund_pending);
+            assert!(pending_refunds(&db).unwrap().is_empty());
+
+            let mut locked = request("2", Status::TokenReceived, &db);
+            locked.cancel(&db, Timestamp::now()).unwrap();
+            assert_eq!(locked.status, Status::Canceled);
+            assert!(locked.refundable());
+            assert_eq!(pending_refunds(&db).unwrap(), vec![locked.id.clone()]);
+
+            // Queued once
+            locked.queue_refund(&db, Timestamp::now()).unwrap();
+            assert_eq!(pending_refunds(&db).unwrap().len(), 1);
+
+            locked
+                .complete_refund(&db, "0xrefund", Timestamp::now())
+                .unwrap();
+            let stored = crate::request_data(&locked.id, &db).unwrap().unwrap();
+            assert_eq!(stored.status, Status::Refunded);
+            assert!(!stored.refund_pending && !stored.refundable());
+            assert_eq!(
+                stored.tx_hashes.last().map(String::as_str),
+                Some("0xrefund")
+            );
+            assert!(stored.finalized_at.is_some());
+            assert!(pending_refunds(&db).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_abandoned_refund_stays_canceled() {
+        for db in each_engine() {
+            let mut minted = request("3", Status::TokenMinted, &db);
+            minted.cancel(&db, Timestamp::now()).unwrap();
+            assert_eq!(pending_refunds(&db).unwrap(), vec![minted.id.clone()]);
+
+            minted
+                .abandon_refund(&db, "destination token minted", Timestamp::now())
+                .unwrap();
+            let stored = crate::request_data(&minted.id, &db).unwrap().unwrap();
+            assert_eq!(stored.status, Status::Canceled);
+            assert!(!stored.refundable());
+            assert!(pending_refunds(&db).unwrap().is_empty());
+        }
+    }
+}
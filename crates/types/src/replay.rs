@@ -0,0 +1,326 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use storage::{db::Database, keys::REPLAY_PREFIX};
+
+use crate::{
+    redact_urls, request_data, BRequest, Chains, RequestId, Result, Status, Timestamp,
+    RELAYER_VERSION,
+};
+
+/// Largest replay bundle of a request, inputs captured past it are counted and dropped
+pub const MAX_REPLAY_BUNDLE_LEN: usize = 256 * 1024;
+/// Largest captured input, larger payloads are replaced by their size
+pub const MAX_REPLAY_INPUT_LEN: usize = 16 * 1024;
+/// Object fields whose values never go to a bundle, matched on the lowercase field name
+const SECRET_FIELDS: [&str; 6] = [
+    "secret",
+    "private_key",
+    "password",
+    "api_key",
+    "authorization",
+    "admin_token",
+];
+
+static REPLAY_CAPTURE: AtomicBool = AtomicBool::new(false);
+
+/// Captures the inputs of the request decisions into replay bundles from now on. Set once
+/// on startup from `REPLAY_CAPTURE`, a debugging aid off in production
+pub fn set_replay_capture(enabled: bool) {
+    REPLAY_CAPTURE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn replay_capture() -> bool {
+    REPLAY_CAPTURE.load(Ordering::Relaxed)
+}
+
+/// Input a decision on a request was taken from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReplayInput {
+    /// Bridge contract event of the request
+    Event {
+        chain: Chains,
+        name: String,
+        payload: Value,
+    },
+    /// Result of a chain read
+    ChainRead {
+        chain: Chains,
+        operation: String,
+        result: Value,
+    },
+    /// Output of a simulated call
+    Simulation {
+        chain: Chains,
+        operation: String,
+        output: Value,
+    },
+}
+
+impl ReplayInput {
+    fn value_mut(&mut self) -> &mut Value {
+        match self {
+            ReplayInput::Event { payload, .. } => payload,
+            ReplayInput::ChainRead { result, .. } => result,
+            ReplayInput::Simulation { output, .. } => output,
+        }
+    }
+
+    /// Event, read result or simulation output parsed as `T`
+    pub fn value<T: DeserializeOwned>(&self) -> Result<T> {
+        let value = match self {
+            ReplayInput::Event { payload, .. } => payload,
+            ReplayInput::ChainRead { result, .. } => result,
+            ReplayInput::Simulation { output, .. } => output,
+        };
+        Ok(serde_json::from_value(value.clone())?)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayEntry {
+    /// Time of the decision, replays run on it
+    pub at: Timestamp,
+    pub input: ReplayInput,
+    /// The input was over `MAX_REPLAY_INPUT_LEN`, only its size was kept
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// Status and history events of a request, the transitions a replay reproduces. The times
+/// are left out, they come from the clock of the run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayTransitions {
+    pub status: Status,
+    pub events: Vec<String>,
+}
+
+impl ReplayTransitions {
+    pub fn of(request: &BRequest) -> Self {
+        ReplayTransitions {
+            status: request.status.clone(),
+            events: request
+                .history
+                .iter()
+                .map(|entry| entry.event.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Inputs captured for a request from the state it had before the first of them, enough
+/// to run its decisions again without the chains. Secrets and URLs are redacted
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplayBundle {
+    pub request_id: RequestId,
+    pub relayer_version: String,
+    pub initial: BRequest,
+    pub entries: Vec<ReplayEntry>,
+    /// Inputs left out once the bundle reached `MAX_REPLAY_BUNDLE_LEN`
+    #[serde(default)]
+    pub dropped: u32,
+    /// Transitions of the request when the bundle was exported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<ReplayTransitions>,
+}
+
+fn replay_key(request_id: &str) -> String {
+    format!("{REPLAY_PREFIX}{request_id}")
+}
+
+/// `value` without the values of secret fields, its strings with their URLs redacted
+pub fn redact_value(value: Value) -> Value {
+    match value {
+        Value::String(text) => Value::String(redact_urls(&text)),
+        Value::Array(values) => Value::Array(values.into_iter().map(redact_value).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(name, value)| {
+                    let lowercase = name.to_lowercase();
+                    if SECRET_FIELDS
+                        .iter()
+                        .any(|secret| lowercase.contains(secret))
+                    {
+                        (name, Value::String("[REDACTED]".to_string()))
+                    } else {
+                        (name, redact_value(value))
+                    }
+                })
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+fn redacted<T: Serialize + DeserializeOwned>(value: &T) -> Result<T> {
+    Ok(serde_json::from_value(redact_value(serde_json::to_value(
+        value,
+    )?))?)
+}
+
+fn capture(db: &Database, request: &BRequest, at: Timestamp, mut input: ReplayInput) -> Result<()> {
+    let key = replay_key(&request.id);
+    let mut bundle = match db.read::<_, ReplayBundle>(&key)? {
+        Some(bundle) => bundle,
+        None => ReplayBundle {
+            request_id: request.id.clone(),
+            relayer_version: RELAYER_VERSION.to_string(),
+            initial: redacted(request)?,
+            entries: vec![],
+            dropped: 0,
+            expected: None,
+        },
+    };
+
+    let value = input.value_mut();
+    *value = redact_value(value.take());
+    let len = serde_json::to_vec(value)?.len();
+    let truncated = len > MAX_REPLAY_INPUT_LEN;
+    if truncated {
+        *value = Value::String(format!("[TRUNCATED {len} bytes]"));
+    }
+    let entry = ReplayEntry {
+        at,
+        input,
+        truncated,
+    };
+
+    let bundle_len = serde_json::to_vec(&bundle)?.len() + serde_json::to_vec(&entry)?.len();
+    if bundle_len > MAX_REPLAY_BUNDLE_LEN {
+        bundle.dropped += 1;
+    } else {
+        bundle.entries.push(entry);
+    }
+    db.write_value(&key, &bundle)?;
+    Ok(())
+}
+
+/// Adds the input of a decision on `request` to its replay bundle under `REPLAY_CAPTURE`,
+/// the request as it is before the decision. Failures are logged, a capture never fails
+/// the decision
+pub fn capture_replay_input(db: &Database, request: &BRequest, at: Timestamp, input: ReplayInput) {
+    if !replay_capture() {
+        return;
+    }
+    if let Err(e) = capture(db, request, at, input) {
+        error!(
+            "Could not capture a replay input of request {}: {e}",
+            request.id
+        );
+    }
+}
+
+/// Replay bundle of a request with the transitions of the request as it is now, None
+/// when nothing was captured for it
+pub fn export_replay_bundle(db: &Database, request_id: &str) -> Result<Option<ReplayBundle>> {
+    let Some(mut bundle) = db.read::<_, ReplayBundle>(replay_key(request_id))? else {
+        return Ok(None);
+    };
+    bundle.expected = request_data(request_id, db)?.map(|request| ReplayTransitions::of(&request));
+    Ok(Some(bundle))
+}
+
+#[cfg(test)]
+mod replay_test {
+    use serde_json::{json, Value};
+    use storage::testing::each_engine;
+
+    use crate::{
+        export_replay_bundle, redact_value, replay::capture, BRequest, Chains, InputRequest,
+        ReplayInput, Status, Timestamp, MAX_REPLAY_BUNDLE_LEN, MAX_REPLAY_INPUT_LEN,
+    };
+
+    const AT: Timestamp = Timestamp::from_secs(1_700_000_000);
+
+    fn request() -> BRequest {
+        BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xcontract".to_string(),
+                token_id: "1".to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        )
+    }
+
+    fn read(result: Value) -> ReplayInput {
+        ReplayInput::ChainRead {
+            chain: Chains::SOLANA,
+            operation: "get_signature_statuses".to_string(),
+            result,
+        }
+    }
+
+    #[test]
+    fn test_bundle_redacted_and_exported() {
+        let redacted = redact_value(json!({
+            "rpc": "https://rpc.example.com/key",
+            "nested": [{ "evm_private_key": "0xac09", "token_id": "1" }],
+            "Authorization": "Bearer admin",
+        }));
+        assert_eq!(
+            redacted,
+            json!({
+                "rpc": "https://rpc.example.com/[REDACTED]",
+                "nested": [{ "evm_private_key": "[REDACTED]", "token_id": "1" }],
+                "Authorization": "[REDACTED]",
+            })
+        );
+
+        for db in each_engine() {
+            let mut request = request();
+            request.save(&db).unwrap();
+            assert!(export_replay_bundle(&db, &request.id).unwrap().is_none());
+
+            capture(&db, &request, AT, read(json!({ "slot": 1 }))).unwrap();
+            request.status = Status::TokenReceived;
+            request
+                .record_event("Token received", &db, Timestamp::now())
+                .unwrap();
+            capture(&db, &request, AT, read(json!({ "api_key": "k" }))).unwrap();
+
+            let bundle = export_replay_bundle(&db, &request.id).unwrap().unwrap();
+            // The request before the first input
+            assert_eq!(bundle.initial.status, Status::RequestReceived);
+            assert_eq!(bundle.entries.len(), 2);
+            assert_eq!(
+                bundle.entries[1].input,
+                read(json!({ "api_key": "[REDACTED]" }))
+            );
+            let expected = bundle.expected.unwrap();
+            assert_eq!(expected.status, Status::TokenReceived);
+            assert_eq!(expected.events, vec!["Token received".to_string()]);
+        }
+    }
+
+    #[test]
+    fn test_bundle_size_capped() {
+        for db in each_engine() {
+            let request = request();
+            request.save(&db).unwrap();
+            let large = "a".repeat(MAX_REPLAY_INPUT_LEN);
+            capture(&db, &request, AT, read(json!(large))).unwrap();
+            let bundle = export_replay_bundle(&db, &request.id).unwrap().unwrap();
+            assert!(bundle.entries[0].truncated);
+            assert_eq!(
+                bundle.entries[0].input,
+                read(json!(format!("[TRUNCATED {} bytes]", large.len() + 2)))
+            );
+
+            let input = "a".repeat(MAX_REPLAY_INPUT_LEN / 2);
+            for _ in 0..2 * MAX_REPLAY_BUNDLE_LEN / input.len() {
+                capture(&db, &request, AT, read(json!(input))).unwrap();
+            }
+            let mut bundle = export_replay_bundle(&db, &request.id).unwrap().unwrap();
+            assert!(bundle.dropped > 0);
+            bundle.expected = None;
+            assert!(serde_json::to_vec(&bundle).unwrap().len() <= MAX_REPLAY_BUNDLE_LEN);
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use std::{borrow::Borrow, fmt, ops::Deref, str::FromStr};
+
+use alloy::primitives::B256;
+use serde::{Deserialize, Serialize};
+
+/// Length of a request id, `0x` followed by the 64 hex digits of a keccak256 hash
+pub const REQUEST_ID_LEN: usize = 66;
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid request id {0:?}, expected 0x followed by 64 hex digits")]
+pub struct InvalidRequestId(pub String);
+
+/// Id of a bridge request, the hash generated by `BRequest::generate_id`. Only built
+/// through a checked parse, ids read from the API, events and storage all go through it
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Parses an id, surrounding whitespace is trimmed and hex digits are lowercased
+    pub fn parse(value: &str) -> Result<Self, InvalidRequestId> {
+        let trimmed = value.trim();
+        let valid = trimmed.len() == REQUEST_ID_LEN
+            && trimmed.starts_with("0x")
+            && trimmed[2..].bytes().all(|b| b.is_ascii_hexdigit());
+        if !valid {
+            return Err(InvalidRequestId(value.to_string()));
+        }
+        Ok(RequestId(trimmed.to_ascii_lowercase()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<B256> for RequestId {
+    fn from(hash: B256) -> Self {
+        RequestId(hash.to_string())
+    }
+}
+
+impl FromStr for RequestId {
+    type Err = InvalidRequestId;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        RequestId::parse(value)
+    }
+}
+
+impl TryFrom<String> for RequestId {
+    type Error = InvalidRequestId;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        RequestId::parse(&value)
+    }
+}
+
+impl TryFrom<&str> for RequestId {
+    type Error = InvalidRequestId;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        RequestId::parse(value)
+    }
+}
+
+impl From<RequestId> for String {
+    fn from(id: RequestId) -> Self {
+        id.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Deref for RequestId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for RequestId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Requests are stored under their id
+impl AsRef<[u8]> for RequestId {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl Borrow<str> for RequestId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for RequestId {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for RequestId {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for RequestId {
+    fn eq(&self, other: &String) -> bool {
+        &self.0 == other
+    }
+}
+
+impl PartialEq<RequestId> for String {
+    fn eq(&self, other: &RequestId) -> bool {
+        *self == other.0
+    }
+}
+
+impl PartialEq<RequestId> for &str {
+    fn eq(&self, other: &RequestId) -> bool {
+        *self == other.0
+    }
+}
+
+#[cfg(test)]
+mod request_id_test {
+    use alloy::primitives::Address;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    use crate::{BRequest, InvalidRequestId, RequestId, REQUEST_ID_LEN};
+
+    const ID: &str = "0x2c2a1c6c2d1a8b1f9d4e0a7b3c5d6e8f9a0b1c2d3e4f5a6b7c8d9e0f1a2b3c4d";
+
+    /// Contract or owner of an EVM request, fixed length like the real ones
+    fn evm_address() -> impl Strategy<Value = String> {
+        any::<[u8; 20]>().prop_map(|bytes| Address::from(bytes).to_string())
+    }
+
+    proptest! {
+        #[test]
+        fn test_generated_ids_are_stable(
+            contract in "\\PC{0,64}",
+            token_id in "\\PC{0,32}",
+            owner in "\\PC{0,64}",
+        ) {
+            let id = BRequest::generate_id(&contract, &token_id, &owner);
+            prop_assert_eq!(&id, &BRequest::generate_id(&contract, &token_id, &owner));
+
+            let formatted = id.to_string();
+            prop_assert_eq!(formatted.len(), REQUEST_ID_LEN);
+            prop_assert!(formatted.starts_with("0x"));
+            prop_assert!(formatted[2..]
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)));
+            prop_assert_eq!(&RequestId::parse(&formatted).unwrap(), &id);
+            let upper = format!(" 0x{} ", formatted[2..].to_uppercase());
+            prop_assert_eq!(&RequestId::parse(&upper).unwrap(), &id);
+            let stored = serde_json::to_value(&id).unwrap();
+            prop_assert_eq!(serde_json::from_value::<RequestId>(stored).unwrap(), id);
+        }
+
+        #[test]
+        fn test_distinct_evm_inputs_get_distinct_ids(
+            a in (evm_address(), any::<u64>(), evm_address()),
+            b in (evm_address(), any::<u64>(), evm_address()),
+        ) {
+            prop_assume!(a != b);
+            let id = |(contract, token_id, owner): &(String, u64, String)| {
+                BRequest::generate_id(contract, &token_id.to_string(), owner)
+            };
+            prop_assert_ne!(id(&a), id(&b));
+        }
+
+        /// The fields are hashed without separators, inputs are told apart when their
+        /// concatenations differ, as with the fixed length addresses above
+        #[test]
+        fn test_distinguishable_inputs_get_distinct_ids(
+            a in ("\\PC{0,16}", "\\PC{0,8}", "\\PC{0,16}"),
+            b in ("\\PC{0,16}", "\\PC{0,8}", "\\PC{0,16}"),
+        ) {
+            prop_assume!(format!("{}{}{}", a.0, a.1, a.2) != format!("{}{}{}", b.0, b.1, b.2));
+            prop_assert_ne!(
+                BRequest::generate_id(&a.0, &a.1, &a.2),
+                BRequest::generate_id(&b.0, &b.1, &b.2)
+            );
+        }
+
+        #[test]
+        fn test_parse_never_panics(value in "\\PC{0,80}") {
+            match RequestId::parse(&value) {
+                Ok(id) => prop_assert_eq!(id.as_str(), value.trim().to_ascii_lowercase()),
+                Err(e) => prop_assert_eq!(e, InvalidRequestId(value)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_failures() {
+        for invalid in [
+            String::new(),
+            "0x".to_string(),
+            "request123".to_string(),
+            ID[2..].to_string(),
+            ID[..65].to_string(),
+            format!("{ID}0"),
+            format!("0X{}", &ID[2..]),
+            format!("0x{}g", &ID[3..]),
+            format!("{}\u{e9}", &ID[..65]),
+        ] {
+            assert_eq!(
+                RequestId::parse(&invalid),
+                Err(InvalidRequestId(invalid.clone())),
+                "{invalid:?} parsed"
+            );
+        }
+
+        let id =
+            RequestId::parse(&format!("  {}\n", ID.to_uppercase().replace("0X", "0x"))).unwrap();
+        assert_eq!(id, ID);
+        assert_eq!(id.to_string(), ID);
+        assert_eq!(ID.parse::<RequestId>().unwrap(), id);
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let id = RequestId::parse(ID).unwrap();
+        assert_eq!(serde_json::to_value(&id).unwrap(), json!(ID));
+        assert_eq!(serde_json::from_value::<RequestId>(json!(ID)).unwrap(), id);
+        assert!(serde_json::from_value::<RequestId>(json!("request123")).is_err());
+        assert!(serde_json::from_value::<RequestId>(json!(42)).is_err());
+    }
+
+    #[test]
+    fn test_stored_requests_read_plain_string_ids() {
+        let id = BRequest::generate_id("0xcontract", "1", "0xowner");
+        // Requests stored before the newtype, the id is a plain JSON string
+        let stored = json!({
+            "id": id.as_str(),
+            "status": "Completed",
+            "input": {
+                "contract_or_mint": "0xcontract",
+                "token_id": "1",
+                "token_owner": "0xowner",
+                "origin_network": "EVM",
+                "destination_account": "account"
+            },
+            "tx_hashes": [],
+            "output": {
+                "detination_token_id_or_account": "",
+                "detination_contract_id_or_mint": ""
+            },
+            "last_update": 1_700_000_000_000u64
+        });
+
+        let request: BRequest = serde_json::from_value(stored.clone()).unwrap();
+        assert_eq!(request.id, id);
+        assert_eq!(serde_json::to_value(&request).unwrap()["id"], stored["id"]);
+    }
+}
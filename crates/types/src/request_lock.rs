@@ -0,0 +1,61 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+/// Requests currently being processed by the pending sweep or a manual reprocess, so
+/// the same request is never processed twice at the same time
+#[derive(Clone, Debug, Default)]
+pub struct RequestLocks {
+    locked: Arc<Mutex<HashSet<String>>>,
+}
+
+impl RequestLocks {
+    /// Locks the request until the guard is dropped, None when it is already locked
+    pub fn try_lock(&self, request_id: &str) -> Option<RequestLockGuard> {
+        if !self.locked.lock().unwrap().insert(request_id.to_string()) {
+            return None;
+        }
+        Some(RequestLockGuard {
+            locks: self.clone(),
+            request_id: request_id.to_string(),
+        })
+    }
+
+    pub fn is_locked(&self, request_id: &str) -> bool {
+        self.locked.lock().unwrap().contains(request_id)
+    }
+}
+
+#[derive(Debug)]
+pub struct RequestLockGuard {
+    locks: RequestLocks,
+    request_id: String,
+}
+
+impl Drop for RequestLockGuard {
+    fn drop(&mut self) {
+        self.locks.locked.lock().unwrap().remove(&self.request_id);
+    }
+}
+
+#[cfg(test)]
+mod request_lock_test {
+    use crate::RequestLocks;
+
+    #[test]
+    fn test_request_locked_until_guard_dropped() {
+        let locks = RequestLocks::default();
+
+        let guard = locks.try_lock("request1").unwrap();
+        assert!(locks.is_locked("request1"));
+        assert!(locks.try_lock("request1").is_none());
+        // Other requests are not affected
+        assert!(locks.try_lock("request2").is_some());
+        assert!(!locks.is_locked("request2"));
+
+        drop(guard);
+        assert!(!locks.is_locked("request1"));
+        assert!(locks.try_lock("request1").is_some());
+    }
+}
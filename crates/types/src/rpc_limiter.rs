@@ -0,0 +1,76 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{sync::Mutex, time::Instant};
+
+/// Requests per second to the chain RPC endpoints when not configured
+pub const DEFAULT_RPC_REQUESTS_PER_SECOND: u32 = 10;
+
+/// Spaces the RPC requests of its holders evenly under a rate, the clones share it
+#[derive(Clone, Debug)]
+pub struct RpcLimiter {
+    interval: Duration,
+    next_slot: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Default for RpcLimiter {
+    fn default() -> Self {
+        RpcLimiter::per_second(DEFAULT_RPC_REQUESTS_PER_SECOND)
+    }
+}
+
+impl RpcLimiter {
+    /// Limiter of `requests` per second, 0 doesn't limit
+    pub fn per_second(requests: u32) -> Self {
+        let interval = match requests {
+            0 => Duration::ZERO,
+            requests => Duration::from_secs(1) / requests,
+        };
+        RpcLimiter {
+            interval,
+            next_slot: Arc::default(),
+        }
+    }
+
+    /// Waits for the slot of the next request
+    pub async fn acquire(&self) {
+        if self.interval.is_zero() {
+            return;
+        }
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let now = Instant::now();
+            let slot = next_slot.map_or(now, |next| next.max(now));
+            *next_slot = Some(slot + self.interval);
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+#[cfg(test)]
+mod rpc_limiter_test {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use crate::RpcLimiter;
+
+    #[tokio::test]
+    async fn test_requests_spaced_across_clones() {
+        let limiter = RpcLimiter::per_second(50);
+        let other = limiter.clone();
+        let start = Instant::now();
+        limiter.acquire().await;
+        other.acquire().await;
+        limiter.acquire().await;
+        // The first request goes at once, the next two wait 20ms each
+        assert!(start.elapsed() >= Duration::from_millis(40));
+
+        let unlimited = RpcLimiter::per_second(0);
+        let start = Instant::now();
+        for _ in 0..100 {
+            unlimited.acquire().await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,460 @@
+use std::{future::Future, pin::Pin};
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use storage::{db::Database, keys::SEND_INTENT_PREFIX};
+
+use crate::{
+    request_data, BRequest, BridgeError, Chains, InFlightRegistry, RequestId, Result, Status,
+    Timestamp,
+};
+
+/// Transaction a tx processor sends for a request
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SendOperation {
+    Lock,
+    Mint,
+    Refund,
+}
+
+impl SendOperation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SendOperation::Lock => "lock",
+            SendOperation::Mint => "mint",
+            SendOperation::Refund => "refund",
+        }
+    }
+}
+
+/// Write-ahead record of a transaction, stored before it is sent and removed once its
+/// hash is recorded on the request. An intent left by a crash tells what to look for on
+/// the chain before the transaction is sent again
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SendIntent {
+    pub request_id: RequestId,
+    pub chain: Chains,
+    pub operation: SendOperation,
+    /// Nonce of an EVM transaction
+    pub nonce: Option<u64>,
+    /// Blockhash a Solana transaction is signed with, it can't land once expired
+    pub recent_blockhash: Option<String>,
+    /// Account a landed Solana transaction touches: the mint it creates or the token
+    /// account it transfers to
+    pub destination: Option<String>,
+    /// Hash of a transaction signed before its send, or returned by the send
+    pub tx_hash: Option<String>,
+    /// Set once the send returned
+    pub sent: bool,
+    pub recorded_at: Timestamp,
+}
+
+impl SendIntent {
+    pub fn new(request_id: &str, chain: Chains, operation: SendOperation) -> Self {
+        SendIntent {
+            request_id: request_id.to_string(),
+            chain,
+            operation,
+            nonce: None,
+            recent_blockhash: None,
+            destination: None,
+            tx_hash: None,
+            sent: false,
+            recorded_at: Timestamp::now(),
+        }
+    }
+}
+
+fn intent_key(request_id: &str, operation: SendOperation) -> String {
+    format!("{SEND_INTENT_PREFIX}{request_id}:{}", operation.name())
+}
+
+/// Stores the intent before its transaction is sent, replacing the one of an earlier
+/// attempt
+pub fn record_send_intent(db: &Database, intent: &SendIntent) -> Result<()> {
+    db.write_value(intent_key(&intent.request_id, intent.operation), intent)
+}
+
+/// Marks the intent sent with the hash returned by the send
+pub fn mark_intent_sent(
+    db: &Database,
+    request_id: &str,
+    operation: SendOperation,
+    tx_hash: &str,
+) -> Result<()> {
+    let key = intent_key(request_id, operation);
+    let Some(mut intent) = db.read::<_, SendIntent>(&key)? else {
+        return Ok(());
+    };
+    intent.tx_hash = Some(tx_hash.to_string());
+    intent.sent = true;
+    db.write_value(&key, &intent)
+}
+
+/// Removes the intent once the hash is recorded on the request, or the send failed
+/// without reaching the chain. Failures leave it to the next lookup
+pub fn clear_send_intent(db: &Database, request_id: &str, operation: SendOperation) {
+    if let Err(e) = db.delete(intent_key(request_id, operation)) {
+        error!(
+            "Could not clear the {} intent of request {request_id}: {e}",
+            operation.name()
+        );
+    }
+}
+
+pub fn send_intent(
+    db: &Database,
+    request_id: &str,
+    operation: SendOperation,
+) -> Result<Option<SendIntent>> {
+    db.read(intent_key(request_id, operation))
+}
+
+/// Intents of `chain` still stored, their transactions may or may not have landed
+pub fn dangling_send_intents(db: &Database, chain: &Chains) -> Result<Vec<SendIntent>> {
+    Ok(db
+        .scan_prefix::<SendIntent>(SEND_INTENT_PREFIX, None)?
+        .into_iter()
+        .map(|(_, intent)| intent)
+        .filter(|intent| intent.chain == *chain)
+        .collect())
+}
+
+/// Transaction of an intent as its chain knows it
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntentLookup {
+    /// Landed or known to the chain with this hash, not sent again
+    Found(String),
+    /// Can't land anymore, the transaction is sent again
+    NotFound,
+    /// May still land, not sent again until a later lookup decides
+    Undecided,
+}
+
+pub type IntentLookupFuture<'a> = Pin<Box<dyn Future<Output = Result<IntentLookup>> + Send + 'a>>;
+
+/// Chain access of the send journal
+pub trait SendLookup: Send + Sync {
+    /// Looks for the transaction of `intent` on its chain
+    fn lookup<'a>(&'a self, intent: &'a SendIntent) -> IntentLookupFuture<'a>;
+}
+
+/// Looks up the intent left by an earlier send of the operation before it is sent again.
+/// Some hash when that transaction is found, to be taken as the send result. None when
+/// there is nothing to look for or the transaction can't land, then it is sent again
+pub async fn resolve_send_intent(
+    db: &Database,
+    lookup: &dyn SendLookup,
+    request_id: &str,
+    operation: SendOperation,
+) -> Result<Option<String>> {
+    let Some(intent) = send_intent(db, request_id, operation)? else {
+        return Ok(None);
+    };
+    match lookup.lookup(&intent).await? {
+        IntentLookup::Found(tx_hash) => {
+            info!(
+                "The {} of request {request_id} landed in {tx_hash}, not sent again",
+                operation.name()
+            );
+            Ok(Some(tx_hash))
+        }
+        IntentLookup::NotFound => {
+            clear_send_intent(db, request_id, operation);
+            Ok(None)
+        }
+        IntentLookup::Undecided => Err(BridgeError::Other(format!(
+            "The {} of request {request_id} may still land, not sent again",
+            operation.name()
+        ))),
+    }
+}
+
+/// Dangling intents of a chain after a restart
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SendRecovery {
+    /// Transactions found and recorded on their request
+    pub landed: usize,
+    /// Transactions not found, left to the sweep to send again
+    pub resend: usize,
+    /// Transactions that may still land, their requests are kept in flight
+    pub undecided: usize,
+}
+
+impl BRequest {
+    /// Records the landed transaction of `operation` as its tx processor would have
+    fn recover_sent_tx(
+        &mut self,
+        operation: SendOperation,
+        tx_hash: &str,
+        db: &Database,
+        now: Timestamp,
+    ) -> Result<()> {
+        match operation {
+            SendOperation::Lock => self.lock_sent(tx_hash, db, now),
+            // The sweep verifies the minted token of a TokenMinted request from its tx
+            SendOperation::Mint => {
+                self.add_tx(tx_hash, db)?;
+                if self.status == Status::TokenReceived {
+                    self.update_state(db, now)?;
+                }
+                Ok(())
+            }
+            SendOperation::Refund if self.refundable() => self.complete_refund(db, tx_hash, now),
+            SendOperation::Refund => self.add_tx(tx_hash, db),
+        }
+    }
+}
+
+/// Looks up the intents of `chain` left by a crash before the tx processors start. A
+/// transaction found is recorded on its request, one that can't land is left to the
+/// sweep to send again, and the requests of the ones that may still land stay in flight
+/// so their messages are dropped until a later lookup decides
+pub async fn recover_send_intents(
+    db: &Database,
+    chain: &Chains,
+    lookup: &dyn SendLookup,
+    in_flight: &InFlightRegistry,
+    now: Timestamp,
+) -> Result<SendRecovery> {
+    let mut recovery = SendRecovery::default();
+    for intent in dangling_send_intents(db, chain)? {
+        let request_id = &intent.request_id;
+        let Some(mut request) = request_data(request_id, db)? else {
+            clear_send_intent(db, request_id, intent.operation);
+            continue;
+        };
+        // Crashed after the hash was recorded
+        if let Some(tx_hash) = &intent.tx_hash {
+            if request.tx_hashes.contains(tx_hash) {
+                clear_send_intent(db, request_id, intent.operation);
+                continue;
+            }
+        }
+
+        match lookup.lookup(&intent).await {
+            Ok(IntentLookup::Found(tx_hash)) => {
+                info!(
+                    "The {} of request {request_id} landed in {tx_hash} before the restart",
+                    intent.operation.name()
+                );
+                request.recover_sent_tx(intent.operation, &tx_hash, db, now)?;
+                clear_send_intent(db, request_id, intent.operation);
+                in_flight.release(request_id, db);
+                recovery.landed += 1;
+            }
+            Ok(IntentLookup::NotFound) => {
+                info!(
+                    "The {} of request {request_id} never landed, sending it again",
+                    intent.operation.name()
+                );
+                clear_send_intent(db, request_id, intent.operation);
+                in_flight.release(request_id, db);
+                recovery.resend += 1;
+            }
+            Ok(IntentLookup::Undecided) => {
+                in_flight.try_acquire(request_id, db);
+                recovery.undecided += 1;
+            }
+            Err(e) => {
+                warn!(
+                    "Could not look up the {} of request {request_id}: {e}",
+                    intent.operation.name()
+                );
+                in_flight.try_acquire(request_id, db);
+                recovery.undecided += 1;
+            }
+        }
+    }
+    Ok(recovery)
+}
+
+#[cfg(test)]
+mod send_journal_test {
+    use std::{collections::HashMap, sync::Arc};
+
+    use storage::{db::Database, testing::each_engine};
+
+    use crate::{
+        dangling_send_intents, mark_intent_sent, record_send_intent, recover_send_intents,
+        request_data, resolve_send_intent, send_intent, BRequest, BridgeError, Chains,
+        InFlightRegistry, InputRequest, IntentLookup, IntentLookupFuture, MockClock, SendIntent,
+        SendLookup, SendOperation, SendRecovery, Status, Timestamp, IN_FLIGHT_TIMEOUT,
+    };
+
+    /// Chain with the canned lookups of the intents, by request id
+    struct MockLookup(HashMap<String, IntentLookup>);
+
+    impl SendLookup for MockLookup {
+        fn lookup<'a>(&'a self, intent: &'a SendIntent) -> IntentLookupFuture<'a> {
+            let found = self.0.get(&intent.request_id).cloned();
+            Box::pin(async move {
+                found.ok_or_else(|| BridgeError::Other("node unavailable".to_string()))
+            })
+        }
+    }
+
+    fn in_flight(db: &Database) -> InFlightRegistry {
+        let clock = Arc::new(MockClock::new(Timestamp::from_secs(1_700_000_000)));
+        InFlightRegistry::load(db, IN_FLIGHT_TIMEOUT, clock)
+    }
+
+    fn request_in_status(origin: Chains, token_id: &str, status: Status) -> BRequest {
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "contract".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "owner".to_string(),
+                origin_network: origin,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.status = status;
+        request
+    }
+
+    #[tokio::test]
+    async fn test_dangling_evm_mints_recovered() {
+        for db in each_engine() {
+            let mut lookups = HashMap::new();
+            let mut ids = vec![];
+            for (token_id, lookup) in [
+                ("1", IntentLookup::Found("0xlanded".to_string())),
+                ("2", IntentLookup::NotFound),
+                ("3", IntentLookup::Undecided),
+            ] {
+                let request = request_in_status(Chains::SOLANA, token_id, Status::TokenReceived);
+                request.save(&db).unwrap();
+                let mut intent = SendIntent::new(&request.id, Chains::EVM, SendOperation::Mint);
+                intent.nonce = Some(7);
+                intent.tx_hash = Some(format!("0xsigned{token_id}"));
+                record_send_intent(&db, &intent).unwrap();
+                lookups.insert(request.id.clone(), lookup);
+                ids.push(request.id);
+            }
+            let in_flight = in_flight(&db);
+
+            let recovery = recover_send_intents(
+                &db,
+                &Chains::EVM,
+                &MockLookup(lookups),
+                &in_flight,
+                Timestamp::now(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(
+                recovery,
+                SendRecovery {
+                    landed: 1,
+                    resend: 1,
+                    undecided: 1,
+                }
+            );
+
+            // The landed mint is recorded and not sent again
+            let landed = request_data(&ids[0], &db).unwrap().unwrap();
+            assert_eq!(landed.status, Status::TokenMinted);
+            assert_eq!(landed.tx_hashes, vec!["0xlanded".to_string()]);
+            // The lost one is sent again by the sweep
+            let lost = request_data(&ids[1], &db).unwrap().unwrap();
+            assert_eq!(lost.status, Status::TokenReceived);
+            assert!(send_intent(&db, &ids[1], SendOperation::Mint)
+                .unwrap()
+                .is_none());
+            // The pending one waits in flight with its intent
+            assert!(in_flight.is_in_flight(&ids[2]));
+            assert!(!in_flight.is_in_flight(&ids[1]));
+            let dangling = dangling_send_intents(&db, &Chains::EVM).unwrap();
+            assert_eq!(dangling.len(), 1);
+            assert_eq!(dangling[0].request_id, ids[2]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dangling_solana_sends_recovered() {
+        for db in each_engine() {
+            let lock = request_in_status(Chains::SOLANA, "1", Status::Initializing);
+            lock.save(&db).unwrap();
+            let mut intent = SendIntent::new(&lock.id, Chains::SOLANA, SendOperation::Lock);
+            intent.recent_blockhash = Some("blockhash".to_string());
+            intent.destination = Some("bridge token account".to_string());
+            record_send_intent(&db, &intent).unwrap();
+
+            // Sent and recorded before the crash, nothing to look up
+            let mut minted = request_in_status(Chains::EVM, "2", Status::TokenMinted);
+            minted.tx_hashes.push("sent".to_string());
+            minted.save(&db).unwrap();
+            record_send_intent(
+                &db,
+                &SendIntent::new(&minted.id, Chains::SOLANA, SendOperation::Mint),
+            )
+            .unwrap();
+            mark_intent_sent(&db, &minted.id, SendOperation::Mint, "sent").unwrap();
+
+            let lookup = MockLookup(HashMap::from([(
+                lock.id.clone(),
+                IntentLookup::Found("signature".to_string()),
+            )]));
+            let recovery = recover_send_intents(
+                &db,
+                &Chains::SOLANA,
+                &lookup,
+                &in_flight(&db),
+                Timestamp::now(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(recovery.landed, 1);
+            let lock = request_data(&lock.id, &db).unwrap().unwrap();
+            assert_eq!(lock.status, Status::RequestReceived);
+            assert_eq!(lock.tx_hashes, vec!["signature".to_string()]);
+            let minted = request_data(&minted.id, &db).unwrap().unwrap();
+            assert_eq!(minted.tx_hashes, vec!["sent".to_string()]);
+            assert!(dangling_send_intents(&db, &Chains::SOLANA)
+                .unwrap()
+                .is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_repeated_only_when_not_found() {
+        for db in each_engine() {
+            let lookup = MockLookup(HashMap::from([
+                (
+                    "landed".to_string(),
+                    IntentLookup::Found("0xlanded".to_string()),
+                ),
+                ("lost".to_string(), IntentLookup::NotFound),
+                ("pending".to_string(), IntentLookup::Undecided),
+            ]));
+            let resolve = |request_id: &'static str| {
+                let (db, lookup) = (&db, &lookup);
+                async move { resolve_send_intent(db, lookup, request_id, SendOperation::Refund).await }
+            };
+            for request_id in ["landed", "lost", "pending"] {
+                record_send_intent(
+                    &db,
+                    &SendIntent::new(request_id, Chains::EVM, SendOperation::Refund),
+                )
+                .unwrap();
+            }
+
+            assert_eq!(resolve("none").await.unwrap(), None);
+            assert_eq!(
+                resolve("landed").await.unwrap(),
+                Some("0xlanded".to_string())
+            );
+            assert_eq!(resolve("lost").await.unwrap(), None);
+            assert!(send_intent(&db, "lost", SendOperation::Refund)
+                .unwrap()
+                .is_none());
+            assert!(resolve("pending").await.is_err());
+            assert!(send_intent(&db, "pending", SendOperation::Refund)
+                .unwrap()
+                .is_some());
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    collections::VecDeque,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{BRequest, RequestId};
+
+const STATUS_FEED_CAPACITY: usize = 1024;
+/// Resyncs a subscriber of a request can need within `STATUS_RESYNC_WINDOW`, one more
+/// and it is dropped
+pub const MAX_STATUS_RESYNCS: usize = 3;
+pub const STATUS_RESYNC_WINDOW: Duration = Duration::from_secs(60);
+
+static STATUS_FEED: OnceLock<broadcast::Sender<BRequest>> = OnceLock::new();
+
+fn status_feed() -> &'static broadcast::Sender<BRequest> {
+    STATUS_FEED.get_or_init(|| broadcast::channel(STATUS_FEED_CAPACITY).0)
+}
+
+/// Receives every saved request, subscribers filter the transitions they need.
+/// Slow subscribers lag and must read the request again from the database
+pub fn subscribe_status_updates() -> broadcast::Receiver<BRequest> {
+    status_feed().subscribe()
+}
+
+/// Never waits for the subscribers, the feed drops the oldest updates of the ones that
+/// are behind
+pub(crate) fn publish_status(request: &BRequest) {
+    let feed = status_feed();
+    if feed.receiver_count() > 0 {
+        let _ = feed.send(request.clone());
+    }
+}
+
+/// Next thing a subscriber of a request gets from the feed
+#[derive(Debug, Clone)]
+pub enum StatusFeedItem {
+    /// The request was saved
+    Update(BRequest),
+    /// Updates were dropped while the subscriber was behind, it reads the request again
+    /// instead of replaying them
+    Resync,
+    /// Behind more than `MAX_STATUS_RESYNCS` times within `STATUS_RESYNC_WINDOW`, the
+    /// subscriber is closed
+    TooSlow,
+    Closed,
+}
+
+/// Updates of one request, with the lags of the subscriber turned into resyncs
+#[derive(Debug)]
+pub struct RequestUpdates {
+    request_id: RequestId,
+    updates: broadcast::Receiver<BRequest>,
+    resyncs: VecDeque<Instant>,
+}
+
+impl RequestUpdates {
+    pub fn new(request_id: &RequestId, updates: broadcast::Receiver<BRequest>) -> Self {
+        RequestUpdates {
+            request_id: request_id.clone(),
+            updates,
+            resyncs: VecDeque::new(),
+        }
+    }
+
+    /// Follows `request_id` in the status feed
+    pub fn subscribe(request_id: &RequestId) -> Self {
+        RequestUpdates::new(request_id, subscribe_status_updates())
+    }
+
+    pub async fn next(&mut self) -> StatusFeedItem {
+        loop {
+            match self.updates.recv().await {
+                Ok(update) if update.id == self.request_id => {
+                    return StatusFeedItem::Update(update)
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    let now = Instant::now();
+                    self.resyncs
+                        .retain(|resync| now.duration_since(*resync) < STATUS_RESYNC_WINDOW);
+                    if self.resyncs.len() == MAX_STATUS_RESYNCS {
+                        warn!(
+                            "Subscriber of request {} missed {missed} updates after {MAX_STATUS_RESYNCS} resyncs, closing it",
+                            self.request_id
+                        );
+                        return StatusFeedItem::TooSlow;
+                    }
+                    self.resyncs.push_back(now);
+                    return StatusFeedItem::Resync;
+                }
+                Err(RecvError::Closed) => return StatusFeedItem::Closed,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod status_feed_test {
+    use std::time::{Duration, Instant};
+
+    use tokio::sync::broadcast;
+
+    use crate::{
+        publish_status, subscribe_status_updates, BRequest, Chains, InputRequest, RequestUpdates,
+        StatusFeedItem, Timestamp, MAX_STATUS_RESYNCS,
+    };
+
+    fn request(token_id: &str) -> BRequest {
+        BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xcontract".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: String::new(),
+            },
+            Timestamp::now(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_publishing_never_waits_for_a_slow_subscriber() {
+        let mut slow = subscribe_status_updates();
+        let reader = tokio::spawn(async move {
+            while slow.recv().await.is_ok() {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+        let request = request("1");
+
+        let start = Instant::now();
+        for _ in 0..10_000 {
+            publish_status(&request);
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+        reader.abort();
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_resyncs_then_is_closed() {
+        let (feed, receiver) = broadcast::channel(4);
+        let (followed, other) = (request("1"), request("2"));
+        let mut updates = RequestUpdates::new(&followed.id, receiver);
+
+        feed.send(other.clone()).unwrap();
+        feed.send(followed.clone()).unwrap();
+        assert!(
+            matches!(updates.next().await, StatusFeedItem::Update(update) if update.id == followed.id)
+        );
+
+        for _ in 0..MAX_STATUS_RESYNCS {
+            for _ in 0..10 {
+                feed.send(other.clone()).unwrap();
+            }
+            assert!(matches!(updates.next().await, StatusFeedItem::Resync));
+        }
+        for _ in 0..10 {
+            feed.send(other.clone()).unwrap();
+        }
+        assert!(matches!(updates.next().await, StatusFeedItem::TooSlow));
+
+        let (_, receiver) = broadcast::channel::<BRequest>(4);
+        let mut closed = RequestUpdates::new(&followed.id, receiver);
+        assert!(matches!(closed.next().await, StatusFeedItem::Closed));
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{error, info};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+use crate::{Chains, Timestamp, TxReceiver};
+
+/// Longest wait of a tx processor for a message, it beats its heartbeat after each
+/// message and each wait
+pub const PROCESSOR_IDLE_TICK: Duration = Duration::from_secs(30);
+/// Longest wait before restarting a tx processor that panicked or stopped beating, the
+/// wait doubles from `Intervals::processor_restart_backoff`
+pub const MAX_PROCESSOR_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Liveness of a tx processor task: the time of its last heartbeat and the number of
+/// restarts by its supervisor
+#[derive(Debug, Clone, Default)]
+pub struct TaskHealth {
+    /// Unix millis, 0 until the first beat
+    last_beat: Arc<AtomicU64>,
+    restarts: Arc<AtomicU64>,
+}
+
+impl TaskHealth {
+    pub fn beat(&self, now: Timestamp) {
+        self.last_beat.store(now.as_millis(), Ordering::Relaxed);
+    }
+
+    pub fn last_beat(&self) -> Option<Timestamp> {
+        match self.last_beat.load(Ordering::Relaxed) {
+            0 => None,
+            millis => Some(Timestamp::from_millis(millis)),
+        }
+    }
+
+    pub fn restarts(&self) -> u64 {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    fn record_restart(&self) {
+        self.restarts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Started and silent for more than `max_silence`, stuck on a message
+    pub fn wedged(&self, now: Timestamp, max_silence: Duration) -> bool {
+        self.last_beat()
+            .is_some_and(|last| last.elapsed_until(now) > max_silence)
+    }
+}
+
+/// Health of the tx processors of both chains
+#[derive(Debug, Clone, Default)]
+pub struct ProcessorHealth {
+    evm: TaskHealth,
+    solana: TaskHealth,
+}
+
+impl ProcessorHealth {
+    pub fn of(&self, chain: &Chains) -> &TaskHealth {
+        match chain {
+            Chains::EVM => &self.evm,
+            Chains::SOLANA => &self.solana,
+        }
+    }
+}
+
+/// Runs the tx processor of `chain` and restarts it when it panics or stops beating for
+/// `wedged_after`, waiting `backoff` doubled on each restart. The receiver is kept here
+/// and handed to each instance, messages sent meanwhile wait in the channel. Returns
+/// when the processor returns, once the channel is closed
+pub async fn supervise_processor<F, Fut>(
+    chain: Chains,
+    rx: TxReceiver,
+    health: TaskHealth,
+    backoff: Duration,
+    wedged_after: Duration,
+    processor: F,
+) where
+    F: Fn(OwnedMutexGuard<TxReceiver>) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let rx = Arc::new(Mutex::new(rx));
+    let check_every = (wedged_after / 4).max(Duration::from_millis(10));
+    let mut delay = backoff;
+    loop {
+        // The previous instance dropped its guard when it stopped
+        let guard = rx.clone().lock_owned().await;
+        let started_at = Timestamp::now();
+        health.beat(started_at);
+        let mut handle = tokio::spawn(processor(guard));
+
+        let reason = loop {
+            tokio::select! {
+                joined = &mut handle => match joined {
+                    Ok(()) => {
+                        info!("{chain:?} tx processor stopped, its channel is closed");
+                        return;
+                    }
+                    Err(e) if e.is_panic() => break format!("panicked: {e}"),
+                    Err(e) => break format!("failed: {e}"),
+                },
+                _ = tokio::time::sleep(check_every) => {
+                    if health.wedged(Timestamp::now(), wedged_after) {
+                        handle.abort();
+                        _ = (&mut handle).await;
+                        break format!("silent for more than {} seconds", wedged_after.as_secs());
+                    }
+                }
+            }
+        };
+
+        // Instances that ran for a while start the backoff over
+        if started_at.elapsed_until(Timestamp::now()) > MAX_PROCESSOR_RESTART_BACKOFF {
+            delay = backoff;
+        }
+        health.record_restart();
+        error!(
+            "{chain:?} tx processor {reason}, restart {} in {} ms",
+            health.restarts(),
+            delay.as_millis()
+        );
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_PROCESSOR_RESTART_BACKOFF);
+    }
+}
+
+#[cfg(test)]
+mod supervisor_test {
+    use std::time::Duration;
+
+    use tokio::sync::{mpsc, OwnedMutexGuard};
+
+    use crate::{
+        supervise_processor, tx_channel, BRequest, Chains, ChannelMetrics, MessageMint, TaskHealth,
+        Timestamp, TxMessage, TxReceiver,
+    };
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    fn mint(token_id: &str) -> TxMessage {
+        TxMessage::Mint(MessageMint {
+            request_id: BRequest::generate_id("0xcontract", token_id, "0xowner"),
+            token_metadata: token_id.to_string(),
+            trace_context: None,
+        })
+    }
+
+    /// Processor forwarding the metadata of each mint, panicking on `panic` and hanging
+    /// without beating on `hang`
+    async fn mock_processor(
+        mut rx: OwnedMutexGuard<TxReceiver>,
+        health: TaskHealth,
+        processed: mpsc::UnboundedSender<String>,
+    ) {
+        loop {
+            health.beat(Timestamp::now());
+            let message = match tokio::time::timeout(Duration::from_millis(20), rx.recv()).await {
+                Ok(Some(message)) => message,
+                Ok(None) => return,
+                Err(_) => continue,
+            };
+            let TxMessage::Mint(mint) = message else {
+                continue;
+            };
+            match mint.token_metadata.as_str() {
+                "panic" => panic!("invalid u64"),
+                "hang" => std::future::pending::<()>().await,
+                metadata => processed.send(metadata.to_string()).unwrap(),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicked_processor_restarted() {
+        let (tx, rx) = tx_channel(Chains::EVM, 10, &ChannelMetrics::default());
+        let (processed_tx, mut processed) = mpsc::unbounded_channel();
+        let health = TaskHealth::default();
+        let supervisor = tokio::spawn(supervise_processor(
+            Chains::EVM,
+            rx,
+            health.clone(),
+            Duration::from_millis(10),
+            Duration::from_secs(60),
+            {
+                let health = health.clone();
+                move |rx| mock_processor(rx, health.clone(), processed_tx.clone())
+            },
+        ));
+
+        tx.send(mint("1")).await.unwrap();
+        tx.send(mint("panic")).await.unwrap();
+        tx.send(mint("2")).await.unwrap();
+        assert_eq!(
+            tokio::time::timeout(WAIT, processed.recv())
+                .await
+                .unwrap()
+                .unwrap(),
+            "1"
+        );
+        // Received by the restarted instance
+        assert_eq!(
+            tokio::time::timeout(WAIT, processed.recv())
+                .await
+                .unwrap()
+                .unwrap(),
+            "2"
+        );
+        assert_eq!(health.restarts(), 1);
+
+        tx.send(mint("panic")).await.unwrap();
+        tx.send(mint("3")).await.unwrap();
+        assert_eq!(
+            tokio::time::timeout(WAIT, processed.recv())
+                .await
+                .unwrap()
+                .unwrap(),
+            "3"
+        );
+        assert_eq!(health.restarts(), 2);
+
+        // Closing the channel stops the processor and its supervisor
+        drop(tx);
+        tokio::time::timeout(WAIT, supervisor)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wedged_processor_restarted() {
+        let (tx, rx) = tx_channel(Chains::SOLANA, 10, &ChannelMetrics::default());
+        let (processed_tx, mut processed) = mpsc::unbounded_channel();
+        let health = TaskHealth::default();
+        tokio::spawn(supervise_processor(
+            Chains::SOLANA,
+            rx,
+            health.clone(),
+            Duration::from_millis(10),
+            Duration::from_millis(200),
+            {
+                let health = health.clone();
+                move |rx| mock_processor(rx, health.clone(), processed_tx.clone())
+            },
+        ));
+
+        tx.send(mint("hang")).await.unwrap();
+        tx.send(mint("1")).await.unwrap();
+        assert_eq!(
+            tokio::time::timeout(WAIT, processed.recv())
+                .await
+                .unwrap()
+                .unwrap(),
+            "1"
+        );
+        assert_eq!(health.restarts(), 1);
+        let now = Timestamp::now();
+        assert!(!health.wedged(now, Duration::from_millis(200)));
+        assert!(health.wedged(
+            now.saturating_add(Duration::from_secs(1)),
+            Duration::from_millis(200)
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+use std::{error::Error, future::IntoFuture, time::Duration};
+
+use crate::{BridgeError, Result};
+
+pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_SEND_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Limits applied to outbound chain calls, cheap reads and transaction sends
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RpcTimeouts {
+    pub read: Duration,
+    pub send: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{operation} timed out after {after:?}")]
+pub struct TimeoutError {
+    pub operation: String,
+    pub after: Duration,
+}
+
+impl Default for RpcTimeouts {
+    fn default() -> Self {
+        RpcTimeouts {
+            read: DEFAULT_READ_TIMEOUT,
+            send: DEFAULT_SEND_TIMEOUT,
+        }
+    }
+}
+
+impl RpcTimeouts {
+    pub fn from_config(read_secs: Option<u64>, send_secs: Option<u64>) -> Self {
+        RpcTimeouts {
+            read: read_secs
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_READ_TIMEOUT),
+            send: send_secs
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_SEND_TIMEOUT),
+        }
+    }
+}
+
+/// Awaits the call, failing with a `TimeoutError` once `after` elapses
+pub async fn with_timeout<T, E, F>(operation: &str, after: Duration, call: F) -> Result<T>
+where
+    F: IntoFuture<Output = Result<T, E>>,
+    E: Error + Send + Sync + 'static,
+{
+    match tokio::time::timeout(after, call).await {
+        Ok(result) => result.map_err(BridgeError::from_error),
+        Err(_) => Err(TimeoutError {
+            operation: operation.to_string(),
+            after,
+        }
+        .into()),
+    }
+}
+
+/// Runs a blocking call on the blocking thread pool so it can't wedge the async runtime,
+/// the timeout is applied to the join handle
+pub async fn blocking_with_timeout<T, F>(operation: &str, after: Duration, call: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let handle = tokio::task::spawn_blocking(call);
+    match tokio::time::timeout(after, handle).await {
+        Ok(joined) => joined.map_err(BridgeError::from_error)?,
+        Err(_) => Err(TimeoutError {
+            operation: operation.to_string(),
+            after,
+        }
+        .into()),
+    }
+}
+
+/// Timeouts are transient, the call can be retried
+pub fn is_timeout(err: &BridgeError) -> bool {
+    matches!(err.root(), BridgeError::Timeout(_))
+}
+
+#[cfg(test)]
+mod timeout_test {
+    use crate::{blocking_with_timeout, is_timeout, with_timeout, BridgeError, RpcTimeouts};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_never_resolving_call_times_out() {
+        let result = with_timeout(
+            "get_transaction_count",
+            Duration::from_millis(20),
+            std::future::pending::<Result<u64, BridgeError>>(),
+        )
+        .await;
+
+        let err = result.unwrap_err();
+        assert!(is_timeout(&err));
+        assert!(err.to_string().contains("get_transaction_count"));
+
+        // The caller continues with the next call
+        let result = with_timeout("get_block_number", Duration::from_millis(20), async {
+            Ok::<u64, BridgeError>(7)
+        })
+        .await;
+        assert_eq!(result.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_blocking_call_times_out() {
+        let result = blocking_with_timeout("get_account_data", Duration::from_millis(20), || {
+            std::thread::sleep(Duration::from_millis(500));
+            Ok(())
+        })
+        .await;
+        assert!(is_timeout(&result.unwrap_err()));
+
+        let result = blocking_with_timeout("get_slot", Duration::from_secs(1), || Ok(42u64)).await;
+        assert_eq!(result.unwrap(), 42);
+
+        // Errors are not timeouts
+        let result: Result<(), BridgeError> =
+            blocking_with_timeout("get_slot", Duration::from_secs(1), || {
+                Err(BridgeError::Other("rpc error".to_string()))
+            })
+            .await;
+        assert!(!is_timeout(&result.unwrap_err()));
+    }
+
+    #[test]
+    fn test_rpc_timeouts_from_config() {
+        let timeouts = RpcTimeouts::from_config(Some(3), None);
+        assert_eq!(timeouts.read, Duration::from_secs(3));
+        assert_eq!(timeouts.send, RpcTimeouts::default().send);
+    }
+}
@@ -0,0 +1,88 @@
+use alloy::primitives::U256;
+
+use crate::BridgeError;
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TokenIdError {
+    #[error("empty token id")]
+    Empty,
+
+    #[error("invalid token id {0:?}, expected a decimal number or 0x-prefixed hex")]
+    Invalid(String),
+
+    #[error("token id {0} doesn't fit in 256 bits")]
+    Overflow(String),
+}
+
+/// A stored token id that doesn't parse fails on every retry
+impl From<TokenIdError> for BridgeError {
+    fn from(err: TokenIdError) -> Self {
+        BridgeError::CorruptedData(err.to_string())
+    }
+}
+
+/// EVM token id written in decimal or in hex with a `0x` prefix, as explorers and
+/// marketplaces show them. Surrounding spaces are ignored. Signs, separators, the other
+/// prefixes parsed by `U256::from_str` (`0b`, `0o`) and hex digits without the prefix
+/// are refused, `10` is never read as hex
+pub fn parse_token_id(value: &str) -> Result<U256, TokenIdError> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(TokenIdError::Empty);
+    }
+    let (digits, radix) = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) if !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()) => (hex, 16),
+        None if value.chars().all(|c| c.is_ascii_digit()) => (value, 10),
+        _ => return Err(TokenIdError::Invalid(value.to_string())),
+    };
+    // The digits are valid, the only failure left is the size
+    U256::from_str_radix(digits, radix).map_err(|_| TokenIdError::Overflow(value.to_string()))
+}
+
+/// Canonical decimal form of an EVM token id, the form requests are stored and hashed
+/// with so the same token always gets the same request id
+pub fn normalize_token_id(value: &str) -> Result<String, TokenIdError> {
+    parse_token_id(value).map(|id| id.to_string())
+}
+
+#[cfg(test)]
+mod token_id_test {
+    use alloy::primitives::U256;
+
+    use crate::{normalize_token_id, parse_token_id, TokenIdError};
+
+    #[test]
+    fn test_hex_and_decimal_token_ids() {
+        for value in ["420", "0x1a4", "0X1A4", " 0x01a4 ", "000420"] {
+            assert_eq!(parse_token_id(value), Ok(U256::from(420)), "{value}");
+            assert_eq!(normalize_token_id(value).unwrap(), "420");
+        }
+        assert_eq!(parse_token_id("0"), Ok(U256::ZERO));
+        assert_eq!(
+            normalize_token_id(&format!("0x{}", "f".repeat(64))).unwrap(),
+            U256::MAX.to_string()
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_token_ids_refused() {
+        assert_eq!(parse_token_id("  "), Err(TokenIdError::Empty));
+        for value in [
+            "0x", "1a4", "-1", "+1", "1_000", "1 000", "0b101", "0o17", "0x1g",
+        ] {
+            assert_eq!(
+                parse_token_id(value),
+                Err(TokenIdError::Invalid(value.to_string())),
+                "{value}"
+            );
+        }
+        let too_big = format!("0x1{}", "0".repeat(64));
+        assert_eq!(
+            parse_token_id(&too_big),
+            Err(TokenIdError::Overflow(too_big.clone()))
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use opentelemetry::{
+    propagation::TextMapPropagator,
+    trace::{TraceContextExt, TraceId},
+    Context,
+};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use serde::{Deserialize, Serialize};
+use storage::db::Database;
+use tracing::{info_span, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::{request_data, BRequest, Chains, RequestId, TxMessage};
+
+/// W3C trace context (`traceparent` header) of a span. Carried by the tx messages across
+/// the channel hop and stored on the request at creation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct TraceContext(HashMap<String, String>);
+
+impl TraceContext {
+    /// Context of the current span, None when traces are not exported
+    pub fn current() -> Option<Self> {
+        let context = Span::current().context();
+        if !context.span().span_context().is_valid() {
+            return None;
+        }
+        let mut carrier = HashMap::new();
+        TraceContextPropagator::new().inject_context(&context, &mut carrier);
+        Some(TraceContext(carrier))
+    }
+
+    fn context(&self) -> Context {
+        TraceContextPropagator::new().extract(&self.0)
+    }
+
+    pub fn trace_id(&self) -> TraceId {
+        self.context().span().span_context().trace_id()
+    }
+
+    /// Makes `span` a child of the span of this context
+    pub fn attach(&self, span: &Span) {
+        span.set_parent(self.context());
+    }
+
+    /// Links `span` to the span of this context, for spans of another trace
+    pub fn link(&self, span: &Span) {
+        span.add_link(self.context().span().span_context().clone());
+    }
+}
+
+impl TxMessage {
+    /// Span of the processing of the message, child of the span that sent it
+    pub fn span(&self, chain: &Chains) -> Span {
+        let function = self.function();
+        let span = info_span!(
+            "tx_message",
+            otel.name = ?function,
+            chain = ?chain,
+            request_id = %self.request_id()
+        );
+        if let Some(context) = self.trace_context() {
+            context.attach(&span);
+        }
+        span
+    }
+}
+
+/// Span of a chain event of `request_id`, linked to the trace of the request creation.
+/// Events are polled, they start a new trace
+pub fn event_span(chain: &Chains, event: &str, request_id: &RequestId, db: &Database) -> Span {
+    let span = info_span!(
+        "bridge_event",
+        otel.name = event,
+        chain = ?chain,
+        request_id = %request_id
+    );
+    if let Ok(Some(BRequest {
+        trace_context: Some(context),
+        ..
+    })) = request_data(request_id, db)
+    {
+        context.link(&span);
+    }
+    span
+}
+
+#[cfg(test)]
+mod trace_context_test {
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+    use storage::testing::each_engine;
+    use tracing::info_span;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::{
+        event_span, tx_channel, BRequest, Chains, ChannelMetrics, InputRequest, MessageMint,
+        Timestamp, TraceContext, TxMessage,
+    };
+
+    fn input() -> InputRequest {
+        InputRequest {
+            contract_or_mint: "0x5fbdb2315678afecb367f032d93f642f64180aa3".to_string(),
+            token_id: "1".to_string(),
+            token_owner: "0xowner".to_string(),
+            origin_network: Chains::EVM,
+            destination_account: "destination".to_string(),
+        }
+    }
+
+    fn finished<'a>(spans: &'a [SpanData], name: &str) -> &'a SpanData {
+        spans.iter().find(|span| span.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_no_context_without_exporter() {
+        let _span = info_span!("new_request").entered();
+        assert_eq!(TraceContext::current(), None);
+        assert_eq!(BRequest::new(input(), Timestamp::now()).trace_context, None);
+    }
+
+    #[tokio::test]
+    async fn test_mint_span_is_child_of_creation_across_channel() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        for db in each_engine() {
+            let request =
+                info_span!("new_request").in_scope(|| BRequest::new(input(), Timestamp::now()));
+            request.save(&db).unwrap();
+            let creation_context = request.trace_context.clone().unwrap();
+
+            let (tx, mut rx) = tx_channel(Chains::SOLANA, 1, &ChannelMetrics::default());
+            tx.send(TxMessage::Mint(MessageMint {
+                request_id: request.id.clone(),
+                token_metadata: "https://example.com/1.json".to_string(),
+                trace_context: request.trace_context.clone(),
+            }))
+            .await
+            .unwrap();
+            let message = rx.recv().await.unwrap();
+            drop(message.span(&Chains::SOLANA));
+            drop(event_span(&Chains::EVM, "NewRequest", &request.id, &db));
+
+            provider.force_flush().unwrap();
+            let spans = exporter.get_finished_spans().unwrap();
+            let creation = finished(&spans, "new_request");
+            let mint = finished(&spans, "Mint");
+            let event = finished(&spans, "NewRequest");
+
+            assert_eq!(
+                creation_context.trace_id(),
+                creation.span_context.trace_id()
+            );
+            assert_eq!(
+                mint.span_context.trace_id(),
+                creation.span_context.trace_id()
+            );
+            assert_eq!(mint.parent_span_id, creation.span_context.span_id());
+            // The event starts its own trace, linked to the creation
+            assert_ne!(
+                event.span_context.trace_id(),
+                creation.span_context.trace_id()
+            );
+            assert_eq!(event.links.links.len(), 1);
+            assert_eq!(
+                event.links.links[0].span_context.span_id(),
+                creation.span_context.span_id()
+            );
+        }
+    }
+}
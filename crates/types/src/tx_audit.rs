@@ -0,0 +1,417 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use storage::{
+    db::Database,
+    keys::{TX_AUDIT_REPORT, TX_VERIFIED_PREFIX},
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    canonical_tx_hash, request_data, requests_by_status, tx_hash_chain, BRequest, Chains,
+    RequestId, Result, Status, Timestamp,
+};
+
+/// Requests finalized this long ago are audited when not configured, two nightly runs
+pub const DEFAULT_TX_AUDIT_LOOKBACK: Duration = Duration::from_secs(2 * 24 * 3600);
+
+/// A recorded transaction as its chain knows it
+#[derive(Debug, Clone, PartialEq)]
+pub enum OnChainTx {
+    /// Unknown to the node, or not confirmed
+    Missing,
+    /// Mined or confirmed, with the accounts it called: the `to` of an EVM transaction,
+    /// the programs invoked by a Solana one
+    Found { success: bool, called: Vec<String> },
+}
+
+pub type TxReadFuture<'a> = Pin<Box<dyn Future<Output = Result<OnChainTx>> + Send + 'a>>;
+
+/// Chain access of the tx audit
+pub trait TxReader: Send + Sync {
+    /// Reads the transaction `tx_hash` of `chain`
+    fn read_tx<'a>(&'a self, chain: &'a Chains, tx_hash: &'a str) -> TxReadFuture<'a>;
+    /// Bridge contract or program called on `chain` by the requests without recorded
+    /// bridge addresses
+    fn bridge(&self, chain: &Chains) -> String;
+}
+
+/// What is wrong with a recorded tx hash
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TxDiscrepancyKind {
+    /// Not found or not confirmed on its chain
+    Missing,
+    /// Mined but failed
+    Failed,
+    /// Calls neither the bridge contract nor the bridge program
+    UnexpectedTarget { called: Vec<String> },
+}
+
+impl TxDiscrepancyKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TxDiscrepancyKind::Missing => "missing",
+            TxDiscrepancyKind::Failed => "failed",
+            TxDiscrepancyKind::UnexpectedTarget { .. } => "unexpected_target",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TxDiscrepancy {
+    pub request_id: RequestId,
+    pub chain: Chains,
+    pub tx_hash: String,
+    #[serde(flatten)]
+    pub kind: TxDiscrepancyKind,
+}
+
+/// Outcome of a tx audit run, the last one is kept under `TX_AUDIT_REPORT`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TxAuditReport {
+    pub ran_at: Timestamp,
+    /// Requests finalized from then on were audited
+    pub since: Timestamp,
+    pub requests: usize,
+    /// Found on their chain by this run
+    pub verified: usize,
+    /// Found by an earlier run, not read again
+    pub already_verified: usize,
+    /// The node could not be read, audited again by the next run
+    pub unreadable: usize,
+    /// Hashes of neither format, like the ids of the mock chain layer
+    pub skipped: usize,
+    pub discrepancies: Vec<TxDiscrepancy>,
+}
+
+/// Audit of the tx hashes recorded on the finalized requests. A single run at a time, the
+/// counters of its findings are shared by the clones
+#[derive(Debug, Clone)]
+pub struct TxAudit {
+    pub lookback: Duration,
+    running: Arc<Mutex<()>>,
+    verified: Arc<AtomicU64>,
+    missing: Arc<AtomicU64>,
+    failed: Arc<AtomicU64>,
+    unexpected_target: Arc<AtomicU64>,
+}
+
+impl Default for TxAudit {
+    fn default() -> Self {
+        TxAudit::new(DEFAULT_TX_AUDIT_LOOKBACK)
+    }
+}
+
+fn tx_verified_key(chain: &Chains, tx_hash: &str) -> String {
+    format!(
+        "{TX_VERIFIED_PREFIX}{chain:?}:{}",
+        canonical_tx_hash(chain, tx_hash)
+    )
+}
+
+/// Report of the last tx audit run, None before the first one
+pub fn last_tx_audit_report(db: &Database) -> Result<Option<TxAuditReport>> {
+    Ok(db.read(TX_AUDIT_REPORT)?)
+}
+
+impl TxAudit {
+    pub fn new(lookback: Duration) -> Self {
+        TxAudit {
+            lookback,
+            running: Arc::default(),
+            verified: Arc::default(),
+            missing: Arc::default(),
+            failed: Arc::default(),
+            unexpected_target: Arc::default(),
+        }
+    }
+
+    /// Hashes found on their chain since the start
+    pub fn verified_count(&self) -> u64 {
+        self.verified.load(Ordering::Relaxed)
+    }
+
+    /// Discrepancies found since the start by kind name, a hash still wrong is counted
+    /// again by each run
+    pub fn discrepancy_counts(&self) -> [(&'static str, u64); 3] {
+        [
+            ("missing", self.missing.load(Ordering::Relaxed)),
+            ("failed", self.failed.load(Ordering::Relaxed)),
+            (
+                "unexpected_target",
+                self.unexpected_target.load(Ordering::Relaxed),
+            ),
+        ]
+    }
+
+    fn count_discrepancy(&self, kind: &TxDiscrepancyKind) {
+        let counter = match kind {
+            TxDiscrepancyKind::Missing => &self.missing,
+            TxDiscrepancyKind::Failed => &self.failed,
+            TxDiscrepancyKind::UnexpectedTarget { .. } => &self.unexpected_target,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Reads from their chain the tx hashes of the requests Completed or Refunded within
+    /// the lookback. Hashes found calling the bridge are marked verified and not read
+    /// again, the others are reported. The report is stored and returned, None when
+    /// another run is in progress
+    pub async fn run(
+        &self,
+        db: &Database,
+        reader: &dyn TxReader,
+        now: Timestamp,
+    ) -> Result<Option<TxAuditReport>> {
+        let Ok(_running) = self.running.try_lock() else {
+            return Ok(None);
+        };
+        let since = now.saturating_sub(self.lookback);
+        let mut report = TxAuditReport {
+            ran_at: now,
+            since,
+            ..Default::default()
+        };
+
+        for status in [Status::Completed, Status::Refunded] {
+            for (id, last_update) in requests_by_status(db, &status, None)? {
+                // Finalized at the latest on their last update
+                if last_update < since.as_secs() {
+                    continue;
+                }
+                let Some(request) = request_data(&id, db)? else {
+                    continue;
+                };
+                if !request.finalized_at.is_some_and(|at| at >= since) {
+                    continue;
+                }
+                report.requests += 1;
+                self.audit_request(db, reader, &request, now, &mut report)
+                    .await?;
+            }
+        }
+
+        db.write_value(TX_AUDIT_REPORT, &report)?;
+        info!(
+            "Tx audit of {} requests: {} hashes verified, {} discrepancies",
+            report.requests,
+            report.verified,
+            report.discrepancies.len()
+        );
+        Ok(Some(report))
+    }
+
+    async fn audit_request(
+        &self,
+        db: &Database,
+        reader: &dyn TxReader,
+        request: &BRequest,
+        now: Timestamp,
+        report: &mut TxAuditReport,
+    ) -> Result<()> {
+        for tx_hash in &request.tx_hashes {
+            let Some(chain) = tx_hash_chain(tx_hash) else {
+                report.skipped += 1;
+                continue;
+            };
+            let verified_key = tx_verified_key(&chain, tx_hash);
+            if db.read::<_, Timestamp>(&verified_key)?.is_some() {
+                report.already_verified += 1;
+                continue;
+            }
+
+            let kind = match reader.read_tx(&chain, tx_hash).await {
+                Ok(OnChainTx::Missing) => TxDiscrepancyKind::Missing,
+                Ok(OnChainTx::Found { success: false, .. }) => TxDiscrepancyKind::Failed,
+                Ok(OnChainTx::Found { called, .. }) => {
+                    // Requests created before an upgrade call the bridge they recorded
+                    let bridge = match &request.bridge_addresses {
+                        Some(addresses) => addresses.bridge_of(&chain).to_string(),
+                        None => reader.bridge(&chain),
+                    };
+                    let calls_bridge = called.iter().any(|account| match chain {
+                        Chains::EVM => account.eq_ignore_ascii_case(&bridge),
+                        Chains::SOLANA => *account == bridge,
+                    });
+                    if calls_bridge {
+                        db.write_value(&verified_key, &now)?;
+                        self.verified.fetch_add(1, Ordering::Relaxed);
+                        report.verified += 1;
+                        continue;
+                    }
+                    TxDiscrepancyKind::UnexpectedTarget { called }
+                }
+                Err(e) => {
+                    warn!("Tx audit could not read {chain:?} tx {tx_hash}: {e}");
+                    report.unreadable += 1;
+                    continue;
+                }
+            };
+
+            warn!(
+                "Tx {tx_hash} recorded on request {} is {}",
+                request.id,
+                kind.name()
+            );
+            self.count_discrepancy(&kind);
+            report.discrepancies.push(TxDiscrepancy {
+                request_id: request.id.clone(),
+                chain,
+                tx_hash: tx_hash.clone(),
+                kind,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tx_audit_test {
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use storage::testing::each_engine;
+
+    use crate::{
+        last_tx_audit_report, BRequest, BridgeError, Chains, InputRequest, OnChainTx, Status,
+        Timestamp, TxAudit, TxDiscrepancyKind, TxReadFuture, TxReader,
+    };
+
+    const BRIDGE: &str = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+    const OTHER_CONTRACT: &str = "0xe7f1725e7734ce288f8367e1bb143e90bb3f0512";
+
+    /// Chain with the canned transactions, counting the reads
+    #[derive(Default)]
+    struct MockReader {
+        txs: HashMap<String, OnChainTx>,
+        reads: Arc<AtomicUsize>,
+    }
+
+    impl TxReader for MockReader {
+        fn read_tx<'a>(&'a self, _chain: &'a Chains, tx_hash: &'a str) -> TxReadFuture<'a> {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            let tx = self.txs.get(tx_hash).cloned();
+            Box::pin(async move {
+                tx.ok_or_else(|| BridgeError::Other("connection refused".to_string()))
+            })
+        }
+
+        fn bridge(&self, _chain: &Chains) -> String {
+            BRIDGE.to_string()
+        }
+    }
+
+    fn tx_hash(byte: u8) -> String {
+        format!("0x{}", format!("{byte:02x}").repeat(32))
+    }
+
+    fn finalized_request(db: &storage::db::Database, token_id: &str, txs: &[String]) -> BRequest {
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xabc123".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.tx_hashes = txs.to_vec();
+        request.status = Status::Completed;
+        request.finalized_at = Some(request.last_update);
+        request.save(db).unwrap();
+        request
+    }
+
+    #[tokio::test]
+    async fn test_recorded_txs_checked_on_chain() {
+        for db in each_engine() {
+            let (good, missing, unrelated) = (tx_hash(1), tx_hash(2), tx_hash(3));
+            let reader = MockReader {
+                txs: HashMap::from([
+                    (
+                        good.clone(),
+                        OnChainTx::Found {
+                            success: true,
+                            // Addresses compare case insensitive
+                            called: vec![BRIDGE.to_lowercase()],
+                        },
+                    ),
+                    (missing.clone(), OnChainTx::Missing),
+                    (
+                        unrelated.clone(),
+                        OnChainTx::Found {
+                            success: true,
+                            called: vec![OTHER_CONTRACT.to_string()],
+                        },
+                    ),
+                ]),
+                ..Default::default()
+            };
+            let request = finalized_request(
+                &db,
+                "1",
+                &[
+                    good.clone(),
+                    missing.clone(),
+                    unrelated.clone(),
+                    "mock-tx".to_string(),
+                ],
+            );
+            // The node fails on this one
+            finalized_request(&db, "2", &[tx_hash(4)]);
+
+            let audit = TxAudit::new(Duration::from_secs(3600));
+            let now = Timestamp::now();
+            let report = audit.run(&db, &reader, now).await.unwrap().unwrap();
+            assert_eq!(report.requests, 2);
+            assert_eq!(report.verified, 1);
+            assert_eq!((report.skipped, report.unreadable), (1, 1));
+            assert_eq!(report.discrepancies.len(), 2);
+            assert_eq!(report.discrepancies[0].request_id, request.id);
+            assert_eq!(report.discrepancies[0].tx_hash, missing);
+            assert_eq!(report.discrepancies[0].kind, TxDiscrepancyKind::Missing);
+            assert_eq!(report.discrepancies[1].tx_hash, unrelated);
+            assert_eq!(
+                report.discrepancies[1].kind,
+                TxDiscrepancyKind::UnexpectedTarget {
+                    called: vec![OTHER_CONTRACT.to_string()]
+                }
+            );
+            assert_eq!(last_tx_audit_report(&db).unwrap(), Some(report));
+            assert_eq!(audit.verified_count(), 1);
+            assert_eq!(
+                audit.discrepancy_counts(),
+                [("missing", 1), ("failed", 0), ("unexpected_target", 1)]
+            );
+
+            // The verified hash is not read again, the others are
+            let reads = reader.reads.load(Ordering::Relaxed);
+            let report = audit.run(&db, &reader, now).await.unwrap().unwrap();
+            assert_eq!(report.already_verified, 1);
+            assert_eq!(report.discrepancies.len(), 2);
+            assert_eq!(reader.reads.load(Ordering::Relaxed) - reads, 3);
+
+            // Requests finalized before the lookback are left out
+            let later = now.saturating_add(Duration::from_secs(7200));
+            let report = audit.run(&db, &reader, later).await.unwrap().unwrap();
+            assert_eq!(report.requests, 0);
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::mpsc::{self, error::SendError};
+
+use crate::{Chains, Function, TxMessage};
+
+/// Latency samples kept per chain and function, the oldest are discarded first
+pub const LATENCY_SAMPLES: usize = 1024;
+
+#[derive(Debug, Default)]
+struct LatencySamples {
+    samples: VecDeque<Duration>,
+}
+
+impl LatencySamples {
+    fn record(&mut self, latency: Duration) {
+        if self.samples.len() == LATENCY_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(latency);
+    }
+
+    fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort();
+        let index = ((sorted.len() - 1) as f64 * percentile).round() as usize;
+        Some(sorted[index])
+    }
+}
+
+#[derive(Debug, Default)]
+struct MessageLatencies {
+    /// Send to recv
+    wait: LatencySamples,
+    /// Recv to transaction confirmed
+    processing: LatencySamples,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyStats {
+    pub chain: String,
+    pub function: String,
+    pub messages: usize,
+    pub wait_p50: Option<Duration>,
+    pub wait_p95: Option<Duration>,
+    pub processing_p50: Option<Duration>,
+    pub processing_p95: Option<Duration>,
+}
+
+/// Depth and latencies of the tx message channels, tagged by the chain processing the
+/// message and its function
+#[derive(Debug, Clone, Default)]
+pub struct ChannelMetrics {
+    latencies: Arc<Mutex<HashMap<(String, String), MessageLatencies>>>,
+    depths: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl ChannelMetrics {
+    pub fn record_wait(&self, chain: &Chains, function: &Function, wait: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        latencies
+            .entry(latency_key(chain, function))
+            .or_default()
+            .wait
+            .record(wait);
+    }
+
+    pub fn record_processing(&self, chain: &Chains, function: &Function, processing: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        latencies
+            .entry(latency_key(chain, function))
+            .or_default()
+            .processing
+            .record(processing);
+    }
+
+    /// Messages sent and not received yet
+    pub fn depth(&self, chain: &Chains) -> usize {
+        let depths = self.depths.lock().unwrap();
+        depths.get(&format!("{:?}", chain)).copied().unwrap_or(0)
+    }
+
+    pub fn wait_p95(&self, chain: &Chains, function: &Function) -> Option<Duration> {
+        let latencies = self.latencies.lock().unwrap();
+        latencies
+            .get(&latency_key(chain, function))
+            .and_then(|latencies| latencies.wait.percentile(0.95))
+    }
+
+    pub fn latency_stats(&self) -> Vec<LatencyStats> {
+        let latencies = self.latencies.lock().unwrap();
+        let mut stats: Vec<LatencyStats> = latencies
+            .iter()
+            .map(|((chain, function), latencies)| LatencyStats {
+                chain: chain.clone(),
+                function: function.clone(),
+                messages: latencies.wait.samples.len(),
+                wait_p50: latencies.wait.percentile(0.5),
+                wait_p95: latencies.wait.percentile(0.95),
+                processing_p50: latencies.processing.percentile(0.5),
+                processing_p95: latencies.processing.percentile(0.95),
+            })
+            .collect();
+        stats.sort_by(|a, b| (&a.chain, &a.function).cmp(&(&b.chain, &b.function)));
+        stats
+    }
+
+    fn increase_depth(&self, chain: &Chains) {
+        let mut depths = self.depths.lock().unwrap();
+        *depths.entry(format!("{:?}", chain)).or_default() += 1;
+    }
+
+    fn decrease_depth(&self, chain: &Chains) {
+        let mut depths = self.depths.lock().unwrap();
+        let depth = depths.entry(format!("{:?}", chain)).or_default();
+        *depth = depth.saturating_sub(1);
+    }
+}
+
+fn latency_key(chain: &Chains, function: &Function) -> (String, String) {
+    (format!("{:?}", chain), format!("{:?}", function))
+}
+
+/// Message in the channel with the time it was sent
+#[derive(Debug)]
+struct Queued {
+    message: TxMessage,
+    enqueued_at: Instant,
+}
+
+/// Sending side of a tx message channel, stamps the enqueue time of every message
+#[derive(Debug, Clone)]
+pub struct TxSender {
+    chain: Chains,
+    inner: mpsc::Sender<Queued>,
+    priority: mpsc::Sender<Queued>,
+    metrics: ChannelMetrics,
+}
+
+impl TxSender {
+    pub async fn send(&self, message: TxMessage) -> Result<(), SendError<TxMessage>> {
+        self.send_to(&self.inner, message).await
+    }
+
+    /// Sends on the priority lane, received before every message of the regular one
+    pub async fn send_priority(&self, message: TxMessage) -> Result<(), SendError<TxMessage>> {
+        self.send_to(&self.priority, message).await
+    }
+
+    async fn send_to(
+        &self,
+        lane: &mpsc::Sender<Queued>,
+        message: TxMessage,
+    ) -> Result<(), SendError<TxMessage>> {
+        self.metrics.increase_depth(&self.chain);
+        let queued = Queued {
+            message,
+            enqueued_at: Instant::now(),
+        };
+        lane.send(queued).await.map_err(|SendError(queued)| {
+            self.metrics.decrease_depth(&self.chain);
+            SendError(queued.message)
+        })
+    }
+}
+
+/// Receiving side of a tx message channel, records the queue wait of every message
+#[derive(Debug)]
+pub struct TxReceiver {
+    chain: Chains,
+    inner: mpsc::Receiver<Queued>,
+    priority: mpsc::Receiver<Queued>,
+    metrics: ChannelMetrics,
+}
+
+impl TxReceiver {
+    /// Next message, the priority lane first
+    pub async fn recv(&mut self) -> Option<TxMessage> {
+        // Both lanes close together, when their senders are dropped
+        let Queued {
+            message,
+            enqueued_at,
+        } = tokio::select! {
+            biased;
+            Some(queued) = self.priority.recv() => queued,
+            queued = self.inner.recv() => queued?,
+        };
+        self.metrics.decrease_depth(&self.chain);
+        self.metrics
+            .record_wait(&self.chain, &message.function(), enqueued_at.elapsed());
+        Some(message)
+    }
+
+    /// Records the time since the message was received until its transaction finished
+    pub fn record_processing(&self, function: &Function, received_at: Instant) {
+        self.metrics
+            .record_processing(&self.chain, function, received_at.elapsed());
+    }
+}
+
+/// Tx message channel of the `chain` processor
+pub fn tx_channel(
+    chain: Chains,
+    buffer: usize,
+    metrics: &ChannelMetrics,
+) -> (TxSender, TxReceiver) {
+    let (tx, rx) = mpsc::channel(buffer);
+    let (priority_tx, priority_rx) = mpsc::channel(buffer);
+    (
+        TxSender {
+            chain: chain.clone(),
+            inner: tx,
+            priority: priority_tx,
+            metrics: metrics.clone(),
+        },
+        TxReceiver {
+            chain,
+            inner: rx,
+            priority: priority_rx,
+            metrics: metrics.clone(),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tx_channel_test {
+    use std::time::{Duration, Instant};
+
+    use crate::{tx_channel, BRequest, Chains, ChannelMetrics, Function, MessageMint, TxMessage};
+
+    fn mint_message(token_id: &str) -> TxMessage {
+        TxMessage::Mint(MessageMint {
+            request_id: BRequest::generate_id("0xcontract", token_id, "0xowner"),
+            token_metadata: "https://example.com/1.json".to_string(),
+            trace_context: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_channel_records_waits_and_processing() {
+        let metrics = ChannelMetrics::default();
+        let (tx, mut rx) = tx_channel(Chains::EVM, 10, &metrics);
+
+        for id in ["1", "2", "3"] {
+            tx.send(mint_message(id)).await.unwrap();
+        }
+        assert_eq!(metrics.depth(&Chains::EVM), 3);
+        assert_eq!(metrics.depth(&Chains::SOLANA), 0);
+
+        // Messages wait in the queue before the processor starts
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Mock processor taking 10ms per message
+        for _ in 0..3 {
+            let message = rx.recv().await.unwrap();
+            let received_at = Instant::now();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            rx.record_processing(&message.function(), received_at);
+        }
+        assert_eq!(metrics.depth(&Chains::EVM), 0);
+
+        let stats = metrics.latency_stats();
+        assert_eq!(stats.len(), 1);
+        let stats = &stats[0];
+        assert_eq!(
+            (stats.chain.as_str(), stats.function.as_str()),
+            ("EVM", "Mint")
+        );
+        assert_eq!(stats.messages, 3);
+
+        let wait_p50 = stats.wait_p50.unwrap();
+        let wait_p95 = stats.wait_p95.unwrap();
+        assert!(wait_p50 >= Duration::from_millis(50) && wait_p50 < Duration::from_secs(5));
+        // Later messages waited for the earlier ones to be processed
+        assert!(wait_p95 >= wait_p50);
+        assert_eq!(
+            metrics.wait_p95(&Chains::EVM, &Function::Mint),
+            Some(wait_p95)
+        );
+
+        let processing_p50 = stats.processing_p50.unwrap();
+        assert!(
+            processing_p50 >= Duration::from_millis(10) && processing_p50 < Duration::from_secs(5)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_priority_messages_received_first() {
+        let metrics = ChannelMetrics::default();
+        let (tx, mut rx) = tx_channel(Chains::SOLANA, 10, &metrics);
+        let (first, second, urgent) = (mint_message("1"), mint_message("2"), mint_message("3"));
+
+        tx.send(first.clone()).await.unwrap();
+        tx.send(second.clone()).await.unwrap();
+        tx.send_priority(urgent.clone()).await.unwrap();
+        assert_eq!(metrics.depth(&Chains::SOLANA), 3);
+
+        assert_eq!(rx.recv().await.unwrap(), urgent);
+        assert_eq!(rx.recv().await.unwrap(), first);
+        assert_eq!(rx.recv().await.unwrap(), second);
+        assert_eq!(metrics.depth(&Chains::SOLANA), 0);
+
+        drop(tx);
+        assert_eq!(rx.recv().await, None);
+    }
+}
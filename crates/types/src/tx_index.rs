@@ -0,0 +1,180 @@
+use log::info;
+use storage::{
+    db::{Batch, Database},
+    keys::{REQUEST_KEY_PREFIX, TX_INDEX_PREFIX},
+};
+
+use crate::{valid_explorer_value, BRequest, Chains, ExplorerItem, RequestId, Result};
+
+/// Chain of a transaction hash told by its format: `0x` and 64 hex digits for EVM, a
+/// base58 signature for Solana. None for anything else
+pub fn tx_hash_chain(tx_hash: &str) -> Option<Chains> {
+    [Chains::EVM, Chains::SOLANA]
+        .into_iter()
+        .find(|chain| valid_explorer_value(chain, ExplorerItem::Tx, tx_hash))
+}
+
+/// Hash in the form it is keyed with. EVM hashes are case insensitive, Solana signatures
+/// are kept as they are
+pub(crate) fn canonical_tx_hash(chain: &Chains, tx_hash: &str) -> String {
+    match chain {
+        Chains::EVM => tx_hash.to_ascii_lowercase(),
+        Chains::SOLANA => tx_hash.to_string(),
+    }
+}
+
+/// Index key of a hash recorded by a request
+fn tx_index_key(chain: &Chains, tx_hash: &str, request_id: &str) -> String {
+    format!(
+        "{TX_INDEX_PREFIX}{}:{request_id}",
+        canonical_tx_hash(chain, tx_hash)
+    )
+}
+
+/// Adds the index entries of the tx hashes of a request to the batch saving it. Hashes of
+/// neither format, like the ids of the mock chain layer, are not indexed
+pub(crate) fn update_tx_index(request: &BRequest, batch: &mut Batch) -> Result<()> {
+    for tx_hash in request.tx_hashes.iter() {
+        if let Some(chain) = tx_hash_chain(tx_hash) {
+            batch.put(tx_index_key(&chain, tx_hash, &request.id), &request.id)?;
+        }
+    }
+    Ok(())
+}
+
+/// Requests that recorded `tx_hash`, a single one unless the index was corrupted. Empty
+/// for unknown hashes and hashes of neither format
+pub fn requests_by_tx(db: &Database, tx_hash: &str) -> Result<Vec<RequestId>> {
+    let Some(chain) = tx_hash_chain(tx_hash) else {
+        return Ok(vec![]);
+    };
+    // The request id closes the prefix, a hash is never the prefix of another one
+    let prefix = tx_index_key(&chain, tx_hash, "");
+    Ok(db
+        .scan_prefix::<RequestId>(&prefix, None)?
+        .into_iter()
+        .map(|(_, request_id)| request_id)
+        .collect())
+}
+
+/// Drops the tx hash index and writes it again from every request, covering the hashes
+/// recorded before the index existed. Returns the number of requests indexed
+pub fn rebuild_tx_index(db: &Database) -> Result<usize> {
+    let mut batch = Batch::default();
+    for (key, _) in db.scan_prefix::<RequestId>(TX_INDEX_PREFIX, None)? {
+        batch.delete(key);
+    }
+
+    let mut rebuilt = 0;
+    for (_, request) in db.scan_prefix::<BRequest>(REQUEST_KEY_PREFIX, None)? {
+        if !request.tx_hashes.is_empty() {
+            update_tx_index(&request, &mut batch)?;
+            rebuilt += 1;
+        }
+    }
+    db.write_batch(batch)?;
+
+    info!("Rebuilt the tx hash index for {rebuilt} requests");
+    Ok(rebuilt)
+}
+
+#[cfg(test)]
+mod tx_index_test {
+    use storage::{keys::TX_INDEX_PREFIX, testing::each_engine};
+
+    use crate::{
+        rebuild_tx_index, requests_by_tx, tx_hash_chain, BRequest, Chains, InputRequest, RequestId,
+        Timestamp,
+    };
+
+    const EVM_TX: &str = "0x5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060";
+    const SOLANA_TX: &str =
+        "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW";
+
+    fn request(token_id: &str, origin: Chains) -> BRequest {
+        BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xcontract".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: origin,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        )
+    }
+
+    #[test]
+    fn test_hashes_of_both_chains_indexed() {
+        assert_eq!(tx_hash_chain(EVM_TX), Some(Chains::EVM));
+        assert_eq!(tx_hash_chain(SOLANA_TX), Some(Chains::SOLANA));
+        assert_eq!(tx_hash_chain("0xtx"), None);
+
+        for db in each_engine() {
+            let mut evm = request("1", Chains::EVM);
+            evm.save(&db).unwrap();
+            evm.add_tx(EVM_TX, &db).unwrap();
+            let mut solana = request("2", Chains::SOLANA);
+            solana.save(&db).unwrap();
+            solana.add_tx(SOLANA_TX, &db).unwrap();
+            solana.add_tx("0xtx", &db).unwrap();
+
+            assert_eq!(requests_by_tx(&db, EVM_TX).unwrap(), vec![evm.id.clone()]);
+            // EVM hashes in any case
+            assert_eq!(
+                requests_by_tx(&db, &EVM_TX.to_uppercase().replacen("0X", "0x", 1)).unwrap(),
+                vec![evm.id.clone()]
+            );
+            assert_eq!(
+                requests_by_tx(&db, SOLANA_TX).unwrap(),
+                vec![solana.id.clone()]
+            );
+            assert!(requests_by_tx(&db, &SOLANA_TX.to_lowercase())
+                .unwrap()
+                .is_empty());
+            assert!(requests_by_tx(&db, "0xtx").unwrap().is_empty());
+            assert_eq!(
+                db.scan_prefix::<RequestId>(TX_INDEX_PREFIX, None)
+                    .unwrap()
+                    .len(),
+                2
+            );
+
+            // Recorded by a second request, both are returned
+            let mut other = request("3", Chains::EVM);
+            other.save(&db).unwrap();
+            other.add_tx(EVM_TX, &db).unwrap();
+            let mut found = requests_by_tx(&db, EVM_TX).unwrap();
+            found.sort();
+            let mut expected = vec![evm.id.clone(), other.id.clone()];
+            expected.sort();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_rebuild_indexes_older_records() {
+        for db in each_engine() {
+            let mut evm = request("1", Chains::EVM);
+            evm.save(&db).unwrap();
+            evm.add_tx(EVM_TX, &db).unwrap();
+            let mut solana = request("2", Chains::SOLANA);
+            solana.save(&db).unwrap();
+            solana.add_tx(SOLANA_TX, &db).unwrap();
+            request("3", Chains::EVM).save(&db).unwrap();
+
+            // Written before the index existed
+            for (key, _) in db.scan_prefix::<RequestId>(TX_INDEX_PREFIX, None).unwrap() {
+                db.delete(key).unwrap();
+            }
+            assert!(requests_by_tx(&db, EVM_TX).unwrap().is_empty());
+
+            assert_eq!(rebuild_tx_index(&db).unwrap(), 2);
+            assert_eq!(requests_by_tx(&db, EVM_TX).unwrap(), vec![evm.id.clone()]);
+            assert_eq!(requests_by_tx(&db, SOLANA_TX).unwrap(), vec![solana.id]);
+            // Idempotent
+            assert_eq!(rebuild_tx_index(&db).unwrap(), 2);
+            assert_eq!(requests_by_tx(&db, EVM_TX).unwrap(), vec![evm.id]);
+        }
+    }
+}
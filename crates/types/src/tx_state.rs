@@ -0,0 +1,60 @@
+use serde::Serialize;
+
+/// State of a relayer transaction as seen by the node of its chain
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum TxState {
+    /// Waiting to be included. EVM transactions carry their max fee and the current base
+    /// fee, they can't be included while the base fee is higher
+    Pending {
+        max_fee_per_gas: Option<u128>,
+        base_fee_per_gas: Option<u128>,
+    },
+    /// Included in a block, `success` is false when it reverted
+    Mined { success: bool },
+    /// Unknown to the node, dropped from the mempool or never broadcast
+    Dropped,
+}
+
+impl TxState {
+    /// Pending with a max fee under the current base fee
+    pub fn priced_out(&self) -> bool {
+        match *self {
+            TxState::Pending {
+                max_fee_per_gas: Some(max_fee),
+                base_fee_per_gas: Some(base_fee),
+            } => max_fee < base_fee,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tx_state_test {
+    use serde_json::json;
+
+    use crate::TxState;
+
+    #[test]
+    fn test_priced_out_and_json() {
+        let pending = |max_fee, base_fee| TxState::Pending {
+            max_fee_per_gas: max_fee,
+            base_fee_per_gas: base_fee,
+        };
+        assert!(pending(Some(10), Some(11)).priced_out());
+        assert!(!pending(Some(11), Some(11)).priced_out());
+        // Solana transactions and nodes without a base fee
+        assert!(!pending(None, None).priced_out());
+        assert!(!pending(Some(10), None).priced_out());
+        assert!(!TxState::Dropped.priced_out());
+
+        assert_eq!(
+            serde_json::to_value(pending(Some(10), Some(11))).unwrap(),
+            json!({ "state": "pending", "max_fee_per_gas": 10, "base_fee_per_gas": 11 })
+        );
+        assert_eq!(
+            serde_json::to_value(TxState::Mined { success: false }).unwrap(),
+            json!({ "state": "mined", "success": false })
+        );
+    }
+}
@@ -1,13 +1,21 @@
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::str::FromStr;
 
-use alloy::primitives::keccak256;
+use alloy::primitives::{keccak256, U256};
 
-use eyre::Result;
-use log::info;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
-use storage::db::Database;
-
-use crate::add_completed_request;
+use storage::db::{Batch, Database};
+
+use crate::{
+    publish_status, redact_urls, request_data, stage_completed_request, stage_custody_exposure,
+    stage_pending_refund, stage_pending_removal, status_index_key, truncate_message,
+    update_account_index, update_callback_index, update_client_reference_index,
+    update_custody_index, update_tx_index, update_wrapped_registry, AppliedBranding,
+    BridgeAddresses, BridgeError, CallContext, CallbackDelivery, ClientLabels, CustodyChain,
+    DestinationAccountPending, ErrorComponent, ErrorRecord, FailedAttempt, FailureReport,
+    FinalityPending, MetadataPending, MetadataSnapshot, RequestId, Result, Timestamp, TraceContext,
+    RELAYER_VERSION,
+};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum Status {
@@ -16,6 +24,38 @@ pub enum Status {
     TokenMinted,
     Completed,
     Canceled,
+    /// Token in custody for a request id never submitted to the API, waiting for the
+    /// owner to claim it with a destination
+    NeedsDestination,
+    /// Orphan not claimed in time, the token can be returned to its owner
+    RefundEligible,
+    /// Origin token returned to its owner after the request was canceled
+    Refunded,
+    /// Accepted by the API, its lock transaction is queued to the tx processor of the
+    /// origin chain
+    Initializing,
+    /// Minted to another account than the destination, held for an operator with the
+    /// token still in custody
+    NeedsIntervention,
+    /// Token in custody, the mint waits for the destination account to fund its
+    /// destination token account
+    AwaitingDestinationAccount,
+}
+
+impl Status {
+    pub const ALL: [Status; 11] = [
+        Status::RequestReceived,
+        Status::TokenReceived,
+        Status::TokenMinted,
+        Status::Completed,
+        Status::Canceled,
+        Status::NeedsDestination,
+        Status::RefundEligible,
+        Status::Refunded,
+        Status::Initializing,
+        Status::NeedsIntervention,
+        Status::AwaitingDestinationAccount,
+    ];
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -24,6 +64,30 @@ pub enum Chains {
     SOLANA,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum CancelReason {
+    MetadataInvalid(String),
+    /// The stored request data can't be parsed, retrying will never succeed
+    DataCorrupted(String),
+    /// The token is held by the bridge for another active request, with its id
+    TokenAlreadyBridging(String),
+    /// The gas estimate of the transaction is over the configured limit
+    GasLimitExceeded(String),
+    /// The lock transaction is mined without the token reaching the bridge
+    LockFailed(String),
+    /// The token did not reach the bridge before `RECEIVED_EXPIRY`
+    Expired(String),
+    /// Given up after `MAX_SWEEP_ATTEMPTS` failed attempts, with the last error. Kept for
+    /// the requests canceled so by older versions, they are parked in NeedsIntervention now
+    RetriesExhausted(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct HistoryEntry {
+    pub time: Timestamp,
+    pub event: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct InputRequest {
     pub contract_or_mint: String,
@@ -41,16 +105,108 @@ pub struct OutputResult {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BRequest {
-    pub id: String,
+    pub id: RequestId,
     pub status: Status,
     pub input: InputRequest,
     pub tx_hashes: Vec<String>,
     pub output: OutputResult,
-    pub last_update: Duration,
+    pub last_update: Timestamp,
+    #[serde(default)]
+    pub created_by_version: String,
+    #[serde(default)]
+    pub cancel_reason: Option<CancelReason>,
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+    #[serde(default)]
+    pub created_at: Timestamp,
+    #[serde(default)]
+    pub finalized_at: Option<Timestamp>,
+    /// Message of the latest error, bounded as its record
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// Chain call of the last error
+    #[serde(default)]
+    pub last_error_context: Option<CallContext>,
+    /// Origin token URI, kept when the metadata was pinned to IPFS before minting
+    #[serde(default)]
+    pub original_token_uri: Option<String>,
+    #[serde(default)]
+    pub pinned_metadata_cid: Option<String>,
+    /// Canceled with the origin token in custody, the token is not returned yet
+    #[serde(default)]
+    pub refund_pending: bool,
+    /// Waiting for the URI of the origin token, set while the request is TokenReceived
+    #[serde(default)]
+    pub metadata_pending: Option<MetadataPending>,
+    /// Trace of the request creation, the chain events link back to it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_context: Option<TraceContext>,
+    /// Callback URL given at creation and the state of its delivery
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback: Option<CallbackDelivery>,
+    /// Failed sweep attempts in the current status
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_attempts: Vec<FailedAttempt>,
+    /// Set when the request is given up after its last retry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_report: Option<FailureReport>,
+    /// Solana transaction that moved the token to the bridge token account after the
+    /// request was created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custody_signature: Option<String>,
+    /// Id of the request under the other id scheme, an alias record resolves it to `id`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_id: Option<RequestId>,
+    /// Rebuilt from the chain history by a backfill, not created by this relayer
+    #[serde(default)]
+    pub reconstructed: bool,
+    /// Token URI given to the mint: the origin URI, its pinned copy or its translation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minted_token_uri: Option<String>,
+    /// Metadata obtained for the first mint attempt, the retries mint it again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_snapshot: Option<MetadataSnapshot>,
+    /// Custody seen at confirmed commitment, the mint waits for its finality
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finality_pending: Option<FinalityPending>,
+    /// Latest error with where it comes from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error_record: Option<ErrorRecord>,
+    /// Errors before the latest one, oldest first and at most `ERROR_RING_SIZE`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_errors: Vec<ErrorRecord>,
+    /// Name, symbol and URI marks given to the destination token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branding: Option<AppliedBranding>,
+    /// Set when the accounts of the request were replaced by purge markers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purged_at: Option<Timestamp>,
+    /// Block (EVM) or slot (Solana) of the origin chain the lock transaction was seen in,
+    /// null until its NewRequest event and for the requests saved before
+    #[serde(default)]
+    pub lock_block: Option<u64>,
+    /// Reference of the integrator given at creation, indexed for its lookup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_reference: Option<String>,
+    /// Tags of the integrator given at creation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Holder of the origin token and how the token owner is authorized by it, set when
+    /// the token owner doesn't hold the token itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custody_chain: Option<CustodyChain>,
+    /// Bridge addresses of the relayer when the request was created, it is processed
+    /// against them after an upgrade. None for the requests saved before
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bridge_addresses: Option<BridgeAddresses>,
+    /// Destination token account the mint waits for, set while the request is
+    /// AwaitingDestinationAccount
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination_account_pending: Option<DestinationAccountPending>,
 }
 
 impl BRequest {
-    pub fn new(input: InputRequest) -> Self {
+    pub fn new(input: InputRequest, now: Timestamp) -> Self {
         let request_id =
             BRequest::generate_id(&input.contract_or_mint, &input.token_id, &input.token_owner);
         BRequest {
@@ -59,59 +215,323 @@ impl BRequest {
             input,
             tx_hashes: vec![],
             output: OutputResult::default(),
-            last_update: Self::current_time(),
+            last_update: now,
+            created_by_version: RELAYER_VERSION.to_string(),
+            cancel_reason: None,
+            history: vec![],
+            created_at: now,
+            finalized_at: None,
+            last_error: None,
+            last_error_context: None,
+            original_token_uri: None,
+            pinned_metadata_cid: None,
+            refund_pending: false,
+            metadata_pending: None,
+            trace_context: TraceContext::current(),
+            callback: None,
+            failed_attempts: vec![],
+            failure_report: None,
+            custody_signature: None,
+            alias_id: None,
+            reconstructed: false,
+            minted_token_uri: None,
+            metadata_snapshot: None,
+            finality_pending: None,
+            last_error_record: None,
+            previous_errors: vec![],
+            branding: None,
+            purged_at: None,
+            lock_block: None,
+            client_reference: None,
+            tags: vec![],
+            custody_chain: None,
+            bridge_addresses: None,
+            destination_account_pending: None,
         }
     }
 
-    pub fn update_state(&mut self, db: &Database) -> Result<()> {
+    pub fn update_state(&mut self, db: &Database, now: Timestamp) -> Result<()> {
+        self.update_state_with(db, Batch::default(), now)
+    }
+
+    /// Moves to the next status writing the extra writes of `batch` together with it. A
+    /// TokenMinted request is only completed by `complete_minted`, as a compare and set
+    fn update_state_with(&mut self, db: &Database, batch: Batch, now: Timestamp) -> Result<()> {
         match self.status {
+            Status::Initializing => self.status = Status::RequestReceived,
             Status::RequestReceived => self.status = Status::TokenReceived,
             Status::TokenReceived => self.status = Status::TokenMinted,
-            Status::TokenMinted => self.status = Status::Completed,
-            Status::Completed | Status::Canceled => {}
+            Status::TokenMinted => {
+                return Err(BridgeError::Other(format!(
+                    "Request {} is completed by its minted token only",
+                    self.id
+                )));
+            }
+            Status::Completed
+            | Status::Canceled
+            | Status::NeedsDestination
+            | Status::RefundEligible
+            | Status::Refunded
+            | Status::NeedsIntervention
+            | Status::AwaitingDestinationAccount => {}
         }
-        self.last_update = Self::current_time();
+        self.last_update = now;
 
-        db.write_value(&self.id, &self)?;
+        self.save_with(db, batch)?;
         info!("Request id {} status updated {:?}", self.id, self.status);
         Ok(())
     }
 
-    pub fn cancel(&mut self, db: &Database) -> Result<()> {
+    /// Cancels the request, a token already in custody is queued to be refunded in the
+    /// same write
+    pub fn cancel(&mut self, db: &Database, now: Timestamp) -> Result<()> {
+        let mut batch = Batch::default();
+        if self.holds_origin_token() {
+            self.refund_pending = true;
+            stage_pending_refund(&self.id, db, &mut batch)?;
+            self.history.push(HistoryEntry {
+                time: now,
+                event: "Canceled with the token in custody, refund queued".to_string(),
+            });
+        }
         self.status = Status::Canceled;
 
-        db.write_value(&self.id, &self)?;
+        self.save_with(db, batch)
+    }
+
+    pub fn cancel_with_reason(
+        &mut self,
+        db: &Database,
+        reason: CancelReason,
+        now: Timestamp,
+    ) -> Result<()> {
+        self.history.push(HistoryEntry {
+            time: now,
+            event: format!("Canceled: {:?}", reason),
+        });
+        self.cancel_reason = Some(reason);
+        self.cancel(db, now)
+    }
+
+    /// Permanent failure, the request is canceled keeping the error that caused it
+    pub fn cancel_corrupted(&mut self, db: &Database, error: &str, now: Timestamp) -> Result<()> {
+        let (message, _) = truncate_message(&redact_urls(error));
+        self.last_error = Some(message.clone());
+        self.cancel_with_reason(db, CancelReason::DataCorrupted(message), now)
+    }
+
+    /// Keeps the error chain of a failed attempt of `component`, the request stays
+    /// retryable. The error is returned by the API, its URLs are redacted and its length
+    /// bounded
+    pub fn record_error(
+        &mut self,
+        component: ErrorComponent,
+        err: &BridgeError,
+        db: &Database,
+        now: Timestamp,
+    ) -> Result<()> {
+        let record = ErrorRecord::of_bridge_error(component, err, now);
+        self.record_error_record(record, CallContext::of(err).cloned(), db)
+    }
+
+    /// Appends an entry to the request audit history
+    pub fn record_event(&mut self, event: &str, db: &Database, now: Timestamp) -> Result<()> {
+        self.push_history(event.to_string(), now);
+
+        self.save(db)?;
         Ok(())
     }
 
-    pub fn finalize(&mut self, db: &Database, token_contract: &str, token_id: &str) -> Result<()> {
+    /// Appends `event` to the audit history unless it is already the last entry, a step
+    /// seen twice is recorded once
+    fn push_history(&mut self, event: String, now: Timestamp) {
+        if self.history.last().is_some_and(|last| last.event == event) {
+            return;
+        }
+        self.history.push(HistoryEntry { time: now, event });
+    }
+
+    /// Keeps the token the mint of the request was sent for. The request stays TokenMinted
+    /// until `complete_minted` confirms the token on the destination chain
+    pub fn finalize(
+        &mut self,
+        db: &Database,
+        token_contract: &str,
+        token_id: &str,
+        now: Timestamp,
+    ) -> Result<()> {
         self.output.detination_contract_id_or_mint = token_contract.to_string();
         self.output.detination_token_id_or_account = token_id.to_string();
-        self.last_update = Self::current_time();
+        self.last_update = now;
+
+        self.save(db)
+    }
+
+    /// Completes a TokenMinted request with the token reported by the destination chain.
+    /// The chain is the source of truth, a different predicted output is overwritten. The
+    /// event handler and the sweep can both complete a request: the write only lands while
+    /// the stored request is still TokenMinted, the other one reloads the completed request
+    pub fn complete_minted(
+        &mut self,
+        db: &Database,
+        token_contract: &str,
+        token_id: &str,
+        now: Timestamp,
+    ) -> Result<()> {
+        if self.status != Status::TokenMinted {
+            return Ok(());
+        }
 
-        db.write_value(&self.id, &self)?;
-        add_completed_request(&self.id, db)?;
+        let predicted_contract = self.output.detination_contract_id_or_mint.clone();
+        let predicted_token_id = self.output.detination_token_id_or_account.clone();
+        let same_token_id = match (
+            U256::from_str(predicted_token_id.trim()),
+            U256::from_str(token_id.trim()),
+        ) {
+            (Ok(predicted), Ok(minted)) => predicted == minted,
+            _ => predicted_token_id == token_id,
+        };
+        if !predicted_contract.eq_ignore_ascii_case(token_contract) || !same_token_id {
+            warn!(
+                "Minted token of request {} differs from the prediction, minted {} {}, predicted {} {}",
+                self.id, token_contract, token_id, predicted_contract, predicted_token_id
+            );
+            self.history.push(HistoryEntry {
+                time: now,
+                event: format!(
+                    "Minted token {token_contract} {token_id} differs from the predicted {predicted_contract} {predicted_token_id}"
+                ),
+            });
+        }
+
+        self.output.detination_contract_id_or_mint = token_contract.to_string();
+        self.output.detination_token_id_or_account = token_id.to_string();
+        let mut batch = Batch::default();
+        stage_completed_request(&self.id, db, &mut batch, now)?;
+        self.status = Status::Completed;
+        self.last_update = now;
+        if self.finalized_at.is_none() {
+            self.finalized_at = Some(self.last_update);
+        }
+        self.push_history("Completed".to_string(), now);
+
+        if !self.save_with_if(db, batch, Status::TokenMinted)? {
+            info!("Request {} already completed by another task", self.id);
+            if let Some(stored) = request_data(&self.id, db)? {
+                *self = stored;
+            }
+            return Ok(());
+        }
+        info!("Request id {} status updated {:?}", self.id, self.status);
         Ok(())
     }
 
     pub fn add_tx(&mut self, tx: &str, db: &Database) -> Result<()> {
         self.tx_hashes.push(tx.to_string());
-        db.write_value(&self.id, &self)?;
+        self.save(db)?;
+        Ok(())
+    }
+
+    /// Lock transaction of an Initializing request sent by its tx processor, the request
+    /// waits for its token from now on
+    pub fn lock_sent(&mut self, tx: &str, db: &Database, now: Timestamp) -> Result<()> {
+        if self.status != Status::Initializing {
+            return Ok(());
+        }
+        self.tx_hashes.push(tx.to_string());
+        self.history.push(HistoryEntry {
+            time: now,
+            event: format!("Lock transaction {tx} sent"),
+        });
+        self.update_state(db, now)
+    }
+
+    /// Lock transaction of an Initializing request not sent, the request is canceled with
+    /// the error so the owner can create it again
+    pub fn lock_not_sent(
+        &mut self,
+        db: &Database,
+        reason: fn(String) -> CancelReason,
+        error: &BridgeError,
+        now: Timestamp,
+    ) -> Result<()> {
+        let (message, _) = truncate_message(&redact_urls(&error.to_string()));
+        self.last_error = Some(message.clone());
+        self.cancel_with_reason(db, reason(message), now)
+    }
+
+    /// Writes the request together with its status and custody index entries in a single
+    /// batch, so the indexes never disagree with the record
+    pub fn save(&self, db: &Database) -> Result<()> {
+        self.save_with(db, Batch::default())
+    }
+
+    /// Same as `save` adding the writes already in `batch`. A terminal request leaves the
+    /// pending queue in the same batch, so the queue never keeps a finished request
+    pub(crate) fn save_with(&self, db: &Database, mut batch: Batch) -> Result<()> {
+        self.stage_save(db, &mut batch)?;
+        db.write_batch(batch)?;
+        publish_status(self);
+        Ok(())
+    }
+
+    /// Same as `save_with` when the stored request is still in `expected`, checked and
+    /// written as a compare and set. A request not stored yet is written. Returns whether
+    /// it was written
+    pub(crate) fn save_with_if(
+        &self,
+        db: &Database,
+        mut batch: Batch,
+        expected: Status,
+    ) -> Result<bool> {
+        self.stage_save(db, &mut batch)?;
+        let written = db.write_batch_if(
+            &self.id,
+            |stored: Option<BRequest>| stored.is_none_or(|stored| stored.status == expected),
+            batch,
+        )?;
+        if written {
+            publish_status(self);
+        }
+        Ok(written)
+    }
+
+    fn stage_save(&self, db: &Database, batch: &mut Batch) -> Result<()> {
+        batch.put(&self.id, self)?;
+        if let Some(alias_id) = &self.alias_id {
+            batch.put_alias(alias_id, &self.id)?;
+        }
+        for status in Status::ALL.iter() {
+            let key = status_index_key(status, &self.id);
+            if *status == self.status {
+                batch.put(key, &self.last_update.as_secs())?;
+            } else {
+                batch.delete(key);
+            }
+        }
+        update_custody_index(self, db, batch)?;
+        stage_custody_exposure(self, batch)?;
+        update_callback_index(self, batch)?;
+        update_wrapped_registry(self, batch)?;
+        update_tx_index(self, batch)?;
+        update_account_index(self, batch)?;
+        update_client_reference_index(self, batch)?;
+        if matches!(
+            self.status,
+            Status::Completed | Status::Canceled | Status::Refunded | Status::NeedsIntervention
+        ) {
+            stage_pending_removal(&self.id, db, batch)?;
+        }
         Ok(())
     }
 
-    pub fn generate_id(contract: &str, token_id: &str, token_owner: &str) -> String {
+    pub fn generate_id(contract: &str, token_id: &str, token_owner: &str) -> RequestId {
         let mut data = Vec::new();
         data.extend_from_slice(contract.as_bytes());
         data.extend_from_slice(token_id.as_bytes());
         data.extend_from_slice(token_owner.as_bytes());
 
-        keccak256(&data).to_string()
-    }
-
-    fn current_time() -> Duration {
-        let now = SystemTime::now();
-        now.duration_since(UNIX_EPOCH).expect("Time went backwards")
+        keccak256(&data).into()
     }
 }
 
@@ -122,6 +542,22 @@ pub struct SolanaInputRequest {
     pub token_account: String,
     pub origin_network: Chains,
     pub destination_account: String,
+    /// https URL the terminal status of the request is POSTed to
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    #[serde(default)]
+    pub client_reference: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl SolanaInputRequest {
+    pub fn labels(&self) -> ClientLabels {
+        ClientLabels {
+            client_reference: self.client_reference.clone(),
+            tags: self.tags.clone(),
+        }
+    }
 }
 
 impl From<SolanaInputRequest> for InputRequest {
@@ -143,6 +579,22 @@ pub struct EVMInputRequest {
     pub token_owner: String,
     pub origin_network: Chains,
     pub destination_account: String,
+    /// https URL the terminal status of the request is POSTed to
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    #[serde(default)]
+    pub client_reference: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl EVMInputRequest {
+    pub fn labels(&self) -> ClientLabels {
+        ClientLabels {
+            client_reference: self.client_reference.clone(),
+            tags: self.tags.clone(),
+        }
+    }
 }
 
 impl From<EVMInputRequest> for InputRequest {
@@ -161,44 +613,87 @@ impl From<EVMInputRequest> for InputRequest {
 pub enum Function {
     Mint,
     NewRequest,
+    Refund,
 }
 
-#[derive(Debug, Clone)]
-pub struct TxMessage {
-    pub accion: Function,
-    pub mint_data: Option<MessageMint>,
-    pub request_data: Option<MessageNewRequest>,
+/// Message of a chain tx processor, each variant carries the data of its transaction
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum TxMessage {
+    Mint(MessageMint),
+    NewRequest(MessageNewRequest),
+    Refund(MessageRefund),
 }
 
-#[derive(Debug, Clone)]
+impl TxMessage {
+    pub fn function(&self) -> Function {
+        match self {
+            TxMessage::Mint(_) => Function::Mint,
+            TxMessage::NewRequest(_) => Function::NewRequest,
+            TxMessage::Refund(_) => Function::Refund,
+        }
+    }
+
+    pub fn request_id(&self) -> &RequestId {
+        match self {
+            TxMessage::Mint(mint) => &mint.request_id,
+            TxMessage::NewRequest(request) => &request.request_id,
+            TxMessage::Refund(refund) => &refund.request_id,
+        }
+    }
+
+    /// Trace of the span that sent the message
+    pub fn trace_context(&self) -> Option<&TraceContext> {
+        match self {
+            TxMessage::Mint(mint) => mint.trace_context.as_ref(),
+            TxMessage::NewRequest(request) => request.trace_context.as_ref(),
+            TxMessage::Refund(refund) => refund.trace_context.as_ref(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct MessageMint {
-    pub request_id: String,
+    pub request_id: RequestId,
     pub token_metadata: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_context: Option<TraceContext>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct MessageNewRequest {
     pub token_contract: String,
     pub token_owner: String,
     pub token_id: String,
-    pub request_id: String,
+    pub request_id: RequestId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_context: Option<TraceContext>,
+}
+
+/// Returns the origin token of a canceled request to its owner
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MessageRefund {
+    pub request_id: RequestId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_context: Option<TraceContext>,
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
-        completed_requests, BRequest, Chains, EVMInputRequest, Function, InputRequest, MessageMint,
-        MessageNewRequest, OutputResult, SolanaInputRequest, Status, TxMessage,
+        completed_requests, has_status, pending_requests, stage_pending_addition, BRequest,
+        CancelReason, Chains, EVMInputRequest, Function, InputRequest, MessageMint,
+        MessageNewRequest, MessageRefund, OutputResult, SolanaInputRequest, Status, Timestamp,
+        TxMessage, RELAYER_VERSION,
+    };
+    use serde_json::json;
+    use std::sync::{Arc, Barrier};
+    use storage::{
+        db::{Batch, Database},
+        testing::each_engine,
     };
-    use storage::db::Database;
-    use tempfile::tempdir;
 
     // Helper function to create a test database
-    fn setup_test_db() -> Database {
-        let dir = tempdir().unwrap();
-        let path = dir.path().to_str().unwrap();
-        Database::open(path).unwrap()
-    }
 
     // Helper function to create a test InputRequest
     fn create_test_input_request() -> InputRequest {
@@ -229,13 +724,14 @@ mod test {
     #[test]
     fn test_brequest_new() {
         let input = create_test_input_request();
-        let request = BRequest::new(input.clone());
+        let request = BRequest::new(input.clone(), Timestamp::now());
 
         // Check that the request was created with the correct values
         assert_eq!(request.status, Status::RequestReceived);
         assert_eq!(request.input, input);
         assert!(request.tx_hashes.is_empty());
         assert_eq!(request.output, OutputResult::default());
+        assert_eq!(request.created_by_version, RELAYER_VERSION);
 
         // Check that the ID was generated correctly
         let expected_id =
@@ -243,6 +739,43 @@ mod test {
         assert_eq!(request.id, expected_id);
     }
 
+    #[test]
+    fn test_brequest_reads_duration_times() {
+        let request = BRequest::new(create_test_input_request(), Timestamp::now());
+        let mut value = serde_json::to_value(&request).unwrap();
+        // Times stored before the millis encoding
+        value["last_update"] = json!({"secs": 1_700_000_000, "nanos": 5_000_000});
+        value["created_at"] = json!({"secs": 1_600_000_000, "nanos": 0});
+        value["finalized_at"] = json!({"secs": 1_700_000_001, "nanos": 0});
+
+        let request: BRequest = serde_json::from_value(value).unwrap();
+        assert_eq!(
+            request.last_update,
+            Timestamp::from_millis(1_700_000_000_005)
+        );
+        assert_eq!(request.created_at, Timestamp::from_secs(1_600_000_000));
+        assert_eq!(
+            request.finalized_at,
+            Some(Timestamp::from_secs(1_700_000_001))
+        );
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["last_update"],
+            json!(1_700_000_000_005u64)
+        );
+    }
+
+    #[test]
+    fn test_brequest_without_version_deserializes() {
+        // Requests stored before the version stamp was added
+        let mut value =
+            serde_json::to_value(BRequest::new(create_test_input_request(), Timestamp::now()))
+                .unwrap();
+        value.as_object_mut().unwrap().remove("created_by_version");
+
+        let request: BRequest = serde_json::from_value(value).unwrap();
+        assert_eq!(request.created_by_version, "");
+    }
+
     #[test]
     fn test_brequest_generate_id() {
         // Test that generate_id produces consistent results
@@ -256,113 +789,318 @@ mod test {
 
     #[test]
     fn test_brequest_update_state() {
-        let db = setup_test_db();
-        let input = create_test_input_request();
-        let mut request = BRequest::new(input);
-
-        // Initial state
-        assert_eq!(request.status, Status::RequestReceived);
-
-        // Update state and check transitions
-        request.update_state(&db).unwrap();
-        assert_eq!(request.status, Status::TokenReceived);
-
-        request.update_state(&db).unwrap();
-        assert_eq!(request.status, Status::TokenMinted);
-
-        request.update_state(&db).unwrap();
-        assert_eq!(request.status, Status::Completed);
-
-        // State should not change after Completed
-        request.update_state(&db).unwrap();
-        assert_eq!(request.status, Status::Completed);
-
-        // Verify the request was saved to the database
-        let retrieved: BRequest = db.read(&request.id).unwrap().unwrap();
-        assert_eq!(retrieved.status, Status::Completed);
+        for db in each_engine() {
+            let input = create_test_input_request();
+            let mut request = BRequest::new(input, Timestamp::now());
+
+            // Initial state
+            assert_eq!(request.status, Status::RequestReceived);
+
+            // Update state and check transitions
+            request.update_state(&db, Timestamp::now()).unwrap();
+            assert_eq!(request.status, Status::TokenReceived);
+
+            request.update_state(&db, Timestamp::now()).unwrap();
+            assert_eq!(request.status, Status::TokenMinted);
+
+            // Only the minted token completes it
+            assert!(request.update_state(&db, Timestamp::now()).is_err());
+            assert_eq!(request.status, Status::TokenMinted);
+            request
+                .complete_minted(&db, "0xcontract", "42", Timestamp::now())
+                .unwrap();
+            assert_eq!(request.status, Status::Completed);
+
+            // State should not change after Completed
+            request.update_state(&db, Timestamp::now()).unwrap();
+            assert_eq!(request.status, Status::Completed);
+
+            // Verify the request was saved to the database
+            let retrieved: BRequest = db.read(&request.id).unwrap().unwrap();
+            assert_eq!(retrieved.status, Status::Completed);
+        }
     }
 
     #[test]
     fn test_brequest_cancel() {
-        let db = setup_test_db();
-        let input = create_test_input_request();
-        let mut request = BRequest::new(input);
+        for db in each_engine() {
+            let input = create_test_input_request();
+            let mut request = BRequest::new(input, Timestamp::now());
 
-        // Initial state
-        assert_eq!(request.status, Status::RequestReceived);
+            // Initial state
+            assert_eq!(request.status, Status::RequestReceived);
 
-        // Cancel the request
-        request.cancel(&db).unwrap();
-        assert_eq!(request.status, Status::Canceled);
+            // Cancel the request
+            request.cancel(&db, Timestamp::now()).unwrap();
+            assert_eq!(request.status, Status::Canceled);
 
-        // Verify the request was saved to the database
-        let retrieved: BRequest = db.read(&request.id).unwrap().unwrap();
-        assert_eq!(retrieved.status, Status::Canceled);
+            // Verify the request was saved to the database
+            let retrieved: BRequest = db.read(&request.id).unwrap().unwrap();
+            assert_eq!(retrieved.status, Status::Canceled);
+        }
     }
 
     #[test]
-    fn test_brequest_finalize() {
-        let db = setup_test_db();
-        let input = create_test_input_request();
-        let mut request = BRequest::new(input);
-
-        // Initial state
-        assert_eq!(request.status, Status::RequestReceived);
+    fn test_brequest_cancel_with_reason() {
+        for db in each_engine() {
+            let input = create_test_input_request();
+            let mut request = BRequest::new(input, Timestamp::now());
+
+            request
+                .record_event("Token URI rewritten", &db, Timestamp::now())
+                .unwrap();
+            request
+                .cancel_with_reason(
+                    &db,
+                    CancelReason::MetadataInvalid("javascript".into()),
+                    Timestamp::now(),
+                )
+                .unwrap();
+            assert_eq!(request.status, Status::Canceled);
+
+            // Reason and history are persisted
+            let retrieved: BRequest = db.read(&request.id).unwrap().unwrap();
+            assert_eq!(
+                retrieved.cancel_reason,
+                Some(CancelReason::MetadataInvalid("javascript".into()))
+            );
+            assert_eq!(retrieved.history.len(), 2);
+            assert_eq!(retrieved.history[0].event, "Token URI rewritten");
+        }
+    }
 
-        // Finalize the request
-        let token_contract = "0xfinalcontract";
-        let token_id = "999";
-        request.finalize(&db, token_contract, token_id).unwrap();
+    #[test]
+    fn test_complete_minted_uses_the_chain_token() {
+        for db in each_engine() {
+            let mut request = BRequest::new(create_test_input_request(), Timestamp::now());
+            request.update_state(&db, Timestamp::now()).unwrap();
+            request.update_state(&db, Timestamp::now()).unwrap();
+            // Predicted from the origin mint
+            request
+                .finalize(&db, "0xContract", "12345", Timestamp::now())
+                .unwrap();
+
+            // The contract assigned a sequential id instead
+            request
+                .complete_minted(&db, "0xcontract", "7", Timestamp::now())
+                .unwrap();
+
+            let retrieved: BRequest = db.read(&request.id).unwrap().unwrap();
+            assert_eq!(retrieved.status, Status::Completed);
+            assert_eq!(
+                retrieved.output.detination_contract_id_or_mint,
+                "0xcontract"
+            );
+            assert_eq!(retrieved.output.detination_token_id_or_account, "7");
+            assert_eq!(retrieved.history.len(), 2);
+            assert!(retrieved.history[0]
+                .event
+                .contains("differs from the predicted"));
+            assert_eq!(retrieved.history[1].event, "Completed");
+
+            // Matching prediction completes without a discrepancy
+            let mut input = create_test_input_request();
+            input.token_id = "2".to_string();
+            let mut request = BRequest::new(input, Timestamp::now());
+            request.update_state(&db, Timestamp::now()).unwrap();
+            request.update_state(&db, Timestamp::now()).unwrap();
+            request
+                .finalize(&db, "0xcontract", "42", Timestamp::now())
+                .unwrap();
+            request
+                .complete_minted(&db, "0xcontract", "0x2a", Timestamp::now())
+                .unwrap();
+            assert_eq!(request.status, Status::Completed);
+            assert_eq!(request.history.len(), 1);
+        }
+    }
 
-        // Check that the request was updated correctly
-        assert_eq!(request.status, Status::Completed);
-        assert_eq!(
-            request.output.detination_contract_id_or_mint,
-            token_contract
-        );
-        assert_eq!(request.output.detination_token_id_or_account, token_id);
+    #[test]
+    fn test_concurrent_completions_complete_once() {
+        for db in each_engine() {
+            let mut request = BRequest::new(create_test_input_request(), Timestamp::now());
+            request.update_state(&db, Timestamp::now()).unwrap();
+            request.update_state(&db, Timestamp::now()).unwrap();
+            request
+                .finalize(&db, "0xcontract", "42", Timestamp::now())
+                .unwrap();
+
+            // The TokenMinted event handler and the sweep, each with its copy
+            let barrier = Arc::new(Barrier::new(2));
+            let handles: Vec<_> = (0..2)
+                .map(|_| {
+                    let db: Database = (*db).clone();
+                    let barrier = barrier.clone();
+                    let mut request = request.clone();
+                    std::thread::spawn(move || {
+                        barrier.wait();
+                        request
+                            .complete_minted(&db, "0xcontract", "42", Timestamp::now())
+                            .unwrap();
+                        request
+                    })
+                })
+                .collect();
+            for handle in handles {
+                // The task losing the race ends with the completed request too
+                assert_eq!(handle.join().unwrap().status, Status::Completed);
+            }
 
-        // Verify the request was saved to the database
-        let retrieved: BRequest = db.read(&request.id).unwrap().unwrap();
-        assert_eq!(retrieved.status, Status::Completed);
-        assert_eq!(
-            retrieved.output.detination_contract_id_or_mint,
-            token_contract
-        );
-        assert_eq!(retrieved.output.detination_token_id_or_account, token_id);
+            let stored: BRequest = db.read(&request.id).unwrap().unwrap();
+            assert_eq!(stored.status, Status::Completed);
+            let completions = stored
+                .history
+                .iter()
+                .filter(|entry| entry.event == "Completed")
+                .count();
+            assert_eq!(completions, 1, "{:?}", stored.history);
+            assert_eq!(completed_requests(&db).unwrap(), vec![request.id.clone()]);
+
+            // A later attempt doesn't complete it again
+            let mut late = request.clone();
+            late.complete_minted(&db, "0xcontract", "42", Timestamp::now())
+                .unwrap();
+            let stored: BRequest = db.read(&request.id).unwrap().unwrap();
+            assert_eq!(stored.history.len(), 1);
+        }
+    }
 
-        // Verify the request was added to completed requests
-        let completed = completed_requests(&db).unwrap();
-        assert!(completed.contains(&request.id));
+    fn add_pending(db: &Database, ids: &[&str]) {
+        let mut batch = Batch::default();
+        for id in ids {
+            stage_pending_addition(id, db, &mut batch).unwrap();
+            db.write_batch(std::mem::take(&mut batch)).unwrap();
+        }
     }
 
     #[test]
-    fn test_brequest_add_tx() {
-        let db = setup_test_db();
-        let input = create_test_input_request();
-        let mut request = BRequest::new(input);
-
-        // Initial state
-        assert!(request.tx_hashes.is_empty());
+    fn test_terminal_writes_are_all_or_nothing() {
+        for db in each_engine() {
+            let mut request = BRequest::new(create_test_input_request(), Timestamp::now());
+            request.update_state(&db, Timestamp::now()).unwrap();
+            request.update_state(&db, Timestamp::now()).unwrap();
+            add_pending(&db, &[request.id.as_str(), "0xother"]);
+
+            db.set_failing_batches(true);
+            assert!(request
+                .finalize(&db, "0xcontract", "42", Timestamp::now())
+                .is_err());
+            assert!(request
+                .clone()
+                .complete_minted(&db, "0xcontract", "42", Timestamp::now())
+                .is_err());
+
+            // Nothing of the failed writes is visible
+            let stored: BRequest = db.read(&request.id).unwrap().unwrap();
+            assert_eq!(stored.status, Status::TokenMinted);
+            assert_eq!(stored.finalized_at, None);
+            assert!(completed_requests(&db).is_none());
+            assert!(has_status(&db, &request.id, &Status::TokenMinted).unwrap());
+            assert!(pending_requests(&db)
+                .unwrap()
+                .contains(&request.id.to_string()));
+
+            // The retry lands the record, the completed entry and the pending removal together
+            db.set_failing_batches(false);
+            let mut request = stored;
+            request
+                .complete_minted(&db, "0xcontract", "42", Timestamp::now())
+                .unwrap();
+            let stored: BRequest = db.read(&request.id).unwrap().unwrap();
+            assert_eq!(stored.status, Status::Completed);
+            assert_eq!(completed_requests(&db).unwrap(), vec![request.id.clone()]);
+            assert!(has_status(&db, &request.id, &Status::Completed).unwrap());
+            assert_eq!(pending_requests(&db).unwrap(), vec!["0xother".to_string()]);
+        }
+    }
 
-        // Add a transaction
-        let tx_hash = "0xtx123";
-        request.add_tx(tx_hash, &db).unwrap();
-        assert_eq!(request.tx_hashes.len(), 1);
-        assert_eq!(request.tx_hashes[0], tx_hash);
+    #[test]
+    fn test_cancel_leaves_pending_atomically() {
+        for db in each_engine() {
+            let mut request = BRequest::new(create_test_input_request(), Timestamp::now());
+            request.save(&db).unwrap();
+            add_pending(&db, &[request.id.as_str()]);
+
+            db.set_failing_batches(true);
+            assert!(request.clone().cancel(&db, Timestamp::now()).is_err());
+            let stored: BRequest = db.read(&request.id).unwrap().unwrap();
+            assert_eq!(stored.status, Status::RequestReceived);
+            assert_eq!(pending_requests(&db).unwrap(), vec![request.id.clone()]);
+
+            db.set_failing_batches(false);
+            request.cancel(&db, Timestamp::now()).unwrap();
+            let stored: BRequest = db.read(&request.id).unwrap().unwrap();
+            assert_eq!(stored.status, Status::Canceled);
+            assert!(pending_requests(&db).unwrap().is_empty());
+        }
+    }
 
-        // Add another transaction
-        let tx_hash2 = "0xtx456";
-        request.add_tx(tx_hash2, &db).unwrap();
-        assert_eq!(request.tx_hashes.len(), 2);
-        assert_eq!(request.tx_hashes[0], tx_hash);
-        assert_eq!(request.tx_hashes[1], tx_hash2);
+    #[test]
+    fn test_brequest_finalize() {
+        for db in each_engine() {
+            let input = create_test_input_request();
+            let mut request = BRequest::new(input, Timestamp::now());
+            request.update_state(&db, Timestamp::now()).unwrap();
+            request.update_state(&db, Timestamp::now()).unwrap();
+
+            // Finalize the request when its mint is sent
+            let token_contract = "0xfinalcontract";
+            let token_id = "999";
+            request
+                .finalize(&db, token_contract, token_id, Timestamp::now())
+                .unwrap();
+
+            // The sent token is kept, the request waits for its confirmation
+            let retrieved: BRequest = db.read(&request.id).unwrap().unwrap();
+            assert_eq!(retrieved.status, Status::TokenMinted);
+            assert_eq!(
+                retrieved.output.detination_contract_id_or_mint,
+                token_contract
+            );
+            assert_eq!(retrieved.output.detination_token_id_or_account, token_id);
+            assert_eq!(retrieved.finalized_at, None);
+            assert!(completed_requests(&db).is_none());
+
+            // Completed once the chain reports the token
+            request
+                .complete_minted(&db, token_contract, token_id, Timestamp::now())
+                .unwrap();
+            let retrieved: BRequest = db.read(&request.id).unwrap().unwrap();
+            assert_eq!(retrieved.status, Status::Completed);
+            assert_eq!(retrieved.finalized_at, Some(retrieved.last_update));
+            let completed = completed_requests(&db).unwrap();
+            assert!(completed.contains(&request.id.to_string()));
+        }
+    }
 
-        // Verify the request was saved to the database
-        let retrieved: BRequest = db.read(&request.id).unwrap().unwrap();
-        assert_eq!(retrieved.tx_hashes.len(), 2);
-        assert_eq!(retrieved.tx_hashes[0], tx_hash);
-        assert_eq!(retrieved.tx_hashes[1], tx_hash2);
+    #[test]
+    fn test_brequest_add_tx() {
+        for db in each_engine() {
+            let input = create_test_input_request();
+            let mut request = BRequest::new(input, Timestamp::now());
+
+            // Initial state
+            assert!(request.tx_hashes.is_empty());
+
+            // Add a transaction
+            let tx_hash = "0xtx123";
+            request.add_tx(tx_hash, &db).unwrap();
+            assert_eq!(request.tx_hashes.len(), 1);
+            assert_eq!(request.tx_hashes[0], tx_hash);
+
+            // Add another transaction
+            let tx_hash2 = "0xtx456";
+            request.add_tx(tx_hash2, &db).unwrap();
+            assert_eq!(request.tx_hashes.len(), 2);
+            assert_eq!(request.tx_hashes[0], tx_hash);
+            assert_eq!(request.tx_hashes[1], tx_hash2);
+
+            // Verify the request was saved to the database
+            let retrieved: BRequest = db.read(&request.id).unwrap().unwrap();
+            assert_eq!(retrieved.tx_hashes.len(), 2);
+            assert_eq!(retrieved.tx_hashes[0], tx_hash);
+            assert_eq!(retrieved.tx_hashes[1], tx_hash2);
+        }
     }
 
     #[test]
@@ -372,6 +1110,9 @@ mod test {
             token_account: "account456".to_string(),
             origin_network: Chains::SOLANA,
             destination_account: "dest789".to_string(),
+            callback_url: None,
+            client_reference: None,
+            tags: vec![],
         };
 
         let input_request: InputRequest = solana_input.clone().into();
@@ -394,6 +1135,9 @@ mod test {
             token_owner: "owner789".to_string(),
             origin_network: Chains::EVM,
             destination_account: "dest012".to_string(),
+            callback_url: None,
+            client_reference: None,
+            tags: vec![],
         };
 
         let input_request: InputRequest = evm_input.clone().into();
@@ -410,10 +1154,12 @@ mod test {
 
     #[test]
     fn test_tx_message_types() {
+        let request_id = BRequest::generate_id("contract123", "token789", "owner456");
         // Test MessageMint
         let mint_data = MessageMint {
-            request_id: "request123".to_string(),
+            request_id: request_id.clone(),
             token_metadata: "metadata456".to_string(),
+            trace_context: None,
         };
 
         // Test MessageNewRequest
@@ -421,42 +1167,56 @@ mod test {
             token_contract: "contract123".to_string(),
             token_owner: "owner456".to_string(),
             token_id: "token789".to_string(),
-            request_id: "request123".to_string(),
+            request_id: request_id.clone(),
+            trace_context: None,
         };
 
-        // Test TxMessage with Mint function
-        let tx_message_mint = TxMessage {
-            accion: Function::Mint,
-            mint_data: Some(mint_data.clone()),
-            request_data: None,
-        };
-
-        // Test TxMessage with NewRequest function
-        let tx_message_request = TxMessage {
-            accion: Function::NewRequest,
-            mint_data: None,
-            request_data: Some(request_data.clone()),
-        };
-
-        // Verify the data is stored correctly
-        match tx_message_mint.accion {
-            Function::Mint => {
-                let mint_data = tx_message_mint.mint_data.unwrap();
-                assert_eq!(mint_data.request_id, "request123");
-                assert_eq!(mint_data.token_metadata, "metadata456");
+        let tx_message_mint = TxMessage::Mint(mint_data);
+        let tx_message_request = TxMessage::NewRequest(request_data);
+        let tx_message_refund = TxMessage::Refund(MessageRefund {
+            request_id: request_id.clone(),
+            trace_context: None,
+        });
+
+        // No wildcard arm, a new variant has to be handled here
+        for message in [&tx_message_mint, &tx_message_request, &tx_message_refund] {
+            assert_eq!(message.request_id(), &request_id);
+            match message {
+                TxMessage::Mint(mint_data) => {
+                    assert!(matches!(message.function(), Function::Mint));
+                    assert_eq!(mint_data.token_metadata, "metadata456");
+                }
+                TxMessage::NewRequest(request_data) => {
+                    assert!(matches!(message.function(), Function::NewRequest));
+                    assert_eq!(request_data.token_contract, "contract123");
+                    assert_eq!(request_data.token_owner, "owner456");
+                    assert_eq!(request_data.token_id, "token789");
+                }
+                TxMessage::Refund(_) => {
+                    assert!(matches!(message.function(), Function::Refund));
+                }
             }
-            _ => panic!("Expected Mint function"),
         }
 
-        match tx_message_request.accion {
-            Function::NewRequest => {
-                let request_data = tx_message_request.request_data.unwrap();
-                assert_eq!(request_data.token_contract, "contract123");
-                assert_eq!(request_data.token_owner, "owner456");
-                assert_eq!(request_data.token_id, "token789");
-                assert_eq!(request_data.request_id, "request123");
-            }
-            _ => panic!("Expected NewRequest function"),
+        // Messages are persisted as JSON, every variant round trips
+        for message in [tx_message_mint, tx_message_request, tx_message_refund] {
+            let json = serde_json::to_string(&message).unwrap();
+            assert_eq!(serde_json::from_str::<TxMessage>(&json).unwrap(), message);
         }
+        assert_eq!(
+            serde_json::to_value(TxMessage::Mint(MessageMint {
+                request_id: request_id.clone(),
+                token_metadata: "uri".to_string(),
+                trace_context: None,
+            }))
+            .unwrap(),
+            json!({ "type": "Mint", "request_id": request_id.as_str(), "token_metadata": "uri" })
+        );
+        assert!(serde_json::from_value::<TxMessage>(json!({ "type": "NewRequest" })).is_err());
+        // Malformed ids are rejected when a persisted message is decoded
+        assert!(serde_json::from_value::<TxMessage>(
+            json!({ "type": "Refund", "request_id": "request123" })
+        )
+        .is_err());
     }
 }
@@ -0,0 +1,383 @@
+use std::fmt;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+pub const SOL_DECIMALS: u8 = 9;
+pub const ETH_DECIMALS: u8 = 18;
+const GWEI: u128 = 1_000_000_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum UnitsError {
+    #[error("Amount overflow")]
+    Overflow,
+
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(String),
+
+    #[error("Amount {0} has more than {1} decimals")]
+    PrecisionLoss(String, u8),
+}
+
+/// Formats a raw integer amount in human units, `1500000000` with 9 decimals is `1.5`
+pub fn format_units(raw: u128, decimals: u8) -> String {
+    let digits = raw.to_string();
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return digits;
+    }
+
+    let padded = format!("{digits:0>width$}", width = decimals + 1);
+    let (integer, fraction) = padded.split_at(padded.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        integer.to_string()
+    } else {
+        format!("{integer}.{fraction}")
+    }
+}
+
+/// Parses a human amount into its raw integer value, rejecting digits past `decimals`
+pub fn parse_units(value: &str, decimals: u8) -> Result<u128, UnitsError> {
+    let value = value.trim();
+    let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if (integer.is_empty() && fraction.is_empty()) || !is_digits(integer) || !is_digits(fraction) {
+        return Err(UnitsError::InvalidAmount(value.to_string()));
+    }
+
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > decimals as usize {
+        return Err(UnitsError::PrecisionLoss(value.to_string(), decimals));
+    }
+
+    let scale = 10u128
+        .checked_pow(decimals as u32)
+        .ok_or(UnitsError::Overflow)?;
+    let integer: u128 = match integer {
+        "" => 0,
+        integer => integer.parse().map_err(|_| UnitsError::Overflow)?,
+    };
+    let fraction: u128 = match fraction {
+        "" => 0,
+        fraction => {
+            fraction.parse::<u128>().map_err(|_| UnitsError::Overflow)?
+                * 10u128.pow((decimals as usize - fraction.len()) as u32)
+        }
+    };
+
+    integer
+        .checked_mul(scale)
+        .and_then(|integer| integer.checked_add(fraction))
+        .ok_or(UnitsError::Overflow)
+}
+
+/// JSON form of the amounts, the raw value as a string to keep its precision in JS clients
+#[derive(Serialize, Deserialize)]
+struct AmountJson {
+    raw: String,
+    #[serde(default, skip_deserializing)]
+    formatted: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    unit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    decimals: Option<u8>,
+}
+
+fn parse_raw<E: de::Error>(raw: &str) -> Result<u128, E> {
+    raw.parse()
+        .map_err(|_| E::custom(UnitsError::InvalidAmount(raw.to_string())))
+}
+
+macro_rules! native_amount {
+    ($name:ident, $raw:ty, $decimals:expr, $unit:expr) => {
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct $name($raw);
+
+        impl $name {
+            pub const ZERO: $name = $name(0);
+            pub const MAX: $name = $name(<$raw>::MAX);
+            pub const DECIMALS: u8 = $decimals;
+            pub const UNIT: &'static str = $unit;
+
+            pub const fn new(raw: $raw) -> Self {
+                $name(raw)
+            }
+
+            pub const fn raw(&self) -> $raw {
+                self.0
+            }
+
+            /// Parses an amount in human units
+            pub fn from_human(value: &str) -> Result<Self, UnitsError> {
+                let raw = parse_units(value, Self::DECIMALS)?;
+                <$raw>::try_from(raw)
+                    .map($name)
+                    .map_err(|_| UnitsError::Overflow)
+            }
+
+            pub fn checked_add(self, other: Self) -> Option<Self> {
+                self.0.checked_add(other.0).map($name)
+            }
+
+            pub fn checked_sub(self, other: Self) -> Option<Self> {
+                self.0.checked_sub(other.0).map($name)
+            }
+
+            pub fn checked_mul(self, factor: $raw) -> Option<Self> {
+                self.0.checked_mul(factor).map($name)
+            }
+
+            pub fn to_token_amount(self) -> TokenAmount {
+                TokenAmount::new(u128::from(self.0), Self::DECIMALS)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(
+                    f,
+                    "{} {}",
+                    format_units(u128::from(self.0), Self::DECIMALS),
+                    Self::UNIT
+                )
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                AmountJson {
+                    raw: self.0.to_string(),
+                    formatted: format_units(u128::from(self.0), Self::DECIMALS),
+                    unit: Some(Self::UNIT.to_string()),
+                    decimals: None,
+                }
+                .serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let json = AmountJson::deserialize(deserializer)?;
+                let raw = parse_raw::<D::Error>(&json.raw)?;
+                <$raw>::try_from(raw)
+                    .map($name)
+                    .map_err(|_| de::Error::custom(UnitsError::Overflow))
+            }
+        }
+    };
+}
+
+native_amount!(Lamports, u64, SOL_DECIMALS, "SOL");
+native_amount!(Wei, u128, ETH_DECIMALS, "ETH");
+
+impl Wei {
+    pub fn from_gwei(gwei: u128) -> Option<Self> {
+        gwei.checked_mul(GWEI).map(Wei)
+    }
+}
+
+/// Amount of a token with its own decimals, ERC-1155 balances or SPL tokens
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TokenAmount {
+    pub amount: u128,
+    pub decimals: u8,
+}
+
+impl TokenAmount {
+    pub const fn new(amount: u128, decimals: u8) -> Self {
+        TokenAmount { amount, decimals }
+    }
+
+    /// None on overflow or when the decimals differ
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+        self.amount
+            .checked_add(other.amount)
+            .map(|amount| TokenAmount::new(amount, self.decimals))
+    }
+
+    /// None on underflow or when the decimals differ
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+        self.amount
+            .checked_sub(other.amount)
+            .map(|amount| TokenAmount::new(amount, self.decimals))
+    }
+
+    pub fn checked_mul(self, factor: u128) -> Option<Self> {
+        self.amount
+            .checked_mul(factor)
+            .map(|amount| TokenAmount::new(amount, self.decimals))
+    }
+
+    /// Same amount with other decimals, fails instead of dropping digits
+    pub fn rescale(self, decimals: u8) -> Result<Self, UnitsError> {
+        let pow = |exp: u8| 10u128.checked_pow(exp as u32).ok_or(UnitsError::Overflow);
+        let amount = if decimals >= self.decimals {
+            self.amount
+                .checked_mul(pow(decimals - self.decimals)?)
+                .ok_or(UnitsError::Overflow)?
+        } else {
+            let divisor = pow(self.decimals - decimals)?;
+            if self.amount % divisor != 0 {
+                return Err(UnitsError::PrecisionLoss(self.to_string(), decimals));
+            }
+            self.amount / divisor
+        };
+        Ok(TokenAmount::new(amount, decimals))
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_units(self.amount, self.decimals))
+    }
+}
+
+impl Serialize for TokenAmount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        AmountJson {
+            raw: self.amount.to_string(),
+            formatted: format_units(self.amount, self.decimals),
+            unit: None,
+            decimals: Some(self.decimals),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TokenAmount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = AmountJson::deserialize(deserializer)?;
+        Ok(TokenAmount::new(
+            parse_raw::<D::Error>(&json.raw)?,
+            json.decimals.unwrap_or_default(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod units_test {
+    use crate::{format_units, parse_units, Lamports, TokenAmount, UnitsError, Wei};
+    use serde_json::json;
+
+    #[test]
+    fn test_format_units() {
+        assert_eq!(format_units(0, 9), "0");
+        assert_eq!(format_units(0, 0), "0");
+        assert_eq!(format_units(1, 9), "0.000000001");
+        assert_eq!(format_units(1_500_000_000, 9), "1.5");
+        assert_eq!(format_units(2_000_000_000, 9), "2");
+        assert_eq!(format_units(123, 0), "123");
+        assert_eq!(
+            format_units(u128::MAX, 18),
+            "340282366920938463463.374607431768211455"
+        );
+        // More decimals than digits in u128
+        assert_eq!(
+            format_units(1, 40),
+            "0.0000000000000000000000000000000000000001"
+        );
+
+        assert_eq!(Lamports::new(1_500_000_000).to_string(), "1.5 SOL");
+        assert_eq!(Lamports::MAX.to_string(), "18446744073.709551615 SOL");
+        assert_eq!(Wei::from_gwei(3).unwrap().to_string(), "0.000000003 ETH");
+        assert_eq!(TokenAmount::new(1050, 2).to_string(), "10.5");
+    }
+
+    #[test]
+    fn test_parse_units() {
+        assert_eq!(parse_units("1.5", 9), Ok(1_500_000_000));
+        assert_eq!(parse_units(".5", 1), Ok(5));
+        assert_eq!(parse_units("7", 0), Ok(7));
+        assert_eq!(parse_units("1.50", 1), Ok(15));
+        assert!(matches!(
+            parse_units("1.05", 1),
+            Err(UnitsError::PrecisionLoss(_, 1))
+        ));
+        assert!(matches!(
+            parse_units("1,5", 9),
+            Err(UnitsError::InvalidAmount(_))
+        ));
+        assert!(matches!(
+            parse_units("", 9),
+            Err(UnitsError::InvalidAmount(_))
+        ));
+        assert_eq!(
+            parse_units("340282366920938463464", 18),
+            Err(UnitsError::Overflow)
+        );
+        assert_eq!(
+            Lamports::from_human("18446744073.709551616"),
+            Err(UnitsError::Overflow)
+        );
+        assert_eq!(Lamports::from_human("0.000000001"), Ok(Lamports::new(1)));
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        assert_eq!(Lamports::MAX.checked_add(Lamports::new(1)), None);
+        assert_eq!(Lamports::ZERO.checked_sub(Lamports::new(1)), None);
+        assert_eq!(Wei::MAX.checked_mul(2), None);
+        assert_eq!(Wei::from_gwei(u128::MAX), None);
+        assert_eq!(
+            Lamports::new(2).checked_add(Lamports::new(3)),
+            Some(Lamports::new(5))
+        );
+
+        let amount = TokenAmount::new(10, 2);
+        assert_eq!(amount.checked_add(TokenAmount::new(1, 3)), None);
+        assert_eq!(
+            amount.checked_add(TokenAmount::new(5, 2)),
+            Some(TokenAmount::new(15, 2))
+        );
+        assert_eq!(TokenAmount::new(u128::MAX, 0).checked_mul(2), None);
+
+        assert_eq!(amount.rescale(4), Ok(TokenAmount::new(1000, 4)));
+        assert_eq!(amount.rescale(1), Ok(TokenAmount::new(1, 1)));
+        assert!(matches!(
+            TokenAmount::new(15, 2).rescale(1),
+            Err(UnitsError::PrecisionLoss(_, 1))
+        ));
+        assert_eq!(
+            TokenAmount::new(u128::MAX, 0).rescale(1),
+            Err(UnitsError::Overflow)
+        );
+        assert_eq!(Lamports::new(5).to_token_amount(), TokenAmount::new(5, 9));
+    }
+
+    #[test]
+    fn test_amount_serde() {
+        let value = serde_json::to_value(Lamports::new(1_500_000_000)).unwrap();
+        assert_eq!(
+            value,
+            json!({"raw": "1500000000", "formatted": "1.5", "unit": "SOL"})
+        );
+        assert_eq!(
+            serde_json::from_value::<Lamports>(value).unwrap(),
+            Lamports::new(1_500_000_000)
+        );
+
+        let value = serde_json::to_value(Wei::MAX).unwrap();
+        assert_eq!(value["raw"], u128::MAX.to_string());
+        assert_eq!(serde_json::from_value::<Wei>(value).unwrap(), Wei::MAX);
+
+        let value = serde_json::to_value(TokenAmount::new(1050, 2)).unwrap();
+        assert_eq!(
+            value,
+            json!({"raw": "1050", "formatted": "10.5", "decimals": 2})
+        );
+        assert_eq!(
+            serde_json::from_value::<TokenAmount>(value).unwrap(),
+            TokenAmount::new(1050, 2)
+        );
+
+        // Raw value out of range of the unit
+        assert!(serde_json::from_value::<Lamports>(json!({"raw": u128::MAX.to_string()})).is_err());
+        assert!(serde_json::from_value::<Wei>(json!({"raw": "1.5"})).is_err());
+    }
+}
@@ -0,0 +1,454 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use log::{error, info};
+use storage::db::Database;
+
+use crate::{BRequest, CancelReason, Result, Timestamp};
+
+pub const DEFAULT_ALLOWED_SCHEMES: [&str; 3] = ["https", "ipfs", "ar"];
+pub const DEFAULT_MAX_URI_LENGTH: usize = 2048;
+pub const DEFAULT_MAX_DATA_URI_SIZE: usize = 8192;
+
+/// Rules applied to the origin token URI before it is copied into the destination mint
+#[derive(Debug, Clone, PartialEq)]
+pub struct UriPolicy {
+    pub allowed_schemes: Vec<String>,
+    pub max_length: usize,
+    /// data: URIs up to this size are accepted, 0 rejects all of them
+    pub max_data_uri_size: usize,
+    /// When set ipfs:// URIs are rewritten to this http gateway
+    pub ipfs_gateway: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UriDecision {
+    Accept(String),
+    Rewrite(String),
+    Reject(String),
+}
+
+impl Default for UriPolicy {
+    fn default() -> Self {
+        UriPolicy {
+            allowed_schemes: DEFAULT_ALLOWED_SCHEMES
+                .iter()
+                .map(|scheme| scheme.to_string())
+                .collect(),
+            max_length: DEFAULT_MAX_URI_LENGTH,
+            max_data_uri_size: DEFAULT_MAX_DATA_URI_SIZE,
+            ipfs_gateway: None,
+        }
+    }
+}
+
+impl UriPolicy {
+    /// Builds the policy from the optional deployment settings, `allowed_schemes` is comma separated
+    pub fn from_config(
+        allowed_schemes: Option<&str>,
+        max_length: Option<usize>,
+        max_data_uri_size: Option<usize>,
+    ) -> Self {
+        let default = UriPolicy::default();
+        UriPolicy {
+            allowed_schemes: allowed_schemes
+                .map(|schemes| {
+                    schemes
+                        .split(',')
+                        .map(|scheme| scheme.trim().to_lowercase())
+                        .filter(|scheme| !scheme.is_empty())
+                        .collect()
+                })
+                .unwrap_or(default.allowed_schemes),
+            max_length: max_length.unwrap_or(default.max_length),
+            max_data_uri_size: max_data_uri_size.unwrap_or(default.max_data_uri_size),
+            ipfs_gateway: None,
+        }
+    }
+
+    pub fn with_ipfs_gateway(mut self, gateway: Option<String>) -> Self {
+        self.ipfs_gateway = gateway.map(|gateway| gateway.trim_end_matches('/').to_string());
+        self
+    }
+
+    pub fn apply(&self, uri: &str) -> UriDecision {
+        let uri = uri.trim();
+        if uri.is_empty() {
+            return UriDecision::Reject("Empty token URI".to_string());
+        }
+
+        let Some((scheme, rest)) = uri.split_once(':') else {
+            return UriDecision::Reject("Token URI without scheme".to_string());
+        };
+        let scheme = scheme.to_lowercase();
+
+        if scheme == "data" {
+            if uri.len() <= self.max_data_uri_size {
+                return UriDecision::Accept(uri.to_string());
+            }
+            return UriDecision::Reject(format!(
+                "data URI of {} bytes exceeds the maximum of {}",
+                uri.len(),
+                self.max_data_uri_size
+            ));
+        }
+
+        if uri.len() > self.max_length {
+            return UriDecision::Reject(format!(
+                "Token URI of {} bytes exceeds the maximum of {}",
+                uri.len(),
+                self.max_length
+            ));
+        }
+
+        if !self.allowed_schemes.contains(&scheme) {
+            return UriDecision::Reject(format!("Token URI scheme {scheme} not allowed"));
+        }
+
+        if (scheme == "http" || scheme == "https") && is_private_host(rest) {
+            return UriDecision::Reject("Token URI points to a private network".to_string());
+        }
+
+        if scheme == "ipfs" {
+            if let Some(gateway) = &self.ipfs_gateway {
+                let path = rest.trim_start_matches("//").trim_start_matches("ipfs/");
+                return UriDecision::Rewrite(format!("{gateway}/{path}"));
+            }
+        }
+
+        UriDecision::Accept(uri.to_string())
+    }
+}
+
+/// Applies the policy to the URI to mint, recording rewrites in the request history and
+/// canceling the request on rejection. Returns the URI to use, None if canceled
+pub fn sanitize_token_uri(
+    policy: &UriPolicy,
+    request: &mut BRequest,
+    uri: &str,
+    db: &Database,
+    now: Timestamp,
+) -> Result<Option<String>> {
+    match policy.apply(uri) {
+        UriDecision::Accept(uri) => Ok(Some(uri)),
+        UriDecision::Rewrite(rewritten) => {
+            info!(
+                "Request {} token URI rewritten to {}",
+                request.id, rewritten
+            );
+            request.record_event(
+                &format!("Token URI {uri} rewritten to {rewritten}"),
+                db,
+                now,
+            )?;
+            Ok(Some(rewritten))
+        }
+        UriDecision::Reject(reason) => {
+            error!("Request {} token URI rejected: {}", request.id, reason);
+            request.cancel_with_reason(db, CancelReason::MetadataInvalid(reason), now)?;
+            Ok(None)
+        }
+    }
+}
+
+fn is_private_host(rest: &str) -> bool {
+    let authority = rest
+        .trim_start_matches("//")
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default();
+    let authority = authority.rsplit('@').next().unwrap_or_default();
+    let host = if authority.starts_with('[') {
+        authority
+            .trim_start_matches('[')
+            .split(']')
+            .next()
+            .unwrap_or_default()
+    } else {
+        authority.split(':').next().unwrap_or_default()
+    }
+    .to_lowercase();
+    is_private_hostname(&host)
+}
+
+/// Loopback, private, link local and unspecified addresses and local names. Addresses
+/// are parsed in every form a resolver accepts: IPv6 with an embedded IPv4, and IPv4 as
+/// one integer or with octal and hex parts
+pub(crate) fn is_private_hostname(host: &str) -> bool {
+    let host = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_lowercase();
+    // Percent-encoded hosts and IPv6 zone ids are decoded by the HTTP client, the
+    // address they name can't be checked here
+    if host.is_empty()
+        || host.contains('%')
+        || host == "localhost"
+        || host.ends_with(".localhost")
+        || host.ends_with(".local")
+    {
+        return true;
+    }
+
+    host.parse::<IpAddr>()
+        .ok()
+        .or_else(|| legacy_ipv4(&host).map(IpAddr::V4))
+        .is_some_and(is_private_ip)
+}
+
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                // 0.0.0.0/8 "this network" and 100.64.0.0/10 shared address space
+                || first == 0
+                || (first == 100 && second & 0xc0 == 64)
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            if ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() {
+                return true;
+            }
+            // IPv4-mapped ::ffff:a.b.c.d and IPv4-compatible ::a.b.c.d addresses
+            if let Some(ip) = ip.to_ipv4() {
+                return is_private_ip(IpAddr::V4(ip));
+            }
+            // Unique local fc00::/7, link local fe80::/10 and site local fec0::/10
+            first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80 || first & 0xffc0 == 0xfec0
+        }
+    }
+}
+
+/// IPv4 address of the forms `inet_aton` accepts besides the dotted decimal one: 1 to 4
+/// parts in decimal, octal with a leading `0` or hex with `0x`, the last part filling
+/// the remaining bytes (`2130706433`, `0177.0.0.1`, `0x7f.1`)
+fn legacy_ipv4(host: &str) -> Option<Ipv4Addr> {
+    if !host
+        .bytes()
+        .all(|b| b.is_ascii_hexdigit() || b == b'.' || b == b'x')
+    {
+        return None;
+    }
+    let parts = host
+        .split('.')
+        .map(|part| {
+            if let Some(hex) = part.strip_prefix("0x") {
+                if hex.is_empty() {
+                    return Some(0);
+                }
+                u32::from_str_radix(hex, 16).ok()
+            } else if let Some(octal) = part.strip_prefix('0').filter(|octal| !octal.is_empty()) {
+                u32::from_str_radix(octal, 8).ok()
+            } else {
+                part.parse::<u32>().ok()
+            }
+        })
+        .collect::<Option<Vec<u32>>>()?;
+    let (last, leading) = parts.split_last()?;
+    if leading.len() > 3 || leading.iter().any(|part| *part > 0xff) {
+        return None;
+    }
+    let last_bits = 8 * (4 - leading.len() as u32);
+    if last_bits < 32 && *last >> last_bits != 0 {
+        return None;
+    }
+    let leading = leading
+        .iter()
+        .enumerate()
+        .fold(0, |ip, (i, part)| ip | part << (24 - 8 * i as u32));
+    Some(Ipv4Addr::from(leading | last))
+}
+
+#[cfg(test)]
+mod uri_policy_test {
+    use crate::{
+        sanitize_token_uri, BRequest, CancelReason, Chains, InputRequest, Status, Timestamp,
+        UriDecision, UriPolicy,
+    };
+    use storage::testing::each_engine;
+
+    #[test]
+    fn test_uri_policy_table() {
+        let policy =
+            UriPolicy::default().with_ipfs_gateway(Some("https://gateway.io/ipfs/".into()));
+        let big_data_uri = format!("data:application/json;base64,{}", "A".repeat(10_000));
+        let long_uri = format!("https://example.com/{}", "a".repeat(3000));
+
+        let cases = vec![
+            (
+                "https://example.com/1.json",
+                UriDecision::Accept("https://example.com/1.json".into()),
+            ),
+            ("ar://abcdef", UriDecision::Accept("ar://abcdef".into())),
+            (
+                "ipfs://Qm123/1.json",
+                UriDecision::Rewrite("https://gateway.io/ipfs/Qm123/1.json".into()),
+            ),
+            (
+                "ipfs://ipfs/Qm123",
+                UriDecision::Rewrite("https://gateway.io/ipfs/Qm123".into()),
+            ),
+            (
+                "data:application/json,{}",
+                UriDecision::Accept("data:application/json,{}".into()),
+            ),
+        ];
+        for (uri, expected) in cases {
+            assert_eq!(policy.apply(uri), expected, "uri {uri}");
+        }
+
+        let rejected = vec![
+            "",
+            "no-scheme",
+            "javascript:alert(1)",
+            "http://example.com/1.json",
+            "https://localhost/1.json",
+            "https://192.168.1.10/1.json",
+            "https://user@10.0.0.1:8080/1.json",
+            "https://[::1]/1.json",
+            big_data_uri.as_str(),
+            long_uri.as_str(),
+        ];
+        for uri in rejected {
+            assert!(
+                matches!(policy.apply(uri), UriDecision::Reject(_)),
+                "uri {uri}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_private_hosts_in_every_address_form() {
+        let policy = UriPolicy::default();
+        let private = [
+            // Loopback, private, link local and unspecified IPv4
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "100.64.0.1",
+            "255.255.255.255",
+            // Trailing dot and local names
+            "127.0.0.1.",
+            "LOCALHOST",
+            "app.localhost",
+            "printer.local",
+            // One integer, octal, hex and shortened IPv4
+            "2130706433",
+            "0177.0.0.1",
+            "0x7f000001",
+            "0x7f.1",
+            "0300.0250.1.1",
+            "10.1",
+            // IPv6 loopback, unspecified, unique and link local
+            "[::1]",
+            "[::]",
+            "[fc00::1]",
+            "[fd12:3456::1]",
+            "[fe80::1]",
+            "[FEBF::1]",
+            // IPv4-mapped and IPv4-compatible IPv6
+            "[::ffff:127.0.0.1]",
+            "[::ffff:7f00:1]",
+            "[::ffff:192.168.0.1]",
+            "[::10.0.0.1]",
+            // Percent-encoded host and zone id
+            "%31%32%37.0.0.1",
+            "[fe80::1%25eth0]",
+        ];
+        for host in private {
+            let uri = format!("https://{host}/1.json");
+            assert_eq!(
+                policy.apply(&uri),
+                UriDecision::Reject("Token URI points to a private network".into()),
+                "uri {uri}"
+            );
+        }
+
+        let public = [
+            "8.8.8.8",
+            "134744072",
+            "0x08080808",
+            "[2001:4860:4860::8888]",
+            "[::ffff:8.8.8.8]",
+            "100.128.0.1",
+            "172.32.0.1",
+            "example.com",
+            "0x7f.example.com",
+        ];
+        for host in public {
+            let uri = format!("https://{host}/1.json");
+            assert_eq!(policy.apply(&uri), UriDecision::Accept(uri.clone()));
+        }
+    }
+
+    #[test]
+    fn test_uri_policy_from_config() {
+        let policy = UriPolicy::from_config(Some("https, HTTP"), Some(10), Some(0));
+        assert_eq!(policy.allowed_schemes, vec!["https", "http"]);
+
+        assert_eq!(
+            policy.apply("http://a.io"),
+            UriDecision::Accept("http://a.io".into())
+        );
+        assert!(matches!(policy.apply("ipfs://Qm"), UriDecision::Reject(_)));
+        assert!(matches!(
+            policy.apply("https://example.com"),
+            UriDecision::Reject(_)
+        ));
+        assert!(matches!(policy.apply("data:,"), UriDecision::Reject(_)));
+
+        // Without a gateway ipfs is kept as is
+        let policy = UriPolicy::default();
+        assert_eq!(
+            policy.apply("ipfs://Qm123"),
+            UriDecision::Accept("ipfs://Qm123".into())
+        );
+    }
+
+    #[test]
+    fn test_sanitize_token_uri() {
+        for db in each_engine() {
+            let policy =
+                UriPolicy::default().with_ipfs_gateway(Some("https://gateway.io/ipfs".into()));
+            let mut request = BRequest::new(
+                InputRequest {
+                    contract_or_mint: "0xabc123".to_string(),
+                    token_id: "42".to_string(),
+                    token_owner: "0xowner456".to_string(),
+                    origin_network: Chains::EVM,
+                    destination_account: "destination789".to_string(),
+                },
+                Timestamp::now(),
+            );
+
+            let uri =
+                sanitize_token_uri(&policy, &mut request, "ipfs://Qm1", &db, Timestamp::now())
+                    .unwrap();
+            assert_eq!(uri, Some("https://gateway.io/ipfs/Qm1".to_string()));
+            assert_eq!(request.history.len(), 1);
+
+            let uri = sanitize_token_uri(
+                &policy,
+                &mut request,
+                "javascript:alert(1)",
+                &db,
+                Timestamp::now(),
+            )
+            .unwrap();
+            assert_eq!(uri, None);
+            assert_eq!(request.status, Status::Canceled);
+            assert!(matches!(
+                request.cancel_reason,
+                Some(CancelReason::MetadataInvalid(_))
+            ));
+        }
+    }
+}
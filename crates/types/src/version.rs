@@ -0,0 +1,5 @@
+/// Relayer version, shared by every crate of the workspace
+pub const RELAYER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Git commit the binary was built from, "unknown" outside a git checkout
+pub const RELAYER_GIT_COMMIT: &str = env!("RELAYER_GIT_COMMIT");
@@ -0,0 +1,148 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use storage::{db::Database, keys::WORK_CLAIM_PREFIX};
+
+use crate::{Result, SharedClock, Timestamp};
+
+/// Time a sweeper claim on a request lasts, a handler still running past it can be
+/// doubled by a later tick
+pub const WORK_CLAIM_LEASE: Duration = Duration::from_secs(300);
+
+/// Claim of a request by a sweeper tick, stored until released or expired
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorkClaim {
+    /// Run of the relayer holding the claim, claims of earlier runs are stale
+    pub run: String,
+    pub claimed_at: Timestamp,
+    pub expires_at: Timestamp,
+}
+
+/// Claims of the requests handled by the sweeper ticks. A request claimed by a running
+/// tick is skipped by the others until the claim is released or its lease expires, the
+/// claims left by a crashed run don't hold after a restart
+#[derive(Clone, Debug)]
+pub struct WorkClaims {
+    run: String,
+    lease: Duration,
+    clock: SharedClock,
+    /// Serializes the read and the write of a claim
+    claiming: Arc<Mutex<()>>,
+    skipped: Arc<AtomicU64>,
+}
+
+fn claim_key(request_id: &str) -> String {
+    format!("{WORK_CLAIM_PREFIX}{request_id}")
+}
+
+impl WorkClaims {
+    pub fn new(lease: Duration, clock: SharedClock) -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        WorkClaims {
+            run: format!("{}-{started}", std::process::id()),
+            lease,
+            clock,
+            claiming: Arc::default(),
+            skipped: Arc::default(),
+        }
+    }
+
+    fn holds(&self, claim: &WorkClaim, now: Timestamp) -> bool {
+        claim.run == self.run && claim.expires_at > now
+    }
+
+    /// Claims the request for the calling tick, false when another tick holds it
+    pub fn try_claim(&self, db: &Database, request_id: &str) -> Result<bool> {
+        let _claiming = self.claiming.lock().unwrap();
+        let now = self.clock.now();
+        let key = claim_key(request_id);
+        match db.read::<_, WorkClaim>(&key)? {
+            Some(claim) if self.holds(&claim, now) => {
+                self.skipped.fetch_add(1, Ordering::Relaxed);
+                info!("Request {request_id} is claimed by another sweep, skipping");
+                return Ok(false);
+            }
+            Some(_) => info!("Claim of request {request_id} expired, claiming it again"),
+            None => {}
+        }
+        db.write_value(
+            &key,
+            &WorkClaim {
+                run: self.run.clone(),
+                claimed_at: now,
+                expires_at: now.saturating_add(self.lease),
+            },
+        )?;
+        Ok(true)
+    }
+
+    /// Releases the claim once its handler is done, failures leave it to expire
+    pub fn release(&self, db: &Database, request_id: &str) {
+        let _claiming = self.claiming.lock().unwrap();
+        if let Err(e) = db.delete(claim_key(request_id)) {
+            error!("Could not release the claim of request {request_id}: {e}");
+        }
+    }
+
+    /// Requests claimed by a running handler
+    pub fn active_count(&self, db: &Database) -> Result<usize> {
+        let now = self.clock.now();
+        Ok(db
+            .scan_prefix::<WorkClaim>(WORK_CLAIM_PREFIX, None)?
+            .iter()
+            .filter(|(_, claim)| self.holds(claim, now))
+            .count())
+    }
+
+    /// Requests skipped since startup because another tick claimed them
+    pub fn skipped_count(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod work_claim_test {
+    use std::sync::Arc;
+
+    use storage::testing::each_engine;
+
+    use crate::{MockClock, Timestamp, WorkClaims, WORK_CLAIM_LEASE};
+
+    #[test]
+    fn test_claims_expire_and_end_with_their_run() {
+        for db in each_engine() {
+            let clock = Arc::new(MockClock::new(Timestamp::from_secs(1_700_000_000)));
+            let claims = WorkClaims::new(WORK_CLAIM_LEASE, clock.clone());
+
+            assert!(claims.try_claim(&db, "request1").unwrap());
+            assert!(!claims.try_claim(&db, "request1").unwrap());
+            assert!(claims.try_claim(&db, "request2").unwrap());
+            assert_eq!(claims.active_count(&db).unwrap(), 2);
+            assert_eq!(claims.skipped_count(), 1);
+
+            claims.release(&db, "request2");
+            assert_eq!(claims.active_count(&db).unwrap(), 1);
+
+            // A restarted relayer doesn't wait for the claims of the crashed run
+            let restarted = WorkClaims::new(WORK_CLAIM_LEASE, clock.clone());
+            assert_eq!(restarted.active_count(&db).unwrap(), 0);
+            assert!(restarted.try_claim(&db, "request1").unwrap());
+
+            // Nor for a handler that never released its claim
+            assert!(restarted.try_claim(&db, "request3").unwrap());
+            clock.advance(WORK_CLAIM_LEASE);
+            assert_eq!(restarted.active_count(&db).unwrap(), 0);
+            assert!(restarted.try_claim(&db, "request3").unwrap());
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::str::FromStr;
+
+use alloy::primitives::U256;
+use log::info;
+use serde::{Deserialize, Serialize};
+use storage::{
+    db::{Batch, Database},
+    keys::{WRAPPED_ORIGIN_PREFIX, WRAPPED_PREFIX},
+};
+
+use crate::{
+    completed_requests, request_data, BRequest, Chains, RequestId, Result, Status, Timestamp,
+};
+
+/// Token on one side of the bridge
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BridgedToken {
+    pub chain: Chains,
+    pub contract_or_mint: String,
+    /// Empty for Solana mints
+    pub token_id: String,
+}
+
+impl BridgedToken {
+    pub fn new(chain: Chains, contract_or_mint: &str, token_id: &str) -> Self {
+        let token_id = match chain {
+            Chains::EVM => token_id.trim().to_string(),
+            Chains::SOLANA => String::new(),
+        };
+        BridgedToken {
+            chain,
+            contract_or_mint: contract_or_mint.trim().to_string(),
+            token_id,
+        }
+    }
+
+    /// EVM contracts are case insensitive and token ids are compared as numbers, Solana
+    /// mints are identified by the mint alone
+    fn key(&self, prefix: &str) -> String {
+        match self.chain {
+            Chains::EVM => {
+                let token_id = U256::from_str(&self.token_id)
+                    .map(|id| id.to_string())
+                    .unwrap_or_else(|_| self.token_id.clone());
+                format!(
+                    "{prefix}EVM:{}:{token_id}",
+                    self.contract_or_mint.to_ascii_lowercase()
+                )
+            }
+            Chains::SOLANA => format!("{prefix}SOLANA:{}", self.contract_or_mint),
+        }
+    }
+}
+
+/// Origin token of a completed request and the wrapped token minted for it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WrappedAsset {
+    pub origin: BridgedToken,
+    pub destination: BridgedToken,
+    pub request_id: RequestId,
+    pub wrapped_at: Timestamp,
+}
+
+impl WrappedAsset {
+    /// None until the request completes with its minted token
+    pub fn of(request: &BRequest) -> Option<Self> {
+        let output = &request.output;
+        if request.status != Status::Completed || output.detination_contract_id_or_mint.is_empty() {
+            return None;
+        }
+        let destination_chain = match request.input.origin_network {
+            Chains::EVM => Chains::SOLANA,
+            Chains::SOLANA => Chains::EVM,
+        };
+        Some(WrappedAsset {
+            origin: BridgedToken::new(
+                request.input.origin_network.clone(),
+                &request.input.contract_or_mint,
+                &request.input.token_id,
+            ),
+            destination: BridgedToken::new(
+                destination_chain,
+                &output.detination_contract_id_or_mint,
+                &output.detination_token_id_or_account,
+            ),
+            request_id: request.id.clone(),
+            wrapped_at: request.finalized_at.unwrap_or(request.last_update),
+        })
+    }
+}
+
+/// Adds the registry entries of a completed request to the batch saving it: the wrapped
+/// token to its origin and the origin to the wrapped token
+pub(crate) fn update_wrapped_registry(request: &BRequest, batch: &mut Batch) -> Result<()> {
+    if let Some(asset) = WrappedAsset::of(request) {
+        batch.put(asset.destination.key(WRAPPED_PREFIX), &asset)?;
+        batch.put(asset.origin.key(WRAPPED_ORIGIN_PREFIX), &asset)?;
+    }
+    Ok(())
+}
+
+/// Bridging of a token, looked up as the wrapped token first and then as an origin token
+pub fn wrapped_asset(db: &Database, token: &BridgedToken) -> Result<Option<WrappedAsset>> {
+    if let Some(asset) = db.read(token.key(WRAPPED_PREFIX))? {
+        return Ok(Some(asset));
+    }
+    Ok(db.read(token.key(WRAPPED_ORIGIN_PREFIX))?)
+}
+
+/// Drops the registry and writes it again from the completed requests, covering the
+/// requests completed before the registry existed. Returns the number of assets
+pub fn rebuild_wrapped_registry(db: &Database) -> Result<usize> {
+    let mut batch = Batch::default();
+    for prefix in [WRAPPED_PREFIX, WRAPPED_ORIGIN_PREFIX] {
+        for (key, _) in db.scan_prefix::<WrappedAsset>(prefix, None)? {
+            batch.delete(key);
+        }
+    }
+
+    let mut rebuilt = 0;
+    for request_id in completed_requests(db).unwrap_or_default() {
+        let Some(request) = request_data(&request_id, db)? else {
+            continue;
+        };
+        if WrappedAsset::of(&request).is_some() {
+            update_wrapped_registry(&request, &mut batch)?;
+            rebuilt += 1;
+        }
+    }
+    db.write_batch(batch)?;
+
+    info!("Rebuilt the wrapped asset registry for {rebuilt} requests");
+    Ok(rebuilt)
+}
+
+#[cfg(test)]
+mod wrapped_test {
+    use storage::{
+        db::Database,
+        keys::{WRAPPED_ORIGIN_PREFIX, WRAPPED_PREFIX},
+        testing::each_engine,
+    };
+
+    use crate::{
+        rebuild_wrapped_registry, wrapped_asset, BRequest, BridgedToken, Chains, InputRequest,
+        OutputResult, Timestamp, WrappedAsset,
+    };
+
+    fn completed(db: &Database, token_id: &str) -> BRequest {
+        let mut request = BRequest::new(
+            InputRequest {
+                contract_or_mint: "0xABC123".to_string(),
+                token_id: token_id.to_string(),
+                token_owner: "0xowner".to_string(),
+                origin_network: Chains::EVM,
+                destination_account: "destination".to_string(),
+            },
+            Timestamp::now(),
+        );
+        request.save(db).unwrap();
+        request.update_state(db, Timestamp::now()).unwrap();
+        request.output = OutputResult {
+            detination_token_id_or_account: format!("account{token_id}"),
+            detination_contract_id_or_mint: format!("Mint{token_id}"),
+        };
+        request.update_state(db, Timestamp::now()).unwrap();
+        assert_eq!(wrapped_asset(db, &mint(token_id)).unwrap(), None);
+        request
+            .complete_minted(
+                db,
+                &format!("Mint{token_id}"),
+                &format!("account{token_id}"),
+                Timestamp::now(),
+            )
+            .unwrap();
+        request
+    }
+
+    fn mint(token_id: &str) -> BridgedToken {
+        BridgedToken::new(Chains::SOLANA, &format!("Mint{token_id}"), "")
+    }
+
+    fn registry(db: &Database) -> Vec<(String, WrappedAsset)> {
+        let mut entries = db.scan_prefix(WRAPPED_PREFIX, None).unwrap();
+        entries.extend(db.scan_prefix(WRAPPED_ORIGIN_PREFIX, None).unwrap());
+        entries
+    }
+
+    #[test]
+    fn test_registry_written_on_finalize_in_both_directions() {
+        for db in each_engine() {
+            let request = completed(&db, "42");
+
+            let asset = wrapped_asset(&db, &mint("42")).unwrap().unwrap();
+            assert_eq!(asset.request_id, request.id);
+            assert_eq!(asset.origin.contract_or_mint, "0xABC123");
+            assert_eq!(asset.origin.token_id, "42");
+            assert_eq!(asset.destination.token_id, "");
+
+            // The origin token is found whatever the spelling of the contract and token id
+            let origin = BridgedToken::new(Chains::EVM, "0xabc123", "0x2a");
+            assert_eq!(wrapped_asset(&db, &origin).unwrap(), Some(asset));
+            assert_eq!(wrapped_asset(&db, &mint("43")).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_rebuild_matches_the_finalized_registry() {
+        for db in each_engine() {
+            completed(&db, "1");
+            completed(&db, "2");
+            let written = registry(&db);
+            assert_eq!(written.len(), 4);
+
+            // Completed before the registry existed
+            for (key, _) in written.iter() {
+                db.delete(key).unwrap();
+            }
+            assert_eq!(wrapped_asset(&db, &mint("1")).unwrap(), None);
+
+            assert_eq!(rebuild_wrapped_registry(&db).unwrap(), 2);
+            assert_eq!(registry(&db), written);
+            assert_eq!(rebuild_wrapped_registry(&db).unwrap(), 2);
+            assert_eq!(registry(&db), written);
+        }
+    }
+}
@@ -0,0 +1,31 @@
+#![no_main]
+
+use alloy::{
+    primitives::{Address, Bytes, Log as PrimitiveLog, B256},
+    rpc::types::Log,
+    sol_types::SolEvent,
+};
+use evm::{NewRequest, TokenMinted};
+use libfuzzer_sys::fuzz_target;
+
+// Bridge contract logs of arbitrary data, the first byte picks the event topic
+fuzz_target!(|data: &[u8]| {
+    let Some((selector, data)) = data.split_first() else {
+        return;
+    };
+    let topic0 = match selector % 3 {
+        0 => NewRequest::SIGNATURE_HASH,
+        1 => TokenMinted::SIGNATURE_HASH,
+        _ => B256::ZERO,
+    };
+    let log = Log {
+        inner: PrimitiveLog::new_unchecked(
+            Address::ZERO,
+            vec![topic0],
+            Bytes::copy_from_slice(data),
+        ),
+        ..Default::default()
+    };
+    let _ = evm::decode_bridge_log(&log);
+    let _ = evm::historical_event(&log);
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use libfuzzer_sys::fuzz_target;
+
+// Program data logs of arbitrary event data, and arbitrary log lines
+fuzz_target!(|data: &[u8]| {
+    let _ = solana::decode_event(&format!("Program data: {}", BASE64_STANDARD.encode(data)));
+    if let Ok(log) = std::str::from_utf8(data) {
+        let _ = solana::decode_event(log);
+    }
+});